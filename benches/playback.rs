@@ -0,0 +1,154 @@
+//! Parse, scheduling and send costs of the playback path. Real devices can't
+//! run here, so the USB and Bluetooth figures cover the work done before
+//! bytes reach the system; run the app with `--timing` to measure the
+//! devices themselves.
+
+#[path = "../tests/common/mod.rs"]
+mod common;
+
+use std::sync::Arc;
+use std::time::Duration;
+
+use criterion::{BatchSize, Criterion, Throughput, criterion_group, criterion_main};
+use midi_piano_rs::devices::{MIN_BLE_PACKET_SIZE, pack_ble_midi_packets};
+use midi_piano_rs::midi::filter::{FilteredSink, OutputFilter, QuietLimit};
+use midi_piano_rs::midi::validate::{RunningStatus, ValidatingSink};
+use midi_piano_rs::midi::{
+    MidiPlayer, MidiSequence, MidiSink, NullSink, PlayerEvent, SharedMidiSink,
+};
+use tokio::runtime::Runtime;
+use tokio::sync::mpsc;
+
+/// `count` notes across four channels, a new one every `spacing` ticks.
+fn notes(count: u32, spacing: u32) -> Vec<common::Note> {
+    (0..count)
+        .map(|i| {
+            (
+                i * spacing,
+                spacing.max(1) * 4,
+                (i % 4) as u8,
+                36 + (i % 48) as u8,
+            )
+        })
+        .collect()
+}
+
+/// Chords of eight notes as the player would send them.
+fn chord_batches(count: usize) -> Vec<Vec<Vec<u8>>> {
+    (0..count)
+        .map(|chord| {
+            (0..8u8)
+                .map(|note| vec![0x90, 48 + note * 3 + (chord % 5) as u8, 90])
+                .collect()
+        })
+        .collect()
+}
+
+fn parse(c: &mut Criterion) {
+    let mut group = c.benchmark_group("parse");
+    for count in [500, 5000] {
+        let bytes = common::smf_bytes(&notes(count, 60));
+        group.throughput(Throughput::Bytes(bytes.len() as u64));
+        group.bench_function(format!("{count}_notes"), |b| {
+            b.iter(|| MidiSequence::from_bytes(&bytes).unwrap())
+        });
+    }
+    group.finish();
+}
+
+fn send(c: &mut Criterion) {
+    let runtime = Runtime::new().unwrap();
+    let batches = chord_batches(64);
+    let mut group = c.benchmark_group("send");
+    group.throughput(Throughput::Elements(batches.len() as u64));
+
+    let null = Arc::new(NullSink::new());
+    group.bench_function("null", |b| {
+        b.iter(|| {
+            runtime.block_on(async {
+                for batch in &batches {
+                    null.send_batch(batch).await.unwrap();
+                }
+            });
+            null.take();
+        })
+    });
+
+    // What every connection adds on top of the device.
+    let stacked: SharedMidiSink = Arc::new(FilteredSink::new(
+        Arc::new(ValidatingSink::new(null.clone())),
+        OutputFilter {
+            quiet: Some(QuietLimit::default()),
+            ..OutputFilter::default()
+        },
+    ));
+    group.bench_function("null_with_validation_and_filter", |b| {
+        b.iter(|| {
+            runtime.block_on(async {
+                for batch in &batches {
+                    stacked.send_batch(batch).await.unwrap();
+                }
+            });
+            null.take();
+        })
+    });
+
+    group.bench_function("usb_running_status", |b| {
+        b.iter(|| {
+            let mut running = RunningStatus::default();
+            batches
+                .iter()
+                .flatten()
+                .map(|message| running.compress(message).len())
+                .sum::<usize>()
+        })
+    });
+
+    group.bench_function("ble_packing", |b| {
+        b.iter(|| {
+            batches
+                .iter()
+                .map(|batch| pack_ble_midi_packets(batch, MIN_BLE_PACKET_SIZE).len())
+                .sum::<usize>()
+        })
+    });
+    group.finish();
+}
+
+fn schedule(c: &mut Criterion) {
+    let runtime = Runtime::new().unwrap();
+    // A note about every millisecond for 200ms, so the time above that is
+    // the player's.
+    let sequence = Arc::new(MidiSequence::from_bytes(&common::smf_bytes(&notes(200, 1))).unwrap());
+    let sink = Arc::new(NullSink::new());
+    let mut group = c.benchmark_group("schedule");
+    group
+        .sample_size(20)
+        .measurement_time(Duration::from_secs(5));
+    group.bench_function("200_notes", |b| {
+        b.iter_batched(
+            || {
+                sink.take();
+                mpsc::unbounded_channel()
+            },
+            |(sender, mut events)| {
+                runtime.block_on(async {
+                    let mut player = MidiPlayer::new(sender);
+                    player
+                        .start_playback(sequence.clone(), sink.clone(), None)
+                        .unwrap();
+                    while let Some(event) = events.recv().await {
+                        if matches!(event, PlayerEvent::Finished) {
+                            break;
+                        }
+                    }
+                })
+            },
+            BatchSize::PerIteration,
+        )
+    });
+    group.finish();
+}
+
+criterion_group!(benches, parse, send, schedule);
+criterion_main!(benches);
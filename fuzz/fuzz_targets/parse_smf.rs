@@ -0,0 +1,41 @@
+//! Feeds arbitrary bytes through everything a downloaded file goes through
+//! before it plays, both as they are and wrapped in an RMID file. Run with
+//! `cargo +nightly fuzz run parse_smf` from the repository root.
+
+#![no_main]
+
+use libfuzzer_sys::fuzz_target;
+use midi_piano_rs::midi::MidiSequence;
+use midi_piano_rs::midi::sequence::{PlaybackAdjustments, file_timing};
+
+fuzz_target!(|data: &[u8]| {
+    play(data);
+    play(&rmid(data));
+});
+
+fn play(data: &[u8]) {
+    let _ = file_timing(data);
+    let Ok(sequence) = MidiSequence::from_bytes(data) else {
+        return;
+    };
+    let adjusted = sequence.adjusted(PlaybackAdjustments {
+        tempo_percent: PlaybackAdjustments::MIN_TEMPO_PERCENT,
+        transpose: 12,
+        ..PlaybackAdjustments::default()
+    });
+    let _ = adjusted.excerpt(adjusted.duration / 4, adjusted.duration / 2);
+    let _ = adjusted.to_smf_bytes();
+}
+
+/// `smf` as the `data` chunk of an RMID file.
+fn rmid(smf: &[u8]) -> Vec<u8> {
+    let data_len = smf.len() as u32;
+    let padded = smf.len() + smf.len() % 2;
+    let mut bytes = b"RIFF".to_vec();
+    bytes.extend((4 + 8 + padded as u32).to_le_bytes());
+    bytes.extend(b"RMIDdata");
+    bytes.extend(data_len.to_le_bytes());
+    bytes.extend(smf);
+    bytes.resize(bytes.len() + smf.len() % 2, 0);
+    bytes
+}
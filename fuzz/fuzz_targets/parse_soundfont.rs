@@ -0,0 +1,19 @@
+//! Feeds arbitrary bytes to the SoundFont parser and looks up a few notes in
+//! whatever it accepts. Run with `cargo +nightly fuzz run parse_soundfont`
+//! from the repository root.
+
+#![no_main]
+
+use libfuzzer_sys::fuzz_target;
+use midi_piano_rs::midi::soundfont::SoundFont;
+
+fuzz_target!(|data: &[u8]| {
+    let Ok(soundfont) = SoundFont::parse(data) else {
+        return;
+    };
+    for (bank, program) in [(0, 0), (0, 40), (128, 0)] {
+        for key in [0, 60, 127] {
+            let _ = soundfont.regions(bank, program, key, 100);
+        }
+    }
+});
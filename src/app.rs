@@ -1,49 +1,153 @@
-use std::collections::{BTreeMap, HashMap, HashSet};
+use std::cell::{Ref, RefCell};
+use std::collections::{BTreeMap, BTreeSet, HashMap, HashSet};
 use std::fmt;
+use std::ops::ControlFlow;
 use std::path::PathBuf;
 use std::sync::Arc;
-use std::time::Duration;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::time::{Duration, Instant};
 
+use chrono::{NaiveTime, Timelike};
 use futures::stream;
 use iced::alignment::{Horizontal, Vertical};
 use iced::widget::{
-    Column, button, column, container, pick_list, row, scrollable, text, text::Shaping, text_input,
+    Column, Row, button, checkbox, column, container, mouse_area, pick_list, progress_bar, row,
+    scrollable, slider, stack, text, text::Shaping, text_input, tooltip,
 };
 use iced::{
-    Color, Element, Font, Length, Subscription, Task, Theme, application, executor, time, window,
-};
-use rand::{
-    rng,
-    seq::{IndexedRandom, IteratorRandom, SliceRandom},
+    Color, Element, Font, Length, Size, Subscription, Task, Theme, application, executor, keyboard,
+    time, window,
 };
+use rand::{rng, seq::IteratorRandom};
 use serde::{Deserialize, Serialize};
-use tokio::sync::Mutex;
-use tokio::sync::mpsc::{self, UnboundedReceiver};
+use tokio::sync::mpsc::{self, UnboundedReceiver, UnboundedSender};
+use tokio::sync::watch;
+use tokio::sync::{Mutex, Notify};
 use uuid::Uuid;
 
-use crate::devices::{MidiDeviceDescriptor, MidiDeviceManager};
-use crate::midi::sink::MidiTransport;
-use crate::midi::{MidiLibrary, MidiPlayer, MidiSequence, PlayerEvent, SharedMidiSink};
+use crate::debug::{self, DebugOptions, MessageRecorder};
+use crate::i18n::{self, UiLanguage, t};
+use crate::lesson::{Assignment, AssignmentItem};
+use crate::media_controls::{self, MediaCommand, MediaControlsHandle, MediaState, MediaStatus};
+use crate::notifications::{Notifications, Severity};
+use crate::practice::{self, DateRange, PracticeLog, PracticeSession, StatsExportKind};
+use crate::schedule::{self, Schedule};
+use crate::tray::{self, TrayCommand, TrayHandle, TrayState};
+use midi_piano_rs::control::{
+    ControlCommand, ControlServer, ControlState, ControlStatus, ControlTrack, seek_target,
+};
+use midi_piano_rs::devices::profile::{DeviceProfile, DeviceProfiles};
+use midi_piano_rs::devices::{
+    DEFAULT_CONNECT_TIMEOUT, MAX_BLE_PACKET_SIZE, MIN_BLE_PACKET_SIZE, MidiDeviceDescriptor,
+    MidiDeviceManager, VIRTUAL_PORT_NAME, VIRTUAL_PORTS_SUPPORTED,
+};
+use midi_piano_rs::error::PlaybackError;
+use midi_piano_rs::midi::capabilities::{CapabilityIssue, DeviceCapabilities};
+use midi_piano_rs::midi::catalog::{self, CatalogEntry};
+use midi_piano_rs::midi::collation::TitleCollation;
+use midi_piano_rs::midi::filter::{
+    DeviceReset, FilterAction, FilteredControl, OutputFilter, QuietLimit, VelocityCurve,
+};
+use midi_piano_rs::midi::folder_label::{self, FolderColor, FolderLabel};
+use midi_piano_rs::midi::inbox::{
+    self, InboxGrouping, InboxImport, InboxReport, WatchFolderConfig,
+};
+use midi_piano_rs::midi::key::{self, KeyMatchMode, MusicalKey};
+use midi_piano_rs::midi::metadata::LibraryMetadata;
+use midi_piano_rs::midi::monitor::{self, MessageKind, MidiMonitor, MonitorEntry};
+use midi_piano_rs::midi::remote::{self, RemoteCache, RemoteEntry};
+use midi_piano_rs::midi::render;
+use midi_piano_rs::midi::sequence::{
+    self, HandClassifier, HandSplit, MidiSource, PercussionHandling, PlaybackAdjustments,
+    SequenceInfo, TempoSummary,
+};
+use midi_piano_rs::midi::sink::{MidiTransport, SinkStats};
+use midi_piano_rs::midi::soundfont::SoundFont;
+use midi_piano_rs::midi::timing::TimingCapture;
+use midi_piano_rs::midi::trace::{self as send_trace, SendTrace, TraceFormat};
+use midi_piano_rs::midi::validate::ValidationReport;
+use midi_piano_rs::midi::{
+    AssetProgress, DEFAULT_PROGRESS_INTERVAL, LeadIn, ManifestChanges, MidiLibrary, MidiPlayer,
+    MidiSequence, PlayerEvent, SearchQuery, SharedMidiSink, SilenceWatch, SongDetails,
+    play_preview, read_midi_file,
+};
+use midi_piano_rs::shuffle::{self, PlaybackHistory, ShuffleHistory};
+use midi_piano_rs::webhook::{self, NowPlayingEvent, NowPlayingKind};
+use midi_piano_rs::works::{self, Work, WorkIndex};
 
 const TICK_INTERVAL: Duration = Duration::from_millis(100);
+const DEBUG_DUMP_DIR: &str = "data/debug";
+const INBOX_POLL_INTERVAL: Duration = Duration::from_secs(10);
+const MONITOR_CAPACITY: usize = 500;
+/// Manifest items checked before the library shows what it has so far.
+const LIBRARY_LOAD_CHUNK: usize = 200;
+const RECENTLY_PLAYED_LIMIT: usize = 25;
+/// How much of a song [`Message::PreviewSong`] plays.
+const PREVIEW_LENGTH: Duration = Duration::from_secs(10);
+const MINI_PLAYER_SIZE: Size = Size::new(460.0, 140.0);
+const TRAY_RECENT_LIMIT: usize = 5;
+/// How much of a channel meter's level is left after each tick.
+const METER_DECAY: f32 = 0.7;
+const METER_HEIGHT: f32 = 36.0;
+/// Longest the app waits for devices to go quiet before exiting anyway.
+const SHUTDOWN_TIMEOUT: Duration = Duration::from_secs(2);
 
 type AsyncResult<T> = Result<T, String>;
 
 const NOTO_SANS_SC: &[u8] = include_bytes!("../assets/fonts/NotoSansSC-Regular.otf");
 const DEFAULT_FONT: Font = Font::with_name("Noto Sans SC");
 const USER_DATA_FILE: &str = "data/user_preferences.json";
+const PRACTICE_LOG_FILE: &str = "data/practice_stats.json";
+const RESUME_STATE_FILE: &str = "data/resume_state.json";
+const LIBRARY_METADATA_FILE: &str = "data/library_metadata.json";
+const REMOTE_CACHE_DIR: &str = "data/remote_cache";
+const SEND_TRACE_DIR: &str = "data/logs";
+const RESUME_SAVE_INTERVAL: Duration = Duration::from_secs(5);
+const MIN_SESSION_LENGTH: Duration = Duration::from_secs(1);
+const DEFAULT_REMOTE_CONTROL_PORT: u16 = 8765;
 
 #[derive(Debug, Clone)]
 enum Message {
-    LibraryLoaded(AsyncResult<MidiLibrary>),
+    LibraryLoadUpdate(LibraryLoadUpdate),
+    CancelLibraryLoad,
+    RetryLibraryLoad,
+    RescanAssets,
+    RemoteCatalogUrlChanged(String),
+    LoadRemoteCatalog,
+    RemoteCatalogLoaded(AsyncResult<Vec<RemoteEntry>>),
+    RemoteDownloaded(Uuid, Duration, AsyncResult<Arc<[u8]>>),
+    ClearRemoteCache,
+    RemoteCacheCleared(AsyncResult<usize>),
+    AssetsRescanned(AsyncResult<(MidiLibrary, ManifestChanges)>),
     DevicesRefreshed(AsyncResult<Vec<MidiDeviceDescriptor>>),
     BleScanUpdate(AsyncResult<Vec<MidiDeviceDescriptor>>),
     UserDataLoaded(AsyncResult<UserPreferences>),
     PreferencesSaved(AsyncResult<()>),
+    PracticeLogLoaded(AsyncResult<PracticeLog>),
+    PracticeLogSaved(AsyncResult<()>),
+    WebhookUrlChanged(String),
+    SaveWebhookUrl,
+    SendTestWebhook,
+    WebhookSent(AsyncResult<()>),
+    WebhookTested(AsyncResult<()>),
+    ResumeStateLoaded(AsyncResult<Option<ResumeState>>),
+    ResumeStateSaved(AsyncResult<()>),
+    ResumePlayback,
+    DismissResume,
+    ResetInterruptedDevice,
+    AddSchedule,
+    RemoveSchedule(Uuid),
+    ScheduleEnabledToggled(Uuid, bool),
+    ScheduleDayToggled(Uuid, usize),
+    ScheduleTimeSelected(Uuid, NaiveTime),
+    SchedulePlaylistSelected(Uuid, PlaylistChoice),
+    ScheduleDeviceSelected(Uuid, DeviceChoice),
+    StartScheduledNow,
+    CancelScheduled,
+    SleepTimerSelected(SleepTimerChoice),
     TreeDataLoaded {
         request_id: u64,
         tree: LibraryNode,
-        folders: HashMap<String, Vec<Uuid>>,
     },
     TreeDataFailed {
         request_id: u64,
@@ -52,22 +156,148 @@ enum Message {
     DeviceSelected(Uuid),
     SongSelected(Uuid),
     SearchChanged(String),
+    LibrarySortSelected(LibrarySort),
+    TreeGroupingSelected(TreeGrouping),
+    LibraryKey(LibraryKey),
     PlayPressed,
     StopPressed,
+    PlayPause,
+    SeekChanged(f32),
+    SeekReleased,
+    CancelPreparing,
+    PanicPressed,
+    KeyMatchModeSelected(KeyMatchMode),
+    ProgramOverrideSelected(ProgramChoice),
+    OutputFilterModeSelected(FilteredControl, FilterMode),
+    OutputFilterClampStep(FilteredControl, i16),
+    DeviceProfileSelected(ProfileChoice),
+    NewDeviceProfile,
+    DeleteDeviceProfile,
+    DeviceProfileRenamed(String),
+    DeviceLatencyStep(i16),
+    DevicePercussionSelected(PercussionChoice),
+    DevicePercussionChannelStep(i8),
+    DeviceReliableWritesToggled(bool),
+    VelocityCurveSelected(VelocityCurve),
+    FixedVelocityStep(i16),
+    ChannelMapSelected(u8, ChannelFilter),
+    OutputFilterSysExToggled(bool),
+    OutputFilterResetSelected(ResetChoice),
+    OutputFilterBendRangeSelected(BendRangeChoice),
+    DeviceCapabilitiesChanged(DeviceCapabilities),
+    CapabilityWarningResolved(CapabilityChoice),
+    SilenceActionSelected(SilenceAction),
+    SilenceThresholdStep(i16),
+    ShuffleAvoidStep(i16),
+    WeightedShuffleToggled(bool),
+    TitleCollationSelected(TitleCollation),
+    TrimLeadingSilenceToggled(bool),
+    PreRollStep(i16),
+    CountInToggled(bool),
+    CountdownToggled(bool),
+    MetronomeToggled(bool),
+    QuietModeToggled(bool),
+    TogglePianoMode,
+    QuietVelocityStep(i8),
+    QuietVolumeToggled(bool),
+    QuietVolumeStep(i8),
+    SongSilenceWatchToggled(Uuid),
+    QueueKeysDetected(u64, AsyncResult<Vec<Option<MusicalKey>>>),
+    MasterTempoStep(i16),
+    MasterTransposeStep(i8),
+    ClearMasterAdjustments,
+    RepeatStep(i8),
+    SongTempoStep(Uuid, i16),
+    SongTransposeStep(Uuid, i8),
+    SongChannelMuteToggled(Uuid, u8),
+    ResetSongSettings(Uuid),
+    SongHandSplitSelected(Uuid, HandSplitKind),
+    SongHandSplitStep(Uuid, i16),
+    SongHandChannelStep(Uuid, Hand, i8),
+    SongPercussionSelected(Uuid, PercussionChoice),
+    SongPercussionChannelStep(Uuid, i8),
+    ShowSongInfo(Uuid),
+    SongInfoLoaded(Uuid, AsyncResult<SequenceInfo>),
+    SongTitleChanged(String),
+    SongComposerChanged(String),
+    SaveSongDetails,
+    FillSongDetailsFromFile,
+    SongDetailsRead(Uuid, AsyncResult<SongDetails>),
+    ExportArrangement(Uuid),
+    ArrangementExported(AsyncResult<PathBuf>),
+    ExcerptUnitSelected(ExcerptUnit),
+    ExcerptFromChanged(String),
+    ExcerptToChanged(String),
+    SaveExcerpt,
+    ExcerptSaved(AsyncResult<PathBuf>),
+    CloseSongInfo,
+    ScoreOverlayToggled(bool),
+    ScoreOverlaySizeChanged(u16),
+    ScoreOverlayTap,
+    ScoreOverlayShiftBar(i32),
+    PanicSent(AsyncResult<()>),
     AddLocalFile,
-    PlaybackPrepared(AsyncResult<PreparedPlayback>),
+    AddLocalFolder,
+    /// Drops a local file from the library; `forget` also clears its
+    /// ratings, tags, statistics and playlist references.
+    RemoveEntry {
+        id: Uuid,
+        forget: bool,
+    },
+    CleanMissingFiles,
+    FolderImportUpdate(FolderImportUpdate),
+    CancelFolderImport,
+    PlaybackPrepared(Result<PreparedPlayback, AppError>),
+    NextTrackPreloaded(AsyncResult<PreloadedTrack>),
     RefreshDevices,
     SetRating(Uuid, u8),
     ToggleFavorite(Uuid),
     SwitchTab(LibraryTab),
     ToggleFolder(String),
+    ExpandAllFolders,
+    CollapseAllFolders,
     SelectFolder(String),
     PlaylistDraftAdd(Uuid),
     PlaylistDraftRemove(usize),
     PlaylistDraftNameChanged(String),
+    PlaylistDraftAddAll(Vec<Uuid>),
+    PlaylistDraftRemoveAll,
+    PlaylistDraftMove(usize, usize),
     PlaylistDraftClear,
     PlaylistDraftSave,
+    TogglePlaylistBuilder,
+    PlaylistBuilderQueryChanged(String),
+    PlaylistDragStarted(usize),
+    PlaylistDragEnded,
     StartPlayback(Uuid),
+    /// Queues every movement of a work, in order.
+    PlayWork(Uuid),
+    /// Shows or hides a work's movements in lists.
+    ToggleWork(Uuid),
+    SaveDraftAsWork,
+    WorkNameChanged(Uuid, String),
+    WorkPauseSelected(Uuid, WorkPauseChoice),
+    UngroupWork(Uuid),
+    /// Plays the opening of a song, or stops the preview of that song.
+    PreviewSong(Uuid),
+    PreviewFinished(u64, AsyncResult<()>),
+    PlayNext(Uuid),
+    AddToQueue(Uuid),
+    ToggleQueuePanel,
+    ClearRecentlyPlayed,
+    TagDraftChanged(String),
+    AddTag(Uuid),
+    RemoveTag(Uuid, String),
+    /// Opens the label editor under a tree folder, or closes it.
+    EditFolderLabel(Option<String>),
+    FolderColorSelected(String, Option<FolderColor>),
+    FolderEmojiChanged(String, String),
+    TagFilterToggled(String),
+    TagFilterMatchAllToggled(bool),
+    MinRatingSelected(RatingFilter),
+    FavoritesOnlyToggled(bool),
+    ClearLibraryFilters,
+    CreateSmartPlaylist,
     PlayFavorites {
         shuffle: bool,
     },
@@ -75,16 +305,382 @@ enum Message {
         id: Uuid,
         shuffle: bool,
     },
+    PlayFolder {
+        id: String,
+        shuffle: bool,
+    },
     NextTrack,
     PrevTrack,
     PlaylistSelect(Option<Uuid>),
+    PlaylistDeviceSelected(Uuid, Option<Uuid>),
+    PlaylistShuffleToggled(Uuid, bool),
+    PlaylistTempoStep(Uuid, i16),
+    PlaylistTransposeStep(Uuid, i8),
     PlaylistDelete(Uuid),
+    /// Takes a song out of a playlist: the playlist, then the song.
+    RemoveFromPlaylist(Uuid, Uuid),
     PlaylistLoadToDraft(Uuid),
+    PlaylistMove(Uuid, i8),
+    PlaylistDuplicate(Uuid),
+    PlaylistFolderAssigned(Uuid, FolderChoice),
+    RenameStart(RenameTarget),
+    RenameChanged(String),
+    RenameCommit,
+    RenameCancel,
+    NewFolderNameChanged(String),
+    CreatePlaylistFolder,
+    PlaylistFolderMove(Uuid, i8),
+    PlaylistFolderDelete(Uuid),
     GenerateRandomPlaylist,
+    ToggleSettings,
+    ToggleMiniPlayer,
+    WindowResized(Size),
+    TraySpawned(AsyncResult<TrayHandle>),
+    LibraryMetadataRefreshed(AsyncResult<LibraryMetadata>),
+    MediaControlsSpawned(AsyncResult<MediaControlsHandle>),
+    RemoteControlToggled(bool),
+    RemoteControlPortDraftChanged(String),
+    RemoteControlPortSubmitted,
+    RegenerateRemoteControlToken,
+    RemoteControlStopped(AsyncResult<()>),
+    CloseToTrayToggled(bool),
+    ConnectTimeoutChanged(u16),
+    VirtualPortToggled(bool),
+    VirtualPortUpdated(AsyncResult<()>),
+    BlePacketSizeAutoToggled(bool),
+    BlePacketSizeChanged(u16),
+    UsbRunningStatusToggled(bool),
+    BleAdaptersListed(AsyncResult<Vec<String>>),
+    BleAdapterSelected(AdapterChoice),
+    ProgressIntervalChanged(u16),
+    FadeLengthChanged(u16),
+    SendTraceToggled(bool),
+    SendTraceFormatSelected(TraceFormat),
+    OpenSendTraceFolder,
+    AnimationFrame,
+    HideToTray,
+    ShowWindow,
+    WindowCloseRequested(window::Id),
+    Quit,
+    ShutdownWindow(window::Id),
+    PickInboxFolder,
+    PickInboxLibraryRoot,
+    InboxGroupingSelected(InboxGrouping),
+    DisableWatchFolder,
+    InboxPoll,
+    InboxScanned(AsyncResult<InboxReport>),
+    WatchLibraryIndexed(AsyncResult<Vec<InboxImport>>),
+    PickSoundfont,
+    ExportWav,
+    RenderUpdate(RenderUpdate),
+    CancelRender,
+    StatsFromChanged(String),
+    StatsToChanged(String),
+    StatsExportKindSelected(StatsExportKind),
+    ExportStats,
+    StatsExported(AsyncResult<PathBuf>),
+    AssignmentDraftFromPlaylist,
+    AssignmentTitleChanged(String),
+    AssignmentDueChanged(String),
+    AssignmentItemTempoChanged(usize, String),
+    AssignmentItemSectionChanged(usize, String),
+    AssignmentItemPlaysChanged(usize, String),
+    AssignmentDraftRemove(usize),
+    AssignmentDraftExport,
+    ImportAssignment,
+    RemoveAssignment(Uuid),
+    ExportAssignmentResults(Uuid),
+    DumpDebugLog,
+    DumpTimings,
+    ToggleMonitor,
+    MonitorPauseToggled,
+    MonitorChannelFilterSelected(ChannelFilter),
+    MonitorKindFilterSelected(KindFilter),
+    MonitorHexToggled(bool),
+    ClearMonitor,
+    OnboardingNext,
+    OnboardingBack,
+    OnboardingFinish,
+    RestartOnboarding,
+    SetDefaultDevice,
+    LanguageSelected(UiLanguage),
+    ThemeSelected(Theme),
+    PickMusicFolder,
+    RemoveMusicFolder(usize),
+    MusicFolderIndexed(AsyncResult<Vec<InboxImport>>),
+    SendTestTone,
+    TestToneSent(AsyncResult<()>),
+    ImportPreferences,
+    PreferencesImported(AsyncResult<UserPreferences>),
+    ExportCatalog,
+    CatalogExported(AsyncResult<PathBuf>),
+    ImportCatalog,
+    CatalogImported(AsyncResult<Vec<CatalogEntry>>),
+    ExportUserData,
+    UserDataExported(AsyncResult<PathBuf>),
+    ImportUserData(UserDataImport),
+    UserDataImported(UserDataImport, AsyncResult<UserDataBundle>),
     Tick,
+    DismissToast(u64),
+    DismissStatus,
+}
+
+/// The subset of [`Message`] that can be recorded and re-issued by the
+/// `--debug` message replay. Anything that opens a dialog or carries an
+/// async result is left out.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+enum ReplayMessage {
+    DeviceSelected(Uuid),
+    SongSelected(Uuid),
+    SearchChanged(String),
+    LibrarySortSelected(LibrarySort),
+    TreeGroupingSelected(TreeGrouping),
+    LibraryKey(LibraryKey),
+    PlayPressed,
+    StopPressed,
+    PlayPause,
+    SeekChanged(f32),
+    SeekReleased,
+    CancelPreparing,
+    PanicPressed,
+    StartPlayback(Uuid),
+    PlayWork(Uuid),
+    ToggleWork(Uuid),
+    PreviewSong(Uuid),
+    PlayNext(Uuid),
+    AddToQueue(Uuid),
+    NextTrack,
+    PrevTrack,
+    PlayFavorites { shuffle: bool },
+    PlayPlaylist { id: Uuid, shuffle: bool },
+    PlayFolder { id: String, shuffle: bool },
+    SetRating(Uuid, u8),
+    ToggleFavorite(Uuid),
+    SwitchTab(LibraryTab),
+    ToggleFolder(String),
+    ExpandAllFolders,
+    CollapseAllFolders,
+    SelectFolder(String),
+    PlaylistSelect(Option<Uuid>),
+    PlaylistDraftAdd(Uuid),
+    PlaylistDraftRemove(usize),
+    PlaylistDraftNameChanged(String),
+    PlaylistDraftAddAll(Vec<Uuid>),
+    PlaylistDraftRemoveAll,
+    PlaylistDraftMove(usize, usize),
+    PlaylistDraftClear,
+    PlaylistDraftSave,
+    ToggleSettings,
+    ToggleMiniPlayer,
+    ShowSongInfo(Uuid),
+    CloseSongInfo,
+    MasterTempoStep(i16),
+    MasterTransposeStep(i8),
+    ClearMasterAdjustments,
+    RepeatStep(i8),
+    SongTempoStep(Uuid, i16),
+    SongTransposeStep(Uuid, i8),
+    SongChannelMuteToggled(Uuid, u8),
+    ScoreOverlayTap,
+    ScoreOverlayShiftBar(i32),
+    DismissToast(u64),
     DismissStatus,
 }
 
+impl ReplayMessage {
+    fn capture(message: &Message) -> Option<Self> {
+        Some(match message {
+            Message::DeviceSelected(id) => ReplayMessage::DeviceSelected(*id),
+            Message::SongSelected(id) => ReplayMessage::SongSelected(*id),
+            Message::SearchChanged(query) => ReplayMessage::SearchChanged(query.clone()),
+            Message::LibrarySortSelected(sort) => ReplayMessage::LibrarySortSelected(*sort),
+            Message::TreeGroupingSelected(grouping) => {
+                ReplayMessage::TreeGroupingSelected(*grouping)
+            }
+            Message::LibraryKey(key) => ReplayMessage::LibraryKey(key.clone()),
+            Message::PlayPressed => ReplayMessage::PlayPressed,
+            Message::StopPressed => ReplayMessage::StopPressed,
+            Message::PlayPause => ReplayMessage::PlayPause,
+            Message::SeekChanged(seconds) => ReplayMessage::SeekChanged(*seconds),
+            Message::SeekReleased => ReplayMessage::SeekReleased,
+            Message::CancelPreparing => ReplayMessage::CancelPreparing,
+            Message::PanicPressed => ReplayMessage::PanicPressed,
+            Message::StartPlayback(id) => ReplayMessage::StartPlayback(*id),
+            Message::PlayWork(id) => ReplayMessage::PlayWork(*id),
+            Message::ToggleWork(id) => ReplayMessage::ToggleWork(*id),
+            Message::PreviewSong(id) => ReplayMessage::PreviewSong(*id),
+            Message::PlayNext(id) => ReplayMessage::PlayNext(*id),
+            Message::AddToQueue(id) => ReplayMessage::AddToQueue(*id),
+            Message::NextTrack => ReplayMessage::NextTrack,
+            Message::PrevTrack => ReplayMessage::PrevTrack,
+            Message::PlayFavorites { shuffle } => {
+                ReplayMessage::PlayFavorites { shuffle: *shuffle }
+            }
+            Message::PlayPlaylist { id, shuffle } => ReplayMessage::PlayPlaylist {
+                id: *id,
+                shuffle: *shuffle,
+            },
+            Message::PlayFolder { id, shuffle } => ReplayMessage::PlayFolder {
+                id: id.clone(),
+                shuffle: *shuffle,
+            },
+            Message::SetRating(id, rating) => ReplayMessage::SetRating(*id, *rating),
+            Message::ToggleFavorite(id) => ReplayMessage::ToggleFavorite(*id),
+            Message::SwitchTab(tab) => ReplayMessage::SwitchTab(*tab),
+            Message::ToggleFolder(id) => ReplayMessage::ToggleFolder(id.clone()),
+            Message::ExpandAllFolders => ReplayMessage::ExpandAllFolders,
+            Message::CollapseAllFolders => ReplayMessage::CollapseAllFolders,
+            Message::SelectFolder(id) => ReplayMessage::SelectFolder(id.clone()),
+            Message::PlaylistSelect(id) => ReplayMessage::PlaylistSelect(*id),
+            Message::PlaylistDraftAdd(id) => ReplayMessage::PlaylistDraftAdd(*id),
+            Message::PlaylistDraftRemove(index) => ReplayMessage::PlaylistDraftRemove(*index),
+            Message::PlaylistDraftNameChanged(name) => {
+                ReplayMessage::PlaylistDraftNameChanged(name.clone())
+            }
+            Message::PlaylistDraftAddAll(ids) => ReplayMessage::PlaylistDraftAddAll(ids.clone()),
+            Message::PlaylistDraftRemoveAll => ReplayMessage::PlaylistDraftRemoveAll,
+            Message::PlaylistDraftMove(from, to) => ReplayMessage::PlaylistDraftMove(*from, *to),
+            Message::PlaylistDraftClear => ReplayMessage::PlaylistDraftClear,
+            Message::PlaylistDraftSave => ReplayMessage::PlaylistDraftSave,
+            Message::ToggleSettings => ReplayMessage::ToggleSettings,
+            Message::ToggleMiniPlayer => ReplayMessage::ToggleMiniPlayer,
+            Message::ShowSongInfo(id) => ReplayMessage::ShowSongInfo(*id),
+            Message::CloseSongInfo => ReplayMessage::CloseSongInfo,
+            Message::MasterTempoStep(delta) => ReplayMessage::MasterTempoStep(*delta),
+            Message::MasterTransposeStep(delta) => ReplayMessage::MasterTransposeStep(*delta),
+            Message::ClearMasterAdjustments => ReplayMessage::ClearMasterAdjustments,
+            Message::RepeatStep(delta) => ReplayMessage::RepeatStep(*delta),
+            Message::SongTempoStep(id, delta) => ReplayMessage::SongTempoStep(*id, *delta),
+            Message::SongTransposeStep(id, delta) => ReplayMessage::SongTransposeStep(*id, *delta),
+            Message::SongChannelMuteToggled(id, channel) => {
+                ReplayMessage::SongChannelMuteToggled(*id, *channel)
+            }
+            Message::ScoreOverlayTap => ReplayMessage::ScoreOverlayTap,
+            Message::ScoreOverlayShiftBar(delta) => ReplayMessage::ScoreOverlayShiftBar(*delta),
+            Message::DismissToast(id) => ReplayMessage::DismissToast(*id),
+            Message::DismissStatus => ReplayMessage::DismissStatus,
+            _ => return None,
+        })
+    }
+
+    fn into_message(self) -> Message {
+        match self {
+            ReplayMessage::DeviceSelected(id) => Message::DeviceSelected(id),
+            ReplayMessage::SongSelected(id) => Message::SongSelected(id),
+            ReplayMessage::SearchChanged(query) => Message::SearchChanged(query),
+            ReplayMessage::LibraryKey(key) => Message::LibraryKey(key),
+            ReplayMessage::LibrarySortSelected(sort) => Message::LibrarySortSelected(sort),
+            ReplayMessage::TreeGroupingSelected(grouping) => {
+                Message::TreeGroupingSelected(grouping)
+            }
+            ReplayMessage::PlayPressed => Message::PlayPressed,
+            ReplayMessage::StopPressed => Message::StopPressed,
+            ReplayMessage::PlayPause => Message::PlayPause,
+            ReplayMessage::SeekChanged(seconds) => Message::SeekChanged(seconds),
+            ReplayMessage::SeekReleased => Message::SeekReleased,
+            ReplayMessage::CancelPreparing => Message::CancelPreparing,
+            ReplayMessage::PanicPressed => Message::PanicPressed,
+            ReplayMessage::StartPlayback(id) => Message::StartPlayback(id),
+            ReplayMessage::PlayWork(id) => Message::PlayWork(id),
+            ReplayMessage::ToggleWork(id) => Message::ToggleWork(id),
+            ReplayMessage::PreviewSong(id) => Message::PreviewSong(id),
+            ReplayMessage::PlayNext(id) => Message::PlayNext(id),
+            ReplayMessage::AddToQueue(id) => Message::AddToQueue(id),
+            ReplayMessage::NextTrack => Message::NextTrack,
+            ReplayMessage::PrevTrack => Message::PrevTrack,
+            ReplayMessage::PlayFavorites { shuffle } => Message::PlayFavorites { shuffle },
+            ReplayMessage::PlayPlaylist { id, shuffle } => Message::PlayPlaylist { id, shuffle },
+            ReplayMessage::PlayFolder { id, shuffle } => Message::PlayFolder { id, shuffle },
+            ReplayMessage::SetRating(id, rating) => Message::SetRating(id, rating),
+            ReplayMessage::ToggleFavorite(id) => Message::ToggleFavorite(id),
+            ReplayMessage::SwitchTab(tab) => Message::SwitchTab(tab),
+            ReplayMessage::ToggleFolder(id) => Message::ToggleFolder(id),
+            ReplayMessage::ExpandAllFolders => Message::ExpandAllFolders,
+            ReplayMessage::CollapseAllFolders => Message::CollapseAllFolders,
+            ReplayMessage::SelectFolder(id) => Message::SelectFolder(id),
+            ReplayMessage::PlaylistSelect(id) => Message::PlaylistSelect(id),
+            ReplayMessage::PlaylistDraftAdd(id) => Message::PlaylistDraftAdd(id),
+            ReplayMessage::PlaylistDraftRemove(index) => Message::PlaylistDraftRemove(index),
+            ReplayMessage::PlaylistDraftNameChanged(name) => {
+                Message::PlaylistDraftNameChanged(name)
+            }
+            ReplayMessage::PlaylistDraftAddAll(ids) => Message::PlaylistDraftAddAll(ids),
+            ReplayMessage::PlaylistDraftRemoveAll => Message::PlaylistDraftRemoveAll,
+            ReplayMessage::PlaylistDraftMove(from, to) => Message::PlaylistDraftMove(from, to),
+            ReplayMessage::PlaylistDraftClear => Message::PlaylistDraftClear,
+            ReplayMessage::PlaylistDraftSave => Message::PlaylistDraftSave,
+            ReplayMessage::ToggleSettings => Message::ToggleSettings,
+            ReplayMessage::ToggleMiniPlayer => Message::ToggleMiniPlayer,
+            ReplayMessage::ShowSongInfo(id) => Message::ShowSongInfo(id),
+            ReplayMessage::CloseSongInfo => Message::CloseSongInfo,
+            ReplayMessage::MasterTempoStep(delta) => Message::MasterTempoStep(delta),
+            ReplayMessage::MasterTransposeStep(delta) => Message::MasterTransposeStep(delta),
+            ReplayMessage::ClearMasterAdjustments => Message::ClearMasterAdjustments,
+            ReplayMessage::RepeatStep(delta) => Message::RepeatStep(delta),
+            ReplayMessage::SongTempoStep(id, delta) => Message::SongTempoStep(id, delta),
+            ReplayMessage::SongTransposeStep(id, delta) => Message::SongTransposeStep(id, delta),
+            ReplayMessage::SongChannelMuteToggled(id, channel) => {
+                Message::SongChannelMuteToggled(id, channel)
+            }
+            ReplayMessage::ScoreOverlayTap => Message::ScoreOverlayTap,
+            ReplayMessage::ScoreOverlayShiftBar(delta) => Message::ScoreOverlayShiftBar(delta),
+            ReplayMessage::DismissToast(id) => Message::DismissToast(id),
+            ReplayMessage::DismissStatus => Message::DismissStatus,
+        }
+    }
+}
+
+/// Short description for the debug log. Messages carrying loaded data are
+/// reduced to their name and outcome instead of being formatted in full.
+fn summarize_message(message: &Message) -> String {
+    fn outcome<T, E: fmt::Display>(name: &str, result: &Result<T, E>) -> String {
+        match result {
+            Ok(_) => format!("{name}(Ok)"),
+            Err(err) => format!("{name}(Err({err}))"),
+        }
+    }
+    match message {
+        Message::LibraryLoadUpdate(LibraryLoadUpdate::Chunk(_, progress)) => format!(
+            "LibraryLoadUpdate({}/{})",
+            progress.verified, progress.total
+        ),
+        Message::LibraryLoadUpdate(LibraryLoadUpdate::Finished(result)) => {
+            outcome("LibraryLoaded", result)
+        }
+        Message::AssetsRescanned(result) => outcome("AssetsRescanned", result),
+        Message::RemoteCatalogLoaded(result) => outcome("RemoteCatalogLoaded", result),
+        Message::RemoteDownloaded(_, _, result) => outcome("RemoteDownloaded", result),
+        Message::RemoteCacheCleared(result) => outcome("RemoteCacheCleared", result),
+        Message::DevicesRefreshed(result) => outcome("DevicesRefreshed", result),
+        Message::VirtualPortUpdated(result) => outcome("VirtualPortUpdated", result),
+        Message::BleScanUpdate(result) => outcome("BleScanUpdate", result),
+        Message::BleAdaptersListed(result) => outcome("BleAdaptersListed", result),
+        Message::UserDataLoaded(result) => outcome("UserDataLoaded", result),
+        Message::PracticeLogLoaded(result) => outcome("PracticeLogLoaded", result),
+        Message::ResumeStateLoaded(result) => outcome("ResumeStateLoaded", result),
+        Message::PlaybackPrepared(result) => outcome("PlaybackPrepared", result),
+        Message::NextTrackPreloaded(result) => outcome("NextTrackPreloaded", result),
+        Message::InboxScanned(result) => outcome("InboxScanned", result),
+        Message::WatchLibraryIndexed(result) => outcome("WatchLibraryIndexed", result),
+        Message::MusicFolderIndexed(result) => outcome("MusicFolderIndexed", result),
+        Message::PreferencesImported(result) => outcome("PreferencesImported", result),
+        Message::UserDataImported(_, result) => outcome("UserDataImported", result),
+        Message::CatalogImported(result) => outcome("CatalogImported", result),
+        Message::QueueKeysDetected(_, result) => outcome("QueueKeysDetected", result),
+        Message::SongInfoLoaded(_, result) => outcome("SongInfoLoaded", result),
+        Message::SongDetailsRead(_, result) => outcome("SongDetailsRead", result),
+        Message::PreviewFinished(_, result) => outcome("PreviewFinished", result),
+        Message::ArrangementExported(result) => outcome("ArrangementExported", result),
+        Message::ExcerptSaved(result) => outcome("ExcerptSaved", result),
+        Message::TraySpawned(result) => outcome("TraySpawned", result),
+        Message::LibraryMetadataRefreshed(result) => outcome("LibraryMetadataRefreshed", result),
+        Message::MediaControlsSpawned(result) => outcome("MediaControlsSpawned", result),
+        Message::RemoteControlStopped(result) => outcome("RemoteControlStopped", result),
+        Message::TreeDataLoaded { request_id, .. } => format!("TreeDataLoaded({request_id})"),
+        other => format!("{other:?}"),
+    }
+}
+
 #[derive(Clone, Debug, PartialEq, Eq)]
 struct DeviceChoice {
     id: Uuid,
@@ -107,6 +703,8 @@ impl fmt::Display for DeviceChoice {
         let transport = match self.transport {
             MidiTransport::Usb => "USB",
             MidiTransport::Bluetooth => "BLE",
+            MidiTransport::Virtual => t!("Debug"),
+            MidiTransport::VirtualPort => t!("Virtual Port"),
         };
         write!(f, "[{transport}] {}", self.name)
     }
@@ -119,14 +717,36 @@ struct PlaylistChoice {
 }
 
 impl PlaylistChoice {
-    fn from(playlist: &Playlist) -> Self {
+    fn new(playlist: &Playlist, folder: Option<&PlaylistFolder>) -> Self {
+        let name = match folder {
+            Some(folder) => format!("{} / {}", folder.name, playlist.name),
+            None => playlist.name.clone(),
+        };
         Self {
             id: playlist.id,
-            name: playlist.name.clone(),
+            name,
         }
     }
 }
 
+#[derive(Clone, Debug, PartialEq, Eq)]
+struct FolderChoice {
+    id: Option<Uuid>,
+    name: String,
+}
+
+impl fmt::Display for FolderChoice {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.name)
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum RenameTarget {
+    Playlist(Uuid),
+    Folder(Uuid),
+}
+
 impl fmt::Display for PlaylistChoice {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
         write!(f, "{}", self.name)
@@ -138,960 +758,9526 @@ struct UserPreferences {
     ratings: HashMap<Uuid, u8>,
     favorites: HashSet<Uuid>,
     playlists: Vec<Playlist>,
+    #[serde(default)]
+    playlist_folders: Vec<PlaylistFolder>,
+    #[serde(default)]
+    tags: HashMap<Uuid, BTreeSet<String>>,
+    /// Titles and composers given to songs in place of their file names.
+    #[serde(default)]
+    song_details: HashMap<Uuid, SongDetails>,
+    #[serde(default)]
+    works: Vec<Work>,
+    #[serde(default)]
+    expanded_folders: HashSet<String>,
+    #[serde(default)]
+    remote_catalog_url: Option<String>,
+    /// Most recently started tracks, newest first.
+    #[serde(default)]
+    recently_played: Vec<Uuid>,
+    #[serde(default)]
+    watch_folder: Option<WatchFolderConfig>,
+    #[serde(default)]
+    soundfont_path: Option<PathBuf>,
+    #[serde(default)]
+    assignments: Vec<Assignment>,
+    #[serde(default)]
+    score_overlay: ScoreOverlaySettings,
+    #[serde(default)]
+    song_settings: HashMap<Uuid, SongSettings>,
+    #[serde(default)]
+    key_match_mode: KeyMatchMode,
+    #[serde(default)]
+    silence_watch: SilenceWatchSettings,
+    #[serde(default)]
+    lead_in: LeadInSettings,
+    /// Click along with the song's beats while playing.
+    #[serde(default)]
+    metronome: bool,
+    #[serde(default)]
+    quiet_mode: bool,
+    #[serde(default)]
+    quiet_limit: QuietLimit,
+    /// Merges songs onto one piano channel; see [`OutputFilter::piano_mode`].
+    #[serde(default)]
+    piano_mode: bool,
+    #[serde(default)]
+    music_folders: Vec<PathBuf>,
+    #[serde(default)]
+    program_override: Option<u8>,
+    #[serde(default)]
+    default_output_filter: OutputFilter,
+    /// Per-device filters saved before profiles existed, moved into
+    /// `device_profiles` on load.
+    #[serde(default, skip_serializing)]
+    device_output_filters: HashMap<Uuid, OutputFilter>,
+    #[serde(default)]
+    device_profiles: DeviceProfiles,
+    /// What each device can play; devices not listed take anything.
+    #[serde(default)]
+    device_capabilities: HashMap<Uuid, DeviceCapabilities>,
+    #[serde(default)]
+    language: UiLanguage,
+    #[serde(default)]
+    theme: Option<String>,
+    #[serde(default)]
+    mini_player: bool,
+    #[serde(default)]
+    close_to_tray: bool,
+    #[serde(default)]
+    remote_control: RemoteControlSettings,
+    #[serde(default)]
+    schedules: Vec<Schedule>,
+    /// Receives a JSON post whenever a song starts, finishes or stops.
+    #[serde(default)]
+    webhook_url: Option<String>,
+    /// Whether the app opens a virtual output port for other software.
+    #[serde(default)]
+    virtual_port: bool,
+    /// Seconds to wait for a device to connect; `None` uses the default.
+    #[serde(default)]
+    connect_timeout_secs: Option<u16>,
+    /// Largest BLE-MIDI packet; `None` uses each device's negotiated MTU.
+    #[serde(default)]
+    ble_packet_size: Option<u16>,
+    /// The only Bluetooth adapter scanned, by its adapter info; `None`
+    /// scans them all.
+    #[serde(default)]
+    ble_adapter: Option<String>,
+    /// Leave out repeated status bytes when sending to USB devices.
+    #[serde(default)]
+    usb_running_status: bool,
+    /// How often playback reports its position; `None` uses the default.
+    #[serde(default)]
+    progress_interval_ms: Option<u16>,
+    /// Fade in at the start and out on stop; zero cuts straight in and out.
+    #[serde(default)]
+    fade_ms: u16,
+    /// Logs every message sent to devices under [`SEND_TRACE_DIR`].
+    #[serde(default)]
+    send_trace: Option<TraceFormat>,
+    #[serde(default)]
+    play_stats: HashMap<Uuid, PlayStats>,
+    #[serde(default)]
+    shuffle_history: ShuffleHistory,
+    /// Recent plays a shuffle keeps for the end; `None` uses the default.
+    #[serde(default)]
+    shuffle_avoid: Option<u16>,
+    /// Shuffles favour higher-rated songs, which may then come up more than
+    /// once in a queue.
+    #[serde(default)]
+    weighted_shuffle: bool,
+    #[serde(default)]
+    library_sort: LibrarySort,
+    #[serde(default)]
+    title_collation: TitleCollation,
+    #[serde(default)]
+    tree_grouping: TreeGrouping,
+    /// Preference files written before the setup wizard existed belong to
+    /// users who are already set up.
+    #[serde(default = "existing_install")]
+    onboarding_complete: bool,
+    /// Selected on start, and whenever it turns up with nothing selected.
+    #[serde(default)]
+    default_device: Option<Uuid>,
+    /// Colors and emoji shown on tree folders, by folder id.
+    #[serde(default)]
+    folder_labels: HashMap<String, FolderLabel>,
 }
 
-#[derive(Debug, Clone, Serialize, Deserialize)]
-struct Playlist {
-    id: Uuid,
-    name: String,
-    tracks: Vec<Uuid>,
+fn existing_install() -> bool {
+    true
 }
 
-impl Playlist {
-    fn new(name: impl Into<String>, tracks: Vec<Uuid>) -> Self {
-        Self {
-            id: Uuid::new_v4(),
-            name: name.into(),
-            tracks,
+impl UserPreferences {
+    /// Turns each per-device filter from older preference files into a
+    /// profile of its own.
+    fn migrate_device_filters(&mut self) {
+        for (device, filter) in std::mem::take(&mut self.device_output_filters) {
+            let name = t!(
+                "Profile {number}",
+                number = self.device_profiles.iter().count() + 1
+            );
+            let profile = self.device_profiles.add(DeviceProfile::new(name, filter));
+            self.device_profiles.assign(device, Some(profile));
+        }
+    }
+
+    fn connect_timeout(&self) -> Duration {
+        self.connect_timeout_secs
+            .map_or(DEFAULT_CONNECT_TIMEOUT, |secs| {
+                Duration::from_secs(secs.into())
+            })
+    }
+
+    fn progress_interval(&self) -> Duration {
+        self.progress_interval_ms
+            .map_or(DEFAULT_PROGRESS_INTERVAL, |millis| {
+                Duration::from_millis(millis.into())
+            })
+    }
+
+    fn tree_groups(&self) -> TreeGroups<'_> {
+        TreeGroups {
+            grouping: self.tree_grouping,
+            ratings: &self.ratings,
+            tags: &self.tags,
+        }
+    }
+
+    /// Adds what `other` has that these preferences lack. Settings, and
+    /// anything set on both sides, stay as they are here.
+    fn merge(&mut self, other: UserPreferences) {
+        for (id, rating) in other.ratings {
+            self.ratings.entry(id).or_insert(rating);
+        }
+        self.favorites.extend(other.favorites);
+        for playlist in other.playlists {
+            if !self
+                .playlists
+                .iter()
+                .any(|existing| existing.id == playlist.id)
+            {
+                self.playlists.push(playlist);
+            }
+        }
+        for folder in other.playlist_folders {
+            if !self
+                .playlist_folders
+                .iter()
+                .any(|existing| existing.id == folder.id)
+            {
+                self.playlist_folders.push(folder);
+            }
+        }
+        for (id, tags) in other.tags {
+            self.tags.entry(id).or_default().extend(tags);
+        }
+        for assignment in other.assignments {
+            if !self
+                .assignments
+                .iter()
+                .any(|existing| existing.id == assignment.id)
+            {
+                self.assignments.push(assignment);
+            }
+        }
+        for schedule in other.schedules {
+            if !self
+                .schedules
+                .iter()
+                .any(|existing| existing.id == schedule.id)
+            {
+                self.schedules.push(schedule);
+            }
+        }
+        for (id, settings) in other.song_settings {
+            self.song_settings.entry(id).or_insert(settings);
+        }
+        for (id, details) in other.song_details {
+            self.song_details.entry(id).or_insert(details);
+        }
+        works::merge(&mut self.works, other.works);
+        folder_label::merge(&mut self.folder_labels, other.folder_labels);
+        for (id, capabilities) in other.device_capabilities {
+            self.device_capabilities.entry(id).or_insert(capabilities);
+        }
+        self.device_profiles.merge(other.device_profiles);
+        for folder in other.music_folders {
+            if !self.music_folders.contains(&folder) {
+                self.music_folders.push(folder);
+            }
+        }
+        for (id, theirs) in other.play_stats {
+            let stats = self.play_stats.entry(id).or_default();
+            // The larger count, not the sum, so importing twice doesn't
+            // double it.
+            stats.count = stats.count.max(theirs.count);
+            stats.last_played = stats.last_played.max(theirs.last_played);
         }
     }
+
+    fn shuffle_avoid(&self) -> usize {
+        self.shuffle_avoid
+            .map_or(ShuffleHistory::DEFAULT_AVOID, usize::from)
+    }
 }
 
-#[derive(Debug, Clone, Default)]
-struct PlaylistDraft {
-    name: String,
-    tracks: Vec<Uuid>,
+/// The reset sent at song start, or none, as offered in the settings.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+struct ResetChoice(Option<DeviceReset>);
+
+impl ResetChoice {
+    const ALL: [ResetChoice; 4] = [
+        ResetChoice(None),
+        ResetChoice(Some(DeviceReset::Gm)),
+        ResetChoice(Some(DeviceReset::Gs)),
+        ResetChoice(Some(DeviceReset::Xg)),
+    ];
 }
 
-#[derive(Debug, Clone)]
-struct PlayQueue {
-    tracks: Vec<Uuid>,
-    index: usize,
-    mode: QueueMode,
+/// The pitch bend range set at song start, or none, as offered in the
+/// settings.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+struct BendRangeChoice(Option<u8>);
+
+impl BendRangeChoice {
+    const ALL: [BendRangeChoice; 6] = [
+        BendRangeChoice(None),
+        BendRangeChoice(Some(1)),
+        BendRangeChoice(Some(2)),
+        BendRangeChoice(Some(7)),
+        BendRangeChoice(Some(12)),
+        BendRangeChoice(Some(24)),
+    ];
 }
 
-#[derive(Debug, Clone)]
-enum QueueMode {
-    Single,
-    Favorites,
-    Playlist(Uuid),
+impl fmt::Display for BendRangeChoice {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self.0 {
+            Some(semitones) => f.write_str(&t!("Bend range ±{semitones}", semitones = semitones)),
+            None => f.write_str(t!("Bend range as is")),
+        }
+    }
 }
 
-#[derive(Debug, Clone)]
-struct LibraryNode {
-    id: String,
-    name: String,
-    children: BTreeMap<String, LibraryNode>,
+/// Silence between the movements of a work, as offered in the song
+/// information panel.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+struct WorkPauseChoice(u8);
+
+impl WorkPauseChoice {
+    const ALL: [WorkPauseChoice; 6] = [
+        WorkPauseChoice(0),
+        WorkPauseChoice(2),
+        WorkPauseChoice(3),
+        WorkPauseChoice(5),
+        WorkPauseChoice(10),
+        WorkPauseChoice(30),
+    ];
 }
 
-impl LibraryNode {
-    fn new(id: String, name: String) -> Self {
-        Self {
-            id,
-            name,
-            children: BTreeMap::new(),
+impl fmt::Display for WorkPauseChoice {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self.0 {
+            0 => f.write_str(t!("No pause")),
+            seconds => f.write_str(&t!("{seconds} s pause", seconds = seconds)),
         }
     }
+}
 
-    fn ensure_child(&mut self, id: String, name: String) -> &mut LibraryNode {
-        self.children
-            .entry(id.clone())
-            .or_insert_with(|| LibraryNode::new(id, name))
+/// How to go on with a song that needs more than the device can play.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum CapabilityChoice {
+    Remap,
+    /// Remap, and do so without asking from now on.
+    AlwaysRemap,
+    PlayAsWritten,
+    Cancel,
+}
+
+/// The opt-in WebSocket server other devices control playback through.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default)]
+struct RemoteControlSettings {
+    enabled: bool,
+    port: u16,
+    /// Clients must present this; generated when the server is first
+    /// enabled.
+    token: String,
+}
+
+impl Default for RemoteControlSettings {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            port: DEFAULT_REMOTE_CONTROL_PORT,
+            token: String::new(),
+        }
     }
 }
 
+fn new_remote_control_token() -> String {
+    Uuid::new_v4().simple().to_string()
+}
+
+/// An hour or minute as offered in the schedule editor.
 #[derive(Debug, Clone, Copy, PartialEq, Eq)]
-enum LibraryTab {
-    Tree,
-    Favorites,
+struct ClockTime(u32);
+
+impl fmt::Display for ClockTime {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{:02}", self.0)
+    }
 }
 
-#[derive(Debug, Clone)]
-struct TreeItem {
-    id: String,
-    name: String,
-    depth: usize,
-    has_children: bool,
-    is_expanded: bool,
+/// Monday first, as in [`Schedule::days`].
+fn weekday_label(day: usize) -> &'static str {
+    match day {
+        0 => t!("Mo"),
+        1 => t!("Tu"),
+        2 => t!("We"),
+        3 => t!("Th"),
+        4 => t!("Fr"),
+        5 => t!("Sa"),
+        _ => t!("Su"),
+    }
 }
 
-pub struct MidiPianoApp {
-    library: MidiLibrary,
-    device_manager: Arc<Mutex<MidiDeviceManager>>,
-    devices: Vec<DeviceChoice>,
-    selected_device: Option<Uuid>,
-    selected_song: Option<Uuid>,
-    search_query: String,
-    midi_player: MidiPlayer,
-    player_events: UnboundedReceiver<PlayerEvent>,
-    current_sink: Option<SharedMidiSink>,
-    playback_phase: PlaybackPhase,
-    playback_progress: Option<PlaybackProgress>,
-    status_message: Option<String>,
-    error_message: Option<String>,
-    is_scanning_devices: bool,
-    is_preparing_playback: bool,
-    user_prefs: UserPreferences,
-    active_tab: LibraryTab,
-    library_tree: LibraryNode,
-    folder_entries: HashMap<String, Vec<Uuid>>,
-    expanded_folders: HashSet<String>,
-    selected_folder: Option<String>,
-    playlist_draft: PlaylistDraft,
-    selected_playlist: Option<Uuid>,
-    tree_cache: Vec<TreeItem>,
-    tree_loading: bool,
-    tree_request_id: u64,
-    play_queue: Option<PlayQueue>,
+/// How long a timed sleep timer takes to fade out before stopping.
+const SLEEP_FADE: Duration = Duration::from_secs(10);
+
+/// When playback stops by itself, as offered next to the transport.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum SleepTimerChoice {
+    Off,
+    Minutes(u64),
+    Tracks(u32),
+    /// Stop once the tracks queued when it was chosen have played.
+    EndOfQueue,
 }
 
-impl MidiPianoApp {
-    fn init() -> (Self, Task<Message>) {
-        let (event_tx, event_rx) = mpsc::unbounded_channel();
-        let device_manager = Arc::new(Mutex::new(MidiDeviceManager::new()));
-        let mut expanded_folders = HashSet::new();
-        expanded_folders.insert("root".into());
+impl SleepTimerChoice {
+    const ALL: [SleepTimerChoice; 9] = [
+        SleepTimerChoice::Off,
+        SleepTimerChoice::Minutes(15),
+        SleepTimerChoice::Minutes(30),
+        SleepTimerChoice::Minutes(45),
+        SleepTimerChoice::Minutes(60),
+        SleepTimerChoice::Minutes(90),
+        SleepTimerChoice::Tracks(1),
+        SleepTimerChoice::Tracks(3),
+        SleepTimerChoice::EndOfQueue,
+    ];
+}
 
-        let app = MidiPianoApp {
-            library: MidiLibrary::default(),
-            device_manager: device_manager.clone(),
-            devices: Vec::new(),
-            selected_device: None,
-            selected_song: None,
-            search_query: String::new(),
-            midi_player: MidiPlayer::new(event_tx),
-            player_events: event_rx,
-            current_sink: None,
-            playback_phase: PlaybackPhase::Idle,
-            playback_progress: None,
-            status_message: None,
-            error_message: None,
-            is_scanning_devices: true,
-            is_preparing_playback: false,
-            user_prefs: UserPreferences::default(),
-            active_tab: LibraryTab::Tree,
-            library_tree: LibraryNode::new("root".into(), "Library".into()),
-            folder_entries: HashMap::new(),
-            expanded_folders,
-            selected_folder: None,
-            playlist_draft: PlaylistDraft::default(),
-            selected_playlist: None,
-            tree_cache: Vec::new(),
-            tree_loading: false,
-            tree_request_id: 0,
-            play_queue: None,
-        };
+impl fmt::Display for SleepTimerChoice {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            SleepTimerChoice::Off => f.write_str(t!("Sleep timer: off")),
+            SleepTimerChoice::Minutes(minutes) => {
+                f.write_str(&t!("Stop after {minutes} min", minutes = minutes))
+            }
+            SleepTimerChoice::Tracks(1) => f.write_str(t!("Stop after this track")),
+            SleepTimerChoice::Tracks(tracks) => {
+                f.write_str(&t!("Stop after {tracks} tracks", tracks = tracks))
+            }
+            SleepTimerChoice::EndOfQueue => f.write_str(t!("Stop at end of queue")),
+        }
+    }
+}
 
-        let mut app = app;
-        app.refresh_tree_cache();
+/// A running sleep timer; tracks count down as each one finishes.
+#[derive(Debug, Clone, Copy)]
+enum SleepTimer {
+    Until(Instant),
+    Tracks(u32),
+}
 
-        let task = Task::batch([
-            Task::perform(load_library(), Message::LibraryLoaded),
-            Task::perform(
-                refresh_devices(device_manager.clone()),
-                Message::DevicesRefreshed,
-            ),
-            Task::perform(load_user_preferences(), Message::UserDataLoaded),
-            Self::ble_scan_task(device_manager.clone()),
-        ]);
+/// A scheduled start coming up within [`schedule::COUNTDOWN`].
+#[derive(Debug, Clone)]
+struct ScheduleCountdown {
+    schedule_id: Uuid,
+    due: chrono::DateTime<chrono::Local>,
+}
 
-        (app, task)
+/// A Bluetooth adapter to scan, or all of them, as offered in the settings.
+#[derive(Debug, Clone, PartialEq, Eq)]
+struct AdapterChoice(Option<String>);
+
+impl fmt::Display for AdapterChoice {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match &self.0 {
+            Some(adapter) => f.write_str(adapter),
+            None => f.write_str(t!("All Bluetooth adapters")),
+        }
     }
+}
 
-    fn update(&mut self, message: Message) -> Task<Message> {
-        match message {
-            Message::LibraryLoaded(result) => {
+/// A device's profile, or the default rules, as offered in the settings.
+#[derive(Debug, Clone, PartialEq, Eq)]
+struct ProfileChoice {
+    id: Option<Uuid>,
+    name: String,
+}
+
+impl fmt::Display for ProfileChoice {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self.id {
+            Some(_) => f.write_str(&self.name),
+            None => f.write_str(t!("Default rules")),
+        }
+    }
+}
+
+impl fmt::Display for ResetChoice {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self.0 {
+            Some(reset) => write!(f, "{reset}"),
+            None => f.write_str(t!("No reset")),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum FilterMode {
+    Pass,
+    Drop,
+    Clamp,
+    Scale,
+}
+
+impl FilterMode {
+    const ALL: [FilterMode; 4] = [
+        FilterMode::Pass,
+        FilterMode::Drop,
+        FilterMode::Clamp,
+        FilterMode::Scale,
+    ];
+    const DEFAULT_CLAMP: u8 = 64;
+    const DEFAULT_SCALE: u8 = 50;
+
+    fn of(action: FilterAction) -> Self {
+        match action {
+            FilterAction::Pass => FilterMode::Pass,
+            FilterAction::Drop => FilterMode::Drop,
+            FilterAction::Clamp(_) => FilterMode::Clamp,
+            FilterAction::Scale(_) => FilterMode::Scale,
+        }
+    }
+}
+
+impl fmt::Display for FilterMode {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let label = match self {
+            FilterMode::Pass => t!("Pass through"),
+            FilterMode::Drop => t!("Drop"),
+            FilterMode::Clamp => t!("Clamp"),
+            FilterMode::Scale => t!("Scale"),
+        };
+        write!(f, "{label}")
+    }
+}
+
+/// Instrument forced on playback, or `None` to keep the file's own programs.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+struct ProgramChoice(Option<u8>);
+
+impl ProgramChoice {
+    fn options() -> Vec<ProgramChoice> {
+        std::iter::once(ProgramChoice(None))
+            .chain((0..128).map(|program| ProgramChoice(Some(program))))
+            .collect()
+    }
+}
+
+impl fmt::Display for ProgramChoice {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self.0 {
+            Some(program) => write!(f, "{} {}", program + 1, monitor::gm_program_name(program)),
+            None => f.write_str(t!("Song's own instruments")),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+struct ChannelFilter(Option<u8>);
+
+impl ChannelFilter {
+    fn options() -> Vec<ChannelFilter> {
+        std::iter::once(ChannelFilter(None))
+            .chain((0..16).map(|channel| ChannelFilter(Some(channel))))
+            .collect()
+    }
+}
+
+impl fmt::Display for ChannelFilter {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self.0 {
+            Some(channel) => f.write_str(&t!("Channel {number}", number = channel + 1)),
+            None => f.write_str(t!("All channels")),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+struct KindFilter(Option<MessageKind>);
+
+impl KindFilter {
+    fn options() -> Vec<KindFilter> {
+        std::iter::once(KindFilter(None))
+            .chain(
+                MessageKind::ALL
+                    .into_iter()
+                    .map(|kind| KindFilter(Some(kind))),
+            )
+            .collect()
+    }
+}
+
+impl fmt::Display for KindFilter {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self.0 {
+            Some(kind) => write!(f, "{kind}"),
+            None => f.write_str(t!("All messages")),
+        }
+    }
+}
+
+/// State of the open MIDI monitor pane. `entries` is a snapshot of the
+/// shared monitor, refreshed on tick unless paused.
+#[derive(Debug, Default)]
+struct MonitorPane {
+    paused: bool,
+    hex: bool,
+    channel: ChannelFilter,
+    kind: KindFilter,
+    entries: Vec<MonitorEntry>,
+    revision: u64,
+}
+
+impl MonitorPane {
+    fn refresh(&mut self, monitor: &MidiMonitor) {
+        let revision = monitor.revision();
+        if revision != self.revision {
+            self.revision = revision;
+            self.entries = monitor.entries();
+        }
+    }
+
+    fn shows(&self, entry: &MonitorEntry) -> bool {
+        self.channel
+            .0
+            .is_none_or(|channel| entry.channel() == Some(channel))
+            && self.kind.0.is_none_or(|kind| entry.kind() == kind)
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum OnboardingStep {
+    Appearance,
+    MusicFolder,
+    Devices,
+    TestTone,
+    ImportPreferences,
+}
+
+impl OnboardingStep {
+    const ALL: [OnboardingStep; 5] = [
+        OnboardingStep::Appearance,
+        OnboardingStep::MusicFolder,
+        OnboardingStep::Devices,
+        OnboardingStep::TestTone,
+        OnboardingStep::ImportPreferences,
+    ];
+
+    fn position(self) -> usize {
+        Self::ALL
+            .iter()
+            .position(|step| *step == self)
+            .unwrap_or_default()
+    }
+
+    fn next(self) -> Option<Self> {
+        Self::ALL.get(self.position() + 1).copied()
+    }
+
+    fn previous(self) -> Option<Self> {
+        self.position()
+            .checked_sub(1)
+            .and_then(|index| Self::ALL.get(index).copied())
+    }
+
+    fn title(self) -> &'static str {
+        match self {
+            OnboardingStep::Appearance => t!("Language and theme"),
+            OnboardingStep::MusicFolder => t!("Your music"),
+            OnboardingStep::Devices => t!("Connect your piano"),
+            OnboardingStep::TestTone => t!("Test the connection"),
+            OnboardingStep::ImportPreferences => t!("Bring your settings"),
+        }
+    }
+}
+
+/// Progress through the first-run setup wizard.
+#[derive(Debug, Clone)]
+struct Onboarding {
+    step: OnboardingStep,
+    scanning_folder: bool,
+    found_files: usize,
+    test_tone: Option<AsyncResult<()>>,
+    imported_from: Option<PathBuf>,
+}
+
+impl Onboarding {
+    fn new() -> Self {
+        Self {
+            step: OnboardingStep::Appearance,
+            scanning_folder: false,
+            found_files: 0,
+            test_tone: None,
+            imported_from: None,
+        }
+    }
+}
+
+/// Platform-specific hints shown when no device turns up during setup.
+/// Shown while no piano has been found. Bluetooth MIDI is scanned for
+/// directly, so the piano only needs to be advertising.
+const BLE_PAIRING_HINTS: [&str; 3] = [
+    "Switch on Bluetooth MIDI at the piano; many models need a button held or a menu setting.",
+    "Keep the piano within a few metres and disconnect it from phones or tablets first.",
+    "Pianos that also offer Bluetooth audio list a separate MIDI device; that is the one to use.",
+];
+
+fn device_troubleshooting_tips() -> Vec<&'static str> {
+    if cfg!(target_os = "windows") {
+        vec![
+            t!(
+                "Close other apps using the piano: Windows MIDI ports can only be opened by one program at a time."
+            ),
+            t!(
+                "Install the USB-MIDI driver from your piano's manufacturer if the device is not listed."
+            ),
+            t!("Bluetooth pianos must be paired in Settings > Bluetooth & devices first."),
+        ]
+    } else if cfg!(target_os = "macos") {
+        vec![
+            t!("Open Audio MIDI Setup > MIDI Studio to check that macOS sees the piano."),
+            t!("Bluetooth pianos are connected from the Bluetooth button in MIDI Studio."),
+            t!("Allow Bluetooth access for this app in System Settings > Privacy & Security."),
+        ]
+    } else {
+        vec![
+            t!(
+                "Check that the piano shows up in `aconnect -l`; your user may need to be in the audio group."
+            ),
+            t!("Bluetooth pianos need BlueZ running and the piano in pairing mode."),
+            t!("Unplug and reconnect USB cables, then press Refresh."),
+        ]
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+enum SilenceAction {
+    #[default]
+    Off,
+    Notify,
+    FastForward,
+}
+
+impl SilenceAction {
+    const ALL: [SilenceAction; 3] = [
+        SilenceAction::Off,
+        SilenceAction::Notify,
+        SilenceAction::FastForward,
+    ];
+}
+
+impl fmt::Display for SilenceAction {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let label = match self {
+            SilenceAction::Off => t!("Off"),
+            SilenceAction::Notify => t!("Notify me"),
+            SilenceAction::FastForward => t!("Fast-forward"),
+        };
+        write!(f, "{label}")
+    }
+}
+
+#[derive(Debug, Clone, Copy, Serialize, Deserialize)]
+struct SilenceWatchSettings {
+    action: SilenceAction,
+    threshold_secs: u16,
+}
+
+impl Default for SilenceWatchSettings {
+    fn default() -> Self {
+        Self {
+            action: SilenceAction::Off,
+            threshold_secs: 8,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, Serialize, Deserialize)]
+#[serde(default)]
+struct LeadInSettings {
+    trim_silence: bool,
+    pre_roll_ms: u16,
+    count_in: bool,
+    countdown: bool,
+}
+
+impl Default for LeadInSettings {
+    fn default() -> Self {
+        Self {
+            trim_silence: false,
+            pre_roll_ms: 500,
+            count_in: false,
+            countdown: false,
+        }
+    }
+}
+
+impl From<LeadInSettings> for LeadIn {
+    fn from(settings: LeadInSettings) -> Self {
+        LeadIn {
+            trim_to: settings
+                .trim_silence
+                .then(|| Duration::from_millis(settings.pre_roll_ms.into())),
+            count_in: settings.count_in,
+            countdown: if settings.countdown {
+                LeadIn::COUNTDOWN
+            } else {
+                0
+            },
+        }
+    }
+}
+
+/// Practice setup remembered per library entry and restored on playback.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default)]
+struct SongSettings {
+    tempo_percent: u16,
+    transpose: i8,
+    muted_channels: u16,
+    hand_split: Option<HandSplit>,
+    silence_watch_disabled: bool,
+    /// Overrides the device profile's percussion handling.
+    percussion: Option<PercussionHandling>,
+}
+
+impl Default for SongSettings {
+    fn default() -> Self {
+        Self {
+            tempo_percent: 100,
+            transpose: 0,
+            muted_channels: 0,
+            hand_split: None,
+            silence_watch_disabled: false,
+            percussion: None,
+        }
+    }
+}
+
+/// Percussion handling as picked in a list; `Profile` leaves a song to its
+/// device's profile.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum PercussionChoice {
+    Profile,
+    Keep,
+    Drop,
+    Rhythm,
+}
+
+impl PercussionChoice {
+    const ALL: [PercussionChoice; 4] = [
+        PercussionChoice::Profile,
+        PercussionChoice::Keep,
+        PercussionChoice::Drop,
+        PercussionChoice::Rhythm,
+    ];
+    const DEVICE: [PercussionChoice; 3] = [
+        PercussionChoice::Keep,
+        PercussionChoice::Drop,
+        PercussionChoice::Rhythm,
+    ];
+
+    fn of(handling: Option<PercussionHandling>) -> Self {
+        match handling {
+            None => PercussionChoice::Profile,
+            Some(PercussionHandling::Keep) => PercussionChoice::Keep,
+            Some(PercussionHandling::Drop) => PercussionChoice::Drop,
+            Some(PercussionHandling::Rhythm(_)) => PercussionChoice::Rhythm,
+        }
+    }
+
+    /// The handling chosen, keeping the rhythm channel of `current`.
+    fn handling(self, current: Option<PercussionHandling>) -> Option<PercussionHandling> {
+        match self {
+            PercussionChoice::Profile => None,
+            PercussionChoice::Keep => Some(PercussionHandling::Keep),
+            PercussionChoice::Drop => Some(PercussionHandling::Drop),
+            PercussionChoice::Rhythm => Some(match current {
+                Some(rhythm @ PercussionHandling::Rhythm(_)) => rhythm,
+                _ => PercussionHandling::Rhythm(PercussionHandling::DEFAULT_RHYTHM_CHANNEL),
+            }),
+        }
+    }
+}
+
+impl fmt::Display for PercussionChoice {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let label = match self {
+            PercussionChoice::Profile => t!("Drums: device default"),
+            PercussionChoice::Keep => t!("Drums: keep"),
+            PercussionChoice::Drop => t!("Drums: drop"),
+            PercussionChoice::Rhythm => t!("Drums: piano rhythm"),
+        };
+        write!(f, "{label}")
+    }
+}
+
+/// Moves a rhythm to the next channel in `delta`'s direction, skipping the
+/// percussion channel itself.
+fn step_rhythm_channel(handling: &mut PercussionHandling, delta: i8) {
+    if let PercussionHandling::Rhythm(channel) = handling {
+        let mut next = (*channel as i8 + delta).rem_euclid(16) as u8;
+        if next == 9 {
+            next = (next as i8 + delta).rem_euclid(16) as u8;
+        }
+        *channel = next;
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum HandSplitKind {
+    Off,
+    ByPitch,
+    ByTrack,
+}
+
+impl HandSplitKind {
+    const ALL: [HandSplitKind; 3] = [
+        HandSplitKind::Off,
+        HandSplitKind::ByPitch,
+        HandSplitKind::ByTrack,
+    ];
+
+    fn of(split: Option<HandSplit>) -> Self {
+        match split.map(|split| split.classifier) {
+            None => HandSplitKind::Off,
+            Some(HandClassifier::SplitPoint(_)) => HandSplitKind::ByPitch,
+            Some(HandClassifier::LeftTrack(_)) => HandSplitKind::ByTrack,
+        }
+    }
+}
+
+impl fmt::Display for HandSplitKind {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let label = match self {
+            HandSplitKind::Off => t!("Hands: off"),
+            HandSplitKind::ByPitch => t!("Hands: by split point"),
+            HandSplitKind::ByTrack => t!("Hands: by track"),
+        };
+        write!(f, "{label}")
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Hand {
+    Left,
+    Right,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+struct ScoreOverlaySettings {
+    enabled: bool,
+    text_size: u16,
+}
+
+impl Default for ScoreOverlaySettings {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            text_size: 96,
+        }
+    }
+}
+
+/// Minimum star rating shown in the library; zero shows everything.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+struct RatingFilter(u8);
+
+impl RatingFilter {
+    const ALL: [RatingFilter; 6] = [
+        RatingFilter(0),
+        RatingFilter(1),
+        RatingFilter(2),
+        RatingFilter(3),
+        RatingFilter(4),
+        RatingFilter(5),
+    ];
+}
+
+impl fmt::Display for RatingFilter {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self.0 {
+            0 => f.write_str(t!("Any rating")),
+            stars => write!(f, "≥ {}", "★".repeat(stars.into())),
+        }
+    }
+}
+
+/// Plays of a library entry that finished or got past halfway.
+#[derive(Debug, Clone, Copy, Default, Serialize, Deserialize)]
+struct PlayStats {
+    count: u32,
+    last_played: Option<chrono::DateTime<chrono::Utc>>,
+}
+
+/// Preferences, playlists, statistics and device profiles in one file, for
+/// moving a setup to another computer.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+struct UserDataBundle {
+    version: u32,
+    exported_at: chrono::DateTime<chrono::Utc>,
+    preferences: UserPreferences,
+    practice_log: PracticeLog,
+}
+
+impl UserDataBundle {
+    const VERSION: u32 = 1;
+}
+
+/// What importing a [`UserDataBundle`] does with the data already here.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum UserDataImport {
+    /// Keep it, adding whatever it lacks from the bundle.
+    Merge,
+    /// Swap it for the bundle's.
+    Replace,
+}
+
+/// A key pressed while the library list has keyboard focus.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+enum LibraryKey {
+    Previous,
+    Next,
+    First,
+    Last,
+    Activate,
+    Leave,
+    /// Printable text, matched against the start of entry names.
+    Type(String),
+}
+
+/// Most passes a song can be set to repeat for.
+const MAX_REPEAT_PASSES: u8 = 99;
+
+/// How long a pause ends a type-ahead search in the library list.
+const TYPE_AHEAD_TIMEOUT: Duration = Duration::from_secs(1);
+/// How long typing in the search box has to pause before the library list
+/// is filtered again.
+const SEARCH_DEBOUNCE: Duration = Duration::from_millis(250);
+/// Smaller libraries filter on every keystroke.
+const SEARCH_DEBOUNCE_MIN_ENTRIES: usize = 2_000;
+
+fn library_list_id() -> scrollable::Id {
+    scrollable::Id::new("library-list")
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+enum LibrarySort {
+    #[default]
+    Name,
+    MostPlayed,
+    RecentlyPlayed,
+}
+
+impl LibrarySort {
+    const ALL: [LibrarySort; 3] = [
+        LibrarySort::Name,
+        LibrarySort::MostPlayed,
+        LibrarySort::RecentlyPlayed,
+    ];
+}
+
+impl fmt::Display for LibrarySort {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            LibrarySort::Name => t!("Name"),
+            LibrarySort::MostPlayed => t!("Most played"),
+            LibrarySort::RecentlyPlayed => t!("Recently played"),
+        })
+    }
+}
+
+/// What the library tree files songs under.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+enum TreeGrouping {
+    #[default]
+    Folder,
+    Composer,
+    Tag,
+    Rating,
+}
+
+impl TreeGrouping {
+    const ALL: [TreeGrouping; 4] = [
+        TreeGrouping::Folder,
+        TreeGrouping::Composer,
+        TreeGrouping::Tag,
+        TreeGrouping::Rating,
+    ];
+}
+
+impl fmt::Display for TreeGrouping {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            TreeGrouping::Folder => t!("By folder"),
+            TreeGrouping::Composer => t!("By composer"),
+            TreeGrouping::Tag => t!("By tag"),
+            TreeGrouping::Rating => t!("By rating"),
+        })
+    }
+}
+
+/// The groups a [`TreeGrouping`] files each song under, looked up in the
+/// ratings and tags it borrows.
+#[derive(Clone, Copy)]
+struct TreeGroups<'a> {
+    grouping: TreeGrouping,
+    ratings: &'a HashMap<Uuid, u8>,
+    tags: &'a HashMap<Uuid, BTreeSet<String>>,
+}
+
+impl TreeGroups<'_> {
+    /// The ids and names of the groups `entry` is in. Folder grouping has
+    /// none; its folders come from library paths.
+    fn of(&self, entry: &midi_piano_rs::midi::MidiEntry) -> Vec<(String, String)> {
+        match self.grouping {
+            TreeGrouping::Folder => Vec::new(),
+            TreeGrouping::Composer => vec![match &entry.composer {
+                Some(composer) => (format!("composer:{composer}"), composer.clone()),
+                None => ("composer:".into(), t!("Unknown composer").into()),
+            }],
+            TreeGrouping::Tag => match self.tags.get(&entry.id).filter(|tags| !tags.is_empty()) {
+                Some(tags) => tags
+                    .iter()
+                    .map(|tag| (format!("tag:{tag}"), tag.clone()))
+                    .collect(),
+                None => vec![("tag:".into(), t!("Untagged").into())],
+            },
+            TreeGrouping::Rating => {
+                let rating = self.ratings.get(&entry.id).copied();
+                vec![match rating.filter(|stars| (1..=5).contains(stars)) {
+                    Some(stars) => (format!("rating:{stars}"), "★".repeat(stars.into())),
+                    None => ("rating:".into(), t!("Unrated").into()),
+                }]
+            }
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+struct Playlist {
+    id: Uuid,
+    name: String,
+    tracks: Vec<Uuid>,
+    #[serde(default)]
+    folder: Option<Uuid>,
+    /// Smart playlists pick their tracks from tags instead of `tracks`.
+    #[serde(default)]
+    rule: Option<TagRule>,
+    /// Order of a smart playlist's tracks.
+    #[serde(default)]
+    order: LibrarySort,
+    #[serde(default)]
+    defaults: PlaylistDefaults,
+}
+
+impl Playlist {
+    fn new(name: impl Into<String>, tracks: Vec<Uuid>) -> Self {
+        Self {
+            id: Uuid::new_v4(),
+            name: name.into(),
+            tracks,
+            folder: None,
+            rule: None,
+            order: LibrarySort::Name,
+            defaults: PlaylistDefaults::default(),
+        }
+    }
+}
+
+/// Applied whenever a playlist is played. Speed and transpose stack on the
+/// song's own settings and the session master while its queue plays.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+struct PlaylistDefaults {
+    device_id: Option<Uuid>,
+    /// Always shuffle, even when played in order.
+    shuffle: bool,
+    /// `None` plays at the written tempo.
+    tempo_percent: Option<u16>,
+    transpose: i8,
+}
+
+/// A set of tags matched either all together or any one of them. An empty
+/// rule matches everything.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+struct TagRule {
+    tags: BTreeSet<String>,
+    match_all: bool,
+}
+
+impl TagRule {
+    fn matches(&self, tags: Option<&BTreeSet<String>>) -> bool {
+        if self.tags.is_empty() {
+            return true;
+        }
+        let Some(tags) = tags else {
+            return false;
+        };
+        if self.match_all {
+            self.tags.is_subset(tags)
+        } else {
+            !self.tags.is_disjoint(tags)
+        }
+    }
+}
+
+impl fmt::Display for TagRule {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let joiner = if self.match_all { " + " } else { t!(" or ") };
+        let tags = self.tags.iter().cloned().collect::<Vec<_>>().join(joiner);
+        write!(f, "{tags}")
+    }
+}
+
+/// Tags are compared case-insensitively with runs of whitespace collapsed.
+fn normalize_tag(raw: &str) -> Option<String> {
+    let tag = raw.split_whitespace().collect::<Vec<_>>().join(" ");
+    (!tag.is_empty()).then(|| tag.to_lowercase())
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+struct PlaylistFolder {
+    id: Uuid,
+    name: String,
+}
+
+impl UserPreferences {
+    fn all_tags(&self) -> BTreeSet<&String> {
+        self.tags.values().flatten().collect()
+    }
+
+    fn playlist_folder(&self, id: Option<Uuid>) -> Option<&PlaylistFolder> {
+        let id = id?;
+        self.playlist_folders.iter().find(|folder| folder.id == id)
+    }
+
+    /// Unfiled playlists first, then each folder's playlists in folder
+    /// order. Within a group the stored order is kept.
+    fn playlist_groups(&self) -> Vec<(Option<&PlaylistFolder>, Vec<&Playlist>)> {
+        let in_folder = |folder: Option<Uuid>| -> Vec<&Playlist> {
+            self.playlists
+                .iter()
+                .filter(|playlist| playlist.folder == folder)
+                .collect()
+        };
+        let unfiled: Vec<&Playlist> = self
+            .playlists
+            .iter()
+            .filter(|playlist| self.playlist_folder(playlist.folder).is_none())
+            .collect();
+        std::iter::once((None, unfiled))
+            .chain(
+                self.playlist_folders
+                    .iter()
+                    .map(|folder| (Some(folder), in_folder(Some(folder.id)))),
+            )
+            .collect()
+    }
+}
+
+#[derive(Debug, Clone, Default)]
+struct PlaylistDraft {
+    name: String,
+    tracks: Vec<Uuid>,
+}
+
+/// Where each library entry comes in title order, so lists can be sorted
+/// without building collation keys every frame.
+#[derive(Debug, Default)]
+struct TitleRanks {
+    /// Library revision and collation the ranks were computed for.
+    source: Option<(u64, TitleCollation)>,
+    ranks: HashMap<Uuid, usize>,
+}
+
+/// The side-by-side view for building the draft, while it is open.
+#[derive(Debug, Clone, Default)]
+struct PlaylistBuilder {
+    /// Narrows the library list by name.
+    query: String,
+    /// Draft position of the song being dragged.
+    dragging: Option<usize>,
+}
+
+#[derive(Debug, Clone)]
+struct SongInfoPanel {
+    entry_id: Uuid,
+    name: String,
+    path: PathBuf,
+    info: Option<SequenceInfo>,
+    excerpt: ExcerptDraft,
+    title_draft: String,
+    composer_draft: String,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+enum ExcerptUnit {
+    #[default]
+    Bars,
+    Time,
+}
+
+impl ExcerptUnit {
+    const ALL: [ExcerptUnit; 2] = [ExcerptUnit::Bars, ExcerptUnit::Time];
+}
+
+impl fmt::Display for ExcerptUnit {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            ExcerptUnit::Bars => t!("Bars"),
+            ExcerptUnit::Time => t!("Time (m:ss)"),
+        })
+    }
+}
+
+/// The section typed into the song information panel, saved as a new file.
+#[derive(Debug, Clone, Default)]
+struct ExcerptDraft {
+    unit: ExcerptUnit,
+    from: String,
+    to: String,
+}
+
+#[derive(Debug, Clone, Copy)]
+enum ExcerptRange {
+    /// First and last bar, both included.
+    Bars(u32, u32),
+    Time(Duration, Duration),
+}
+
+impl ExcerptDraft {
+    fn range(&self) -> Option<ExcerptRange> {
+        match self.unit {
+            ExcerptUnit::Bars => {
+                let from: u32 = self.from.trim().parse().ok()?;
+                let to: u32 = self.to.trim().parse().ok()?;
+                (from >= 1 && from <= to).then_some(ExcerptRange::Bars(from, to))
+            }
+            ExcerptUnit::Time => {
+                let from = parse_timestamp(&self.from)?;
+                let to = parse_timestamp(&self.to)?;
+                (from < to).then_some(ExcerptRange::Time(from, to))
+            }
+        }
+    }
+}
+
+#[derive(Debug, Clone, Default)]
+struct AssignmentDraft {
+    title: String,
+    due: String,
+    items: Vec<AssignmentItem>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+struct PlayQueue {
+    tracks: Vec<Uuid>,
+    index: usize,
+    mode: QueueMode,
+    /// Semitone shifts chosen by key matching, applied on top of song presets.
+    key_shifts: HashMap<Uuid, i8>,
+    /// Songs put in with Play Next or Add to Queue rather than by how the
+    /// queue was started.
+    #[serde(default)]
+    inserted: HashSet<Uuid>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+enum QueueMode {
+    Single,
+    Favorites,
+    Playlist(Uuid),
+    /// Everything filed under a library tree folder, by tree node id.
+    Folder(String),
+    Work(Uuid),
+}
+
+/// Where playback was when the app last saved, so it can be picked up after
+/// a restart.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+struct ResumeState {
+    queue: PlayQueue,
+    position_ms: u64,
+    device_id: Option<Uuid>,
+    /// Saved while playing. Still set when loaded, the app was killed
+    /// mid-song and the device may have notes hanging.
+    #[serde(default)]
+    interrupted: bool,
+}
+
+impl ResumeState {
+    fn track_id(&self) -> Option<Uuid> {
+        self.queue.tracks.get(self.queue.index).copied()
+    }
+
+    fn position(&self) -> Duration {
+        Duration::from_millis(self.position_ms)
+    }
+}
+
+#[derive(Debug, Clone)]
+struct LibraryNode {
+    id: String,
+    name: String,
+    /// Tracks in this folder and all of its sub-folders.
+    track_count: usize,
+    /// Combined length of those tracks whose duration is known.
+    total_duration: Duration,
+    children: BTreeMap<String, LibraryNode>,
+}
+
+impl LibraryNode {
+    fn new(id: String, name: String) -> Self {
+        Self {
+            id,
+            name,
+            track_count: 0,
+            total_duration: Duration::ZERO,
+            children: BTreeMap::new(),
+        }
+    }
+
+    fn contains(&self, id: &str) -> bool {
+        self.find(id).is_some()
+    }
+
+    fn find(&self, id: &str) -> Option<&LibraryNode> {
+        if self.id == id {
+            return Some(self);
+        }
+        self.children.values().find_map(|child| child.find(id))
+    }
+
+    fn collect_parent_ids(&self, ids: &mut HashSet<String>) {
+        if !self.children.is_empty() {
+            ids.insert(self.id.clone());
+            for child in self.children.values() {
+                child.collect_parent_ids(ids);
+            }
+        }
+    }
+
+    fn ensure_child(&mut self, id: String, name: String) -> &mut LibraryNode {
+        self.children
+            .entry(id.clone())
+            .or_insert_with(|| LibraryNode::new(id, name))
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+enum LibraryTab {
+    Tree,
+    Favorites,
+    Recent,
+    Lessons,
+}
+
+/// How a folder color is drawn.
+fn folder_color(color: FolderColor) -> Color {
+    match color {
+        FolderColor::Red => Color::from_rgb(0.9, 0.3, 0.3),
+        FolderColor::Orange => Color::from_rgb(0.95, 0.6, 0.2),
+        FolderColor::Yellow => Color::from_rgb(0.95, 0.85, 0.3),
+        FolderColor::Green => Color::from_rgb(0.4, 0.8, 0.4),
+        FolderColor::Blue => Color::from_rgb(0.35, 0.6, 0.95),
+        FolderColor::Purple => Color::from_rgb(0.7, 0.45, 0.9),
+        FolderColor::Grey => Color::from_rgb(0.6, 0.6, 0.6),
+    }
+}
+
+fn folder_color_name(color: FolderColor) -> &'static str {
+    match color {
+        FolderColor::Red => t!("Red"),
+        FolderColor::Orange => t!("Orange"),
+        FolderColor::Yellow => t!("Yellow"),
+        FolderColor::Green => t!("Green"),
+        FolderColor::Blue => t!("Blue"),
+        FolderColor::Purple => t!("Purple"),
+        FolderColor::Grey => t!("Grey"),
+    }
+}
+
+#[derive(Debug, Clone)]
+struct TreeItem {
+    id: String,
+    name: String,
+    depth: usize,
+    track_count: usize,
+    total_duration: Duration,
+    has_children: bool,
+    is_expanded: bool,
+}
+
+pub struct MidiPianoApp {
+    library: MidiLibrary,
+    device_manager: Arc<Mutex<MidiDeviceManager>>,
+    devices: Vec<DeviceChoice>,
+    selected_device: Option<Uuid>,
+    selected_song: Option<Uuid>,
+    search_query: String,
+    /// The search the library list is filtered by; trails `search_query`
+    /// while typing in a large library.
+    applied_search: SearchQuery,
+    search_edited_at: Option<Instant>,
+    title_ranks: RefCell<TitleRanks>,
+    midi_player: MidiPlayer,
+    player_events: UnboundedReceiver<PlayerEvent>,
+    current_sink: Option<SharedMidiSink>,
+    /// Sends through the current connection, or the last one after
+    /// playback ends.
+    sink_stats: Option<SinkStats>,
+    playback_phase: PlaybackPhase,
+    playback_progress: Option<PlaybackProgress>,
+    notifications: Notifications<ErrorAction>,
+    /// Window size outside the mini player.
+    full_window_size: Size,
+    is_scanning_devices: bool,
+    is_preparing_playback: bool,
+    /// Aborts the download or connection behind `is_preparing_playback`.
+    preparing_handle: Option<iced::task::Handle>,
+    user_prefs: UserPreferences,
+    active_tab: LibraryTab,
+    library_tree: LibraryNode,
+    /// Entries of the selected folder, worked out when the selection or the
+    /// library changes.
+    folder_entries: Vec<Uuid>,
+    selected_folder: Option<String>,
+    playlist_draft: PlaylistDraft,
+    playlist_builder: Option<PlaylistBuilder>,
+    /// Tree folder whose label editor is open.
+    editing_folder_label: Option<String>,
+    /// Ids of the playlists listing each song, kept up to date with the
+    /// preferences.
+    playlist_index: HashMap<Uuid, Vec<Uuid>>,
+    selected_playlist: Option<Uuid>,
+    tree_cache: Vec<TreeItem>,
+    tree_loading: bool,
+    tree_request_id: u64,
+    play_queue: Option<PlayQueue>,
+    /// What actually played, across queues and single plays, for Previous.
+    playback_history: PlaybackHistory,
+    show_settings: bool,
+    /// Bluetooth adapters found when the settings were last opened.
+    ble_adapters: Vec<String>,
+    inbox_scan_running: bool,
+    render_job: Option<RenderJob>,
+    library_load: Option<LibraryLoadJob>,
+    /// Why the last asset load did not finish, offered for a retry.
+    library_load_error: Option<String>,
+    folder_import: Option<FolderImportJob>,
+    is_rescanning_assets: bool,
+    remote_cache: RemoteCache,
+    remote_catalog: Vec<RemoteEntry>,
+    remote_catalog_draft: String,
+    webhook_draft: String,
+    is_loading_remote_catalog: bool,
+    practice_log: PracticeLog,
+    now_playing: Option<Uuid>,
+    playing_sequence: Option<Arc<MidiSequence>>,
+    /// The queue track after the playing one, parsed while this one plays.
+    preloaded: Option<PreloadedTrack>,
+    /// A connected song waiting for the user to decide how to play parts
+    /// the device cannot.
+    capability_warning: Option<CapabilityWarning>,
+    preview: Option<SongPreview>,
+    preview_requests: u64,
+    /// Works shown with their movements in lists.
+    expanded_works: HashSet<Uuid>,
+    pending_movement: Option<PendingMovement>,
+    /// Songs fitted to the device's capabilities this session, so seeking
+    /// and replays keep the remap.
+    remapped_songs: HashSet<Uuid>,
+    song_info: Option<SongInfoPanel>,
+    master_tempo_percent: u16,
+    master_transpose: i8,
+    /// Times in a row the playing song is played; back to one when it
+    /// finishes.
+    repeat_passes: u8,
+    /// Which of those passes is playing.
+    playback_pass: u8,
+    /// Seconds left before playback starts, while counting down.
+    countdown: Option<u8>,
+    /// The tempo playing now, as the player last reported it.
+    playback_bpm: Option<f64>,
+    show_queue: bool,
+    key_match_request: u64,
+    playback_clock: Option<Instant>,
+    score_offset_ms: i64,
+    active_session: Option<ActiveSession>,
+    stats_from: String,
+    stats_to: String,
+    stats_export_kind: StatsExportKind,
+    assignment_draft: AssignmentDraft,
+    debug_recorder: Option<MessageRecorder<ReplayMessage>>,
+    /// Set with `--timing`.
+    timing_capture: Option<Arc<TimingCapture>>,
+    onboarding: Option<Onboarding>,
+    monitor: Arc<MidiMonitor>,
+    monitor_pane: Option<MonitorPane>,
+    renaming: Option<(RenameTarget, String)>,
+    new_folder_name: String,
+    pending_resume: Option<ResumeState>,
+    resume_waiting_for_device: bool,
+    /// Scheduled starts up to this time have run, been cancelled or were
+    /// missed; only later ones are looked for.
+    schedule_checked_at: chrono::DateTime<chrono::Local>,
+    schedule_countdown: Option<ScheduleCountdown>,
+    sleep_timer_choice: SleepTimerChoice,
+    sleep_timer: Option<SleepTimer>,
+    send_trace: Option<Arc<SendTrace>>,
+    tag_filter: TagRule,
+    min_rating: RatingFilter,
+    favorites_only: bool,
+    /// Whether arrow keys, Enter and typing drive the library list.
+    library_focused: bool,
+    type_ahead: String,
+    type_ahead_at: Option<Instant>,
+    tag_draft: String,
+    resume_saved_at: Option<Duration>,
+    tray: Option<TrayHandle>,
+    tray_events: UnboundedReceiver<TrayCommand>,
+    /// What the tray menu was last told, to skip redundant updates.
+    tray_state: TrayState,
+    /// Track and position to pick up from after pausing.
+    paused_at: Option<(Uuid, Duration)>,
+    /// Where the seek bar is being dragged to, applied on release.
+    seek_preview: Option<Duration>,
+    /// Recent note activity per channel, from 0 to 1, fading every tick.
+    channel_levels: [f32; 16],
+    /// Song durations and the like; `None` until first read from disk.
+    library_metadata: Option<LibraryMetadata>,
+    metadata_refreshing: bool,
+    media_controls: Option<MediaControlsHandle>,
+    media_events: UnboundedReceiver<MediaCommand>,
+    /// What the desktop media controls were last told.
+    media_state: MediaState,
+    /// Handed to each control server started, which forwards client
+    /// commands through it.
+    remote_command_tx: UnboundedSender<ControlCommand>,
+    remote_events: UnboundedReceiver<ControlCommand>,
+    /// What remote control clients are told; they are sent every change.
+    remote_status: watch::Sender<ControlStatus>,
+    remote_control_handle: Option<iced::task::Handle>,
+    /// The running control server. A restart waits for it to end so the
+    /// port is free before binding again.
+    remote_control_server: Option<tokio::task::JoinHandle<()>>,
+    remote_port_draft: String,
+}
+
+impl MidiPianoApp {
+    fn init(debug_options: DebugOptions) -> (Self, Task<Message>) {
+        let (event_tx, event_rx) = mpsc::unbounded_channel();
+        let (tray_tx, tray_rx) = mpsc::unbounded_channel();
+        let (media_tx, media_rx) = mpsc::unbounded_channel();
+        let (remote_command_tx, remote_events) = mpsc::unbounded_channel();
+        let monitor = Arc::new(MidiMonitor::new(MONITOR_CAPACITY));
+        let mut device_manager = MidiDeviceManager::new();
+        device_manager
+            .null_sink()
+            .set_logging(debug_options.enabled);
+        device_manager.set_monitor(Some(monitor.clone()));
+        let timing_capture = debug_options.timing.then(|| Arc::new(TimingCapture::new()));
+        device_manager.set_timing_capture(timing_capture.clone());
+        let mut midi_player = MidiPlayer::new(event_tx);
+        midi_player.set_timing_capture(timing_capture.clone());
+        let device_manager = Arc::new(Mutex::new(device_manager));
+
+        let app = MidiPianoApp {
+            library: MidiLibrary::default(),
+            device_manager: device_manager.clone(),
+            devices: Vec::new(),
+            selected_device: None,
+            selected_song: None,
+            search_query: String::new(),
+            applied_search: SearchQuery::default(),
+            search_edited_at: None,
+            title_ranks: RefCell::default(),
+            midi_player,
+            player_events: event_rx,
+            current_sink: None,
+            sink_stats: None,
+            playback_phase: PlaybackPhase::Idle,
+            playback_progress: None,
+            notifications: Notifications::default(),
+            full_window_size: window::Settings::default().size,
+            is_scanning_devices: true,
+            is_preparing_playback: false,
+            preparing_handle: None,
+            user_prefs: UserPreferences::default(),
+            active_tab: LibraryTab::Tree,
+            library_tree: LibraryNode::new("root".into(), t!("Library").into()),
+            folder_entries: Vec::new(),
+            selected_folder: None,
+            playlist_draft: PlaylistDraft::default(),
+            playlist_builder: None,
+            editing_folder_label: None,
+            playlist_index: HashMap::new(),
+            selected_playlist: None,
+            tree_cache: Vec::new(),
+            tree_loading: false,
+            tree_request_id: 0,
+            play_queue: None,
+            playback_history: PlaybackHistory::default(),
+            show_settings: false,
+            ble_adapters: Vec::new(),
+            inbox_scan_running: false,
+            render_job: None,
+            folder_import: None,
+            is_rescanning_assets: false,
+            library_load: None,
+            library_load_error: None,
+            remote_cache: RemoteCache::new(REMOTE_CACHE_DIR),
+            remote_catalog: Vec::new(),
+            remote_catalog_draft: String::new(),
+            webhook_draft: String::new(),
+            is_loading_remote_catalog: false,
+            practice_log: PracticeLog::default(),
+            now_playing: None,
+            playing_sequence: None,
+            preloaded: None,
+            capability_warning: None,
+            preview: None,
+            preview_requests: 0,
+            expanded_works: HashSet::new(),
+            pending_movement: None,
+            remapped_songs: HashSet::new(),
+            song_info: None,
+            master_tempo_percent: 100,
+            master_transpose: 0,
+            repeat_passes: 1,
+            playback_pass: 1,
+            countdown: None,
+            playback_bpm: None,
+            show_queue: false,
+            key_match_request: 0,
+            playback_clock: None,
+            score_offset_ms: 0,
+            active_session: None,
+            stats_from: String::new(),
+            stats_to: String::new(),
+            stats_export_kind: StatsExportKind::SessionsCsv,
+            assignment_draft: AssignmentDraft::default(),
+            debug_recorder: debug_options
+                .enabled
+                .then(|| MessageRecorder::new(debug_options.capacity)),
+            timing_capture,
+            onboarding: None,
+            monitor,
+            monitor_pane: None,
+            renaming: None,
+            new_folder_name: String::new(),
+            pending_resume: None,
+            schedule_checked_at: chrono::Local::now(),
+            schedule_countdown: None,
+            sleep_timer_choice: SleepTimerChoice::Off,
+            sleep_timer: None,
+            send_trace: None,
+            resume_waiting_for_device: false,
+            tag_filter: TagRule {
+                match_all: true,
+                ..TagRule::default()
+            },
+            min_rating: RatingFilter(0),
+            favorites_only: false,
+            library_focused: false,
+            type_ahead: String::new(),
+            type_ahead_at: None,
+            tag_draft: String::new(),
+            resume_saved_at: None,
+            tray: None,
+            tray_events: tray_rx,
+            tray_state: TrayState::default(),
+            paused_at: None,
+            seek_preview: None,
+            channel_levels: [0.0; 16],
+            library_metadata: None,
+            metadata_refreshing: false,
+            media_controls: None,
+            media_events: media_rx,
+            media_state: MediaState::default(),
+            remote_command_tx,
+            remote_events,
+            remote_status: watch::Sender::new(ControlStatus::default()),
+            remote_control_handle: None,
+            remote_control_server: None,
+            remote_port_draft: DEFAULT_REMOTE_CONTROL_PORT.to_string(),
+        };
+
+        let mut app = app;
+        app.refresh_tree_cache();
+
+        let replay = match debug_options.replay.as_deref().map(Self::replay_task) {
+            Some(Ok(task)) => task,
+            Some(Err(err)) => {
+                app.notifications.error(t!(
+                    "Failed to load message replay: {err}",
+                    err = format!("{err:?}")
+                ));
+                Task::none()
+            }
+            None => Task::none(),
+        };
+
+        let load_library = app.load_library_task();
+        let task = Task::batch([
+            replay,
+            load_library,
+            Task::perform(
+                refresh_devices(device_manager.clone()),
+                Message::DevicesRefreshed,
+            ),
+            Task::perform(load_user_preferences(), Message::UserDataLoaded),
+            Task::perform(load_practice_log(), Message::PracticeLogLoaded),
+            Task::perform(load_resume_state(), Message::ResumeStateLoaded),
+            Self::ble_scan_task(device_manager.clone()),
+            Task::perform(tray::spawn(tray_tx), Message::TraySpawned),
+            Task::perform(
+                media_controls::spawn(media_tx),
+                Message::MediaControlsSpawned,
+            ),
+        ]);
+
+        (app, task)
+    }
+
+    /// Re-issues a recorded session with its original timing, relative to
+    /// app start.
+    fn replay_task(path: &std::path::Path) -> anyhow::Result<Task<Message>> {
+        let recording = debug::load_recording::<ReplayMessage>(path)?;
+        let steps: Vec<(u64, ReplayMessage)> = recording
+            .into_iter()
+            .filter_map(|logged| logged.replay.map(|replay| (logged.at_ms, replay)))
+            .collect();
+        log::info!(
+            "replaying {} message(s) from {}",
+            steps.len(),
+            path.display()
+        );
+        let replay = stream::unfold(
+            (steps.into_iter(), 0u64),
+            |(mut steps, last_ms)| async move {
+                let (at_ms, replay) = steps.next()?;
+                tokio::time::sleep(Duration::from_millis(at_ms.saturating_sub(last_ms))).await;
+                Some((replay.into_message(), (steps, at_ms)))
+            },
+        );
+        Ok(Task::run(replay, |message| message))
+    }
+
+    fn update(&mut self, message: Message) -> Task<Message> {
+        if let Some(recorder) = self.debug_recorder.as_mut()
+            && !matches!(
+                message,
+                Message::Tick | Message::AnimationFrame | Message::RenderUpdate(_)
+            )
+        {
+            recorder.record(
+                summarize_message(&message),
+                ReplayMessage::capture(&message),
+            );
+        }
+        match message {
+            Message::LibraryLoadUpdate(update) => match update {
+                LibraryLoadUpdate::Chunk(chunk, progress) => {
+                    let Some(job) = self.library_load.as_mut() else {
+                        return Task::none();
+                    };
+                    job.progress = progress;
+                    if chunk.entries().is_empty() {
+                        return Task::none();
+                    }
+                    self.library.append(*chunk);
+                    self.schedule_tree_rebuild()
+                }
+                LibraryLoadUpdate::Finished(result) => {
+                    if self.library_load.take().is_none() {
+                        return Task::none();
+                    }
+                    match result {
+                        Ok(()) => {
+                            self.add_remote_entries();
+                            self.notifications.info(t!("Library loaded"));
+                            Task::batch([
+                                self.schedule_tree_rebuild(),
+                                self.index_watch_library_task(),
+                                self.index_music_folders_task(),
+                            ])
+                        }
+                        Err(err) => {
+                            self.notifications
+                                .error(t!("Failed to load MIDI library: {err}", err = err));
+                            self.library_load_error = Some(err);
+                            Task::none()
+                        }
+                    }
+                }
+            },
+            Message::CancelLibraryLoad => {
+                if let Some(job) = self.library_load.take() {
+                    job.cancel.store(true, Ordering::Relaxed);
+                    job.handle.abort();
+                    self.notifications.info(t!("Library loading cancelled"));
+                    self.library_load_error = Some(t!("Cancelled").into());
+                }
+                Task::none()
+            }
+            Message::RetryLibraryLoad => self.load_library_task(),
+            Message::RescanAssets => {
+                self.is_rescanning_assets = true;
+                Task::perform(rescan_assets(), Message::AssetsRescanned)
+            }
+            Message::AssetsRescanned(result) => {
+                self.is_rescanning_assets = false;
+                match result {
+                    Ok((mut library, changes)) => {
+                        library.add_local_entries_from(&self.library);
+                        library.set_all_details(self.user_prefs.song_details.clone());
+                        self.library = library;
+                        self.add_remote_entries();
+                        self.notifications.info(if changes.is_empty() {
+                            t!("Assets are up to date").into()
+                        } else {
+                            t!(
+                                "Assets rescanned: {added} added, {removed} removed",
+                                added = changes.added.len(),
+                                removed = changes.removed.len()
+                            )
+                        });
+                        if self
+                            .selected_song
+                            .is_some_and(|id| self.library.get(&id).is_none())
+                        {
+                            self.selected_song = None;
+                        }
+                        return self.schedule_tree_rebuild();
+                    }
+                    Err(err) => {
+                        self.notifications
+                            .error(t!("Failed to rescan assets: {err}", err = err));
+                    }
+                }
+                Task::none()
+            }
+            Message::RemoteCatalogUrlChanged(value) => {
+                self.remote_catalog_draft = value;
+                Task::none()
+            }
+            Message::LoadRemoteCatalog => {
+                let url = self.remote_catalog_draft.trim();
+                self.user_prefs.remote_catalog_url = (!url.is_empty()).then(|| url.to_owned());
+                if self.user_prefs.remote_catalog_url.is_none() {
+                    self.remote_catalog.clear();
+                    self.add_remote_entries();
+                    self.notifications.info(t!("Remote catalog removed"));
+                    return Task::batch([
+                        self.save_preferences_task(),
+                        self.schedule_tree_rebuild(),
+                    ]);
+                }
+                Task::batch([self.save_preferences_task(), self.remote_catalog_task()])
+            }
+            Message::RemoteCatalogLoaded(result) => {
+                self.is_loading_remote_catalog = false;
+                match result {
+                    Ok(entries) => {
+                        self.notifications.info(t!(
+                            "Remote catalog lists {count} song(s)",
+                            count = entries.len()
+                        ));
+                        self.remote_catalog = entries;
+                        self.add_remote_entries();
+                        return self.schedule_tree_rebuild();
+                    }
+                    Err(err) => {
+                        self.notifications
+                            .error(t!("Failed to load remote catalog: {err}", err = err));
+                    }
+                }
+                Task::none()
+            }
+            Message::RemoteDownloaded(track_id, position, result) => {
+                self.is_preparing_playback = false;
+                self.preparing_handle = None;
+                self.playback_phase = PlaybackPhase::Idle;
+                match result {
+                    Ok(contents) => {
+                        return self.play_track_source(
+                            track_id,
+                            MidiSource::Memory(contents),
+                            position,
+                        );
+                    }
+                    Err(err) => {
+                        self.notifications
+                            .error(t!("Download failed: {err}", err = err));
+                    }
+                }
+                Task::none()
+            }
+            Message::ClearRemoteCache => {
+                let cache = self.remote_cache.clone();
+                Task::perform(
+                    async move {
+                        tokio::task::spawn_blocking(move || cache.clear())
+                            .await
+                            .map_err(|err| format!("cache task failed: {err:?}"))?
+                            .map_err(|err| format!("{err:?}"))
+                    },
+                    Message::RemoteCacheCleared,
+                )
+            }
+            Message::RemoteCacheCleared(result) => {
+                match result {
+                    Ok(removed) => {
+                        self.notifications
+                            .info(t!("Removed {removed} cached file(s)", removed = removed));
+                    }
+                    Err(err) => {
+                        self.notifications
+                            .error(t!("Failed to clear cache: {err}", err = err));
+                    }
+                }
+                Task::none()
+            }
+            Message::DevicesRefreshed(result) => {
+                self.is_scanning_devices = false;
+                match result {
+                    Ok(descriptors) => {
+                        self.devices = descriptors.iter().map(DeviceChoice::from).collect();
+                        if let Some(selected) = self.selected_device
+                            && !self.devices.iter().any(|choice| choice.id == selected)
+                        {
+                            self.selected_device = None;
+                        }
+                        self.select_default_device();
+                        self.devices.sort_by(|a, b| a.name.cmp(&b.name));
+                        self.notifications.info(t!("Devices updated"));
+                    }
+                    Err(err) => {
+                        self.notifications
+                            .error(t!("Failed to refresh devices: {err}", err = err));
+                    }
+                }
+                if std::mem::take(&mut self.resume_waiting_for_device) {
+                    return self.resume_playback(false);
+                }
+                Task::none()
+            }
+            Message::BleScanUpdate(result) => {
+                match result {
+                    Ok(new_devices) => {
+                        if !new_devices.is_empty() {
+                            let mut added_names = Vec::new();
+                            for descriptor in new_devices {
+                                if let Some(existing) = self
+                                    .devices
+                                    .iter_mut()
+                                    .find(|choice| choice.id == descriptor.info.id)
+                                {
+                                    existing.name = descriptor.info.name.clone();
+                                } else {
+                                    self.devices.push(DeviceChoice::from(&descriptor));
+                                    added_names.push(descriptor.info.name.clone());
+                                }
+                            }
+                            if !added_names.is_empty() {
+                                self.select_default_device();
+                                self.devices.sort_by(|a, b| a.name.cmp(&b.name));
+                                self.notifications.info(t!(
+                                    "New BLE devices: {names}",
+                                    names = added_names.join(", ")
+                                ));
+                            }
+                        }
+                    }
+                    Err(err) => {
+                        self.notifications
+                            .error(t!("BLE scan failed: {err}", err = err));
+                    }
+                }
+                Task::none()
+            }
+            Message::UserDataLoaded(result) => {
+                match result {
+                    Ok(prefs) => {
+                        self.user_prefs = prefs;
+                        i18n::set_language(self.user_prefs.language);
+                        self.library
+                            .set_all_details(self.user_prefs.song_details.clone());
+                        self.refresh_tree_cache();
+                        self.refresh_playlist_index();
+                        self.notifications.info(t!("Preferences loaded"));
+                        if !self.user_prefs.onboarding_complete {
+                            self.onboarding = Some(Onboarding::new());
+                        }
+                        self.remote_catalog_draft = self
+                            .user_prefs
+                            .remote_catalog_url
+                            .clone()
+                            .unwrap_or_default();
+                        self.webhook_draft =
+                            self.user_prefs.webhook_url.clone().unwrap_or_default();
+                        self.open_send_trace();
+                        let sync_devices = if self.user_prefs.ble_adapter.is_some() {
+                            self.rescan_ble_adapter_task()
+                        } else {
+                            self.sync_device_settings_task()
+                        };
+                        return Task::batch([
+                            self.resize_window_task(),
+                            self.schedule_tree_rebuild(),
+                            sync_devices,
+                            self.virtual_port_task(),
+                            self.restart_remote_control(),
+                            self.index_watch_library_task(),
+                            self.index_music_folders_task(),
+                            self.remote_catalog_task(),
+                        ]);
+                    }
+                    Err(err) => {
+                        self.notifications
+                            .error(t!("Failed to load preferences: {err}", err = err));
+                    }
+                }
+                Task::none()
+            }
+            Message::PreferencesSaved(result) => {
+                match result {
+                    Ok(()) => {
+                        self.notifications.info(t!("Preferences saved"));
+                    }
+                    Err(err) => {
+                        self.notifications
+                            .error(t!("Failed to save preferences: {err}", err = err));
+                    }
+                }
+                Task::none()
+            }
+            Message::PracticeLogLoaded(result) => {
+                match result {
+                    Ok(log) => {
+                        // Sessions recorded before the log finished loading are kept.
+                        let recorded = std::mem::replace(&mut self.practice_log, log);
+                        self.practice_log.sessions.extend(recorded.sessions);
+                    }
+                    Err(err) => {
+                        self.notifications
+                            .error(t!("Failed to load practice statistics: {err}", err = err));
+                    }
+                }
+                Task::none()
+            }
+            Message::PracticeLogSaved(result) => {
+                if let Err(err) = result {
+                    self.notifications
+                        .error(t!("Failed to save practice statistics: {err}", err = err));
+                }
+                Task::none()
+            }
+            Message::WebhookUrlChanged(value) => {
+                self.webhook_draft = value;
+                Task::none()
+            }
+            Message::SaveWebhookUrl => {
+                let url = self.webhook_draft.trim();
+                self.user_prefs.webhook_url = (!url.is_empty()).then(|| url.to_owned());
+                self.notifications
+                    .info(if self.user_prefs.webhook_url.is_some() {
+                        t!("Webhook saved")
+                    } else {
+                        t!("Webhook removed")
+                    });
+                self.save_preferences_task()
+            }
+            Message::SendTestWebhook => {
+                let url = self.webhook_draft.trim().to_owned();
+                if url.is_empty() {
+                    return Task::none();
+                }
+                let now = chrono::Utc::now();
+                let event = NowPlayingEvent {
+                    event: NowPlayingKind::Started,
+                    track_id: Uuid::nil(),
+                    track_name: t!("Test event").to_owned(),
+                    duration_secs: 0.0,
+                    played_secs: 0.0,
+                    device: self.selected_device_name(),
+                    started_at: now,
+                    sent_at: now,
+                };
+                Task::perform(post_webhook(url, event), Message::WebhookTested)
+            }
+            Message::WebhookSent(result) => {
+                // Reported quietly so an offline server does not interrupt
+                // every song.
+                if let Err(err) = result {
+                    log::warn!("{err}");
+                }
+                Task::none()
+            }
+            Message::WebhookTested(result) => {
+                match result {
+                    Ok(()) => self.notifications.info(t!("Test event delivered")),
+                    Err(err) => self
+                        .notifications
+                        .error(t!("Test event failed: {err}", err = err)),
+                }
+                Task::none()
+            }
+            Message::TreeDataLoaded { request_id, tree } => {
+                if request_id == self.tree_request_id {
+                    self.tree_loading = false;
+                    self.apply_tree_data(tree);
+                }
+                Task::none()
+            }
+            Message::TreeDataFailed { request_id, error } => {
+                if request_id == self.tree_request_id {
+                    self.tree_loading = false;
+                    self.notifications
+                        .error(t!("Failed to update library tree: {error}", error = error));
+                }
+                Task::none()
+            }
+            Message::ResumeStateLoaded(result) => {
+                match result {
+                    Ok(state) => self.pending_resume = state,
+                    Err(err) => log::warn!("failed to load resume state: {err}"),
+                }
+                Task::none()
+            }
+            Message::ResumeStateSaved(result) => {
+                if let Err(err) = result {
+                    log::warn!("failed to save resume state: {err}");
+                }
+                Task::none()
+            }
+            Message::ResumePlayback => self.resume_playback(true),
+            Message::ResetInterruptedDevice => {
+                let Some(state) = self.pending_resume.as_mut() else {
+                    return Task::none();
+                };
+                state.interrupted = false;
+                Task::perform(
+                    send_panic(None, state.device_id, self.device_manager.clone()),
+                    Message::PanicSent,
+                )
+            }
+            Message::DismissResume => {
+                self.pending_resume = None;
+                self.resume_waiting_for_device = false;
+                Task::perform(save_resume_state(None), Message::ResumeStateSaved)
+            }
+            Message::AddSchedule => {
+                let mut schedule = Schedule::new();
+                schedule.playlist_id = self.selected_playlist;
+                schedule.device_id = self.selected_device;
+                self.user_prefs.schedules.push(schedule);
+                self.save_preferences_task()
+            }
+            Message::RemoveSchedule(id) => {
+                self.user_prefs
+                    .schedules
+                    .retain(|schedule| schedule.id != id);
+                self.schedules_changed()
+            }
+            Message::ScheduleEnabledToggled(id, enabled) => {
+                self.update_schedule(id, |schedule| schedule.enabled = enabled)
+            }
+            Message::ScheduleDayToggled(id, day) => self.update_schedule(id, |schedule| {
+                schedule.days[day] = !schedule.days[day];
+            }),
+            Message::ScheduleTimeSelected(id, time) => {
+                self.update_schedule(id, |schedule| schedule.time = time)
+            }
+            Message::SchedulePlaylistSelected(id, choice) => {
+                self.update_schedule(id, |schedule| schedule.playlist_id = Some(choice.id))
+            }
+            Message::ScheduleDeviceSelected(id, choice) => {
+                self.update_schedule(id, |schedule| schedule.device_id = Some(choice.id))
+            }
+            Message::SleepTimerSelected(choice) => {
+                self.midi_player.set_level(100);
+                self.sleep_timer_choice = choice;
+                self.sleep_timer = match choice {
+                    SleepTimerChoice::Off => None,
+                    SleepTimerChoice::Minutes(minutes) => Some(SleepTimer::Until(
+                        Instant::now() + Duration::from_secs(minutes * 60),
+                    )),
+                    SleepTimerChoice::Tracks(tracks) => Some(SleepTimer::Tracks(tracks)),
+                    SleepTimerChoice::EndOfQueue => Some(SleepTimer::Tracks(
+                        self.play_queue
+                            .as_ref()
+                            .map_or(1, |queue| queue.tracks.len().saturating_sub(queue.index))
+                            .max(1) as u32,
+                    )),
+                };
+                Task::none()
+            }
+            Message::StartScheduledNow => self.start_scheduled(),
+            Message::CancelScheduled => {
+                if let Some(countdown) = self.schedule_countdown.take() {
+                    self.schedule_checked_at = countdown.due;
+                    self.notifications.info(t!("Scheduled playback cancelled"));
+                }
+                Task::none()
+            }
+            Message::RefreshDevices => {
+                self.is_scanning_devices = true;
+                Task::perform(
+                    refresh_devices(self.device_manager.clone()),
+                    Message::DevicesRefreshed,
+                )
+            }
+            Message::DeviceSelected(id) => {
+                self.selected_device = Some(id);
+                Task::none()
+            }
+            Message::SongSelected(id) => {
+                self.selected_song = Some(id);
+                self.library_focused = true;
+                Task::none()
+            }
+            Message::SearchChanged(query) => {
+                self.search_query = query;
+                self.library_focused = false;
+                if self.library.entries().len() < SEARCH_DEBOUNCE_MIN_ENTRIES {
+                    self.apply_search();
+                } else {
+                    self.search_edited_at = Some(Instant::now());
+                }
+                Task::none()
+            }
+            Message::LibraryKey(key) => self.handle_library_key(key),
+            Message::SwitchTab(tab) => {
+                if self.active_tab != tab {
+                    self.active_tab = tab;
+                    if matches!(self.active_tab, LibraryTab::Tree) {
+                        if self.selected_folder.is_none() {
+                            self.selected_folder = Some("root".into());
+                        }
+                        if self.tree_cache.is_empty() && !self.tree_loading {
+                            return self.schedule_tree_rebuild();
+                        }
+                    }
+                }
+                Task::none()
+            }
+            Message::ToggleFolder(folder_id) => {
+                self.selected_folder = Some(folder_id.clone());
+                let expanded = &mut self.user_prefs.expanded_folders;
+                if !expanded.remove(&folder_id) {
+                    expanded.insert(folder_id);
+                }
+                self.refresh_tree_cache();
+                self.refresh_folder_entries();
+                self.save_preferences_task()
+            }
+            Message::ExpandAllFolders => {
+                self.library_tree
+                    .collect_parent_ids(&mut self.user_prefs.expanded_folders);
+                self.refresh_tree_cache();
+                self.save_preferences_task()
+            }
+            Message::CollapseAllFolders => {
+                self.user_prefs.expanded_folders.clear();
+                self.refresh_tree_cache();
+                self.save_preferences_task()
+            }
+            Message::SelectFolder(folder_id) => {
+                if self.library_tree.contains(&folder_id) {
+                    self.selected_folder = Some(folder_id);
+                    self.refresh_folder_entries();
+                }
+                Task::none()
+            }
+            Message::SetRating(id, rating) => {
+                if rating == 0 {
+                    self.user_prefs.ratings.remove(&id);
+                } else if rating <= 5 {
+                    self.user_prefs.ratings.insert(id, rating);
+                }
+                self.notifications.info(t!("Rating updated"));
+                Task::batch([
+                    self.save_preferences_task(),
+                    self.regroup_tree_task(TreeGrouping::Rating),
+                ])
+            }
+            Message::ToggleFavorite(id) => {
+                if !self.user_prefs.favorites.remove(&id) {
+                    self.user_prefs.favorites.insert(id);
+                    self.notifications.info(t!("Added to favorites"));
+                } else {
+                    self.notifications.info(t!("Removed from favorites"));
+                }
+                self.save_preferences_task()
+            }
+            Message::PlaylistDraftAdd(id) => {
+                if self.library.get(&id).is_none() {
+                    self.notifications
+                        .error(t!("Selected track is not available"));
+                } else if !self.playlist_draft.tracks.contains(&id) {
+                    self.playlist_draft.tracks.push(id);
+                    self.notifications.info(t!("Track added to draft playlist"));
+                }
+                Task::none()
+            }
+            Message::PlaylistDraftRemove(index) => {
+                if index < self.playlist_draft.tracks.len() {
+                    self.playlist_draft.tracks.remove(index);
+                    self.notifications
+                        .info(t!("Track removed from draft playlist"));
+                }
+                Task::none()
+            }
+            Message::PlaylistDraftNameChanged(name) => {
+                self.playlist_draft.name = name;
+                Task::none()
+            }
+            Message::PlaylistDraftAddAll(ids) => {
+                for id in ids {
+                    if self.library.get(&id).is_some() && !self.playlist_draft.tracks.contains(&id)
+                    {
+                        self.playlist_draft.tracks.push(id);
+                    }
+                }
+                Task::none()
+            }
+            Message::PlaylistDraftRemoveAll => {
+                self.playlist_draft.tracks.clear();
+                Task::none()
+            }
+            Message::PlaylistDraftMove(from, to) => {
+                let tracks = &mut self.playlist_draft.tracks;
+                if from < tracks.len() && to < tracks.len() {
+                    let id = tracks.remove(from);
+                    tracks.insert(to, id);
+                    if let Some(builder) = self.playlist_builder.as_mut()
+                        && builder.dragging == Some(from)
+                    {
+                        builder.dragging = Some(to);
+                    }
+                }
+                Task::none()
+            }
+            Message::PlaylistDraftClear => {
+                self.playlist_draft = PlaylistDraft::default();
+                self.notifications.info(t!("Playlist draft cleared"));
+                Task::none()
+            }
+            Message::TogglePlaylistBuilder => {
+                self.playlist_builder = match self.playlist_builder {
+                    Some(_) => None,
+                    None => Some(PlaylistBuilder::default()),
+                };
+                Task::none()
+            }
+            Message::PlaylistBuilderQueryChanged(query) => {
+                if let Some(builder) = self.playlist_builder.as_mut() {
+                    builder.query = query;
+                }
+                Task::none()
+            }
+            Message::PlaylistDragStarted(index) => {
+                if let Some(builder) = self.playlist_builder.as_mut() {
+                    builder.dragging = Some(index);
+                }
+                Task::none()
+            }
+            Message::PlaylistDragEnded => {
+                if let Some(builder) = self.playlist_builder.as_mut() {
+                    builder.dragging = None;
+                }
+                Task::none()
+            }
+            Message::PlaylistDraftSave => {
+                if self.playlist_draft.tracks.is_empty() {
+                    self.notifications
+                        .error(t!("Add at least one track before saving a playlist"));
+                    return Task::none();
+                }
+                let name = if self.playlist_draft.name.trim().is_empty() {
+                    t!(
+                        "Playlist {number}",
+                        number = self.user_prefs.playlists.len() + 1
+                    )
+                } else {
+                    self.playlist_draft.name.trim().to_owned()
+                };
+                let tracks = self.playlist_draft.tracks.clone();
+                if let Some(active_id) = self.selected_playlist {
+                    if let Some(existing) = self
+                        .user_prefs
+                        .playlists
+                        .iter_mut()
+                        .find(|playlist| playlist.id == active_id)
+                    {
+                        existing.name = name.clone();
+                        existing.tracks = tracks.clone();
+                        // Saving a draft pins the current matches.
+                        existing.rule = None;
+                        self.notifications
+                            .info(t!("Playlist '{name}' updated", name = existing.name));
+                    } else {
+                        let playlist = Playlist::new(name.clone(), tracks);
+                        self.selected_playlist = Some(playlist.id);
+                        self.user_prefs.playlists.push(playlist);
+                        self.notifications
+                            .info(t!("Playlist '{name}' created", name = name));
+                    }
+                } else {
+                    let playlist = Playlist::new(name.clone(), tracks);
+                    self.selected_playlist = Some(playlist.id);
+                    self.user_prefs.playlists.push(playlist);
+                    self.notifications
+                        .info(t!("Playlist '{name}' created", name = name));
+                }
+                self.playlist_draft.name = name;
+                self.save_preferences_task()
+            }
+            Message::PlaylistSelect(selection) => {
+                self.selected_playlist = selection;
+                Task::none()
+            }
+            Message::RemoveFromPlaylist(playlist_id, track_id) => {
+                let Some(playlist) = self
+                    .user_prefs
+                    .playlists
+                    .iter_mut()
+                    .find(|playlist| playlist.id == playlist_id)
+                else {
+                    return Task::none();
+                };
+                playlist.tracks.retain(|id| *id != track_id);
+                self.notifications
+                    .info(t!("Removed from '{name}'", name = playlist.name.clone()));
+                self.save_preferences_task()
+            }
+            Message::EditFolderLabel(folder_id) => {
+                self.editing_folder_label = folder_id;
+                Task::none()
+            }
+            Message::FolderColorSelected(folder_id, color) => {
+                self.update_folder_label(folder_id, |label| label.color = color)
+            }
+            Message::FolderEmojiChanged(folder_id, emoji) => {
+                self.update_folder_label(folder_id, |label| {
+                    label.emoji = emoji
+                        .trim()
+                        .chars()
+                        .take(FolderLabel::MAX_EMOJI_CHARS)
+                        .collect();
+                })
+            }
+            Message::PlaylistDelete(id) => {
+                let before = self.user_prefs.playlists.len();
+                self.user_prefs
+                    .playlists
+                    .retain(|playlist| playlist.id != id);
+                if before != self.user_prefs.playlists.len() {
+                    if self.selected_playlist == Some(id) {
+                        self.selected_playlist = None;
+                    }
+                    if let Some(queue) = &self.play_queue
+                        && matches!(queue.mode, QueueMode::Playlist(queue_id) if queue_id == id)
+                    {
+                        self.play_queue = None;
+                    }
+                    self.notifications.info(t!("Playlist deleted"));
+                    self.save_preferences_task()
+                } else {
+                    Task::none()
+                }
+            }
+            Message::PlaylistLoadToDraft(id) => {
+                if let Some(playlist) = self
+                    .user_prefs
+                    .playlists
+                    .iter()
+                    .find(|playlist| playlist.id == id)
+                    .cloned()
+                {
+                    self.playlist_draft.name = playlist.name.clone();
+                    self.playlist_draft.tracks = self.playlist_tracks(&playlist);
+                    self.selected_playlist = Some(id);
+                    self.notifications.info(t!("Loaded playlist into draft"));
+                }
+                Task::none()
+            }
+            Message::PlaylistMove(id, direction) => {
+                let playlists = &mut self.user_prefs.playlists;
+                let Some(index) = playlists.iter().position(|playlist| playlist.id == id) else {
+                    return Task::none();
+                };
+                let folder = playlists[index].folder;
+                let neighbour = if direction < 0 {
+                    playlists[..index]
+                        .iter()
+                        .rposition(|playlist| playlist.folder == folder)
+                } else {
+                    playlists[index + 1..]
+                        .iter()
+                        .position(|playlist| playlist.folder == folder)
+                        .map(|offset| index + 1 + offset)
+                };
+                match neighbour {
+                    Some(neighbour) => {
+                        playlists.swap(index, neighbour);
+                        self.save_preferences_task()
+                    }
+                    None => Task::none(),
+                }
+            }
+            Message::PlaylistDuplicate(id) => {
+                let playlists = &mut self.user_prefs.playlists;
+                let Some(index) = playlists.iter().position(|playlist| playlist.id == id) else {
+                    return Task::none();
+                };
+                let mut copy = playlists[index].clone();
+                copy.id = Uuid::new_v4();
+                copy.name = format!("{} (copy)", copy.name);
+                self.notifications
+                    .info(t!("Created '{name}'", name = copy.name));
+                self.selected_playlist = Some(copy.id);
+                playlists.insert(index + 1, copy);
+                self.save_preferences_task()
+            }
+            Message::PlaylistFolderAssigned(id, choice) => {
+                let Some(index) = self
+                    .user_prefs
+                    .playlists
+                    .iter()
+                    .position(|playlist| playlist.id == id)
+                else {
+                    return Task::none();
+                };
+                // Moved playlists go to the end of their new folder.
+                let mut playlist = self.user_prefs.playlists.remove(index);
+                playlist.folder = choice.id;
+                self.user_prefs.playlists.push(playlist);
+                self.save_preferences_task()
+            }
+            Message::RenameStart(target) => {
+                let current = match target {
+                    RenameTarget::Playlist(id) => self
+                        .user_prefs
+                        .playlists
+                        .iter()
+                        .find(|playlist| playlist.id == id)
+                        .map(|playlist| playlist.name.clone()),
+                    RenameTarget::Folder(id) => self
+                        .user_prefs
+                        .playlist_folder(Some(id))
+                        .map(|folder| folder.name.clone()),
+                };
+                self.renaming = current.map(|name| (target, name));
+                Task::none()
+            }
+            Message::RenameChanged(name) => {
+                if let Some((_, draft)) = self.renaming.as_mut() {
+                    *draft = name;
+                }
+                Task::none()
+            }
+            Message::RenameCommit => {
+                let Some((target, name)) = self.renaming.take() else {
+                    return Task::none();
+                };
+                let name = name.trim().to_owned();
+                if name.is_empty() {
+                    self.notifications.error(t!("Names cannot be empty"));
+                    return Task::none();
+                }
+                match target {
+                    RenameTarget::Playlist(id) => {
+                        if let Some(playlist) = self
+                            .user_prefs
+                            .playlists
+                            .iter_mut()
+                            .find(|playlist| playlist.id == id)
+                        {
+                            playlist.name = name.clone();
+                        }
+                        if self.selected_playlist == Some(id) {
+                            self.playlist_draft.name = name;
+                        }
+                    }
+                    RenameTarget::Folder(id) => {
+                        if let Some(folder) = self
+                            .user_prefs
+                            .playlist_folders
+                            .iter_mut()
+                            .find(|folder| folder.id == id)
+                        {
+                            folder.name = name;
+                        }
+                    }
+                }
+                self.save_preferences_task()
+            }
+            Message::RenameCancel => {
+                self.renaming = None;
+                Task::none()
+            }
+            Message::NewFolderNameChanged(name) => {
+                self.new_folder_name = name;
+                Task::none()
+            }
+            Message::CreatePlaylistFolder => {
+                let name = self.new_folder_name.trim();
+                if name.is_empty() {
+                    return Task::none();
+                }
+                self.user_prefs.playlist_folders.push(PlaylistFolder {
+                    id: Uuid::new_v4(),
+                    name: name.to_owned(),
+                });
+                self.new_folder_name.clear();
+                self.save_preferences_task()
+            }
+            Message::PlaylistFolderMove(id, direction) => {
+                let folders = &mut self.user_prefs.playlist_folders;
+                let Some(index) = folders.iter().position(|folder| folder.id == id) else {
+                    return Task::none();
+                };
+                let target = index as isize + direction as isize;
+                if target < 0 || target as usize >= folders.len() {
+                    return Task::none();
+                }
+                folders.swap(index, target as usize);
+                self.save_preferences_task()
+            }
+            Message::PlaylistFolderDelete(id) => {
+                self.user_prefs
+                    .playlist_folders
+                    .retain(|folder| folder.id != id);
+                for playlist in &mut self.user_prefs.playlists {
+                    if playlist.folder == Some(id) {
+                        playlist.folder = None;
+                    }
+                }
+                self.notifications
+                    .info(t!("Folder removed; its playlists are now unfiled"));
+                self.save_preferences_task()
+            }
+            Message::GenerateRandomPlaylist => {
+                let mut rng = rand::rng();
+                let selection: Vec<Uuid> = self
+                    .library
+                    .entries()
+                    .iter()
+                    .map(|entry| entry.id)
+                    .choose_multiple(&mut rng, 50);
+                self.playlist_draft.name = t!("Random 50").into();
+                self.playlist_draft.tracks = selection;
+                self.notifications
+                    .info(t!("Generated random playlist draft"));
+                Task::none()
+            }
+            Message::StartPlayback(id) => self.start_single_track(id),
+            Message::PlayWork(id) => self.play_work(id),
+            Message::ToggleWork(id) => {
+                if !self.expanded_works.remove(&id) {
+                    self.expanded_works.insert(id);
+                }
+                Task::none()
+            }
+            Message::SaveDraftAsWork => {
+                let mut seen = HashSet::new();
+                let movements: Vec<Uuid> = self
+                    .playlist_draft
+                    .tracks
+                    .iter()
+                    .copied()
+                    .filter(|id| seen.insert(*id))
+                    .collect();
+                if movements.len() < 2 {
+                    self.notifications
+                        .error(t!("Add at least two movements before saving a work"));
+                    return Task::none();
+                }
+                let name = if self.playlist_draft.name.trim().is_empty() {
+                    t!("Work {number}", number = self.user_prefs.works.len() + 1)
+                } else {
+                    self.playlist_draft.name.trim().to_owned()
+                };
+                works::insert(
+                    &mut self.user_prefs.works,
+                    Work::new(name.clone(), movements),
+                );
+                self.notifications
+                    .info(t!("Work '{name}' created", name = name));
+                self.save_preferences_task()
+            }
+            Message::WorkNameChanged(id, name) => {
+                let Some(work) = self.user_prefs.works.iter_mut().find(|work| work.id == id) else {
+                    return Task::none();
+                };
+                work.name = name;
+                self.save_preferences_task()
+            }
+            Message::WorkPauseSelected(id, choice) => {
+                let Some(work) = self.user_prefs.works.iter_mut().find(|work| work.id == id) else {
+                    return Task::none();
+                };
+                work.pause_secs = choice.0;
+                self.save_preferences_task()
+            }
+            Message::UngroupWork(id) => {
+                self.user_prefs.works.retain(|work| work.id != id);
+                self.expanded_works.remove(&id);
+                self.save_preferences_task()
+            }
+            Message::PreviewSong(id) => {
+                if self
+                    .preview
+                    .as_ref()
+                    .is_some_and(|preview| preview.track_id == id)
+                {
+                    self.stop_preview();
+                    return Task::none();
+                }
+                self.preview_song(id)
+            }
+            Message::PreviewFinished(request, result) => {
+                if self
+                    .preview
+                    .as_ref()
+                    .is_none_or(|preview| preview.request != request)
+                {
+                    return Task::none();
+                }
+                self.preview = None;
+                if let Err(err) = result {
+                    self.notifications
+                        .error(t!("Preview failed: {err}", err = err));
+                }
+                Task::none()
+            }
+            Message::PlayNext(id) => self.play_next(id),
+            Message::AddToQueue(id) => self.enqueue_track(id),
+            Message::ToggleQueuePanel => {
+                self.show_queue = !self.show_queue;
+                Task::none()
+            }
+            Message::TagDraftChanged(value) => {
+                self.tag_draft = value;
+                Task::none()
+            }
+            Message::AddTag(id) => {
+                let Some(tag) = normalize_tag(&self.tag_draft) else {
+                    return Task::none();
+                };
+                self.tag_draft.clear();
+                if self.user_prefs.tags.entry(id).or_default().insert(tag) {
+                    Task::batch([
+                        self.save_preferences_task(),
+                        self.regroup_tree_task(TreeGrouping::Tag),
+                    ])
+                } else {
+                    Task::none()
+                }
+            }
+            Message::RemoveTag(id, tag) => {
+                let Some(tags) = self.user_prefs.tags.get_mut(&id) else {
+                    return Task::none();
+                };
+                tags.remove(&tag);
+                if tags.is_empty() {
+                    self.user_prefs.tags.remove(&id);
+                }
+                if !self.user_prefs.all_tags().contains(&tag) {
+                    self.tag_filter.tags.remove(&tag);
+                }
+                Task::batch([
+                    self.save_preferences_task(),
+                    self.regroup_tree_task(TreeGrouping::Tag),
+                ])
+            }
+            Message::TagFilterToggled(tag) => {
+                if !self.tag_filter.tags.remove(&tag) {
+                    self.tag_filter.tags.insert(tag);
+                }
+                Task::none()
+            }
+            Message::TagFilterMatchAllToggled(match_all) => {
+                self.tag_filter.match_all = match_all;
+                Task::none()
+            }
+            Message::MinRatingSelected(rating) => {
+                self.min_rating = rating;
+                Task::none()
+            }
+            Message::FavoritesOnlyToggled(enabled) => {
+                self.favorites_only = enabled;
+                Task::none()
+            }
+            Message::ClearLibraryFilters => {
+                self.tag_filter.tags.clear();
+                self.min_rating = RatingFilter(0);
+                self.favorites_only = false;
+                Task::none()
+            }
+            Message::CreateSmartPlaylist => {
+                if self.tag_filter.tags.is_empty() {
+                    self.notifications
+                        .error(t!("Select one or more tags to build a smart playlist"));
+                    return Task::none();
+                }
+                let name = match self.playlist_draft.name.trim() {
+                    "" => self.tag_filter.to_string(),
+                    name => name.to_owned(),
+                };
+                let mut playlist = Playlist::new(name, Vec::new());
+                playlist.rule = Some(self.tag_filter.clone());
+                playlist.order = self.user_prefs.library_sort;
+                self.notifications
+                    .info(t!("Smart playlist '{name}' created", name = playlist.name));
+                self.selected_playlist = Some(playlist.id);
+                self.user_prefs.playlists.push(playlist);
+                self.save_preferences_task()
+            }
+            Message::LibrarySortSelected(sort) => {
+                self.user_prefs.library_sort = sort;
+                self.save_preferences_task()
+            }
+            Message::TreeGroupingSelected(grouping) => {
+                if self.user_prefs.tree_grouping == grouping {
+                    return Task::none();
+                }
+                self.user_prefs.tree_grouping = grouping;
+                self.selected_folder = Some("root".into());
+                Task::batch([self.save_preferences_task(), self.schedule_tree_rebuild()])
+            }
+            Message::ClearRecentlyPlayed => {
+                self.user_prefs.recently_played.clear();
+                self.save_preferences_task()
+            }
+            Message::PlayFavorites { shuffle } => self.play_favorites(shuffle),
+            Message::PlayFolder { id, shuffle } => self.play_folder(id, shuffle),
+            Message::PlayPlaylist { id, shuffle } => self.play_playlist(id, shuffle, None),
+            Message::PlaylistDeviceSelected(id, device_id) => {
+                self.update_playlist_defaults(id, |defaults| defaults.device_id = device_id)
+            }
+            Message::PlaylistShuffleToggled(id, shuffle) => {
+                self.update_playlist_defaults(id, |defaults| defaults.shuffle = shuffle)
+            }
+            Message::PlaylistTempoStep(id, delta) => {
+                self.update_playlist_defaults(id, |defaults| {
+                    let tempo = defaults
+                        .tempo_percent
+                        .unwrap_or(100)
+                        .saturating_add_signed(delta)
+                        .clamp(
+                            PlaybackAdjustments::MIN_TEMPO_PERCENT,
+                            PlaybackAdjustments::MAX_TEMPO_PERCENT,
+                        );
+                    defaults.tempo_percent = (tempo != 100).then_some(tempo);
+                })
+            }
+            Message::PlaylistTransposeStep(id, delta) => {
+                self.update_playlist_defaults(id, |defaults| {
+                    defaults.transpose = defaults.transpose.saturating_add(delta).clamp(-24, 24);
+                })
+            }
+            Message::NextTrack => {
+                if let Some(track) = self.resume_from_history() {
+                    self.play_track(track)
+                } else if let Some(next_id) = self.advance_queue(true) {
+                    let sink = self.current_sink.clone();
+                    self.play_queued_track(next_id, sink)
+                } else {
+                    Task::none()
+                }
+            }
+            Message::PrevTrack => {
+                if let Some(prev_id) = self.playback_history.back() {
+                    self.selected_song = Some(prev_id);
+                    self.play_track(prev_id)
+                } else if self.playback_history.is_stepping_back() {
+                    self.notifications.info(t!("Already at the beginning"));
+                    Task::none()
+                } else if let Some(prev_id) = self.advance_queue(false) {
+                    self.play_track(prev_id)
+                } else {
+                    Task::none()
+                }
+            }
+            Message::PlayPressed => {
+                if let Some(id) = self.selected_song {
+                    self.start_single_track(id)
+                } else {
+                    self.notifications.error(t!("Select a MIDI file to play"));
+                    Task::none()
+                }
+            }
+            Message::PlaybackPrepared(result) => {
+                self.is_preparing_playback = false;
+                self.preparing_handle = None;
+                match result {
+                    Ok(prepared) => return self.start_prepared(prepared),
+                    Err(err) => {
+                        self.show_error(t!("Failed to prepare playback"), err);
+                        self.playback_phase = PlaybackPhase::Idle;
+                        self.playback_progress = None;
+                    }
+                }
+                Task::none()
+            }
+            Message::NextTrackPreloaded(result) => {
+                match result {
+                    Ok(preloaded) => self.preloaded = Some(preloaded),
+                    // Playing the track will load it again and report the problem.
+                    Err(err) => log::debug!("could not preload the next track: {err}"),
+                }
+                Task::none()
+            }
+            Message::PlayPause => {
+                if matches!(self.playback_phase, PlaybackPhase::Playing)
+                    && let (Some(id), Some(progress)) = (self.now_playing, &self.playback_progress)
+                {
+                    // Pausing stops the player but keeps the queue, so
+                    // playing again carries on from here.
+                    let position = progress.elapsed;
+                    self.paused_at = Some((id, position));
+                    self.midi_player.stop();
+                    self.playback_phase = PlaybackPhase::Idle;
+                    // Saved again so a crash while paused is not taken for
+                    // one mid-song.
+                    self.save_resume_task(position).unwrap_or_else(Task::none)
+                } else if let Some((id, position)) = self.paused_at.take() {
+                    self.play_track_from(id, position)
+                } else {
+                    self.update(Message::PlayPressed)
+                }
+            }
+            Message::SeekChanged(seconds) => {
+                if matches!(self.playback_phase, PlaybackPhase::Playing)
+                    && let Ok(position) = Duration::try_from_secs_f32(seconds.max(0.0))
+                {
+                    self.seek_preview = Some(position);
+                }
+                Task::none()
+            }
+            Message::SeekReleased => {
+                let Some(position) = self.seek_preview.take() else {
+                    return Task::none();
+                };
+                let (Some(id), Some(sink)) = (self.now_playing, self.current_sink.clone()) else {
+                    return Task::none();
+                };
+                let silence_watch = self.silence_watch_for(id);
+                if let Err(err) = self.midi_player.seek(sink, silence_watch, position) {
+                    self.show_error(t!("Failed to seek"), AppError::new(err));
+                }
+                Task::none()
+            }
+            Message::CancelPreparing => {
+                // Dropping the task abandons the download or connection, so
+                // its result never arrives.
+                if let Some(handle) = self.preparing_handle.take() {
+                    handle.abort();
+                }
+                if self.is_preparing_playback {
+                    self.is_preparing_playback = false;
+                    self.playback_phase = PlaybackPhase::Idle;
+                    self.notifications.info(t!("Playback cancelled"));
+                }
+                Task::none()
+            }
+            Message::ConnectTimeoutChanged(secs) => {
+                self.user_prefs.connect_timeout_secs = Some(secs);
+                self.save_preferences_task()
+            }
+            Message::VirtualPortToggled(enabled) => {
+                self.user_prefs.virtual_port = enabled;
+                Task::batch([self.virtual_port_task(), self.save_preferences_task()])
+            }
+            Message::VirtualPortUpdated(result) => match result {
+                Ok(()) => self.update(Message::RefreshDevices),
+                Err(err) => {
+                    self.notifications
+                        .error(t!("Could not open the virtual MIDI port: {err}", err = err));
+                    self.user_prefs.virtual_port = false;
+                    self.save_preferences_task()
+                }
+            },
+            Message::BlePacketSizeAutoToggled(auto) => {
+                self.user_prefs.ble_packet_size = (!auto).then_some(MIN_BLE_PACKET_SIZE as u16);
+                self.connection_settings_changed(t!(
+                    "Packet size applies when the device next connects"
+                ))
+            }
+            Message::BlePacketSizeChanged(size) => {
+                self.user_prefs.ble_packet_size = Some(size);
+                self.connection_settings_changed(t!(
+                    "Packet size applies when the device next connects"
+                ))
+            }
+            Message::BleAdaptersListed(result) => {
+                match result {
+                    Ok(adapters) => self.ble_adapters = adapters,
+                    Err(err) => log::debug!("could not list Bluetooth adapters: {err}"),
+                }
+                Task::none()
+            }
+            Message::BleAdapterSelected(AdapterChoice(adapter)) => {
+                if self.user_prefs.ble_adapter == adapter {
+                    return Task::none();
+                }
+                self.user_prefs.ble_adapter = adapter;
+                Task::batch([self.save_preferences_task(), self.rescan_ble_adapter_task()])
+            }
+            Message::UsbRunningStatusToggled(enabled) => {
+                self.user_prefs.usb_running_status = enabled;
+                self.connection_settings_changed(t!(
+                    "Running status applies when the device next connects"
+                ))
+            }
+            Message::SendTraceToggled(enabled) => {
+                self.user_prefs.send_trace = enabled.then_some(TraceFormat::default());
+                self.send_trace_changed()
+            }
+            Message::SendTraceFormatSelected(format) => {
+                self.user_prefs.send_trace = Some(format);
+                self.send_trace_changed()
+            }
+            Message::OpenSendTraceFolder => {
+                let dir = PathBuf::from(SEND_TRACE_DIR);
+                if let Err(err) = std::fs::create_dir_all(&dir).and_then(|()| open_folder(&dir)) {
+                    self.notifications.error(t!(
+                        "Failed to open {path}: {err}",
+                        path = dir.display(),
+                        err = err
+                    ));
+                }
+                Task::none()
+            }
+            Message::FadeLengthChanged(millis) => {
+                self.user_prefs.fade_ms = millis;
+                self.midi_player
+                    .set_fade(Duration::from_millis(millis.into()));
+                self.save_preferences_task()
+            }
+            Message::ProgressIntervalChanged(millis) => {
+                self.user_prefs.progress_interval_ms = Some(millis);
+                self.midi_player
+                    .set_progress_interval(self.user_prefs.progress_interval());
+                self.save_preferences_task()
+            }
+            // Only here to redraw the interpolated position.
+            Message::AnimationFrame => Task::none(),
+            Message::StopPressed => {
+                self.paused_at = None;
+                self.midi_player.stop();
+                self.playback_phase = PlaybackPhase::Idle;
+                self.playback_progress = None;
+                self.current_sink = None;
+                self.play_queue = None;
+                self.pending_movement = None;
+                self.resume_saved_at = None;
+                Task::perform(save_resume_state(None), Message::ResumeStateSaved)
+            }
+            Message::PanicPressed => {
+                if self.current_sink.is_none() && self.selected_device.is_none() {
+                    self.notifications.error(t!("Select a MIDI device first"));
+                    return Task::none();
+                }
+                Task::perform(
+                    send_panic(
+                        self.current_sink.clone(),
+                        self.selected_device,
+                        self.device_manager.clone(),
+                    ),
+                    Message::PanicSent,
+                )
+            }
+            Message::SilenceActionSelected(action) => {
+                self.user_prefs.silence_watch.action = action;
+                self.save_preferences_task()
+            }
+            Message::SilenceThresholdStep(delta) => {
+                let watch = &mut self.user_prefs.silence_watch;
+                watch.threshold_secs = watch
+                    .threshold_secs
+                    .saturating_add_signed(delta)
+                    .clamp(2, 120);
+                self.save_preferences_task()
+            }
+            Message::TrimLeadingSilenceToggled(enabled) => {
+                self.user_prefs.lead_in.trim_silence = enabled;
+                self.save_preferences_task()
+            }
+            Message::PreRollStep(delta) => {
+                let lead_in = &mut self.user_prefs.lead_in;
+                lead_in.pre_roll_ms = lead_in
+                    .pre_roll_ms
+                    .saturating_add_signed(delta * 250)
+                    .min(4000);
+                self.save_preferences_task()
+            }
+            Message::CountInToggled(enabled) => {
+                self.user_prefs.lead_in.count_in = enabled;
+                self.save_preferences_task()
+            }
+            Message::CountdownToggled(enabled) => {
+                self.user_prefs.lead_in.countdown = enabled;
+                self.save_preferences_task()
+            }
+            Message::MetronomeToggled(enabled) => {
+                self.user_prefs.metronome = enabled;
+                self.midi_player.set_metronome(enabled);
+                self.save_preferences_task()
+            }
+            Message::QuietModeToggled(enabled) => {
+                self.user_prefs.quiet_mode = enabled;
+                self.quiet_mode_changed()
+            }
+            Message::TogglePianoMode => {
+                self.user_prefs.piano_mode = !self.user_prefs.piano_mode;
+                self.next_song_filter_changed(t!("Piano Mode applies from the next song"))
+            }
+            Message::QuietVelocityStep(delta) => {
+                let limit = &mut self.user_prefs.quiet_limit;
+                limit.max_velocity = limit
+                    .max_velocity
+                    .saturating_add_signed(delta * 5)
+                    .clamp(5, 127);
+                self.quiet_mode_changed()
+            }
+            Message::QuietVolumeToggled(enabled) => {
+                self.user_prefs.quiet_limit.max_volume =
+                    enabled.then_some(QuietLimit::DEFAULT_MAX_VOLUME);
+                self.quiet_mode_changed()
+            }
+            Message::QuietVolumeStep(delta) => {
+                if let Some(volume) = &mut self.user_prefs.quiet_limit.max_volume {
+                    *volume = volume.saturating_add_signed(delta * 5).min(127);
+                }
+                self.quiet_mode_changed()
+            }
+            Message::SongSilenceWatchToggled(id) => self.update_song_settings(id, |settings| {
+                settings.silence_watch_disabled = !settings.silence_watch_disabled;
+            }),
+            Message::KeyMatchModeSelected(mode) => {
+                self.user_prefs.key_match_mode = mode;
+                self.save_preferences_task()
+            }
+            Message::ShuffleAvoidStep(delta) => {
+                let avoid = self.user_prefs.shuffle_avoid() as u16;
+                self.user_prefs.shuffle_avoid =
+                    Some(avoid.saturating_add_signed(delta * 5).min(100));
+                self.save_preferences_task()
+            }
+            Message::TitleCollationSelected(collation) => {
+                self.user_prefs.title_collation = collation;
+                self.refresh_tree_cache();
+                self.save_preferences_task()
+            }
+            Message::WeightedShuffleToggled(enabled) => {
+                self.user_prefs.weighted_shuffle = enabled;
+                self.save_preferences_task()
+            }
+            Message::ProgramOverrideSelected(choice) => {
+                self.user_prefs.program_override = choice.0;
+                self.save_preferences_task()
+            }
+            Message::OutputFilterModeSelected(control, mode) => {
+                let filter = self.edited_output_filter();
+                let action = match (mode, filter.action(control)) {
+                    (FilterMode::Pass, _) => FilterAction::Pass,
+                    (FilterMode::Drop, _) => FilterAction::Drop,
+                    (FilterMode::Clamp, FilterAction::Clamp(limit)) => FilterAction::Clamp(limit),
+                    (FilterMode::Clamp, _) => FilterAction::Clamp(FilterMode::DEFAULT_CLAMP),
+                    (FilterMode::Scale, FilterAction::Scale(percent)) => {
+                        FilterAction::Scale(percent)
+                    }
+                    (FilterMode::Scale, _) => FilterAction::Scale(FilterMode::DEFAULT_SCALE),
+                };
+                filter.set_action(control, action);
+                self.output_filters_changed()
+            }
+            Message::OutputFilterClampStep(control, delta) => {
+                let filter = self.edited_output_filter();
+                match filter.action(control) {
+                    FilterAction::Clamp(limit) => {
+                        let limit = (limit as i16 + delta).clamp(0, 127) as u8;
+                        filter.set_action(control, FilterAction::Clamp(limit));
+                    }
+                    FilterAction::Scale(percent) => {
+                        let percent = (percent as i16 + delta).clamp(0, 100) as u8;
+                        filter.set_action(control, FilterAction::Scale(percent));
+                    }
+                    _ => {}
+                }
+                self.output_filters_changed()
+            }
+            Message::DeviceProfileSelected(choice) => {
+                let Some(device_id) = self.selected_device else {
+                    return Task::none();
+                };
+                self.user_prefs.device_profiles.assign(device_id, choice.id);
+                self.output_filters_changed()
+            }
+            Message::NewDeviceProfile => {
+                let Some(device_id) = self.selected_device else {
+                    return Task::none();
+                };
+                // Starts from the rules the device uses now.
+                let filter = self.output_filter_for(device_id);
+                let profiles = &mut self.user_prefs.device_profiles;
+                let name = t!("Profile {number}", number = profiles.iter().count() + 1);
+                let profile = profiles.add(DeviceProfile::new(name, filter));
+                profiles.assign(device_id, Some(profile));
+                self.output_filters_changed()
+            }
+            Message::DeleteDeviceProfile => {
+                let Some(profile) = self.selected_profile().map(|profile| profile.id) else {
+                    return Task::none();
+                };
+                self.user_prefs.device_profiles.remove(profile);
+                self.output_filters_changed()
+            }
+            Message::DeviceProfileRenamed(name) => {
+                if let Some(profile) = self.selected_profile_mut() {
+                    profile.name = name;
+                }
+                self.save_preferences_task()
+            }
+            Message::DeviceLatencyStep(delta) => {
+                if let Some(profile) = self.selected_profile_mut() {
+                    profile.latency_ms = profile
+                        .latency_ms
+                        .saturating_add_signed(delta)
+                        .min(DeviceProfile::MAX_LATENCY_MS);
+                }
+                self.save_preferences_task()
+            }
+            Message::DevicePercussionSelected(choice) => {
+                if let Some(profile) = self.selected_profile_mut() {
+                    profile.percussion = choice
+                        .handling(Some(profile.percussion))
+                        .unwrap_or_default();
+                }
+                self.save_preferences_task()
+            }
+            Message::DevicePercussionChannelStep(delta) => {
+                if let Some(profile) = self.selected_profile_mut() {
+                    step_rhythm_channel(&mut profile.percussion, delta);
+                }
+                self.save_preferences_task()
+            }
+            Message::DeviceReliableWritesToggled(enabled) => {
+                if let Some(profile) = self.selected_profile_mut() {
+                    profile.reliable_writes = enabled;
+                }
+                self.connection_settings_changed(t!(
+                    "Reliable writes apply when the device next connects"
+                ))
+            }
+            Message::VelocityCurveSelected(curve) => {
+                self.edited_output_filter().velocity_curve = curve;
+                self.output_filters_changed()
+            }
+            Message::FixedVelocityStep(delta) => {
+                let filter = self.edited_output_filter();
+                if let VelocityCurve::Fixed(velocity) = filter.velocity_curve {
+                    let velocity = (velocity as i16 + delta).clamp(1, 127) as u8;
+                    filter.velocity_curve = VelocityCurve::Fixed(velocity);
+                }
+                self.output_filters_changed()
+            }
+            Message::ChannelMapSelected(channel, ChannelFilter(target)) => {
+                let filter = self.edited_output_filter();
+                filter.channel_map.0[channel as usize] = target.unwrap_or(channel);
+                self.output_filters_changed()
+            }
+            Message::OutputFilterSysExToggled(drop) => {
+                self.edited_output_filter().drop_sysex = drop;
+                self.output_filters_changed()
+            }
+            Message::OutputFilterResetSelected(ResetChoice(reset)) => {
+                self.edited_output_filter().reset = reset;
+                self.output_filters_changed()
+            }
+            Message::OutputFilterBendRangeSelected(BendRangeChoice(range)) => {
+                self.edited_output_filter().bend_range = range;
+                self.output_filters_changed()
+            }
+            Message::DeviceCapabilitiesChanged(capabilities) => {
+                let Some(device_id) = self.selected_device else {
+                    return Task::none();
+                };
+                if capabilities == DeviceCapabilities::default() {
+                    self.user_prefs.device_capabilities.remove(&device_id);
+                } else {
+                    self.user_prefs
+                        .device_capabilities
+                        .insert(device_id, capabilities);
+                }
+                self.save_preferences_task()
+            }
+            Message::CapabilityWarningResolved(choice) => {
+                let Some(warning) = self.capability_warning.take() else {
+                    return Task::none();
+                };
+                match choice {
+                    CapabilityChoice::Remap => self.begin_remapped(warning.prepared),
+                    CapabilityChoice::AlwaysRemap => {
+                        let device_id = warning.device_id;
+                        let mut capabilities = self.capabilities_for(device_id);
+                        capabilities.auto_remap = true;
+                        self.user_prefs
+                            .device_capabilities
+                            .insert(device_id, capabilities);
+                        Task::batch([
+                            self.begin_remapped(warning.prepared),
+                            self.save_preferences_task(),
+                        ])
+                    }
+                    CapabilityChoice::PlayAsWritten => self.begin_playback(warning.prepared),
+                    CapabilityChoice::Cancel => {
+                        self.playback_phase = PlaybackPhase::Idle;
+                        self.playback_progress = None;
+                        Task::none()
+                    }
+                }
+            }
+            Message::QueueKeysDetected(request, result) => {
+                if request != self.key_match_request {
+                    return Task::none();
+                }
+                match result {
+                    Ok(keys) => self.apply_key_matching(&keys),
+                    Err(err) => {
+                        self.notifications
+                            .error(t!("Key matching failed: {err}", err = err));
+                    }
+                }
+                Task::none()
+            }
+            Message::MasterTempoStep(delta) => {
+                self.master_tempo_percent = self
+                    .master_tempo_percent
+                    .saturating_add_signed(delta)
+                    .clamp(
+                        PlaybackAdjustments::MIN_TEMPO_PERCENT,
+                        PlaybackAdjustments::MAX_TEMPO_PERCENT,
+                    );
+                Task::none()
+            }
+            Message::MasterTransposeStep(delta) => {
+                self.master_transpose = self.master_transpose.saturating_add(delta).clamp(-24, 24);
+                Task::none()
+            }
+            Message::ClearMasterAdjustments => {
+                self.master_tempo_percent = 100;
+                self.master_transpose = 0;
+                Task::none()
+            }
+            Message::RepeatStep(delta) => {
+                self.set_repeat_passes(
+                    self.repeat_passes
+                        .saturating_add_signed(delta)
+                        .clamp(1, MAX_REPEAT_PASSES),
+                );
+                Task::none()
+            }
+            Message::SongTempoStep(id, delta) => self.update_song_settings(id, |settings| {
+                settings.tempo_percent = settings.tempo_percent.saturating_add_signed(delta).clamp(
+                    PlaybackAdjustments::MIN_TEMPO_PERCENT,
+                    PlaybackAdjustments::MAX_TEMPO_PERCENT,
+                );
+            }),
+            Message::SongTransposeStep(id, delta) => self.update_song_settings(id, |settings| {
+                settings.transpose = settings.transpose.saturating_add(delta).clamp(-24, 24);
+            }),
+            Message::SongChannelMuteToggled(id, channel) => {
+                self.update_song_settings(id, |settings| {
+                    settings.muted_channels ^= 1 << channel;
+                })
+            }
+            Message::ResetSongSettings(id) => {
+                self.update_song_settings(id, |settings| *settings = SongSettings::default())
+            }
+            Message::SongHandSplitSelected(id, kind) => self.update_song_settings(id, |settings| {
+                let current = settings.hand_split.unwrap_or_default();
+                settings.hand_split = match kind {
+                    HandSplitKind::Off => None,
+                    HandSplitKind::ByPitch => Some(HandSplit {
+                        classifier: HandClassifier::SplitPoint(60),
+                        ..current
+                    }),
+                    // Format 1 piano files usually carry the left hand on the
+                    // second note track, after the conductor track.
+                    HandSplitKind::ByTrack => Some(HandSplit {
+                        classifier: HandClassifier::LeftTrack(2),
+                        ..current
+                    }),
+                };
+            }),
+            Message::SongHandSplitStep(id, delta) => self.update_song_settings(id, |settings| {
+                if let Some(split) = settings.hand_split.as_mut() {
+                    split.classifier = match split.classifier {
+                        HandClassifier::SplitPoint(key) => {
+                            HandClassifier::SplitPoint((key as i16 + delta).clamp(1, 127) as u8)
+                        }
+                        HandClassifier::LeftTrack(track) => {
+                            HandClassifier::LeftTrack(track.saturating_add_signed(delta))
+                        }
+                    };
+                }
+            }),
+            Message::SongPercussionSelected(id, choice) => {
+                self.update_song_settings(id, |settings| {
+                    settings.percussion = choice.handling(settings.percussion);
+                })
+            }
+            Message::SongPercussionChannelStep(id, delta) => {
+                self.update_song_settings(id, |settings| {
+                    if let Some(percussion) = settings.percussion.as_mut() {
+                        step_rhythm_channel(percussion, delta);
+                    }
+                })
+            }
+            Message::SongHandChannelStep(id, hand, delta) => {
+                self.update_song_settings(id, |settings| {
+                    if let Some(split) = settings.hand_split.as_mut() {
+                        let channel = match hand {
+                            Hand::Left => &mut split.left_channel,
+                            Hand::Right => &mut split.right_channel,
+                        };
+                        // Channel 10 is reserved for percussion, so skip it.
+                        let mut next = (*channel as i8 + delta).rem_euclid(16) as u8;
+                        if next == 9 {
+                            next = (next as i8 + delta).rem_euclid(16) as u8;
+                        }
+                        *channel = next;
+                    }
+                })
+            }
+            Message::ShowSongInfo(id) => {
+                let Some(entry) = self.library.get(&id) else {
+                    return Task::none();
+                };
+                let path = entry.path.clone();
+                let details = self.library.details(&id).cloned().unwrap_or_default();
+                self.song_info = Some(SongInfoPanel {
+                    entry_id: id,
+                    name: entry.name.clone(),
+                    path: path.clone(),
+                    info: None,
+                    excerpt: ExcerptDraft::default(),
+                    title_draft: details.title.unwrap_or_default(),
+                    composer_draft: details.composer.unwrap_or_default(),
+                });
+                Task::perform(inspect_song(path), move |result| {
+                    Message::SongInfoLoaded(id, result)
+                })
+            }
+            Message::SongInfoLoaded(id, result) => {
+                match result {
+                    Ok(info) => {
+                        if let Some(panel) =
+                            self.song_info.as_mut().filter(|panel| panel.entry_id == id)
+                        {
+                            panel.info = Some(info);
+                        }
+                    }
+                    Err(err) => {
+                        if self
+                            .song_info
+                            .as_ref()
+                            .is_some_and(|panel| panel.entry_id == id)
+                        {
+                            self.song_info = None;
+                        }
+                        self.notifications
+                            .error(t!("Failed to read song information: {err}", err = err));
+                    }
+                }
+                Task::none()
+            }
+            Message::SongTitleChanged(title) => {
+                if let Some(panel) = self.song_info.as_mut() {
+                    panel.title_draft = title;
+                }
+                Task::none()
+            }
+            Message::SongComposerChanged(composer) => {
+                if let Some(panel) = self.song_info.as_mut() {
+                    panel.composer_draft = composer;
+                }
+                Task::none()
+            }
+            Message::SaveSongDetails => self.save_song_details(),
+            Message::FillSongDetailsFromFile => {
+                let Some(panel) = &self.song_info else {
+                    return Task::none();
+                };
+                let id = panel.entry_id;
+                Task::perform(read_song_details(panel.path.clone()), move |result| {
+                    Message::SongDetailsRead(id, result)
+                })
+            }
+            Message::SongDetailsRead(id, result) => {
+                let Some(panel) = self.song_info.as_mut().filter(|panel| panel.entry_id == id)
+                else {
+                    return Task::none();
+                };
+                match result {
+                    Ok(details) if details.is_empty() => {
+                        self.notifications
+                            .info(t!("The file names no title or composer"));
+                        Task::none()
+                    }
+                    Ok(details) => {
+                        if let Some(title) = details.title {
+                            panel.title_draft = title;
+                        }
+                        if let Some(composer) = details.composer {
+                            panel.composer_draft = composer;
+                        }
+                        self.save_song_details()
+                    }
+                    Err(err) => {
+                        self.notifications
+                            .error(t!("Failed to read song information: {err}", err = err));
+                        Task::none()
+                    }
+                }
+            }
+            Message::CloseSongInfo => {
+                self.song_info = None;
+                Task::none()
+            }
+            Message::ExcerptUnitSelected(unit) => {
+                if let Some(panel) = self.song_info.as_mut() {
+                    panel.excerpt = ExcerptDraft {
+                        unit,
+                        ..ExcerptDraft::default()
+                    };
+                }
+                Task::none()
+            }
+            Message::ExcerptFromChanged(value) => {
+                if let Some(panel) = self.song_info.as_mut() {
+                    panel.excerpt.from = value;
+                }
+                Task::none()
+            }
+            Message::ExcerptToChanged(value) => {
+                if let Some(panel) = self.song_info.as_mut() {
+                    panel.excerpt.to = value;
+                }
+                Task::none()
+            }
+            Message::SaveExcerpt => {
+                let Some(panel) = &self.song_info else {
+                    return Task::none();
+                };
+                let Some(range) = panel.excerpt.range() else {
+                    return Task::none();
+                };
+                let suffix = match range {
+                    ExcerptRange::Bars(from, to) => t!("bars {from}-{to}", from = from, to = to),
+                    ExcerptRange::Time(from, to) => format!(
+                        "{}-{}",
+                        format_duration(from).replace(':', "."),
+                        format_duration(to).replace(':', ".")
+                    ),
+                };
+                let Some(output) = rfd::FileDialog::new()
+                    .set_title(t!("Save excerpt"))
+                    .add_filter(t!("MIDI Files"), &["mid", "midi"])
+                    .set_file_name(format!("{} ({suffix}).mid", panel.name))
+                    .save_file()
+                else {
+                    return Task::none();
+                };
+                Task::perform(
+                    save_excerpt(panel.path.clone(), range, output),
+                    Message::ExcerptSaved,
+                )
+            }
+            Message::ExcerptSaved(result) => {
+                match result.and_then(|path| self.add_exported_file(path)) {
+                    Ok(name) => {
+                        self.notifications
+                            .info(t!("Saved excerpt {name} to the library", name = name));
+                        self.schedule_tree_rebuild()
+                    }
+                    Err(err) => {
+                        self.notifications
+                            .error(t!("Failed to save excerpt: {err}", err = err));
+                        Task::none()
+                    }
+                }
+            }
+            Message::ScoreOverlayToggled(enabled) => {
+                self.user_prefs.score_overlay.enabled = enabled;
+                self.save_preferences_task()
+            }
+            Message::ScoreOverlaySizeChanged(size) => {
+                self.user_prefs.score_overlay.text_size = size;
+                self.save_preferences_task()
+            }
+            Message::ScoreOverlayTap => {
+                // Snap the overlay to the beat nearest the tap.
+                let (Some(sequence), Some(now)) = (&self.playing_sequence, self.score_time())
+                else {
+                    return Task::none();
+                };
+                let index = sequence.beats.partition_point(|beat| beat.at <= now);
+                let nearest = [index.checked_sub(1), Some(index)]
+                    .into_iter()
+                    .flatten()
+                    .filter_map(|index| sequence.beats.get(index))
+                    .min_by_key(|beat| beat.at.abs_diff(now));
+                if let Some(beat) = nearest {
+                    self.score_offset_ms += beat.at.as_millis() as i64 - now.as_millis() as i64;
+                }
+                Task::none()
+            }
+            Message::ScoreOverlayShiftBar(delta) => {
+                let (Some(sequence), Some(now)) = (&self.playing_sequence, self.score_time())
+                else {
+                    return Task::none();
+                };
+                let Some(current) = sequence.beat_at(now) else {
+                    return Task::none();
+                };
+                let measure_start = |measure: u32| {
+                    sequence
+                        .beats
+                        .iter()
+                        .find(|beat| beat.measure == measure)
+                        .map(|beat| beat.at)
+                };
+                let target = current.measure.saturating_add_signed(delta).max(1);
+                if let (Some(from), Some(to)) =
+                    (measure_start(current.measure), measure_start(target))
+                {
+                    self.score_offset_ms += to.as_millis() as i64 - from.as_millis() as i64;
+                }
+                Task::none()
+            }
+            Message::PanicSent(result) => {
+                match result {
+                    Ok(()) => self.notifications.info(t!("All notes off sent")),
+                    Err(err) => self
+                        .notifications
+                        .error(t!("Panic failed: {err}", err = err)),
+                }
+                Task::none()
+            }
+            Message::AddLocalFile => {
+                if let Some(path) = rfd::FileDialog::new()
+                    .add_filter(t!("MIDI Files"), &["mid", "midi"])
+                    .pick_file()
+                {
+                    match self.library.add_local_file(path) {
+                        Ok(entry) => {
+                            self.selected_song = Some(entry.id);
+                            self.notifications
+                                .info(t!("Added {name}", name = entry.name));
+                            return self.schedule_tree_rebuild();
+                        }
+                        Err(err) => {
+                            self.notifications.error(t!(
+                                "Failed to add MIDI file: {err}",
+                                err = format!("{err:?}")
+                            ));
+                        }
+                    }
+                }
+                Task::none()
+            }
+            Message::AddLocalFolder => self.start_folder_import(),
+            Message::RemoveEntry { id, forget } => {
+                let Some(entry) = self.library.remove(&id) else {
+                    return Task::none();
+                };
+                if self.selected_song == Some(id) {
+                    self.selected_song = None;
+                }
+                self.notifications
+                    .info(t!("Removed {name} from the library", name = entry.name));
+                let rebuild = self.schedule_tree_rebuild();
+                if forget {
+                    self.forget_entries(&HashSet::from([id]));
+                    Task::batch([rebuild, self.save_preferences_task()])
+                } else {
+                    rebuild
+                }
+            }
+            Message::CleanMissingFiles => {
+                let missing = self.library.remove_missing_local();
+                if missing.is_empty() {
+                    self.notifications.info(t!("No missing files found"));
+                    return Task::none();
+                }
+                if self
+                    .selected_song
+                    .is_some_and(|id| missing.iter().any(|entry| entry.id == id))
+                {
+                    self.selected_song = None;
+                }
+                self.notifications
+                    .info(t!("Removed {count} missing file(s)", count = missing.len()));
+                self.schedule_tree_rebuild()
+            }
+            Message::FolderImportUpdate(update) => {
+                match update {
+                    FolderImportUpdate::Found(total) => {
+                        if let Some(job) = self.folder_import.as_mut() {
+                            job.total = total;
+                        }
+                    }
+                    FolderImportUpdate::Imported(import) => {
+                        if let Some(job) = self.folder_import.as_mut() {
+                            job.done += 1;
+                            self.notifications.info(t!(
+                                "Imported {done} of {total}",
+                                done = job.done,
+                                total = job.total
+                            ));
+                        }
+                        if let Some(import) = import
+                            && let Err(err) = self
+                                .library
+                                .add_local_file_at(&import.path, Some(import.library_path))
+                        {
+                            log::warn!("failed to add imported file: {err:?}");
+                        }
+                    }
+                    FolderImportUpdate::Finished(result) => {
+                        let job = self.folder_import.take();
+                        match result {
+                            Ok(summary) => {
+                                let done = job.map(|job| job.done).unwrap_or_default();
+                                let mut message = if summary.cancelled {
+                                    t!("Import cancelled after {done} file(s)", done = done)
+                                } else {
+                                    t!("Imported {done} file(s)", done = done)
+                                };
+                                if summary.skipped > 0 {
+                                    message.push_str(&t!(
+                                        "; skipped {count} unreadable file(s)",
+                                        count = summary.skipped
+                                    ));
+                                }
+                                self.notifications.info(message);
+                            }
+                            Err(err) => {
+                                self.notifications
+                                    .error(t!("Folder import failed: {err}", err = err));
+                            }
+                        }
+                        return self.schedule_tree_rebuild();
+                    }
+                }
+                Task::none()
+            }
+            Message::CancelFolderImport => {
+                if let Some(job) = &self.folder_import {
+                    job.cancel.store(true, Ordering::Relaxed);
+                }
+                Task::none()
+            }
+            Message::ToggleSettings => {
+                self.show_settings = !self.show_settings;
+                if !self.show_settings {
+                    return Task::none();
+                }
+                let manager = self.device_manager.clone();
+                Task::perform(
+                    async move {
+                        let mut guard = manager.lock().await;
+                        guard.ble_adapters().await.map_err(|err| format!("{err:?}"))
+                    },
+                    Message::BleAdaptersListed,
+                )
+            }
+            Message::ToggleMiniPlayer => {
+                self.user_prefs.mini_player = !self.user_prefs.mini_player;
+                Task::batch([self.resize_window_task(), self.save_preferences_task()])
+            }
+            Message::LibraryMetadataRefreshed(result) => {
+                self.metadata_refreshing = false;
+                match result {
+                    Ok(metadata) => {
+                        self.library_metadata = Some(metadata);
+                        // Folder totals depend on the durations.
+                        self.schedule_tree_rebuild()
+                    }
+                    Err(err) => {
+                        log::warn!("failed to refresh library metadata: {err}");
+                        Task::none()
+                    }
+                }
+            }
+            Message::TraySpawned(result) => {
+                match result {
+                    Ok(handle) => self.tray = Some(handle),
+                    Err(err) => log::info!("system tray unavailable: {err}"),
+                }
+                Task::none()
+            }
+            Message::MediaControlsSpawned(result) => {
+                match result {
+                    Ok(handle) => self.media_controls = Some(handle),
+                    Err(err) => log::info!("desktop media controls unavailable: {err}"),
+                }
+                Task::none()
+            }
+            Message::RemoteControlToggled(enabled) => {
+                self.user_prefs.remote_control.enabled = enabled;
+                Task::batch([self.restart_remote_control(), self.save_preferences_task()])
+            }
+            Message::RemoteControlPortDraftChanged(draft) => {
+                self.remote_port_draft = draft;
+                Task::none()
+            }
+            Message::RemoteControlPortSubmitted => {
+                match self.remote_port_draft.trim().parse::<u16>() {
+                    Ok(port) if port > 0 => {
+                        self.user_prefs.remote_control.port = port;
+                        Task::batch([self.restart_remote_control(), self.save_preferences_task()])
+                    }
+                    _ => {
+                        self.notifications
+                            .error(t!("Enter a port between 1 and 65535"));
+                        Task::none()
+                    }
+                }
+            }
+            Message::RegenerateRemoteControlToken => {
+                // Restarting disconnects clients still using the old token.
+                self.user_prefs.remote_control.token = new_remote_control_token();
+                Task::batch([self.restart_remote_control(), self.save_preferences_task()])
+            }
+            Message::RemoteControlStopped(result) => {
+                self.remote_control_handle = None;
+                if let Err(err) = result {
+                    self.notifications
+                        .error(t!("Remote control server stopped: {err}", err = err));
+                }
+                Task::none()
+            }
+            Message::CloseToTrayToggled(enabled) => {
+                self.user_prefs.close_to_tray = enabled;
+                self.save_preferences_task()
+            }
+            Message::HideToTray => {
+                window::get_latest().and_then(|id| window::change_mode(id, window::Mode::Hidden))
+            }
+            Message::ShowWindow => window::get_latest().and_then(|id| {
+                window::change_mode(id, window::Mode::Windowed).chain(window::gain_focus(id))
+            }),
+            Message::WindowCloseRequested(id) => {
+                if self.user_prefs.close_to_tray && self.tray.is_some() {
+                    window::change_mode(id, window::Mode::Hidden)
+                } else {
+                    self.shutdown_task(id)
+                }
+            }
+            Message::Quit => {
+                window::get_latest().and_then(|id| Task::done(Message::ShutdownWindow(id)))
+            }
+            Message::ShutdownWindow(id) => self.shutdown_task(id),
+            Message::WindowResized(size) => {
+                // Remember the full layout's size so leaving the mini player
+                // restores it.
+                if !self.user_prefs.mini_player {
+                    self.full_window_size = size;
+                }
+                Task::none()
+            }
+            Message::PickInboxFolder => {
+                if let Some(inbox) = rfd::FileDialog::new()
+                    .set_title(t!("Choose inbox folder"))
+                    .pick_folder()
+                {
+                    let library_root = self
+                        .user_prefs
+                        .watch_folder
+                        .as_ref()
+                        .map(|config| config.library_root.clone())
+                        .unwrap_or_else(|| inbox.join("Library"));
+                    let grouping = self
+                        .user_prefs
+                        .watch_folder
+                        .as_ref()
+                        .map(|config| config.grouping)
+                        .unwrap_or_default();
+                    self.user_prefs.watch_folder = Some(WatchFolderConfig {
+                        inbox,
+                        library_root,
+                        grouping,
+                    });
+                    self.notifications.info(t!("Watch folder updated"));
+                    return Task::batch([self.save_preferences_task(), self.inbox_scan_task()]);
+                }
+                Task::none()
+            }
+            Message::PickInboxLibraryRoot => {
+                if self.user_prefs.watch_folder.is_none() {
+                    self.notifications.error(t!("Choose an inbox folder first"));
+                    return Task::none();
+                }
+                if let Some(root) = rfd::FileDialog::new()
+                    .set_title(t!("Choose library folder for imported files"))
+                    .pick_folder()
+                    && let Some(config) = self.user_prefs.watch_folder.as_mut()
+                {
+                    config.library_root = root;
+                    self.notifications.info(t!("Import destination updated"));
+                    return Task::batch([
+                        self.save_preferences_task(),
+                        self.index_watch_library_task(),
+                    ]);
+                }
+                Task::none()
+            }
+            Message::InboxGroupingSelected(grouping) => {
+                if let Some(config) = self.user_prefs.watch_folder.as_mut() {
+                    config.grouping = grouping;
+                    return self.save_preferences_task();
+                }
+                Task::none()
+            }
+            Message::DisableWatchFolder => {
+                if self.user_prefs.watch_folder.take().is_some() {
+                    self.notifications.info(t!("Watch folder disabled"));
+                    return self.save_preferences_task();
+                }
+                Task::none()
+            }
+            Message::InboxPoll => self.inbox_scan_task(),
+            Message::InboxScanned(result) => {
+                self.inbox_scan_running = false;
+                match result {
+                    Ok(report) => {
+                        if report.imported.is_empty() && report.rejected.is_empty() {
+                            return Task::none();
+                        }
+                        let mut names = Vec::new();
+                        for import in report.imported {
+                            match self
+                                .library
+                                .add_local_file_at(&import.path, Some(import.library_path))
+                            {
+                                Ok(entry) => names.push(entry.name.clone()),
+                                Err(err) => log::warn!("failed to add imported file: {err:?}"),
+                            }
+                        }
+                        let mut summary =
+                            t!("Inbox: imported {count} file(s)", count = names.len());
+                        if !names.is_empty() {
+                            summary.push_str(&format!(" ({})", names.join(", ")));
+                        }
+                        if !report.rejected.is_empty() {
+                            summary.push_str(&t!(
+                                ", {count} rejected as invalid MIDI",
+                                count = report.rejected.len()
+                            ));
+                        }
+                        self.notifications.info(summary);
+                        if !names.is_empty() {
+                            return self.schedule_tree_rebuild();
+                        }
+                    }
+                    Err(err) => {
+                        self.notifications
+                            .error(t!("Inbox import failed: {err}", err = err));
+                    }
+                }
+                Task::none()
+            }
+            Message::WatchLibraryIndexed(result) => match result {
+                Ok(found) => self.add_indexed_files(found),
+                Err(err) => {
+                    self.notifications
+                        .error(t!("Failed to index imported library: {err}", err = err));
+                    Task::none()
+                }
+            },
+            Message::PickMusicFolder => {
+                let Some(folder) = rfd::FileDialog::new()
+                    .set_title(t!("Choose your music folder"))
+                    .pick_folder()
+                else {
+                    return Task::none();
+                };
+                if !self.user_prefs.music_folders.contains(&folder) {
+                    self.user_prefs.music_folders.push(folder.clone());
+                }
+                if let Some(onboarding) = self.onboarding.as_mut() {
+                    onboarding.scanning_folder = true;
+                }
+                self.notifications
+                    .info(t!("Scanning {folder}", folder = folder.display()));
+                Task::batch([
+                    self.save_preferences_task(),
+                    Task::perform(
+                        scan_music_folders(vec![folder]),
+                        Message::MusicFolderIndexed,
+                    ),
+                ])
+            }
+            Message::RemoveMusicFolder(index) => {
+                if index < self.user_prefs.music_folders.len() {
+                    self.user_prefs.music_folders.remove(index);
+                    return self.save_preferences_task();
+                }
+                Task::none()
+            }
+            Message::MusicFolderIndexed(result) => {
+                if let Some(onboarding) = self.onboarding.as_mut() {
+                    onboarding.scanning_folder = false;
+                }
+                match result {
+                    Ok(found) => {
+                        if let Some(onboarding) = self.onboarding.as_mut() {
+                            onboarding.found_files += found.len();
+                        }
+                        self.add_indexed_files(found)
+                    }
+                    Err(err) => {
+                        self.notifications
+                            .error(t!("Failed to scan music folder: {err}", err = err));
+                        Task::none()
+                    }
+                }
+            }
+            Message::OnboardingNext => {
+                if let Some(onboarding) = self.onboarding.as_mut() {
+                    match onboarding.step.next() {
+                        Some(step) => onboarding.step = step,
+                        None => return self.update(Message::OnboardingFinish),
+                    }
+                    // Look for the piano straight away rather than showing
+                    // whatever was found at startup.
+                    if onboarding.step == OnboardingStep::Devices && !self.is_scanning_devices {
+                        return self.update(Message::RefreshDevices);
+                    }
+                }
+                Task::none()
+            }
+            Message::OnboardingBack => {
+                if let Some(onboarding) = self.onboarding.as_mut()
+                    && let Some(step) = onboarding.step.previous()
+                {
+                    onboarding.step = step;
+                }
+                Task::none()
+            }
+            Message::OnboardingFinish => {
+                self.onboarding = None;
+                self.user_prefs.onboarding_complete = true;
+                if self.selected_device.is_some() {
+                    self.user_prefs.default_device = self.selected_device;
+                }
+                self.notifications
+                    .info(t!("Setup complete. Pick a song and press Play."));
+                self.save_preferences_task()
+            }
+            Message::RestartOnboarding => {
+                self.show_settings = false;
+                self.onboarding = Some(Onboarding::new());
+                Task::none()
+            }
+            Message::SetDefaultDevice => {
+                self.user_prefs.default_device = self.selected_device;
+                self.notifications
+                    .info(t!("This device will be selected on start"));
+                self.save_preferences_task()
+            }
+            Message::LanguageSelected(language) => {
+                self.user_prefs.language = language;
+                i18n::set_language(language);
+                // Folder names in the tree are translated when it is built.
+                Task::batch([self.schedule_tree_rebuild(), self.save_preferences_task()])
+            }
+            Message::ThemeSelected(theme) => {
+                self.user_prefs.theme = Some(theme.to_string());
+                self.save_preferences_task()
+            }
+            Message::SendTestTone => {
+                let Some(device_id) = self.selected_device else {
+                    self.notifications.error(t!("Select a MIDI device first"));
+                    return Task::none();
+                };
+                if let Some(onboarding) = self.onboarding.as_mut() {
+                    onboarding.test_tone = None;
+                }
+                Task::perform(
+                    send_test_tone(device_id, self.device_manager.clone()),
+                    Message::TestToneSent,
+                )
+            }
+            Message::TestToneSent(result) => {
+                if let Err(err) = &result {
+                    self.notifications
+                        .error(t!("Test tone failed: {err}", err = err));
+                }
+                if let Some(onboarding) = self.onboarding.as_mut() {
+                    onboarding.test_tone = Some(result);
+                }
+                Task::none()
+            }
+            Message::ImportPreferences => {
+                let Some(path) = rfd::FileDialog::new()
+                    .set_title(t!("Import preferences"))
+                    .add_filter("JSON", &["json"])
+                    .pick_file()
+                else {
+                    return Task::none();
+                };
+                if let Some(onboarding) = self.onboarding.as_mut() {
+                    onboarding.imported_from = Some(path.clone());
+                }
+                Task::perform(read_user_preferences(path), Message::PreferencesImported)
+            }
+            Message::PreferencesImported(result) => match result {
+                Ok(prefs) => {
+                    self.notifications.info(t!("Preferences imported"));
+                    self.apply_imported_preferences(prefs)
+                }
+                Err(err) => {
+                    if let Some(onboarding) = self.onboarding.as_mut() {
+                        onboarding.imported_from = None;
+                    }
+                    self.notifications
+                        .error(t!("Failed to import preferences: {err}", err = err));
+                    Task::none()
+                }
+            },
+            Message::ExportCatalog => {
+                let Some(path) = rfd::FileDialog::new()
+                    .set_title(t!("Export library catalog"))
+                    .add_filter("JSON", &["json"])
+                    .add_filter("CSV", &["csv"])
+                    .set_file_name("midi_piano_catalog.json")
+                    .save_file()
+                else {
+                    return Task::none();
+                };
+                Task::perform(
+                    export_catalog(self.catalog_entries(), path),
+                    Message::CatalogExported,
+                )
+            }
+            Message::CatalogExported(result) => {
+                match result {
+                    Ok(path) => {
+                        self.notifications
+                            .info(t!("Catalog exported to {path}", path = path.display()));
+                    }
+                    Err(err) => {
+                        self.notifications
+                            .error(t!("Failed to export catalog: {err}", err = err));
+                    }
+                }
+                Task::none()
+            }
+            Message::ImportCatalog => {
+                let Some(path) = rfd::FileDialog::new()
+                    .set_title(t!("Import ratings from catalog"))
+                    .add_filter(t!("Catalog"), &["json", "csv"])
+                    .pick_file()
+                else {
+                    return Task::none();
+                };
+                Task::perform(read_catalog(path), Message::CatalogImported)
+            }
+            Message::CatalogImported(result) => match result {
+                Ok(entries) => {
+                    let updated = self.apply_catalog(entries);
+                    self.notifications.info(t!(
+                        "Updated {count} song(s) from the catalog",
+                        count = updated
+                    ));
+                    Task::batch([
+                        self.save_preferences_task(),
+                        self.regroup_tree_task(TreeGrouping::Rating),
+                    ])
+                }
+                Err(err) => {
+                    self.notifications
+                        .error(t!("Failed to import catalog: {err}", err = err));
+                    Task::none()
+                }
+            },
+            Message::ExportUserData => {
+                let Some(path) = rfd::FileDialog::new()
+                    .set_title(t!("Export user data"))
+                    .add_filter("JSON", &["json"])
+                    .set_file_name("midi_piano_user_data.json")
+                    .save_file()
+                else {
+                    return Task::none();
+                };
+                let bundle = UserDataBundle {
+                    version: UserDataBundle::VERSION,
+                    exported_at: chrono::Utc::now(),
+                    preferences: self.user_prefs.clone(),
+                    practice_log: self.practice_log.clone(),
+                };
+                Task::perform(export_user_data(bundle, path), Message::UserDataExported)
+            }
+            Message::UserDataExported(result) => {
+                match result {
+                    Ok(path) => {
+                        self.notifications
+                            .info(t!("User data exported to {path}", path = path.display()));
+                    }
+                    Err(err) => {
+                        self.notifications
+                            .error(t!("Failed to export user data: {err}", err = err));
+                    }
+                }
+                Task::none()
+            }
+            Message::ImportUserData(mode) => {
+                let Some(path) = rfd::FileDialog::new()
+                    .set_title(t!("Import user data"))
+                    .add_filter("JSON", &["json"])
+                    .pick_file()
+                else {
+                    return Task::none();
+                };
+                Task::perform(read_user_data(path), move |result| {
+                    Message::UserDataImported(mode, result)
+                })
+            }
+            Message::UserDataImported(mode, result) => match result {
+                Ok(bundle) => {
+                    let prefs = match mode {
+                        UserDataImport::Merge => {
+                            self.practice_log.merge(bundle.practice_log);
+                            let mut prefs = self.user_prefs.clone();
+                            prefs.merge(bundle.preferences);
+                            prefs
+                        }
+                        UserDataImport::Replace => {
+                            self.practice_log = bundle.practice_log;
+                            bundle.preferences
+                        }
+                    };
+                    self.notifications.info(t!("User data imported"));
+                    Task::batch([
+                        self.apply_imported_preferences(prefs),
+                        Task::perform(
+                            save_practice_log(self.practice_log.clone()),
+                            Message::PracticeLogSaved,
+                        ),
+                    ])
+                }
+                Err(err) => {
+                    self.notifications
+                        .error(t!("Failed to import user data: {err}", err = err));
+                    Task::none()
+                }
+            },
+            Message::PickSoundfont => {
+                if let Some(path) = pick_soundfont() {
+                    self.user_prefs.soundfont_path = Some(path);
+                    self.notifications.info(t!("Soundfont updated"));
+                    return self.save_preferences_task();
+                }
+                Task::none()
+            }
+            Message::ExportArrangement(id) => {
+                let Some(entry) = self.library.get(&id) else {
+                    return Task::none();
+                };
+                let Some(output) = rfd::FileDialog::new()
+                    .set_title(t!("Export arrangement"))
+                    .add_filter(t!("MIDI Files"), &["mid", "midi"])
+                    .set_file_name(format!("{} ({}).mid", entry.name, t!("arranged")))
+                    .save_file()
+                else {
+                    return Task::none();
+                };
+                Task::perform(
+                    export_arrangement(entry.path.clone(), self.playback_adjustments(id), output),
+                    Message::ArrangementExported,
+                )
+            }
+            Message::ArrangementExported(result) => {
+                match result.and_then(|path| self.add_exported_file(path)) {
+                    Ok(name) => {
+                        self.notifications
+                            .info(t!("Exported {name} to the library", name = name));
+                        self.schedule_tree_rebuild()
+                    }
+                    Err(err) => {
+                        self.notifications
+                            .error(t!("Failed to export arrangement: {err}", err = err));
+                        Task::none()
+                    }
+                }
+            }
+            Message::ExportWav => self.start_wav_export(),
+            Message::RenderUpdate(update) => {
+                match update {
+                    RenderUpdate::Progress(fraction) => {
+                        if let Some(job) = self.render_job.as_mut() {
+                            job.progress = fraction;
+                        }
+                    }
+                    RenderUpdate::Finished(result) => {
+                        let job = self.render_job.take();
+                        match result {
+                            Ok(true) => {
+                                if let Some(job) = job {
+                                    self.notifications.info(t!(
+                                        "Exported {output}",
+                                        output = job.output.display()
+                                    ));
+                                }
+                            }
+                            Ok(false) => {
+                                if let Some(job) = job {
+                                    let _ = std::fs::remove_file(&job.output);
+                                }
+                                self.notifications.info(t!("WAV export cancelled"));
+                            }
+                            Err(err) => {
+                                self.notifications
+                                    .error(t!("WAV export failed: {err}", err = err));
+                            }
+                        }
+                    }
+                }
+                Task::none()
+            }
+            Message::CancelRender => {
+                if let Some(job) = &self.render_job {
+                    job.cancel.store(true, Ordering::Relaxed);
+                }
+                Task::none()
+            }
+            Message::StatsFromChanged(value) => {
+                self.stats_from = value;
+                Task::none()
+            }
+            Message::StatsToChanged(value) => {
+                self.stats_to = value;
+                Task::none()
+            }
+            Message::StatsExportKindSelected(kind) => {
+                self.stats_export_kind = kind;
+                Task::none()
+            }
+            Message::ExportStats => {
+                let range = match (
+                    practice::parse_date(&self.stats_from),
+                    practice::parse_date(&self.stats_to),
+                ) {
+                    (Ok(from), Ok(to)) => DateRange { from, to },
+                    (Err(err), _) | (_, Err(err)) => {
+                        self.notifications.error(err);
+                        return Task::none();
+                    }
+                };
+                let kind = self.stats_export_kind;
+                let Some(path) = rfd::FileDialog::new()
+                    .set_title(t!("Export practice statistics"))
+                    .add_filter(kind.to_string(), &[kind.extension()])
+                    .set_file_name(format!("practice_stats.{}", kind.extension()))
+                    .save_file()
+                else {
+                    return Task::none();
+                };
+                Task::perform(
+                    export_practice_stats(self.practice_log.clone(), kind, range, path),
+                    Message::StatsExported,
+                )
+            }
+            Message::StatsExported(result) => {
                 match result {
-                    Ok(library) => {
-                        self.library = library;
-                        self.status_message = Some("Library loaded".into());
-                        return self.schedule_tree_rebuild();
+                    Ok(path) => {
+                        self.notifications
+                            .info(t!("Statistics exported to {path}", path = path.display()));
+                    }
+                    Err(err) => {
+                        self.notifications
+                            .error(t!("Failed to export statistics: {err}", err = err));
+                    }
+                }
+                Task::none()
+            }
+            Message::AssignmentDraftFromPlaylist => {
+                if self.playlist_draft.tracks.is_empty() {
+                    self.notifications.error(t!(
+                        "Add pieces to the playlist draft to build an assignment"
+                    ));
+                    return Task::none();
+                }
+                self.assignment_draft.items = self
+                    .playlist_draft
+                    .tracks
+                    .iter()
+                    .filter_map(|id| self.library.get(id))
+                    .map(|entry| AssignmentItem {
+                        entry_id: entry.id,
+                        entry_name: entry.name.clone(),
+                        target_tempo: 100,
+                        section: String::new(),
+                        target_plays: 3,
+                    })
+                    .collect();
+                if self.assignment_draft.title.trim().is_empty() {
+                    self.assignment_draft.title = self.playlist_draft.name.clone();
+                }
+                Task::none()
+            }
+            Message::AssignmentTitleChanged(title) => {
+                self.assignment_draft.title = title;
+                Task::none()
+            }
+            Message::AssignmentDueChanged(due) => {
+                self.assignment_draft.due = due;
+                Task::none()
+            }
+            Message::AssignmentItemTempoChanged(index, value) => {
+                if let Some(item) = self.assignment_draft.items.get_mut(index) {
+                    if value.is_empty() {
+                        item.target_tempo = 0;
+                    } else if let Ok(tempo) = value.parse::<u16>() {
+                        item.target_tempo = tempo.min(400);
+                    }
+                }
+                Task::none()
+            }
+            Message::AssignmentItemSectionChanged(index, value) => {
+                if let Some(item) = self.assignment_draft.items.get_mut(index) {
+                    item.section = value;
+                }
+                Task::none()
+            }
+            Message::AssignmentItemPlaysChanged(index, value) => {
+                if let Some(item) = self.assignment_draft.items.get_mut(index) {
+                    if value.is_empty() {
+                        item.target_plays = 0;
+                    } else if let Ok(plays) = value.parse::<u32>() {
+                        item.target_plays = plays;
+                    }
+                }
+                Task::none()
+            }
+            Message::AssignmentDraftRemove(index) => {
+                if index < self.assignment_draft.items.len() {
+                    self.assignment_draft.items.remove(index);
+                }
+                Task::none()
+            }
+            Message::AssignmentDraftExport => {
+                let draft = &self.assignment_draft;
+                if draft.items.is_empty() {
+                    self.notifications.error(t!("The assignment has no pieces"));
+                    return Task::none();
+                }
+                let due = match practice::parse_date(&draft.due) {
+                    Ok(due) => due,
+                    Err(err) => {
+                        self.notifications.error(err);
+                        return Task::none();
+                    }
+                };
+                let title = if draft.title.trim().is_empty() {
+                    t!("Assignment").to_owned()
+                } else {
+                    draft.title.trim().to_owned()
+                };
+                let items = draft
+                    .items
+                    .iter()
+                    .cloned()
+                    .map(|mut item| {
+                        item.target_tempo = item.target_tempo.max(1);
+                        item.target_plays = item.target_plays.max(1);
+                        item
+                    })
+                    .collect();
+                let assignment = Assignment::new(title.clone(), due, items);
+                if let Some(path) = rfd::FileDialog::new()
+                    .set_title(t!("Export assignment"))
+                    .add_filter(t!("Lesson assignment"), &["json"])
+                    .set_file_name(format!("{title}.lesson.json"))
+                    .save_file()
+                {
+                    match assignment.export_bundle(&path) {
+                        Ok(()) => {
+                            self.notifications
+                                .info(t!("Assignment exported to {path}", path = path.display()));
+                        }
+                        Err(err) => {
+                            self.notifications.error(t!(
+                                "Failed to export assignment: {err}",
+                                err = format!("{err:?}")
+                            ));
+                        }
+                    }
+                }
+                Task::none()
+            }
+            Message::ImportAssignment => {
+                let Some(path) = rfd::FileDialog::new()
+                    .set_title(t!("Import assignment"))
+                    .add_filter(t!("Lesson assignment"), &["json"])
+                    .pick_file()
+                else {
+                    return Task::none();
+                };
+                match Assignment::import_bundle(&path) {
+                    Ok(assignment) => {
+                        let missing = assignment
+                            .items
+                            .iter()
+                            .filter(|item| self.resolve_assignment_item(item).is_none())
+                            .count();
+                        self.notifications.info(if missing == 0 {
+                            t!("Imported assignment '{title}'", title = assignment.title)
+                        } else {
+                            t!("Imported assignment '{title}' ({missing} piece(s) not in your library)", title = assignment.title, missing = missing)
+                        });
+                        self.user_prefs
+                            .assignments
+                            .retain(|existing| existing.id != assignment.id);
+                        self.user_prefs.assignments.push(assignment);
+                        self.save_preferences_task()
+                    }
+                    Err(err) => {
+                        self.notifications.error(t!(
+                            "Failed to import assignment: {err}",
+                            err = format!("{err:?}")
+                        ));
+                        Task::none()
+                    }
+                }
+            }
+            Message::RemoveAssignment(id) => {
+                let before = self.user_prefs.assignments.len();
+                self.user_prefs
+                    .assignments
+                    .retain(|assignment| assignment.id != id);
+                if before != self.user_prefs.assignments.len() {
+                    self.notifications.info(t!("Assignment removed"));
+                    self.save_preferences_task()
+                } else {
+                    Task::none()
+                }
+            }
+            Message::ExportAssignmentResults(id) => {
+                let Some(assignment) = self
+                    .user_prefs
+                    .assignments
+                    .iter()
+                    .find(|assignment| assignment.id == id)
+                else {
+                    return Task::none();
+                };
+                if let Some(path) = rfd::FileDialog::new()
+                    .set_title(t!("Export assignment results"))
+                    .add_filter(t!("Results report"), &["json"])
+                    .set_file_name(format!("{} results.json", assignment.title))
+                    .save_file()
+                {
+                    match assignment.export_results(&self.practice_log, &path) {
+                        Ok(()) => {
+                            self.notifications
+                                .info(t!("Results exported to {path}", path = path.display()));
+                        }
+                        Err(err) => {
+                            self.notifications.error(t!(
+                                "Failed to export results: {err}",
+                                err = format!("{err:?}")
+                            ));
+                        }
+                    }
+                }
+                Task::none()
+            }
+            Message::DumpDebugLog => {
+                let Some(recorder) = &self.debug_recorder else {
+                    return Task::none();
+                };
+                let path = PathBuf::from(DEBUG_DUMP_DIR).join(format!(
+                    "messages-{}.json",
+                    chrono::Local::now().format("%Y%m%d-%H%M%S")
+                ));
+                match recorder.dump(&path) {
+                    Ok(()) => {
+                        self.notifications.info(t!(
+                            "Dumped {count} message(s) to {path}",
+                            count = recorder.len(),
+                            path = path.display()
+                        ));
+                    }
+                    Err(err) => {
+                        self.notifications.error(t!(
+                            "Failed to dump message log: {err}",
+                            err = format!("{err:?}")
+                        ));
+                    }
+                }
+                Task::none()
+            }
+            Message::DumpTimings => {
+                let Some(capture) = &self.timing_capture else {
+                    return Task::none();
+                };
+                let path = PathBuf::from(DEBUG_DUMP_DIR).join(format!(
+                    "timings-{}.json",
+                    chrono::Local::now().format("%Y%m%d-%H%M%S")
+                ));
+                match capture.report().dump(&path) {
+                    Ok(()) => {
+                        self.notifications
+                            .info(t!("Timings written to {path}", path = path.display()));
                     }
                     Err(err) => {
-                        self.error_message = Some(format!("Failed to load MIDI library: {err}"));
+                        self.notifications.error(t!(
+                            "Failed to write timings: {err}",
+                            err = format!("{err:?}")
+                        ));
+                    }
+                }
+                Task::none()
+            }
+            Message::ToggleMonitor => {
+                self.monitor_pane = match self.monitor_pane.take() {
+                    Some(_) => None,
+                    None => {
+                        let mut pane = MonitorPane::default();
+                        pane.refresh(&self.monitor);
+                        Some(pane)
+                    }
+                };
+                Task::none()
+            }
+            Message::MonitorPauseToggled => {
+                if let Some(pane) = self.monitor_pane.as_mut() {
+                    pane.paused = !pane.paused;
+                    if !pane.paused {
+                        pane.refresh(&self.monitor);
+                    }
+                }
+                Task::none()
+            }
+            Message::MonitorChannelFilterSelected(filter) => {
+                if let Some(pane) = self.monitor_pane.as_mut() {
+                    pane.channel = filter;
+                }
+                Task::none()
+            }
+            Message::MonitorKindFilterSelected(filter) => {
+                if let Some(pane) = self.monitor_pane.as_mut() {
+                    pane.kind = filter;
+                }
+                Task::none()
+            }
+            Message::MonitorHexToggled(hex) => {
+                if let Some(pane) = self.monitor_pane.as_mut() {
+                    pane.hex = hex;
+                }
+                Task::none()
+            }
+            Message::ClearMonitor => {
+                self.monitor.clear();
+                if let Some(pane) = self.monitor_pane.as_mut() {
+                    pane.refresh(&self.monitor);
+                }
+                Task::none()
+            }
+            Message::Tick => {
+                if let Some(pane) = self.monitor_pane.as_mut()
+                    && !pane.paused
+                {
+                    pane.refresh(&self.monitor);
+                }
+                self.notifications.expire(Instant::now());
+                if self
+                    .search_edited_at
+                    .is_some_and(|at| at.elapsed() >= SEARCH_DEBOUNCE)
+                {
+                    self.apply_search();
+                }
+                for level in &mut self.channel_levels {
+                    *level *= METER_DECAY;
+                }
+                self.refresh_sink_stats();
+                let mut tasks = Vec::new();
+                while let Ok(event) = self.player_events.try_recv() {
+                    if let Some(task) = self.handle_player_event(event) {
+                        tasks.push(task);
                     }
                 }
-                Task::none()
+                while let Ok(command) = self.tray_events.try_recv() {
+                    let message = match command {
+                        TrayCommand::PlayPause => Message::PlayPause,
+                        TrayCommand::Next => Message::NextTrack,
+                        TrayCommand::Stop => Message::StopPressed,
+                        TrayCommand::Play(id) => Message::StartPlayback(id),
+                        TrayCommand::ShowWindow => Message::ShowWindow,
+                        TrayCommand::Quit => Message::Quit,
+                    };
+                    tasks.push(self.update(message));
+                }
+                while let Ok(command) = self.media_events.try_recv() {
+                    let playing = matches!(self.playback_phase, PlaybackPhase::Playing);
+                    let message = match command {
+                        MediaCommand::Play if playing => continue,
+                        MediaCommand::Pause if !playing => continue,
+                        MediaCommand::Play | MediaCommand::Pause | MediaCommand::PlayPause => {
+                            Message::PlayPause
+                        }
+                        MediaCommand::Next => Message::NextTrack,
+                        MediaCommand::Previous => Message::PrevTrack,
+                        MediaCommand::Stop => Message::StopPressed,
+                        MediaCommand::Raise => Message::ShowWindow,
+                    };
+                    tasks.push(self.update(message));
+                }
+                while let Ok(command) = self.remote_events.try_recv() {
+                    let playing = matches!(self.playback_phase, PlaybackPhase::Playing);
+                    let message = match command {
+                        ControlCommand::Play if playing => continue,
+                        ControlCommand::Pause if !playing => continue,
+                        ControlCommand::Play
+                        | ControlCommand::Pause
+                        | ControlCommand::PlayPause => Message::PlayPause,
+                        ControlCommand::Stop => Message::StopPressed,
+                        ControlCommand::Next => Message::NextTrack,
+                        ControlCommand::Previous => Message::PrevTrack,
+                        ControlCommand::Seek { seconds } => {
+                            let Some(position) = self
+                                .playback_progress
+                                .as_ref()
+                                .and_then(|progress| seek_target(seconds, progress.total))
+                            else {
+                                continue;
+                            };
+                            tasks.push(self.update(Message::SeekChanged(position.as_secs_f32())));
+                            Message::SeekReleased
+                        }
+                        ControlCommand::PlayTrack { id } => Message::StartPlayback(id),
+                        ControlCommand::Enqueue { id } => {
+                            tasks.push(self.enqueue_track(id));
+                            continue;
+                        }
+                        ControlCommand::Status => continue,
+                    };
+                    tasks.push(self.update(message));
+                }
+                tasks.extend(self.check_schedules());
+                tasks.extend(self.check_sleep_timer());
+                tasks.extend(self.check_movement_pause());
+                self.sync_tray();
+                self.sync_media_controls();
+                self.sync_remote_status();
+                if tasks.is_empty() {
+                    Task::none()
+                } else {
+                    Task::batch(tasks)
+                }
+            }
+            Message::DismissToast(id) => {
+                self.notifications.dismiss(id);
+                Task::none()
+            }
+            Message::DismissStatus => {
+                self.notifications.dismiss_all();
+                Task::none()
+            }
+        }
+    }
+
+    fn view(&self) -> Element<'_, Message> {
+        if let Some(onboarding) = &self.onboarding {
+            return container(self.onboarding_view(onboarding))
+                .width(Length::Fill)
+                .height(Length::Fill)
+                .padding(32)
+                .center_x(Length::Fill)
+                .into();
+        }
+        if self.user_prefs.mini_player {
+            return self.mini_player_view();
+        }
+
+        let content = column![self.device_section()]
+            .push_maybe(self.sink_stats_row())
+            .push_maybe(self.resume_banner())
+            .push_maybe(self.schedule_banner())
+            .push_maybe(self.show_settings.then(|| self.settings_panel()))
+            .push_maybe(
+                self.monitor_pane
+                    .as_ref()
+                    .map(|pane| self.monitor_panel(pane)),
+            )
+            .push(self.playback_controls())
+            .push_maybe(self.selected_song.map(|id| self.song_settings_row(id)))
+            .push_maybe(self.score_overlay())
+            .push_maybe(
+                self.song_info
+                    .as_ref()
+                    .map(|panel| self.song_info_panel(panel)),
+            )
+            .push_maybe(self.render_job.as_ref().map(|job| self.render_panel(job)))
+            .push_maybe(
+                self.capability_warning
+                    .as_ref()
+                    .map(|warning| self.capability_warning_panel(warning)),
+            )
+            .push_maybe(self.library_load_panel())
+            .push_maybe(self.empty_state_panel())
+            .push_maybe(
+                self.folder_import
+                    .as_ref()
+                    .map(|job| self.folder_import_panel(job)),
+            )
+            .push_maybe(
+                self.playlist_builder
+                    .as_ref()
+                    .map(|builder| self.playlist_builder_view(builder)),
+            );
+        let content = if self.playlist_builder.is_some() {
+            content
+        } else {
+            content
+                .push(self.library_tabs())
+                .push(self.library_view())
+                .push(self.playlist_editor())
+        }
+        .push(self.status_banner())
+        .spacing(16)
+        .padding(16);
+
+        let content = container(content)
+            .width(Length::Fill)
+            .height(Length::Fill)
+            .align_x(Horizontal::Left)
+            .align_y(Vertical::Top);
+        match self.countdown {
+            Some(remaining) => stack![content, countdown_overlay(remaining)].into(),
+            None => content.into(),
+        }
+    }
+
+    fn subscription(&self) -> Subscription<Message> {
+        let mut subscriptions = vec![
+            time::every(TICK_INTERVAL).map(|_| Message::Tick),
+            window::resize_events().map(|(_, size)| Message::WindowResized(size)),
+            window::close_requests().map(Message::WindowCloseRequested),
+            keyboard::on_key_press(key_message),
+        ];
+        if self.user_prefs.watch_folder.is_some() {
+            subscriptions.push(time::every(INBOX_POLL_INTERVAL).map(|_| Message::InboxPoll));
+        }
+        if matches!(self.playback_phase, PlaybackPhase::Playing) && self.seek_preview.is_none() {
+            subscriptions.push(window::frames().map(|_| Message::AnimationFrame));
+        }
+        Subscription::batch(subscriptions)
+    }
+
+    fn theme(&self) -> Theme {
+        self.user_prefs
+            .theme
+            .as_deref()
+            .and_then(|name| Theme::ALL.iter().find(|theme| theme.to_string() == name))
+            .cloned()
+            .unwrap_or(Theme::Dark)
+    }
+
+    fn set_repeat_passes(&mut self, passes: u8) {
+        self.repeat_passes = passes;
+        self.midi_player.set_repeat(passes);
+    }
+
+    fn handle_player_event(&mut self, event: PlayerEvent) -> Option<Task<Message>> {
+        if !matches!(
+            event,
+            PlayerEvent::Countdown(_) | PlayerEvent::ChannelActivity(_)
+        ) {
+            self.countdown = None;
+        }
+        match event {
+            PlayerEvent::Countdown(remaining) => {
+                self.countdown = Some(remaining);
+                None
+            }
+            PlayerEvent::Started {
+                position,
+                total,
+                count_in,
+            } => {
+                self.paused_at = None;
+                self.playback_clock = Some(Instant::now());
+                self.score_offset_ms = position.as_millis() as i64 - count_in.as_millis() as i64;
+                let mut webhook = None;
+                if let Some(entry_id) = self.now_playing {
+                    self.playback_history.record(entry_id);
+                    let session = ActiveSession {
+                        entry_id,
+                        started_at: chrono::Utc::now(),
+                        elapsed: Duration::ZERO,
+                        total,
+                    };
+                    webhook = self.webhook_task(NowPlayingKind::Started, &session, Duration::ZERO);
+                    self.active_session = Some(session);
+                }
+                self.playback_phase = PlaybackPhase::Playing;
+                self.playback_progress = Some(PlaybackProgress::new(position, total));
+                self.playback_pass = 1;
+                self.notifications.info(t!("Playback started"));
+                match (self.save_resume_task(position), webhook) {
+                    (Some(save), Some(webhook)) => Some(Task::batch([save, webhook])),
+                    (save, webhook) => save.or(webhook),
+                }
+            }
+            PlayerEvent::Tempo(bpm) => {
+                self.playback_bpm = Some(bpm);
+                None
+            }
+            PlayerEvent::ChannelActivity(velocities) => {
+                for (level, velocity) in self.channel_levels.iter_mut().zip(velocities) {
+                    *level = level.max(f32::from(velocity) / 127.0);
+                }
+                None
+            }
+            PlayerEvent::Repeated {
+                pass,
+                passes: _,
+                position,
+                total,
+            } => {
+                self.playback_pass = pass;
+                self.playback_clock = Some(Instant::now());
+                self.score_offset_ms = position.as_millis() as i64;
+                self.playback_progress = Some(PlaybackProgress::new(position, total));
+                None
+            }
+            PlayerEvent::Seeked { position, total } => {
+                self.playback_clock = Some(Instant::now());
+                self.score_offset_ms = position.as_millis() as i64;
+                if let Some(session) = self.active_session.as_mut() {
+                    session.elapsed = position;
+                }
+                self.playback_progress = Some(PlaybackProgress::new(position, total));
+                self.save_resume_task(position)
+            }
+            PlayerEvent::SilenceGap {
+                at,
+                length,
+                skipped,
+            } => {
+                if skipped.is_zero() {
+                    self.notifications.info(t!(
+                        "Silent gap of {seconds}s at {at}",
+                        seconds = length.as_secs(),
+                        at = format_duration(at)
+                    ));
+                } else {
+                    // The score overlay runs on wall-clock time, so move it along
+                    // with the player.
+                    self.score_offset_ms += skipped.as_millis() as i64;
+                    self.notifications.info(t!(
+                        "Skipped {seconds}s of silence at {at}",
+                        seconds = skipped.as_secs(),
+                        at = format_duration(at)
+                    ));
+                }
+                None
+            }
+            PlayerEvent::Progress { elapsed, total } => {
+                if let Some(session) = self.active_session.as_mut() {
+                    session.elapsed = elapsed;
+                }
+                self.playback_progress = Some(PlaybackProgress::new(elapsed, total));
+                let due = self
+                    .resume_saved_at
+                    .is_none_or(|saved| elapsed.abs_diff(saved) >= RESUME_SAVE_INTERVAL);
+                if due {
+                    self.save_resume_task(elapsed)
+                } else {
+                    None
+                }
+            }
+            PlayerEvent::Finished => {
+                let finished = self.now_playing;
+                self.playback_bpm = None;
+                self.set_repeat_passes(1);
+                let save = self.finish_practice_session(true);
+                self.playback_clock = None;
+                self.playback_phase = PlaybackPhase::Finished;
+                let sink = self.current_sink.take();
+                let next_id = if self.sleep_timer_track_finished() {
+                    self.play_queue = None;
+                    None
+                } else if let Some(track) = self.resume_from_history() {
+                    Some(track)
+                } else {
+                    self.advance_queue(true)
+                };
+                let next = if let Some(next_id) = next_id {
+                    Some(self.continue_queue(finished, next_id, sink))
+                } else {
+                    self.notifications.info(t!("Playback finished"));
+                    self.resume_saved_at = None;
+                    Some(Task::perform(
+                        save_resume_state(None),
+                        Message::ResumeStateSaved,
+                    ))
+                };
+                match (save, next) {
+                    (Some(save), Some(next)) => Some(Task::batch([save, next])),
+                    (save, next) => save.or(next),
+                }
+            }
+            PlayerEvent::Stopped => {
+                self.playback_bpm = None;
+                self.playback_clock = None;
+                self.playback_phase = PlaybackPhase::Idle;
+                self.playback_progress = None;
+                self.notifications.info(t!("Playback stopped"));
+                self.refresh_sink_stats();
+                self.current_sink = None;
+                self.finish_practice_session(false)
+            }
+            PlayerEvent::Error(message) => {
+                // The player only fails when the sink rejects a message.
+                self.show_error(t!("Playback stopped"), AppError::send_failed(message));
+                self.playback_bpm = None;
+                self.playback_clock = None;
+                self.playback_phase = PlaybackPhase::Idle;
+                self.playback_progress = None;
+                self.refresh_sink_stats();
+                self.current_sink = None;
+                self.finish_practice_session(false)
+            }
+        }
+    }
+
+    fn finish_practice_session(&mut self, completed: bool) -> Option<Task<Message>> {
+        let session = self.active_session.take()?;
+        let elapsed = if completed {
+            session.total
+        } else {
+            session.elapsed
+        };
+        let kind = if completed {
+            NowPlayingKind::Finished
+        } else {
+            NowPlayingKind::Stopped
+        };
+        let webhook = self.webhook_task(kind, &session, elapsed);
+        // Even a song skipped straight away counts as heard for shuffling.
+        self.user_prefs.shuffle_history.record(session.entry_id);
+        if completed || elapsed * 2 > session.total {
+            let stats = self
+                .user_prefs
+                .play_stats
+                .entry(session.entry_id)
+                .or_default();
+            stats.count += 1;
+            stats.last_played = Some(chrono::Utc::now());
+        }
+        let save_prefs = self.save_preferences_task();
+        if elapsed < MIN_SESSION_LENGTH {
+            return Some(Task::batch([save_prefs].into_iter().chain(webhook)));
+        }
+        let entry_name = self
+            .library
+            .get(&session.entry_id)
+            .map(|entry| entry.name.clone())
+            .unwrap_or_default();
+        self.practice_log.record(PracticeSession {
+            entry_id: session.entry_id,
+            entry_name,
+            started_at: session.started_at,
+            played_secs: elapsed.as_secs_f64(),
+            total_secs: session.total.as_secs_f64(),
+            completed,
+            accuracy: None,
+        });
+        let save_log = Task::perform(
+            save_practice_log(self.practice_log.clone()),
+            Message::PracticeLogSaved,
+        );
+        Some(Task::batch(
+            [save_prefs].into_iter().chain(webhook).chain([save_log]),
+        ))
+    }
+
+    /// Posts `kind` for `session` to the configured webhook, if any.
+    fn webhook_task(
+        &self,
+        kind: NowPlayingKind,
+        session: &ActiveSession,
+        played: Duration,
+    ) -> Option<Task<Message>> {
+        let url = self.user_prefs.webhook_url.clone()?;
+        let event = NowPlayingEvent {
+            event: kind,
+            track_id: session.entry_id,
+            track_name: self
+                .library
+                .get(&session.entry_id)
+                .map(|entry| entry.name.clone())
+                .unwrap_or_default(),
+            duration_secs: session.total.as_secs_f64(),
+            played_secs: played.as_secs_f64(),
+            device: self.selected_device_name(),
+            started_at: session.started_at,
+            sent_at: chrono::Utc::now(),
+        };
+        Some(Task::perform(
+            post_webhook(url, event),
+            Message::WebhookSent,
+        ))
+    }
+
+    fn selected_device_name(&self) -> Option<String> {
+        let id = self.selected_device?;
+        self.devices
+            .iter()
+            .find(|choice| choice.id == id)
+            .map(|choice| choice.name.clone())
+    }
+
+    fn show_error(&mut self, context: &str, err: AppError) {
+        let message = format!("{context}: {err}");
+        match err.action() {
+            Some(action) => self.notifications.error_with_action(message, action),
+            None => self.notifications.error(message),
+        }
+    }
+
+    /// Pushes playback state and recent tracks to the tray menu when they
+    /// have changed.
+    fn sync_tray(&mut self) {
+        let Some(tray) = &self.tray else {
+            return;
+        };
+        let state = TrayState {
+            playing: matches!(self.playback_phase, PlaybackPhase::Playing),
+            now_playing: self
+                .now_playing
+                .and_then(|id| self.library.get(&id))
+                .map(|entry| entry.name.clone()),
+            recent: self
+                .user_prefs
+                .recently_played
+                .iter()
+                .filter_map(|id| self.library.get(id))
+                .take(TRAY_RECENT_LIMIT)
+                .map(|entry| (entry.id, entry.name.clone()))
+                .collect(),
+        };
+        if state != self.tray_state {
+            tray.update(state.clone());
+            self.tray_state = state;
+        }
+    }
+
+    /// Publishes now-playing metadata to the desktop. Position is only
+    /// republished once it has drifted a second from the last update.
+    fn sync_media_controls(&mut self) {
+        let Some(controls) = &self.media_controls else {
+            return;
+        };
+        let (status, track_id, position) = match (&self.playback_phase, self.paused_at) {
+            (PlaybackPhase::Playing, _) => (
+                MediaStatus::Playing,
+                self.now_playing,
+                self.playback_progress
+                    .as_ref()
+                    .map_or(Duration::ZERO, |progress| progress.elapsed),
+            ),
+            (_, Some((id, position))) => (MediaStatus::Paused, Some(id), position),
+            _ => (MediaStatus::Stopped, None, Duration::ZERO),
+        };
+        let state = MediaState {
+            status,
+            track: track_id
+                .and_then(|id| self.library.get(&id))
+                .map(|entry| (entry.id, entry.name.clone())),
+            length: self
+                .playback_progress
+                .as_ref()
+                .map(|progress| progress.total),
+            position,
+        };
+        let drifted = state.position.abs_diff(self.media_state.position) >= Duration::from_secs(1);
+        let changed = state.status != self.media_state.status
+            || state.track != self.media_state.track
+            || state.length != self.media_state.length;
+        if changed || drifted {
+            controls.update(state.clone());
+            self.media_state = state;
+        }
+    }
+
+    /// Tells remote control clients about playback. Like the media
+    /// controls, position alone is only republished once it has drifted a
+    /// second.
+    fn sync_remote_status(&mut self) {
+        if self.remote_control_handle.is_none() {
+            return;
+        }
+        let (state, track_id, position) = match (&self.playback_phase, self.paused_at) {
+            (PlaybackPhase::Playing, _) => (
+                ControlState::Playing,
+                self.now_playing,
+                self.playback_progress
+                    .as_ref()
+                    .map_or(Duration::ZERO, |progress| progress.elapsed),
+            ),
+            (_, Some((id, position))) => (ControlState::Paused, Some(id), position),
+            _ => (ControlState::Stopped, None, Duration::ZERO),
+        };
+        let control_track = |id: Uuid| ControlTrack {
+            id,
+            name: self
+                .library
+                .get(&id)
+                .map(|entry| entry.name.clone())
+                .unwrap_or_default(),
+        };
+        let status = ControlStatus {
+            state,
+            track: track_id.map(control_track),
+            position_secs: position.as_secs_f64(),
+            duration_secs: self
+                .playback_progress
+                .as_ref()
+                .map(|progress| progress.total.as_secs_f64()),
+            queue: self
+                .play_queue
+                .as_ref()
+                .map(|queue| queue.tracks.iter().copied().map(control_track).collect())
+                .unwrap_or_default(),
+            queue_index: self.play_queue.as_ref().map(|queue| queue.index),
+        };
+        self.remote_status.send_if_modified(|current| {
+            let drifted = (status.position_secs - current.position_secs).abs() >= 1.0;
+            let changed = ControlStatus {
+                position_secs: current.position_secs,
+                ..status.clone()
+            } != *current;
+            if changed || drifted {
+                *current = status;
+            }
+            changed || drifted
+        });
+    }
+
+    /// Stops any running control server and starts one with the current
+    /// settings if remote control is enabled.
+    fn restart_remote_control(&mut self) -> Task<Message> {
+        if let Some(handle) = self.remote_control_handle.take() {
+            handle.abort();
+        }
+        let previous = self.remote_control_server.take();
+        if let Some(previous) = &previous {
+            previous.abort();
+        }
+        let settings = &mut self.user_prefs.remote_control;
+        self.remote_port_draft = settings.port.to_string();
+        if !settings.enabled {
+            return Task::none();
+        }
+        if settings.token.is_empty() {
+            settings.token = new_remote_control_token();
+        }
+        let addr = std::net::SocketAddr::from(([0, 0, 0, 0], settings.port));
+        let token = settings.token.clone();
+        let commands = self.remote_command_tx.clone();
+        let status = self.remote_status.subscribe();
+        let (stopped_tx, stopped_rx) = tokio::sync::oneshot::channel();
+        self.remote_control_server = Some(tokio::spawn(async move {
+            // An aborted server lets go of the port once its task has ended.
+            if let Some(previous) = previous {
+                let _ = previous.await;
+            }
+            let result = async {
+                let server = ControlServer::bind(addr, token)
+                    .await
+                    .map_err(|err| format!("{err:?}"))?;
+                log::info!("remote control listening on {addr}");
+                server
+                    .run(commands, status)
+                    .await
+                    .map_err(|err| format!("{err:?}"))
+            }
+            .await;
+            let _ = stopped_tx.send(result);
+        }));
+        let (task, handle) = Task::perform(
+            async move { stopped_rx.await.unwrap_or(Ok(())) },
+            Message::RemoteControlStopped,
+        )
+        .abortable();
+        self.remote_control_handle = Some(handle);
+        task
+    }
+
+    /// Adds `id` to the end of the queue, or plays it when nothing is
+    /// queued.
+    fn enqueue_track(&mut self, id: Uuid) -> Task<Message> {
+        let Some(name) = self.library.get(&id).map(|entry| entry.name.clone()) else {
+            self.notifications.error(t!("Track not available"));
+            return Task::none();
+        };
+        let Some(queue) = self.play_queue.as_mut() else {
+            return self.start_single_track(id);
+        };
+        queue.tracks.push(id);
+        queue.inserted.insert(id);
+        self.notifications
+            .info(t!("Added {name} to the queue", name = name));
+        self.preload_next_task()
+    }
+
+    /// Puts `id` right after the playing song, leaving the rest of the
+    /// queue as it is, or plays it when nothing is queued.
+    fn play_next(&mut self, id: Uuid) -> Task<Message> {
+        let Some(name) = self.library.get(&id).map(|entry| entry.name.clone()) else {
+            self.notifications.error(t!("Track not available"));
+            return Task::none();
+        };
+        let Some(queue) = self.play_queue.as_mut() else {
+            return self.start_single_track(id);
+        };
+        let at = (queue.index + 1).min(queue.tracks.len());
+        queue.tracks.insert(at, id);
+        queue.inserted.insert(id);
+        self.notifications
+            .info(t!("{name} plays next", name = name));
+        self.preload_next_task()
+    }
+
+    /// Closes `id` once playback is stopped, every channel has been sent
+    /// All Notes Off, device connections are closed and preferences, the
+    /// queue position and the practice log are saved. Devices that do not
+    /// answer within [`SHUTDOWN_TIMEOUT`] are abandoned.
+    fn shutdown_task(&mut self, id: window::Id) -> Task<Message> {
+        let position = self
+            .playback_progress
+            .as_ref()
+            .map(|progress| progress.elapsed)
+            .or(self.paused_at.map(|(_, position)| position));
+        // Stopped before saving so the resume state records a clean exit.
+        self.midi_player.stop_now();
+        self.playback_phase = PlaybackPhase::Idle;
+
+        // Finishing the session first keeps its play count in the saved
+        // preferences.
+        let mut saves: Vec<_> = self.finish_practice_session(false).into_iter().collect();
+        saves.push(self.save_preferences_task());
+        saves.extend(position.and_then(|position| self.save_resume_task(position)));
+
+        let sink = self.current_sink.take();
+        let manager = self.device_manager.clone();
+        let quiet_devices = Task::future(async move {
+            let quiet = async {
+                if let Some(sink) = sink
+                    && let Err(err) = sink.panic().await
+                {
+                    log::warn!("failed to silence the device on exit: {err:?}");
+                }
+                let closing = manager.lock().await.disconnect_all();
+                closing.await;
+            };
+            if tokio::time::timeout(SHUTDOWN_TIMEOUT, quiet).await.is_err() {
+                log::warn!("devices did not close in time; exiting anyway");
+            }
+        })
+        .discard();
+
+        Task::batch(saves)
+            .chain(quiet_devices)
+            .chain(window::close(id))
+    }
+
+    /// Sizes the window for the current layout.
+    fn resize_window_task(&self) -> Task<Message> {
+        let size = if self.user_prefs.mini_player {
+            MINI_PLAYER_SIZE
+        } else {
+            self.full_window_size
+        };
+        window::get_latest().and_then(move |id| window::resize(id, size))
+    }
+
+    /// Switches to imported preferences and brings everything that depends
+    /// on them up to date.
+    fn apply_imported_preferences(&mut self, mut prefs: UserPreferences) -> Task<Message> {
+        // An import never sends a set-up user back through the wizard, nor
+        // skips it mid-setup; finishing the wizard marks it complete.
+        prefs.onboarding_complete = self.user_prefs.onboarding_complete;
+        self.user_prefs = prefs;
+        i18n::set_language(self.user_prefs.language);
+        self.library
+            .set_all_details(self.user_prefs.song_details.clone());
+        self.refresh_tree_cache();
+        self.webhook_draft = self.user_prefs.webhook_url.clone().unwrap_or_default();
+        self.open_send_trace();
+        Task::batch([
+            self.schedule_tree_rebuild(),
+            self.save_preferences_task(),
+            self.sync_device_settings_task(),
+            self.virtual_port_task(),
+            self.restart_remote_control(),
+            self.index_watch_library_task(),
+            self.index_music_folders_task(),
+        ])
+    }
+
+    /// Saves the preferences, first bringing what is worked out from them
+    /// up to date.
+    fn save_preferences_task(&mut self) -> Task<Message> {
+        self.refresh_playlist_index();
+        Task::perform(
+            save_user_preferences(self.user_prefs.clone()),
+            Message::PreferencesSaved,
+        )
+    }
+
+    /// Rebuilds which playlists list each song. Smart playlists aren't
+    /// included, as their songs come and go with their tags.
+    /// Changes a folder's label, forgetting it once it is back to plain.
+    fn update_folder_label(
+        &mut self,
+        folder_id: String,
+        update: impl FnOnce(&mut FolderLabel),
+    ) -> Task<Message> {
+        let labels = &mut self.user_prefs.folder_labels;
+        let label = labels.entry(folder_id.clone()).or_default();
+        update(label);
+        if *label == FolderLabel::default() {
+            labels.remove(&folder_id);
+        }
+        self.save_preferences_task()
+    }
+
+    fn refresh_playlist_index(&mut self) {
+        self.playlist_index.clear();
+        for playlist in &self.user_prefs.playlists {
+            if playlist.rule.is_some() {
+                continue;
+            }
+            for id in &playlist.tracks {
+                let playlists = self.playlist_index.entry(*id).or_default();
+                if !playlists.contains(&playlist.id) {
+                    playlists.push(playlist.id);
+                }
+            }
+        }
+    }
+
+    fn schedule_tree_rebuild(&mut self) -> Task<Message> {
+        self.tree_loading = true;
+        self.tree_request_id = self.tree_request_id.wrapping_add(1);
+        let request_id = self.tree_request_id;
+        let entries = self.library.entries().to_vec();
+        let durations = entries
+            .iter()
+            .filter_map(|entry| Some((entry.id, self.entry_duration(entry)?)))
+            .collect();
+        let prefs = &self.user_prefs;
+        let grouping = (
+            prefs.tree_grouping,
+            prefs.ratings.clone(),
+            prefs.tags.clone(),
+        );
+        let rebuild = Task::perform(
+            compute_tree_data(entries, durations, grouping),
+            move |result| match result {
+                Ok(tree) => Message::TreeDataLoaded { request_id, tree },
+                Err(err) => Message::TreeDataFailed {
+                    request_id,
+                    error: err,
+                },
+            },
+        );
+        Task::batch([rebuild, self.refresh_metadata_task()])
+    }
+
+    /// Rebuilds the tree when it is grouped by what just changed.
+    fn regroup_tree_task(&mut self, changed: TreeGrouping) -> Task<Message> {
+        if self.user_prefs.tree_grouping == changed {
+            self.schedule_tree_rebuild()
+        } else {
+            Task::none()
+        }
+    }
+
+    /// Looks up durations for library files that have not been seen yet.
+    /// The first run also picks up files changed since the last session.
+    fn refresh_metadata_task(&mut self) -> Task<Message> {
+        if self.metadata_refreshing {
+            return Task::none();
+        }
+        let entries = self.library.entries();
+        let up_to_date = self
+            .library_metadata
+            .as_ref()
+            .is_some_and(|metadata| entries.iter().all(|entry| metadata.contains(&entry.path)));
+        if up_to_date || entries.is_empty() {
+            return Task::none();
+        }
+        self.metadata_refreshing = true;
+        let paths = entries.iter().map(|entry| entry.path.clone()).collect();
+        Task::perform(
+            refresh_library_metadata(self.library_metadata.clone(), paths),
+            Message::LibraryMetadataRefreshed,
+        )
+    }
+
+    fn entry_duration(&self, entry: &midi_piano_rs::midi::MidiEntry) -> Option<Duration> {
+        self.library_metadata.as_ref()?.duration(&entry.path)
+    }
+
+    fn entry_tempo(&self, entry: &midi_piano_rs::midi::MidiEntry) -> Option<TempoSummary> {
+        self.library_metadata.as_ref()?.tempo(&entry.path)
+    }
+
+    /// Every library entry as exported for other tools, folder by folder.
+    fn catalog_entries(&self) -> Vec<CatalogEntry> {
+        let mut entries: Vec<CatalogEntry> = self
+            .library
+            .entries()
+            .iter()
+            .map(|entry| CatalogEntry {
+                id: entry.id,
+                name: entry.name.clone(),
+                folder: entry.library_path.clone().unwrap_or_default(),
+                path: entry.path.clone(),
+                duration_secs: self
+                    .entry_duration(entry)
+                    .map(|duration| duration.as_secs_f64()),
+                rating: self.user_prefs.ratings.get(&entry.id).copied(),
+                favorite: self.user_prefs.favorites.contains(&entry.id),
+            })
+            .collect();
+        let collation = self.user_prefs.title_collation;
+        entries.sort_by_cached_key(|entry| {
+            (
+                entry
+                    .folder
+                    .iter()
+                    .map(|folder| collation.key(folder))
+                    .collect::<Vec<_>>(),
+                collation.key(&entry.name),
+            )
+        });
+        entries
+    }
+
+    /// Takes ratings and favorites from a catalog for the songs it lists
+    /// that are in the library, returning how many changed.
+    fn apply_catalog(&mut self, entries: Vec<CatalogEntry>) -> usize {
+        let prefs = &mut self.user_prefs;
+        let mut updated = 0;
+        for entry in entries {
+            if self.library.get(&entry.id).is_none() {
+                continue;
+            }
+            let rating_changed = match entry.rating {
+                Some(stars) => prefs.ratings.insert(entry.id, stars) != Some(stars),
+                None => prefs.ratings.remove(&entry.id).is_some(),
+            };
+            let favorite_changed = if entry.favorite {
+                prefs.favorites.insert(entry.id)
+            } else {
+                prefs.favorites.remove(&entry.id)
+            };
+            if rating_changed || favorite_changed {
+                updated += 1;
+            }
+        }
+        updated
+    }
+
+    fn apply_tree_data(&mut self, tree: LibraryNode) {
+        self.tree_loading = false;
+        self.library_tree = tree;
+        if self
+            .selected_folder
+            .as_ref()
+            .is_none_or(|id| !self.library_tree.contains(id))
+        {
+            self.selected_folder = Some("root".into());
+        }
+        self.refresh_tree_cache();
+        self.refresh_folder_entries();
+    }
+
+    /// Clears everything remembered about `ids`: ratings, favorites, tags,
+    /// per-song settings, statistics and their places in playlists.
+    fn forget_entries(&mut self, ids: &HashSet<Uuid>) {
+        let prefs = &mut self.user_prefs;
+        prefs.ratings.retain(|id, _| !ids.contains(id));
+        prefs.favorites.retain(|id| !ids.contains(id));
+        prefs.tags.retain(|id, _| !ids.contains(id));
+        works::forget(&mut prefs.works, ids);
+        prefs.song_settings.retain(|id, _| !ids.contains(id));
+        prefs.play_stats.retain(|id, _| !ids.contains(id));
+        prefs.recently_played.retain(|id| !ids.contains(id));
+        for playlist in &mut prefs.playlists {
+            playlist.tracks.retain(|id| !ids.contains(id));
+        }
+        self.playlist_draft.tracks.retain(|id| !ids.contains(id));
+    }
+
+    fn refresh_folder_entries(&mut self) {
+        let folder_id = self.selected_folder.as_deref().unwrap_or("root");
+        let groups = self.user_prefs.tree_groups();
+        self.folder_entries = self
+            .library
+            .entries()
+            .iter()
+            .filter(|entry| folder_contains(folder_id, entry, false, groups))
+            .map(|entry| entry.id)
+            .collect();
+    }
+
+    fn inbox_scan_task(&mut self) -> Task<Message> {
+        let Some(config) = self.user_prefs.watch_folder.clone() else {
+            return Task::none();
+        };
+        if self.inbox_scan_running {
+            return Task::none();
+        }
+        self.inbox_scan_running = true;
+        Task::perform(import_inbox(config), Message::InboxScanned)
+    }
+
+    fn index_watch_library_task(&self) -> Task<Message> {
+        match self.user_prefs.watch_folder.clone() {
+            Some(config) => Task::perform(scan_watch_library(config), Message::WatchLibraryIndexed),
+            None => Task::none(),
+        }
+    }
+
+    /// The profile assigned to the selected device.
+    fn selected_profile(&self) -> Option<&DeviceProfile> {
+        self.user_prefs
+            .device_profiles
+            .profile_for(self.selected_device?)
+    }
+
+    fn selected_profile_mut(&mut self) -> Option<&mut DeviceProfile> {
+        let id = self.selected_profile()?.id;
+        self.user_prefs.device_profiles.get_mut(id)
+    }
+
+    /// The selected device's profile filter when it has one, otherwise the
+    /// default used by all other devices.
+    fn edited_output_filter(&mut self) -> &mut OutputFilter {
+        match self.selected_profile().map(|profile| profile.id) {
+            Some(id) => {
+                &mut self
+                    .user_prefs
+                    .device_profiles
+                    .get_mut(id)
+                    .expect("profile looked up above")
+                    .filter
+            }
+            None => &mut self.user_prefs.default_output_filter,
+        }
+    }
+
+    /// The filter connections to `device_id` are made with.
+    fn output_filter_for(&self, device_id: Uuid) -> OutputFilter {
+        self.user_prefs
+            .device_profiles
+            .profile_for(device_id)
+            .map_or(self.user_prefs.default_output_filter, |profile| {
+                profile.filter
+            })
+    }
+
+    fn quiet_mode_changed(&mut self) -> Task<Message> {
+        self.next_song_filter_changed(t!("Quiet mode applies from the next song"))
+    }
+
+    /// Quiet mode and Piano Mode are part of the output filter, so a song
+    /// already connected keeps the old setting and is told so with
+    /// `pending`; the next one connects afresh.
+    fn next_song_filter_changed(&mut self, pending: &str) -> Task<Message> {
+        self.preloaded = None;
+        if self.now_playing.is_some() {
+            self.notifications.info(pending);
+        }
+        Task::batch([
+            self.save_preferences_task(),
+            self.sync_device_settings_task(),
+        ])
+    }
+
+    fn output_filters_changed(&mut self) -> Task<Message> {
+        self.notifications
+            .info(t!("Output filter updated; applies from the next song"));
+        Task::batch([
+            self.save_preferences_task(),
+            self.sync_device_settings_task(),
+        ])
+    }
+
+    /// Open connections keep the settings they were made with. Close them
+    /// unless a song is using one, so the next song connects with the new
+    /// settings; otherwise tell the user with `pending`.
+    fn connection_settings_changed(&mut self, pending: &str) -> Task<Message> {
+        let reconnect = if matches!(
+            self.playback_phase,
+            PlaybackPhase::Playing | PlaybackPhase::Preparing
+        ) {
+            self.notifications.info(pending);
+            Task::none()
+        } else {
+            let manager = self.device_manager.clone();
+            Task::future(async move {
+                let closing = manager.lock().await.disconnect_all();
+                closing.await;
+            })
+            .discard()
+        };
+        Task::batch([
+            self.save_preferences_task(),
+            self.sync_device_settings_task(),
+            reconnect,
+        ])
+    }
+
+    /// Opens or closes the virtual output port to match the preferences.
+    fn virtual_port_task(&self) -> Task<Message> {
+        if !VIRTUAL_PORTS_SUPPORTED {
+            return Task::none();
+        }
+        let manager = self.device_manager.clone();
+        let enabled = self.user_prefs.virtual_port;
+        Task::perform(
+            async move {
+                manager
+                    .lock()
+                    .await
+                    .set_virtual_port(enabled)
+                    .map_err(|err| format!("{err:?}"))
+            },
+            Message::VirtualPortUpdated,
+        )
+    }
+
+    /// Applies the chosen Bluetooth adapter and scans again, so devices
+    /// found through other adapters go away.
+    fn rescan_ble_adapter_task(&mut self) -> Task<Message> {
+        self.is_scanning_devices = true;
+        self.sync_device_settings_task().chain(Task::perform(
+            refresh_devices(self.device_manager.clone()),
+            Message::DevicesRefreshed,
+        ))
+    }
+
+    /// Hands the output filters, BLE settings and send trace to the device
+    /// manager.
+    fn sync_device_settings_task(&self) -> Task<Message> {
+        let manager = self.device_manager.clone();
+        let default = self.user_prefs.default_output_filter;
+        let per_device = self.user_prefs.device_profiles.filters();
+        let reliable_writes = self.user_prefs.device_profiles.reliable_write_devices();
+        let quiet = self
+            .user_prefs
+            .quiet_mode
+            .then_some(self.user_prefs.quiet_limit);
+        let piano_mode = self.user_prefs.piano_mode;
+        let ble_packet_size = self.user_prefs.ble_packet_size.map(usize::from);
+        let ble_adapter = self.user_prefs.ble_adapter.clone();
+        let usb_running_status = self.user_prefs.usb_running_status;
+        let trace = self.send_trace.clone();
+        Task::future(async move {
+            let mut manager = manager.lock().await;
+            manager.set_output_filters(default, per_device);
+            manager.set_quiet(quiet);
+            manager.set_piano_mode(piano_mode);
+            manager.set_ble_packet_size(ble_packet_size);
+            manager.set_reliable_ble_writes(reliable_writes);
+            manager.set_ble_adapter(ble_adapter);
+            manager.set_usb_running_status(usb_running_status);
+            manager.set_trace(trace);
+        })
+        .discard()
+    }
+
+    /// Opens the send trace the preferences ask for, or closes it.
+    fn open_send_trace(&mut self) {
+        let Some(format) = self.user_prefs.send_trace else {
+            self.send_trace = None;
+            return;
+        };
+        if self
+            .send_trace
+            .as_ref()
+            .is_some_and(|trace| trace.format() == format)
+        {
+            return;
+        }
+        self.send_trace = match SendTrace::open(
+            &PathBuf::from(SEND_TRACE_DIR),
+            format,
+            send_trace::DEFAULT_MAX_FILE_BYTES,
+        ) {
+            Ok(trace) => Some(Arc::new(trace)),
+            Err(err) => {
+                self.notifications.error(t!(
+                    "Failed to open the send log: {err}",
+                    err = format!("{err:?}")
+                ));
+                None
+            }
+        };
+    }
+
+    fn send_trace_changed(&mut self) -> Task<Message> {
+        self.open_send_trace();
+        self.notifications
+            .info(t!("Send log setting applies from the next song"));
+        Task::batch([
+            self.save_preferences_task(),
+            self.sync_device_settings_task(),
+        ])
+    }
+
+    fn index_music_folders_task(&self) -> Task<Message> {
+        if self.user_prefs.music_folders.is_empty() {
+            return Task::none();
+        }
+        Task::perform(
+            scan_music_folders(self.user_prefs.music_folders.clone()),
+            Message::MusicFolderIndexed,
+        )
+    }
+
+    fn remote_catalog_task(&mut self) -> Task<Message> {
+        let Some(url) = self.user_prefs.remote_catalog_url.clone() else {
+            return Task::none();
+        };
+        self.is_loading_remote_catalog = true;
+        Task::perform(
+            async move {
+                tokio::task::spawn_blocking(move || remote::fetch_catalog(&url))
+                    .await
+                    .map_err(|err| format!("catalog task failed: {err:?}"))?
+                    .map_err(|err| format!("{err:?}"))
+            },
+            Message::RemoteCatalogLoaded,
+        )
+    }
+
+    /// Replaces the library's remote entries with the current catalog.
+    fn add_remote_entries(&mut self) {
+        self.library
+            .remove_origin(midi_piano_rs::midi::MidiOrigin::Remote);
+        for entry in &self.remote_catalog {
+            self.library.add_remote_entry(
+                &entry.name,
+                &entry.url,
+                self.remote_cache.path_for(&entry.url),
+            );
+        }
+    }
+
+    fn add_indexed_files(&mut self, found: Vec<InboxImport>) -> Task<Message> {
+        let before = self.library.entries().len();
+        for import in found {
+            if let Err(err) = self
+                .library
+                .add_local_file_at(&import.path, Some(import.library_path))
+            {
+                log::warn!("failed to add imported file: {err:?}");
+            }
+        }
+        if self.library.entries().len() != before {
+            return self.schedule_tree_rebuild();
+        }
+        Task::none()
+    }
+
+    fn ble_scan_task(manager: Arc<Mutex<MidiDeviceManager>>) -> Task<Message> {
+        Task::run(
+            stream::unfold(manager, |manager| async move {
+                tokio::time::sleep(Duration::from_secs(5)).await;
+                let result = {
+                    let mut guard = manager.lock().await;
+                    guard.scan_ble_once().await.map_err(|err| err.to_string())
+                };
+                Some((Message::BleScanUpdate(result), manager))
+            }),
+            |message| message,
+        )
+    }
+
+    fn refresh_tree_cache(&mut self) {
+        let mut items = Vec::new();
+        collect_tree_items(
+            &self.library_tree,
+            0,
+            &self.user_prefs.expanded_folders,
+            self.user_prefs.title_collation,
+            &mut items,
+        );
+        self.tree_cache = items;
+    }
+
+    /// Moves the selection through the visible entries. Arrow keys take the
+    /// focus when nothing else has it; Enter and typing only act on a list
+    /// that was focused by clicking an entry or navigating into it.
+    fn handle_library_key(&mut self, key: LibraryKey) -> Task<Message> {
+        let ids: Vec<Uuid> = self
+            .visible_entries()
+            .iter()
+            .map(|entry| entry.id)
+            .collect();
+        if ids.is_empty() {
+            return Task::none();
+        }
+        let current = self
+            .selected_song
+            .and_then(|id| ids.iter().position(|candidate| *candidate == id));
+        let target = match key {
+            LibraryKey::Previous => current.map_or(ids.len() - 1, |index| index.saturating_sub(1)),
+            LibraryKey::Next => current.map_or(0, |index| (index + 1).min(ids.len() - 1)),
+            LibraryKey::First => 0,
+            LibraryKey::Last => ids.len() - 1,
+            LibraryKey::Leave => {
+                self.library_focused = false;
+                return Task::none();
+            }
+            LibraryKey::Activate => {
+                return match (self.library_focused, current) {
+                    (true, Some(index)) => Task::done(Message::StartPlayback(ids[index])),
+                    _ => Task::none(),
+                };
+            }
+            LibraryKey::Type(typed) => {
+                if !self.library_focused {
+                    return Task::none();
+                }
+                let now = Instant::now();
+                if self
+                    .type_ahead_at
+                    .is_none_or(|at| now.duration_since(at) > TYPE_AHEAD_TIMEOUT)
+                {
+                    self.type_ahead.clear();
+                }
+                self.type_ahead_at = Some(now);
+                self.type_ahead.push_str(&typed.to_lowercase());
+                let prefix = self.type_ahead.clone();
+                let Some(index) = self
+                    .visible_entries()
+                    .iter()
+                    .position(|entry| entry.name.to_lowercase().starts_with(&prefix))
+                else {
+                    return Task::none();
+                };
+                index
+            }
+        };
+        self.library_focused = true;
+        self.selected_song = Some(ids[target]);
+        let y = if ids.len() > 1 {
+            target as f32 / (ids.len() - 1) as f32
+        } else {
+            0.0
+        };
+        scrollable::snap_to(library_list_id(), scrollable::RelativeOffset { x: 0.0, y })
+    }
+
+    fn apply_search(&mut self) {
+        self.applied_search = SearchQuery::new(&self.search_query);
+        self.search_edited_at = None;
+    }
+
+    fn visible_entries(&self) -> Vec<&midi_piano_rs::midi::MidiEntry> {
+        let mut base: Vec<&midi_piano_rs::midi::MidiEntry> = match self.active_tab {
+            LibraryTab::Tree => self
+                .folder_entries
+                .iter()
+                .filter_map(|id| self.library.get(id))
+                .collect(),
+            LibraryTab::Favorites => self
+                .user_prefs
+                .favorites
+                .iter()
+                .filter_map(|id| self.library.get(id))
+                .collect(),
+            LibraryTab::Recent => self
+                .user_prefs
+                .recently_played
+                .iter()
+                .filter_map(|id| self.library.get(id))
+                .collect(),
+            LibraryTab::Lessons => Vec::new(),
+        };
+
+        if !self.applied_search.is_empty() {
+            base.retain(|entry| self.library.matches(&entry.id, &self.applied_search));
+        }
+        base.retain(|entry| self.tag_filter.matches(self.user_prefs.tags.get(&entry.id)));
+        if self.min_rating.0 > 0 {
+            base.retain(|entry| {
+                self.user_prefs.ratings.get(&entry.id).copied().unwrap_or(0) >= self.min_rating.0
+            });
+        }
+        if self.favorites_only {
+            base.retain(|entry| self.user_prefs.favorites.contains(&entry.id));
+        }
+
+        // Recent keeps play order so the piece to continue stays on top.
+        if self.active_tab != LibraryTab::Recent {
+            self.sort_entries(&mut base, self.user_prefs.library_sort);
+        }
+        base
+    }
+
+    /// Sorts by `order`, falling back to the name for ties and entries
+    /// never played.
+    fn sort_entries(&self, entries: &mut [&midi_piano_rs::midi::MidiEntry], order: LibrarySort) {
+        let stats = |entry: &midi_piano_rs::midi::MidiEntry| {
+            self.user_prefs
+                .play_stats
+                .get(&entry.id)
+                .copied()
+                .unwrap_or_default()
+        };
+        let ranks = self.title_ranks();
+        entries.sort_by_key(|entry| ranks.get(&entry.id).copied().unwrap_or(usize::MAX));
+        match order {
+            LibrarySort::Name => {}
+            LibrarySort::MostPlayed => {
+                entries.sort_by_key(|entry| std::cmp::Reverse(stats(entry).count));
             }
-            Message::DevicesRefreshed(result) => {
-                self.is_scanning_devices = false;
-                match result {
-                    Ok(descriptors) => {
-                        self.devices = descriptors.iter().map(DeviceChoice::from).collect();
-                        if let Some(selected) = self.selected_device {
-                            if !self.devices.iter().any(|choice| choice.id == selected) {
-                                self.selected_device = None;
-                            }
-                        }
-                        self.devices.sort_by(|a, b| a.name.cmp(&b.name));
-                        self.status_message = Some("Devices updated".into());
-                    }
-                    Err(err) => {
-                        self.error_message = Some(format!("Failed to refresh devices: {err}"));
-                    }
-                }
-                Task::none()
+            LibrarySort::RecentlyPlayed => {
+                entries.sort_by_key(|entry| std::cmp::Reverse(stats(entry).last_played));
             }
-            Message::BleScanUpdate(result) => {
-                match result {
-                    Ok(new_devices) => {
-                        if !new_devices.is_empty() {
-                            let mut added_names = Vec::new();
-                            for descriptor in new_devices {
-                                if let Some(existing) = self
-                                    .devices
-                                    .iter_mut()
-                                    .find(|choice| choice.id == descriptor.info.id)
-                                {
-                                    existing.name = descriptor.info.name.clone();
-                                } else {
-                                    self.devices.push(DeviceChoice::from(&descriptor));
-                                    added_names.push(descriptor.info.name.clone());
-                                }
-                            }
-                            if !added_names.is_empty() {
-                                self.devices.sort_by(|a, b| a.name.cmp(&b.name));
-                                self.status_message =
-                                    Some(format!("New BLE devices: {}", added_names.join(", ")));
-                            }
-                        }
-                    }
-                    Err(err) => {
-                        self.error_message = Some(format!("BLE scan failed: {err}"));
-                    }
+        }
+    }
+
+    /// Every library entry's place in title order, recomputed only when the
+    /// library or the collation changes.
+    fn title_ranks(&self) -> Ref<'_, HashMap<Uuid, usize>> {
+        let source = (self.library.revision(), self.user_prefs.title_collation);
+        if self.title_ranks.borrow().source != Some(source) {
+            let collation = source.1;
+            let mut entries: Vec<_> = self.library.entries().iter().collect();
+            entries.sort_by_cached_key(|entry| collation.key(&entry.name));
+            *self.title_ranks.borrow_mut() = TitleRanks {
+                source: Some(source),
+                ranks: entries
+                    .iter()
+                    .enumerate()
+                    .map(|(rank, entry)| (entry.id, rank))
+                    .collect(),
+            };
+        }
+        Ref::map(self.title_ranks.borrow(), |cache| &cache.ranks)
+    }
+
+    /// Assignments travel between machines, so pieces that are not bundled
+    /// assets are matched by name when the id is unknown locally.
+    fn resolve_assignment_item(&self, item: &AssignmentItem) -> Option<Uuid> {
+        if self.library.get(&item.entry_id).is_some() {
+            return Some(item.entry_id);
+        }
+        self.library
+            .entries()
+            .iter()
+            .find(|entry| entry.name == item.entry_name)
+            .map(|entry| entry.id)
+    }
+
+    /// Keeps the song info panel's title and composer, or goes back to the
+    /// file name when both are blank.
+    fn save_song_details(&mut self) -> Task<Message> {
+        let Some(panel) = self.song_info.as_mut() else {
+            return Task::none();
+        };
+        let id = panel.entry_id;
+        let details = SongDetails {
+            title: Some(panel.title_draft.clone()),
+            composer: Some(panel.composer_draft.clone()),
+        }
+        .trimmed();
+        if details.is_empty() {
+            self.user_prefs.song_details.remove(&id);
+        } else {
+            self.user_prefs.song_details.insert(id, details.clone());
+        }
+        self.library.set_details(id, details);
+        if let Some(entry) = self.library.get(&id) {
+            panel.name = entry.name.clone();
+        }
+        Task::batch([self.save_preferences_task(), self.schedule_tree_rebuild()])
+    }
+
+    fn update_song_settings(
+        &mut self,
+        id: Uuid,
+        change: impl FnOnce(&mut SongSettings),
+    ) -> Task<Message> {
+        let mut settings = self.song_settings(id);
+        change(&mut settings);
+        if settings == SongSettings::default() {
+            self.user_prefs.song_settings.remove(&id);
+        } else {
+            self.user_prefs.song_settings.insert(id, settings);
+        }
+        self.save_preferences_task()
+    }
+
+    fn song_settings(&self, id: Uuid) -> SongSettings {
+        self.user_prefs
+            .song_settings
+            .get(&id)
+            .copied()
+            .unwrap_or_default()
+    }
+
+    fn has_master_adjustments(&self) -> bool {
+        self.master_tempo_percent != 100 || self.master_transpose != 0
+    }
+
+    fn silence_watch_for(&self, id: Uuid) -> Option<SilenceWatch> {
+        let settings = self.user_prefs.silence_watch;
+        if self.song_settings(id).silence_watch_disabled {
+            return None;
+        }
+        let fast_forward = match settings.action {
+            SilenceAction::Off => return None,
+            SilenceAction::Notify => false,
+            SilenceAction::FastForward => true,
+        };
+        Some(SilenceWatch {
+            threshold: Duration::from_secs(settings.threshold_secs as u64),
+            fast_forward,
+        })
+    }
+
+    fn queue_key_shift(&self, id: Uuid) -> i8 {
+        self.play_queue
+            .as_ref()
+            .and_then(|queue| queue.key_shifts.get(&id).copied())
+            .unwrap_or(0)
+    }
+
+    /// Per-song settings with the session master values and any queue key
+    /// matching shift layered on top.
+    fn playback_adjustments(&self, id: Uuid) -> PlaybackAdjustments {
+        let song = self.song_settings(id);
+        let playlist = self.queue_playlist_defaults();
+        let tempo = song.tempo_percent as u32 * self.master_tempo_percent as u32 / 100
+            * playlist.tempo_percent.unwrap_or(100) as u32
+            / 100;
+        let adjustments = PlaybackAdjustments {
+            tempo_percent: tempo.clamp(
+                PlaybackAdjustments::MIN_TEMPO_PERCENT as u32,
+                PlaybackAdjustments::MAX_TEMPO_PERCENT as u32,
+            ) as u16,
+            transpose: song
+                .transpose
+                .saturating_add(self.master_transpose)
+                .saturating_add(playlist.transpose)
+                .saturating_add(self.queue_key_shift(id))
+                .clamp(-48, 48),
+            muted_channels: song.muted_channels,
+            hand_split: song.hand_split,
+            program_override: self.user_prefs.program_override,
+            merge_channel: None,
+            percussion: song
+                .percussion
+                .or_else(|| self.selected_profile().map(|profile| profile.percussion))
+                .unwrap_or_default(),
+        };
+        match self.selected_device {
+            Some(device_id) if self.remapped_songs.contains(&id) => {
+                let remap = self.capabilities_for(device_id).remap_adjustments();
+                PlaybackAdjustments {
+                    muted_channels: adjustments.muted_channels | remap.muted_channels,
+                    program_override: adjustments.program_override.or(remap.program_override),
+                    merge_channel: remap.merge_channel,
+                    ..adjustments
                 }
-                Task::none()
             }
-            Message::UserDataLoaded(result) => {
-                match result {
-                    Ok(prefs) => {
-                        self.user_prefs = prefs;
-                        self.status_message = Some("Preferences loaded".into());
-                    }
-                    Err(err) => {
-                        self.error_message = Some(format!("Failed to load preferences: {err}"));
-                    }
-                }
-                Task::none()
+            _ => adjustments,
+        }
+    }
+
+    /// Defaults of the playlist being played, if the queue came from one.
+    fn queue_playlist_defaults(&self) -> PlaylistDefaults {
+        match self.play_queue.as_ref().map(|queue| &queue.mode) {
+            Some(QueueMode::Playlist(id)) => self
+                .user_prefs
+                .playlists
+                .iter()
+                .find(|playlist| playlist.id == *id)
+                .map(|playlist| playlist.defaults)
+                .unwrap_or_default(),
+            _ => PlaylistDefaults::default(),
+        }
+    }
+
+    fn update_playlist_defaults(
+        &mut self,
+        id: Uuid,
+        change: impl FnOnce(&mut PlaylistDefaults),
+    ) -> Task<Message> {
+        if let Some(playlist) = self
+            .user_prefs
+            .playlists
+            .iter_mut()
+            .find(|playlist| playlist.id == id)
+        {
+            change(&mut playlist.defaults);
+        }
+        self.save_preferences_task()
+    }
+
+    fn capabilities_for(&self, device_id: Uuid) -> DeviceCapabilities {
+        self.user_prefs
+            .device_capabilities
+            .get(&device_id)
+            .copied()
+            .unwrap_or_default()
+    }
+
+    /// Playback position as followed in the printed score, including any
+    /// manual re-sync applied from the overlay and the device's latency.
+    fn score_time(&self) -> Option<Duration> {
+        let elapsed = self.playback_clock?.elapsed().as_millis() as i64;
+        let latency = self
+            .selected_profile()
+            .map_or(0, |profile| profile.latency_ms as i64);
+        let adjusted = (elapsed + self.score_offset_ms - latency).max(0);
+        Some(Duration::from_millis(adjusted as u64))
+    }
+
+    fn start_single_track(&mut self, track_id: Uuid) -> Task<Message> {
+        if self.library.get(&track_id).is_none() {
+            self.notifications
+                .error(t!("Selected track is not available"));
+            return Task::none();
+        }
+        let _ = self.queue_with_tracks(vec![track_id], track_id, QueueMode::Single, false);
+        let play = self.play_track(track_id);
+        self.record_recently_played(track_id);
+        Task::batch([play, self.save_preferences_task()])
+    }
+
+    fn play_work(&mut self, work_id: Uuid) -> Task<Message> {
+        let Some(work) = self.user_prefs.works.iter().find(|work| work.id == work_id) else {
+            self.notifications.error(t!("Work not found"));
+            return Task::none();
+        };
+        let name = work.name.clone();
+        let tracks: Vec<Uuid> = work
+            .movements
+            .iter()
+            .copied()
+            .filter(|id| self.library.get(id).is_some())
+            .collect();
+        let Some(&first) = tracks.first() else {
+            self.notifications
+                .error(t!("None of the work's movements are in the library"));
+            return Task::none();
+        };
+        match self.queue_with_tracks(tracks, first, QueueMode::Work(work_id), false) {
+            Some(start) => {
+                self.notifications.info(t!("Playing '{name}'", name = name));
+                self.play_track(start)
             }
-            Message::PreferencesSaved(result) => {
-                match result {
-                    Ok(()) => {
-                        self.status_message = Some("Preferences saved".into());
-                    }
-                    Err(err) => {
-                        self.error_message = Some(format!("Failed to save preferences: {err}"));
-                    }
-                }
-                Task::none()
+            None => Task::none(),
+        }
+    }
+
+    fn record_recently_played(&mut self, track_id: Uuid) {
+        let recent = &mut self.user_prefs.recently_played;
+        recent.retain(|id| *id != track_id);
+        recent.insert(0, track_id);
+        recent.truncate(RECENTLY_PLAYED_LIMIT);
+    }
+
+    fn play_favorites(&mut self, shuffle: bool) -> Task<Message> {
+        let mut entries: Vec<_> = self
+            .user_prefs
+            .favorites
+            .iter()
+            .filter_map(|id| self.library.get(id))
+            .collect();
+        self.sort_entries(&mut entries, LibrarySort::Name);
+        let tracks: Vec<Uuid> = entries.iter().map(|entry| entry.id).collect();
+        if tracks.is_empty() {
+            self.notifications
+                .error(t!("No favorites available to play"));
+            return Task::none();
+        }
+        let start_track = if shuffle {
+            self.shuffle_start(&tracks)
+        } else {
+            tracks[0]
+        };
+        if let Some(start) =
+            self.queue_with_tracks(tracks, start_track, QueueMode::Favorites, shuffle)
+        {
+            self.notifications.info(t!("Playing favorites"));
+            Task::batch([self.play_track(start), self.key_match_task()])
+        } else {
+            Task::none()
+        }
+    }
+
+    /// Library tracks of a playlist; smart playlists list every tagged match
+    /// in the playlist's order.
+    fn playlist_tracks(&self, playlist: &Playlist) -> Vec<Uuid> {
+        match &playlist.rule {
+            Some(rule) => {
+                let mut entries: Vec<_> = self
+                    .library
+                    .entries()
+                    .iter()
+                    .filter(|entry| rule.matches(self.user_prefs.tags.get(&entry.id)))
+                    .collect();
+                self.sort_entries(&mut entries, playlist.order);
+                entries.iter().map(|entry| entry.id).collect()
             }
-            Message::TreeDataLoaded {
-                request_id,
-                tree,
-                folders,
-            } => {
-                if request_id == self.tree_request_id {
-                    self.tree_loading = false;
-                    self.apply_tree_data(tree, folders);
-                }
-                Task::none()
+            None => playlist
+                .tracks
+                .iter()
+                .filter_map(|id| self.library.get(id).map(|entry| entry.id))
+                .collect(),
+        }
+    }
+
+    /// Queues every entry under a tree folder and its sub-folders, folder by
+    /// folder in tree order.
+    fn play_folder(&mut self, folder_id: String, shuffle: bool) -> Task<Message> {
+        let Some(name) = self
+            .library_tree
+            .find(&folder_id)
+            .map(|node| node.name.clone())
+        else {
+            self.notifications.error(t!("Folder not found"));
+            return Task::none();
+        };
+        let mut entries: Vec<_> = self
+            .library
+            .entries()
+            .iter()
+            .filter(|entry| folder_contains(&folder_id, entry, true, self.user_prefs.tree_groups()))
+            .collect();
+        let collation = self.user_prefs.title_collation;
+        entries.sort_by_cached_key(|entry| {
+            (
+                entry
+                    .library_path
+                    .iter()
+                    .flatten()
+                    .map(|folder| collation.key(folder))
+                    .collect::<Vec<_>>(),
+                collation.key(&entry.name),
+            )
+        });
+        let tracks: Vec<Uuid> = entries.iter().map(|entry| entry.id).collect();
+        if tracks.is_empty() {
+            self.notifications
+                .error(t!("Folder has no playable tracks"));
+            return Task::none();
+        }
+        let start_track = if shuffle {
+            self.shuffle_start(&tracks)
+        } else {
+            tracks[0]
+        };
+        if let Some(start) =
+            self.queue_with_tracks(tracks, start_track, QueueMode::Folder(folder_id), shuffle)
+        {
+            self.notifications
+                .info(t!("Playing folder '{name}'", name = name));
+            Task::batch([self.play_track(start), self.key_match_task()])
+        } else {
+            Task::none()
+        }
+    }
+
+    /// Queues and plays a playlist with its defaults applied. `device`, when
+    /// given, is used instead of the playlist's own.
+    fn play_playlist(
+        &mut self,
+        playlist_id: Uuid,
+        shuffle: bool,
+        device: Option<Uuid>,
+    ) -> Task<Message> {
+        let playlist = match self
+            .user_prefs
+            .playlists
+            .iter()
+            .find(|playlist| playlist.id == playlist_id)
+            .cloned()
+        {
+            Some(playlist) => playlist,
+            None => {
+                self.notifications.error(t!("Playlist not found"));
+                return Task::none();
             }
-            Message::TreeDataFailed { request_id, error } => {
-                if request_id == self.tree_request_id {
-                    self.tree_loading = false;
-                    self.error_message = Some(format!("Failed to update library tree: {error}"));
-                }
-                Task::none()
+        };
+
+        let tracks = self.playlist_tracks(&playlist);
+
+        if tracks.is_empty() {
+            self.notifications
+                .error(t!("Playlist has no playable tracks"));
+            return Task::none();
+        }
+
+        if let Some(device_id) = device.or(playlist.defaults.device_id) {
+            if !self.devices.iter().any(|choice| choice.id == device_id) {
+                self.notifications.error(t!(
+                    "The device for '{name}' is not connected",
+                    name = playlist.name
+                ));
+                return Task::none();
+            }
+            self.selected_device = Some(device_id);
+        }
+        let shuffle = shuffle || playlist.defaults.shuffle;
+
+        let start_track = if shuffle {
+            self.shuffle_start(&tracks)
+        } else {
+            tracks[0]
+        };
+
+        if let Some(start) = self.queue_with_tracks(
+            tracks,
+            start_track,
+            QueueMode::Playlist(playlist_id),
+            shuffle,
+        ) {
+            self.notifications
+                .info(t!("Playing playlist '{name}'", name = playlist.name));
+            Task::batch([self.play_track(start), self.key_match_task()])
+        } else {
+            Task::none()
+        }
+    }
+
+    /// `tracks` in random order, with those played lately at the end.
+    fn shuffled(&self, tracks: &[Uuid]) -> Vec<Uuid> {
+        let mut tracks = tracks.to_vec();
+        self.user_prefs.shuffle_history.shuffle(
+            &mut tracks,
+            self.user_prefs.shuffle_avoid(),
+            &mut rng(),
+        );
+        tracks
+    }
+
+    /// The song a shuffled queue of `tracks` starts with.
+    fn shuffle_start(&self, tracks: &[Uuid]) -> Uuid {
+        if self.user_prefs.weighted_shuffle {
+            self.weighted_draw(tracks, 1, None)
+                .first()
+                .copied()
+                .unwrap_or(tracks[0])
+        } else {
+            self.shuffled(tracks)[0]
+        }
+    }
+
+    /// `count` songs from `tracks` drawn by rating, repeats allowed.
+    fn weighted_draw(&self, tracks: &[Uuid], count: usize, previous: Option<Uuid>) -> Vec<Uuid> {
+        let ratings = &self.user_prefs.ratings;
+        shuffle::weighted_draw(
+            tracks,
+            count,
+            previous,
+            |id| shuffle::rating_weight(ratings.get(&id).copied()),
+            &mut rng(),
+        )
+    }
+
+    /// Queues `tracks`, starting with `start_track` or, outside
+    /// [`QueueMode::Single`], the first movement of its work. Works are
+    /// queued whole, their movements together and in order. Returns the
+    /// track to play first.
+    fn queue_with_tracks(
+        &mut self,
+        tracks: Vec<Uuid>,
+        start_track: Uuid,
+        mode: QueueMode,
+        shuffle: bool,
+    ) -> Option<Uuid> {
+        if self.library.get(&start_track).is_none() {
+            self.notifications
+                .error(t!("Selected track is not available"));
+            return None;
+        }
+        self.playback_history.resume();
+
+        let mut seen = HashSet::new();
+        let mut ordered = Vec::new();
+        for id in tracks.into_iter() {
+            if self.library.get(&id).is_some() && seen.insert(id) {
+                ordered.push(id);
             }
-            Message::RefreshDevices => {
-                self.is_scanning_devices = true;
-                Task::perform(
-                    refresh_devices(self.device_manager.clone()),
-                    Message::DevicesRefreshed,
-                )
+        }
+
+        if ordered.is_empty() {
+            self.play_queue = None;
+            return None;
+        }
+
+        if seen.insert(start_track) {
+            ordered.insert(0, start_track);
+        }
+
+        if shuffle && self.user_prefs.weighted_shuffle {
+            let drawn = self.weighted_draw(&ordered, ordered.len() - 1, Some(start_track));
+            ordered = std::iter::once(start_track).chain(drawn).collect();
+        } else if shuffle {
+            ordered = self.shuffled(&ordered);
+            // Moved rather than swapped so recent plays stay at the end.
+            ordered.retain(|id| *id != start_track);
+            ordered.insert(0, start_track);
+        } else if let Some(pos) = ordered.iter().position(|id| *id == start_track) {
+            ordered.swap(0, pos);
+        } else {
+            ordered.insert(0, start_track);
+        }
+
+        if !matches!(mode, QueueMode::Single) {
+            ordered = WorkIndex::new(&self.user_prefs.works).keep_together(&ordered);
+            ordered.retain(|id| self.library.get(id).is_some());
+        }
+        let start = ordered[0];
+        self.play_queue = Some(PlayQueue {
+            tracks: ordered,
+            index: 0,
+            mode,
+            key_shifts: HashMap::new(),
+            inserted: HashSet::new(),
+        });
+        self.selected_song = Some(start);
+        Some(start)
+    }
+
+    /// Detects the keys of the queued pieces in the background so the queue
+    /// can be rearranged once they are known.
+    fn key_match_task(&mut self) -> Task<Message> {
+        let mode = self.user_prefs.key_match_mode;
+        let Some(queue) = &self.play_queue else {
+            return Task::none();
+        };
+        if mode == KeyMatchMode::Off || queue.tracks.len() < 2 {
+            return Task::none();
+        }
+        // Rearranging would split works up.
+        let works = WorkIndex::new(&self.user_prefs.works);
+        if queue.tracks.iter().any(|id| works.work_of(*id).is_some()) {
+            return Task::none();
+        }
+        let paths: Vec<PathBuf> = queue
+            .tracks
+            .iter()
+            .filter_map(|id| self.library.get(id).map(|entry| entry.path.clone()))
+            .collect();
+        if paths.len() != queue.tracks.len() {
+            return Task::none();
+        }
+        self.key_match_request = self.key_match_request.wrapping_add(1);
+        let request = self.key_match_request;
+        Task::perform(detect_queue_keys(paths), move |result| {
+            Message::QueueKeysDetected(request, result)
+        })
+    }
+
+    /// Rearranges the not-yet-played part of the queue, starting from the
+    /// current track, using keys detected for every queued track.
+    fn apply_key_matching(&mut self, keys: &[Option<MusicalKey>]) {
+        let mode = self.user_prefs.key_match_mode;
+        let Some(queue) = self.play_queue.as_mut() else {
+            return;
+        };
+        if keys.len() != queue.tracks.len() {
+            return;
+        }
+        let upcoming = &queue.tracks[queue.index..];
+        let upcoming_keys: Vec<Option<MusicalKey>> = keys[queue.index..]
+            .iter()
+            .zip(upcoming)
+            .map(|(key, id)| {
+                let shift = queue.key_shifts.get(id).copied().unwrap_or(0);
+                key.map(|key| key.transposed(shift))
+            })
+            .collect();
+        let plan = key::plan_key_matched_order(&upcoming_keys, mode);
+
+        let reordered: Vec<Uuid> = plan.iter().map(|(offset, _)| upcoming[*offset]).collect();
+        let mut transposed = 0;
+        for ((_, shift), id) in plan.iter().zip(&reordered).skip(1) {
+            if *shift != 0 {
+                transposed += 1;
+                queue.key_shifts.insert(*id, *shift);
+            } else {
+                queue.key_shifts.remove(id);
             }
-            Message::DeviceSelected(id) => {
-                self.selected_device = Some(id);
-                Task::none()
+        }
+        queue.tracks.truncate(queue.index);
+        queue.tracks.extend(reordered);
+
+        self.notifications.info(if transposed > 0 {
+            t!(
+                "Queue arranged by key ({transposed} piece(s) transposed)",
+                transposed = transposed
+            )
+        } else {
+            t!("Queue arranged by key").into()
+        });
+    }
+
+    /// Leaves songs Previous stepped back to, returning to the queue's
+    /// current song, or to the latest song played when there is no queue.
+    fn resume_from_history(&mut self) -> Option<Uuid> {
+        let latest = self.playback_history.resume()?;
+        let track = self
+            .play_queue
+            .as_ref()
+            .and_then(|queue| queue.tracks.get(queue.index).copied())
+            .unwrap_or(latest);
+        self.selected_song = Some(track);
+        Some(track)
+    }
+
+    fn advance_queue(&mut self, forward: bool) -> Option<Uuid> {
+        let queue = self.play_queue.as_mut()?;
+        if queue.tracks.is_empty() {
+            self.play_queue = None;
+            return None;
+        }
+        if forward {
+            if queue.index + 1 < queue.tracks.len() {
+                queue.index += 1;
+                let track = queue.tracks[queue.index];
+                self.selected_song = Some(track);
+                Some(track)
+            } else {
+                self.play_queue = None;
+                self.notifications.info(t!("Queue finished"));
+                None
             }
-            Message::SongSelected(id) => {
-                self.selected_song = Some(id);
-                Task::none()
+        } else if queue.index > 0 {
+            queue.index -= 1;
+            let track = queue.tracks[queue.index];
+            self.selected_song = Some(track);
+            Some(track)
+        } else {
+            self.notifications.info(t!("Already at the beginning"));
+            None
+        }
+    }
+
+    fn queue_label(&self, queue: &PlayQueue) -> String {
+        let mode_label = match &queue.mode {
+            QueueMode::Single => t!("Single").to_string(),
+            QueueMode::Favorites => t!("Favorites").to_string(),
+            QueueMode::Playlist(id) => self
+                .user_prefs
+                .playlists
+                .iter()
+                .find(|playlist| &playlist.id == id)
+                .map(|playlist| playlist.name.clone())
+                .unwrap_or_else(|| t!("Playlist").into()),
+            QueueMode::Folder(id) => self
+                .library_tree
+                .find(id)
+                .map(|node| node.name.clone())
+                .unwrap_or_else(|| t!("Folder").into()),
+            QueueMode::Work(id) => self
+                .user_prefs
+                .works
+                .iter()
+                .find(|work| &work.id == id)
+                .map(|work| work.name.clone())
+                .unwrap_or_else(|| t!("Work").into()),
+        };
+        format!("{}: {}/{}", mode_label, queue.index + 1, queue.tracks.len())
+    }
+
+    fn current_track_label(&self) -> String {
+        if let Some(id) = self.selected_song
+            && let Some(entry) = self.library.get(&id)
+        {
+            let shift = self.queue_key_shift(id);
+            if shift != 0 {
+                return t!(
+                    "Now: {name} (key-matched {shift})",
+                    name = entry.name,
+                    shift = format!("{shift:+}")
+                );
             }
-            Message::SearchChanged(query) => {
-                self.search_query = query;
-                Task::none()
+            if let Some(composer) = &entry.composer {
+                return t!(
+                    "Now: {name} by {composer}",
+                    name = entry.name,
+                    composer = composer
+                );
             }
-            Message::SwitchTab(tab) => {
-                if self.active_tab != tab {
-                    self.active_tab = tab;
-                    if matches!(self.active_tab, LibraryTab::Tree) {
-                        if self.selected_folder.is_none() {
-                            self.selected_folder = Some("root".into());
+            return t!("Now: {name}", name = entry.name);
+        }
+        t!("Now: --").into()
+    }
+
+    /// Checks the bundled assets on a blocking task, streaming them into the
+    /// library a chunk at a time. A path that hangs, e.g. on an unresponsive
+    /// network mount, can be abandoned with [`Message::CancelLibraryLoad`].
+    fn load_library_task(&mut self) -> Task<Message> {
+        if self.library_load.is_some() {
+            return Task::none();
+        }
+        self.library_load_error = None;
+        let cancel = Arc::new(AtomicBool::new(false));
+        let (sender, receiver) = futures::channel::mpsc::unbounded();
+        let stop = cancel.clone();
+        tokio::task::spawn_blocking(move || {
+            let result =
+                MidiLibrary::load_assets_in_chunks(LIBRARY_LOAD_CHUNK, |chunk, progress| {
+                    let delivered = sender
+                        .unbounded_send(LibraryLoadUpdate::Chunk(Box::new(chunk), progress))
+                        .is_ok();
+                    if delivered && !stop.load(Ordering::Relaxed) {
+                        ControlFlow::Continue(())
+                    } else {
+                        ControlFlow::Break(())
+                    }
+                })
+                .map_err(|err| format!("{err:?}"));
+            let _ = sender.unbounded_send(LibraryLoadUpdate::Finished(result));
+        });
+        let (task, handle) = Task::run(receiver, Message::LibraryLoadUpdate).abortable();
+        self.library_load = Some(LibraryLoadJob {
+            progress: AssetProgress::default(),
+            cancel,
+            handle,
+        });
+        task
+    }
+
+    /// Walks a chosen folder on a blocking task, checking that each MIDI file
+    /// parses and streaming the results back so the library fills in as the
+    /// import runs.
+    fn start_folder_import(&mut self) -> Task<Message> {
+        if self.folder_import.is_some() {
+            self.notifications
+                .info(t!("A folder import is already running"));
+            return Task::none();
+        }
+        let Some(root) = rfd::FileDialog::new()
+            .set_title(t!("Add folder of MIDI files"))
+            .pick_folder()
+        else {
+            return Task::none();
+        };
+
+        let cancel = Arc::new(AtomicBool::new(false));
+        self.folder_import = Some(FolderImportJob {
+            name: root
+                .file_name()
+                .map(|name| name.to_string_lossy().into_owned())
+                .unwrap_or_else(|| root.display().to_string()),
+            done: 0,
+            total: 0,
+            cancel: cancel.clone(),
+        });
+        self.notifications
+            .info(t!("Scanning {folder}", folder = root.display()));
+
+        let (sender, receiver) = futures::channel::mpsc::unbounded();
+        tokio::task::spawn_blocking(move || {
+            let result = inbox::scan_music_folder(&root)
+                .map(|found| {
+                    let _ = sender.unbounded_send(FolderImportUpdate::Found(found.len()));
+                    let mut summary = FolderImportSummary::default();
+                    for import in found {
+                        if cancel.load(Ordering::Relaxed) {
+                            summary.cancelled = true;
+                            break;
                         }
-                        if self.tree_cache.is_empty() && !self.tree_loading {
-                            return self.schedule_tree_rebuild();
+                        let valid = std::fs::read(&import.path).is_ok_and(|bytes| {
+                            sequence::check_timecode(&bytes).is_ok()
+                                && midly::Smf::parse(&bytes).is_ok()
+                        });
+                        if !valid {
+                            log::warn!("skipping unreadable MIDI file {}", import.path.display());
+                            summary.skipped += 1;
                         }
+                        let _ = sender
+                            .unbounded_send(FolderImportUpdate::Imported(valid.then_some(import)));
                     }
+                    summary
+                })
+                .map_err(|err| format!("{err:?}"));
+            let _ = sender.unbounded_send(FolderImportUpdate::Finished(result));
+        });
+        Task::run(receiver, Message::FolderImportUpdate)
+    }
+
+    /// Adds a file written by an export to the library, returning its name.
+    fn add_exported_file(&mut self, path: PathBuf) -> Result<String, String> {
+        self.library
+            .add_local_file(path)
+            .map(|entry| entry.name.clone())
+            .map_err(|err| format!("{err:?}"))
+    }
+
+    fn start_wav_export(&mut self) -> Task<Message> {
+        if self.render_job.is_some() {
+            self.notifications
+                .info(t!("A WAV export is already running"));
+            return Task::none();
+        }
+        let Some(entry) = self
+            .selected_song
+            .and_then(|id| self.library.get(&id))
+            .cloned()
+        else {
+            self.notifications.error(t!("Select a MIDI file to export"));
+            return Task::none();
+        };
+
+        let mut tasks = Vec::new();
+        let soundfont_path = match self.user_prefs.soundfont_path.clone() {
+            Some(path) => path,
+            None => match pick_soundfont() {
+                Some(path) => {
+                    self.user_prefs.soundfont_path = Some(path.clone());
+                    tasks.push(self.save_preferences_task());
+                    path
                 }
-                Task::none()
-            }
-            Message::ToggleFolder(folder_id) => {
-                self.selected_folder = Some(folder_id.clone());
-                if !self.expanded_folders.remove(&folder_id) {
-                    self.expanded_folders.insert(folder_id);
-                }
-                self.refresh_tree_cache();
-                Task::none()
-            }
-            Message::SelectFolder(folder_id) => {
-                if self.folder_entries.contains_key(&folder_id) {
-                    self.selected_folder = Some(folder_id);
-                }
-                Task::none()
-            }
-            Message::SetRating(id, rating) => {
-                if rating == 0 {
-                    self.user_prefs.ratings.remove(&id);
-                } else if rating <= 5 {
-                    self.user_prefs.ratings.insert(id, rating);
-                }
-                self.status_message = Some("Rating updated".into());
-                self.save_preferences_task()
+                None => return Task::none(),
+            },
+        };
+
+        let Some(output) = rfd::FileDialog::new()
+            .set_title(t!("Export WAV"))
+            .add_filter(t!("WAV audio"), &["wav"])
+            .set_file_name(format!("{}.wav", entry.name))
+            .save_file()
+        else {
+            return Task::batch(tasks);
+        };
+
+        let cancel = Arc::new(AtomicBool::new(false));
+        self.render_job = Some(RenderJob {
+            name: entry.name.clone(),
+            output: output.clone(),
+            progress: 0.0,
+            cancel: cancel.clone(),
+        });
+        self.notifications
+            .info(t!("Rendering {name}", name = entry.name));
+
+        let (sender, receiver) = futures::channel::mpsc::unbounded();
+        tokio::task::spawn_blocking(move || {
+            let progress_sender = sender.clone();
+            let result = (|| {
+                let sequence = MidiSequence::from_file(&entry.path)?;
+                let soundfont = SoundFont::from_file(&soundfont_path)?;
+                render::render_to_wav(&sequence, &soundfont, &output, &cancel, |progress| {
+                    let _ =
+                        progress_sender.unbounded_send(RenderUpdate::Progress(progress.fraction()));
+                })
+            })()
+            .map_err(|err| format!("{err:?}"));
+            let _ = sender.unbounded_send(RenderUpdate::Finished(result));
+        });
+        tasks.push(Task::run(receiver, Message::RenderUpdate));
+        Task::batch(tasks)
+    }
+
+    fn play_track(&mut self, track_id: Uuid) -> Task<Message> {
+        self.play_track_from(track_id, Duration::ZERO)
+    }
+
+    /// Moves on from `finished` to `track_id`, first pausing for as long as
+    /// their work asks when `track_id` is its next movement.
+    fn continue_queue(
+        &mut self,
+        finished: Option<Uuid>,
+        track_id: Uuid,
+        sink: Option<SharedMidiSink>,
+    ) -> Task<Message> {
+        let pause = finished
+            .and_then(|finished| {
+                self.user_prefs
+                    .works
+                    .iter()
+                    .find(|work| work.movement_after(finished) == Some(track_id))
+            })
+            .map(Work::pause)
+            .filter(|pause| !pause.is_zero());
+        let Some(pause) = pause else {
+            return self.play_queued_track(track_id, sink);
+        };
+        self.pending_movement = Some(PendingMovement {
+            track_id,
+            sink,
+            at: Instant::now() + pause,
+        });
+        self.notifications.info(t!(
+            "Next movement in {seconds} s",
+            seconds = pause.as_secs()
+        ));
+        Task::none()
+    }
+
+    /// Starts the movement waiting in [`PendingMovement`] once its pause is
+    /// over.
+    fn check_movement_pause(&mut self) -> Option<Task<Message>> {
+        let pending = self
+            .pending_movement
+            .take_if(|pending| pending.at <= Instant::now())?;
+        Some(self.play_queued_track(pending.track_id, pending.sink))
+    }
+
+    /// Moves the queue on to `track_id`. When it was preloaded and the
+    /// previous track's connection is still open, playback starts right
+    /// away instead of loading the file and connecting again.
+    fn play_queued_track(&mut self, track_id: Uuid, sink: Option<SharedMidiSink>) -> Task<Message> {
+        self.pending_movement = None;
+        // A reset is sent when connecting, so songs that need one connect
+        // afresh.
+        let preloaded = self.preloaded.take().filter(|preloaded| {
+            preloaded.track_id == track_id
+                && self.selected_device == Some(preloaded.device_id)
+                && self.playback_adjustments(track_id) == preloaded.adjustments
+                && self.output_filter_for(preloaded.device_id).reset.is_none()
+        });
+        match (preloaded, sink) {
+            (Some(preloaded), Some(sink)) if !self.is_preparing_playback => {
+                self.selected_song = Some(track_id);
+                self.update(Message::PlaybackPrepared(Ok(PreparedPlayback {
+                    track_id,
+                    sequence: preloaded.sequence,
+                    sink,
+                    position: Duration::ZERO,
+                })))
             }
-            Message::ToggleFavorite(id) => {
-                if !self.user_prefs.favorites.remove(&id) {
-                    self.user_prefs.favorites.insert(id);
-                    self.status_message = Some("Added to favorites".into());
-                } else {
-                    self.status_message = Some("Removed from favorites".into());
+            _ => self.play_track(track_id),
+        }
+    }
+
+    /// Starts a connected song, first checking it against what the device
+    /// can play. Songs that need more are remapped straight away when the
+    /// device allows it, and otherwise wait for the user to choose.
+    fn start_prepared(&mut self, prepared: PreparedPlayback) -> Task<Message> {
+        let Some(device_id) = self.selected_device else {
+            return self.begin_playback(prepared);
+        };
+        let capabilities = self.capabilities_for(device_id);
+        if capabilities.is_unrestricted() || self.remapped_songs.contains(&prepared.track_id) {
+            return self.begin_playback(prepared);
+        }
+        let issues = capabilities.issues(&prepared.sequence);
+        if issues.is_empty() {
+            return self.begin_playback(prepared);
+        }
+        if capabilities.auto_remap {
+            return self.begin_remapped(prepared);
+        }
+        self.playback_phase = PlaybackPhase::Idle;
+        self.capability_warning = Some(CapabilityWarning {
+            device_id,
+            prepared,
+            issues,
+        });
+        Task::none()
+    }
+
+    /// Fits the song to the device and starts it.
+    fn begin_remapped(&mut self, mut prepared: PreparedPlayback) -> Task<Message> {
+        let Some(device_id) = self.selected_device else {
+            return self.begin_playback(prepared);
+        };
+        let remap = self.capabilities_for(device_id).remap_adjustments();
+        prepared.sequence = Arc::new(prepared.sequence.adjusted(remap));
+        self.remapped_songs.insert(prepared.track_id);
+        self.notifications
+            .info(t!("Song remapped to fit the device"));
+        self.begin_playback(prepared)
+    }
+
+    fn begin_playback(&mut self, prepared: PreparedPlayback) -> Task<Message> {
+        self.capability_warning = None;
+        let silence_watch = self.silence_watch_for(prepared.track_id);
+        self.midi_player.set_lead_in(self.user_prefs.lead_in.into());
+        self.midi_player.set_metronome(self.user_prefs.metronome);
+        self.midi_player
+            .set_progress_interval(self.user_prefs.progress_interval());
+        self.midi_player
+            .set_fade(Duration::from_millis(self.user_prefs.fade_ms.into()));
+        match self.midi_player.start_playback_from(
+            prepared.sequence.clone(),
+            prepared.sink.clone(),
+            silence_watch,
+            prepared.position,
+        ) {
+            Ok(_) => {
+                if prepared.position.is_zero() && !prepared.sequence.validation.is_clean() {
+                    self.report_malformed_messages(&prepared.sequence.validation);
                 }
-                self.save_preferences_task()
+                self.now_playing = Some(prepared.track_id);
+                self.playing_sequence = Some(prepared.sequence.clone());
+                self.current_sink = Some(prepared.sink);
+                self.playback_phase = PlaybackPhase::Playing;
+                self.playback_progress = Some(PlaybackProgress::new(
+                    prepared.position,
+                    prepared.sequence.duration,
+                ));
+                self.preload_next_task()
             }
-            Message::PlaylistDraftAdd(id) => {
-                if self.library.get(&id).is_none() {
-                    self.error_message = Some("Selected track is not available".into());
-                } else if !self.playlist_draft.tracks.contains(&id) {
-                    self.playlist_draft.tracks.push(id);
-                    self.status_message = Some("Track added to draft playlist".into());
-                }
+            Err(err) => {
+                self.show_error(t!("Failed to start playback"), AppError::new(err));
+                self.playback_phase = PlaybackPhase::Idle;
+                self.playback_progress = None;
                 Task::none()
             }
-            Message::PlaylistDraftRemove(index) => {
-                if index < self.playlist_draft.tracks.len() {
-                    self.playlist_draft.tracks.remove(index);
-                    self.status_message = Some("Track removed from draft playlist".into());
-                }
-                Task::none()
+        }
+    }
+
+    /// Tells the user which tracks of the song had messages left out or
+    /// fixed on loading, so a bad file is not mistaken for a bad device.
+    fn report_malformed_messages(&mut self, report: &ValidationReport) {
+        let tracks: Vec<String> = report
+            .tracks()
+            .map(|(track, counts)| {
+                t!(
+                    "track {number}: {dropped} skipped, {fixed} fixed",
+                    number = track + 1,
+                    dropped = counts.dropped,
+                    fixed = counts.fixed
+                )
+            })
+            .collect();
+        self.notifications.info(t!(
+            "Malformed messages in this song ({tracks})",
+            tracks = tracks.join("; ")
+        ));
+    }
+
+    /// Loads the track after the playing one in the background, so the
+    /// queue can move on to it without a pause.
+    fn preload_next_task(&mut self) -> Task<Message> {
+        let Some(next_id) = self
+            .play_queue
+            .as_ref()
+            .and_then(|queue| queue.tracks.get(queue.index + 1).copied())
+        else {
+            return Task::none();
+        };
+        let (Some(device_id), Some(entry)) = (self.selected_device, self.library.get(&next_id))
+        else {
+            return Task::none();
+        };
+        let adjustments = self.playback_adjustments(next_id);
+        if self.preloaded.as_ref().is_some_and(|preloaded| {
+            preloaded.track_id == next_id && preloaded.adjustments == adjustments
+        }) {
+            return Task::none();
+        }
+        // Remote songs that are not cached yet are downloaded when their
+        // turn comes.
+        if !entry.path.exists() {
+            return Task::none();
+        }
+        let load = SequenceLoad {
+            source: MidiSource::File(entry.path.clone()),
+            adjustments,
+            timing: self.timing_capture.clone(),
+        };
+        Task::perform(
+            async move {
+                let sequence = tokio::task::spawn_blocking(move || load.load())
+                    .await
+                    .map_err(|err| format!("preload task failed: {err:?}"))?
+                    .map_err(|err| format!("{err:?}"))?;
+                Ok(PreloadedTrack {
+                    track_id: next_id,
+                    device_id,
+                    adjustments,
+                    sequence: Arc::new(sequence),
+                })
+            },
+            Message::NextTrackPreloaded,
+        )
+    }
+
+    fn play_track_from(&mut self, track_id: Uuid, position: Duration) -> Task<Message> {
+        let Some(path) = self.library.get(&track_id).map(|entry| entry.path.clone()) else {
+            self.notifications.error(t!("Track not available"));
+            return Task::none();
+        };
+        self.play_track_source(track_id, MidiSource::File(path), position)
+    }
+
+    /// Prepares `track_id` for playback from `source`. Remote entries that
+    /// have not been downloaded yet are fetched first and played from memory.
+    fn play_track_source(
+        &mut self,
+        track_id: Uuid,
+        source: MidiSource,
+        position: Duration,
+    ) -> Task<Message> {
+        if self.is_preparing_playback {
+            self.notifications.info(t!("Already preparing a track"));
+            return Task::none();
+        }
+        self.stop_preview();
+        self.pending_movement = None;
+
+        let entry = match self.library.get(&track_id).cloned() {
+            Some(entry) => entry,
+            None => {
+                self.notifications.error(t!("Track not available"));
+                return Task::none();
             }
-            Message::PlaylistDraftNameChanged(name) => {
-                self.playlist_draft.name = name;
-                Task::none()
+        };
+
+        let device_id = match self.selected_device {
+            Some(id) => id,
+            None => {
+                self.notifications
+                    .error(t!("Select a MIDI output device first"));
+                return Task::none();
             }
-            Message::PlaylistDraftClear => {
-                self.playlist_draft = PlaylistDraft::default();
-                self.status_message = Some("Playlist draft cleared".into());
-                Task::none()
+        };
+
+        if let Some(url) = entry.source_url.clone()
+            && matches!(&source, MidiSource::File(path) if !path.exists())
+        {
+            self.is_preparing_playback = true;
+            self.playback_phase = PlaybackPhase::Preparing;
+            self.notifications
+                .info(t!("Downloading {name}", name = entry.name));
+            let cache = self.remote_cache.clone();
+            return self.cancellable_preparation(Task::perform(
+                async move {
+                    tokio::task::spawn_blocking(move || cache.fetch_bytes(&url))
+                        .await
+                        .map_err(|err| format!("download task failed: {err:?}"))?
+                        .map(Arc::from)
+                        .map_err(|err| format!("{err:?}"))
+                },
+                move |result| Message::RemoteDownloaded(track_id, position, result),
+            ));
+        }
+
+        self.is_preparing_playback = true;
+        self.playback_phase = PlaybackPhase::Preparing;
+        self.notifications
+            .info(t!("Preparing {name}", name = entry.name));
+        self.selected_song = Some(track_id);
+        let adjustments = self.playback_adjustments(track_id);
+
+        self.cancellable_preparation(Task::perform(
+            prepare_playback(
+                track_id,
+                SequenceLoad {
+                    source,
+                    adjustments,
+                    timing: self.timing_capture.clone(),
+                },
+                position,
+                device_id,
+                self.device_manager.clone(),
+                self.user_prefs.connect_timeout(),
+            ),
+            Message::PlaybackPrepared,
+        ))
+    }
+
+    /// Plays the opening of `track_id` on the selected device, leaving the
+    /// queue, history and play counts alone. Songs cannot be previewed
+    /// while another one plays on the device.
+    fn preview_song(&mut self, track_id: Uuid) -> Task<Message> {
+        if self.is_preparing_playback || matches!(self.playback_phase, PlaybackPhase::Playing) {
+            self.notifications
+                .info(t!("Stop playback to preview a song"));
+            return Task::none();
+        }
+        let Some(entry) = self.library.get(&track_id) else {
+            self.notifications.error(t!("Track not available"));
+            return Task::none();
+        };
+        let Some(device_id) = self.selected_device else {
+            self.notifications
+                .error(t!("Select a MIDI output device first"));
+            return Task::none();
+        };
+        let load = SequenceLoad {
+            source: MidiSource::File(entry.path.clone()),
+            adjustments: self.playback_adjustments(track_id),
+            timing: None,
+        };
+        self.stop_preview();
+        self.preview_requests = self.preview_requests.wrapping_add(1);
+        let request = self.preview_requests;
+        let cancel = Arc::new(Notify::new());
+        self.preview = Some(SongPreview {
+            track_id,
+            request,
+            cancel: cancel.clone(),
+        });
+        Task::perform(
+            preview_song(
+                load,
+                device_id,
+                self.device_manager.clone(),
+                self.user_prefs.connect_timeout(),
+                cancel,
+            ),
+            move |result| Message::PreviewFinished(request, result),
+        )
+    }
+
+    fn stop_preview(&mut self) {
+        if let Some(preview) = self.preview.take() {
+            preview.cancel.notify_one();
+        }
+    }
+
+    /// Lets [`Message::CancelPreparing`] abort `task`.
+    fn cancellable_preparation(&mut self, task: Task<Message>) -> Task<Message> {
+        let (task, handle) = task.abortable();
+        self.preparing_handle = Some(handle);
+        task
+    }
+
+    /// Fades the level down over the last [`SLEEP_FADE`] of a timed sleep
+    /// timer, then stops.
+    fn check_sleep_timer(&mut self) -> Option<Task<Message>> {
+        let Some(SleepTimer::Until(deadline)) = self.sleep_timer else {
+            return None;
+        };
+        let remaining = deadline.saturating_duration_since(Instant::now());
+        if !remaining.is_zero() {
+            let fraction = remaining.as_secs_f32() / SLEEP_FADE.as_secs_f32();
+            self.midi_player
+                .set_level((fraction.min(1.0) * 100.0) as u8);
+            return None;
+        }
+        self.end_sleep_timer();
+        let playing = matches!(
+            self.playback_phase,
+            PlaybackPhase::Playing | PlaybackPhase::Preparing
+        ) || self.paused_at.is_some();
+        playing.then(|| {
+            self.notifications.info(t!("Sleep timer stopped playback"));
+            self.update(Message::StopPressed)
+        })
+    }
+
+    /// Counts a finished track against the sleep timer, returning whether
+    /// playback should stop here.
+    fn sleep_timer_track_finished(&mut self) -> bool {
+        let Some(SleepTimer::Tracks(tracks)) = self.sleep_timer.as_mut() else {
+            return false;
+        };
+        *tracks = tracks.saturating_sub(1);
+        if *tracks > 0 {
+            return false;
+        }
+        self.end_sleep_timer();
+        self.notifications.info(t!("Sleep timer stopped playback"));
+        true
+    }
+
+    fn end_sleep_timer(&mut self) {
+        self.sleep_timer = None;
+        self.sleep_timer_choice = SleepTimerChoice::Off;
+        self.midi_player.set_level(100);
+    }
+
+    fn sleep_timer_label(&self) -> Option<String> {
+        Some(match self.sleep_timer? {
+            SleepTimer::Until(deadline) => t!(
+                "Stops in {time}",
+                time = format_duration(deadline.saturating_duration_since(Instant::now()))
+            ),
+            SleepTimer::Tracks(1) => t!("Stops after this track").to_owned(),
+            SleepTimer::Tracks(tracks) => {
+                t!("Stops after {tracks} tracks", tracks = tracks)
             }
-            Message::PlaylistDraftSave => {
-                if self.playlist_draft.tracks.is_empty() {
-                    self.error_message =
-                        Some("Add at least one track before saving a playlist".into());
-                    return Task::none();
-                }
-                let name = if self.playlist_draft.name.trim().is_empty() {
-                    format!("Playlist {}", self.user_prefs.playlists.len() + 1)
-                } else {
-                    self.playlist_draft.name.trim().to_owned()
-                };
-                let tracks = self.playlist_draft.tracks.clone();
-                if let Some(active_id) = self.selected_playlist {
-                    if let Some(existing) = self
-                        .user_prefs
-                        .playlists
-                        .iter_mut()
-                        .find(|playlist| playlist.id == active_id)
-                    {
-                        existing.name = name.clone();
-                        existing.tracks = tracks.clone();
-                        self.status_message = Some(format!("Playlist '{}' updated", existing.name));
-                    } else {
-                        let playlist = Playlist::new(name.clone(), tracks);
-                        self.selected_playlist = Some(playlist.id);
-                        self.user_prefs.playlists.push(playlist);
-                        self.status_message = Some(format!("Playlist '{}' created", name));
-                    }
-                } else {
-                    let playlist = Playlist::new(name.clone(), tracks);
-                    self.selected_playlist = Some(playlist.id);
-                    self.user_prefs.playlists.push(playlist);
-                    self.status_message = Some(format!("Playlist '{}' created", name));
-                }
-                self.playlist_draft.name = name;
-                self.save_preferences_task()
+        })
+    }
+
+    /// Selects the default output when nothing is selected and it has been
+    /// found.
+    fn select_default_device(&mut self) {
+        if self.selected_device.is_none()
+            && let Some(default) = self.user_prefs.default_device
+            && self.devices.iter().any(|choice| choice.id == default)
+        {
+            self.selected_device = Some(default);
+        }
+    }
+
+    fn update_schedule(&mut self, id: Uuid, change: impl FnOnce(&mut Schedule)) -> Task<Message> {
+        if let Some(schedule) = self
+            .user_prefs
+            .schedules
+            .iter_mut()
+            .find(|schedule| schedule.id == id)
+        {
+            change(schedule);
+        }
+        self.schedules_changed()
+    }
+
+    /// Drops a running countdown, which may no longer match its schedule.
+    /// The next tick looks again.
+    fn schedules_changed(&mut self) -> Task<Message> {
+        self.schedule_countdown = None;
+        self.save_preferences_task()
+    }
+
+    /// Starts the countdown for a schedule coming up, and its playlist once
+    /// the countdown runs out. Starts missed by more than the countdown,
+    /// e.g. while the computer slept, are skipped.
+    fn check_schedules(&mut self) -> Option<Task<Message>> {
+        let now = chrono::Local::now();
+        if let Some(countdown) = &self.schedule_countdown {
+            return (now >= countdown.due).then(|| self.start_scheduled());
+        }
+        let (schedule_id, due) =
+            schedule::next_due(&self.user_prefs.schedules, self.schedule_checked_at)?;
+        if now - due > schedule::COUNTDOWN {
+            self.schedule_checked_at = due;
+            return None;
+        }
+        if due - now > schedule::COUNTDOWN {
+            return None;
+        }
+        self.schedule_countdown = Some(ScheduleCountdown { schedule_id, due });
+        let schedule = self
+            .user_prefs
+            .schedules
+            .iter()
+            .find(|schedule| schedule.id == schedule_id)?;
+        self.notifications.info(t!(
+            "{playlist} starts in {seconds}s",
+            playlist = self.playlist_name(schedule.playlist_id),
+            seconds = (due - now).num_seconds().max(0)
+        ));
+        // Bluetooth devices may need a scan before they can be used.
+        let device_known = schedule
+            .device_id
+            .is_none_or(|id| self.devices.iter().any(|choice| choice.id == id));
+        (!device_known).then(|| self.update(Message::RefreshDevices))
+    }
+
+    fn start_scheduled(&mut self) -> Task<Message> {
+        let Some(countdown) = self.schedule_countdown.take() else {
+            return Task::none();
+        };
+        self.schedule_checked_at = countdown.due;
+        let Some(schedule) = self
+            .user_prefs
+            .schedules
+            .iter()
+            .find(|schedule| schedule.id == countdown.schedule_id)
+            .cloned()
+        else {
+            return Task::none();
+        };
+        let Some(playlist_id) = schedule.playlist_id else {
+            return Task::none();
+        };
+        if matches!(
+            self.playback_phase,
+            PlaybackPhase::Playing | PlaybackPhase::Preparing
+        ) {
+            self.notifications.info(t!(
+                "Scheduled playlist skipped; something is already playing"
+            ));
+            return Task::none();
+        }
+        if schedule
+            .device_id
+            .is_some_and(|device_id| !self.devices.iter().any(|choice| choice.id == device_id))
+        {
+            self.notifications
+                .error(t!("The scheduled device is not connected"));
+            return Task::none();
+        }
+        self.play_playlist(playlist_id, false, schedule.device_id)
+    }
+
+    fn playlist_name(&self, id: Option<Uuid>) -> String {
+        id.and_then(|id| {
+            self.user_prefs
+                .playlists
+                .iter()
+                .find(|playlist| playlist.id == id)
+        })
+        .map_or_else(
+            || t!("Missing playlist").to_owned(),
+            |playlist| playlist.name.clone(),
+        )
+    }
+
+    /// Picks up the saved queue where it stopped. The saved device is used
+    /// when it is connected; otherwise devices are rescanned once first.
+    fn resume_playback(&mut self, rescan_if_missing: bool) -> Task<Message> {
+        let Some(state) = self.pending_resume.clone() else {
+            return Task::none();
+        };
+        let Some(track_id) = state.track_id().filter(|id| self.library.get(id).is_some()) else {
+            self.pending_resume = None;
+            self.notifications
+                .error(t!("The saved song is no longer in the library"));
+            return Task::none();
+        };
+        match state.device_id {
+            Some(device_id) if self.devices.iter().any(|choice| choice.id == device_id) => {
+                self.selected_device = Some(device_id);
             }
-            Message::PlaylistSelect(selection) => {
-                self.selected_playlist = selection;
-                Task::none()
+            Some(_) if rescan_if_missing => {
+                self.resume_waiting_for_device = true;
+                self.notifications
+                    .info(t!("Looking for the saved device..."));
+                return self.update(Message::RefreshDevices);
             }
-            Message::PlaylistDelete(id) => {
-                let before = self.user_prefs.playlists.len();
-                self.user_prefs
-                    .playlists
-                    .retain(|playlist| playlist.id != id);
-                if before != self.user_prefs.playlists.len() {
-                    if self.selected_playlist == Some(id) {
-                        self.selected_playlist = None;
-                    }
-                    if let Some(queue) = &self.play_queue {
-                        if matches!(queue.mode, QueueMode::Playlist(queue_id) if queue_id == id) {
-                            self.play_queue = None;
-                        }
-                    }
-                    self.status_message = Some("Playlist deleted".into());
-                    self.save_preferences_task()
-                } else {
-                    Task::none()
-                }
+            _ if self.selected_device.is_some() => {}
+            _ => {
+                self.notifications.error(t!(
+                    "The saved device is not connected; select a device and resume"
+                ));
+                return Task::none();
             }
-            Message::PlaylistLoadToDraft(id) => {
-                if let Some(playlist) = self
-                    .user_prefs
-                    .playlists
-                    .iter()
-                    .find(|playlist| playlist.id == id)
-                    .cloned()
-                {
-                    self.playlist_draft.name = playlist.name.clone();
-                    self.playlist_draft.tracks = playlist.tracks.clone();
-                    self.selected_playlist = Some(id);
-                    self.status_message = Some("Loaded playlist into draft".into());
-                }
-                Task::none()
+        }
+        self.pending_resume = None;
+        self.play_queue = Some(state.queue.clone());
+        self.play_track_from(track_id, state.position())
+    }
+
+    fn save_resume_task(&mut self, position: Duration) -> Option<Task<Message>> {
+        let queue = self.play_queue.clone()?;
+        self.resume_saved_at = Some(position);
+        let state = ResumeState {
+            queue,
+            position_ms: position.as_millis() as u64,
+            device_id: self.selected_device,
+            interrupted: matches!(
+                self.playback_phase,
+                PlaybackPhase::Playing | PlaybackPhase::Preparing
+            ),
+        };
+        Some(Task::perform(
+            save_resume_state(Some(state)),
+            Message::ResumeStateSaved,
+        ))
+    }
+
+    fn resume_banner(&self) -> Option<Element<'_, Message>> {
+        let state = self.pending_resume.as_ref()?;
+        let entry = self.library.get(&state.track_id()?)?;
+        let position = format_duration(state.position());
+        let label = if state.interrupted {
+            t!(
+                "Playback was interrupted: {name} at {position}",
+                name = entry.name,
+                position = position
+            )
+        } else {
+            t!(
+                "Resume: {name} at {position}",
+                name = entry.name,
+                position = position
+            )
+        };
+        let device_found = state
+            .device_id
+            .is_some_and(|id| self.devices.iter().any(|choice| choice.id == id));
+        Some(
+            container(
+                row![text(label).shaping(Shaping::Advanced).width(Length::Fill),]
+                    .push_maybe(state.interrupted.then(|| {
+                        button(t!("Reset Device"))
+                            .on_press_maybe(device_found.then_some(Message::ResetInterruptedDevice))
+                            .style(iced::widget::button::secondary)
+                    }))
+                    .push(button(t!("Resume")).on_press_maybe(
+                        (!self.is_preparing_playback).then_some(Message::ResumePlayback),
+                    ))
+                    .push(
+                        button(t!("Dismiss"))
+                            .on_press(Message::DismissResume)
+                            .style(iced::widget::button::secondary),
+                    )
+                    .spacing(12)
+                    .align_y(iced::Alignment::Center),
+            )
+            .padding(12)
+            .style(container::rounded_box)
+            .into(),
+        )
+    }
+
+    fn schedule_banner(&self) -> Option<Element<'_, Message>> {
+        let countdown = self.schedule_countdown.as_ref()?;
+        let schedule = self
+            .user_prefs
+            .schedules
+            .iter()
+            .find(|schedule| schedule.id == countdown.schedule_id)?;
+        let seconds = (countdown.due - chrono::Local::now()).num_seconds().max(0);
+        Some(
+            container(
+                row![
+                    text(t!(
+                        "{playlist} starts in {seconds}s",
+                        playlist = self.playlist_name(schedule.playlist_id),
+                        seconds = seconds
+                    ))
+                    .shaping(Shaping::Advanced)
+                    .width(Length::Fill),
+                    button(t!("Start Now")).on_press(Message::StartScheduledNow),
+                    button(t!("Cancel"))
+                        .on_press(Message::CancelScheduled)
+                        .style(iced::widget::button::secondary),
+                ]
+                .spacing(12)
+                .align_y(iced::Alignment::Center),
+            )
+            .padding(12)
+            .style(container::rounded_box)
+            .into(),
+        )
+    }
+
+    fn device_section(&self) -> Element<'_, Message> {
+        let selected_choice = self
+            .selected_device
+            .and_then(|id| self.devices.iter().find(|choice| choice.id == id))
+            .cloned();
+
+        let pick_list = pick_list(
+            self.devices.clone(),
+            selected_choice,
+            |choice: DeviceChoice| Message::DeviceSelected(choice.id),
+        )
+        .placeholder(if self.is_scanning_devices {
+            t!("Scanning devices...")
+        } else {
+            t!("Select output device")
+        });
+
+        let refresh_button = button(t!("Refresh")).on_press(Message::RefreshDevices);
+        let default_button = button(t!("Make Default"))
+            .on_press_maybe(
+                (self.selected_device.is_some()
+                    && self.selected_device != self.user_prefs.default_device)
+                    .then_some(Message::SetDefaultDevice),
+            )
+            .style(iced::widget::button::secondary);
+        let add_button = button(t!("Add Local MIDI")).on_press(Message::AddLocalFile);
+        let add_folder_button = button(t!("Add Folder")).on_press_maybe(
+            self.folder_import
+                .is_none()
+                .then_some(Message::AddLocalFolder),
+        );
+        let settings_button = button(t!("Settings")).on_press(Message::ToggleSettings);
+        let mini_button = button(t!("Mini Player")).on_press(Message::ToggleMiniPlayer);
+        let tray_button = self.tray.as_ref().map(|_| {
+            button(t!("Hide to Tray"))
+                .on_press(Message::HideToTray)
+                .style(iced::widget::button::secondary)
+        });
+        let monitor_button = button(t!("Monitor")).on_press(Message::ToggleMonitor);
+
+        row![
+            pick_list,
+            refresh_button.style(iced::widget::button::secondary),
+            default_button,
+            add_button.style(iced::widget::button::secondary),
+            add_folder_button.style(iced::widget::button::secondary),
+            settings_button.style(if self.show_settings {
+                iced::widget::button::primary
+            } else {
+                iced::widget::button::secondary
+            }),
+            monitor_button.style(if self.monitor_pane.is_some() {
+                iced::widget::button::primary
+            } else {
+                iced::widget::button::secondary
+            }),
+            mini_button.style(iced::widget::button::secondary)
+        ]
+        .push_maybe(tray_button)
+        .push_maybe(self.debug_recorder.as_ref().map(|_| {
+            button(t!("Dump Message Log"))
+                .on_press(Message::DumpDebugLog)
+                .style(iced::widget::button::secondary)
+        }))
+        .push_maybe(self.timing_capture.as_ref().map(|_| {
+            button(t!("Dump Timings"))
+                .on_press(Message::DumpTimings)
+                .style(iced::widget::button::secondary)
+        }))
+        .spacing(12)
+        .into()
+    }
+
+    fn refresh_sink_stats(&mut self) {
+        if let Some(sink) = &self.current_sink {
+            self.sink_stats = sink.stats();
+        }
+    }
+
+    /// How sends through the output are going, so dropouts can be put down
+    /// to the app or the connection.
+    fn sink_stats_row(&self) -> Option<Element<'_, Message>> {
+        let stats = self.sink_stats.as_ref()?;
+        let summary = t!(
+            "Output: {messages} messages, {kilobytes} KB sent · {latency} ms per send · {errors} errors",
+            messages = stats.messages,
+            kilobytes = format!("{:.1}", stats.bytes as f64 / 1024.0),
+            latency = format!("{:.2}", stats.average_latency.as_secs_f64() * 1000.0),
+            errors = stats.errors
+        );
+        let last_error = stats.last_error.as_ref().map(|error| {
+            text(t!("Last send error: {error}", error = error))
+                .shaping(Shaping::Advanced)
+                .size(13)
+                .color(Color::from_rgb(0.9, 0.4, 0.4))
+        });
+        Some(
+            column![text(summary).shaping(Shaping::Advanced).size(13)]
+                .push_maybe(last_error)
+                .spacing(2)
+                .into(),
+        )
+    }
+
+    fn has_hardware_device(&self) -> bool {
+        self.devices.iter().any(|choice| {
+            matches!(
+                choice.transport,
+                MidiTransport::Usb | MidiTransport::Bluetooth
+            )
+        })
+    }
+
+    /// Guidance in place of empty panels, for an empty library or when no
+    /// piano has been found.
+    fn empty_state_panel(&self) -> Option<Element<'_, Message>> {
+        let no_songs = self.library.entries().is_empty() && self.library_load.is_none();
+        let no_devices = !self.has_hardware_device() && !self.is_scanning_devices;
+        if !no_songs && !no_devices {
+            return None;
+        }
+        let mut panel = column![].spacing(12);
+        if no_songs {
+            panel = panel.push(
+                column![
+                    text(t!("No songs yet")).size(18),
+                    text(t!(
+                        "Choose a folder with MIDI files, or add files one at a time."
+                    )),
+                    row![
+                        button(t!("Choose Music Folder")).on_press(Message::PickMusicFolder),
+                        button(t!("Add Local MIDI"))
+                            .on_press(Message::AddLocalFile)
+                            .style(iced::widget::button::secondary),
+                    ]
+                    .spacing(12),
+                ]
+                .spacing(8),
+            );
+        }
+        if no_devices {
+            let mut hints = column![].spacing(4);
+            for hint in BLE_PAIRING_HINTS {
+                hints = hints.push(text(format!("• {}", i18n::tr(hint))));
             }
-            Message::GenerateRandomPlaylist => {
-                let mut rng = rand::rng();
-                let selection: Vec<Uuid> = self
-                    .library
-                    .entries()
-                    .iter()
-                    .map(|entry| entry.id)
-                    .choose_multiple(&mut rng, 50);
-                self.playlist_draft.name = "Random 50".into();
-                self.playlist_draft.tracks = selection;
-                self.status_message = Some("Generated random playlist draft".into());
-                Task::none()
+            panel = panel.push(
+                column![
+                    text(t!("No piano found")).size(18),
+                    text(t!("Connect your piano by USB or Bluetooth, then scan again. Songs can still be played to the debug output.")),
+                    hints,
+                    row![
+                        button(t!("Scan Again")).on_press(Message::RefreshDevices),
+                        button(t!("Run Setup"))
+                            .on_press(Message::RestartOnboarding)
+                            .style(iced::widget::button::secondary),
+                    ]
+                    .spacing(12),
+                ]
+                .spacing(8),
+            );
+        }
+        Some(
+            container(panel)
+                .padding(16)
+                .width(Length::Fill)
+                .style(container::rounded_box)
+                .into(),
+        )
+    }
+
+    fn onboarding_view(&self, onboarding: &Onboarding) -> Element<'_, Message> {
+        let step = onboarding.step;
+        let header = column![
+            text(t!("Welcome to MIDI Piano")).size(28),
+            text(t!(
+                "Step {number} of {total}: {title}",
+                number = step.position() + 1,
+                total = OnboardingStep::ALL.len(),
+                title = step.title()
+            ))
+            .size(18),
+        ]
+        .spacing(4);
+
+        let body: Element<'_, Message> = match step {
+            OnboardingStep::Appearance => {
+                let theme = self.theme();
+                column![
+                    row![
+                        text(t!("Language")).width(Length::Fixed(120.0)),
+                        pick_list(
+                            UiLanguage::ALL,
+                            Some(self.user_prefs.language),
+                            Message::LanguageSelected,
+                        ),
+                    ]
+                    .spacing(12)
+                    .align_y(iced::Alignment::Center),
+                    row![
+                        text(t!("Theme")).width(Length::Fixed(120.0)),
+                        pick_list(Theme::ALL, Some(theme), Message::ThemeSelected),
+                    ]
+                    .spacing(12)
+                    .align_y(iced::Alignment::Center),
+                ]
+                .spacing(12)
+                .into()
             }
-            Message::StartPlayback(id) => self.start_single_track(id),
-            Message::PlayFavorites { shuffle } => self.play_favorites(shuffle),
-            Message::PlayPlaylist { id, shuffle } => self.play_playlist(id, shuffle),
-            Message::NextTrack => {
-                if let Some(next_id) = self.advance_queue(true) {
-                    self.play_track(next_id)
-                } else {
-                    Task::none()
+            OnboardingStep::MusicFolder => {
+                let mut body = column![
+                    text(t!("Choose a folder with your MIDI files. Sub-folders are scanned too, and the folder is checked again on every start.")),
+                    button(t!("Choose Folder")).on_press(Message::PickMusicFolder),
+                ]
+                .spacing(12);
+                for folder in &self.user_prefs.music_folders {
+                    body = body.push(text(folder.display().to_string()).shaping(Shaping::Advanced));
                 }
-            }
-            Message::PrevTrack => {
-                if let Some(prev_id) = self.advance_queue(false) {
-                    self.play_track(prev_id)
+                let summary = if onboarding.scanning_folder {
+                    t!("Scanning...").to_string()
+                } else if self.user_prefs.music_folders.is_empty() {
+                    t!("You can also skip this and add files later.").to_string()
                 } else {
-                    Task::none()
-                }
+                    t!("Found {found_files} MIDI file(s)", found_files = onboarding.found_files)
+                };
+                body.push(text(summary)).into()
             }
-            Message::PlayPressed => {
-                if let Some(id) = self.selected_song {
-                    self.start_single_track(id)
-                } else {
-                    self.error_message = Some("Select a MIDI file to play".into());
-                    Task::none()
+            OnboardingStep::Devices => {
+                let mut devices = column![].spacing(4);
+                if self.devices.is_empty() {
+                    devices = devices.push(text(if self.is_scanning_devices {
+                        t!("Scanning for devices...")
+                    } else {
+                        t!("No MIDI devices found yet.")
+                    }));
                 }
-            }
-            Message::PlaybackPrepared(result) => {
-                self.is_preparing_playback = false;
-                match result {
-                    Ok(prepared) => {
-                        match self
-                            .midi_player
-                            .start_playback(prepared.sequence.clone(), prepared.sink.clone())
-                        {
-                            Ok(_) => {
-                                self.current_sink = Some(prepared.sink);
-                                self.playback_phase = PlaybackPhase::Playing;
-                                self.playback_progress = Some(PlaybackProgress {
-                                    elapsed: Duration::ZERO,
-                                    total: prepared.sequence.duration,
-                                });
-                            }
-                            Err(err) => {
-                                self.error_message =
-                                    Some(format!("Failed to start playback: {err:?}"));
-                                self.playback_phase = PlaybackPhase::Idle;
-                                self.playback_progress = None;
-                            }
-                        }
-                    }
-                    Err(err) => {
-                        self.error_message = Some(format!("Failed to prepare playback: {err}"));
-                        self.playback_phase = PlaybackPhase::Idle;
-                        self.playback_progress = None;
-                    }
+                for choice in &self.devices {
+                    let selected = self.selected_device == Some(choice.id);
+                    devices = devices.push(
+                        button(text(choice.to_string()).shaping(Shaping::Advanced))
+                            .on_press(Message::DeviceSelected(choice.id))
+                            .width(Length::Fill)
+                            .style(if selected {
+                                iced::widget::button::primary
+                            } else {
+                                iced::widget::button::secondary
+                            }),
+                    );
                 }
-                Task::none()
-            }
-            Message::StopPressed => {
-                self.midi_player.stop();
-                self.playback_phase = PlaybackPhase::Idle;
-                self.playback_progress = None;
-                self.current_sink = None;
-                self.play_queue = None;
-                Task::none()
-            }
-            Message::AddLocalFile => {
-                if let Some(path) = rfd::FileDialog::new()
-                    .add_filter("MIDI Files", &["mid", "midi"])
-                    .pick_file()
-                {
-                    match self.library.add_local_file(path) {
-                        Ok(entry) => {
-                            self.selected_song = Some(entry.id);
-                            self.status_message = Some(format!("Added {}", entry.name));
-                            return self.schedule_tree_rebuild();
-                        }
-                        Err(err) => {
-                            self.error_message = Some(format!("Failed to add MIDI file: {err:?}"));
-                        }
+                let mut tips = column![text(t!("Troubleshooting")).size(16)].spacing(4);
+                for tip in device_troubleshooting_tips() {
+                    tips = tips.push(text(format!("• {tip}")));
+                }
+                if !self.has_hardware_device() {
+                    tips = tips.push(text(t!("Bluetooth pairing")).size(16));
+                    for hint in BLE_PAIRING_HINTS {
+                        tips = tips.push(text(format!("• {}", i18n::tr(hint))));
                     }
                 }
-                Task::none()
+                column![
+                    text(t!("Turn on your piano, connect it by USB or Bluetooth and select it below. It will be selected automatically from now on.")),
+                    row![
+                        button(t!("Refresh"))
+                            .on_press(Message::RefreshDevices)
+                            .style(iced::widget::button::secondary)
+                    ],
+                    devices,
+                    tips,
+                ]
+                .spacing(12)
+                .into()
             }
-            Message::Tick => {
-                let mut tasks = Vec::new();
-                while let Ok(event) = self.player_events.try_recv() {
-                    if let Some(task) = self.handle_player_event(event) {
-                        tasks.push(task);
+            OnboardingStep::TestTone => {
+                let device_name = self
+                    .selected_device
+                    .and_then(|id| self.devices.iter().find(|choice| choice.id == id))
+                    .map(|choice| choice.name.clone());
+                let result = match &onboarding.test_tone {
+                    None => String::new(),
+                    Some(Ok(())) => {
+                        t!("Sent. If you heard a chord you're ready to play; otherwise check the piano's volume and local control.")
+                            .into()
                     }
-                }
-                if tasks.is_empty() {
-                    Task::none()
+                    Some(Err(err)) => t!("Could not reach the device: {err}", err = err),
+                };
+                column![
+                    text(match &device_name {
+                        Some(name) => t!("Play a short C major chord on {name}.", name = name),
+                        None => t!("Go back and select a device to send a test tone.").into(),
+                    })
+                    .shaping(Shaping::Advanced),
+                    button(t!("Send Test Tone"))
+                        .on_press_maybe(device_name.map(|_| Message::SendTestTone)),
+                    text(result),
+                ]
+                .spacing(12)
+                .into()
+            }
+            OnboardingStep::ImportPreferences => column![
+                text(t!("Used MIDI Piano before? Import a user_preferences.json to bring over ratings, favorites, playlists and settings.")),
+                button(t!("Import Preferences"))
+                    .on_press(Message::ImportPreferences)
+                    .style(iced::widget::button::secondary),
+            ]
+            .push_maybe(
+                onboarding
+                    .imported_from
+                    .as_ref()
+                    .map(|path| text(t!("Imported from {path}", path = path.display()))),
+            )
+            .spacing(12)
+            .into(),
+        };
+
+        let next_label = if step.next().is_some() {
+            t!("Next")
+        } else {
+            t!("Start Playing")
+        };
+        let footer = row![
+            button(t!("Skip Setup"))
+                .on_press(Message::OnboardingFinish)
+                .style(iced::widget::button::text),
+            iced::widget::horizontal_space(),
+            button(t!("Back"))
+                .on_press_maybe(step.previous().map(|_| Message::OnboardingBack))
+                .style(iced::widget::button::secondary),
+            button(next_label).on_press(Message::OnboardingNext),
+        ]
+        .spacing(12)
+        .align_y(iced::Alignment::Center);
+
+        column![header, scrollable(body).height(Length::Fill), footer]
+            .spacing(24)
+            .max_width(720)
+            .into()
+    }
+
+    fn monitor_panel(&self, pane: &MonitorPane) -> Element<'_, Message> {
+        let controls = row![
+            text(t!("MIDI monitor")).size(18).width(Length::Fill),
+            pick_list(
+                ChannelFilter::options(),
+                Some(pane.channel),
+                Message::MonitorChannelFilterSelected,
+            ),
+            pick_list(
+                KindFilter::options(),
+                Some(pane.kind),
+                Message::MonitorKindFilterSelected,
+            ),
+            checkbox(t!("Hex"), pane.hex).on_toggle(Message::MonitorHexToggled),
+            button(if pane.paused {
+                t!("Resume")
+            } else {
+                t!("Pause")
+            })
+            .on_press(Message::MonitorPauseToggled)
+            .style(iced::widget::button::secondary),
+            button(t!("Clear"))
+                .on_press(Message::ClearMonitor)
+                .style(iced::widget::button::secondary),
+        ]
+        .spacing(12)
+        .align_y(iced::Alignment::Center);
+
+        let mut lines = column![].spacing(2);
+        let mut shown = 0;
+        for entry in pane.entries.iter().filter(|entry| pane.shows(entry)) {
+            let channel = entry
+                .channel()
+                .map(|channel| format!("ch{:<2}", channel + 1))
+                .unwrap_or_else(|| "    ".into());
+            let mut line = format!("{:>9.3}s  {channel}  ", entry.at.as_secs_f64());
+            if pane.hex {
+                line.push_str(&format!("{:<10}  ", monitor::hex_bytes(&entry.data)));
+            }
+            line.push_str(&entry.describe());
+            lines = lines.push(text(line).font(Font::MONOSPACE).size(13));
+            shown += 1;
+        }
+        if shown == 0 {
+            lines = lines.push(text(if pane.entries.is_empty() {
+                t!("Nothing sent yet. Start playback or send a test tone.")
+            } else {
+                t!("No messages match the filters.")
+            }));
+        }
+
+        container(
+            column![
+                controls,
+                scrollable(lines)
+                    .anchor_bottom()
+                    .height(Length::Fixed(240.0))
+                    .width(Length::Fill),
+            ]
+            .spacing(8),
+        )
+        .padding(12)
+        .width(Length::Fill)
+        .style(container::rounded_box)
+        .into()
+    }
+
+    fn settings_panel(&self) -> Element<'_, Message> {
+        let watch = self.user_prefs.watch_folder.as_ref();
+        let inbox_label = watch
+            .map(|config| t!("Inbox: {inbox}", inbox = config.inbox.display()))
+            .unwrap_or_else(|| t!("Inbox: not configured").into());
+
+        let inbox_row = row![
+            text(inbox_label)
+                .shaping(Shaping::Advanced)
+                .width(Length::Fill),
+            button(t!("Choose Inbox"))
+                .on_press(Message::PickInboxFolder)
+                .style(iced::widget::button::secondary),
+            button(t!("Disable"))
+                .on_press_maybe(watch.map(|_| Message::DisableWatchFolder))
+                .style(iced::widget::button::secondary),
+        ]
+        .spacing(12)
+        .align_y(iced::Alignment::Center);
+
+        let mut panel = column![text(t!("Watch folder")).size(18), inbox_row].spacing(8);
+        if let Some(config) = watch {
+            let grouping = pick_list(
+                [InboxGrouping::Composer, InboxGrouping::FirstLetter],
+                Some(config.grouping),
+                Message::InboxGroupingSelected,
+            );
+            panel = panel.push(
+                row![
+                    text(t!(
+                        "Imports go to: {library_root}",
+                        library_root = config.library_root.display()
+                    ))
+                    .shaping(Shaping::Advanced)
+                    .width(Length::Fill),
+                    grouping,
+                    button(t!("Choose Destination"))
+                        .on_press(Message::PickInboxLibraryRoot)
+                        .style(iced::widget::button::secondary),
+                ]
+                .spacing(12)
+                .align_y(iced::Alignment::Center),
+            );
+        }
+
+        let soundfont_label = self
+            .user_prefs
+            .soundfont_path
+            .as_ref()
+            .map(|path| t!("Soundfont: {path}", path = path.display()))
+            .unwrap_or_else(|| t!("Soundfont: not selected").into());
+        panel = panel.push(text(t!("WAV export")).size(18)).push(
+            row![
+                text(soundfont_label)
+                    .shaping(Shaping::Advanced)
+                    .width(Length::Fill),
+                button(t!("Choose Soundfont"))
+                    .on_press(Message::PickSoundfont)
+                    .style(iced::widget::button::secondary),
+            ]
+            .spacing(12)
+            .align_y(iced::Alignment::Center),
+        );
+
+        panel = panel.push(text(t!("Instrument")).size(18)).push(
+            row![
+                text(t!("Play every song with")).width(Length::Fill),
+                pick_list(
+                    ProgramChoice::options(),
+                    Some(ProgramChoice(self.user_prefs.program_override)),
+                    Message::ProgramOverrideSelected,
+                ),
+            ]
+            .spacing(12)
+            .align_y(iced::Alignment::Center),
+        );
+
+        let selected_device = self
+            .selected_device
+            .and_then(|id| self.devices.iter().find(|choice| choice.id == id));
+        let profile = self.selected_profile();
+        let filter = profile.map_or(&self.user_prefs.default_output_filter, |profile| {
+            &profile.filter
+        });
+        panel = panel.push(text(t!("Device profile")).size(18));
+        match selected_device {
+            Some(choice) => {
+                let options: Vec<ProfileChoice> = std::iter::once(ProfileChoice {
+                    id: None,
+                    name: String::new(),
+                })
+                .chain(
+                    self.user_prefs
+                        .device_profiles
+                        .iter()
+                        .map(|profile| ProfileChoice {
+                            id: Some(profile.id),
+                            name: profile.name.clone(),
+                        }),
+                )
+                .collect();
+                let selected = ProfileChoice {
+                    id: profile.map(|profile| profile.id),
+                    name: profile.map_or_else(String::new, |profile| profile.name.clone()),
+                };
+                panel = panel.push(
+                    row![
+                        text(t!("Profile for {name}", name = choice.name))
+                            .shaping(Shaping::Advanced)
+                            .width(Length::Fill),
+                        pick_list(options, Some(selected), Message::DeviceProfileSelected)
+                            .text_shaping(Shaping::Advanced),
+                        button(t!("New Profile"))
+                            .on_press(Message::NewDeviceProfile)
+                            .style(iced::widget::button::secondary),
+                    ]
+                    .push_maybe(profile.map(|_| {
+                        button(t!("Delete Profile"))
+                            .on_press(Message::DeleteDeviceProfile)
+                            .style(iced::widget::button::danger)
+                    }))
+                    .spacing(12)
+                    .align_y(iced::Alignment::Center),
+                );
+            }
+            None => {
+                panel = panel.push(text(t!("Select a device to give it a profile")));
+            }
+        }
+        if let Some(profile) = profile {
+            panel = panel.push(
+                row![
+                    text(t!("Name")),
+                    text_input(t!("Profile name"), &profile.name)
+                        .on_input(Message::DeviceProfileRenamed)
+                        .width(Length::Fill),
+                    text(t!("Latency")),
+                    button("−")
+                        .on_press(Message::DeviceLatencyStep(-5))
+                        .style(iced::widget::button::secondary),
+                    text(t!("{millis} ms", millis = profile.latency_ms)),
+                    button("+")
+                        .on_press(Message::DeviceLatencyStep(5))
+                        .style(iced::widget::button::secondary),
+                ]
+                .spacing(12)
+                .align_y(iced::Alignment::Center),
+            );
+            let mut drums = row![pick_list(
+                PercussionChoice::DEVICE,
+                Some(PercussionChoice::of(Some(profile.percussion))),
+                Message::DevicePercussionSelected,
+            )]
+            .spacing(12)
+            .align_y(iced::Alignment::Center);
+            if let PercussionHandling::Rhythm(channel) = profile.percussion {
+                drums = drums.push(
+                    row![
+                        text(t!("Rhythm ch")),
+                        button("−")
+                            .on_press(Message::DevicePercussionChannelStep(-1))
+                            .style(iced::widget::button::secondary),
+                        text((channel + 1).to_string()),
+                        button("+")
+                            .on_press(Message::DevicePercussionChannelStep(1))
+                            .style(iced::widget::button::secondary),
+                    ]
+                    .spacing(12)
+                    .align_y(iced::Alignment::Center),
+                );
+            }
+            panel = panel.push(drums);
+            panel = panel.push(
+                checkbox(
+                    t!("Wait for Bluetooth devices to confirm every message (slower, drops none)"),
+                    profile.reliable_writes,
+                )
+                .on_toggle(Message::DeviceReliableWritesToggled),
+            );
+        }
+        let filter_scope = match profile {
+            Some(profile) => t!("Rules for {name}", name = profile.name),
+            None => t!("Rules for all devices without a profile").to_string(),
+        };
+        panel = panel.push(text(t!("Output filter")).size(18)).push(
+            text(filter_scope)
+                .shaping(Shaping::Advanced)
+                .width(Length::Fill),
+        );
+        let curves = [
+            VelocityCurve::Linear,
+            VelocityCurve::Soft,
+            VelocityCurve::Hard,
+            match filter.velocity_curve {
+                fixed @ VelocityCurve::Fixed(_) => fixed,
+                _ => VelocityCurve::Fixed(VelocityCurve::DEFAULT_FIXED),
+            },
+        ];
+        panel = panel.push(
+            row![
+                text(t!("Velocity curve")).width(Length::Fill),
+                pick_list(
+                    curves,
+                    Some(filter.velocity_curve),
+                    Message::VelocityCurveSelected
+                ),
+            ]
+            .push_maybe(
+                matches!(filter.velocity_curve, VelocityCurve::Fixed(_)).then(|| {
+                    row![
+                        button("−")
+                            .on_press(Message::FixedVelocityStep(-8))
+                            .style(iced::widget::button::secondary),
+                        button("+")
+                            .on_press(Message::FixedVelocityStep(8))
+                            .style(iced::widget::button::secondary),
+                    ]
+                    .spacing(8)
+                }),
+            )
+            .spacing(12)
+            .align_y(iced::Alignment::Center),
+        );
+        let channel_targets: Vec<ChannelFilter> = (0..16)
+            .map(|channel| ChannelFilter(Some(channel)))
+            .collect();
+        panel = panel.push(text(t!("Send each channel on"))).push(
+            Row::with_children((0..16u8).map(|channel| {
+                row![
+                    text(format!("{} →", channel + 1)),
+                    pick_list(
+                        channel_targets.clone(),
+                        Some(ChannelFilter(Some(filter.channel_map.target(channel)))),
+                        move |target| Message::ChannelMapSelected(channel, target),
+                    ),
+                ]
+                .spacing(4)
+                .align_y(iced::Alignment::Center)
+                .into()
+            }))
+            .spacing(12)
+            .wrap(),
+        );
+        for control in FilteredControl::ALL {
+            let action = filter.action(control);
+            let value = match action {
+                FilterAction::Clamp(limit) => Some((t!("max"), limit.to_string(), 8)),
+                FilterAction::Scale(percent) => Some((t!("to"), format!("{percent}%"), 5)),
+                _ => None,
+            };
+            let clamp = value.map(|(label, value, step)| {
+                row![
+                    text(label),
+                    button("−")
+                        .on_press(Message::OutputFilterClampStep(control, -step))
+                        .style(iced::widget::button::secondary),
+                    text(value),
+                    button("+")
+                        .on_press(Message::OutputFilterClampStep(control, step))
+                        .style(iced::widget::button::secondary),
+                ]
+                .spacing(8)
+                .align_y(iced::Alignment::Center)
+            });
+            panel = panel.push(
+                row![
+                    text(control.to_string()).width(Length::Fill),
+                    pick_list(FilterMode::ALL, Some(FilterMode::of(action)), move |mode| {
+                        Message::OutputFilterModeSelected(control, mode)
+                    },),
+                ]
+                .push_maybe(clamp)
+                .spacing(12)
+                .align_y(iced::Alignment::Center),
+            );
+        }
+        panel = panel.push(
+            row![
+                checkbox(t!("Drop SysEx messages from songs"), filter.drop_sysex)
+                    .on_toggle(Message::OutputFilterSysExToggled)
+                    .width(Length::Fill),
+                text(t!("At song start")),
+                pick_list(
+                    ResetChoice::ALL,
+                    Some(ResetChoice(filter.reset)),
+                    Message::OutputFilterResetSelected,
+                ),
+                pick_list(
+                    BendRangeChoice::ALL,
+                    Some(BendRangeChoice(filter.bend_range)),
+                    Message::OutputFilterBendRangeSelected,
+                ),
+            ]
+            .spacing(12)
+            .align_y(iced::Alignment::Center),
+        );
+
+        if let Some(choice) = selected_device {
+            let capabilities = self.capabilities_for(choice.id);
+            panel =
+                panel
+                    .push(
+                        text(t!("What {name} can play", name = choice.name))
+                            .size(18)
+                            .shaping(Shaping::Advanced),
+                    )
+                    .push(
+                        row![
+                            checkbox(t!("Plays channel 1 only"), capabilities.single_channel)
+                                .on_toggle(move |single_channel| {
+                                    Message::DeviceCapabilitiesChanged(DeviceCapabilities {
+                                        single_channel,
+                                        ..capabilities
+                                    })
+                                }),
+                            checkbox(t!("Has drums on channel 10"), capabilities.percussion)
+                                .on_toggle(move |percussion| {
+                                    Message::DeviceCapabilitiesChanged(DeviceCapabilities {
+                                        percussion,
+                                        ..capabilities
+                                    })
+                                }),
+                            checkbox(
+                                t!("Switches sounds on program changes"),
+                                capabilities.program_changes
+                            )
+                            .on_toggle(move |program_changes| {
+                                Message::DeviceCapabilitiesChanged(DeviceCapabilities {
+                                    program_changes,
+                                    ..capabilities
+                                })
+                            }),
+                            checkbox(t!("Remap without asking"), capabilities.auto_remap)
+                                .on_toggle(move |auto_remap| {
+                                    Message::DeviceCapabilitiesChanged(DeviceCapabilities {
+                                        auto_remap,
+                                        ..capabilities
+                                    })
+                                }),
+                        ]
+                        .spacing(16)
+                        .wrap(),
+                    );
+        }
+
+        panel = panel.push(text(t!("Scheduled playback")).size(18));
+        let playlist_choices: Vec<PlaylistChoice> = self
+            .user_prefs
+            .playlist_groups()
+            .into_iter()
+            .flat_map(|(folder, playlists)| {
+                playlists
+                    .into_iter()
+                    .map(move |playlist| PlaylistChoice::new(playlist, folder))
+            })
+            .collect();
+        let hours: Vec<ClockTime> = (0..24).map(ClockTime).collect();
+        let minutes: Vec<ClockTime> = (0..60).step_by(5).map(ClockTime).collect();
+        for schedule in &self.user_prefs.schedules {
+            let id = schedule.id;
+            let time = schedule.time;
+            let days = (0..7).map(|day| {
+                button(text(weekday_label(day)))
+                    .on_press(Message::ScheduleDayToggled(id, day))
+                    .style(if schedule.days[day] {
+                        iced::widget::button::primary
+                    } else {
+                        iced::widget::button::secondary
+                    })
+                    .into()
+            });
+            let selected_playlist = playlist_choices
+                .iter()
+                .find(|choice| Some(choice.id) == schedule.playlist_id)
+                .cloned();
+            let selected_device = self
+                .devices
+                .iter()
+                .find(|choice| Some(choice.id) == schedule.device_id)
+                .cloned();
+            panel = panel.push(
+                row![
+                    checkbox("", schedule.enabled)
+                        .on_toggle(move |enabled| Message::ScheduleEnabledToggled(id, enabled)),
+                    Row::with_children(days).spacing(4),
+                    pick_list(hours.clone(), Some(ClockTime(time.hour())), move |hour| {
+                        Message::ScheduleTimeSelected(id, time.with_hour(hour.0).unwrap_or(time))
+                    },),
+                    text(":"),
+                    pick_list(
+                        minutes.clone(),
+                        Some(ClockTime(time.minute())),
+                        move |minute| {
+                            Message::ScheduleTimeSelected(
+                                id,
+                                time.with_minute(minute.0).unwrap_or(time),
+                            )
+                        },
+                    ),
+                    pick_list(playlist_choices.clone(), selected_playlist, move |choice| {
+                        Message::SchedulePlaylistSelected(id, choice)
+                    })
+                    .placeholder(t!("Playlist"))
+                    .text_shaping(Shaping::Advanced),
+                    pick_list(self.devices.clone(), selected_device, move |choice| {
+                        Message::ScheduleDeviceSelected(id, choice)
+                    })
+                    .placeholder(t!("Selected device"))
+                    .text_shaping(Shaping::Advanced),
+                    button(t!("Remove"))
+                        .on_press(Message::RemoveSchedule(id))
+                        .style(iced::widget::button::secondary),
+                ]
+                .spacing(8)
+                .align_y(iced::Alignment::Center)
+                .wrap(),
+            );
+        }
+        panel = panel.push(
+            button(t!("Add Schedule"))
+                .on_press(Message::AddSchedule)
+                .style(iced::widget::button::secondary),
+        );
+
+        panel = panel.push(text(t!("Queue")).size(18)).push(
+            row![
+                text(t!("Match keys between consecutive pieces")).width(Length::Fill),
+                pick_list(
+                    KeyMatchMode::ALL,
+                    Some(self.user_prefs.key_match_mode),
+                    Message::KeyMatchModeSelected,
+                ),
+            ]
+            .spacing(12)
+            .align_y(iced::Alignment::Center),
+        );
+        panel = panel.push(
+            row![
+                text(t!("Shuffle plays the most recently heard songs last")).width(Length::Fill),
+                button("−")
+                    .on_press(Message::ShuffleAvoidStep(-1))
+                    .style(iced::widget::button::secondary),
+                text(match self.user_prefs.shuffle_avoid() {
+                    0 => t!("Off").to_string(),
+                    count => t!("{count} songs", count = count),
+                }),
+                button("+")
+                    .on_press(Message::ShuffleAvoidStep(1))
+                    .style(iced::widget::button::secondary),
+            ]
+            .spacing(12)
+            .align_y(iced::Alignment::Center),
+        );
+        panel = panel.push(
+            checkbox(
+                t!("Weight shuffles by rating (higher-rated songs come up more often and may repeat)"),
+                self.user_prefs.weighted_shuffle,
+            )
+            .on_toggle(Message::WeightedShuffleToggled),
+        );
+
+        let silence = self.user_prefs.silence_watch;
+        panel = panel.push(text(t!("Silence watchdog")).size(18)).push(
+            row![
+                text(t!("When a song goes silent mid-piece")).width(Length::Fill),
+                pick_list(
+                    SilenceAction::ALL,
+                    Some(silence.action),
+                    Message::SilenceActionSelected,
+                ),
+                text(t!("after")),
+                button("−")
+                    .on_press(Message::SilenceThresholdStep(-1))
+                    .style(iced::widget::button::secondary),
+                text(format!("{}s", silence.threshold_secs)),
+                button("+")
+                    .on_press(Message::SilenceThresholdStep(1))
+                    .style(iced::widget::button::secondary),
+            ]
+            .spacing(12)
+            .align_y(iced::Alignment::Center),
+        );
+
+        let lead_in = self.user_prefs.lead_in;
+        panel = panel
+            .push(text(t!("Song start")).size(18))
+            .push(
+                row![
+                    checkbox(
+                        t!("Skip silence before the first note"),
+                        lead_in.trim_silence
+                    )
+                    .on_toggle(Message::TrimLeadingSilenceToggled)
+                    .width(Length::Fill),
+                    text(t!("pre-roll")),
+                    button("−")
+                        .on_press(Message::PreRollStep(-1))
+                        .style(iced::widget::button::secondary),
+                    text(format!("{:.2}s", f32::from(lead_in.pre_roll_ms) / 1000.0)),
+                    button("+")
+                        .on_press(Message::PreRollStep(1))
+                        .style(iced::widget::button::secondary),
+                ]
+                .spacing(12)
+                .align_y(iced::Alignment::Center),
+            )
+            .push(
+                checkbox(
+                    t!("Count in one measure before the first note"),
+                    lead_in.count_in,
+                )
+                .on_toggle(Message::CountInToggled),
+            )
+            .push(
+                checkbox(
+                    t!("Count down 3-2-1 before playing, clicking along with the count-in"),
+                    lead_in.countdown,
+                )
+                .on_toggle(Message::CountdownToggled),
+            )
+            .push(
+                checkbox(
+                    t!("Metronome: click every beat while playing, accenting each bar"),
+                    self.user_prefs.metronome,
+                )
+                .on_toggle(Message::MetronomeToggled),
+            );
+
+        let quiet = self.user_prefs.quiet_limit;
+        let quiet_volume = quiet.max_volume.map(|volume| {
+            row![
+                button("−")
+                    .on_press(Message::QuietVolumeStep(-1))
+                    .style(iced::widget::button::secondary),
+                text(volume.to_string()),
+                button("+")
+                    .on_press(Message::QuietVolumeStep(1))
+                    .style(iced::widget::button::secondary),
+            ]
+            .spacing(8)
+            .align_y(iced::Alignment::Center)
+        });
+        panel = panel.push(text(t!("Quiet mode")).size(18)).push(
+            row![
+                checkbox(t!("Play quietly"), self.user_prefs.quiet_mode)
+                    .on_toggle(Message::QuietModeToggled)
+                    .width(Length::Fill),
+                text(t!("Loudest note")),
+                button("−")
+                    .on_press(Message::QuietVelocityStep(-1))
+                    .style(iced::widget::button::secondary),
+                text(quiet.max_velocity.to_string()),
+                button("+")
+                    .on_press(Message::QuietVelocityStep(1))
+                    .style(iced::widget::button::secondary),
+                checkbox(t!("Lower volume to"), quiet.max_volume.is_some())
+                    .on_toggle(Message::QuietVolumeToggled),
+            ]
+            .push_maybe(quiet_volume)
+            .spacing(12)
+            .align_y(iced::Alignment::Center),
+        );
+
+        let overlay = &self.user_prefs.score_overlay;
+        panel = panel.push(text(t!("Score follow overlay")).size(18)).push(
+            row![
+                checkbox(t!("Show bar counter while playing"), overlay.enabled)
+                    .on_toggle(Message::ScoreOverlayToggled)
+                    .width(Length::Fill),
+                text(t!("Size {text_size}", text_size = overlay.text_size)),
+                slider(
+                    48..=240,
+                    overlay.text_size,
+                    Message::ScoreOverlaySizeChanged
+                )
+                .step(8u16)
+                .width(Length::Fixed(200.0)),
+            ]
+            .spacing(12)
+            .align_y(iced::Alignment::Center),
+        );
+
+        let timeout_secs =
+            u16::try_from(self.user_prefs.connect_timeout().as_secs()).unwrap_or(u16::MAX);
+        panel = panel.push(text(t!("Device connection")).size(18)).push(
+            row![
+                text(t!(
+                    "Give up connecting after {seconds}s",
+                    seconds = timeout_secs
+                ))
+                .width(Length::Fill),
+                slider(3..=60, timeout_secs, Message::ConnectTimeoutChanged)
+                    .width(Length::Fixed(200.0)),
+            ]
+            .spacing(12)
+            .align_y(iced::Alignment::Center),
+        );
+        let send_trace = self.user_prefs.send_trace;
+        panel = panel.push(
+            row![
+                checkbox(
+                    t!("Log every message sent to devices (for bug reports)"),
+                    send_trace.is_some(),
+                )
+                .on_toggle(Message::SendTraceToggled),
+            ]
+            .push_maybe(send_trace.map(|format| {
+                pick_list(
+                    TraceFormat::ALL,
+                    Some(format),
+                    Message::SendTraceFormatSelected,
+                )
+            }))
+            .push(
+                button(t!("Open Log Folder"))
+                    .on_press(Message::OpenSendTraceFolder)
+                    .style(iced::widget::button::secondary),
+            )
+            .spacing(12)
+            .align_y(iced::Alignment::Center),
+        );
+        if VIRTUAL_PORTS_SUPPORTED {
+            panel = panel.push(
+                checkbox(
+                    t!(
+                        "Offer a virtual port named \"{name}\" to other apps",
+                        name = VIRTUAL_PORT_NAME
+                    ),
+                    self.user_prefs.virtual_port,
+                )
+                .on_toggle(Message::VirtualPortToggled),
+            );
+        }
+        let remote = &self.user_prefs.remote_control;
+        panel = panel.push(text(t!("Remote control")).size(18)).push(
+            row![
+                checkbox(
+                    t!("Let other devices on the network control playback"),
+                    remote.enabled
+                )
+                .on_toggle(Message::RemoteControlToggled)
+                .width(Length::Fill),
+                text(t!("Port")),
+                text_input("8765", &self.remote_port_draft)
+                    .on_input(Message::RemoteControlPortDraftChanged)
+                    .on_submit(Message::RemoteControlPortSubmitted)
+                    .width(Length::Fixed(80.0)),
+            ]
+            .spacing(12)
+            .align_y(iced::Alignment::Center),
+        );
+        if remote.enabled {
+            let status = if self.remote_control_handle.is_some() {
+                t!(
+                    "Connect to ws://<this computer>:{port}/?token={token}",
+                    port = remote.port,
+                    token = remote.token
+                )
+            } else {
+                t!("Remote control is not running").to_string()
+            };
+            panel = panel.push(
+                row![
+                    text(status).width(Length::Fill),
+                    button(t!("New Token"))
+                        .on_press(Message::RegenerateRemoteControlToken)
+                        .style(iced::widget::button::secondary),
+                ]
+                .spacing(12)
+                .align_y(iced::Alignment::Center),
+            );
+            panel = panel.push(
+                text(t!(
+                    "The server listens on every network interface: anyone on your network with the token can control playback."
+                ))
+                .size(12),
+            );
+        }
+        if self.ble_adapters.len() > 1 || self.user_prefs.ble_adapter.is_some() {
+            let options: Vec<AdapterChoice> = std::iter::once(AdapterChoice(None))
+                .chain(
+                    self.ble_adapters
+                        .iter()
+                        .map(|adapter| AdapterChoice(Some(adapter.clone()))),
+                )
+                .collect();
+            panel = panel.push(
+                row![
+                    text(t!("Scan for Bluetooth devices with")).width(Length::Fill),
+                    pick_list(
+                        options,
+                        Some(AdapterChoice(self.user_prefs.ble_adapter.clone())),
+                        Message::BleAdapterSelected,
+                    ),
+                ]
+                .spacing(12)
+                .align_y(iced::Alignment::Center),
+            );
+        }
+        let ble_packet_size = self.user_prefs.ble_packet_size;
+        panel = panel.push(
+            row![
+                checkbox(
+                    t!("Size Bluetooth packets from the negotiated MTU"),
+                    ble_packet_size.is_none()
+                )
+                .on_toggle(Message::BlePacketSizeAutoToggled)
+                .width(Length::Fill),
+            ]
+            .push_maybe(ble_packet_size.map(|size| {
+                row![
+                    text(t!("{bytes} bytes", bytes = size)),
+                    slider(
+                        MIN_BLE_PACKET_SIZE as u16..=MAX_BLE_PACKET_SIZE as u16,
+                        size,
+                        Message::BlePacketSizeChanged
+                    )
+                    .width(Length::Fixed(200.0)),
+                ]
+                .spacing(12)
+                .align_y(iced::Alignment::Center)
+            }))
+            .spacing(12)
+            .align_y(iced::Alignment::Center),
+        );
+        panel = panel.push(
+            checkbox(
+                t!("Use running status on USB devices (fewer bytes; not for macOS)"),
+                self.user_prefs.usb_running_status,
+            )
+            .on_toggle(Message::UsbRunningStatusToggled),
+        );
+
+        let interval_ms =
+            u16::try_from(self.user_prefs.progress_interval().as_millis()).unwrap_or(u16::MAX);
+        panel = panel.push(text(t!("Progress display")).size(18)).push(
+            row![
+                text(t!(
+                    "Update the playback position every {millis} ms",
+                    millis = interval_ms
+                ))
+                .width(Length::Fill),
+                slider(20..=500, interval_ms, Message::ProgressIntervalChanged)
+                    .step(10u16)
+                    .width(Length::Fixed(200.0)),
+            ]
+            .spacing(12)
+            .align_y(iced::Alignment::Center),
+        );
+
+        let fade_ms = self.user_prefs.fade_ms;
+        panel = panel.push(text(t!("Fades")).size(18)).push(
+            row![
+                text(if fade_ms == 0 {
+                    t!("Start and stop without fading").to_owned()
                 } else {
-                    Task::batch(tasks)
-                }
-            }
-            Message::DismissStatus => {
-                self.status_message = None;
-                self.error_message = None;
-                Task::none()
-            }
+                    t!(
+                        "Fade in at the start and out when stopped over {millis} ms",
+                        millis = fade_ms
+                    )
+                })
+                .width(Length::Fill),
+                slider(0..=5000, fade_ms, Message::FadeLengthChanged)
+                    .step(250u16)
+                    .width(Length::Fixed(200.0)),
+            ]
+            .spacing(12)
+            .align_y(iced::Alignment::Center),
+        );
+
+        if self.tray.is_some() {
+            panel = panel.push(text(t!("System tray")).size(18)).push(
+                checkbox(
+                    t!("Closing the window keeps the app running in the tray"),
+                    self.user_prefs.close_to_tray,
+                )
+                .on_toggle(Message::CloseToTrayToggled),
+            );
         }
-    }
 
-    fn view(&self) -> Element<'_, Message> {
-        let content = column![
-            self.device_section(),
-            self.playback_controls(),
-            self.library_tabs(),
-            self.library_view(),
-            self.playlist_editor(),
-            self.status_banner()
-        ]
-        .spacing(16)
-        .padding(16);
+        let sessions = self.practice_log.sessions.len();
+        panel = panel.push(text(t!("Practice statistics")).size(18)).push(
+            row![
+                text(format!("{sessions} recorded session(s)")).width(Length::Fill),
+                text_input(t!("From (YYYY-MM-DD)"), &self.stats_from)
+                    .on_input(Message::StatsFromChanged)
+                    .width(Length::Fixed(160.0)),
+                text_input(t!("To (YYYY-MM-DD)"), &self.stats_to)
+                    .on_input(Message::StatsToChanged)
+                    .width(Length::Fixed(160.0)),
+                pick_list(
+                    StatsExportKind::ALL,
+                    Some(self.stats_export_kind),
+                    Message::StatsExportKindSelected,
+                ),
+                button(t!("Export Stats"))
+                    .on_press(Message::ExportStats)
+                    .style(iced::widget::button::secondary),
+            ]
+            .spacing(12)
+            .align_y(iced::Alignment::Center),
+        );
 
-        container(content)
-            .width(Length::Fill)
-            .height(Length::Fill)
-            .align_x(Horizontal::Left)
-            .align_y(Vertical::Top)
-            .into()
-    }
+        panel = panel.push(text(t!("User data")).size(18)).push(
+            row![
+                text(t!(
+                    "Preferences, playlists, statistics and device profiles in one file"
+                ))
+                .width(Length::Fill),
+                button(t!("Export User Data"))
+                    .on_press(Message::ExportUserData)
+                    .style(iced::widget::button::secondary),
+                button(t!("Import and Merge"))
+                    .on_press(Message::ImportUserData(UserDataImport::Merge))
+                    .style(iced::widget::button::secondary),
+                button(t!("Import and Replace"))
+                    .on_press(Message::ImportUserData(UserDataImport::Replace))
+                    .style(iced::widget::button::danger),
+            ]
+            .spacing(12)
+            .align_y(iced::Alignment::Center),
+        );
 
-    fn subscription(&self) -> Subscription<Message> {
-        time::every(TICK_INTERVAL).map(|_| Message::Tick)
-    }
+        panel = panel.push(
+            row![
+                text(t!("Sort titles")).width(Length::Fill),
+                pick_list(
+                    TitleCollation::ALL,
+                    Some(self.user_prefs.title_collation),
+                    Message::TitleCollationSelected,
+                ),
+            ]
+            .spacing(12)
+            .align_y(iced::Alignment::Center),
+        );
 
-    fn theme(&self) -> Theme {
-        Theme::Dark
-    }
+        panel = panel.push(
+            row![
+                text(t!("Music folders")).size(18).width(Length::Fill),
+                button(t!("Add Folder"))
+                    .on_press(Message::PickMusicFolder)
+                    .style(iced::widget::button::secondary),
+                button(if self.is_rescanning_assets {
+                    t!("Rescanning...")
+                } else {
+                    t!("Rescan Assets")
+                })
+                .on_press_maybe((!self.is_rescanning_assets).then_some(Message::RescanAssets))
+                .style(iced::widget::button::secondary),
+                button(t!("Clean Missing Files"))
+                    .on_press(Message::CleanMissingFiles)
+                    .style(iced::widget::button::secondary),
+                button(t!("Run Setup Again"))
+                    .on_press(Message::RestartOnboarding)
+                    .style(iced::widget::button::secondary),
+            ]
+            .spacing(12)
+            .align_y(iced::Alignment::Center),
+        );
+        for (index, folder) in self.user_prefs.music_folders.iter().enumerate() {
+            panel = panel.push(
+                row![
+                    text(folder.display().to_string())
+                        .shaping(Shaping::Advanced)
+                        .width(Length::Fill),
+                    button(t!("Remove"))
+                        .on_press(Message::RemoveMusicFolder(index))
+                        .style(iced::widget::button::secondary),
+                ]
+                .spacing(12)
+                .align_y(iced::Alignment::Center),
+            );
+        }
 
-    fn handle_player_event(&mut self, event: PlayerEvent) -> Option<Task<Message>> {
-        match event {
-            PlayerEvent::Started { total } => {
-                self.playback_phase = PlaybackPhase::Playing;
-                self.playback_progress = Some(PlaybackProgress {
-                    elapsed: Duration::ZERO,
-                    total,
-                });
-                self.status_message = Some("Playback started".into());
-                None
-            }
-            PlayerEvent::Progress { elapsed, total } => {
-                self.playback_progress = Some(PlaybackProgress { elapsed, total });
-                None
-            }
-            PlayerEvent::Finished => {
-                self.playback_phase = PlaybackPhase::Finished;
-                self.current_sink = None;
-                if let Some(next_id) = self.advance_queue(true) {
-                    Some(self.play_track(next_id))
+        panel = panel.push(text(t!("Remote catalog")).size(18)).push(
+            row![
+                text_input(
+                    "https://example.com/catalog.json",
+                    &self.remote_catalog_draft
+                )
+                .on_input(Message::RemoteCatalogUrlChanged)
+                .on_submit(Message::LoadRemoteCatalog)
+                .padding(6),
+                button(if self.is_loading_remote_catalog {
+                    t!("Loading...")
                 } else {
-                    self.status_message = Some("Playback finished".into());
-                    None
-                }
-            }
-            PlayerEvent::Stopped => {
-                self.playback_phase = PlaybackPhase::Idle;
-                self.playback_progress = None;
-                self.status_message = Some("Playback stopped".into());
-                self.current_sink = None;
-                None
-            }
-            PlayerEvent::Error(message) => {
-                self.error_message = Some(message);
-                self.playback_phase = PlaybackPhase::Idle;
-                self.playback_progress = None;
-                self.current_sink = None;
-                None
-            }
+                    t!("Load")
+                })
+                .on_press_maybe(
+                    (!self.is_loading_remote_catalog).then_some(Message::LoadRemoteCatalog)
+                )
+                .style(iced::widget::button::secondary),
+                button(t!("Clear Cache"))
+                    .on_press(Message::ClearRemoteCache)
+                    .style(iced::widget::button::secondary),
+            ]
+            .spacing(12)
+            .align_y(iced::Alignment::Center),
+        );
+        if !self.remote_catalog.is_empty() {
+            panel = panel.push(text(t!(
+                "{count} remote song(s); files download on first play",
+                count = self.remote_catalog.len()
+            )));
         }
-    }
 
-    fn save_preferences_task(&self) -> Task<Message> {
-        Task::perform(
-            save_user_preferences(self.user_prefs.clone()),
-            Message::PreferencesSaved,
-        )
+        panel = panel
+            .push(text(t!("Now-playing webhook")).size(18))
+            .push(
+                row![
+                    text_input("https://example.com/hooks/practice", &self.webhook_draft)
+                        .on_input(Message::WebhookUrlChanged)
+                        .on_submit(Message::SaveWebhookUrl)
+                        .padding(6),
+                    button(t!("Save"))
+                        .on_press(Message::SaveWebhookUrl)
+                        .style(iced::widget::button::secondary),
+                    button(t!("Send Test Event"))
+                        .on_press_maybe(
+                            (!self.webhook_draft.trim().is_empty())
+                                .then_some(Message::SendTestWebhook)
+                        )
+                        .style(iced::widget::button::secondary),
+                ]
+                .spacing(12)
+                .align_y(iced::Alignment::Center),
+            )
+            .push(text(t!(
+                "Posts JSON when a song starts, finishes or is stopped"
+            )));
+
+        container(panel)
+            .padding(12)
+            .style(container::rounded_box)
+            .into()
     }
 
-    fn schedule_tree_rebuild(&mut self) -> Task<Message> {
-        self.tree_loading = true;
-        self.tree_request_id = self.tree_request_id.wrapping_add(1);
-        let request_id = self.tree_request_id;
-        let entries = self.library.entries().to_vec();
-        Task::perform(compute_tree_data(entries), move |result| match result {
-            Ok((tree, folders)) => Message::TreeDataLoaded {
-                request_id,
-                tree,
-                folders,
-            },
-            Err(err) => Message::TreeDataFailed {
-                request_id,
-                error: err,
-            },
-        })
-    }
+    fn song_settings_row(&self, id: Uuid) -> Element<'_, Message> {
+        let settings = self.song_settings(id);
+        let step = |label: &'static str, message: Message| {
+            button(text(label).shaping(Shaping::Advanced))
+                .on_press(message)
+                .style(iced::widget::button::secondary)
+        };
 
-    fn apply_tree_data(&mut self, tree: LibraryNode, folders: HashMap<String, Vec<Uuid>>) {
-        self.tree_loading = false;
-        self.library_tree = tree;
-        self.folder_entries = folders;
-        if self
-            .selected_folder
-            .as_ref()
-            .map_or(true, |id| !self.folder_entries.contains_key(id))
-        {
-            self.selected_folder = Some("root".into());
+        let names = self.channel_names(id);
+        let mut mutes = row![text(t!("Mute:"))]
+            .spacing(2)
+            .align_y(iced::Alignment::Center);
+        for channel in 0..16u8 {
+            let muted = settings.muted_channels & (1 << channel) != 0;
+            let label = match names.and_then(|names| names[usize::from(channel)].as_deref()) {
+                Some(name) => format!("{} {name}", channel + 1),
+                None => (channel + 1).to_string(),
+            };
+            mutes = mutes.push(
+                button(text(label).size(12).shaping(Shaping::Advanced))
+                    .padding([2, 4])
+                    .on_press(Message::SongChannelMuteToggled(id, channel))
+                    .style(if muted {
+                        iced::widget::button::danger
+                    } else {
+                        iced::widget::button::secondary
+                    }),
+            );
         }
-        self.refresh_tree_cache();
-    }
 
-    fn ble_scan_task(manager: Arc<Mutex<MidiDeviceManager>>) -> Task<Message> {
-        Task::run(
-            stream::unfold(manager, |manager| async move {
-                tokio::time::sleep(Duration::from_secs(5)).await;
-                let result = {
-                    let mut guard = manager.lock().await;
-                    guard.scan_ble_once().await.map_err(|err| err.to_string())
-                };
-                Some((Message::BleScanUpdate(result), manager))
+        let adjustments = row![
+            text(t!("Speed")),
+            step("−", Message::SongTempoStep(id, -5)),
+            text(format!("{}%", settings.tempo_percent)),
+            step("+", Message::SongTempoStep(id, 5)),
+            text(t!("Transpose")),
+            step("−", Message::SongTransposeStep(id, -1)),
+            text(format!("{:+}", settings.transpose)),
+            step("+", Message::SongTransposeStep(id, 1)),
+        ]
+        .spacing(8)
+        .align_y(iced::Alignment::Center)
+        .push_maybe(
+            (self.user_prefs.silence_watch.action != SilenceAction::Off).then(|| {
+                button(if settings.silence_watch_disabled {
+                    t!("Silence watch: off")
+                } else {
+                    t!("Silence watch: on")
+                })
+                .on_press(Message::SongSilenceWatchToggled(id))
+                .style(iced::widget::button::secondary)
             }),
-            |message| message,
         )
-    }
-
-    fn refresh_tree_cache(&mut self) {
-        let mut items = Vec::new();
-        collect_tree_items(&self.library_tree, 0, &self.expanded_folders, &mut items);
-        self.tree_cache = items;
-    }
-
-    fn visible_entries(&self) -> Vec<&crate::midi::MidiEntry> {
-        let query = self.search_query.trim().to_lowercase();
-
-        let mut base: Vec<&crate::midi::MidiEntry> = match self.active_tab {
-            LibraryTab::Tree => {
-                let folder_id = self.selected_folder.as_deref().unwrap_or("root");
-                self.folder_entries
-                    .get(folder_id)
-                    .into_iter()
-                    .flat_map(|ids| ids.iter())
-                    .filter_map(|id| self.library.get(id))
-                    .collect()
-            }
-            LibraryTab::Favorites => self
-                .user_prefs
-                .favorites
-                .iter()
-                .filter_map(|id| self.library.get(id))
-                .collect(),
-        };
+        .push(
+            button(t!("Reset"))
+                .on_press_maybe(
+                    (settings != SongSettings::default()).then_some(Message::ResetSongSettings(id)),
+                )
+                .style(iced::widget::button::secondary),
+        )
+        .push(
+            button(t!("Export arrangement"))
+                .on_press_maybe(
+                    (!self.playback_adjustments(id).is_identity())
+                        .then_some(Message::ExportArrangement(id)),
+                )
+                .style(iced::widget::button::secondary),
+        );
+
+        let is_local = self
+            .library
+            .get(&id)
+            .is_some_and(|entry| entry.origin == midi_piano_rs::midi::MidiOrigin::Local);
+        let removal = is_local.then(|| {
+            row![
+                button(t!("Remove from Library"))
+                    .on_press(Message::RemoveEntry { id, forget: false })
+                    .style(iced::widget::button::secondary),
+                button(t!("Remove and Forget"))
+                    .on_press(Message::RemoveEntry { id, forget: true })
+                    .style(iced::widget::button::danger),
+            ]
+            .spacing(8)
+        });
 
-        if !query.is_empty() {
-            base.retain(|entry| entry.name.to_lowercase().contains(&query));
+        let mut hands = row![pick_list(
+            HandSplitKind::ALL,
+            Some(HandSplitKind::of(settings.hand_split)),
+            move |kind| Message::SongHandSplitSelected(id, kind),
+        )]
+        .spacing(8)
+        .align_y(iced::Alignment::Center);
+        if let Some(split) = settings.hand_split {
+            let classifier_label = match split.classifier {
+                HandClassifier::SplitPoint(key) => {
+                    t!("LH below {note}", note = sequence::note_name(key))
+                }
+                HandClassifier::LeftTrack(track) => t!("LH = track {track}", track = track + 1),
+            };
+            hands = hands.push(
+                row![
+                    step("−", Message::SongHandSplitStep(id, -1)),
+                    text(classifier_label).shaping(Shaping::Advanced),
+                    step("+", Message::SongHandSplitStep(id, 1)),
+                    text(t!("LH ch")),
+                    step("−", Message::SongHandChannelStep(id, Hand::Left, -1)),
+                    text((split.left_channel + 1).to_string()),
+                    step("+", Message::SongHandChannelStep(id, Hand::Left, 1)),
+                    text(t!("RH ch")),
+                    step("−", Message::SongHandChannelStep(id, Hand::Right, -1)),
+                    text((split.right_channel + 1).to_string()),
+                    step("+", Message::SongHandChannelStep(id, Hand::Right, 1)),
+                ]
+                .spacing(8)
+                .align_y(iced::Alignment::Center),
+            );
         }
 
-        base.sort_by(|a, b| a.name.to_lowercase().cmp(&b.name.to_lowercase()));
-        base
-    }
-
-    fn start_single_track(&mut self, track_id: Uuid) -> Task<Message> {
-        if self.library.get(&track_id).is_none() {
-            self.error_message = Some("Selected track is not available".into());
-            return Task::none();
+        let mut drums = row![pick_list(
+            PercussionChoice::ALL,
+            Some(PercussionChoice::of(settings.percussion)),
+            move |choice| Message::SongPercussionSelected(id, choice),
+        )]
+        .spacing(8)
+        .align_y(iced::Alignment::Center);
+        if let Some(PercussionHandling::Rhythm(channel)) = settings.percussion {
+            drums = drums.push(
+                row![
+                    text(t!("Rhythm ch")),
+                    step("−", Message::SongPercussionChannelStep(id, -1)),
+                    text((channel + 1).to_string()),
+                    step("+", Message::SongPercussionChannelStep(id, 1)),
+                ]
+                .spacing(8)
+                .align_y(iced::Alignment::Center),
+            );
         }
-        self.queue_with_tracks(vec![track_id], track_id, QueueMode::Single, false);
-        self.play_track(track_id)
-    }
 
-    fn play_favorites(&mut self, shuffle: bool) -> Task<Message> {
-        let mut entries: Vec<_> = self
-            .user_prefs
-            .favorites
-            .iter()
-            .filter_map(|id| self.library.get(id))
-            .collect();
-        entries.sort_by(|a, b| a.name.to_lowercase().cmp(&b.name.to_lowercase()));
-        let tracks: Vec<Uuid> = entries.iter().map(|entry| entry.id).collect();
-        if tracks.is_empty() {
-            self.error_message = Some("No favorites available to play".into());
-            return Task::none();
-        }
-        let start_track = if shuffle {
-            let mut rng = rng();
-            *tracks.as_slice().choose(&mut rng).unwrap()
-        } else {
-            tracks[0]
-        };
-        if self.queue_with_tracks(tracks, start_track, QueueMode::Favorites, shuffle) {
-            self.status_message = Some("Playing favorites".into());
-            self.play_track(start_track)
-        } else {
-            Task::none()
-        }
+        column![adjustments, mutes.wrap(), hands, drums]
+            .push_maybe(removal)
+            .spacing(8)
+            .into()
     }
 
-    fn play_playlist(&mut self, playlist_id: Uuid, shuffle: bool) -> Task<Message> {
-        let playlist = match self
-            .user_prefs
-            .playlists
-            .iter()
-            .find(|playlist| playlist.id == playlist_id)
-            .cloned()
+    /// Track names for `id`'s channels, when its file has been read for
+    /// playing or for the song information panel.
+    fn channel_names(&self, id: Uuid) -> Option<&[Option<String>; 16]> {
+        if self.now_playing == Some(id)
+            && let Some(sequence) = &self.playing_sequence
         {
-            Some(playlist) => playlist,
-            None => {
-                self.error_message = Some("Playlist not found".into());
-                return Task::none();
-            }
-        };
-
-        let tracks: Vec<Uuid> = playlist
-            .tracks
-            .iter()
-            .filter_map(|id| self.library.get(id).map(|entry| entry.id))
-            .collect();
-
-        if tracks.is_empty() {
-            self.error_message = Some("Playlist has no playable tracks".into());
-            return Task::none();
-        }
-
-        let start_track = if shuffle {
-            let mut rng = rng();
-            *tracks.as_slice().choose(&mut rng).unwrap()
-        } else {
-            tracks[0]
-        };
-
-        if self.queue_with_tracks(
-            tracks,
-            start_track,
-            QueueMode::Playlist(playlist_id),
-            shuffle,
-        ) {
-            self.status_message = Some(format!("Playing playlist '{}'", playlist.name));
-            self.play_track(start_track)
-        } else {
-            Task::none()
+            return Some(&sequence.channel_names);
         }
+        self.song_info
+            .as_ref()
+            .filter(|panel| panel.entry_id == id)
+            .and_then(|panel| panel.info.as_ref())
+            .map(|info| &info.channel_names)
     }
 
-    fn queue_with_tracks(
-        &mut self,
-        tracks: Vec<Uuid>,
-        start_track: Uuid,
-        mode: QueueMode,
-        shuffle: bool,
-    ) -> bool {
-        if self.library.get(&start_track).is_none() {
-            self.error_message = Some("Selected track is not available".into());
-            return false;
+    fn score_overlay(&self) -> Option<Element<'_, Message>> {
+        let settings = &self.user_prefs.score_overlay;
+        if !settings.enabled || !matches!(self.playback_phase, PlaybackPhase::Playing) {
+            return None;
         }
+        let position = self
+            .playing_sequence
+            .as_ref()
+            .zip(self.score_time())
+            .and_then(|(sequence, now)| sequence.beat_at(now))?;
 
-        let mut seen = HashSet::new();
-        let mut ordered = Vec::new();
-        for id in tracks.into_iter() {
-            if self.library.get(&id).is_some() && seen.insert(id) {
-                ordered.push(id);
-            }
-        }
+        let beats: String = (1..=position.beats_per_measure)
+            .map(|beat| if beat <= position.beat { '●' } else { '○' })
+            .collect();
+        let nudge = row![
+            button(t!("−1 bar"))
+                .on_press(Message::ScoreOverlayShiftBar(-1))
+                .style(iced::widget::button::secondary),
+            button(t!("Tap"))
+                .on_press(Message::ScoreOverlayTap)
+                .style(iced::widget::button::primary),
+            button(t!("+1 bar"))
+                .on_press(Message::ScoreOverlayShiftBar(1))
+                .style(iced::widget::button::secondary),
+        ]
+        .spacing(12);
 
-        if ordered.is_empty() {
-            self.play_queue = None;
-            return false;
-        }
+        let overlay = row![
+            text(position.measure.to_string())
+                .size(settings.text_size)
+                .width(Length::Shrink),
+            column![
+                text(beats)
+                    .shaping(Shaping::Advanced)
+                    .size((settings.text_size / 3).max(16)),
+                text(t!(
+                    "Beat {beat}/{beats_per_measure}",
+                    beat = position.beat,
+                    beats_per_measure = position.beats_per_measure
+                )),
+                nudge,
+            ]
+            .spacing(8),
+        ]
+        .spacing(24)
+        .align_y(iced::Alignment::Center);
+
+        Some(
+            container(overlay)
+                .padding(12)
+                .width(Length::Fill)
+                .style(container::rounded_box)
+                .into(),
+        )
+    }
 
-        if seen.insert(start_track) {
-            ordered.insert(0, start_track);
-        }
+    fn song_info_panel<'a>(&'a self, panel: &'a SongInfoPanel) -> Element<'a, Message> {
+        let header = row![
+            text(t!("Song information: {name}", name = panel.name))
+                .shaping(Shaping::Advanced)
+                .size(18)
+                .width(Length::Fill),
+            button(t!("Close"))
+                .on_press(Message::CloseSongInfo)
+                .style(iced::widget::button::secondary),
+        ]
+        .align_y(iced::Alignment::Center);
+        let mut details = column![
+            header,
+            text(t!("Path: {path}", path = panel.path.display())).shaping(Shaping::Advanced),
+            self.song_details_editor(panel),
+            self.tag_editor(panel.entry_id),
+        ]
+        .push_maybe(self.playlist_badges(panel.entry_id))
+        .push_maybe(self.work_editor(panel.entry_id))
+        .spacing(6);
+
+        let Some(info) = &panel.info else {
+            return container(details.push(text(t!("Reading file..."))))
+                .padding(12)
+                .width(Length::Fill)
+                .style(container::rounded_box)
+                .into();
+        };
 
-        if shuffle {
-            let mut rng = rng();
-            ordered.shuffle(&mut rng);
+        let timing = match info.ppq {
+            Some(ppq) => format!("{ppq} PPQ"),
+            None => t!("SMPTE timecode").to_owned(),
+        };
+        let excerpt = &panel.excerpt;
+        let placeholder = match excerpt.unit {
+            ExcerptUnit::Bars => "1",
+            ExcerptUnit::Time => "0:00",
+        };
+        details = details.push(
+            row![
+                text(t!("Excerpt")),
+                pick_list(
+                    ExcerptUnit::ALL,
+                    Some(excerpt.unit),
+                    Message::ExcerptUnitSelected
+                ),
+                text(t!("from")),
+                text_input(placeholder, &excerpt.from)
+                    .on_input(Message::ExcerptFromChanged)
+                    .width(Length::Fixed(70.0)),
+                text(t!("to")),
+                text_input(placeholder, &excerpt.to)
+                    .on_input(Message::ExcerptToChanged)
+                    .on_submit(Message::SaveExcerpt)
+                    .width(Length::Fixed(70.0)),
+                button(t!("Save excerpt"))
+                    .on_press_maybe(excerpt.range().map(|_| Message::SaveExcerpt))
+                    .style(iced::widget::button::secondary),
+            ]
+            .push_maybe(
+                (info.measures > 0)
+                    .then(|| text(t!("{measures} bar(s)", measures = info.measures)).size(12)),
+            )
+            .spacing(8)
+            .align_y(iced::Alignment::Center),
+        );
+        details = details.push(text(t!(
+            "Size: {size} KB · SMF format {format} · {timing} · {tracks} track(s) · {notes} note(s)",
+            size = format!("{:.1}", info.file_size as f64 / 1024.0),
+            format = info.format,
+            timing = timing,
+            tracks = info.tracks.len(),
+            notes = info.note_count()
+        )));
+
+        let tempos = if info.tempo_changes.is_empty() {
+            "120 BPM (default)".to_owned()
+        } else {
+            info.tempo_changes
+                .iter()
+                .map(|change| format!("{:.0} BPM @ {}", change.bpm, format_duration(change.at)))
+                .collect::<Vec<_>>()
+                .join(", ")
+        };
+        details = details.push(text(t!("Tempo: {tempos}", tempos = tempos)));
+        if let Some(tempo) = info.tempo {
+            details = details.push(text(t!(
+                "Starts at {initial} BPM · averages {average} BPM",
+                initial = format!("{:.0}", tempo.initial_bpm),
+                average = format!("{:.0}", tempo.average_bpm)
+            )));
         }
 
-        if let Some(pos) = ordered.iter().position(|id| *id == start_track) {
-            ordered.swap(0, pos);
+        let signatures = if info.time_signatures.is_empty() {
+            t!("4/4 (default)").to_owned()
         } else {
-            ordered.insert(0, start_track);
-        }
+            info.time_signatures
+                .iter()
+                .map(|sig| {
+                    format!(
+                        "{}/{} @ {}",
+                        sig.numerator,
+                        sig.denominator,
+                        format_duration(sig.at)
+                    )
+                })
+                .collect::<Vec<_>>()
+                .join(", ")
+        };
+        details = details.push(text(t!(
+            "Time signatures: {signatures}",
+            signatures = signatures
+        )));
 
-        self.play_queue = Some(PlayQueue {
-            tracks: ordered,
-            index: 0,
-            mode,
-        });
-        self.selected_song = Some(start_track);
-        true
-    }
+        let channels = info
+            .channels
+            .iter()
+            .map(|channel| match &info.channel_names[usize::from(*channel)] {
+                Some(name) => format!("{} ({name})", channel + 1),
+                None => (channel + 1).to_string(),
+            })
+            .collect::<Vec<_>>()
+            .join(", ");
+        details = details.push(
+            text(t!("Channels used: {channels}", channels = channels)).shaping(Shaping::Advanced),
+        );
+
+        let programs = if info.programs.is_empty() {
+            t!("none (default piano)").to_owned()
+        } else {
+            info.programs
+                .iter()
+                .map(|(channel, program)| {
+                    format!(
+                        "ch{}: {} {}",
+                        channel + 1,
+                        program + 1,
+                        monitor::gm_program_name(*program)
+                    )
+                })
+                .collect::<Vec<_>>()
+                .join(", ")
+        };
+        details = details.push(text(t!("Instruments: {programs}", programs = programs)));
 
-    fn advance_queue(&mut self, forward: bool) -> Option<Uuid> {
-        let queue = self.play_queue.as_mut()?;
-        if queue.tracks.is_empty() {
-            self.play_queue = None;
-            return None;
+        details = details.push(text(t!("Tracks")).size(16));
+        for (index, track) in info.tracks.iter().enumerate() {
+            let channels = track
+                .channels
+                .iter()
+                .map(|channel| (channel + 1).to_string())
+                .collect::<Vec<_>>()
+                .join(", ");
+            details = details.push(
+                text(t!(
+                    "{number}. {name} — channels [{channels}] — {notes} note(s)",
+                    number = index + 1,
+                    name = track.name.as_deref().unwrap_or(t!("(unnamed)")),
+                    channels = channels,
+                    notes = track.note_count
+                ))
+                .shaping(Shaping::Advanced),
+            );
         }
-        if forward {
-            if queue.index + 1 < queue.tracks.len() {
-                queue.index += 1;
-                let track = queue.tracks[queue.index];
-                self.selected_song = Some(track);
-                Some(track)
-            } else {
-                self.play_queue = None;
-                self.status_message = Some("Queue finished".into());
-                None
+
+        if let Some((lowest, highest)) = info.note_range() {
+            details = details.push(
+                text(t!(
+                    "Note range: {lowest} – {highest}",
+                    lowest = sequence::note_name(lowest),
+                    highest = sequence::note_name(highest)
+                ))
+                .size(16)
+                .shaping(Shaping::Advanced),
+            );
+            // One bar per octave keeps the histogram readable at a glance.
+            let octaves: Vec<(u8, u32)> = (lowest / 12..=highest / 12)
+                .map(|octave| {
+                    let start = octave as usize * 12;
+                    let end = (start + 12).min(128);
+                    (octave, info.note_histogram[start..end].iter().sum())
+                })
+                .collect();
+            let max = octaves
+                .iter()
+                .map(|(_, count)| *count)
+                .max()
+                .unwrap_or(1)
+                .max(1);
+            for (octave, count) in octaves {
+                let width = (count as f32 / max as f32 * 40.0).round() as usize;
+                details = details.push(
+                    text(format!(
+                        "{:>4} {} {count}",
+                        sequence::note_name(octave * 12),
+                        "█".repeat(width)
+                    ))
+                    .font(Font::MONOSPACE)
+                    .shaping(Shaping::Advanced),
+                );
             }
-        } else if queue.index > 0 {
-            queue.index -= 1;
-            let track = queue.tracks[queue.index];
-            self.selected_song = Some(track);
-            Some(track)
-        } else {
-            self.status_message = Some("Already at the beginning".into());
-            None
         }
+
+        container(scrollable(details).height(Length::Fixed(320.0)))
+            .padding(12)
+            .width(Length::Fill)
+            .style(container::rounded_box)
+            .into()
     }
 
-    fn queue_label(&self, queue: &PlayQueue) -> String {
-        let mode_label = match &queue.mode {
-            QueueMode::Single => "Single".to_string(),
-            QueueMode::Favorites => "Favorites".to_string(),
-            QueueMode::Playlist(id) => self
-                .user_prefs
-                .playlists
-                .iter()
-                .find(|playlist| &playlist.id == id)
-                .map(|playlist| playlist.name.clone())
-                .unwrap_or_else(|| "Playlist".into()),
-        };
-        format!("{}: {}/{}", mode_label, queue.index + 1, queue.tracks.len())
+    fn render_panel(&self, job: &RenderJob) -> Element<'_, Message> {
+        row![
+            text(t!("Rendering {name} to WAV...", name = job.name)).shaping(Shaping::Advanced),
+            progress_bar(0.0..=1.0, job.progress).height(Length::Fixed(12.0)),
+            text(format!("{:.0}%", job.progress * 100.0)),
+            button(t!("Cancel"))
+                .on_press(Message::CancelRender)
+                .style(iced::widget::button::secondary),
+        ]
+        .spacing(12)
+        .align_y(iced::Alignment::Center)
+        .into()
     }
 
-    fn current_track_label(&self) -> String {
-        if let Some(id) = self.selected_song {
-            if let Some(entry) = self.library.get(&id) {
-                return format!("Now: {}", entry.name);
-            }
+    fn capability_warning_panel(&self, warning: &CapabilityWarning) -> Element<'_, Message> {
+        let name = self
+            .library
+            .get(&warning.prepared.track_id)
+            .map_or("", |entry| entry.name.as_str());
+        let mut panel = column![
+            text(t!(
+                "{name} needs more than this device can play:",
+                name = name
+            ))
+            .shaping(Shaping::Advanced)
+        ]
+        .spacing(8);
+        for issue in &warning.issues {
+            let detail = match issue {
+                CapabilityIssue::Channels(channels) => t!(
+                    "Parts on channels {channels}",
+                    channels = channels
+                        .iter()
+                        .map(|channel| (channel + 1).to_string())
+                        .collect::<Vec<_>>()
+                        .join(", ")
+                ),
+                CapabilityIssue::Percussion => t!("Drums on channel 10").to_string(),
+                CapabilityIssue::Programs(programs) => t!(
+                    "Other instruments: {programs}",
+                    programs = programs
+                        .iter()
+                        .map(|program| monitor::gm_program_name(*program))
+                        .collect::<Vec<_>>()
+                        .join(", ")
+                ),
+            };
+            panel = panel.push(text(format!("• {detail}")).shaping(Shaping::Advanced));
         }
-        "Now: --".into()
+        panel
+            .push(
+                row![
+                    button(t!("Remap to Fit"))
+                        .on_press(Message::CapabilityWarningResolved(CapabilityChoice::Remap)),
+                    button(t!("Always Remap for This Device"))
+                        .on_press(Message::CapabilityWarningResolved(
+                            CapabilityChoice::AlwaysRemap
+                        ))
+                        .style(iced::widget::button::secondary),
+                    button(t!("Play as Written"))
+                        .on_press(Message::CapabilityWarningResolved(
+                            CapabilityChoice::PlayAsWritten
+                        ))
+                        .style(iced::widget::button::secondary),
+                    button(t!("Cancel"))
+                        .on_press(Message::CapabilityWarningResolved(CapabilityChoice::Cancel))
+                        .style(iced::widget::button::secondary),
+                ]
+                .spacing(12),
+            )
+            .into()
     }
 
-    fn play_track(&mut self, track_id: Uuid) -> Task<Message> {
-        if self.is_preparing_playback {
-            self.status_message = Some("Already preparing a track".into());
-            return Task::none();
+    fn library_load_panel(&self) -> Option<Element<'_, Message>> {
+        if let Some(job) = &self.library_load {
+            let progress = if job.progress.total == 0 {
+                0.0
+            } else {
+                job.progress.verified as f32 / job.progress.total as f32
+            };
+            return Some(
+                row![
+                    text(t!("Loading library...")),
+                    progress_bar(0.0..=1.0, progress).height(Length::Fixed(12.0)),
+                    text(t!(
+                        "{verified} of {total} assets verified",
+                        verified = job.progress.verified,
+                        total = job.progress.total
+                    )),
+                    button(t!("Cancel"))
+                        .on_press(Message::CancelLibraryLoad)
+                        .style(iced::widget::button::secondary),
+                ]
+                .spacing(12)
+                .align_y(iced::Alignment::Center)
+                .into(),
+            );
         }
-
-        let entry = match self.library.get(&track_id).cloned() {
-            Some(entry) => entry,
-            None => {
-                self.error_message = Some("Track not available".into());
-                return Task::none();
-            }
-        };
-
-        let device_id = match self.selected_device {
-            Some(id) => id,
-            None => {
-                self.error_message = Some("Select a MIDI output device first".into());
-                return Task::none();
-            }
-        };
-
-        self.is_preparing_playback = true;
-        self.playback_phase = PlaybackPhase::Preparing;
-        self.status_message = Some(format!("Preparing {}", entry.name));
-        self.selected_song = Some(track_id);
-        let path = entry.path.clone();
-
-        Task::perform(
-            prepare_playback(path, device_id, self.device_manager.clone()),
-            Message::PlaybackPrepared,
+        let error = self.library_load_error.as_ref()?;
+        Some(
+            row![
+                text(t!("Library loading stopped: {reason}", reason = error))
+                    .shaping(Shaping::Advanced)
+                    .width(Length::Fill),
+                button(t!("Retry"))
+                    .on_press(Message::RetryLibraryLoad)
+                    .style(iced::widget::button::secondary),
+            ]
+            .spacing(12)
+            .align_y(iced::Alignment::Center)
+            .into(),
         )
     }
 
-    fn device_section(&self) -> Element<'_, Message> {
-        let selected_choice = self
-            .selected_device
-            .and_then(|id| self.devices.iter().find(|choice| choice.id == id))
-            .cloned();
-
-        let pick_list = pick_list(
-            self.devices.clone(),
-            selected_choice,
-            |choice: DeviceChoice| Message::DeviceSelected(choice.id),
-        )
-        .placeholder(if self.is_scanning_devices {
-            "Scanning devices..."
+    fn folder_import_panel(&self, job: &FolderImportJob) -> Element<'_, Message> {
+        let progress = if job.total == 0 {
+            0.0
         } else {
-            "Select output device"
-        });
-
-        let refresh_button = button("Refresh").on_press(Message::RefreshDevices);
-        let add_button = button("Add Local MIDI").on_press(Message::AddLocalFile);
-
+            job.done as f32 / job.total as f32
+        };
         row![
-            pick_list,
-            refresh_button.style(iced::widget::button::secondary),
-            add_button.style(iced::widget::button::secondary)
+            text(t!("Importing {name}...", name = job.name)).shaping(Shaping::Advanced),
+            progress_bar(0.0..=1.0, progress).height(Length::Fixed(12.0)),
+            text(format!("{} of {}", job.done, job.total)),
+            button(t!("Cancel"))
+                .on_press(Message::CancelFolderImport)
+                .style(iced::widget::button::secondary),
         ]
         .spacing(12)
+        .align_y(iced::Alignment::Center)
         .into()
     }
 
     fn library_tabs(&self) -> Element<'_, Message> {
-        let mut tree_button = button(text("Tree").shaping(Shaping::Advanced));
+        let mut tree_button = button(text(t!("Tree")).shaping(Shaping::Advanced));
         if self.active_tab == LibraryTab::Tree {
             tree_button = tree_button.style(iced::widget::button::primary);
         } else {
@@ -1099,7 +10285,7 @@ impl MidiPianoApp {
         }
         let tree_button = tree_button.on_press(Message::SwitchTab(LibraryTab::Tree));
 
-        let mut favorites_button = button(text("Favorites").shaping(Shaping::Advanced));
+        let mut favorites_button = button(text(t!("Favorites")).shaping(Shaping::Advanced));
         if self.active_tab == LibraryTab::Favorites {
             favorites_button = favorites_button.style(iced::widget::button::primary);
         } else {
@@ -1107,7 +10293,57 @@ impl MidiPianoApp {
         }
         let favorites_button = favorites_button.on_press(Message::SwitchTab(LibraryTab::Favorites));
 
-        row![tree_button, favorites_button].spacing(12).into()
+        let recent_button = button(text(t!("Recent")).shaping(Shaping::Advanced))
+            .style(if self.active_tab == LibraryTab::Recent {
+                iced::widget::button::primary
+            } else {
+                iced::widget::button::secondary
+            })
+            .on_press(Message::SwitchTab(LibraryTab::Recent));
+
+        let lessons_button = button(text(t!("Lessons")).shaping(Shaping::Advanced))
+            .style(if self.active_tab == LibraryTab::Lessons {
+                iced::widget::button::primary
+            } else {
+                iced::widget::button::secondary
+            })
+            .on_press(Message::SwitchTab(LibraryTab::Lessons));
+
+        row![tree_button, favorites_button, recent_button, lessons_button]
+            .spacing(12)
+            .into()
+    }
+
+    /// Track, progress and transport only, for running in a corner.
+    fn mini_player_view(&self) -> Element<'_, Message> {
+        let transport = |label: &'static str, message: Message| {
+            button(text(label).shaping(Shaping::Advanced))
+                .on_press(message)
+                .style(iced::widget::button::secondary)
+        };
+        let controls = row![
+            transport("⏮", Message::PrevTrack),
+            button(text("▶").shaping(Shaping::Advanced))
+                .on_press(Message::PlayPressed)
+                .style(iced::widget::button::primary),
+            transport("⏹", Message::StopPressed),
+            transport("⏭", Message::NextTrack),
+            iced::widget::horizontal_space(),
+            button(t!("Expand"))
+                .on_press(Message::ToggleMiniPlayer)
+                .style(iced::widget::button::text),
+        ]
+        .spacing(8)
+        .align_y(iced::Alignment::Center);
+
+        column![
+            text(self.current_track_label()).shaping(Shaping::Advanced),
+            self.seek_bar(),
+            controls,
+        ]
+        .spacing(8)
+        .padding(12)
+        .into()
     }
 
     fn playback_controls(&self) -> Element<'_, Message> {
@@ -1115,11 +10351,11 @@ impl MidiPianoApp {
             .on_press(Message::PrevTrack)
             .style(iced::widget::button::secondary);
 
-        let play_button = button("Play Selected")
+        let play_button = button(t!("Play Selected"))
             .on_press(Message::PlayPressed)
             .style(iced::widget::button::primary);
 
-        let stop_button = button("Stop")
+        let stop_button = button(t!("Stop"))
             .on_press(Message::StopPressed)
             .style(iced::widget::button::secondary);
 
@@ -1127,21 +10363,30 @@ impl MidiPianoApp {
             .on_press(Message::NextTrack)
             .style(iced::widget::button::secondary);
 
+        let panic_button = button(t!("Panic"))
+            .on_press(Message::PanicPressed)
+            .style(iced::widget::button::danger);
+
+        let piano_mode_button = button(t!("Piano Mode"))
+            .on_press(Message::TogglePianoMode)
+            .style(if self.user_prefs.piano_mode {
+                iced::widget::button::primary
+            } else {
+                iced::widget::button::secondary
+            });
+
+        let export_button = button(t!("Export WAV"))
+            .on_press_maybe(
+                (self.selected_song.is_some() && self.render_job.is_none())
+                    .then_some(Message::ExportWav),
+            )
+            .style(iced::widget::button::secondary);
+
         let status_text = match self.playback_phase {
-            PlaybackPhase::Idle => text("Ready"),
-            PlaybackPhase::Preparing => text("Preparing playback..."),
-            PlaybackPhase::Playing => {
-                if let Some(progress) = &self.playback_progress {
-                    text(format!(
-                        "Playing ({}/{} )",
-                        format_duration(progress.elapsed),
-                        format_duration(progress.total)
-                    ))
-                } else {
-                    text("Playing...")
-                }
-            }
-            PlaybackPhase::Finished => text("Completed"),
+            PlaybackPhase::Idle => text(t!("Ready")),
+            PlaybackPhase::Preparing => text(t!("Preparing playback...")),
+            PlaybackPhase::Playing => text(t!("Playing...")),
+            PlaybackPhase::Finished => text(t!("Completed")),
         }
         .shaping(Shaping::Advanced)
         .size(16)
@@ -1151,32 +10396,466 @@ impl MidiPianoApp {
             let label = self.queue_label(queue);
             text(label).shaping(Shaping::Advanced)
         } else {
-            text("Queue: none").shaping(Shaping::Advanced)
+            text(t!("Queue: none")).shaping(Shaping::Advanced)
         };
+        let queue_button = self.play_queue.as_ref().map(|_| {
+            button(if self.show_queue {
+                t!("Hide Queue")
+            } else {
+                t!("Show Queue")
+            })
+            .on_press(Message::ToggleQueuePanel)
+            .style(iced::widget::button::secondary)
+        });
 
         let current_text = text(self.current_track_label()).shaping(Shaping::Advanced);
 
-        row![
+        let sleep_timer = pick_list(
+            SleepTimerChoice::ALL,
+            Some(self.sleep_timer_choice),
+            Message::SleepTimerSelected,
+        )
+        .text_shaping(Shaping::Advanced);
+
+        let repeat = row![
+            text(t!("Repeat")),
+            button(text("−").shaping(Shaping::Advanced))
+                .on_press_maybe((self.repeat_passes > 1).then_some(Message::RepeatStep(-1)))
+                .style(iced::widget::button::secondary),
+            text(format!("×{}", self.repeat_passes)).shaping(Shaping::Advanced),
+            button(text("+").shaping(Shaping::Advanced))
+                .on_press_maybe(
+                    (self.repeat_passes < MAX_REPEAT_PASSES).then_some(Message::RepeatStep(1)),
+                )
+                .style(iced::widget::button::secondary),
+        ]
+        .spacing(4)
+        .align_y(iced::Alignment::Center);
+
+        let transport = row![
             prev_button,
             play_button,
             stop_button,
             next_button,
-            status_text,
-            queue_text,
-            current_text
+            panic_button,
+            piano_mode_button,
+            export_button,
+            sleep_timer,
+            repeat,
         ]
+        .push_maybe(
+            self.sleep_timer_label()
+                .map(|label| text(label).shaping(Shaping::Advanced)),
+        )
+        .push(status_text)
+        .push(queue_text)
+        .push_maybe(queue_button)
+        .push(current_text)
         .spacing(12)
+        .align_y(iced::Alignment::Center);
+
+        let master_active = self.has_master_adjustments();
+        let master_label = text(if master_active {
+            t!(
+                "Session master: {master_tempo_percent}% · {master_transpose} st",
+                master_tempo_percent = self.master_tempo_percent,
+                master_transpose = format!("{:+}", self.master_transpose)
+            )
+        } else {
+            t!("Session master: off").to_owned()
+        })
+        .shaping(Shaping::Advanced)
+        .color_maybe(master_active.then(|| Color::from_rgb(0.95, 0.75, 0.3)));
+        let step = |label: &'static str, message: Message| {
+            button(text(label).shaping(Shaping::Advanced))
+                .on_press(message)
+                .style(iced::widget::button::secondary)
+        };
+        let master_row = row![
+            master_label,
+            text(t!("Speed")),
+            step("−", Message::MasterTempoStep(-5)),
+            step("+", Message::MasterTempoStep(5)),
+            text(t!("Transpose")),
+            step("−", Message::MasterTransposeStep(-1)),
+            step("+", Message::MasterTransposeStep(1)),
+            button(t!("Clear"))
+                .on_press_maybe(master_active.then_some(Message::ClearMasterAdjustments))
+                .style(iced::widget::button::secondary),
+        ]
+        .spacing(8)
+        .align_y(iced::Alignment::Center);
+
+        column![transport, self.seek_bar(), master_row]
+            .push_maybe(
+                self.play_queue
+                    .as_ref()
+                    .filter(|_| self.show_queue)
+                    .map(|queue| self.queue_panel(queue)),
+            )
+            .spacing(8)
+            .push_maybe(
+                matches!(self.playback_phase, PlaybackPhase::Playing)
+                    .then(|| self.channel_meters()),
+            )
+            .into()
+    }
+
+    /// The queued songs in order, with the playing one marked and songs put
+    /// in by hand labelled as such.
+    fn queue_panel(&self, queue: &PlayQueue) -> Element<'_, Message> {
+        let mut list = Column::new().spacing(2);
+        for (index, id) in queue.tracks.iter().enumerate() {
+            let name = self.library.get(id).map_or_else(
+                || t!("Track not available").to_string(),
+                |entry| entry.name.clone(),
+            );
+            let marker = if index == queue.index { "▶" } else { "" };
+            let color = (index < queue.index).then(|| Color::from_rgb(0.6, 0.6, 0.6));
+            list = list.push(
+                row![
+                    text(marker)
+                        .shaping(Shaping::Advanced)
+                        .width(Length::Fixed(20.0)),
+                    text(format!("{}.", index + 1)).width(Length::Fixed(36.0)),
+                    text(name).shaping(Shaping::Advanced).color_maybe(color),
+                ]
+                .push_maybe(queue.inserted.contains(id).then(|| {
+                    text(t!("added"))
+                        .size(12)
+                        .color(Color::from_rgb(0.95, 0.75, 0.3))
+                }))
+                .spacing(8)
+                .align_y(iced::Alignment::Center),
+            );
+        }
+        container(scrollable(list).height(Length::Fixed(180.0)))
+            .padding(8)
+            .width(Length::Fill)
+            .style(container::rounded_box)
+            .into()
+    }
+
+    /// One fading bar per MIDI channel. The numbers below toggle the
+    /// channel's mute for the playing song, from the next time it starts.
+    fn channel_meters(&self) -> Element<'_, Message> {
+        let muted_channels = self
+            .now_playing
+            .map_or(0, |id| self.song_settings(id).muted_channels);
+        let names = self.now_playing.and_then(|id| self.channel_names(id));
+        let mut meters = row![].spacing(4).align_y(iced::Alignment::End);
+        for (channel, level) in (0..16u8).zip(self.channel_levels) {
+            let muted = muted_channels & (1 << channel) != 0;
+            let bar = container(text(""))
+                .width(Length::Fill)
+                .height(Length::Fixed(METER_HEIGHT * level.clamp(0.0, 1.0)))
+                .style(move |theme: &Theme| {
+                    let palette = theme.extended_palette();
+                    container::background(if muted {
+                        palette.danger.weak.color
+                    } else {
+                        palette.success.base.color
+                    })
+                });
+            let meter = container(bar)
+                .width(Length::Fixed(18.0))
+                .height(Length::Fixed(METER_HEIGHT))
+                .align_y(Vertical::Bottom)
+                .style(container::bordered_box);
+            let label = button(text((channel + 1).to_string()).size(10))
+                .padding([1, 2])
+                .width(Length::Fixed(18.0))
+                .on_press_maybe(
+                    self.now_playing
+                        .map(|id| Message::SongChannelMuteToggled(id, channel)),
+                )
+                .style(if muted {
+                    iced::widget::button::danger
+                } else {
+                    iced::widget::button::text
+                });
+            let meter = column![meter, label]
+                .spacing(2)
+                .align_x(iced::Alignment::Center);
+            meters = match names.and_then(|names| names[usize::from(channel)].as_deref()) {
+                Some(name) => meters.push(tooltip(
+                    meter,
+                    container(text(name).size(12).shaping(Shaping::Advanced))
+                        .padding(4)
+                        .style(container::rounded_box),
+                    tooltip::Position::Top,
+                )),
+                None => meters.push(meter),
+            };
+        }
+        meters.into()
+    }
+
+    /// Elapsed and remaining time around a bar that can be dragged to seek
+    /// while playing. Preparing a track shows a label in place of the times.
+    fn seek_bar(&self) -> Element<'_, Message> {
+        const BAR_HEIGHT: f32 = 8.0;
+        let label = |content: String| text(content).size(14).width(Length::Fixed(56.0));
+
+        if matches!(self.playback_phase, PlaybackPhase::Preparing) {
+            return row![
+                text(t!("Preparing playback...")).size(14),
+                progress_bar(0.0..=1.0, 0.0)
+                    .height(Length::Fixed(BAR_HEIGHT))
+                    .style(iced::widget::progress_bar::secondary),
+                button(text(t!("Cancel")).size(14))
+                    .on_press(Message::CancelPreparing)
+                    .style(iced::widget::button::secondary),
+            ]
+            .spacing(8)
+            .align_y(iced::Alignment::Center)
+            .into();
+        }
+
+        let Some(progress) = &self.playback_progress else {
+            return row![
+                label("--:--".into()),
+                progress_bar(0.0..=1.0, 0.0).height(Length::Fixed(BAR_HEIGHT)),
+                label("--:--".into()),
+            ]
+            .spacing(8)
+            .align_y(iced::Alignment::Center)
+            .into();
+        };
+
+        let position = match self.seek_preview {
+            Some(preview) => preview.min(progress.total),
+            None if matches!(self.playback_phase, PlaybackPhase::Playing) => {
+                progress.interpolated(self.user_prefs.progress_interval() * 2)
+            }
+            None => progress.elapsed,
+        };
+        let remaining = progress.total.saturating_sub(position);
+        let bar: Element<'_, Message> = if matches!(self.playback_phase, PlaybackPhase::Playing)
+            && self.current_sink.is_some()
+        {
+            slider(
+                0.0..=progress.total.as_secs_f32(),
+                position.as_secs_f32(),
+                Message::SeekChanged,
+            )
+            .on_release(Message::SeekReleased)
+            .step(0.1)
+            .into()
+        } else {
+            let fraction = if progress.total.is_zero() {
+                0.0
+            } else {
+                position.as_secs_f32() / progress.total.as_secs_f32()
+            };
+            progress_bar(0.0..=1.0, fraction)
+                .height(Length::Fixed(BAR_HEIGHT))
+                .into()
+        };
+
+        // Bars and beats follow the file's tempo and time signature
+        // changes, so they stay in step with a printed score.
+        let bar_beat = self
+            .playing_sequence
+            .as_ref()
+            .filter(|_| self.now_playing.is_some())
+            .and_then(|sequence| sequence.beat_at(position))
+            .map(|beat| label(beat.to_string()).font(Font::MONOSPACE));
+
+        let bpm = self
+            .playback_bpm
+            .filter(|_| self.now_playing.is_some())
+            .map(|bpm| text(t!("{bpm} BPM", bpm = format!("{bpm:.0}"))).size(14));
+
+        let pass = (self.repeat_passes > 1).then(|| {
+            text(t!(
+                "pass {pass}/{passes}",
+                pass = self.playback_pass.min(self.repeat_passes),
+                passes = self.repeat_passes
+            ))
+            .size(14)
+        });
+
+        row![]
+            .push_maybe(pass)
+            .push_maybe(bpm)
+            .push_maybe(bar_beat)
+            .push(label(format_duration(position)))
+            .push(bar)
+            .push(label(format!("-{}", format_duration(remaining))))
+            .spacing(8)
+            .align_y(iced::Alignment::Center)
+            .into()
+    }
+
+    /// Rating, favorite and tag filters applied on top of the search in
+    /// every tab.
+    fn filter_bar(&self) -> Element<'_, Message> {
+        let mut chips = row![
+            pick_list(
+                RatingFilter::ALL,
+                Some(self.min_rating),
+                Message::MinRatingSelected,
+            )
+            .text_shaping(Shaping::Advanced),
+            checkbox(t!("Favorites only"), self.favorites_only)
+                .on_toggle(Message::FavoritesOnlyToggled),
+        ]
+        .spacing(6)
+        .align_y(iced::Alignment::Center);
+        let tags = self.user_prefs.all_tags();
+        if !tags.is_empty() {
+            chips = chips.push(text(t!("Tags:")));
+        }
+        for &tag in &tags {
+            let active = self.tag_filter.tags.contains(tag);
+            chips = chips.push(
+                button(text(tag.as_str()).shaping(Shaping::Advanced).size(13))
+                    .padding([2, 8])
+                    .style(if active {
+                        iced::widget::button::primary
+                    } else {
+                        iced::widget::button::secondary
+                    })
+                    .on_press(Message::TagFilterToggled(tag.clone())),
+            );
+        }
+        if !tags.is_empty() {
+            chips = chips.push(
+                checkbox(t!("Match all"), self.tag_filter.match_all)
+                    .on_toggle(Message::TagFilterMatchAllToggled),
+            );
+        }
+        let filtering =
+            !self.tag_filter.tags.is_empty() || self.min_rating.0 > 0 || self.favorites_only;
+        chips = chips.push(
+            button(t!("Clear"))
+                .padding([2, 8])
+                .style(iced::widget::button::text)
+                .on_press_maybe(filtering.then_some(Message::ClearLibraryFilters)),
+        );
+        chips.wrap().into()
+    }
+
+    /// The title and composer shown for a song in place of its file name.
+    fn song_details_editor<'a>(&self, panel: &'a SongInfoPanel) -> Element<'a, Message> {
+        let file_name = self
+            .library
+            .get(&panel.entry_id)
+            .map_or("", |entry| entry.file_name.as_str());
+        row![
+            text(t!("Title:")),
+            text_input(file_name, &panel.title_draft)
+                .on_input(Message::SongTitleChanged)
+                .on_submit(Message::SaveSongDetails)
+                .width(Length::Fixed(240.0))
+                .padding(4),
+            text(t!("Composer:")),
+            text_input("", &panel.composer_draft)
+                .on_input(Message::SongComposerChanged)
+                .on_submit(Message::SaveSongDetails)
+                .width(Length::Fixed(180.0))
+                .padding(4),
+            button(t!("Save"))
+                .padding([2, 8])
+                .on_press(Message::SaveSongDetails),
+            button(t!("Fill from File"))
+                .padding([2, 8])
+                .on_press(Message::FillSongDetailsFromFile)
+                .style(iced::widget::button::secondary),
+        ]
+        .spacing(6)
         .align_y(iced::Alignment::Center)
+        .wrap()
         .into()
     }
 
+    fn tag_editor(&self, entry_id: Uuid) -> Element<'_, Message> {
+        let mut tags = row![text(t!("Tags:"))]
+            .spacing(6)
+            .align_y(iced::Alignment::Center);
+        for tag in self.user_prefs.tags.get(&entry_id).into_iter().flatten() {
+            tags = tags.push(
+                button(text(format!("{tag} ×")).shaping(Shaping::Advanced).size(13))
+                    .padding([2, 8])
+                    .style(iced::widget::button::secondary)
+                    .on_press(Message::RemoveTag(entry_id, tag.clone())),
+            );
+        }
+        tags = tags
+            .push(
+                text_input(t!("Add tag"), &self.tag_draft)
+                    .on_input(Message::TagDraftChanged)
+                    .on_submit(Message::AddTag(entry_id))
+                    .width(Length::Fixed(160.0))
+                    .padding(4),
+            )
+            .push(
+                button(t!("Add")).padding([2, 8]).on_press_maybe(
+                    normalize_tag(&self.tag_draft).map(|_| Message::AddTag(entry_id)),
+                ),
+            );
+        tags.wrap().into()
+    }
+
+    /// The playlists a song is on, each removable from there.
+    fn playlist_badges(&self, entry_id: Uuid) -> Option<Element<'_, Message>> {
+        let playlists = self.playlist_index.get(&entry_id)?;
+        let mut badges = row![text(t!("In playlists:"))]
+            .spacing(6)
+            .align_y(iced::Alignment::Center);
+        for id in playlists {
+            let Some(playlist) = self
+                .user_prefs
+                .playlists
+                .iter()
+                .find(|playlist| playlist.id == *id)
+            else {
+                continue;
+            };
+            badges = badges.push(
+                button(
+                    text(format!("{} ×", playlist.name))
+                        .shaping(Shaping::Advanced)
+                        .size(13),
+                )
+                .padding([2, 8])
+                .style(iced::widget::button::secondary)
+                .on_press(Message::RemoveFromPlaylist(*id, entry_id)),
+            );
+        }
+        Some(badges.wrap().into())
+    }
+
     fn library_view(&self) -> Element<'_, Message> {
-        let search = text_input("Search MIDI files...", &self.search_query)
-            .on_input(Message::SearchChanged)
-            .padding(8);
+        let search = column![
+            row![
+                text_input(t!("Search MIDI files..."), &self.search_query)
+                    .on_input(Message::SearchChanged)
+                    .padding(8),
+                text(t!("Sort by")),
+                pick_list(
+                    LibrarySort::ALL,
+                    Some(self.user_prefs.library_sort),
+                    Message::LibrarySortSelected,
+                ),
+                button(t!("Export Catalog"))
+                    .on_press(Message::ExportCatalog)
+                    .style(iced::widget::button::secondary),
+                button(t!("Import Ratings"))
+                    .on_press(Message::ImportCatalog)
+                    .style(iced::widget::button::secondary),
+            ]
+            .spacing(8)
+            .align_y(iced::Alignment::Center)
+        ]
+        .push(self.filter_bar())
+        .spacing(8);
 
         let entries = self.visible_entries();
-        let list = scrollable(self.entry_column(entries)).height(Length::Fill);
+        let list = scrollable(self.entry_column(entries))
+            .id(library_list_id())
+            .height(Length::Fill);
 
         match self.active_tab {
             LibraryTab::Tree => {
@@ -1195,44 +10874,299 @@ impl MidiPianoApp {
                 .height(Length::Fill)
                 .into()
             }
-            LibraryTab::Favorites => {
-                let play_row = row![
-                    button("Play Favorites")
-                        .on_press(Message::PlayFavorites { shuffle: false })
-                        .style(iced::widget::button::primary),
-                    button("Shuffle Favorites")
-                        .on_press(Message::PlayFavorites { shuffle: true })
+            LibraryTab::Favorites => {
+                let play_row = row![
+                    button(t!("Play Favorites"))
+                        .on_press(Message::PlayFavorites { shuffle: false })
+                        .style(iced::widget::button::primary),
+                    button(t!("Shuffle Favorites"))
+                        .on_press(Message::PlayFavorites { shuffle: true })
+                        .style(iced::widget::button::secondary)
+                ]
+                .spacing(12);
+
+                column![search, play_row, list]
+                    .spacing(12)
+                    .height(Length::Fill)
+                    .into()
+            }
+            LibraryTab::Recent => {
+                let last = self
+                    .user_prefs
+                    .recently_played
+                    .iter()
+                    .find_map(|id| self.library.get(id));
+                let continue_row = row![
+                    button(
+                        text(match last {
+                            Some(entry) => t!("Continue: {name}", name = entry.name),
+                            None => t!("Nothing played yet").to_string(),
+                        })
+                        .shaping(Shaping::Advanced)
+                    )
+                    .on_press_maybe(last.map(|entry| Message::StartPlayback(entry.id)))
+                    .style(iced::widget::button::primary),
+                    button(t!("Clear History"))
+                        .on_press_maybe(
+                            (!self.user_prefs.recently_played.is_empty())
+                                .then_some(Message::ClearRecentlyPlayed)
+                        )
                         .style(iced::widget::button::secondary)
                 ]
                 .spacing(12);
 
-                column![search, play_row, list]
+                column![search, continue_row, list]
                     .spacing(12)
                     .height(Length::Fill)
                     .into()
             }
+            LibraryTab::Lessons => scrollable(self.lessons_view()).height(Length::Fill).into(),
+        }
+    }
+
+    fn lessons_view(&self) -> Column<'_, Message> {
+        let mut column = Column::new().spacing(12).push(
+            row![
+                text(t!("Assignments")).size(18).width(Length::Fill),
+                button(t!("Import Assignment"))
+                    .on_press(Message::ImportAssignment)
+                    .style(iced::widget::button::primary),
+            ]
+            .align_y(iced::Alignment::Center),
+        );
+
+        if self.user_prefs.assignments.is_empty() {
+            column = column.push(
+                text(t!("No assignments yet. Import a file from your teacher."))
+                    .shaping(Shaping::Advanced),
+            );
+        }
+
+        for assignment in &self.user_prefs.assignments {
+            let progress = assignment.progress(&self.practice_log);
+            let done = assignment
+                .items
+                .iter()
+                .zip(&progress)
+                .filter(|(item, progress)| progress.is_done(item))
+                .count();
+            let due_label = match assignment.due {
+                Some(due) if assignment.is_overdue() => {
+                    text(t!("Overdue since {due}", due = due)).color(Color::from_rgb(0.9, 0.4, 0.4))
+                }
+                Some(due) => text(t!("Due {due}", due = due)),
+                None => text(t!("No due date")),
+            };
+            let mut card = column![
+                row![
+                    text(&assignment.title)
+                        .shaping(Shaping::Advanced)
+                        .size(16)
+                        .width(Length::Fill),
+                    due_label,
+                    text(format!("{done}/{} done", assignment.items.len())),
+                    button(t!("Export Results"))
+                        .on_press(Message::ExportAssignmentResults(assignment.id))
+                        .style(iced::widget::button::secondary),
+                    button(t!("Remove"))
+                        .on_press(Message::RemoveAssignment(assignment.id))
+                        .style(iced::widget::button::danger),
+                ]
+                .spacing(12)
+                .align_y(iced::Alignment::Center)
+            ]
+            .spacing(6);
+
+            for (item, progress) in assignment.items.iter().zip(&progress) {
+                let check = if progress.is_done(item) { "☑" } else { "☐" };
+                let mut label = format!("{check} {}", item.entry_name);
+                if !item.section.is_empty() {
+                    label.push_str(&format!(" — {}", item.section));
+                }
+                let resolved = self.resolve_assignment_item(item);
+                card = card.push(
+                    row![
+                        text(label).shaping(Shaping::Advanced).width(Length::Fill),
+                        text(t!(
+                            "Tempo {target_tempo}%",
+                            target_tempo = item.target_tempo
+                        )),
+                        text(format!("{}/{} plays", progress.plays, item.target_plays)),
+                        button(text("▶").shaping(Shaping::Advanced))
+                            .on_press_maybe(resolved.map(Message::StartPlayback))
+                            .style(iced::widget::button::primary),
+                    ]
+                    .spacing(12)
+                    .align_y(iced::Alignment::Center),
+                );
+            }
+            column = column.push(container(card).padding(8).style(container::rounded_box));
+        }
+
+        let draft = &self.assignment_draft;
+        let mut builder = column![
+            text(t!("Create assignment")).size(18),
+            row![
+                text_input(t!("Assignment title"), &draft.title)
+                    .on_input(Message::AssignmentTitleChanged)
+                    .padding(6),
+                text_input(t!("Due (YYYY-MM-DD)"), &draft.due)
+                    .on_input(Message::AssignmentDueChanged)
+                    .padding(6)
+                    .width(Length::Fixed(160.0)),
+                button(t!("From Playlist Draft"))
+                    .on_press(Message::AssignmentDraftFromPlaylist)
+                    .style(iced::widget::button::secondary),
+                button(t!("Export Assignment"))
+                    .on_press_maybe(
+                        (!draft.items.is_empty()).then_some(Message::AssignmentDraftExport)
+                    )
+                    .style(iced::widget::button::primary),
+            ]
+            .spacing(12),
+        ]
+        .spacing(6);
+        for (index, item) in draft.items.iter().enumerate() {
+            builder = builder.push(
+                row![
+                    text(&item.entry_name)
+                        .shaping(Shaping::Advanced)
+                        .width(Length::Fill),
+                    text_input(t!("Section (e.g. bars 1-16)"), &item.section)
+                        .on_input(move |value| Message::AssignmentItemSectionChanged(index, value))
+                        .width(Length::Fixed(180.0)),
+                    text(t!("Tempo %")),
+                    text_input("100", &item.target_tempo.to_string())
+                        .on_input(move |value| Message::AssignmentItemTempoChanged(index, value))
+                        .width(Length::Fixed(60.0)),
+                    text(t!("Plays")),
+                    text_input("3", &item.target_plays.to_string())
+                        .on_input(move |value| Message::AssignmentItemPlaysChanged(index, value))
+                        .width(Length::Fixed(50.0)),
+                    button(t!("Remove"))
+                        .on_press(Message::AssignmentDraftRemove(index))
+                        .style(iced::widget::button::secondary),
+                ]
+                .spacing(8)
+                .align_y(iced::Alignment::Center),
+            );
         }
+
+        column.push(container(builder).padding(8).style(container::rounded_box))
     }
 
-    fn entry_column<'a>(&'a self, entries: Vec<&'a crate::midi::MidiEntry>) -> Column<'a, Message> {
+    fn entry_column<'a>(
+        &'a self,
+        entries: Vec<&'a midi_piano_rs::midi::MidiEntry>,
+    ) -> Column<'a, Message> {
         let mut column = Column::new().spacing(6);
         if entries.is_empty() {
-            column = column
-                .push(text("No MIDI files match the current filters").shaping(Shaping::Advanced));
+            column = column.push(
+                text(t!("No MIDI files match the current filters")).shaping(Shaping::Advanced),
+            );
         } else {
+            let works = WorkIndex::new(&self.user_prefs.works);
+            let mut shown = HashSet::new();
             for entry in entries {
-                column = column.push(self.entry_row(entry));
+                let Some(work) = works.work_of(entry.id) else {
+                    column = column.push(self.entry_row(entry));
+                    continue;
+                };
+                if !shown.insert(work.id) {
+                    continue;
+                }
+                column = column.push(self.work_row(work));
+                if self.expanded_works.contains(&work.id) {
+                    for movement in work.movements.iter().filter_map(|id| self.library.get(id)) {
+                        column = column.push(container(self.entry_row(movement)).padding(
+                            iced::Padding {
+                                left: 24.0,
+                                ..iced::Padding::ZERO
+                            },
+                        ));
+                    }
+                }
             }
         }
         column
     }
 
-    fn entry_row(&self, entry: &crate::midi::MidiEntry) -> Element<'_, Message> {
+    /// A work in place of its movements, which it lists when expanded.
+    fn work_row<'a>(&'a self, work: &'a Work) -> Element<'a, Message> {
+        let expanded = self.expanded_works.contains(&work.id);
+        let movements: Vec<_> = work
+            .movements
+            .iter()
+            .filter_map(|id| self.library.get(id))
+            .collect();
+        let duration = movements
+            .iter()
+            .map(|entry| self.entry_duration(entry))
+            .sum::<Option<Duration>>()
+            .map(|duration| text(format_duration(duration)).size(13));
+        row![
+            button(
+                text(format!(
+                    "{} {}",
+                    if expanded { "▾" } else { "▸" },
+                    work.name
+                ))
+                .shaping(Shaping::Advanced)
+            )
+            .on_press(Message::ToggleWork(work.id))
+            .style(iced::widget::button::secondary),
+            text(t!("{count} movements", count = movements.len())).size(13),
+        ]
+        .push_maybe(duration)
+        .push(
+            button(text("▶").shaping(Shaping::Advanced))
+                .style(iced::widget::button::primary)
+                .on_press_maybe((!movements.is_empty()).then_some(Message::PlayWork(work.id))),
+        )
+        .spacing(12)
+        .align_y(iced::Alignment::Center)
+        .into()
+    }
+
+    /// The work a song is a movement of, editable.
+    fn work_editor(&self, entry_id: Uuid) -> Option<Element<'_, Message>> {
+        let work = WorkIndex::new(&self.user_prefs.works).work_of(entry_id)?;
+        let number = work.movements.iter().position(|id| *id == entry_id)? + 1;
+        let work_id = work.id;
+        Some(
+            row![
+                text(t!(
+                    "Movement {number} of {count} in",
+                    number = number,
+                    count = work.movements.len()
+                )),
+                text_input(t!("Work name"), &work.name)
+                    .on_input(move |name| Message::WorkNameChanged(work_id, name))
+                    .width(Length::Fixed(220.0))
+                    .padding(4),
+                pick_list(
+                    WorkPauseChoice::ALL,
+                    Some(WorkPauseChoice(work.pause_secs)),
+                    move |choice| Message::WorkPauseSelected(work_id, choice),
+                ),
+                button(t!("Ungroup"))
+                    .padding([2, 8])
+                    .on_press(Message::UngroupWork(work_id))
+                    .style(iced::widget::button::secondary),
+            ]
+            .spacing(6)
+            .align_y(iced::Alignment::Center)
+            .wrap()
+            .into(),
+        )
+    }
+
+    fn entry_row(&self, entry: &midi_piano_rs::midi::MidiEntry) -> Element<'_, Message> {
         let is_selected = Some(entry.id) == self.selected_song;
-        let display_name = if matches!(entry.origin, crate::midi::MidiOrigin::Local) {
-            format!("{} (Local)", entry.name)
-        } else {
-            entry.name.clone()
+        let display_name = match entry.origin {
+            midi_piano_rs::midi::MidiOrigin::Local => t!("{name} (Local)", name = entry.name),
+            midi_piano_rs::midi::MidiOrigin::Remote => t!("{name} (Remote)", name = entry.name),
+            midi_piano_rs::midi::MidiOrigin::Asset => entry.name.clone(),
         };
 
         let mut select_button = button(text(display_name).shaping(Shaping::Advanced))
@@ -1246,6 +11180,42 @@ impl MidiPianoApp {
         let play_button = button(text("▶").shaping(Shaping::Advanced))
             .style(iced::widget::button::primary)
             .on_press(Message::StartPlayback(entry.id));
+        let previewing = self
+            .preview
+            .as_ref()
+            .is_some_and(|preview| preview.track_id == entry.id);
+        let preview_button = button(
+            text(if previewing {
+                t!("Stop Preview")
+            } else {
+                t!("Preview")
+            })
+            .size(13),
+        )
+        .style(iced::widget::button::secondary)
+        .on_press(Message::PreviewSong(entry.id));
+        let duration = self
+            .entry_duration(entry)
+            .map(|duration| text(format_duration(duration)).size(13));
+        let tempo = self
+            .entry_tempo(entry)
+            .map(|tempo| text(tempo_label(tempo)).size(13));
+        let plays = self
+            .user_prefs
+            .play_stats
+            .get(&entry.id)
+            .filter(|stats| stats.count > 0)
+            .map(|stats| {
+                let label = match stats.last_played {
+                    Some(at) => t!(
+                        "Played {count}× · {date}",
+                        count = stats.count,
+                        date = at.with_timezone(&chrono::Local).format("%Y-%m-%d")
+                    ),
+                    None => t!("Played {count}×", count = stats.count),
+                };
+                text(label).size(12).shaping(Shaping::Advanced)
+            });
 
         let current_rating = self.user_prefs.ratings.get(&entry.id).copied().unwrap_or(0);
         let mut stars_row = row![];
@@ -1272,56 +11242,117 @@ impl MidiPianoApp {
             .style(iced::widget::button::secondary)
             .on_press(Message::PlaylistDraftAdd(entry.id));
 
-        row![
-            select_button,
-            play_button,
-            stars_row,
-            favorite_button,
-            add_button,
-        ]
-        .spacing(12)
-        .into()
+        let play_next_button = button(text(t!("Play Next")).size(13))
+            .style(iced::widget::button::secondary)
+            .on_press(Message::PlayNext(entry.id));
+        let enqueue_button = button(text(t!("Add to Queue")).size(13))
+            .style(iced::widget::button::secondary)
+            .on_press(Message::AddToQueue(entry.id));
+
+        let info_button = button(text(t!("Info")))
+            .style(iced::widget::button::secondary)
+            .on_press(Message::ShowSongInfo(entry.id));
+
+        let composer = entry
+            .composer
+            .as_ref()
+            .map(|composer| text(composer.clone()).shaping(Shaping::Advanced).size(13));
+
+        let tags = self
+            .user_prefs
+            .tags
+            .get(&entry.id)
+            .filter(|tags| !tags.is_empty())
+            .map(|tags| {
+                text(tags.iter().cloned().collect::<Vec<_>>().join(", "))
+                    .shaping(Shaping::Advanced)
+                    .size(13)
+            });
+
+        row![select_button]
+            .push_maybe(composer)
+            .push_maybe(duration)
+            .push_maybe(tempo)
+            .push(play_button)
+            .push(preview_button)
+            .push(play_next_button)
+            .push(enqueue_button)
+            .push(stars_row)
+            .push(favorite_button)
+            .push(add_button)
+            .push(info_button)
+            .push_maybe(plays)
+            .push_maybe(tags)
+            .spacing(12)
+            .align_y(iced::Alignment::Center)
+            .into()
     }
 
+    /// Stacked toasts, oldest first. Errors stay until dismissed.
     fn status_banner(&self) -> Element<'_, Message> {
-        if let Some(error) = &self.error_message {
-            return row![
-                text(error)
-                    .shaping(Shaping::Advanced)
-                    .size(16)
-                    .color(Color::from_rgb(0.9, 0.4, 0.4)),
-                button("Dismiss")
-                    .on_press(Message::DismissStatus)
-                    .style(iced::widget::button::secondary)
-            ]
-            .spacing(8)
-            .into();
+        let mut toasts = Column::new().spacing(4);
+        for toast in self.notifications.iter() {
+            let color = match toast.severity {
+                Severity::Info => Color::from_rgb(0.4, 0.9, 0.4),
+                Severity::Error => Color::from_rgb(0.9, 0.4, 0.4),
+            };
+            let action = toast.action.map(|action| {
+                button(action.label())
+                    .on_press(action.message())
+                    .style(iced::widget::button::primary)
+            });
+            toasts = toasts.push(
+                row![
+                    text(&toast.text)
+                        .shaping(Shaping::Advanced)
+                        .size(16)
+                        .color(color)
+                ]
+                .push_maybe(action)
+                .push(
+                    button(t!("Dismiss"))
+                        .on_press(Message::DismissToast(toast.id))
+                        .style(iced::widget::button::secondary),
+                )
+                .spacing(8)
+                .align_y(iced::Alignment::Center),
+            );
         }
-
-        if let Some(status) = &self.status_message {
-            return row![
-                text(status)
-                    .shaping(Shaping::Advanced)
-                    .size(16)
-                    .color(Color::from_rgb(0.4, 0.9, 0.4)),
-                button("Dismiss")
+        if self.notifications.len() > 1 {
+            toasts = toasts.push(
+                button(t!("Dismiss All"))
                     .on_press(Message::DismissStatus)
-                    .style(iced::widget::button::secondary)
-            ]
-            .spacing(8)
-            .into();
+                    .style(iced::widget::button::text),
+            );
         }
-
-        column![].into()
+        toasts.into()
     }
 
     fn tree_panel(&self) -> Column<'_, Message> {
         let mut column = Column::new().spacing(4);
 
         if self.tree_loading && self.tree_cache.is_empty() {
-            return column.push(text("Loading tree...").shaping(Shaping::Advanced));
+            return column.push(text(t!("Loading tree...")).shaping(Shaping::Advanced));
         }
 
+        column = column.push(
+            row![
+                pick_list(
+                    TreeGrouping::ALL,
+                    Some(self.user_prefs.tree_grouping),
+                    Message::TreeGroupingSelected,
+                ),
+                button(t!("Expand All"))
+                    .on_press(Message::ExpandAllFolders)
+                    .style(iced::widget::button::text),
+                button(t!("Collapse All"))
+                    .on_press(Message::CollapseAllFolders)
+                    .style(iced::widget::button::text),
+            ]
+            .spacing(4)
+            .align_y(iced::Alignment::Center),
+        );
+
         for item in &self.tree_cache {
             let indent = "  ".repeat(item.depth);
             let indicator = if item.has_children {
@@ -1329,8 +11360,30 @@ impl MidiPianoApp {
             } else {
                 "•"
             };
-            let label = format!("{indent}{indicator} {}", item.name);
-            let mut button = button(text(label).shaping(Shaping::Advanced));
+            let summary = if item.total_duration.is_zero() {
+                item.track_count.to_string()
+            } else {
+                t!(
+                    "{count} · {minutes} min",
+                    count = item.track_count,
+                    minutes = (item.total_duration.as_secs() + 30) / 60
+                )
+            };
+            let folder_label = self.user_prefs.folder_labels.get(&item.id);
+            let name = match folder_label.filter(|label| !label.emoji.is_empty()) {
+                Some(label) => format!("{} {}", label.emoji, item.name),
+                None => item.name.clone(),
+            };
+            let mut button = button(
+                row![text(format!("{indent}{indicator}")).shaping(Shaping::Advanced)]
+                    .push_maybe(
+                        folder_label
+                            .and_then(|label| label.color)
+                            .map(|color| text("●").color(folder_color(color))),
+                    )
+                    .push(text(format!("{name} ({summary})")).shaping(Shaping::Advanced))
+                    .spacing(4),
+            );
             if item.has_children {
                 button = button.on_press(Message::ToggleFolder(item.id.clone()));
             } else {
@@ -1341,36 +11394,151 @@ impl MidiPianoApp {
             } else {
                 button = button.style(iced::widget::button::secondary);
             }
-            column = column.push(button);
+            let editing = self.editing_folder_label.as_deref() == Some(item.id.as_str());
+            let folder_action = |symbol: &'static str, label: &'static str, shuffle: bool| {
+                tooltip(
+                    iced::widget::button(text(symbol).shaping(Shaping::Advanced))
+                        .on_press(Message::PlayFolder {
+                            id: item.id.clone(),
+                            shuffle,
+                        })
+                        .style(iced::widget::button::text),
+                    text(label),
+                    tooltip::Position::Top,
+                )
+            };
+            column = column.push(
+                row![
+                    button.width(Length::Fill),
+                    folder_action("▶", t!("Play Folder"), false),
+                    folder_action("⇄", t!("Shuffle Folder"), true),
+                    tooltip(
+                        iced::widget::button(text("✎").shaping(Shaping::Advanced))
+                            .on_press(Message::EditFolderLabel(
+                                (!editing).then(|| item.id.clone()),
+                            ))
+                            .style(iced::widget::button::text),
+                        text(t!("Color and label")),
+                        tooltip::Position::Top,
+                    ),
+                ]
+                .spacing(2)
+                .align_y(iced::Alignment::Center),
+            );
+            if editing {
+                column = column.push(self.folder_label_editor(&item.id));
+            }
         }
 
         column
     }
 
+    /// Color swatches and an emoji field for a tree folder, shown under it.
+    fn folder_label_editor(&self, folder_id: &str) -> Element<'_, Message> {
+        let label = self
+            .user_prefs
+            .folder_labels
+            .get(folder_id)
+            .cloned()
+            .unwrap_or_default();
+        let swatch = |color: Option<FolderColor>| {
+            let symbol = if label.color == color { "◉" } else { "●" };
+            let name = color.map_or_else(
+                || t!("No color").to_string(),
+                |color| folder_color_name(color).to_string(),
+            );
+            tooltip(
+                iced::widget::button(
+                    text(symbol)
+                        .shaping(Shaping::Advanced)
+                        .color_maybe(color.map(folder_color)),
+                )
+                .padding([2, 4])
+                .on_press(Message::FolderColorSelected(folder_id.to_owned(), color))
+                .style(iced::widget::button::text),
+                text(name),
+                tooltip::Position::Top,
+            )
+        };
+        let swatches = FolderColor::ALL
+            .into_iter()
+            .fold(row![swatch(None)], |swatches, color| {
+                swatches.push(swatch(Some(color)))
+            })
+            .spacing(2);
+        let folder = folder_id.to_owned();
+        container(
+            row![
+                swatches,
+                text_input(t!("Emoji"), &label.emoji)
+                    .on_input(move |emoji| Message::FolderEmojiChanged(folder.clone(), emoji))
+                    .width(Length::Fixed(70.0))
+                    .padding(4),
+                iced::widget::button(text(t!("Done")))
+                    .on_press(Message::EditFolderLabel(None))
+                    .style(iced::widget::button::secondary),
+            ]
+            .spacing(6)
+            .align_y(iced::Alignment::Center),
+        )
+        .padding(6)
+        .style(container::rounded_box)
+        .into()
+    }
+
     fn playlist_editor(&self) -> Element<'_, Message> {
-        let name_input = text_input("Playlist name", &self.playlist_draft.name)
+        let name_input = text_input(t!("Playlist name"), &self.playlist_draft.name)
             .on_input(Message::PlaylistDraftNameChanged)
             .padding(8);
 
-        let save_button = button("Save Playlist")
+        let save_button = button(t!("Save Playlist"))
             .on_press(Message::PlaylistDraftSave)
             .style(iced::widget::button::primary);
 
-        let clear_button = button("Clear Draft")
+        let clear_button = button(t!("Clear Draft"))
             .on_press(Message::PlaylistDraftClear)
             .style(iced::widget::button::secondary);
 
-        let random_button = button("Random 50")
+        let random_button = button(t!("Random 50"))
             .on_press(Message::GenerateRandomPlaylist)
             .style(iced::widget::button::secondary);
 
-        let controls = row![name_input, save_button, clear_button, random_button].spacing(12);
+        let smart_button = button(t!("Smart from Tags"))
+            .on_press_maybe(
+                (!self.tag_filter.tags.is_empty()).then_some(Message::CreateSmartPlaylist),
+            )
+            .style(iced::widget::button::secondary);
+
+        let builder_button = button(t!("Open Builder"))
+            .on_press(Message::TogglePlaylistBuilder)
+            .style(iced::widget::button::secondary);
+
+        let work_button = button(t!("Save as Work"))
+            .on_press_maybe(
+                (self.playlist_draft.tracks.len() >= 2).then_some(Message::SaveDraftAsWork),
+            )
+            .style(iced::widget::button::secondary);
+
+        let controls = row![
+            name_input,
+            save_button,
+            clear_button,
+            random_button,
+            smart_button,
+            builder_button,
+            work_button
+        ]
+        .spacing(12);
 
         let playlist_choices: Vec<PlaylistChoice> = self
             .user_prefs
-            .playlists
-            .iter()
-            .map(PlaylistChoice::from)
+            .playlist_groups()
+            .into_iter()
+            .flat_map(|(folder, playlists)| {
+                playlists
+                    .into_iter()
+                    .map(move |playlist| PlaylistChoice::new(playlist, folder))
+            })
             .collect();
 
         let selected_choice = self.selected_playlist.and_then(|id| {
@@ -1385,30 +11553,30 @@ impl MidiPianoApp {
             selected_choice,
             |choice: PlaylistChoice| Message::PlaylistSelect(Some(choice.id)),
         )
-        .placeholder("Choose playlist");
+        .placeholder(t!("Choose playlist"));
 
         let load_button = if let Some(id) = self.selected_playlist {
-            button("Load into Draft")
+            button(t!("Load into Draft"))
                 .on_press(Message::PlaylistLoadToDraft(id))
                 .style(iced::widget::button::secondary)
         } else {
-            button("Load into Draft").style(iced::widget::button::secondary)
+            button(t!("Load into Draft")).style(iced::widget::button::secondary)
         };
 
         let delete_button = if let Some(id) = self.selected_playlist {
-            button("Delete Playlist")
+            button(t!("Delete Playlist"))
                 .on_press(Message::PlaylistDelete(id))
                 .style(iced::widget::button::danger)
         } else {
-            button("Delete Playlist").style(iced::widget::button::danger)
+            button(t!("Delete Playlist")).style(iced::widget::button::danger)
         };
 
         let clear_selection_button = if self.selected_playlist.is_some() {
-            button("Clear Selection")
+            button(t!("Clear Selection"))
                 .on_press(Message::PlaylistSelect(None))
                 .style(iced::widget::button::secondary)
         } else {
-            button("Clear Selection").style(iced::widget::button::secondary)
+            button(t!("Clear Selection")).style(iced::widget::button::secondary)
         };
 
         let selection_row = row![
@@ -1420,48 +11588,577 @@ impl MidiPianoApp {
         .spacing(12);
 
         let playlist_play_row: Element<'_, Message> = if let Some(id) = self.selected_playlist {
-            row![
-                button("Play Selected")
+            let playlist = self
+                .user_prefs
+                .playlists
+                .iter()
+                .find(|playlist| playlist.id == id);
+            let rule = playlist.and_then(|playlist| playlist.rule.as_ref());
+            let defaults_row = playlist.map(|playlist| self.playlist_defaults_row(playlist));
+            let play_row = row![
+                button(t!("Play Selected"))
                     .on_press(Message::PlayPlaylist { id, shuffle: false })
                     .style(iced::widget::button::primary),
-                button("Shuffle Selected")
+                button(t!("Shuffle Selected"))
                     .on_press(Message::PlayPlaylist { id, shuffle: true })
                     .style(iced::widget::button::secondary)
             ]
+            .push_maybe(
+                rule.map(|rule| text(t!("Smart: {rule}", rule = rule)).shaping(Shaping::Advanced)),
+            )
             .spacing(12)
-            .into()
+            .align_y(iced::Alignment::Center);
+            column![play_row].push_maybe(defaults_row).spacing(8).into()
         } else {
-            text("Select a playlist to play")
+            text(t!("Select a playlist to play"))
                 .shaping(Shaping::Advanced)
                 .into()
         };
 
         let mut tracks_column = Column::new().spacing(4);
+        let last = self.playlist_draft.tracks.len().saturating_sub(1);
+        // Running length up to and including each row; unknown once any
+        // song before it has no known length.
+        let mut elapsed = Some(Duration::ZERO);
+        let works = WorkIndex::new(&self.user_prefs.works);
+        let mut shown_works = HashSet::new();
         for (index, track_id) in self.playlist_draft.tracks.iter().cloned().enumerate() {
             if let Some(entry) = self.library.get(&track_id) {
+                elapsed = elapsed.zip(self.entry_duration(entry)).map(|(a, b)| a + b);
+                // Movements show under their work, only when it is expanded.
+                if let Some(work) = works.work_of(track_id) {
+                    if shown_works.insert(work.id) {
+                        tracks_column = tracks_column.push(self.work_row(work));
+                    }
+                    if !self.expanded_works.contains(&work.id) {
+                        continue;
+                    }
+                }
+                let step = |label: &'static str, to: Option<usize>| {
+                    button(text(label).shaping(Shaping::Advanced))
+                        .on_press_maybe(to.map(|to| Message::PlaylistDraftMove(index, to)))
+                        .style(iced::widget::button::secondary)
+                };
+                let position = text(format!("{}.", index + 1)).size(13);
                 let label = text(entry.name.clone()).shaping(Shaping::Advanced);
-                let remove_button = button("Remove")
+                let ends_at = elapsed.map(|elapsed| text(format_duration(elapsed)).size(13));
+                let remove_button = button(t!("Remove"))
                     .on_press(Message::PlaylistDraftRemove(index))
                     .style(iced::widget::button::secondary);
-                tracks_column = tracks_column.push(row![label, remove_button].spacing(12));
+                tracks_column = tracks_column.push(
+                    row![
+                        step("↑", index.checked_sub(1)),
+                        step("↓", (index < last).then_some(index + 1)),
+                        position,
+                        label,
+                    ]
+                    .push_maybe(ends_at)
+                    .push(remove_button)
+                    .spacing(12)
+                    .align_y(iced::Alignment::Center),
+                );
             }
         }
         if self.playlist_draft.tracks.is_empty() {
             tracks_column =
-                tracks_column.push(text("Playlist draft is empty").shaping(Shaping::Advanced));
+                tracks_column.push(text(t!("Playlist draft is empty")).shaping(Shaping::Advanced));
         }
 
         let track_list = scrollable(tracks_column).height(Length::Fixed(200.0));
 
-        column![controls, selection_row, playlist_play_row, track_list]
-            .spacing(12)
+        column![
+            controls,
+            selection_row,
+            playlist_play_row,
+            self.playlist_organizer(),
+            track_list
+        ]
+        .spacing(12)
+        .into()
+    }
+
+    /// The library and the draft side by side, for building a playlist
+    /// without scrolling between them. Songs are reordered by dragging their
+    /// handle.
+    fn playlist_builder_view(&self, builder: &PlaylistBuilder) -> Element<'_, Message> {
+        let query = SearchQuery::new(&builder.query);
+        let mut entries: Vec<_> = self.library.search(&query).collect();
+        self.sort_entries(&mut entries, LibrarySort::Name);
+        let in_draft: HashSet<Uuid> = self.playlist_draft.tracks.iter().copied().collect();
+        let addable: Vec<Uuid> = entries
+            .iter()
+            .map(|entry| entry.id)
+            .filter(|id| !in_draft.contains(id))
+            .collect();
+        let duration_label = |duration: Option<Duration>| {
+            text(duration.map(format_duration).unwrap_or_default()).size(13)
+        };
+
+        let mut library_column = Column::new().spacing(4);
+        for entry in &entries {
+            library_column = library_column.push(
+                row![
+                    text(entry.name.clone())
+                        .shaping(Shaping::Advanced)
+                        .width(Length::Fill),
+                    duration_label(self.entry_duration(entry)),
+                    button(text("→").shaping(Shaping::Advanced))
+                        .on_press_maybe(
+                            (!in_draft.contains(&entry.id))
+                                .then_some(Message::PlaylistDraftAdd(entry.id)),
+                        )
+                        .style(iced::widget::button::secondary),
+                ]
+                .spacing(8)
+                .align_y(iced::Alignment::Center),
+            );
+        }
+        let library_side = column![
+            row![
+                text(t!("Library ({count})", count = entries.len()))
+                    .size(18)
+                    .width(Length::Fill),
+                button(text(t!("Add All →")).shaping(Shaping::Advanced))
+                    .on_press_maybe(
+                        (!addable.is_empty()).then_some(Message::PlaylistDraftAddAll(addable)),
+                    )
+                    .style(iced::widget::button::secondary),
+            ]
+            .spacing(8)
+            .align_y(iced::Alignment::Center),
+            text_input(t!("Filter library..."), &builder.query)
+                .on_input(Message::PlaylistBuilderQueryChanged)
+                .padding(6),
+            scrollable(library_column).height(Length::Fill),
+        ]
+        .spacing(8)
+        .width(Length::FillPortion(1));
+
+        let mut total = Duration::ZERO;
+        let mut unknown = 0;
+        let mut draft_column = Column::new().spacing(4);
+        for (index, id) in self.playlist_draft.tracks.iter().enumerate() {
+            let Some(entry) = self.library.get(id) else {
+                continue;
+            };
+            let duration = self.entry_duration(entry);
+            match duration {
+                Some(duration) => total += duration,
+                None => unknown += 1,
+            }
+            let handle = mouse_area(text("≡").shaping(Shaping::Advanced))
+                .on_press(Message::PlaylistDragStarted(index))
+                .interaction(iced::mouse::Interaction::Grab);
+            let track = container(
+                row![
+                    handle,
+                    text(format!("{}.", index + 1)).size(13),
+                    text(entry.name.clone())
+                        .shaping(Shaping::Advanced)
+                        .width(Length::Fill),
+                    duration_label(duration),
+                    button(text("←").shaping(Shaping::Advanced))
+                        .on_press(Message::PlaylistDraftRemove(index))
+                        .style(iced::widget::button::secondary),
+                ]
+                .spacing(8)
+                .align_y(iced::Alignment::Center),
+            )
+            .style(if builder.dragging == Some(index) {
+                container::rounded_box
+            } else {
+                container::transparent
+            });
+            let mut track = mouse_area(track);
+            if let Some(from) = builder.dragging
+                && from != index
+            {
+                track = track.on_enter(Message::PlaylistDraftMove(from, index));
+            }
+            draft_column = draft_column.push(track);
+        }
+        if self.playlist_draft.tracks.is_empty() {
+            draft_column = draft_column.push(text(t!("Add songs from the library on the left")));
+        }
+        let mut totals = t!(
+            "{count} song(s) · {length}",
+            count = self.playlist_draft.tracks.len(),
+            length = format_duration(total)
+        );
+        if unknown > 0 {
+            totals = t!(
+                "{totals} (+{count} of unknown length)",
+                totals = totals,
+                count = unknown
+            );
+        }
+        let draft_side = column![
+            row![
+                text(t!("Draft")).size(18).width(Length::Fill),
+                button(text(t!("← Remove All")).shaping(Shaping::Advanced))
+                    .on_press_maybe(
+                        (!self.playlist_draft.tracks.is_empty())
+                            .then_some(Message::PlaylistDraftRemoveAll),
+                    )
+                    .style(iced::widget::button::secondary),
+            ]
+            .spacing(8)
+            .align_y(iced::Alignment::Center),
+            row![
+                text_input(t!("Playlist name"), &self.playlist_draft.name)
+                    .on_input(Message::PlaylistDraftNameChanged)
+                    .padding(6),
+                button(t!("Save Playlist"))
+                    .on_press(Message::PlaylistDraftSave)
+                    .style(iced::widget::button::primary),
+            ]
+            .spacing(8),
+            text(totals).size(13).shaping(Shaping::Advanced),
+            scrollable(draft_column).height(Length::Fill),
+        ]
+        .spacing(8)
+        .width(Length::FillPortion(1));
+
+        let content = column![
+            row![
+                text(t!("Playlist builder")).size(20).width(Length::Fill),
+                button(t!("Done"))
+                    .on_press(Message::TogglePlaylistBuilder)
+                    .style(iced::widget::button::secondary),
+            ]
+            .align_y(iced::Alignment::Center),
+            row![library_side, draft_side]
+                .spacing(16)
+                .height(Length::Fill),
+        ]
+        .spacing(12)
+        .height(Length::Fill);
+
+        // Released anywhere over the builder, not just over a song.
+        mouse_area(content)
+            .on_release(Message::PlaylistDragEnded)
             .into()
     }
+
+    /// Device, shuffle, speed and transpose the playlist always plays with.
+    fn playlist_defaults_row(&self, playlist: &Playlist) -> Element<'_, Message> {
+        let id = playlist.id;
+        let defaults = playlist.defaults;
+        let selected_device = self
+            .devices
+            .iter()
+            .find(|choice| Some(choice.id) == defaults.device_id)
+            .cloned();
+        let step = |label: &'static str, message: Message| {
+            button(text(label).shaping(Shaping::Advanced))
+                .on_press(message)
+                .style(iced::widget::button::secondary)
+        };
+        row![
+            text(t!("Plays with")),
+            pick_list(self.devices.clone(), selected_device, move |choice| {
+                Message::PlaylistDeviceSelected(id, Some(choice.id))
+            })
+            .placeholder(t!("Selected device"))
+            .text_shaping(Shaping::Advanced),
+        ]
+        .push_maybe(defaults.device_id.map(|_| {
+            button(t!("Any Device"))
+                .on_press(Message::PlaylistDeviceSelected(id, None))
+                .style(iced::widget::button::secondary)
+        }))
+        .push(
+            checkbox(t!("Always shuffle"), defaults.shuffle)
+                .on_toggle(move |shuffle| Message::PlaylistShuffleToggled(id, shuffle)),
+        )
+        .push(text(t!(
+            "Speed {percent}%",
+            percent = defaults.tempo_percent.unwrap_or(100)
+        )))
+        .push(step("−", Message::PlaylistTempoStep(id, -5)))
+        .push(step("+", Message::PlaylistTempoStep(id, 5)))
+        .push(text(t!(
+            "Transpose {semitones} st",
+            semitones = format!("{:+}", defaults.transpose)
+        )))
+        .push(step("−", Message::PlaylistTransposeStep(id, -1)))
+        .push(step("+", Message::PlaylistTransposeStep(id, 1)))
+        .spacing(8)
+        .align_y(iced::Alignment::Center)
+        .wrap()
+        .into()
+    }
+
+    fn playlist_organizer(&self) -> Element<'_, Message> {
+        let folder_choices: Vec<FolderChoice> = std::iter::once(FolderChoice {
+            id: None,
+            name: t!("No folder").into(),
+        })
+        .chain(
+            self.user_prefs
+                .playlist_folders
+                .iter()
+                .map(|folder| FolderChoice {
+                    id: Some(folder.id),
+                    name: folder.name.clone(),
+                }),
+        )
+        .collect();
+
+        let mut organizer = column![
+            row![
+                text(t!("Organize playlists")).size(16).width(Length::Fill),
+                text_input(t!("New folder"), &self.new_folder_name)
+                    .on_input(Message::NewFolderNameChanged)
+                    .on_submit(Message::CreatePlaylistFolder)
+                    .width(Length::Fixed(180.0)),
+                button(t!("Add Folder"))
+                    .on_press(Message::CreatePlaylistFolder)
+                    .style(iced::widget::button::secondary),
+            ]
+            .spacing(8)
+            .align_y(iced::Alignment::Center)
+        ]
+        .spacing(4);
+
+        for (folder, playlists) in self.user_prefs.playlist_groups() {
+            if let Some(folder) = folder {
+                let name: Element<'_, Message> = self
+                    .rename_field(RenameTarget::Folder(folder.id))
+                    .unwrap_or_else(|| {
+                        text(format!("📁 {}", folder.name))
+                            .shaping(Shaping::Advanced)
+                            .width(Length::Fill)
+                            .into()
+                    });
+                organizer = organizer.push(
+                    row![
+                        name,
+                        button("▲")
+                            .on_press(Message::PlaylistFolderMove(folder.id, -1))
+                            .style(iced::widget::button::text),
+                        button("▼")
+                            .on_press(Message::PlaylistFolderMove(folder.id, 1))
+                            .style(iced::widget::button::text),
+                        button(t!("Rename"))
+                            .on_press(Message::RenameStart(RenameTarget::Folder(folder.id)))
+                            .style(iced::widget::button::secondary),
+                        button(t!("Delete Folder"))
+                            .on_press(Message::PlaylistFolderDelete(folder.id))
+                            .style(iced::widget::button::secondary),
+                    ]
+                    .spacing(8)
+                    .align_y(iced::Alignment::Center),
+                );
+            }
+            let indent = if folder.is_some() { 24.0 } else { 0.0 };
+            for playlist in playlists {
+                let selected = self.selected_playlist == Some(playlist.id);
+                let name: Element<'_, Message> = self
+                    .rename_field(RenameTarget::Playlist(playlist.id))
+                    .unwrap_or_else(|| {
+                        button(text(playlist.name.clone()).shaping(Shaping::Advanced))
+                            .on_press(Message::PlaylistSelect(Some(playlist.id)))
+                            .width(Length::Fill)
+                            .style(if selected {
+                                iced::widget::button::primary
+                            } else {
+                                iced::widget::button::text
+                            })
+                            .into()
+                    });
+                let current_folder = folder_choices
+                    .iter()
+                    .find(|choice| choice.id == folder.map(|folder| folder.id))
+                    .cloned();
+                let id = playlist.id;
+                organizer = organizer.push(
+                    row![
+                        iced::widget::horizontal_space().width(Length::Fixed(indent)),
+                        name,
+                        button("▲")
+                            .on_press(Message::PlaylistMove(id, -1))
+                            .style(iced::widget::button::text),
+                        button("▼")
+                            .on_press(Message::PlaylistMove(id, 1))
+                            .style(iced::widget::button::text),
+                        pick_list(folder_choices.clone(), current_folder, move |choice| {
+                            Message::PlaylistFolderAssigned(id, choice)
+                        }),
+                        button(t!("Rename"))
+                            .on_press(Message::RenameStart(RenameTarget::Playlist(id)))
+                            .style(iced::widget::button::secondary),
+                        button(t!("Duplicate"))
+                            .on_press(Message::PlaylistDuplicate(id))
+                            .style(iced::widget::button::secondary),
+                    ]
+                    .spacing(8)
+                    .align_y(iced::Alignment::Center),
+                );
+            }
+        }
+
+        scrollable(organizer).height(Length::Fixed(200.0)).into()
+    }
+
+    /// Inline editor for `target` while it is being renamed.
+    fn rename_field(&self, target: RenameTarget) -> Option<Element<'_, Message>> {
+        let (renaming, draft) = self.renaming.as_ref()?;
+        if *renaming != target {
+            return None;
+        }
+        Some(
+            row![
+                text_input(t!("Name"), draft)
+                    .on_input(Message::RenameChanged)
+                    .on_submit(Message::RenameCommit)
+                    .width(Length::Fill),
+                button(t!("Save")).on_press(Message::RenameCommit),
+                button(t!("Cancel"))
+                    .on_press(Message::RenameCancel)
+                    .style(iced::widget::button::secondary),
+            ]
+            .spacing(8)
+            .width(Length::Fill)
+            .into(),
+        )
+    }
+}
+
+/// A failure shown to the user. The library's [`PlaybackError`], when one
+/// is attached, picks the wording and the suggested fix; the full `anyhow`
+/// chain goes to the log.
+#[derive(Debug, Clone)]
+struct AppError {
+    kind: Option<PlaybackError>,
+    detail: String,
+}
+
+impl AppError {
+    fn new(err: anyhow::Error) -> Self {
+        log::error!("{err:?}");
+        Self {
+            kind: PlaybackError::find(&err).cloned(),
+            detail: format!("{err:#}"),
+        }
+    }
+
+    fn send_failed(detail: String) -> Self {
+        log::error!("{detail}");
+        Self {
+            kind: Some(PlaybackError::SendFailed),
+            detail,
+        }
+    }
+
+    fn action(&self) -> Option<ErrorAction> {
+        match self.kind.as_ref()? {
+            PlaybackError::DeviceUnavailable(_) => Some(ErrorAction::RefreshDevices),
+            PlaybackError::ConnectTimedOut(_) => Some(ErrorAction::Reconnect),
+            PlaybackError::BleCharacteristicMissing(_) => Some(ErrorAction::ReconnectBluetooth),
+            PlaybackError::SendFailed => Some(ErrorAction::Reconnect),
+            PlaybackError::Parse(_) | PlaybackError::UnsupportedFormat(_) => None,
+        }
+    }
+}
+
+impl fmt::Display for AppError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match &self.kind {
+            Some(PlaybackError::DeviceUnavailable(name)) => f.write_str(&t!(
+                "{name} is not connected. Check that it is switched on, then refresh devices.",
+                name = name
+            )),
+            Some(PlaybackError::ConnectTimedOut(name)) => f.write_str(&t!(
+                "{name} did not answer in time. Check that it is nearby and switched on, then try again.",
+                name = name
+            )),
+            Some(PlaybackError::BleCharacteristicMissing(name)) => f.write_str(&t!(
+                "{name} was found but is not acting as a Bluetooth MIDI device. Try reconnecting it.",
+                name = name
+            )),
+            Some(PlaybackError::Parse(_)) => f.write_str(
+                t!("The file could not be read as MIDI. It may be damaged or not a MIDI file."),
+            ),
+            Some(PlaybackError::UnsupportedFormat(what)) => {
+                f.write_str(&t!(
+                    "The file uses {what}, which is not supported.",
+                    what = what
+                ))
+            }
+            Some(PlaybackError::SendFailed) => {
+                f.write_str(t!("The connection to the device was lost while sending notes."))
+            }
+            None => f.write_str(&self.detail),
+        }
+    }
+}
+
+/// Suggested fix shown next to an error.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum ErrorAction {
+    RefreshDevices,
+    ReconnectBluetooth,
+    Reconnect,
+}
+
+impl ErrorAction {
+    fn label(self) -> &'static str {
+        match self {
+            ErrorAction::RefreshDevices => t!("Refresh Devices"),
+            ErrorAction::ReconnectBluetooth => t!("Reconnect Bluetooth"),
+            ErrorAction::Reconnect => t!("Reconnect"),
+        }
+    }
+
+    fn message(self) -> Message {
+        match self {
+            ErrorAction::RefreshDevices => Message::RefreshDevices,
+            // Playing again connects to the device afresh.
+            ErrorAction::ReconnectBluetooth | ErrorAction::Reconnect => Message::PlayPressed,
+        }
+    }
 }
 
 struct PreparedPlayback {
+    track_id: Uuid,
     sequence: Arc<MidiSequence>,
     sink: SharedMidiSink,
+    position: Duration,
+}
+
+/// A song held back because it uses more than its device can play.
+#[derive(Debug)]
+struct CapabilityWarning {
+    device_id: Uuid,
+    prepared: PreparedPlayback,
+    issues: Vec<CapabilityIssue>,
+}
+
+/// The next movement of a work, waiting out the pause before it.
+struct PendingMovement {
+    track_id: Uuid,
+    sink: Option<SharedMidiSink>,
+    at: Instant,
+}
+
+/// The song [`Message::PreviewSong`] is playing.
+#[derive(Debug)]
+struct SongPreview {
+    track_id: Uuid,
+    /// Tells a finished preview from one started after it.
+    request: u64,
+    cancel: Arc<Notify>,
+}
+
+/// A sequence loaded ahead of its turn. It is only used if the song's
+/// adjustments and the output device are still the ones it was loaded for.
+#[derive(Debug, Clone)]
+struct PreloadedTrack {
+    track_id: Uuid,
+    device_id: Uuid,
+    adjustments: PlaybackAdjustments,
+    sequence: Arc<MidiSequence>,
 }
 
 impl fmt::Debug for PreparedPlayback {
@@ -1475,12 +12172,68 @@ impl fmt::Debug for PreparedPlayback {
 impl Clone for PreparedPlayback {
     fn clone(&self) -> Self {
         Self {
+            track_id: self.track_id,
             sequence: Arc::clone(&self.sequence),
             sink: self.sink.clone(),
+            position: self.position,
         }
     }
 }
 
+#[derive(Debug, Clone)]
+enum RenderUpdate {
+    Progress(f32),
+    Finished(AsyncResult<bool>),
+}
+
+struct ActiveSession {
+    entry_id: Uuid,
+    started_at: chrono::DateTime<chrono::Utc>,
+    elapsed: Duration,
+    total: Duration,
+}
+
+#[derive(Debug, Clone)]
+enum FolderImportUpdate {
+    Found(usize),
+    /// One file processed; `None` when it was skipped as unreadable.
+    Imported(Option<InboxImport>),
+    Finished(AsyncResult<FolderImportSummary>),
+}
+
+#[derive(Debug, Clone, Default)]
+struct FolderImportSummary {
+    skipped: usize,
+    cancelled: bool,
+}
+
+#[derive(Debug, Clone)]
+enum LibraryLoadUpdate {
+    /// Assets verified since the last chunk.
+    Chunk(Box<MidiLibrary>, AssetProgress),
+    Finished(AsyncResult<()>),
+}
+
+struct LibraryLoadJob {
+    progress: AssetProgress,
+    cancel: Arc<AtomicBool>,
+    handle: iced::task::Handle,
+}
+
+struct FolderImportJob {
+    name: String,
+    done: usize,
+    total: usize,
+    cancel: Arc<AtomicBool>,
+}
+
+struct RenderJob {
+    name: String,
+    output: PathBuf,
+    progress: f32,
+    cancel: Arc<AtomicBool>,
+}
+
 #[derive(Debug, Clone, Copy)]
 enum PlaybackPhase {
     Idle,
@@ -1493,13 +12246,46 @@ enum PlaybackPhase {
 struct PlaybackProgress {
     elapsed: Duration,
     total: Duration,
+    received: Instant,
 }
 
-async fn load_library() -> AsyncResult<MidiLibrary> {
-    tokio::task::spawn_blocking(MidiLibrary::load_with_assets)
-        .await
-        .map_err(|err| format!("library loader task failed: {err:?}"))?
-        .map_err(|err| format!("{err:?}"))
+impl PlaybackProgress {
+    fn new(elapsed: Duration, total: Duration) -> Self {
+        Self {
+            elapsed,
+            total,
+            received: Instant::now(),
+        }
+    }
+
+    /// `elapsed` moved on by the time since it was reported, for drawing
+    /// between updates. Runs at most `limit` ahead, so a stalled player
+    /// does not look like it is still going.
+    fn interpolated(&self, limit: Duration) -> Duration {
+        (self.elapsed + self.received.elapsed().min(limit)).min(self.total)
+    }
+}
+
+/// The seconds left before playback, large over a dimmed window.
+fn countdown_overlay<'a>(remaining: u8) -> Element<'a, Message> {
+    container(text(remaining.to_string()).size(160))
+        .center(Length::Fill)
+        .style(|_| container::Style {
+            background: Some(Color::from_rgba(0.0, 0.0, 0.0, 0.6).into()),
+            text_color: Some(Color::WHITE),
+            ..container::Style::default()
+        })
+        .into()
+}
+
+async fn rescan_assets() -> AsyncResult<(MidiLibrary, ManifestChanges)> {
+    tokio::task::spawn_blocking(|| {
+        let changes = midi_piano_rs::midi::rescan_assets()?;
+        Ok((MidiLibrary::load_with_assets()?, changes))
+    })
+    .await
+    .map_err(|err| format!("asset rescan task failed: {err:?}"))?
+    .map_err(|err: anyhow::Error| format!("{err:?}"))
 }
 
 async fn refresh_devices(
@@ -1509,6 +12295,74 @@ async fn refresh_devices(
     guard.refresh().await.map_err(|err| format!("{err:?}"))
 }
 
+async fn read_user_preferences(path: PathBuf) -> AsyncResult<UserPreferences> {
+    tokio::task::spawn_blocking(move || {
+        let data = std::fs::read_to_string(&path)
+            .map_err(|err| format!("failed to read {}: {err}", path.display()))?;
+        let mut prefs: UserPreferences = serde_json::from_str(&data)
+            .map_err(|err| format!("failed to parse preferences: {err}"))?;
+        prefs.migrate_device_filters();
+        Ok(prefs)
+    })
+    .await
+    .map_err(|err| format!("failed to join preferences task: {err:?}"))?
+}
+
+async fn export_catalog(entries: Vec<CatalogEntry>, path: PathBuf) -> AsyncResult<PathBuf> {
+    tokio::task::spawn_blocking(move || {
+        catalog::write(&entries, &path)
+            .map(|()| path)
+            .map_err(|err| format!("{err:?}"))
+    })
+    .await
+    .map_err(|err| format!("failed to join export task: {err:?}"))?
+}
+
+async fn read_catalog(path: PathBuf) -> AsyncResult<Vec<CatalogEntry>> {
+    tokio::task::spawn_blocking(move || catalog::read(&path).map_err(|err| format!("{err:?}")))
+        .await
+        .map_err(|err| format!("failed to join import task: {err:?}"))?
+}
+
+async fn export_user_data(bundle: UserDataBundle, path: PathBuf) -> AsyncResult<PathBuf> {
+    tokio::task::spawn_blocking(move || {
+        let serialized = serde_json::to_string_pretty(&bundle)
+            .map_err(|err| format!("failed to serialize user data: {err}"))?;
+        std::fs::write(&path, serialized)
+            .map_err(|err| format!("failed to write {}: {err}", path.display()))?;
+        Ok(path)
+    })
+    .await
+    .map_err(|err| format!("failed to join export task: {err:?}"))?
+}
+
+async fn read_user_data(path: PathBuf) -> AsyncResult<UserDataBundle> {
+    tokio::task::spawn_blocking(move || {
+        let data = std::fs::read_to_string(&path)
+            .map_err(|err| format!("failed to read {}: {err}", path.display()))?;
+        let mut bundle: UserDataBundle = serde_json::from_str(&data)
+            .map_err(|err| format!("failed to parse user data: {err}"))?;
+        if bundle.version > UserDataBundle::VERSION {
+            return Err(format!(
+                "the file was exported by a newer version (format {})",
+                bundle.version
+            ));
+        }
+        bundle.preferences.migrate_device_filters();
+        Ok(bundle)
+    })
+    .await
+    .map_err(|err| format!("failed to join import task: {err:?}"))?
+}
+
+async fn post_webhook(url: String, event: NowPlayingEvent) -> AsyncResult<()> {
+    tokio::task::spawn_blocking(move || {
+        webhook::post(&url, &event).map_err(|err| format!("{err:?}"))
+    })
+    .await
+    .map_err(|err| format!("failed to join webhook task: {err:?}"))?
+}
+
 async fn load_user_preferences() -> AsyncResult<UserPreferences> {
     tokio::task::spawn_blocking(|| {
         let path = std::path::Path::new(USER_DATA_FILE);
@@ -1517,7 +12371,10 @@ async fn load_user_preferences() -> AsyncResult<UserPreferences> {
         }
         let data = std::fs::read_to_string(path)
             .map_err(|err| format!("failed to read preferences: {err}"))?;
-        serde_json::from_str(&data).map_err(|err| format!("failed to parse preferences: {err}"))
+        let mut prefs: UserPreferences = serde_json::from_str(&data)
+            .map_err(|err| format!("failed to parse preferences: {err}"))?;
+        prefs.migrate_device_filters();
+        Ok(prefs)
     })
     .await
     .map_err(|err| format!("failed to join preferences task: {err:?}"))?
@@ -1539,26 +12396,395 @@ async fn save_user_preferences(prefs: UserPreferences) -> AsyncResult<()> {
     .map_err(|err| format!("failed to join save task: {err:?}"))?
 }
 
-async fn prepare_playback(
+async fn load_resume_state() -> AsyncResult<Option<ResumeState>> {
+    tokio::task::spawn_blocking(|| {
+        let path = std::path::Path::new(RESUME_STATE_FILE);
+        if !path.exists() {
+            return Ok(None);
+        }
+        let data = std::fs::read_to_string(path)
+            .map_err(|err| format!("failed to read resume state: {err}"))?;
+        serde_json::from_str(&data)
+            .map(Some)
+            .map_err(|err| format!("failed to parse resume state: {err}"))
+    })
+    .await
+    .map_err(|err| format!("failed to join resume state task: {err:?}"))?
+}
+
+/// Writes the resume point, or removes it when `state` is `None`.
+async fn save_resume_state(state: Option<ResumeState>) -> AsyncResult<()> {
+    tokio::task::spawn_blocking(move || {
+        let path = std::path::Path::new(RESUME_STATE_FILE);
+        let Some(state) = state else {
+            return match std::fs::remove_file(path) {
+                Err(err) if err.kind() != std::io::ErrorKind::NotFound => {
+                    Err(format!("failed to remove resume state: {err}"))
+                }
+                _ => Ok(()),
+            };
+        };
+        if let Some(parent) = path.parent() {
+            std::fs::create_dir_all(parent)
+                .map_err(|err| format!("failed to create data directory: {err}"))?;
+        }
+        let serialized = serde_json::to_string_pretty(&state)
+            .map_err(|err| format!("failed to serialize resume state: {err}"))?;
+        // Written aside and renamed into place, so being killed mid-write
+        // leaves the previous state rather than half a file.
+        let partial = path.with_extension("json.partial");
+        std::fs::write(&partial, serialized)
+            .and_then(|()| std::fs::rename(&partial, path))
+            .map_err(|err| format!("failed to write resume state: {err}"))
+    })
+    .await
+    .map_err(|err| format!("failed to join resume state task: {err:?}"))?
+}
+
+async fn load_practice_log() -> AsyncResult<PracticeLog> {
+    tokio::task::spawn_blocking(|| PracticeLog::load(std::path::Path::new(PRACTICE_LOG_FILE)))
+        .await
+        .map_err(|err| format!("failed to join practice log task: {err:?}"))?
+        .map_err(|err| format!("{err:?}"))
+}
+
+/// Loads the stored metadata when `metadata` is `None`, updates it for
+/// `paths` and writes it back if anything changed.
+async fn refresh_library_metadata(
+    metadata: Option<LibraryMetadata>,
+    paths: Vec<PathBuf>,
+) -> AsyncResult<LibraryMetadata> {
+    tokio::task::spawn_blocking(move || {
+        let file = std::path::Path::new(LIBRARY_METADATA_FILE);
+        let mut metadata = match metadata {
+            Some(metadata) => metadata,
+            None => LibraryMetadata::load(file).unwrap_or_else(|err| {
+                log::warn!("discarding unreadable library metadata: {err:?}");
+                LibraryMetadata::default()
+            }),
+        };
+        if metadata.refresh(paths.iter().map(PathBuf::as_path)) {
+            metadata.save(file).map_err(|err| format!("{err:?}"))?;
+        }
+        Ok(metadata)
+    })
+    .await
+    .map_err(|err| format!("library metadata task failed: {err:?}"))?
+}
+
+async fn save_practice_log(log: PracticeLog) -> AsyncResult<()> {
+    tokio::task::spawn_blocking(move || log.save(std::path::Path::new(PRACTICE_LOG_FILE)))
+        .await
+        .map_err(|err| format!("failed to join practice log task: {err:?}"))?
+        .map_err(|err| format!("{err:?}"))
+}
+
+async fn export_practice_stats(
+    log: PracticeLog,
+    kind: StatsExportKind,
+    range: DateRange,
     path: PathBuf,
+) -> AsyncResult<PathBuf> {
+    tokio::task::spawn_blocking(move || {
+        log.export(kind, range, &path)
+            .map(|_| path)
+            .map_err(|err| format!("{err:?}"))
+    })
+    .await
+    .map_err(|err| format!("failed to join export task: {err:?}"))?
+}
+
+fn pick_soundfont() -> Option<PathBuf> {
+    rfd::FileDialog::new()
+        .set_title(t!("Choose soundfont"))
+        .add_filter("SoundFont", &["sf2"])
+        .pick_file()
+}
+
+async fn import_inbox(config: WatchFolderConfig) -> AsyncResult<InboxReport> {
+    tokio::task::spawn_blocking(move || inbox::import_inbox(&config))
+        .await
+        .map_err(|err| format!("inbox import task failed: {err:?}"))?
+        .map_err(|err| format!("{err:?}"))
+}
+
+async fn scan_watch_library(config: WatchFolderConfig) -> AsyncResult<Vec<InboxImport>> {
+    tokio::task::spawn_blocking(move || inbox::scan_library_root(&config))
+        .await
+        .map_err(|err| format!("library index task failed: {err:?}"))?
+        .map_err(|err| format!("{err:?}"))
+}
+
+async fn scan_music_folders(folders: Vec<PathBuf>) -> AsyncResult<Vec<InboxImport>> {
+    tokio::task::spawn_blocking(move || {
+        let mut found = Vec::new();
+        for folder in &folders {
+            found.extend(inbox::scan_music_folder(folder)?);
+        }
+        Ok(found)
+    })
+    .await
+    .map_err(|err| format!("music folder scan task failed: {err:?}"))?
+    .map_err(|err: anyhow::Error| format!("{err:?}"))
+}
+
+async fn send_test_tone(
     device_id: Uuid,
     manager: Arc<Mutex<MidiDeviceManager>>,
-) -> AsyncResult<PreparedPlayback> {
-    let sequence = tokio::task::spawn_blocking(move || MidiSequence::from_file(&path))
+) -> AsyncResult<()> {
+    const CHORD: [u8; 3] = [60, 64, 67];
+    let connector = manager
+        .lock()
+        .await
+        .connector(&device_id)
+        .map_err(|err| format!("{err:?}"))?;
+    let sink = connector
+        .connect(DEFAULT_CONNECT_TIMEOUT)
         .await
-        .map_err(|err| format!("sequence loader task failed: {err:?}"))?
         .map_err(|err| format!("{err:?}"))?;
+    let note_ons: Vec<Vec<u8>> = CHORD.iter().map(|key| vec![0x90, *key, 90]).collect();
+    let note_offs: Vec<Vec<u8>> = CHORD.iter().map(|key| vec![0x80, *key, 0]).collect();
+    sink.send_batch(&note_ons)
+        .await
+        .map_err(|err| format!("{err:?}"))?;
+    tokio::time::sleep(Duration::from_millis(800)).await;
+    sink.send_batch(&note_offs)
+        .await
+        .map_err(|err| format!("{err:?}"))
+}
+
+/// Writes the `range` section of `source` to `output` as a new file.
+async fn save_excerpt(
+    source: PathBuf,
+    range: ExcerptRange,
+    output: PathBuf,
+) -> AsyncResult<PathBuf> {
+    tokio::task::spawn_blocking(move || {
+        let sequence = MidiSequence::from_file(&source)?;
+        let (start, end) = match range {
+            ExcerptRange::Bars(from, to) => {
+                let start = sequence
+                    .measure_start(from)
+                    .ok_or_else(|| anyhow::anyhow!("the song has no bar {from}"))?;
+                let end = sequence
+                    .measure_start(to.saturating_add(1))
+                    .unwrap_or(sequence.duration);
+                (start, end)
+            }
+            ExcerptRange::Time(start, end) => {
+                if start >= sequence.duration {
+                    anyhow::bail!("the song ends at {}", format_duration(sequence.duration));
+                }
+                (start, end)
+            }
+        };
+        let bytes = sequence.excerpt(start, end).to_smf_bytes()?;
+        std::fs::write(&output, bytes)
+            .map_err(|err| anyhow::anyhow!("failed to write {}: {err}", output.display()))?;
+        Ok(output)
+    })
+    .await
+    .map_err(|err| format!("excerpt task failed: {err:?}"))?
+    .map_err(|err: anyhow::Error| format!("{err:?}"))
+}
+
+/// Writes `source` with `adjustments` applied to `output`, a new file for
+/// the library.
+async fn export_arrangement(
+    source: PathBuf,
+    adjustments: PlaybackAdjustments,
+    output: PathBuf,
+) -> AsyncResult<PathBuf> {
+    tokio::task::spawn_blocking(move || {
+        let bytes = MidiSequence::from_file(&source)?
+            .adjusted(adjustments)
+            .to_smf_bytes()?;
+        std::fs::write(&output, bytes)
+            .map_err(|err| anyhow::anyhow!("failed to write {}: {err}", output.display()))?;
+        Ok(output)
+    })
+    .await
+    .map_err(|err| format!("export task failed: {err:?}"))?
+    .map_err(|err: anyhow::Error| format!("{err:?}"))
+}
+
+/// A song to read and adjust for playback.
+struct SequenceLoad {
+    source: MidiSource,
+    adjustments: PlaybackAdjustments,
+    /// Set with `--timing` to record how long parsing takes.
+    timing: Option<Arc<TimingCapture>>,
+}
+
+impl SequenceLoad {
+    fn load(self) -> anyhow::Result<MidiSequence> {
+        let parse = || MidiSequence::from_source(&self.source);
+        match &self.timing {
+            Some(timing) => timing.time_parse(parse),
+            None => parse(),
+        }
+        .map(|sequence| sequence.adjusted(self.adjustments))
+    }
+}
+
+async fn prepare_playback(
+    track_id: Uuid,
+    load: SequenceLoad,
+    position: Duration,
+    device_id: Uuid,
+    manager: Arc<Mutex<MidiDeviceManager>>,
+    connect_timeout: Duration,
+) -> Result<PreparedPlayback, AppError> {
+    let sequence = tokio::task::spawn_blocking(move || load.load())
+        .await
+        .map_err(|err| AppError::new(anyhow::anyhow!("sequence loader task failed: {err:?}")))?
+        .map_err(AppError::new)?;
     let sequence = Arc::new(sequence);
 
-    let sink = {
-        let guard = manager.lock().await;
-        guard
-            .connect(&device_id)
-            .await
-            .map_err(|err| format!("{err:?}"))?
+    // Only look the device up under the lock, so a slow connection does not
+    // hold up refreshes.
+    let connector = manager
+        .lock()
+        .await
+        .connector(&device_id)
+        .map_err(AppError::new)?;
+    let sink = connector
+        .connect(connect_timeout)
+        .await
+        .map_err(AppError::new)?;
+    // Connections are kept for the next song; drop any left over from a
+    // previously used device.
+    let closing = manager.lock().await.disconnect_others(Some(device_id));
+    closing.await;
+
+    Ok(PreparedPlayback {
+        track_id,
+        sequence,
+        sink,
+        position,
+    })
+}
+
+/// Connects to the device and plays the first [`PREVIEW_LENGTH`] of a song.
+async fn preview_song(
+    load: SequenceLoad,
+    device_id: Uuid,
+    manager: Arc<Mutex<MidiDeviceManager>>,
+    connect_timeout: Duration,
+    cancel: Arc<Notify>,
+) -> AsyncResult<()> {
+    let sequence = tokio::task::spawn_blocking(move || load.load())
+        .await
+        .map_err(|err| format!("preview task failed: {err:?}"))?
+        .map_err(|err| format!("{err:?}"))?;
+    let connector = manager
+        .lock()
+        .await
+        .connector(&device_id)
+        .map_err(|err| format!("{err:?}"))?;
+    let sink = connector
+        .connect(connect_timeout)
+        .await
+        .map_err(|err| format!("{err:?}"))?;
+    play_preview(&sequence, &sink, PREVIEW_LENGTH, &cancel)
+        .await
+        .map_err(|err| format!("{err:?}"))
+}
+
+async fn detect_queue_keys(paths: Vec<PathBuf>) -> AsyncResult<Vec<Option<MusicalKey>>> {
+    tokio::task::spawn_blocking(move || {
+        paths
+            .iter()
+            .map(|path| {
+                key::detect_key(path).unwrap_or_else(|err| {
+                    log::warn!("key detection failed for {}: {err:?}", path.display());
+                    None
+                })
+            })
+            .collect()
+    })
+    .await
+    .map_err(|err| format!("key detection task failed: {err:?}"))
+}
+
+async fn read_song_details(path: PathBuf) -> AsyncResult<SongDetails> {
+    tokio::task::spawn_blocking(move || {
+        let contents = read_midi_file(&path)?;
+        sequence::file_details(&contents)
+    })
+    .await
+    .map_err(|err| format!("song details task failed: {err:?}"))?
+    .map_err(|err| format!("{err:?}"))
+}
+
+async fn inspect_song(path: PathBuf) -> AsyncResult<SequenceInfo> {
+    tokio::task::spawn_blocking(move || sequence::inspect_file(&path))
+        .await
+        .map_err(|err| format!("song inspection task failed: {err:?}"))?
+        .map_err(|err| format!("{err:?}"))
+}
+
+/// Flushes all channels on the playing sink, or on the selected device when
+/// nothing is playing.
+async fn send_panic(
+    sink: Option<SharedMidiSink>,
+    device_id: Option<Uuid>,
+    manager: Arc<Mutex<MidiDeviceManager>>,
+) -> AsyncResult<()> {
+    let sink = match (sink, device_id) {
+        (Some(sink), _) => sink,
+        (None, Some(device_id)) => {
+            let connector = manager
+                .lock()
+                .await
+                .connector(&device_id)
+                .map_err(|err| format!("{err:?}"))?;
+            connector
+                .connect(DEFAULT_CONNECT_TIMEOUT)
+                .await
+                .map_err(|err| format!("{err:?}"))?
+        }
+        (None, None) => return Err("no MIDI device selected".into()),
+    };
+    sink.panic().await.map_err(|err| format!("{err:?}"))
+}
+
+/// Reads `m:ss` or plain seconds, with optional fractions.
+fn parse_timestamp(value: &str) -> Option<Duration> {
+    let value = value.trim();
+    let (minutes, seconds) = match value.split_once(':') {
+        Some((minutes, seconds)) => (minutes.parse::<u64>().ok()?, seconds),
+        None => (0, value),
     };
+    let seconds = Duration::try_from_secs_f64(seconds.parse().ok()?).ok()?;
+    Duration::from_secs(minutes.checked_mul(60)?).checked_add(seconds)
+}
+
+/// Shows `dir` in the system's file manager.
+fn open_folder(dir: &std::path::Path) -> std::io::Result<()> {
+    let program = if cfg!(target_os = "windows") {
+        "explorer"
+    } else if cfg!(target_os = "macos") {
+        "open"
+    } else {
+        "xdg-open"
+    };
+    std::process::Command::new(program).arg(dir).spawn()?;
+    Ok(())
+}
 
-    Ok(PreparedPlayback { sequence, sink })
+/// The opening tempo, with the average after it when the song speeds up or
+/// slows down along the way.
+fn tempo_label(tempo: TempoSummary) -> String {
+    if tempo.varies() {
+        t!(
+            "{initial} BPM (avg {average})",
+            initial = format!("{:.0}", tempo.initial_bpm),
+            average = format!("{:.0}", tempo.average_bpm)
+        )
+    } else {
+        t!("{bpm} BPM", bpm = format!("{:.0}", tempo.initial_bpm))
+    }
 }
 
 fn format_duration(duration: Duration) -> String {
@@ -1576,6 +12802,34 @@ fn view(state: &MidiPianoApp) -> Element<'_, Message> {
     state.view()
 }
 
+/// Maps hardware media keys and library navigation keys pressed while the
+/// window has focus. Keys typed into a text field never arrive here.
+fn key_message(key: keyboard::Key, modifiers: keyboard::Modifiers) -> Option<Message> {
+    use keyboard::key::Named;
+
+    match key {
+        keyboard::Key::Named(Named::ArrowUp) => Some(Message::LibraryKey(LibraryKey::Previous)),
+        keyboard::Key::Named(Named::ArrowDown) => Some(Message::LibraryKey(LibraryKey::Next)),
+        keyboard::Key::Named(Named::Home) => Some(Message::LibraryKey(LibraryKey::First)),
+        keyboard::Key::Named(Named::End) => Some(Message::LibraryKey(LibraryKey::Last)),
+        keyboard::Key::Named(Named::Enter) => Some(Message::LibraryKey(LibraryKey::Activate)),
+        keyboard::Key::Named(Named::Escape) => Some(Message::LibraryKey(LibraryKey::Leave)),
+        keyboard::Key::Named(Named::Space) if !modifiers.command() && !modifiers.alt() => {
+            Some(Message::LibraryKey(LibraryKey::Type(" ".into())))
+        }
+        keyboard::Key::Character(typed) if !modifiers.command() && !modifiers.alt() => {
+            Some(Message::LibraryKey(LibraryKey::Type(typed.to_string())))
+        }
+        keyboard::Key::Named(Named::MediaPlayPause | Named::MediaPlay | Named::MediaPause) => {
+            Some(Message::PlayPause)
+        }
+        keyboard::Key::Named(Named::MediaTrackNext) => Some(Message::NextTrack),
+        keyboard::Key::Named(Named::MediaTrackPrevious) => Some(Message::PrevTrack),
+        keyboard::Key::Named(Named::MediaStop) => Some(Message::StopPressed),
+        _ => None,
+    }
+}
+
 fn subscription(state: &MidiPianoApp) -> Subscription<Message> {
     state.subscription()
 }
@@ -1588,17 +12842,25 @@ fn collect_tree_items(
     node: &LibraryNode,
     depth: usize,
     expanded: &HashSet<String>,
+    collation: TitleCollation,
     items: &mut Vec<TreeItem>,
 ) {
-    for child in node.children.values() {
-        collect_tree_items_inner(child, depth, expanded, items);
+    for child in sorted_children(node, collation) {
+        collect_tree_items_inner(child, depth, expanded, collation, items);
     }
 }
 
+fn sorted_children(node: &LibraryNode, collation: TitleCollation) -> Vec<&LibraryNode> {
+    let mut children: Vec<&LibraryNode> = node.children.values().collect();
+    children.sort_by_cached_key(|child| collation.key(&child.name));
+    children
+}
+
 fn collect_tree_items_inner(
     node: &LibraryNode,
     depth: usize,
     expanded: &HashSet<String>,
+    collation: TitleCollation,
     items: &mut Vec<TreeItem>,
 ) {
     let has_children = !node.children.is_empty();
@@ -1607,12 +12869,14 @@ fn collect_tree_items_inner(
         id: node.id.clone(),
         name: node.name.clone(),
         depth,
+        track_count: node.track_count,
+        total_duration: node.total_duration,
         has_children,
         is_expanded,
     });
     if has_children && is_expanded {
-        for child in node.children.values() {
-            collect_tree_items_inner(child, depth + 1, expanded, items);
+        for child in sorted_children(node, collation) {
+            collect_tree_items_inner(child, depth + 1, expanded, collation, items);
         }
     }
 }
@@ -1633,10 +12897,12 @@ fn build_window_icon() -> Option<window::Icon> {
     window::icon::from_rgba(rgba, size, size).ok()
 }
 
-pub fn run() -> iced::Result {
+pub fn run(debug_options: DebugOptions) -> iced::Result {
     let icon = build_window_icon();
     let window_settings = window::Settings {
         icon,
+        // Closing may only hide the window; see `WindowCloseRequested`.
+        exit_on_close_request: false,
         ..window::Settings::default()
     };
     application("MIDI Piano Player", update, view)
@@ -1646,76 +12912,146 @@ pub fn run() -> iced::Result {
         .font(NOTO_SANS_SC)
         .default_font(DEFAULT_FONT)
         .executor::<executor::Default>()
-        .run_with(MidiPianoApp::init)
+        .run_with(move || MidiPianoApp::init(debug_options))
 }
 
+/// Builds the tree off the UI thread. `grouping` carries copies of the
+/// ratings and tags it may group by.
 async fn compute_tree_data(
-    entries: Vec<crate::midi::MidiEntry>,
-) -> AsyncResult<(LibraryNode, HashMap<String, Vec<Uuid>>)> {
-    tokio::task::spawn_blocking(move || build_tree_data_owned(entries))
-        .await
-        .map_err(|err| format!("tree rebuild task failed: {err:?}"))
+    entries: Vec<midi_piano_rs::midi::MidiEntry>,
+    durations: HashMap<Uuid, Duration>,
+    grouping: (
+        TreeGrouping,
+        HashMap<Uuid, u8>,
+        HashMap<Uuid, BTreeSet<String>>,
+    ),
+) -> AsyncResult<LibraryNode> {
+    tokio::task::spawn_blocking(move || {
+        let (grouping, ratings, tags) = grouping;
+        let groups = TreeGroups {
+            grouping,
+            ratings: &ratings,
+            tags: &tags,
+        };
+        build_tree_data_owned(entries, &durations, groups)
+    })
+    .await
+    .map_err(|err| format!("tree rebuild task failed: {err:?}"))
 }
 
+/// Builds the tree with track counts and total durations, as folders or as
+/// one level of `groups`. Folder contents are not stored here; see
+/// [`folder_contains`].
 fn build_tree_data_owned(
-    entries: Vec<crate::midi::MidiEntry>,
-) -> (LibraryNode, HashMap<String, Vec<Uuid>>) {
-    let mut root = LibraryNode::new("root".into(), "Library".into());
-    let mut folders: HashMap<String, Vec<Uuid>> = HashMap::new();
-    folders.insert("root".into(), Vec::new());
-
-    let mut local_ids: Vec<Uuid> = Vec::new();
+    entries: Vec<midi_piano_rs::midi::MidiEntry>,
+    durations: &HashMap<Uuid, Duration>,
+    groups: TreeGroups<'_>,
+) -> LibraryNode {
+    let duration_of = |entry: &midi_piano_rs::midi::MidiEntry| {
+        durations.get(&entry.id).copied().unwrap_or_default()
+    };
+    let mut root = LibraryNode::new("root".into(), t!("Library").into());
+    root.track_count = entries.len();
+    root.total_duration = entries.iter().map(duration_of).sum();
+
+    if groups.grouping != TreeGrouping::Folder {
+        for entry in &entries {
+            for (id, name) in groups.of(entry) {
+                let node = root.ensure_child(id, name);
+                node.track_count += 1;
+                node.total_duration += duration_of(entry);
+            }
+        }
+        return root;
+    }
 
+    let mut local_entries = Vec::new();
+    let mut remote_entries = Vec::new();
     for entry in entries {
         match entry.origin {
-            crate::midi::MidiOrigin::Asset => {
-                folders
-                    .entry("root".into())
-                    .or_insert_with(Vec::new)
-                    .push(entry.id);
-
-                if let Some(segments) = entry.library_path.clone() {
-                    if segments.is_empty() {
-                        continue;
-                    }
-
-                    let mut node = &mut root;
-                    let mut path_builder = String::new();
-                    for (index, segment) in segments.iter().enumerate() {
-                        if index > 0 {
-                            path_builder.push('/');
-                        }
-                        path_builder.push_str(segment);
-                        let node_id = format!("asset:{}", path_builder);
-                        node = node.ensure_child(node_id.clone(), segment.clone());
-                        folders.entry(node_id.clone()).or_insert_with(Vec::new);
-                    }
-
-                    let leaf_id = format!("asset:{}", path_builder);
-                    folders
-                        .entry(leaf_id)
-                        .or_insert_with(Vec::new)
-                        .push(entry.id);
+            midi_piano_rs::midi::MidiOrigin::Asset => {
+                if let Some(segments) = &entry.library_path {
+                    add_tree_path(&mut root, "asset", segments, duration_of(&entry));
                 }
             }
-            crate::midi::MidiOrigin::Local => {
-                local_ids.push(entry.id);
+            midi_piano_rs::midi::MidiOrigin::Local => local_entries.push(entry),
+            midi_piano_rs::midi::MidiOrigin::Remote => remote_entries.push(entry),
+        }
+    }
+
+    if !remote_entries.is_empty() {
+        let remote_node = root.ensure_child("remote".into(), t!("Remote").into());
+        remote_node.track_count = remote_entries.len();
+        remote_node.total_duration = remote_entries.iter().map(duration_of).sum();
+    }
+
+    if !local_entries.is_empty() {
+        let local_node = root.ensure_child("local".into(), t!("Local").into());
+        local_node.track_count = local_entries.len();
+        local_node.total_duration = local_entries.iter().map(duration_of).sum();
+        for entry in &local_entries {
+            if let Some(segments) = &entry.library_path {
+                add_tree_path(local_node, "local", segments, duration_of(entry));
             }
         }
     }
 
-    if !local_ids.is_empty() {
-        folders
-            .entry("root".into())
-            .or_insert_with(Vec::new)
-            .extend(local_ids.iter().copied());
-        let local_id = "local".to_string();
-        root.ensure_child(local_id.clone(), "Local".into());
-        folders
-            .entry(local_id)
-            .or_insert_with(Vec::new)
-            .extend(local_ids);
+    root
+}
+
+fn add_tree_path(parent: &mut LibraryNode, prefix: &str, segments: &[String], duration: Duration) {
+    let mut node = parent;
+    let mut path_builder = String::new();
+    for (index, segment) in segments.iter().enumerate() {
+        if index > 0 {
+            path_builder.push('/');
+        }
+        path_builder.push_str(segment);
+        node = node.ensure_child(format!("{prefix}:{path_builder}"), segment.clone());
+        node.track_count += 1;
+        node.total_duration += duration;
     }
+}
 
-    (root, folders)
+/// Whether an entry is listed under a tree folder. "root" lists everything,
+/// "local" and "remote" every entry of that origin, and other folders the
+/// files filed directly in them, or also in their sub-folders when
+/// `recursive` is set. Under other groupings a folder lists the entries in
+/// that group.
+fn folder_contains(
+    folder_id: &str,
+    entry: &midi_piano_rs::midi::MidiEntry,
+    recursive: bool,
+    groups: TreeGroups<'_>,
+) -> bool {
+    if groups.grouping != TreeGrouping::Folder {
+        return folder_id == "root" || groups.of(entry).iter().any(|(id, _)| id == folder_id);
+    }
+    let is_local = matches!(entry.origin, midi_piano_rs::midi::MidiOrigin::Local);
+    match folder_id {
+        "root" => true,
+        "local" => is_local,
+        "remote" => matches!(entry.origin, midi_piano_rs::midi::MidiOrigin::Remote),
+        _ => {
+            let (prefix, path) = folder_id.split_once(':').unwrap_or((folder_id, ""));
+            let origin_matches = if is_local {
+                prefix == "local"
+            } else {
+                prefix == "asset"
+            };
+            origin_matches
+                && entry
+                    .library_path
+                    .as_ref()
+                    .filter(|segments| !segments.is_empty())
+                    .is_some_and(|segments| {
+                        let joined = segments.join("/");
+                        joined == path
+                            || (recursive
+                                && joined
+                                    .strip_prefix(path)
+                                    .is_some_and(|rest| rest.starts_with('/')))
+                    })
+        }
+    }
 }
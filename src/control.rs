@@ -0,0 +1,248 @@
+//! A local WebSocket server for controlling playback from other devices,
+//! such as a phone remote or home automation.
+//!
+//! Clients connect to `ws://<host>:<port>/?token=<token>` (or send the token
+//! as an `Authorization: Bearer` header) and exchange JSON text messages.
+//! Commands look like `{"command": "seek", "seconds": 42.0}`; the server
+//! answers with `{"type": "status", ...}` whenever playback changes and
+//! `{"type": "error", "message": ...}` for commands it cannot read.
+
+use std::net::SocketAddr;
+use std::time::Duration;
+
+use anyhow::{Context, Result};
+use futures::{SinkExt, StreamExt};
+use serde::{Deserialize, Serialize};
+use tokio::net::{TcpListener, TcpStream};
+use tokio::sync::{mpsc::UnboundedSender, watch};
+use tokio::task::JoinSet;
+use tokio_tungstenite::tungstenite::Message as WsMessage;
+use tokio_tungstenite::tungstenite::handshake::server::{ErrorResponse, Request, Response};
+use tokio_tungstenite::tungstenite::http::StatusCode;
+use uuid::Uuid;
+
+/// A request from a remote client, handed to the app through a channel.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "command", rename_all = "snake_case")]
+pub enum ControlCommand {
+    Play,
+    Pause,
+    PlayPause,
+    Stop,
+    Next,
+    Previous,
+    Seek {
+        seconds: f64,
+    },
+    /// Plays a library entry on its own.
+    PlayTrack {
+        id: Uuid,
+    },
+    /// Adds a library entry to the end of the queue.
+    Enqueue {
+        id: Uuid,
+    },
+    /// Asks for the current status without changing anything. Answered by
+    /// the server itself.
+    Status,
+}
+
+/// Where a `seek` to `seconds` lands in a song lasting `total`: positions
+/// past the end land on it, negative and non-finite ones nowhere.
+pub fn seek_target(seconds: f64, total: Duration) -> Option<Duration> {
+    if !is_position(seconds) {
+        return None;
+    }
+    Duration::try_from_secs_f64(seconds.min(total.as_secs_f64())).ok()
+}
+
+fn is_position(seconds: f64) -> bool {
+    seconds >= 0.0 && seconds.is_finite()
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ControlState {
+    Playing,
+    Paused,
+    #[default]
+    Stopped,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ControlTrack {
+    pub id: Uuid,
+    pub name: String,
+}
+
+/// What remote clients are told about playback.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct ControlStatus {
+    pub state: ControlState,
+    pub track: Option<ControlTrack>,
+    pub position_secs: f64,
+    pub duration_secs: Option<f64>,
+    pub queue: Vec<ControlTrack>,
+    /// Index of the playing track in `queue`.
+    pub queue_index: Option<usize>,
+}
+
+#[derive(Debug, Serialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+enum Reply<'a> {
+    Status(&'a ControlStatus),
+    Error { message: String },
+}
+
+/// A bound but not yet running control server.
+pub struct ControlServer {
+    listener: TcpListener,
+    token: String,
+}
+
+impl ControlServer {
+    /// Listens on `addr`. Only clients presenting `token` are accepted.
+    pub async fn bind(addr: SocketAddr, token: impl Into<String>) -> Result<Self> {
+        let listener = TcpListener::bind(addr)
+            .await
+            .with_context(|| format!("failed to listen on {addr}"))?;
+        Ok(Self {
+            listener,
+            token: token.into(),
+        })
+    }
+
+    pub fn local_addr(&self) -> Result<SocketAddr> {
+        self.listener
+            .local_addr()
+            .context("control server has no local address")
+    }
+
+    /// Accepts clients until the listener fails. Commands go to `commands`
+    /// and every change to `status` is pushed to all clients. Dropping the
+    /// future disconnects everyone.
+    pub async fn run(
+        self,
+        commands: UnboundedSender<ControlCommand>,
+        status: watch::Receiver<ControlStatus>,
+    ) -> Result<()> {
+        let mut clients = JoinSet::new();
+        loop {
+            tokio::select! {
+                accepted = self.listener.accept() => {
+                    let (stream, peer) = accepted.context("failed to accept control client")?;
+                    let token = self.token.clone();
+                    let commands = commands.clone();
+                    let status = status.clone();
+                    clients.spawn(async move {
+                        if let Err(err) = serve_client(stream, &token, commands, status).await {
+                            log::info!("control client {peer} disconnected: {err:?}");
+                        }
+                    });
+                }
+                // Reaps finished clients so the set does not grow forever.
+                Some(_) = clients.join_next(), if !clients.is_empty() => {}
+            }
+        }
+    }
+}
+
+async fn serve_client(
+    stream: TcpStream,
+    token: &str,
+    commands: UnboundedSender<ControlCommand>,
+    mut status: watch::Receiver<ControlStatus>,
+) -> Result<()> {
+    // tungstenite decides the shape of the rejection.
+    #[allow(clippy::result_large_err)]
+    let authorize = |request: &Request, response: Response| {
+        if presented_token(request).is_some_and(|presented| tokens_match(presented, token)) {
+            Ok(response)
+        } else {
+            let mut rejection = ErrorResponse::new(Some("invalid or missing token".into()));
+            *rejection.status_mut() = StatusCode::UNAUTHORIZED;
+            Err(rejection)
+        }
+    };
+    let mut socket = tokio_tungstenite::accept_hdr_async(stream, authorize)
+        .await
+        .context("control handshake failed")?;
+
+    let current = status.borrow_and_update().clone();
+    socket.send(reply(&Reply::Status(&current))).await?;
+    loop {
+        tokio::select! {
+            incoming = socket.next() => {
+                let Some(incoming) = incoming else {
+                    return Ok(());
+                };
+                let text = match incoming? {
+                    WsMessage::Text(text) => text,
+                    WsMessage::Close(_) => return Ok(()),
+                    _ => continue,
+                };
+                match serde_json::from_str::<ControlCommand>(&text) {
+                    Ok(ControlCommand::Seek { seconds }) if !is_position(seconds) => {
+                        let message = format!("cannot seek to {seconds} seconds");
+                        socket.send(reply(&Reply::Error { message })).await?;
+                    }
+                    Ok(ControlCommand::Status) => {
+                        let current = status.borrow().clone();
+                        socket.send(reply(&Reply::Status(&current))).await?;
+                    }
+                    Ok(command) => {
+                        if commands.send(command).is_err() {
+                            // The app has gone away.
+                            return Ok(());
+                        }
+                    }
+                    Err(err) => {
+                        let message = format!("unrecognised command: {err}");
+                        socket.send(reply(&Reply::Error { message })).await?;
+                    }
+                }
+            }
+            changed = status.changed() => {
+                if changed.is_err() {
+                    return Ok(());
+                }
+                let current = status.borrow_and_update().clone();
+                socket.send(reply(&Reply::Status(&current))).await?;
+            }
+        }
+    }
+}
+
+fn reply(reply: &Reply<'_>) -> WsMessage {
+    let json = serde_json::to_string(reply).expect("control replies always serialize");
+    WsMessage::text(json)
+}
+
+/// The token from the `token` query parameter or a bearer `Authorization`
+/// header.
+fn presented_token(request: &Request) -> Option<&str> {
+    let from_query = request.uri().query().and_then(|query| {
+        query
+            .split('&')
+            .find_map(|pair| pair.strip_prefix("token="))
+    });
+    from_query.or_else(|| {
+        request
+            .headers()
+            .get("authorization")?
+            .to_str()
+            .ok()?
+            .strip_prefix("Bearer ")
+    })
+}
+
+/// Compares without stopping at the first difference, so response times
+/// do not reveal how much of a guess was right.
+fn tokens_match(presented: &str, expected: &str) -> bool {
+    presented.len() == expected.len()
+        && presented
+            .bytes()
+            .zip(expected.bytes())
+            .fold(0, |diff, (a, b)| diff | (a ^ b))
+            == 0
+}
@@ -0,0 +1,123 @@
+use std::collections::VecDeque;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::time::Instant;
+
+use anyhow::{Context, Result};
+use serde::de::DeserializeOwned;
+use serde::{Deserialize, Serialize};
+
+const DEFAULT_CAPACITY: usize = 500;
+const MAX_SUMMARY_LEN: usize = 160;
+
+/// Developer options taken from the command line.
+#[derive(Debug, Clone, Default)]
+pub struct DebugOptions {
+    pub enabled: bool,
+    pub capacity: usize,
+    pub replay: Option<PathBuf>,
+    /// Captures parse, scheduling and send timings for dumping to JSON.
+    pub timing: bool,
+}
+
+impl DebugOptions {
+    /// Recognises `--debug`, `--debug-history <N>`, `--replay <file>` and
+    /// `--timing`. Replay and history size only take effect together with
+    /// `--debug`.
+    pub fn from_args(args: impl IntoIterator<Item = String>) -> Self {
+        let mut options = DebugOptions {
+            capacity: DEFAULT_CAPACITY,
+            ..DebugOptions::default()
+        };
+        let mut args = args.into_iter();
+        while let Some(arg) = args.next() {
+            match arg.as_str() {
+                "--debug" => options.enabled = true,
+                "--timing" => options.timing = true,
+                "--debug-history" => match args.next().and_then(|value| value.parse().ok()) {
+                    Some(capacity) => options.capacity = capacity,
+                    None => log::warn!("--debug-history expects a message count"),
+                },
+                "--replay" => match args.next() {
+                    Some(path) => options.replay = Some(PathBuf::from(path)),
+                    None => log::warn!("--replay expects a file path"),
+                },
+                _ => {}
+            }
+        }
+        if !options.enabled && options.replay.is_some() {
+            log::warn!("--replay is ignored without --debug");
+            options.replay = None;
+        }
+        options
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct LoggedMessage<T> {
+    /// Milliseconds since the recorder was created.
+    pub at_ms: u64,
+    pub summary: String,
+    /// Present for messages that can be re-issued on replay.
+    pub replay: Option<T>,
+}
+
+/// Ring buffer of the most recent app messages.
+#[derive(Debug)]
+pub struct MessageRecorder<T> {
+    started: Instant,
+    capacity: usize,
+    messages: VecDeque<LoggedMessage<T>>,
+}
+
+impl<T: Serialize + DeserializeOwned> MessageRecorder<T> {
+    pub fn new(capacity: usize) -> Self {
+        Self {
+            started: Instant::now(),
+            capacity: capacity.max(1),
+            messages: VecDeque::with_capacity(capacity.max(1)),
+        }
+    }
+
+    pub fn record(&mut self, summary: String, replay: Option<T>) {
+        if self.messages.len() == self.capacity {
+            self.messages.pop_front();
+        }
+        self.messages.push_back(LoggedMessage {
+            at_ms: self.started.elapsed().as_millis() as u64,
+            summary: truncate(summary),
+            replay,
+        });
+    }
+
+    pub fn len(&self) -> usize {
+        self.messages.len()
+    }
+
+    pub fn dump(&self, path: &Path) -> Result<()> {
+        if let Some(parent) = path.parent() {
+            fs::create_dir_all(parent).context("failed to create debug directory")?;
+        }
+        let serialized =
+            serde_json::to_string_pretty(&self.messages).context("failed to serialize messages")?;
+        fs::write(path, serialized).with_context(|| format!("failed to write {}", path.display()))
+    }
+}
+
+pub fn load_recording<T: DeserializeOwned>(path: &Path) -> Result<Vec<LoggedMessage<T>>> {
+    let data =
+        fs::read_to_string(path).with_context(|| format!("failed to read {}", path.display()))?;
+    serde_json::from_str(&data).context("failed to parse message recording")
+}
+
+fn truncate(mut summary: String) -> String {
+    if summary.len() > MAX_SUMMARY_LEN {
+        let mut end = MAX_SUMMARY_LEN;
+        while !summary.is_char_boundary(end) {
+            end -= 1;
+        }
+        summary.truncate(end);
+        summary.push('…');
+    }
+    summary
+}
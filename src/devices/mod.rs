@@ -1,22 +1,37 @@
-use std::collections::{HashMap, hash_map::Entry};
-use std::sync::Arc;
-use std::time::Duration;
+pub mod profile;
+
+use std::collections::{HashMap, HashSet, hash_map::Entry};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, mpsc};
+use std::thread;
+use std::time::{Duration, Instant};
 
 use anyhow::{Context, Result, anyhow};
 use btleplug::api::{
-    Central, Characteristic, Manager as _, Peripheral as _, ScanFilter, WriteType,
+    Central, CharPropFlags, Characteristic, Manager as _, Peripheral as _, ScanFilter, WriteType,
 };
 use btleplug::platform::{Adapter, Manager as BtleManager, Peripheral, PeripheralId};
 use midir::{MidiOutput, MidiOutputConnection};
 use once_cell::sync::Lazy;
-use tokio::sync::Mutex;
+use tokio::sync::{Mutex, OwnedSemaphorePermit, Semaphore, oneshot};
 use tokio::time;
 use uuid::Uuid;
 
-use crate::midi::sink::{MidiSink, MidiSinkInfo, MidiTransport, SharedMidiSink};
+use crate::error::PlaybackError;
+use crate::midi::filter::{FilteredSink, OutputFilter, QuietLimit};
+use crate::midi::monitor::{MidiMonitor, MonitoredSink};
+use crate::midi::null_sink::NullSink;
+use crate::midi::sink::{
+    MidiSink, MidiSinkInfo, MidiTransport, SendStats, SharedMidiSink, SinkStats,
+};
+use crate::midi::timing::{TimingCapture, TimingSink};
+use crate::midi::trace::{SendTrace, TracedSink};
+use crate::midi::validate::{RunningStatus, ValidatingSink};
 
 const CLIENT_NAME: &str = "midi-piano-rs";
 const SCAN_TIMEOUT: Duration = Duration::from_secs(2);
+/// How long [`MidiDeviceManager::connect`] waits for a device.
+pub const DEFAULT_CONNECT_TIMEOUT: Duration = Duration::from_secs(10);
 
 static USB_NAMESPACE: Lazy<Uuid> =
     Lazy::new(|| Uuid::from_u128(0xdea27421_4dbe_474b_99ac_5a4a3f7bf110));
@@ -26,6 +41,33 @@ static BLE_NAMESPACE: Lazy<Uuid> =
 const BLE_MIDI_SERVICE_UUID: Uuid = Uuid::from_u128(0x03b80e5a_ede8_4b33_a751_6ce34ec4c700);
 const BLE_MIDI_CHARACTERISTIC_UUID: Uuid = Uuid::from_u128(0x7772e5db_3868_4112_a1a9_f2669d106bf3);
 
+/// Payload of a write at the minimum ATT MTU of 23 bytes, used when the
+/// negotiated MTU cannot be read.
+pub const MIN_BLE_PACKET_SIZE: usize = 20;
+/// The longest attribute value BLE allows.
+pub const MAX_BLE_PACKET_SIZE: usize = 512;
+/// Bytes of each ATT write taken up by the protocol rather than the value.
+const ATT_WRITE_OVERHEAD: usize = 3;
+/// Writes without response a BLE device may have queued at once.
+pub const BLE_MAX_IN_FLIGHT: usize = 4;
+/// How long a write without response counts as in flight: a slow
+/// connection interval, by which the packet has gone over the air.
+pub const BLE_WRITE_INTERVAL: Duration = Duration::from_millis(15);
+
+/// Fixed id of the built-in null output, so a selection survives refreshes.
+pub const NULL_DEVICE_ID: Uuid = Uuid::from_u128(0x2b7f0c1e_6a54_4d2e_9c83_51f0d6a4e9b2);
+const NULL_DEVICE_NAME: &str = "Null / Debug output";
+/// Fixed id of the app's own virtual output port.
+pub const VIRTUAL_PORT_DEVICE_ID: Uuid = Uuid::from_u128(0x8c1d4e7a_03b9_4f62_a5d8_6e2f91c7b430);
+/// The name other software sees the virtual output port under.
+pub const VIRTUAL_PORT_NAME: &str = "MIDI Piano Virtual Port";
+/// Whether this platform's MIDI backend can create virtual ports.
+pub const VIRTUAL_PORTS_SUPPORTED: bool = cfg!(unix);
+/// Whether this platform's MIDI backend takes several messages in one
+/// send: WinMM as a long message and CoreMIDI as one packet do, but ALSA
+/// only encodes the first.
+const COALESCE_USB_SENDS: bool = cfg!(any(windows, target_os = "macos"));
+
 #[derive(Clone, Debug)]
 pub struct MidiDeviceDescriptor {
     pub info: MidiSinkInfo,
@@ -36,6 +78,20 @@ pub struct MidiDeviceDescriptor {
 pub enum DeviceKind {
     Usb(UsbDevice),
     Ble(BleDevice),
+    Null,
+    VirtualPort,
+}
+
+impl DeviceKind {
+    /// How sends to this kind of device are grouped in timing reports.
+    fn timing_label(&self) -> &'static str {
+        match self {
+            DeviceKind::Usb(_) => "usb",
+            DeviceKind::Ble(_) => "ble",
+            DeviceKind::Null => "null",
+            DeviceKind::VirtualPort => "virtual_port",
+        }
+    }
 }
 
 #[derive(Clone, Debug)]
@@ -51,9 +107,38 @@ pub struct BleDevice {
     pub name: String,
 }
 
+/// Open device connections by device id, before filters and monitoring are
+/// layered on. Shared with [`DeviceConnector`]s so they can add to it
+/// without the manager.
+type ConnectionPool = Arc<std::sync::Mutex<HashMap<Uuid, SharedMidiSink>>>;
+
 pub struct MidiDeviceManager {
     bt_manager: Option<BtleManager>,
     devices: HashMap<Uuid, MidiDeviceDescriptor>,
+    connections: ConnectionPool,
+    null_sink: Arc<NullSink>,
+    monitor: Option<Arc<MidiMonitor>>,
+    trace: Option<Arc<SendTrace>>,
+    timing: Option<Arc<TimingCapture>>,
+    output_filters: HashMap<Uuid, OutputFilter>,
+    default_output_filter: OutputFilter,
+    quiet: Option<QuietLimit>,
+    piano_mode: bool,
+    ble_packet_size: Option<usize>,
+    /// Bluetooth devices always written to with response.
+    reliable_ble_writes: HashSet<Uuid>,
+    /// The only Bluetooth adapter scanned, by its adapter info.
+    ble_adapter: Option<String>,
+    usb_running_status: bool,
+    /// Open for as long as the virtual port is enabled, so other software
+    /// can connect to it before anything plays.
+    virtual_port: Option<SharedMidiSink>,
+}
+
+impl Default for MidiDeviceManager {
+    fn default() -> Self {
+        Self::new()
+    }
 }
 
 impl MidiDeviceManager {
@@ -61,7 +146,111 @@ impl MidiDeviceManager {
         Self {
             bt_manager: None,
             devices: HashMap::new(),
+            connections: ConnectionPool::default(),
+            null_sink: Arc::new(NullSink::new()),
+            monitor: None,
+            trace: None,
+            timing: None,
+            output_filters: HashMap::new(),
+            default_output_filter: OutputFilter::default(),
+            quiet: None,
+            piano_mode: false,
+            ble_packet_size: None,
+            reliable_ble_writes: HashSet::new(),
+            ble_adapter: None,
+            usb_running_status: false,
+            virtual_port: None,
+        }
+    }
+
+    /// Filters applied to connections made from now on; devices without an
+    /// entry in `per_device` use `default`.
+    pub fn set_output_filters(
+        &mut self,
+        default: OutputFilter,
+        per_device: HashMap<Uuid, OutputFilter>,
+    ) {
+        self.default_output_filter = default;
+        self.output_filters = per_device;
+    }
+
+    /// Quiet mode for connections made from now on, on top of each device's
+    /// filter; `None` turns it off.
+    pub fn set_quiet(&mut self, quiet: Option<QuietLimit>) {
+        self.quiet = quiet;
+    }
+
+    /// Puts [`OutputFilter::piano_mode`] over every device's rules for
+    /// connections made from now on.
+    pub fn set_piano_mode(&mut self, enabled: bool) {
+        self.piano_mode = enabled;
+    }
+
+    /// Largest BLE-MIDI packet written to Bluetooth devices connected from
+    /// now on. `None` sizes packets from the MTU each device negotiated.
+    pub fn set_ble_packet_size(&mut self, size: Option<usize>) {
+        self.ble_packet_size = size;
+    }
+
+    /// Bluetooth devices connected from now on that wait for every write to
+    /// be confirmed, when they accept writes with response.
+    pub fn set_reliable_ble_writes(&mut self, devices: HashSet<Uuid>) {
+        self.reliable_ble_writes = devices;
+    }
+
+    /// Scans only the Bluetooth adapter listed as `adapter` by
+    /// [`MidiDeviceManager::ble_adapters`]; `None` scans them all. While the
+    /// chosen adapter is missing, e.g. unplugged, every adapter is scanned.
+    pub fn set_ble_adapter(&mut self, adapter: Option<String>) {
+        self.ble_adapter = adapter;
+    }
+
+    /// Leaves out repeated status bytes on USB devices connected from now
+    /// on, for interfaces that keep up better with fewer bytes. Not every
+    /// system MIDI layer accepts running status (CoreMIDI does not), so
+    /// this is off unless asked for.
+    pub fn set_usb_running_status(&mut self, enabled: bool) {
+        self.usb_running_status = enabled;
+    }
+
+    /// Opens or closes the app's virtual output port. While it is open it is
+    /// listed as a device after the next refresh.
+    pub fn set_virtual_port(&mut self, enabled: bool) -> Result<()> {
+        match (enabled, self.virtual_port.is_some()) {
+            (true, false) => {
+                let connection = create_virtual_port()?;
+                self.virtual_port = Some(Arc::new(MidirSink::spawn(connection, None, false)?));
+            }
+            (false, true) => {
+                self.virtual_port = None;
+                self.devices.remove(&VIRTUAL_PORT_DEVICE_ID);
+            }
+            _ => {}
         }
+        Ok(())
+    }
+
+    /// Records everything sent through sinks connected from now on.
+    pub fn set_monitor(&mut self, monitor: Option<Arc<MidiMonitor>>) {
+        self.monitor = monitor;
+    }
+
+    /// Logs the bytes each device is sent, after output filters, on
+    /// connections made from now on.
+    pub fn set_trace(&mut self, trace: Option<Arc<SendTrace>>) {
+        self.trace = trace;
+    }
+
+    /// Records how long each device takes over every send, by transport, on
+    /// connections made from now on.
+    pub fn set_timing_capture(&mut self, timing: Option<Arc<TimingCapture>>) {
+        self.timing = timing;
+    }
+
+    /// The sink behind the built-in "Null / Debug output" device. It is shared
+    /// by every connection, so its recording spans songs.
+    pub fn null_sink(&self) -> Arc<NullSink> {
+        self.null_sink.clone()
     }
 
     pub async fn refresh(&mut self) -> Result<Vec<MidiDeviceDescriptor>> {
@@ -89,6 +278,21 @@ impl MidiDeviceManager {
             }
         }
 
+        if self.virtual_port.is_some() {
+            descriptors.push(MidiDeviceDescriptor {
+                info: MidiSinkInfo::with_id(
+                    VIRTUAL_PORT_DEVICE_ID,
+                    VIRTUAL_PORT_NAME,
+                    MidiTransport::VirtualPort,
+                ),
+                kind: DeviceKind::VirtualPort,
+            });
+        }
+        descriptors.push(MidiDeviceDescriptor {
+            info: MidiSinkInfo::with_id(NULL_DEVICE_ID, NULL_DEVICE_NAME, MidiTransport::Virtual),
+            kind: DeviceKind::Null,
+        });
+
         self.devices.clear();
         for descriptor in &descriptors {
             self.devices.insert(descriptor.info.id, descriptor.clone());
@@ -98,6 +302,70 @@ impl MidiDeviceManager {
         Ok(descriptors)
     }
 
+    /// Lists USB outputs again without the Bluetooth scan of
+    /// [`MidiDeviceManager::refresh`]; other devices found before stay
+    /// connectable.
+    pub fn refresh_usb(&mut self) -> Result<Vec<MidiDeviceDescriptor>> {
+        let descriptors = self.enumerate_usb_devices()?;
+        self.devices
+            .retain(|_, descriptor| !matches!(descriptor.kind, DeviceKind::Usb(_)));
+        for descriptor in &descriptors {
+            self.devices.insert(descriptor.info.id, descriptor.clone());
+        }
+        Ok(descriptors)
+    }
+
+    /// Closes every open connection except the one to `keep`, e.g. once
+    /// playback has moved to another device. The returned future does not
+    /// borrow the manager, so it can run after a lock on it is released.
+    pub fn disconnect_others(&self, keep: Option<Uuid>) -> impl Future<Output = ()> + use<> {
+        let closing: Vec<SharedMidiSink> = {
+            let mut connections = self.connections.lock().expect("connection pool poisoned");
+            let ids: Vec<Uuid> = connections
+                .keys()
+                .filter(|id| Some(**id) != keep)
+                .copied()
+                .collect();
+            ids.iter().filter_map(|id| connections.remove(id)).collect()
+        };
+        async move {
+            for sink in closing {
+                if let Err(err) = sink.disconnect().await {
+                    log::debug!("failed to disconnect MIDI device: {err:?}");
+                }
+            }
+        }
+    }
+
+    /// Closes every open connection, for app exit.
+    pub fn disconnect_all(&self) -> impl Future<Output = ()> + use<> {
+        self.disconnect_others(None)
+    }
+
+    /// The Bluetooth adapters on this system, by their adapter info (name
+    /// and address where the platform gives them).
+    pub async fn ble_adapters(&mut self) -> Result<Vec<String>> {
+        if self.bt_manager.is_none() {
+            self.bt_manager = Some(
+                BtleManager::new()
+                    .await
+                    .map_err(|err| anyhow!("BLE manager not available: {err}"))?,
+            );
+        }
+        let Some(manager) = &self.bt_manager else {
+            return Ok(Vec::new());
+        };
+        let adapters = manager
+            .adapters()
+            .await
+            .context("failed to retrieve BLE adapters")?;
+        let mut names = Vec::with_capacity(adapters.len());
+        for adapter in &adapters {
+            names.push(adapter_key(adapter).await);
+        }
+        Ok(names)
+    }
+
     pub async fn scan_ble_once(&mut self) -> Result<Vec<MidiDeviceDescriptor>> {
         if self.bt_manager.is_none() {
             match BtleManager::new().await {
@@ -131,16 +399,49 @@ impl MidiDeviceManager {
         Ok(new_devices)
     }
 
+    /// Connects to `id`, borrowing the manager until the device answers.
+    /// When the manager sits behind a lock, take a [`DeviceConnector`] with
+    /// [`MidiDeviceManager::connector`] instead so the lock can be released.
     pub async fn connect(&self, id: &Uuid) -> Result<SharedMidiSink> {
+        self.connector(id)?.connect(DEFAULT_CONNECT_TIMEOUT).await
+    }
+
+    /// Captures what connecting to `id` needs, including the output filter
+    /// and monitor in effect now.
+    pub fn connector(&self, id: &Uuid) -> Result<DeviceConnector> {
         let descriptor = self
             .devices
             .get(id)
             .cloned()
-            .with_context(|| format!("unknown device id {id}"))?;
+            .ok_or_else(|| PlaybackError::DeviceUnavailable(format!("device {id}")))?;
+        Ok(DeviceConnector {
+            descriptor,
+            connections: self.connections.clone(),
+            null_sink: self.null_sink.clone(),
+            virtual_port: self.virtual_port.clone(),
+            filter: self.connection_filter(id),
+            monitor: self.monitor.clone(),
+            trace: self.trace.clone(),
+            timing: self.timing.clone(),
+            ble_packet_size: self.ble_packet_size,
+            ble_reliable_writes: self.reliable_ble_writes.contains(id),
+            usb_running_status: self.usb_running_status,
+        })
+    }
 
-        match descriptor.kind {
-            DeviceKind::Usb(device) => self.connect_usb(&descriptor.info, device).await,
-            DeviceKind::Ble(device) => self.connect_ble(&descriptor.info, device).await,
+    fn connection_filter(&self, id: &Uuid) -> OutputFilter {
+        let filter = OutputFilter {
+            quiet: self.quiet,
+            ..self
+                .output_filters
+                .get(id)
+                .copied()
+                .unwrap_or(self.default_output_filter)
+        };
+        if self.piano_mode {
+            filter.piano_mode()
+        } else {
+            filter
         }
     }
 
@@ -173,6 +474,7 @@ impl MidiDeviceManager {
             .await
             .context("failed to retrieve BLE adapters")?;
 
+        let adapters = self.scanned_adapters(adapters).await;
         if adapters.is_empty() {
             return Ok(descriptors);
         }
@@ -221,7 +523,139 @@ impl MidiDeviceManager {
         Ok(descriptors)
     }
 
-    async fn connect_usb(&self, _info: &MidiSinkInfo, device: UsbDevice) -> Result<SharedMidiSink> {
+    /// The chosen adapter out of `adapters`, or all of them when none is
+    /// chosen or it can't be found.
+    async fn scanned_adapters(&self, adapters: Vec<Adapter>) -> Vec<Adapter> {
+        let Some(chosen) = &self.ble_adapter else {
+            return adapters;
+        };
+        for adapter in &adapters {
+            if adapter_key(adapter).await == *chosen {
+                return vec![adapter.clone()];
+            }
+        }
+        log::warn!("Bluetooth adapter {chosen} not found; scanning all adapters");
+        adapters
+    }
+}
+
+/// A pending connection to one device, independent of the manager that
+/// created it.
+pub struct DeviceConnector {
+    descriptor: MidiDeviceDescriptor,
+    connections: ConnectionPool,
+    null_sink: Arc<NullSink>,
+    virtual_port: Option<SharedMidiSink>,
+    filter: OutputFilter,
+    monitor: Option<Arc<MidiMonitor>>,
+    trace: Option<Arc<SendTrace>>,
+    timing: Option<Arc<TimingCapture>>,
+    ble_packet_size: Option<usize>,
+    ble_reliable_writes: bool,
+    usb_running_status: bool,
+}
+
+impl DeviceConnector {
+    pub fn info(&self) -> &MidiSinkInfo {
+        &self.descriptor.info
+    }
+
+    /// Connects, giving up after `timeout`. An open connection to the device
+    /// is reused if it still works. Dropping the future abandons the attempt.
+    pub async fn connect(self, timeout: Duration) -> Result<SharedMidiSink> {
+        let sink = match self.pooled().await {
+            Some(sink) => sink,
+            None => {
+                let name = self.descriptor.info.name.clone();
+                let sink = time::timeout(timeout, self.connect_device())
+                    .await
+                    .map_err(|_| {
+                        anyhow!("no answer after {}s", timeout.as_secs())
+                            .context(PlaybackError::ConnectTimedOut(name))
+                    })??;
+                // The built-in outputs stay open however connections come
+                // and go.
+                if !matches!(
+                    self.descriptor.kind,
+                    DeviceKind::Null | DeviceKind::VirtualPort
+                ) {
+                    self.connections
+                        .lock()
+                        .expect("connection pool poisoned")
+                        .insert(self.descriptor.info.id, sink.clone());
+                }
+                sink
+            }
+        };
+        let sink = match &self.timing {
+            Some(timing) => Arc::new(TimingSink::new(
+                sink,
+                self.descriptor.kind.timing_label(),
+                timing.clone(),
+            )) as SharedMidiSink,
+            None => sink,
+        };
+        let sink = match &self.trace {
+            Some(trace) => Arc::new(TracedSink::new(
+                sink,
+                trace.clone(),
+                self.descriptor.info.id,
+            )) as SharedMidiSink,
+            None => sink,
+        };
+        // Checked after filtering and before tracing, so the trace shows
+        // exactly what went out.
+        let sink = Arc::new(ValidatingSink::new(sink)) as SharedMidiSink;
+        let sink = if self.filter.is_passthrough() {
+            sink
+        } else {
+            Arc::new(FilteredSink::new(sink, self.filter)) as SharedMidiSink
+        };
+        Ok(match &self.monitor {
+            Some(monitor) => Arc::new(MonitoredSink::new(sink, monitor.clone())) as SharedMidiSink,
+            None => sink,
+        })
+    }
+
+    /// The open connection to this device, if there is one and it is alive.
+    /// A dead one is dropped from the pool.
+    async fn pooled(&self) -> Option<SharedMidiSink> {
+        let id = self.descriptor.info.id;
+        let sink = self
+            .connections
+            .lock()
+            .expect("connection pool poisoned")
+            .get(&id)
+            .cloned()?;
+        if sink.is_alive().await {
+            return Some(sink);
+        }
+        log::info!("reconnecting to {}", self.descriptor.info.name);
+        let mut connections = self.connections.lock().expect("connection pool poisoned");
+        if connections
+            .get(&id)
+            .is_some_and(|pooled| Arc::ptr_eq(pooled, &sink))
+        {
+            connections.remove(&id);
+        }
+        None
+    }
+
+    async fn connect_device(&self) -> Result<SharedMidiSink> {
+        match self.descriptor.kind.clone() {
+            DeviceKind::Usb(device) => Self::connect_usb(device, self.usb_running_status).await,
+            DeviceKind::Ble(device) => {
+                Self::connect_ble(device, self.ble_packet_size, self.ble_reliable_writes).await
+            }
+            DeviceKind::Null => Ok(self.null_sink.clone() as SharedMidiSink),
+            DeviceKind::VirtualPort => self
+                .virtual_port
+                .clone()
+                .ok_or_else(|| PlaybackError::DeviceUnavailable(VIRTUAL_PORT_NAME.into()).into()),
+        }
+    }
+
+    async fn connect_usb(device: UsbDevice, running_status: bool) -> Result<SharedMidiSink> {
         let midi_output = MidiOutput::new(CLIENT_NAME)
             .context("failed to initialize MIDI output for connection")?;
 
@@ -229,36 +663,40 @@ impl MidiDeviceManager {
             .ports()
             .into_iter()
             .find(|port| port.id() == device.port_id)
-            .with_context(|| {
-                format!(
-                    "MIDI output port {} is no longer available",
-                    device.port_name
-                )
-            })?;
-
-        let connection = midi_output
-            .connect(&port, CLIENT_NAME)
-            .map_err(|err| anyhow!("failed to connect to MIDI output port: {}", err))?;
-
-        let sink = Arc::new(MidirSink {
-            connection: Mutex::new(connection),
-        });
+            .ok_or_else(|| PlaybackError::DeviceUnavailable(device.port_name.clone()))?;
+
+        let connection = midi_output.connect(&port, CLIENT_NAME).map_err(|err| {
+            anyhow!("failed to connect to MIDI output port: {}", err)
+                .context(PlaybackError::DeviceUnavailable(device.port_name.clone()))
+        })?;
+
+        let sink = Arc::new(MidirSink::spawn(
+            connection,
+            Some(device.port_id),
+            running_status,
+        )?);
 
         Ok(sink as SharedMidiSink)
     }
 
-    async fn connect_ble(&self, _info: &MidiSinkInfo, device: BleDevice) -> Result<SharedMidiSink> {
+    async fn connect_ble(
+        device: BleDevice,
+        packet_size: Option<usize>,
+        reliable: bool,
+    ) -> Result<SharedMidiSink> {
         let peripheral = device
             .adapter
             .peripheral(&device.peripheral_id)
             .await
-            .context("failed to retrieve BLE peripheral")?;
+            .context("failed to retrieve BLE peripheral")
+            .context(PlaybackError::DeviceUnavailable(device.name.clone()))?;
 
         if !peripheral.is_connected().await.unwrap_or(false) {
             peripheral
                 .connect()
                 .await
-                .context("failed to connect to BLE MIDI device")?;
+                .context("failed to connect to BLE MIDI device")
+                .context(PlaybackError::DeviceUnavailable(device.name.clone()))?;
         }
 
         peripheral
@@ -270,21 +708,183 @@ impl MidiDeviceManager {
             .characteristics()
             .into_iter()
             .find(|c| c.uuid == BLE_MIDI_CHARACTERISTIC_UUID)
-            .ok_or_else(|| anyhow!("BLE MIDI characteristic not found on {}", device.name))?;
+            .ok_or_else(|| PlaybackError::BleCharacteristicMissing(device.name.clone()))?;
+
+        let packet_size = match packet_size {
+            Some(size) => size,
+            None => negotiated_mtu(&peripheral)
+                .await
+                .map_or(MIN_BLE_PACKET_SIZE, |mtu| {
+                    usize::from(mtu).saturating_sub(ATT_WRITE_OVERHEAD)
+                }),
+        }
+        .clamp(MIN_BLE_PACKET_SIZE, MAX_BLE_PACKET_SIZE);
+        log::info!(
+            "writing {packet_size}-byte BLE MIDI packets to {}",
+            device.name
+        );
+
+        let properties = characteristic.properties;
+        let can_confirm = properties.contains(CharPropFlags::WRITE);
+        let with_response = first_ble_write_type(properties, reliable) == WriteType::WithResponse;
+        if with_response {
+            log::info!("writing to {} with response", device.name);
+        } else if reliable {
+            log::warn!(
+                "{} does not accept BLE writes with response; sending without",
+                device.name
+            );
+        }
 
         let sink = Arc::new(BleMidiSink {
             peripheral,
             characteristic,
-            write_type: WriteType::WithoutResponse,
+            packet_size,
+            with_response: AtomicBool::new(with_response),
+            can_confirm,
+            credits: WriteCredits::new(BLE_MAX_IN_FLIGHT, BLE_WRITE_INTERVAL),
             write_lock: Mutex::new(()),
+            failed: AtomicBool::new(false),
+            name: device.name,
+            stats: SendStats::default(),
         });
 
         Ok(sink as SharedMidiSink)
     }
 }
 
+#[cfg(unix)]
+fn create_virtual_port() -> Result<MidiOutputConnection> {
+    use midir::os::unix::VirtualOutput;
+
+    let midi_output = MidiOutput::new(CLIENT_NAME)
+        .context("failed to initialize MIDI output for virtual port")?;
+    midi_output
+        .create_virtual(VIRTUAL_PORT_NAME)
+        .map_err(|err| anyhow!("failed to create virtual MIDI port: {err}"))
+}
+
+#[cfg(not(unix))]
+fn create_virtual_port() -> Result<MidiOutputConnection> {
+    Err(anyhow!(
+        "virtual MIDI ports are not supported on this platform"
+    ))
+}
+
+/// Queues batches for a thread that owns the port, so senders never wait
+/// on each other for a lock and batches queued together go out together.
 struct MidirSink {
-    connection: Mutex<MidiOutputConnection>,
+    jobs: mpsc::Sender<SendJob>,
+    /// `None` for the app's own virtual port, which only goes away when it
+    /// is closed.
+    port_id: Option<String>,
+    /// Set once a send fails, so the connection is not reused.
+    failed: Arc<AtomicBool>,
+    stats: SendStats,
+}
+
+/// A batch for [`MidirSender`], and where to report how sending it went.
+struct SendJob {
+    messages: Vec<Vec<u8>>,
+    done: oneshot::Sender<Result<(), String>>,
+}
+
+/// Owns a port's connection on its own thread until every [`MidirSink`]
+/// queueing to it is gone, which closes the port.
+struct MidirSender {
+    connection: MidiOutputConnection,
+    jobs: mpsc::Receiver<SendJob>,
+    failed: Arc<AtomicBool>,
+    /// Set when repeated status bytes are left out.
+    running_status: Option<RunningStatus>,
+}
+
+impl MidirSink {
+    fn spawn(
+        connection: MidiOutputConnection,
+        port_id: Option<String>,
+        running_status: bool,
+    ) -> Result<Self> {
+        let (jobs, queue) = mpsc::channel();
+        let failed = Arc::new(AtomicBool::new(false));
+        let sender = MidirSender {
+            connection,
+            jobs: queue,
+            failed: Arc::clone(&failed),
+            running_status: running_status.then(RunningStatus::default),
+        };
+        thread::Builder::new()
+            .name("midi-output".into())
+            .spawn(move || sender.run())
+            .context("failed to start MIDI output thread")?;
+        Ok(Self {
+            jobs,
+            port_id,
+            failed,
+            stats: SendStats::default(),
+        })
+    }
+
+    /// Queues `messages` and waits for the output thread to send them.
+    async fn send_queued(&self, messages: &[Vec<u8>]) -> Result<()> {
+        let (done, outcome) = oneshot::channel();
+        let job = SendJob {
+            messages: messages.to_vec(),
+            done,
+        };
+        self.jobs
+            .send(job)
+            .map_err(|_| self.send_failed("the output thread stopped"))?;
+        outcome
+            .await
+            .map_err(|_| self.send_failed("the output thread stopped"))?
+            .map_err(|err| self.send_failed(err))
+    }
+
+    fn send_failed(&self, err: impl std::fmt::Display) -> anyhow::Error {
+        self.failed.store(true, Ordering::Relaxed);
+        anyhow!("failed to send MIDI message: {err}").context(PlaybackError::SendFailed)
+    }
+}
+
+impl MidirSender {
+    fn run(mut self) {
+        while let Ok(first) = self.jobs.recv() {
+            // Whatever queued up meanwhile was due by now as well.
+            let mut jobs = vec![first];
+            jobs.extend(self.jobs.try_iter());
+            let outcome = self.send(&jobs).map_err(|err| {
+                self.failed.store(true, Ordering::Relaxed);
+                if let Some(running_status) = &mut self.running_status {
+                    running_status.reset();
+                }
+                err.to_string()
+            });
+            for job in jobs {
+                let _ = job.done.send(outcome.clone());
+            }
+        }
+    }
+
+    fn send(&mut self, jobs: &[SendJob]) -> Result<(), midir::SendError> {
+        let messages = jobs.iter().flat_map(|job| &job.messages);
+        let bytes: Vec<&[u8]> = match &mut self.running_status {
+            Some(running_status) => messages
+                .map(|message| running_status.compress(message))
+                .collect(),
+            None => messages.map(Vec::as_slice).collect(),
+        };
+        if COALESCE_USB_SENDS {
+            for chunk in coalesce_midi_messages(bytes) {
+                self.connection.send(&chunk)?;
+            }
+        } else {
+            for message in bytes {
+                self.connection.send(message)?;
+            }
+        }
+        Ok(())
+    }
 }
 
 #[async_trait::async_trait]
@@ -294,24 +894,153 @@ impl MidiSink for MidirSink {
     }
 
     async fn send_batch(&self, messages: &[Vec<u8>]) -> Result<()> {
-        let mut connection = self.connection.lock().await;
-        for message in messages {
-            connection
-                .send(message)
-                .map_err(|err| anyhow!("failed to send MIDI message: {err}"))?;
+        if messages.is_empty() {
+            return Ok(());
+        }
+        let started = Instant::now();
+        let result = self.send_queued(messages).await;
+        self.stats.record(messages, started.elapsed(), &result);
+        result
+    }
+
+    fn stats(&self) -> Option<SinkStats> {
+        Some(self.stats.snapshot())
+    }
+
+    async fn is_alive(&self) -> bool {
+        if self.failed.load(Ordering::Relaxed) {
+            return false;
+        }
+        let Some(port_id) = &self.port_id else {
+            return true;
+        };
+        // midir cannot tell whether an open port went away, so check that it
+        // is still listed.
+        MidiOutput::new(CLIENT_NAME)
+            .map(|output| output.ports().iter().any(|port| port.id() == *port_id))
+            .unwrap_or(false)
+    }
+}
+
+/// Caps how many writes may be outstanding at once, so a dense passage
+/// queues here rather than overflowing the peripheral's buffers. A credit
+/// comes back when it is dropped, which for a write with response is once
+/// the peripheral confirms it, or through [`WriteCredit::unconfirmed`].
+pub struct WriteCredits {
+    credits: Arc<Semaphore>,
+    hold: Duration,
+}
+
+/// One credit taken from [`WriteCredits`].
+pub struct WriteCredit {
+    permit: OwnedSemaphorePermit,
+    hold: Duration,
+}
+
+impl WriteCredits {
+    pub fn new(limit: usize, hold: Duration) -> Self {
+        Self {
+            credits: Arc::new(Semaphore::new(limit.max(1))),
+            hold,
+        }
+    }
+
+    /// Waits until fewer than the limit of writes are in flight.
+    pub async fn acquire(&self) -> WriteCredit {
+        let permit = Arc::clone(&self.credits)
+            .acquire_owned()
+            .await
+            .expect("write credits are never closed");
+        WriteCredit {
+            permit,
+            hold: self.hold,
         }
-        Ok(())
+    }
+
+    pub fn available(&self) -> usize {
+        self.credits.available_permits()
+    }
+}
+
+impl WriteCredit {
+    /// For a write nothing will confirm: it stays in flight for the hold
+    /// time.
+    pub fn unconfirmed(self) {
+        let Self { permit, hold } = self;
+        tokio::spawn(async move {
+            time::sleep(hold).await;
+            drop(permit);
+        });
     }
 }
 
 struct BleMidiSink {
     peripheral: Peripheral,
     characteristic: Characteristic,
-    write_type: WriteType,
+    packet_size: usize,
+    /// Set when writes wait for the peripheral to confirm them, either
+    /// because it only takes those or since a write without response failed.
+    with_response: AtomicBool,
+    /// Whether the characteristic takes writes with response at all.
+    can_confirm: bool,
+    credits: WriteCredits,
     write_lock: Mutex<()>,
+    /// Set once a write fails, so the connection is not reused.
+    failed: AtomicBool,
+    name: String,
+    stats: SendStats,
 }
 
-const BLE_MTU: usize = 500;
+impl BleMidiSink {
+    async fn write(&self, packet: &[u8]) -> Result<()> {
+        let credit = self.credits.acquire().await;
+        if self.with_response.load(Ordering::Relaxed) {
+            return self.write_confirmed(packet, credit).await;
+        }
+        match self
+            .peripheral
+            .write(&self.characteristic, packet, WriteType::WithoutResponse)
+            .await
+        {
+            Ok(()) => {
+                credit.unconfirmed();
+                Ok(())
+            }
+            Err(err) if self.can_confirm => {
+                log::warn!(
+                    "BLE write without response to {} failed ({err}); \
+                     waiting for confirmation from now on",
+                    self.name
+                );
+                self.with_response.store(true, Ordering::Relaxed);
+                self.write_confirmed(packet, credit).await
+            }
+            Err(err) => Err(self.write_failed(err)),
+        }
+    }
+
+    /// Holds `_credit` until the peripheral confirms the write.
+    async fn write_confirmed(&self, packet: &[u8], _credit: WriteCredit) -> Result<()> {
+        self.peripheral
+            .write(&self.characteristic, packet, WriteType::WithResponse)
+            .await
+            .map_err(|err| self.write_failed(err))
+    }
+
+    async fn write_packets(&self, messages: &[Vec<u8>]) -> Result<()> {
+        let packets = pack_ble_midi_packets(messages, self.packet_size);
+        let _guard = self.write_lock.lock().await;
+        for packet in packets {
+            self.write(&packet).await?;
+        }
+        Ok(())
+    }
+
+    fn write_failed(&self, err: btleplug::Error) -> anyhow::Error {
+        self.failed.store(true, Ordering::Relaxed);
+        anyhow!("failed to send BLE MIDI data: {err}").context(PlaybackError::SendFailed)
+    }
+}
 
 #[async_trait::async_trait]
 impl MidiSink for BleMidiSink {
@@ -325,83 +1054,188 @@ impl MidiSink for BleMidiSink {
             return Ok(());
         }
 
-        let packets = pack_ble_midi_packets(messages);
-        let _guard = self.write_lock.lock().await;
-        for packet in packets {
-            self.peripheral
-                .write(&self.characteristic, &packet, self.write_type)
-                .await
-                .map_err(|err| anyhow!("failed to send BLE MIDI data: {err}"))?;
+        let started = Instant::now();
+        let result = self.write_packets(messages).await;
+        self.stats.record(messages, started.elapsed(), &result);
+        result
+    }
+
+    fn stats(&self) -> Option<SinkStats> {
+        Some(self.stats.snapshot())
+    }
+
+    async fn is_alive(&self) -> bool {
+        !self.failed.load(Ordering::Relaxed)
+            && self.peripheral.is_connected().await.unwrap_or(false)
+    }
+
+    async fn disconnect(&self) -> Result<()> {
+        self.peripheral
+            .disconnect()
+            .await
+            .context("failed to disconnect BLE MIDI device")
+    }
+}
+
+/// Joins consecutive MIDI messages into one buffer per send, for MIDI layers
+/// that take a byte stream. SysEx goes alone, as some drivers only take it
+/// that way.
+pub fn coalesce_midi_messages<'a>(messages: impl IntoIterator<Item = &'a [u8]>) -> Vec<Vec<u8>> {
+    let mut chunks = Vec::new();
+    let mut chunk = Vec::new();
+    for message in messages {
+        if message.first() == Some(&0xF0) {
+            if !chunk.is_empty() {
+                chunks.push(std::mem::take(&mut chunk));
+            }
+            chunks.push(message.to_vec());
+        } else {
+            chunk.extend_from_slice(message);
         }
-        Ok(())
     }
+    if !chunk.is_empty() {
+        chunks.push(chunk);
+    }
+    chunks
 }
 
-fn pack_ble_midi_packets(messages: &[Vec<u8>]) -> Vec<Vec<u8>> {
+/// How writes to a BLE-MIDI characteristic with `properties` start out:
+/// with response when that is all it accepts, or when `reliable` asks for it
+/// and it accepts them.
+pub fn first_ble_write_type(properties: CharPropFlags, reliable: bool) -> WriteType {
+    let can_confirm = properties.contains(CharPropFlags::WRITE);
+    if can_confirm && (reliable || !properties.contains(CharPropFlags::WRITE_WITHOUT_RESPONSE)) {
+        WriteType::WithResponse
+    } else {
+        WriteType::WithoutResponse
+    }
+}
+
+/// Splits MIDI messages into BLE-MIDI packets of at most `packet_size`
+/// bytes. Every packet opens with a header byte and every message with a
+/// timestamp byte. A SysEx message too long for one packet carries on in
+/// the next ones straight after their header, and its closing 0xF7 gets a
+/// timestamp byte of its own, as the BLE-MIDI specification requires.
+pub fn pack_ble_midi_packets(messages: &[Vec<u8>], packet_size: usize) -> Vec<Vec<u8>> {
     const HEADER: u8 = 0x80;
-    if messages.is_empty() {
-        return Vec::new();
+    const TIMESTAMP: u8 = 0x80;
+    const SYSEX_START: u8 = 0xF0;
+    const SYSEX_END: u8 = 0xF7;
+
+    fn next_packet(packet: &mut Vec<u8>, packets: &mut Vec<Vec<u8>>) {
+        packets.push(std::mem::replace(packet, vec![HEADER]));
     }
 
+    let packet_size = packet_size.clamp(MIN_BLE_PACKET_SIZE, MAX_BLE_PACKET_SIZE);
     let mut packets = Vec::new();
-    let mut packet = Vec::with_capacity(BLE_MTU);
-    packet.push(HEADER);
-    let mut remaining = BLE_MTU - 1;
+    let mut packet = vec![HEADER];
 
-    for message in messages {
-        if message.is_empty() {
+    for message in messages.iter().filter(|message| !message.is_empty()) {
+        if message[0] != SYSEX_START {
+            if packet.len() > 1 && packet.len() + 1 + message.len() > packet_size {
+                next_packet(&mut packet, &mut packets);
+            }
+            packet.push(TIMESTAMP);
+            packet.extend_from_slice(message);
             continue;
         }
 
-        let mut offset = 0;
-        while offset < message.len() {
-            if remaining <= 1 {
-                let mut finished = Vec::new();
-                std::mem::swap(&mut finished, &mut packet);
-                packets.push(finished);
-                packet.push(HEADER);
-                remaining = BLE_MTU - 1;
-                continue;
+        let (body, terminated) = match message.split_last() {
+            Some((&SYSEX_END, body)) => (body, true),
+            _ => (message.as_slice(), false),
+        };
+        if packet.len() + 2 > packet_size {
+            next_packet(&mut packet, &mut packets);
+        }
+        packet.push(TIMESTAMP);
+        for &byte in body {
+            if packet.len() == packet_size {
+                next_packet(&mut packet, &mut packets);
             }
-
-            let available = remaining - 1;
-            let chunk_len = (message.len() - offset).min(available);
-            packet.push(HEADER);
-            packet.extend_from_slice(&message[offset..offset + chunk_len]);
-            offset += chunk_len;
-            remaining -= 1 + chunk_len;
-
-            if offset < message.len() {
-                let mut finished = Vec::new();
-                std::mem::swap(&mut finished, &mut packet);
-                packets.push(finished);
-                packet.push(HEADER);
-                remaining = BLE_MTU - 1;
-            } else if remaining <= 1 {
-                let mut finished = Vec::new();
-                std::mem::swap(&mut finished, &mut packet);
-                packets.push(finished);
-                packet.push(HEADER);
-                remaining = BLE_MTU - 1;
+            packet.push(byte);
+        }
+        if terminated {
+            if packet.len() + 2 > packet_size {
+                next_packet(&mut packet, &mut packets);
             }
+            packet.extend_from_slice(&[TIMESTAMP, SYSEX_END]);
         }
     }
 
     if packet.len() > 1 {
         packets.push(packet);
     }
-
     packets
 }
 
+/// The ATT MTU negotiated with `peripheral`, where the platform reports it.
+async fn negotiated_mtu(peripheral: &Peripheral) -> Option<u16> {
+    #[cfg(target_os = "linux")]
+    match bluez::characteristic_mtu(&peripheral.address().to_string()).await {
+        Ok(mtu) => mtu,
+        Err(err) => {
+            log::debug!("could not read the negotiated BLE MTU: {err}");
+            None
+        }
+    }
+    #[cfg(not(target_os = "linux"))]
+    {
+        let _ = peripheral;
+        None
+    }
+}
+
+/// Reads what btleplug does not expose from BlueZ over the system bus.
+#[cfg(target_os = "linux")]
+mod bluez {
+    use zbus::Connection;
+    use zbus::fdo::ObjectManagerProxy;
+
+    use super::BLE_MIDI_CHARACTERISTIC_UUID;
+
+    const CHARACTERISTIC_INTERFACE: &str = "org.bluez.GattCharacteristic1";
+
+    /// The MTU BlueZ lists for the BLE MIDI characteristic of the device
+    /// with Bluetooth `address`, e.g. "AA:BB:CC:DD:EE:FF".
+    pub async fn characteristic_mtu(address: &str) -> zbus::Result<Option<u16>> {
+        let connection = Connection::system().await?;
+        let objects = ObjectManagerProxy::builder(&connection)
+            .destination("org.bluez")?
+            .path("/")?
+            .build()
+            .await?
+            .get_managed_objects()
+            .await?;
+        let device = format!("/dev_{}/", address.replace(':', "_"));
+        let uuid = BLE_MIDI_CHARACTERISTIC_UUID.to_string();
+        for (path, interfaces) in &objects {
+            if !path.as_str().contains(&device) {
+                continue;
+            }
+            let Some((_, properties)) = interfaces
+                .iter()
+                .find(|(name, _)| name.as_str() == CHARACTERISTIC_INTERFACE)
+            else {
+                continue;
+            };
+            let is_midi = properties
+                .get("UUID")
+                .and_then(|value| <&str>::try_from(value).ok())
+                .is_some_and(|value| value.eq_ignore_ascii_case(&uuid));
+            if is_midi {
+                return Ok(properties
+                    .get("MTU")
+                    .and_then(|value| u16::try_from(value).ok()));
+            }
+        }
+        Ok(None)
+    }
+}
+
 async fn is_midi_candidate(peripheral: &Peripheral) -> bool {
     match peripheral.properties().await {
         Ok(Some(properties)) => {
-            if properties
-                .services
-                .iter()
-                .any(|uuid| *uuid == BLE_MIDI_SERVICE_UUID)
-            {
+            if properties.services.contains(&BLE_MIDI_SERVICE_UUID) {
                 return true;
             }
             if let Some(name) = properties.local_name {
@@ -426,10 +1260,10 @@ async fn adapter_key(adapter: &Adapter) -> String {
 }
 
 async fn peripheral_name(peripheral: &Peripheral) -> String {
-    if let Ok(Some(properties)) = peripheral.properties().await {
-        if let Some(name) = properties.local_name {
-            return name;
-        }
+    if let Ok(Some(properties)) = peripheral.properties().await
+        && let Some(name) = properties.local_name
+    {
+        return name;
     }
     format!("BLE Device {}", peripheral.id())
 }
@@ -0,0 +1,139 @@
+use std::collections::{HashMap, HashSet};
+use std::time::Duration;
+
+use serde::{Deserialize, Serialize};
+use uuid::Uuid;
+
+use crate::midi::filter::OutputFilter;
+use crate::midi::sequence::PercussionHandling;
+
+/// Named settings for a kind of instrument, applied whenever a device it is
+/// assigned to is connected.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct DeviceProfile {
+    pub id: Uuid,
+    pub name: String,
+    #[serde(default)]
+    pub filter: OutputFilter,
+    /// How far the instrument's sound lags behind the messages it is sent,
+    /// so on-screen positions can wait for it.
+    #[serde(default)]
+    pub latency_ms: u16,
+    /// What happens to drum tracks on this instrument, unless a song says
+    /// otherwise.
+    #[serde(default)]
+    pub percussion: PercussionHandling,
+    /// Waits for a Bluetooth instrument to confirm every write, for ones that
+    /// drop notes sent without response even though they accept them.
+    #[serde(default)]
+    pub reliable_writes: bool,
+}
+
+impl DeviceProfile {
+    pub const MAX_LATENCY_MS: u16 = 500;
+
+    pub fn new(name: impl Into<String>, filter: OutputFilter) -> Self {
+        Self {
+            id: Uuid::new_v4(),
+            name: name.into(),
+            filter,
+            latency_ms: 0,
+            percussion: PercussionHandling::Keep,
+            reliable_writes: false,
+        }
+    }
+
+    pub fn latency(&self) -> Duration {
+        Duration::from_millis(self.latency_ms.into())
+    }
+}
+
+/// Every profile, and which device uses which.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct DeviceProfiles {
+    profiles: Vec<DeviceProfile>,
+    /// Profile ids by device id.
+    assignments: HashMap<Uuid, Uuid>,
+}
+
+impl DeviceProfiles {
+    pub fn iter(&self) -> impl Iterator<Item = &DeviceProfile> {
+        self.profiles.iter()
+    }
+
+    pub fn get(&self, id: Uuid) -> Option<&DeviceProfile> {
+        self.profiles.iter().find(|profile| profile.id == id)
+    }
+
+    pub fn get_mut(&mut self, id: Uuid) -> Option<&mut DeviceProfile> {
+        self.profiles.iter_mut().find(|profile| profile.id == id)
+    }
+
+    /// Adds `profile` and returns its id.
+    pub fn add(&mut self, profile: DeviceProfile) -> Uuid {
+        let id = profile.id;
+        self.profiles.push(profile);
+        id
+    }
+
+    /// Deletes a profile. Devices that used it go back to the default rules.
+    pub fn remove(&mut self, id: Uuid) -> Option<DeviceProfile> {
+        let index = self.profiles.iter().position(|profile| profile.id == id)?;
+        self.assignments.retain(|_, profile| *profile != id);
+        Some(self.profiles.remove(index))
+    }
+
+    /// Uses `profile` for `device`, or the default rules for `None`.
+    /// Unknown profile ids are ignored.
+    pub fn assign(&mut self, device: Uuid, profile: Option<Uuid>) {
+        match profile {
+            Some(profile) if self.get(profile).is_some() => {
+                self.assignments.insert(device, profile);
+            }
+            Some(_) => {}
+            None => {
+                self.assignments.remove(&device);
+            }
+        }
+    }
+
+    /// Adds the profiles of `other` not already here, and its assignments
+    /// for devices that have none here.
+    pub fn merge(&mut self, other: DeviceProfiles) {
+        for profile in other.profiles {
+            if self.get(profile.id).is_none() {
+                self.profiles.push(profile);
+            }
+        }
+        for (device, profile) in other.assignments {
+            if !self.assignments.contains_key(&device) {
+                self.assign(device, Some(profile));
+            }
+        }
+    }
+
+    pub fn profile_for(&self, device: Uuid) -> Option<&DeviceProfile> {
+        self.get(*self.assignments.get(&device)?)
+    }
+
+    /// The output filter of every device with a profile.
+    pub fn filters(&self) -> HashMap<Uuid, OutputFilter> {
+        self.assignments
+            .keys()
+            .filter_map(|device| Some((*device, self.profile_for(*device)?.filter)))
+            .collect()
+    }
+
+    /// The devices whose profile asks for reliable writes.
+    pub fn reliable_write_devices(&self) -> HashSet<Uuid> {
+        self.assignments
+            .keys()
+            .filter(|device| {
+                self.profile_for(**device)
+                    .is_some_and(|profile| profile.reliable_writes)
+            })
+            .copied()
+            .collect()
+    }
+}
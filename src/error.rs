@@ -0,0 +1,28 @@
+use thiserror::Error;
+
+/// Failures the app explains to the user rather than just printing. Library
+/// code attaches one of these to its `anyhow` chain, usually as context, so
+/// the full chain stays available for logs while [`PlaybackError::find`]
+/// recovers the kind.
+#[derive(Debug, Clone, PartialEq, Eq, Error)]
+pub enum PlaybackError {
+    #[error("{0} is not available")]
+    DeviceUnavailable(String),
+    #[error("{0} did not answer in time")]
+    ConnectTimedOut(String),
+    #[error("{0} does not offer the Bluetooth MIDI service")]
+    BleCharacteristicMissing(String),
+    #[error("failed to parse MIDI {0}")]
+    Parse(String),
+    #[error("unsupported MIDI file: {0}")]
+    UnsupportedFormat(&'static str),
+    #[error("failed to send MIDI data to the device")]
+    SendFailed,
+}
+
+impl PlaybackError {
+    /// The kind attached anywhere in `err`'s chain, if any.
+    pub fn find(err: &anyhow::Error) -> Option<&PlaybackError> {
+        err.downcast_ref()
+    }
+}
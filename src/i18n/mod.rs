@@ -0,0 +1,88 @@
+//! UI text in the user's language. The English text doubles as the key, so
+//! a string missing from a catalog simply shows in English. Placeholders are
+//! written `{name}` and filled by the [`t!`] macro.
+
+use std::collections::HashMap;
+use std::fmt;
+use std::sync::atomic::{AtomicU8, Ordering};
+
+use once_cell::sync::Lazy;
+use serde::{Deserialize, Serialize};
+
+mod zh;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+pub enum UiLanguage {
+    #[default]
+    English,
+    Chinese,
+}
+
+impl UiLanguage {
+    pub const ALL: [UiLanguage; 2] = [UiLanguage::English, UiLanguage::Chinese];
+}
+
+impl fmt::Display for UiLanguage {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        // Each language is listed under its own name.
+        let label = match self {
+            UiLanguage::English => "English",
+            UiLanguage::Chinese => "中文",
+        };
+        write!(f, "{label}")
+    }
+}
+
+static CURRENT: AtomicU8 = AtomicU8::new(UiLanguage::English as u8);
+
+static CHINESE: Lazy<HashMap<&'static str, &'static str>> =
+    Lazy::new(|| zh::STRINGS.iter().copied().collect());
+
+pub fn set_language(language: UiLanguage) {
+    CURRENT.store(language as u8, Ordering::Relaxed);
+}
+
+fn language() -> UiLanguage {
+    match CURRENT.load(Ordering::Relaxed) {
+        value if value == UiLanguage::Chinese as u8 => UiLanguage::Chinese,
+        _ => UiLanguage::English,
+    }
+}
+
+/// `key` in the current language, or `key` itself when it has no
+/// translation.
+pub fn tr(key: &'static str) -> &'static str {
+    match language() {
+        UiLanguage::English => key,
+        UiLanguage::Chinese => CHINESE.get(key).copied().unwrap_or_else(|| {
+            log::debug!("missing Chinese translation for {key:?}");
+            key
+        }),
+    }
+}
+
+/// Replaces each `{name}` in `template` with its value. Unknown
+/// placeholders are left as they are.
+pub fn fill(template: &str, args: &[(&str, &dyn fmt::Display)]) -> String {
+    let mut text = template.to_owned();
+    for (name, value) in args {
+        text = text.replace(&format!("{{{name}}}"), &value.to_string());
+    }
+    text
+}
+
+/// `t!("Open")` translates a string; `t!("Found {count} file(s)", count = n)`
+/// also fills in its placeholders and returns a `String`.
+macro_rules! t {
+    ($key:literal) => {
+        $crate::i18n::tr($key)
+    };
+    ($key:literal, $($name:ident = $value:expr),+ $(,)?) => {
+        $crate::i18n::fill(
+            $crate::i18n::tr($key),
+            &[$((stringify!($name), &$value as &dyn std::fmt::Display)),+],
+        )
+    };
+}
+
+pub(crate) use t;
@@ -0,0 +1,1069 @@
+//! Chinese (Simplified) UI text, keyed by the English original.
+
+pub const STRINGS: &[(&str, &str)] = &[
+    ("Debug", "调试"),
+    ("Pass through", "直通"),
+    ("Drop", "丢弃"),
+    ("Clamp", "限制"),
+    ("Song's own instruments", "使用乐曲自带音色"),
+    ("Channel {number}", "通道 {number}"),
+    ("All channels", "所有通道"),
+    ("All messages", "所有消息"),
+    ("Language and theme", "语言和主题"),
+    ("Your music", "你的音乐"),
+    ("Connect your piano", "连接钢琴"),
+    ("Test the connection", "测试连接"),
+    ("Bring your settings", "导入设置"),
+    (
+        "Close other apps using the piano: Windows MIDI ports can only be opened by one program at a time.",
+        "关闭其他正在使用钢琴的应用：Windows 的 MIDI 端口同一时间只能被一个程序打开。",
+    ),
+    (
+        "Install the USB-MIDI driver from your piano's manufacturer if the device is not listed.",
+        "如果设备没有出现在列表中，请安装钢琴厂商提供的 USB-MIDI 驱动。",
+    ),
+    (
+        "Bluetooth pianos must be paired in Settings > Bluetooth & devices first.",
+        "蓝牙钢琴需要先在“设置 > 蓝牙和其他设备”中配对。",
+    ),
+    (
+        "Open Audio MIDI Setup > MIDI Studio to check that macOS sees the piano.",
+        "打开“音频 MIDI 设置 > MIDI 工作室”，确认 macOS 能识别钢琴。",
+    ),
+    (
+        "Bluetooth pianos are connected from the Bluetooth button in MIDI Studio.",
+        "蓝牙钢琴需通过 MIDI 工作室中的蓝牙按钮连接。",
+    ),
+    (
+        "Allow Bluetooth access for this app in System Settings > Privacy & Security.",
+        "在“系统设置 > 隐私与安全性”中允许本应用使用蓝牙。",
+    ),
+    (
+        "Check that the piano shows up in `aconnect -l`; your user may need to be in the audio group.",
+        "确认钢琴出现在 `aconnect -l` 的输出中；你的用户可能需要加入 audio 组。",
+    ),
+    (
+        "Bluetooth pianos need BlueZ running and the piano in pairing mode.",
+        "蓝牙钢琴需要 BlueZ 正在运行，并让钢琴处于配对模式。",
+    ),
+    (
+        "Unplug and reconnect USB cables, then press Refresh.",
+        "拔下并重新插上 USB 线，然后点击“刷新”。",
+    ),
+    ("Off", "关闭"),
+    ("Notify me", "提醒我"),
+    ("Fast-forward", "快进"),
+    ("Hands: off", "分手：关闭"),
+    ("Hands: by split point", "分手：按分割点"),
+    ("Hands: by track", "分手：按音轨"),
+    (" or ", " 或 "),
+    ("Library", "曲库"),
+    (
+        "Failed to load message replay: {err}",
+        "加载消息回放失败：{err}",
+    ),
+    ("Library loaded", "曲库已加载"),
+    (
+        "Failed to load MIDI library: {err}",
+        "加载 MIDI 曲库失败：{err}",
+    ),
+    ("Assets are up to date", "资源已是最新"),
+    (
+        "Assets rescanned: {added} added, {removed} removed",
+        "资源已重新扫描：新增 {added} 个，移除 {removed} 个",
+    ),
+    ("Failed to rescan assets: {err}", "重新扫描资源失败：{err}"),
+    ("Remote catalog removed", "已移除远程曲目表"),
+    (
+        "Remote catalog lists {count} song(s)",
+        "远程曲目表共有 {count} 首乐曲",
+    ),
+    (
+        "Failed to load remote catalog: {err}",
+        "加载远程曲目表失败：{err}",
+    ),
+    ("Download failed: {err}", "下载失败：{err}"),
+    (
+        "Removed {removed} cached file(s)",
+        "已删除 {removed} 个缓存文件",
+    ),
+    ("Failed to clear cache: {err}", "清除缓存失败：{err}"),
+    ("Devices updated", "设备列表已更新"),
+    ("Failed to refresh devices: {err}", "刷新设备失败：{err}"),
+    ("New BLE devices: {names}", "新的蓝牙设备：{names}"),
+    ("BLE scan failed: {err}", "蓝牙扫描失败：{err}"),
+    ("Preferences loaded", "偏好设置已加载"),
+    (
+        "Failed to load preferences: {err}",
+        "加载偏好设置失败：{err}",
+    ),
+    ("Preferences saved", "偏好设置已保存"),
+    (
+        "Failed to save preferences: {err}",
+        "保存偏好设置失败：{err}",
+    ),
+    (
+        "Failed to load practice statistics: {err}",
+        "加载练习统计失败：{err}",
+    ),
+    (
+        "Failed to save practice statistics: {err}",
+        "保存练习统计失败：{err}",
+    ),
+    (
+        "Failed to update library tree: {error}",
+        "更新曲库目录失败：{error}",
+    ),
+    ("Rating updated", "评分已更新"),
+    ("Added to favorites", "已加入收藏"),
+    ("Removed from favorites", "已取消收藏"),
+    ("Selected track is not available", "所选曲目不可用"),
+    ("Track added to draft playlist", "曲目已加入播放列表草稿"),
+    (
+        "Track removed from draft playlist",
+        "曲目已从播放列表草稿中移除",
+    ),
+    ("Playlist draft cleared", "播放列表草稿已清空"),
+    (
+        "Add at least one track before saving a playlist",
+        "保存播放列表前请至少添加一首曲目",
+    ),
+    ("Playlist {number}", "播放列表 {number}"),
+    ("Playlist '{name}' updated", "播放列表“{name}”已更新"),
+    ("Playlist '{name}' created", "播放列表“{name}”已创建"),
+    ("Playlist deleted", "播放列表已删除"),
+    ("Loaded playlist into draft", "已将播放列表载入草稿"),
+    ("Created '{name}'", "已创建“{name}”"),
+    ("Names cannot be empty", "名称不能为空"),
+    (
+        "Folder removed; its playlists are now unfiled",
+        "文件夹已删除，其中的播放列表已移出文件夹",
+    ),
+    ("Random 50", "随机 50 首"),
+    ("Generated random playlist draft", "已生成随机播放列表草稿"),
+    (
+        "Select one or more tags to build a smart playlist",
+        "请选择一个或多个标签来创建智能播放列表",
+    ),
+    (
+        "Smart playlist '{name}' created",
+        "智能播放列表“{name}”已创建",
+    ),
+    ("Select a MIDI file to play", "请选择要播放的 MIDI 文件"),
+    ("Failed to start playback", "开始播放失败"),
+    ("Failed to prepare playback", "准备播放失败"),
+    ("Select a MIDI device first", "请先选择 MIDI 设备"),
+    ("Key matching failed: {err}", "调性匹配失败：{err}"),
+    (
+        "Failed to read song information: {err}",
+        "读取乐曲信息失败：{err}",
+    ),
+    ("All notes off sent", "已发送全部音符关闭"),
+    ("Panic failed: {err}", "紧急静音失败：{err}"),
+    ("MIDI Files", "MIDI 文件"),
+    ("Added {name}", "已添加 {name}"),
+    (
+        "Failed to add MIDI file: {err}",
+        "添加 MIDI 文件失败：{err}",
+    ),
+    ("Imported {done} of {total}", "已导入 {done}/{total}"),
+    (
+        "Import cancelled after {done} file(s)",
+        "导入已取消，已完成 {done} 个文件",
+    ),
+    ("Imported {done} file(s)", "已导入 {done} 个文件"),
+    (
+        "; skipped {count} unreadable file(s)",
+        "；跳过 {count} 个无法读取的文件",
+    ),
+    ("Folder import failed: {err}", "文件夹导入失败：{err}"),
+    ("Choose inbox folder", "选择收件文件夹"),
+    ("Watch folder updated", "监视文件夹已更新"),
+    ("Choose an inbox folder first", "请先选择收件文件夹"),
+    (
+        "Choose library folder for imported files",
+        "选择导入文件存放的曲库文件夹",
+    ),
+    ("Import destination updated", "导入位置已更新"),
+    ("Watch folder disabled", "已停用监视文件夹"),
+    (
+        "Inbox: imported {count} file(s)",
+        "收件文件夹：已导入 {count} 个文件",
+    ),
+    (
+        ", {count} rejected as invalid MIDI",
+        "，{count} 个因不是有效 MIDI 被拒绝",
+    ),
+    ("Inbox import failed: {err}", "收件文件夹导入失败：{err}"),
+    (
+        "Failed to index imported library: {err}",
+        "索引导入的曲库失败：{err}",
+    ),
+    ("Choose your music folder", "选择你的音乐文件夹"),
+    ("Scanning {folder}", "正在扫描 {folder}"),
+    (
+        "Failed to scan music folder: {err}",
+        "扫描音乐文件夹失败：{err}",
+    ),
+    (
+        "Setup complete. Pick a song and press Play.",
+        "设置完成。选一首曲子，然后点击播放。",
+    ),
+    ("Test tone failed: {err}", "测试音发送失败：{err}"),
+    ("Import preferences", "导入偏好设置"),
+    ("Preferences imported", "偏好设置已导入"),
+    (
+        "Failed to import preferences: {err}",
+        "导入偏好设置失败：{err}",
+    ),
+    ("Soundfont updated", "音色库已更新"),
+    ("Exported {output}", "已导出 {output}"),
+    ("WAV export cancelled", "WAV 导出已取消"),
+    ("WAV export failed: {err}", "WAV 导出失败：{err}"),
+    ("Export practice statistics", "导出练习统计"),
+    ("Statistics exported to {path}", "统计已导出到 {path}"),
+    ("Failed to export statistics: {err}", "导出统计失败：{err}"),
+    (
+        "Add pieces to the playlist draft to build an assignment",
+        "请先把曲目加入播放列表草稿，再创建作业",
+    ),
+    ("The assignment has no pieces", "该作业没有曲目"),
+    ("Assignment", "作业"),
+    ("Export assignment", "导出作业"),
+    ("Lesson assignment", "课程作业"),
+    ("Assignment exported to {path}", "作业已导出到 {path}"),
+    ("Failed to export assignment: {err}", "导出作业失败：{err}"),
+    ("Import assignment", "导入作业"),
+    ("Imported assignment '{title}'", "已导入作业“{title}”"),
+    (
+        "Imported assignment '{title}' ({missing} piece(s) not in your library)",
+        "已导入作业“{title}”（{missing} 首曲目不在你的曲库中）",
+    ),
+    ("Failed to import assignment: {err}", "导入作业失败：{err}"),
+    ("Assignment removed", "作业已删除"),
+    ("Export assignment results", "导出作业结果"),
+    ("Results report", "结果报告"),
+    ("Results exported to {path}", "结果已导出到 {path}"),
+    ("Failed to export results: {err}", "导出结果失败：{err}"),
+    (
+        "Dumped {count} message(s) to {path}",
+        "已将 {count} 条消息导出到 {path}",
+    ),
+    (
+        "Failed to dump message log: {err}",
+        "导出消息日志失败：{err}",
+    ),
+    ("Playback started", "开始播放"),
+    (
+        "Silent gap of {seconds}s at {at}",
+        "{at} 处有 {seconds} 秒静音",
+    ),
+    (
+        "Skipped {seconds}s of silence at {at}",
+        "已跳过 {at} 处的 {seconds} 秒静音",
+    ),
+    ("Playback finished", "播放完毕"),
+    ("Playback stopped", "播放已停止"),
+    (
+        "Output filter updated; applies from the next song",
+        "输出过滤已更新，将从下一首开始生效",
+    ),
+    ("No favorites available to play", "没有可播放的收藏曲目"),
+    ("Playing favorites", "正在播放收藏"),
+    ("Playlist not found", "找不到播放列表"),
+    (
+        "Playlist has no playable tracks",
+        "播放列表中没有可播放的曲目",
+    ),
+    ("Playing playlist '{name}'", "正在播放播放列表“{name}”"),
+    (
+        "Queue arranged by key ({transposed} piece(s) transposed)",
+        "已按调性排列队列（{transposed} 首已移调）",
+    ),
+    ("Queue arranged by key", "已按调性排列队列"),
+    ("Queue finished", "队列播放完毕"),
+    ("Already at the beginning", "已经是第一首"),
+    ("Single", "单曲"),
+    ("Favorites", "收藏"),
+    ("Playlist", "播放列表"),
+    (
+        "Now: {name} (key-matched {shift})",
+        "正在播放：{name}（调性匹配 {shift}）",
+    ),
+    ("Now: {name}", "正在播放：{name}"),
+    ("Now: --", "正在播放：--"),
+    (
+        "A folder import is already running",
+        "已有文件夹导入正在进行",
+    ),
+    ("Add folder of MIDI files", "添加 MIDI 文件夹"),
+    ("A WAV export is already running", "已有 WAV 导出正在进行"),
+    ("Select a MIDI file to export", "请选择要导出的 MIDI 文件"),
+    ("Export WAV", "导出 WAV"),
+    ("WAV audio", "WAV 音频"),
+    ("Rendering {name}", "正在渲染 {name}"),
+    ("Track not available", "曲目不可用"),
+    ("Already preparing a track", "正在准备另一首曲目"),
+    (
+        "Select a MIDI output device first",
+        "请先选择 MIDI 输出设备",
+    ),
+    ("Downloading {name}", "正在下载 {name}"),
+    ("Preparing {name}", "正在准备 {name}"),
+    (
+        "The saved song is no longer in the library",
+        "保存的乐曲已不在曲库中",
+    ),
+    ("Looking for the saved device...", "正在查找保存的设备……"),
+    (
+        "The saved device is not connected; select a device and resume",
+        "保存的设备未连接，请选择设备后继续",
+    ),
+    (
+        "Resume: {name} at {position}",
+        "继续：{name}，从 {position} 开始",
+    ),
+    ("Resume", "继续"),
+    ("Dismiss", "关闭"),
+    ("Scanning devices...", "正在扫描设备……"),
+    ("Select output device", "选择输出设备"),
+    ("Refresh", "刷新"),
+    ("Add Local MIDI", "添加本地 MIDI"),
+    ("Add Folder", "添加文件夹"),
+    ("Settings", "设置"),
+    ("Monitor", "监视器"),
+    ("Dump Message Log", "导出消息日志"),
+    ("Welcome to MIDI Piano", "欢迎使用 MIDI Piano"),
+    (
+        "Step {number} of {total}: {title}",
+        "第 {number}/{total} 步：{title}",
+    ),
+    ("Language", "语言"),
+    ("Theme", "主题"),
+    (
+        "Choose a folder with your MIDI files. Sub-folders are scanned too, and the folder is checked again on every start.",
+        "选择存放 MIDI 文件的文件夹。子文件夹也会被扫描，并且每次启动时都会重新检查。",
+    ),
+    ("Choose Folder", "选择文件夹"),
+    ("Scanning...", "正在扫描……"),
+    (
+        "You can also skip this and add files later.",
+        "也可以跳过这一步，稍后再添加文件。",
+    ),
+    (
+        "Found {found_files} MIDI file(s)",
+        "找到 {found_files} 个 MIDI 文件",
+    ),
+    ("Scanning for devices...", "正在搜索设备……"),
+    ("No MIDI devices found yet.", "尚未找到 MIDI 设备。"),
+    ("Troubleshooting", "故障排除"),
+    (
+        "Turn on your piano, connect it by USB or Bluetooth and select it below. It will be selected automatically from now on.",
+        "打开钢琴，通过 USB 或蓝牙连接，然后在下方选择它。以后会自动选择该设备。",
+    ),
+    (
+        "Sent. If you heard a chord you're ready to play; otherwise check the piano's volume and local control.",
+        "已发送。如果听到了和弦就可以开始弹奏了；否则请检查钢琴的音量和本地控制设置。",
+    ),
+    ("Could not reach the device: {err}", "无法连接设备：{err}"),
+    (
+        "Play a short C major chord on {name}.",
+        "在 {name} 上播放一个简短的 C 大三和弦。",
+    ),
+    (
+        "Go back and select a device to send a test tone.",
+        "请返回并选择设备以发送测试音。",
+    ),
+    ("Send Test Tone", "发送测试音"),
+    (
+        "Used MIDI Piano before? Import a user_preferences.json to bring over ratings, favorites, playlists and settings.",
+        "以前用过 MIDI Piano？导入 user_preferences.json 即可带入评分、收藏、播放列表和设置。",
+    ),
+    ("Import Preferences", "导入偏好设置"),
+    ("Imported from {path}", "已从 {path} 导入"),
+    ("Next", "下一步"),
+    ("Start Playing", "开始弹奏"),
+    ("Skip Setup", "跳过设置"),
+    ("Back", "上一步"),
+    ("MIDI monitor", "MIDI 监视器"),
+    ("Hex", "十六进制"),
+    ("Pause", "暂停"),
+    ("Clear", "清除"),
+    (
+        "Nothing sent yet. Start playback or send a test tone.",
+        "尚未发送任何消息。开始播放或发送测试音。",
+    ),
+    ("No messages match the filters.", "没有符合筛选条件的消息。"),
+    ("Inbox: {inbox}", "收件文件夹：{inbox}"),
+    ("Inbox: not configured", "收件文件夹：未设置"),
+    ("Choose Inbox", "选择收件文件夹"),
+    ("Disable", "停用"),
+    ("Watch folder", "监视文件夹"),
+    ("Imports go to: {library_root}", "导入到：{library_root}"),
+    ("Choose Destination", "选择导入位置"),
+    ("Soundfont: {path}", "音色库：{path}"),
+    ("Soundfont: not selected", "音色库：未选择"),
+    ("WAV export", "WAV 导出"),
+    ("Choose Soundfont", "选择音色库"),
+    ("Instrument", "乐器"),
+    ("Play every song with", "所有乐曲使用"),
+    ("Rules for {name}", "{name} 的规则"),
+    (
+        "Rules for all devices without a profile",
+        "未设置配置文件的设备所用规则",
+    ),
+    ("Output filter", "输出过滤"),
+    ("max", "上限"),
+    ("Queue", "队列"),
+    (
+        "Match keys between consecutive pieces",
+        "相邻曲目之间匹配调性",
+    ),
+    ("Silence watchdog", "静音检测"),
+    ("When a song goes silent mid-piece", "乐曲中途出现静音时"),
+    ("after", "持续"),
+    ("Score follow overlay", "跟谱浮层"),
+    ("Show bar counter while playing", "播放时显示小节计数"),
+    ("Size {text_size}", "字号 {text_size}"),
+    ("Practice statistics", "练习统计"),
+    ("From (YYYY-MM-DD)", "开始日期（YYYY-MM-DD）"),
+    ("To (YYYY-MM-DD)", "结束日期（YYYY-MM-DD）"),
+    ("Export Stats", "导出统计"),
+    ("Music folders", "音乐文件夹"),
+    ("Rescanning...", "正在重新扫描……"),
+    ("Rescan Assets", "重新扫描资源"),
+    ("Run Setup Again", "重新运行设置向导"),
+    ("Remove", "移除"),
+    ("Remote catalog", "远程曲目表"),
+    ("Loading...", "正在加载……"),
+    ("Load", "加载"),
+    ("Clear Cache", "清除缓存"),
+    (
+        "{count} remote song(s); files download on first play",
+        "{count} 首远程乐曲；文件会在首次播放时下载",
+    ),
+    ("Mute:", "静音："),
+    ("Speed", "速度"),
+    ("Transpose", "移调"),
+    ("Silence watch: off", "静音检测：关闭"),
+    ("Silence watch: on", "静音检测：开启"),
+    ("Reset", "重置"),
+    ("LH below {note}", "{note} 以下为左手"),
+    ("LH = track {track}", "左手 = 音轨 {track}"),
+    ("LH ch", "左手通道"),
+    ("RH ch", "右手通道"),
+    ("−1 bar", "−1 小节"),
+    ("Tap", "打拍"),
+    ("+1 bar", "+1 小节"),
+    (
+        "Beat {beat}/{beats_per_measure}",
+        "第 {beat}/{beats_per_measure} 拍",
+    ),
+    ("Song information: {name}", "乐曲信息：{name}"),
+    ("Close", "关闭"),
+    ("Path: {path}", "路径：{path}"),
+    ("Reading file...", "正在读取文件……"),
+    ("SMPTE timecode", "SMPTE 时间码"),
+    (
+        "Size: {size} KB · SMF format {format} · {timing} · {tracks} track(s) · {notes} note(s)",
+        "大小：{size} KB · SMF 格式 {format} · {timing} · {tracks} 个音轨 · {notes} 个音符",
+    ),
+    ("Tempo: {tempos}", "速度：{tempos}"),
+    ("4/4 (default)", "4/4（默认）"),
+    ("Time signatures: {signatures}", "拍号：{signatures}"),
+    ("Channels used: {channels}", "使用的通道：{channels}"),
+    ("none (default piano)", "无（默认钢琴）"),
+    ("Instruments: {programs}", "乐器：{programs}"),
+    ("Tracks", "音轨"),
+    (
+        "{number}. {name} — channels [{channels}] — {notes} note(s)",
+        "{number}. {name} — 通道 [{channels}] — {notes} 个音符",
+    ),
+    ("(unnamed)", "（未命名）"),
+    (
+        "Note range: {lowest} – {highest}",
+        "音域：{lowest} – {highest}",
+    ),
+    ("Rendering {name} to WAV...", "正在将 {name} 渲染为 WAV……"),
+    ("Cancel", "取消"),
+    ("Importing {name}...", "正在导入 {name}……"),
+    ("Tree", "目录"),
+    ("Recent", "最近播放"),
+    ("Lessons", "课程"),
+    ("Play Selected", "播放所选"),
+    ("Stop", "停止"),
+    ("Panic", "紧急静音"),
+    ("Ready", "就绪"),
+    ("Preparing playback...", "正在准备播放……"),
+    ("Playing...", "正在播放……"),
+    ("Completed", "已完成"),
+    ("Queue: none", "队列：无"),
+    (
+        "Session master: {master_tempo_percent}% · {master_transpose} st",
+        "全局调整：{master_tempo_percent}% · {master_transpose} 半音",
+    ),
+    ("Session master: off", "全局调整：关闭"),
+    ("Tags:", "标签："),
+    ("Match all", "全部匹配"),
+    ("Add tag", "添加标签"),
+    ("Add", "添加"),
+    ("Search MIDI files...", "搜索 MIDI 文件……"),
+    ("Play Favorites", "播放收藏"),
+    ("Shuffle Favorites", "随机播放收藏"),
+    ("Continue: {name}", "继续：{name}"),
+    ("Nothing played yet", "还没有播放记录"),
+    ("Clear History", "清除记录"),
+    ("Assignments", "作业"),
+    ("Import Assignment", "导入作业"),
+    (
+        "No assignments yet. Import a file from your teacher.",
+        "还没有作业。请导入老师发来的文件。",
+    ),
+    ("Overdue since {due}", "已于 {due} 逾期"),
+    ("Due {due}", "截止 {due}"),
+    ("No due date", "无截止日期"),
+    ("Export Results", "导出结果"),
+    ("Tempo {target_tempo}%", "速度 {target_tempo}%"),
+    ("Create assignment", "创建作业"),
+    ("Assignment title", "作业标题"),
+    ("Due (YYYY-MM-DD)", "截止日期（YYYY-MM-DD）"),
+    ("From Playlist Draft", "从播放列表草稿创建"),
+    ("Export Assignment", "导出作业"),
+    ("Section (e.g. bars 1-16)", "段落（例如第 1-16 小节）"),
+    ("Tempo %", "速度 %"),
+    ("Plays", "遍数"),
+    (
+        "No MIDI files match the current filters",
+        "没有符合当前筛选条件的 MIDI 文件",
+    ),
+    ("{name} (Local)", "{name}（本地）"),
+    ("{name} (Remote)", "{name}（远程）"),
+    ("Info", "信息"),
+    ("Dismiss All", "全部关闭"),
+    ("Loading tree...", "正在加载目录……"),
+    ("Expand All", "全部展开"),
+    ("Collapse All", "全部折叠"),
+    ("Playlist name", "播放列表名称"),
+    ("Save Playlist", "保存播放列表"),
+    ("Clear Draft", "清空草稿"),
+    ("Smart from Tags", "按标签创建智能列表"),
+    ("Choose playlist", "选择播放列表"),
+    ("Load into Draft", "载入草稿"),
+    ("Delete Playlist", "删除播放列表"),
+    ("Clear Selection", "清除选择"),
+    ("Shuffle Selected", "随机播放所选"),
+    ("Smart: {rule}", "智能：{rule}"),
+    ("Select a playlist to play", "请选择要播放的播放列表"),
+    ("Playlist draft is empty", "播放列表草稿为空"),
+    ("No folder", "无文件夹"),
+    ("Organize playlists", "整理播放列表"),
+    ("New folder", "新文件夹"),
+    ("Rename", "重命名"),
+    ("Delete Folder", "删除文件夹"),
+    ("Duplicate", "复制"),
+    ("Name", "名称"),
+    ("Save", "保存"),
+    (
+        "{name} is not connected. Check that it is switched on, then refresh devices.",
+        "{name} 未连接。请确认设备已开启，然后刷新设备列表。",
+    ),
+    (
+        "{name} was found but is not acting as a Bluetooth MIDI device. Try reconnecting it.",
+        "找到了 {name}，但它没有作为蓝牙 MIDI 设备工作。请尝试重新连接。",
+    ),
+    (
+        "The file could not be read as MIDI. It may be damaged or not a MIDI file.",
+        "无法以 MIDI 格式读取该文件。文件可能已损坏，或者不是 MIDI 文件。",
+    ),
+    (
+        "The file uses {what}, which is not supported.",
+        "该文件使用了不受支持的 {what}。",
+    ),
+    (
+        "The connection to the device was lost while sending notes.",
+        "发送音符时与设备的连接中断。",
+    ),
+    ("Refresh Devices", "刷新设备"),
+    ("Reconnect Bluetooth", "重新连接蓝牙"),
+    ("Reconnect", "重新连接"),
+    ("Choose soundfont", "选择音色库"),
+    ("Remote", "远程"),
+    ("Local", "本地"),
+    ("Mini Player", "迷你播放器"),
+    ("Expand", "展开"),
+    ("Play", "播放"),
+    ("Next Track", "下一首"),
+    ("Show Window", "显示窗口"),
+    ("Hide to Tray", "隐藏到托盘"),
+    ("System tray", "系统托盘"),
+    (
+        "Closing the window keeps the app running in the tray",
+        "关闭窗口后应用继续在托盘中运行",
+    ),
+    ("Failed to seek", "跳转失败"),
+    ("Playback cancelled", "已取消播放"),
+    ("Device connection", "设备连接"),
+    (
+        "Give up connecting after {seconds}s",
+        "连接超过 {seconds} 秒后放弃",
+    ),
+    (
+        "{name} did not answer in time. Check that it is nearby and switched on, then try again.",
+        "{name} 未及时响应。请确认设备在附近且已开启，然后重试。",
+    ),
+    ("Quit", "退出"),
+    ("{count} · {minutes} min", "{count} · {minutes} 分钟"),
+    ("Song start", "乐曲开头"),
+    (
+        "Skip silence before the first note",
+        "跳过第一个音符前的静音",
+    ),
+    ("pre-roll", "预留"),
+    (
+        "Count in one measure before the first note",
+        "在第一个音符前预拍一小节",
+    ),
+    ("Export arrangement", "导出编曲"),
+    ("arranged", "改编"),
+    ("Exported {name} to the library", "已将 {name} 导出到曲库"),
+    ("Failed to export arrangement: {err}", "导出编曲失败：{err}"),
+    ("Bars", "小节"),
+    ("Time (m:ss)", "时间 (分:秒)"),
+    ("Excerpt", "片段"),
+    ("from", "从"),
+    ("to", "到"),
+    ("Save excerpt", "保存片段"),
+    ("{measures} bar(s)", "{measures} 小节"),
+    ("bars {from}-{to}", "第 {from}-{to} 小节"),
+    (
+        "Saved excerpt {name} to the library",
+        "已将片段 {name} 保存到曲库",
+    ),
+    ("Failed to save excerpt: {err}", "保存片段失败：{err}"),
+    ("Progress display", "进度显示"),
+    (
+        "Update the playback position every {millis} ms",
+        "每 {millis} 毫秒更新播放位置",
+    ),
+    ("Most played", "最常播放"),
+    ("Recently played", "最近播放"),
+    ("Sort by", "排序"),
+    ("Played {count}× · {date}", "已播放 {count} 次 · {date}"),
+    ("Played {count}×", "已播放 {count} 次"),
+    ("Any rating", "任意评分"),
+    ("Favorites only", "仅收藏"),
+    ("Removed {name} from the library", "已从曲库移除 {name}"),
+    ("No missing files found", "没有找到缺失的文件"),
+    (
+        "Removed {count} missing file(s)",
+        "已移除 {count} 个缺失的文件",
+    ),
+    ("Clean Missing Files", "清理缺失文件"),
+    ("Remove from Library", "从曲库移除"),
+    ("Remove and Forget", "移除并清除数据"),
+    ("Folder not found", "未找到文件夹"),
+    ("Folder has no playable tracks", "文件夹中没有可播放的曲目"),
+    ("Playing folder '{name}'", "正在播放文件夹“{name}”"),
+    ("Folder", "文件夹"),
+    ("Play Folder", "播放文件夹"),
+    ("Shuffle Folder", "随机播放文件夹"),
+    ("Library loading cancelled", "已取消加载曲库"),
+    ("Cancelled", "已取消"),
+    ("Loading library...", "正在加载曲库..."),
+    (
+        "{verified} of {total} assets verified",
+        "已校验 {verified}/{total} 个资源",
+    ),
+    (
+        "Library loading stopped: {reason}",
+        "曲库加载已停止：{reason}",
+    ),
+    ("Retry", "重试"),
+    (
+        "Size Bluetooth packets from the negotiated MTU",
+        "按协商的 MTU 确定蓝牙数据包大小",
+    ),
+    ("{bytes} bytes", "{bytes} 字节"),
+    (
+        "Packet size applies when the device next connects",
+        "数据包大小将在设备下次连接时生效",
+    ),
+    ("No reset", "不重置"),
+    ("Drop SysEx messages from songs", "丢弃乐曲中的 SysEx 消息"),
+    ("At song start", "乐曲开始时"),
+    (
+        "{name} needs more than this device can play:",
+        "{name} 需要的功能超出了此设备的能力：",
+    ),
+    ("Parts on channels {channels}", "通道 {channels} 上的声部"),
+    ("Drums on channel 10", "通道 10 上的鼓"),
+    ("Other instruments: {programs}", "其他乐器：{programs}"),
+    ("Remap to Fit", "重新映射以适配"),
+    ("Always Remap for This Device", "此设备始终重新映射"),
+    ("Play as Written", "按原样播放"),
+    (
+        "Song remapped to fit the device",
+        "乐曲已重新映射以适配设备",
+    ),
+    ("What {name} can play", "{name} 能播放的内容"),
+    ("Plays channel 1 only", "仅播放通道 1"),
+    ("Has drums on channel 10", "通道 10 有鼓组"),
+    ("Switches sounds on program changes", "随音色切换更换声音"),
+    ("Remap without asking", "无需询问直接重新映射"),
+    ("Profile {number}", "配置文件 {number}"),
+    ("Default rules", "默认规则"),
+    ("Device profile", "设备配置文件"),
+    ("Profile for {name}", "{name} 的配置文件"),
+    ("New Profile", "新建配置文件"),
+    ("Delete Profile", "删除配置文件"),
+    (
+        "Select a device to give it a profile",
+        "选择设备以为其指定配置文件",
+    ),
+    ("Profile name", "配置文件名称"),
+    ("Latency", "延迟"),
+    ("{millis} ms", "{millis} 毫秒"),
+    ("Velocity curve", "力度曲线"),
+    ("Send each channel on", "各通道发送到"),
+    ("Virtual Port", "虚拟端口"),
+    (
+        "Could not open the virtual MIDI port: {err}",
+        "无法打开虚拟 MIDI 端口：{err}",
+    ),
+    (
+        "Offer a virtual port named \"{name}\" to other apps",
+        "向其他应用提供名为“{name}”的虚拟端口",
+    ),
+    (
+        "Enter a port between 1 and 65535",
+        "请输入 1 到 65535 之间的端口",
+    ),
+    (
+        "Remote control server stopped: {err}",
+        "远程控制服务器已停止：{err}",
+    ),
+    ("Added {name} to the queue", "已将 {name} 加入队列"),
+    ("Remote control", "远程控制"),
+    (
+        "Let other devices on the network control playback",
+        "允许网络中的其他设备控制播放",
+    ),
+    ("Port", "端口"),
+    (
+        "Connect to ws://<this computer>:{port}/?token={token}",
+        "连接到 ws://<本机>:{port}/?token={token}",
+    ),
+    ("Remote control is not running", "远程控制未运行"),
+    ("New Token", "新令牌"),
+    ("Webhook saved", "Webhook 已保存"),
+    ("Webhook removed", "Webhook 已移除"),
+    ("Test event", "测试事件"),
+    ("Test event delivered", "测试事件已送达"),
+    ("Test event failed: {err}", "测试事件发送失败：{err}"),
+    ("Now-playing webhook", "正在播放 Webhook"),
+    ("Send Test Event", "发送测试事件"),
+    (
+        "Posts JSON when a song starts, finishes or is stopped",
+        "在乐曲开始、结束或停止时发送 JSON",
+    ),
+    ("Scheduled playback", "定时播放"),
+    ("Add Schedule", "添加定时"),
+    ("Mo", "一"),
+    ("Tu", "二"),
+    ("We", "三"),
+    ("Th", "四"),
+    ("Fr", "五"),
+    ("Sa", "六"),
+    ("Su", "日"),
+    ("Selected device", "当前设备"),
+    ("Missing playlist", "播放列表已丢失"),
+    ("Start Now", "立即开始"),
+    (
+        "{playlist} starts in {seconds}s",
+        "{playlist} 将在 {seconds} 秒后开始",
+    ),
+    ("Scheduled playback cancelled", "已取消定时播放"),
+    (
+        "Scheduled playlist skipped; something is already playing",
+        "已跳过定时播放列表；当前正在播放",
+    ),
+    (
+        "The scheduled device is not connected",
+        "定时播放的设备未连接",
+    ),
+    ("Sleep timer: off", "睡眠定时：关"),
+    ("Stop after {minutes} min", "{minutes} 分钟后停止"),
+    ("Stop after this track", "本曲结束后停止"),
+    ("Stop after {tracks} tracks", "{tracks} 首后停止"),
+    ("Stop at end of queue", "队列结束后停止"),
+    ("Stops in {time}", "{time} 后停止"),
+    ("Stops after this track", "将在本曲结束后停止"),
+    ("Stops after {tracks} tracks", "将在 {tracks} 首后停止"),
+    ("Sleep timer stopped playback", "睡眠定时已停止播放"),
+    ("Fades", "淡入淡出"),
+    ("Start and stop without fading", "开始和停止时不淡入淡出"),
+    (
+        "Fade in at the start and out when stopped over {millis} ms",
+        "开始时淡入、停止时淡出，时长 {millis} 毫秒",
+    ),
+    ("Plays with", "播放设置"),
+    ("Any Device", "任意设备"),
+    ("Always shuffle", "始终随机播放"),
+    ("Speed {percent}%", "速度 {percent}%"),
+    ("Transpose {semitones} st", "移调 {semitones} 半音"),
+    (
+        "The device for '{name}' is not connected",
+        "“{name}”的设备未连接",
+    ),
+    (
+        "Log every message sent to devices (for bug reports)",
+        "记录发送到设备的每条消息（用于报告问题）",
+    ),
+    ("Open Log Folder", "打开日志文件夹"),
+    ("Failed to open {path}: {err}", "无法打开 {path}：{err}"),
+    (
+        "Failed to open the send log: {err}",
+        "无法打开发送日志：{err}",
+    ),
+    (
+        "Send log setting applies from the next song",
+        "发送日志设置将从下一首乐曲起生效",
+    ),
+    ("Make Default", "设为默认"),
+    (
+        "This device will be selected on start",
+        "启动时将自动选择此设备",
+    ),
+    ("Bluetooth pairing", "蓝牙配对"),
+    (
+        "Switch on Bluetooth MIDI at the piano; many models need a button held or a menu setting.",
+        "在钢琴上打开蓝牙 MIDI；许多型号需要长按按钮或在菜单中设置。",
+    ),
+    (
+        "Keep the piano within a few metres and disconnect it from phones or tablets first.",
+        "让钢琴保持在几米范围内，并先断开它与手机或平板的连接。",
+    ),
+    (
+        "Pianos that also offer Bluetooth audio list a separate MIDI device; that is the one to use.",
+        "同时支持蓝牙音频的钢琴会另外列出一个 MIDI 设备，请使用该设备。",
+    ),
+    ("No songs yet", "还没有乐曲"),
+    (
+        "Choose a folder with MIDI files, or add files one at a time.",
+        "选择一个包含 MIDI 文件的文件夹，或逐个添加文件。",
+    ),
+    ("Choose Music Folder", "选择音乐文件夹"),
+    ("No piano found", "未找到钢琴"),
+    (
+        "Connect your piano by USB or Bluetooth, then scan again. Songs can still be played to the debug output.",
+        "通过 USB 或蓝牙连接钢琴，然后重新扫描。乐曲仍可播放到调试输出。",
+    ),
+    ("Scan Again", "重新扫描"),
+    ("Run Setup", "运行设置向导"),
+    (
+        "Playback was interrupted: {name} at {position}",
+        "播放曾被中断：{name}，位于 {position}",
+    ),
+    ("Reset Device", "重置设备"),
+    (
+        "Shuffle plays the most recently heard songs last",
+        "随机播放时将最近听过的乐曲放在最后",
+    ),
+    ("{count} songs", "{count} 首"),
+    (
+        "Weight shuffles by rating (higher-rated songs come up more often and may repeat)",
+        "随机播放按评分加权（评分越高的乐曲出现越频繁，且可能重复）",
+    ),
+    ("Export user data", "导出用户数据"),
+    ("User data exported to {path}", "用户数据已导出到 {path}"),
+    (
+        "Failed to export user data: {err}",
+        "导出用户数据失败：{err}",
+    ),
+    ("Import user data", "导入用户数据"),
+    ("User data imported", "用户数据已导入"),
+    (
+        "Failed to import user data: {err}",
+        "导入用户数据失败：{err}",
+    ),
+    ("User data", "用户数据"),
+    (
+        "Preferences, playlists, statistics and device profiles in one file",
+        "偏好设置、播放列表、统计和设备配置合为一个文件",
+    ),
+    ("Export User Data", "导出用户数据"),
+    ("Import and Merge", "导入并合并"),
+    ("Import and Replace", "导入并替换"),
+    ("Export library catalog", "导出曲库目录"),
+    ("Catalog exported to {path}", "目录已导出到 {path}"),
+    ("Failed to export catalog: {err}", "导出目录失败：{err}"),
+    ("Import ratings from catalog", "从目录导入评分"),
+    ("Catalog", "目录"),
+    (
+        "Updated {count} song(s) from the catalog",
+        "已根据目录更新 {count} 首乐曲",
+    ),
+    ("Failed to import catalog: {err}", "导入目录失败：{err}"),
+    ("Export Catalog", "导出目录"),
+    ("Import Ratings", "导入评分"),
+    ("Open Builder", "打开编排视图"),
+    ("Library ({count})", "曲库（{count}）"),
+    ("Add All →", "全部添加 →"),
+    ("Filter library...", "筛选曲库..."),
+    (
+        "Add songs from the library on the left",
+        "从左侧曲库添加乐曲",
+    ),
+    ("{count} song(s) · {length}", "{count} 首 · {length}"),
+    (
+        "{totals} (+{count} of unknown length)",
+        "{totals}（另有 {count} 首时长未知）",
+    ),
+    ("Draft", "草稿"),
+    ("← Remove All", "← 全部移除"),
+    ("Playlist builder", "播放列表编排"),
+    ("Done", "完成"),
+    ("Removed from '{name}'", "已从“{name}”移除"),
+    ("In playlists:", "所在播放列表："),
+    ("Red", "红色"),
+    ("Orange", "橙色"),
+    ("Yellow", "黄色"),
+    ("Green", "绿色"),
+    ("Blue", "蓝色"),
+    ("Purple", "紫色"),
+    ("Grey", "灰色"),
+    ("Color and label", "颜色和标签"),
+    ("No color", "无颜色"),
+    ("Emoji", "表情符号"),
+    ("Sort titles", "标题排序"),
+    ("Drums: device default", "鼓：设备默认"),
+    ("Drums: keep", "鼓：保留"),
+    ("Drums: drop", "鼓：去除"),
+    ("Drums: piano rhythm", "鼓：钢琴节奏"),
+    ("Rhythm ch", "节奏通道"),
+    ("Scale", "缩放"),
+    ("Bend range ±{semitones}", "弯音范围 ±{semitones}"),
+    ("Bend range as is", "弯音范围不变"),
+    ("Repeat", "重复"),
+    ("pass {pass}/{passes}", "第 {pass}/{passes} 遍"),
+    (
+        "Count down 3-2-1 before playing, clicking along with the count-in",
+        "播放前倒数 3-2-1，开启预备拍时同时发出节拍声",
+    ),
+    ("{name} plays next", "{name} 将在下一首播放"),
+    ("Play Next", "下一首播放"),
+    ("Add to Queue", "加入队列"),
+    ("Show Queue", "显示队列"),
+    ("Hide Queue", "隐藏队列"),
+    ("added", "已插入"),
+    (
+        "Starts at {initial} BPM · averages {average} BPM",
+        "起始 {initial} BPM · 平均 {average} BPM",
+    ),
+    ("{bpm} BPM", "{bpm} BPM"),
+    (
+        "{initial} BPM (avg {average})",
+        "{initial} BPM（平均 {average}）",
+    ),
+    (
+        "Metronome: click every beat while playing, accenting each bar",
+        "节拍器：播放时每拍发出滴答声，并重读每小节",
+    ),
+    (
+        "Quiet mode applies from the next song",
+        "安静模式从下一首开始生效",
+    ),
+    ("Quiet mode", "安静模式"),
+    ("Play quietly", "安静播放"),
+    ("Loudest note", "最大力度"),
+    ("Lower volume to", "音量降至"),
+    (
+        "Running status applies when the device next connects",
+        "运行状态将在设备下次连接时生效",
+    ),
+    (
+        "Use running status on USB devices (fewer bytes; not for macOS)",
+        "USB 设备使用运行状态（字节更少；不适用于 macOS）",
+    ),
+    (
+        "track {number}: {dropped} skipped, {fixed} fixed",
+        "音轨 {number}：跳过 {dropped} 条，修复 {fixed} 条",
+    ),
+    (
+        "Malformed messages in this song ({tracks})",
+        "此乐曲含有格式错误的消息（{tracks}）",
+    ),
+    ("Dump Timings", "导出计时数据"),
+    ("Timings written to {path}", "计时数据已写入 {path}"),
+    ("Failed to write timings: {err}", "写入计时数据失败：{err}"),
+    ("All Bluetooth adapters", "所有蓝牙适配器"),
+    ("Scan for Bluetooth devices with", "扫描蓝牙设备时使用"),
+    (
+        "Output: {messages} messages, {kilobytes} KB sent · {latency} ms per send · {errors} errors",
+        "输出：已发送 {messages} 条消息，{kilobytes} KB · 每次发送 {latency} 毫秒 · {errors} 个错误",
+    ),
+    ("Last send error: {error}", "最近一次发送错误：{error}"),
+    ("Piano Mode", "钢琴模式"),
+    (
+        "Piano Mode applies from the next song",
+        "钢琴模式将从下一首歌曲开始生效",
+    ),
+    ("Title:", "标题："),
+    ("Composer:", "作曲："),
+    ("Fill from File", "从文件填写"),
+    (
+        "The file names no title or composer",
+        "文件中没有标题或作曲者",
+    ),
+    (
+        "Now: {name} by {composer}",
+        "正在播放：{name}（{composer}）",
+    ),
+    ("By folder", "按文件夹"),
+    ("By composer", "按作曲家"),
+    ("By tag", "按标签"),
+    ("By rating", "按评分"),
+    ("Unknown composer", "未知作曲家"),
+    ("Untagged", "无标签"),
+    ("Unrated", "未评分"),
+    ("Preview", "试听"),
+    ("Stop Preview", "停止试听"),
+    ("Preview failed: {err}", "试听失败：{err}"),
+    ("Stop playback to preview a song", "请先停止播放再试听"),
+    ("No pause", "不停顿"),
+    ("{seconds} s pause", "停顿 {seconds} 秒"),
+    ("Work", "作品"),
+    (
+        "Add at least two movements before saving a work",
+        "保存作品前请至少添加两个乐章",
+    ),
+    ("Work {number}", "作品 {number}"),
+    ("Work '{name}' created", "已创建作品“{name}”"),
+    ("Work not found", "未找到作品"),
+    (
+        "None of the work's movements are in the library",
+        "媒体库中没有该作品的乐章",
+    ),
+    ("Playing '{name}'", "正在播放“{name}”"),
+    ("Next movement in {seconds} s", "{seconds} 秒后播放下一乐章"),
+    ("{count} movements", "{count} 个乐章"),
+    (
+        "Movement {number} of {count} in",
+        "第 {number}/{count} 乐章，属于",
+    ),
+    ("Work name", "作品名称"),
+    ("Ungroup", "取消分组"),
+    ("Save as Work", "保存为作品"),
+    (
+        "Reliable writes apply when the device next connects",
+        "可靠写入将在设备下次连接时生效",
+    ),
+    (
+        "Wait for Bluetooth devices to confirm every message (slower, drops none)",
+        "等待蓝牙设备确认每条消息（较慢，不丢消息）",
+    ),
+    (
+        "The server listens on every network interface: anyone on your network with the token can control playback.",
+        "服务器在所有网络接口上监听：同一网络中持有令牌的任何人都能控制播放。",
+    ),
+];
@@ -0,0 +1,178 @@
+use std::fs;
+use std::path::Path;
+
+use anyhow::{Context, Result, bail};
+use chrono::{DateTime, Local, NaiveDate, Utc};
+use serde::{Deserialize, Serialize};
+use uuid::Uuid;
+
+use crate::practice::PracticeLog;
+
+const BUNDLE_VERSION: u32 = 1;
+
+/// A teacher-authored set of pieces with practice targets, shared as a file.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Assignment {
+    pub id: Uuid,
+    pub title: String,
+    #[serde(default)]
+    pub due: Option<NaiveDate>,
+    pub created_at: DateTime<Utc>,
+    pub items: Vec<AssignmentItem>,
+    /// Set on the student side; practice before this point does not count.
+    #[serde(default)]
+    pub imported_at: Option<DateTime<Utc>>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct AssignmentItem {
+    pub entry_id: Uuid,
+    pub entry_name: String,
+    /// Target tempo as a percentage of the written tempo.
+    pub target_tempo: u16,
+    #[serde(default)]
+    pub section: String,
+    pub target_plays: u32,
+}
+
+#[derive(Debug, Clone, Copy, Default, Serialize)]
+pub struct ItemProgress {
+    pub plays: u32,
+    pub practice_secs: f64,
+}
+
+impl ItemProgress {
+    pub fn is_done(&self, item: &AssignmentItem) -> bool {
+        self.plays >= item.target_plays
+    }
+}
+
+#[derive(Serialize, Deserialize)]
+struct AssignmentBundle {
+    version: u32,
+    assignment: Assignment,
+}
+
+#[derive(Serialize)]
+struct ResultsReport<'a> {
+    assignment_id: Uuid,
+    title: &'a str,
+    due: Option<NaiveDate>,
+    generated_at: DateTime<Utc>,
+    completed_items: usize,
+    total_items: usize,
+    items: Vec<ItemResult<'a>>,
+}
+
+#[derive(Serialize)]
+struct ItemResult<'a> {
+    entry_name: &'a str,
+    section: &'a str,
+    target_tempo: u16,
+    target_plays: u32,
+    plays: u32,
+    practice_minutes: f64,
+    done: bool,
+}
+
+impl Assignment {
+    pub fn new(
+        title: impl Into<String>,
+        due: Option<NaiveDate>,
+        items: Vec<AssignmentItem>,
+    ) -> Self {
+        Self {
+            id: Uuid::new_v4(),
+            title: title.into(),
+            due,
+            created_at: Utc::now(),
+            items,
+            imported_at: None,
+        }
+    }
+
+    pub fn export_bundle(&self, path: &Path) -> Result<()> {
+        let mut assignment = self.clone();
+        assignment.imported_at = None;
+        let bundle = AssignmentBundle {
+            version: BUNDLE_VERSION,
+            assignment,
+        };
+        let serialized =
+            serde_json::to_string_pretty(&bundle).context("failed to serialize assignment")?;
+        fs::write(path, serialized).with_context(|| format!("failed to write {}", path.display()))
+    }
+
+    pub fn import_bundle(path: &Path) -> Result<Self> {
+        let data = fs::read_to_string(path)
+            .with_context(|| format!("failed to read {}", path.display()))?;
+        let bundle: AssignmentBundle =
+            serde_json::from_str(&data).context("file is not a lesson assignment")?;
+        if bundle.version > BUNDLE_VERSION {
+            bail!(
+                "assignment was created by a newer version (format {})",
+                bundle.version
+            );
+        }
+        let mut assignment = bundle.assignment;
+        assignment.imported_at = Some(Utc::now());
+        Ok(assignment)
+    }
+
+    pub fn is_overdue(&self) -> bool {
+        self.due.is_some_and(|due| Local::now().date_naive() > due)
+    }
+
+    /// Counts completed plays and practice time per item since the
+    /// assignment was created or imported.
+    pub fn progress(&self, log: &PracticeLog) -> Vec<ItemProgress> {
+        let since = self.imported_at.unwrap_or(self.created_at);
+        self.items
+            .iter()
+            .map(|item| {
+                log.sessions
+                    .iter()
+                    .filter(|session| {
+                        session.entry_id == item.entry_id && session.started_at >= since
+                    })
+                    .fold(ItemProgress::default(), |mut progress, session| {
+                        if session.completed {
+                            progress.plays += 1;
+                        }
+                        progress.practice_secs += session.played_secs;
+                        progress
+                    })
+            })
+            .collect()
+    }
+
+    pub fn export_results(&self, log: &PracticeLog, path: &Path) -> Result<()> {
+        let progress = self.progress(log);
+        let items: Vec<ItemResult<'_>> = self
+            .items
+            .iter()
+            .zip(&progress)
+            .map(|(item, progress)| ItemResult {
+                entry_name: &item.entry_name,
+                section: &item.section,
+                target_tempo: item.target_tempo,
+                target_plays: item.target_plays,
+                plays: progress.plays,
+                practice_minutes: progress.practice_secs / 60.0,
+                done: progress.is_done(item),
+            })
+            .collect();
+        let report = ResultsReport {
+            assignment_id: self.id,
+            title: &self.title,
+            due: self.due,
+            generated_at: Utc::now(),
+            completed_items: items.iter().filter(|item| item.done).count(),
+            total_items: items.len(),
+            items,
+        };
+        let serialized =
+            serde_json::to_string_pretty(&report).context("failed to serialize results")?;
+        fs::write(path, serialized).with_context(|| format!("failed to write {}", path.display()))
+    }
+}
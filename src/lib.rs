@@ -0,0 +1,21 @@
+//! Device discovery and MIDI playback used by the MIDI Piano app.
+//!
+//! [`devices::MidiDeviceManager`] finds USB and Bluetooth LE MIDI outputs and
+//! connects to them as [`midi::SharedMidiSink`]s. [`midi::MidiSequence`] loads
+//! Standard MIDI Files, and [`midi::MidiPlayer`] streams a sequence to any
+//! sink, reporting progress as [`midi::PlayerEvent`]s. Implement
+//! [`midi::MidiSink`] to play into something other than a hardware device.
+//! Errors a user can act on carry an [`error::PlaybackError`]. The
+//! [`control`] server lets other devices drive playback over WebSocket, and
+//! [`webhook`] reports what is played to a URL of the user's choosing.
+//! [`shuffle::ShuffleHistory`] remembers recent plays so shuffles put them
+//! last, and [`works::WorkIndex`] keeps the movements of a
+//! [`works::Work`] together in a queue.
+
+pub mod control;
+pub mod devices;
+pub mod error;
+pub mod midi;
+pub mod shuffle;
+pub mod webhook;
+pub mod works;
@@ -1,10 +1,16 @@
 mod app;
-mod devices;
-mod midi;
+mod debug;
+mod i18n;
+mod lesson;
+mod media_controls;
+mod notifications;
+mod practice;
+mod schedule;
+mod tray;
 
 fn main() -> iced::Result {
     if env_logger::try_init().is_err() {
         eprintln!("Logger already initialized");
     }
-    app::run()
+    app::run(debug::DebugOptions::from_args(std::env::args().skip(1)))
 }
@@ -0,0 +1,444 @@
+//! Publishes playback to the desktop's media controls and forwards media
+//! key presses back to the app. Linux uses MPRIS over the session bus and
+//! Windows the system media transport controls; elsewhere [`spawn`] fails
+//! and only in-window media keys work.
+
+use std::fmt;
+use std::time::Duration;
+
+use tokio::sync::mpsc::UnboundedSender;
+use uuid::Uuid;
+
+/// A request from the desktop, handed to the app through a channel.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum MediaCommand {
+    Play,
+    Pause,
+    PlayPause,
+    Next,
+    Previous,
+    Stop,
+    Raise,
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum MediaStatus {
+    Playing,
+    Paused,
+    #[default]
+    Stopped,
+}
+
+/// The now-playing metadata shown by the desktop.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct MediaState {
+    pub status: MediaStatus,
+    pub track: Option<(Uuid, String)>,
+    pub length: Option<Duration>,
+    pub position: Duration,
+}
+
+#[derive(Clone)]
+pub struct MediaControlsHandle {
+    #[cfg(target_os = "linux")]
+    connection: zbus::Connection,
+    #[cfg(target_os = "windows")]
+    controls: smtc::Controls,
+}
+
+impl fmt::Debug for MediaControlsHandle {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("MediaControlsHandle")
+            .finish_non_exhaustive()
+    }
+}
+
+impl MediaControlsHandle {
+    /// Publishes `state`. Changes are announced in the background.
+    pub fn update(&self, state: MediaState) {
+        #[cfg(target_os = "linux")]
+        {
+            let connection = self.connection.clone();
+            tokio::spawn(async move {
+                if let Err(err) = mpris::publish(&connection, state).await {
+                    log::warn!("failed to update media controls: {err}");
+                }
+            });
+        }
+        #[cfg(target_os = "windows")]
+        if let Err(err) = smtc::publish(&self.controls, state) {
+            log::warn!("failed to update media controls: {err}");
+        }
+        #[cfg(not(any(target_os = "linux", target_os = "windows")))]
+        let _ = state;
+    }
+}
+
+#[cfg(target_os = "linux")]
+pub async fn spawn(commands: UnboundedSender<MediaCommand>) -> Result<MediaControlsHandle, String> {
+    mpris::serve(commands)
+        .await
+        .map(|connection| MediaControlsHandle { connection })
+        .map_err(|err| err.to_string())
+}
+
+#[cfg(target_os = "windows")]
+pub async fn spawn(commands: UnboundedSender<MediaCommand>) -> Result<MediaControlsHandle, String> {
+    smtc::serve(commands)
+        .map(|controls| MediaControlsHandle { controls })
+        .map_err(|err| err.to_string())
+}
+
+#[cfg(not(any(target_os = "linux", target_os = "windows")))]
+pub async fn spawn(
+    _commands: UnboundedSender<MediaCommand>,
+) -> Result<MediaControlsHandle, String> {
+    Err("desktop media controls are only supported on Linux and Windows".into())
+}
+
+#[cfg(target_os = "windows")]
+mod smtc {
+    use std::sync::{Arc, Mutex};
+
+    use tokio::sync::mpsc::UnboundedSender;
+    use uuid::Uuid;
+    use windows::Foundation::{TimeSpan, TypedEventHandler};
+    use windows::Media::Playback::MediaPlayer;
+    use windows::Media::{
+        MediaPlaybackStatus, MediaPlaybackType, SystemMediaTransportControls,
+        SystemMediaTransportControlsButton, SystemMediaTransportControlsButtonPressedEventArgs,
+        SystemMediaTransportControlsTimelineProperties,
+    };
+    use windows::core::{HSTRING, Result};
+
+    use super::{MediaCommand, MediaState, MediaStatus};
+
+    /// The transport controls of a media player that never plays anything,
+    /// which an app without a window handle to hand can still get. The
+    /// player's own command handling is off, so only the app answers the
+    /// buttons.
+    #[derive(Clone)]
+    pub struct Controls {
+        _player: MediaPlayer,
+        controls: SystemMediaTransportControls,
+        /// The track on display, so the title is only redrawn when it
+        /// changes.
+        shown: Arc<Mutex<Option<(Uuid, String)>>>,
+    }
+
+    pub fn serve(commands: UnboundedSender<MediaCommand>) -> Result<Controls> {
+        let player = MediaPlayer::new()?;
+        player.CommandManager()?.SetIsEnabled(false)?;
+        let controls = player.SystemMediaTransportControls()?;
+        controls.SetIsEnabled(true)?;
+        controls.SetIsPlayEnabled(true)?;
+        controls.SetIsPauseEnabled(true)?;
+        controls.SetIsStopEnabled(true)?;
+        controls.SetIsNextEnabled(true)?;
+        controls.SetIsPreviousEnabled(true)?;
+        controls
+            .DisplayUpdater()?
+            .SetType(MediaPlaybackType::Music)?;
+        controls.ButtonPressed(&TypedEventHandler::<
+            SystemMediaTransportControls,
+            SystemMediaTransportControlsButtonPressedEventArgs,
+        >::new(move |_, args| {
+            let command = match args.ok()?.Button()? {
+                SystemMediaTransportControlsButton::Play => MediaCommand::Play,
+                SystemMediaTransportControlsButton::Pause => MediaCommand::Pause,
+                SystemMediaTransportControlsButton::Stop => MediaCommand::Stop,
+                SystemMediaTransportControlsButton::Next => MediaCommand::Next,
+                SystemMediaTransportControlsButton::Previous => MediaCommand::Previous,
+                _ => return Ok(()),
+            };
+            let _ = commands.send(command);
+            Ok(())
+        }))?;
+        Ok(Controls {
+            _player: player,
+            controls,
+            shown: Arc::default(),
+        })
+    }
+
+    pub fn publish(controls: &Controls, state: MediaState) -> Result<()> {
+        let Controls {
+            controls, shown, ..
+        } = controls;
+        controls.SetPlaybackStatus(match state.status {
+            MediaStatus::Playing => MediaPlaybackStatus::Playing,
+            MediaStatus::Paused => MediaPlaybackStatus::Paused,
+            MediaStatus::Stopped => MediaPlaybackStatus::Stopped,
+        })?;
+
+        let mut shown = shown.lock().unwrap();
+        if *shown != state.track {
+            let display = controls.DisplayUpdater()?;
+            // Clearing forgets the type too.
+            display.ClearAll()?;
+            display.SetType(MediaPlaybackType::Music)?;
+            if let Some((_, name)) = &state.track {
+                display
+                    .MusicProperties()?
+                    .SetTitle(&HSTRING::from(name.as_str()))?;
+            }
+            display.Update()?;
+            *shown = state.track;
+        }
+
+        let length = TimeSpan::from(state.length.unwrap_or_default());
+        let timeline = SystemMediaTransportControlsTimelineProperties::new()?;
+        timeline.SetStartTime(TimeSpan::default())?;
+        timeline.SetEndTime(length)?;
+        timeline.SetMinSeekTime(TimeSpan::default())?;
+        timeline.SetMaxSeekTime(length)?;
+        timeline.SetPosition(TimeSpan::from(state.position))?;
+        controls.UpdateTimelineProperties(&timeline)
+    }
+}
+
+#[cfg(target_os = "linux")]
+mod mpris {
+    use std::collections::HashMap;
+    use std::time::Duration;
+
+    use tokio::sync::mpsc::UnboundedSender;
+    use zbus::object_server::SignalEmitter;
+    use zbus::zvariant::{ObjectPath, OwnedObjectPath, OwnedValue, Str};
+    use zbus::{Connection, interface};
+
+    use super::{MediaCommand, MediaState, MediaStatus};
+
+    const OBJECT_PATH: &str = "/org/mpris/MediaPlayer2";
+    const NO_TRACK: &str = "/org/mpris/MediaPlayer2/TrackList/NoTrack";
+    /// Forward movement beyond this between updates counts as a seek.
+    const SEEK_TOLERANCE: Duration = Duration::from_secs(2);
+
+    pub async fn serve(commands: UnboundedSender<MediaCommand>) -> zbus::Result<Connection> {
+        // The process id keeps a second instance from failing to start.
+        let name = format!(
+            "org.mpris.MediaPlayer2.{}.instance{}",
+            env!("CARGO_PKG_NAME").replace('-', "_"),
+            std::process::id()
+        );
+        zbus::connection::Builder::session()?
+            .name(name)?
+            .serve_at(
+                OBJECT_PATH,
+                Root {
+                    commands: commands.clone(),
+                },
+            )?
+            .serve_at(
+                OBJECT_PATH,
+                Player {
+                    commands,
+                    state: MediaState::default(),
+                },
+            )?
+            .build()
+            .await
+    }
+
+    pub async fn publish(connection: &Connection, state: MediaState) -> zbus::Result<()> {
+        let player = connection
+            .object_server()
+            .interface::<_, Player>(OBJECT_PATH)
+            .await?;
+        let mut player_mut = player.get_mut().await;
+        let previous = std::mem::replace(&mut player_mut.state, state);
+        let emitter = player.signal_emitter();
+        if previous.status != player_mut.state.status {
+            player_mut.playback_status_changed(emitter).await?;
+        }
+        if previous.track != player_mut.state.track || previous.length != player_mut.state.length {
+            player_mut.metadata_changed(emitter).await?;
+            player_mut.can_play_changed(emitter).await?;
+        }
+        // Position is polled, so only jumps (like resuming elsewhere) are
+        // announced.
+        let position = player_mut.state.position;
+        if previous.track == player_mut.state.track
+            && (position < previous.position || position > previous.position + SEEK_TOLERANCE)
+        {
+            Player::seeked(emitter, micros(player_mut.state.position)).await?;
+        }
+        Ok(())
+    }
+
+    fn micros(duration: Duration) -> i64 {
+        i64::try_from(duration.as_micros()).unwrap_or(i64::MAX)
+    }
+
+    struct Root {
+        commands: UnboundedSender<MediaCommand>,
+    }
+
+    #[interface(name = "org.mpris.MediaPlayer2")]
+    impl Root {
+        fn raise(&self) {
+            let _ = self.commands.send(MediaCommand::Raise);
+        }
+
+        fn quit(&self) {}
+
+        #[zbus(property)]
+        fn can_quit(&self) -> bool {
+            false
+        }
+
+        #[zbus(property)]
+        fn can_raise(&self) -> bool {
+            true
+        }
+
+        #[zbus(property)]
+        fn has_track_list(&self) -> bool {
+            false
+        }
+
+        #[zbus(property)]
+        fn identity(&self) -> String {
+            "MIDI Piano Player".into()
+        }
+
+        #[zbus(property)]
+        fn supported_uri_schemes(&self) -> Vec<String> {
+            Vec::new()
+        }
+
+        #[zbus(property)]
+        fn supported_mime_types(&self) -> Vec<String> {
+            Vec::new()
+        }
+    }
+
+    struct Player {
+        commands: UnboundedSender<MediaCommand>,
+        state: MediaState,
+    }
+
+    impl Player {
+        fn send(&self, command: MediaCommand) {
+            // The receiver only goes away when the app is exiting.
+            let _ = self.commands.send(command);
+        }
+    }
+
+    #[interface(name = "org.mpris.MediaPlayer2.Player")]
+    impl Player {
+        fn next(&self) {
+            self.send(MediaCommand::Next);
+        }
+
+        fn previous(&self) {
+            self.send(MediaCommand::Previous);
+        }
+
+        fn pause(&self) {
+            self.send(MediaCommand::Pause);
+        }
+
+        fn play_pause(&self) {
+            self.send(MediaCommand::PlayPause);
+        }
+
+        fn stop(&self) {
+            self.send(MediaCommand::Stop);
+        }
+
+        fn play(&self) {
+            self.send(MediaCommand::Play);
+        }
+
+        fn seek(&self, _offset: i64) {}
+
+        fn set_position(&self, _track_id: ObjectPath<'_>, _position: i64) {}
+
+        fn open_uri(&self, _uri: &str) {}
+
+        #[zbus(signal)]
+        async fn seeked(emitter: &SignalEmitter<'_>, position: i64) -> zbus::Result<()>;
+
+        #[zbus(property)]
+        fn playback_status(&self) -> &str {
+            match self.state.status {
+                MediaStatus::Playing => "Playing",
+                MediaStatus::Paused => "Paused",
+                MediaStatus::Stopped => "Stopped",
+            }
+        }
+
+        #[zbus(property)]
+        fn rate(&self) -> f64 {
+            1.0
+        }
+
+        #[zbus(property)]
+        fn minimum_rate(&self) -> f64 {
+            1.0
+        }
+
+        #[zbus(property)]
+        fn maximum_rate(&self) -> f64 {
+            1.0
+        }
+
+        #[zbus(property)]
+        fn metadata(&self) -> HashMap<String, OwnedValue> {
+            let mut metadata = HashMap::new();
+            let Some((id, title)) = &self.state.track else {
+                let path = OwnedObjectPath::try_from(NO_TRACK).expect("valid object path");
+                metadata.insert("mpris:trackid".into(), OwnedValue::from(path.into_inner()));
+                return metadata;
+            };
+            let path = format!("{OBJECT_PATH}/track/{}", id.simple());
+            if let Ok(path) = ObjectPath::try_from(path) {
+                metadata.insert("mpris:trackid".into(), OwnedValue::from(path));
+            }
+            metadata.insert(
+                "xesam:title".into(),
+                OwnedValue::from(Str::from(title.clone())),
+            );
+            if let Some(length) = self.state.length {
+                metadata.insert("mpris:length".into(), OwnedValue::from(micros(length)));
+            }
+            metadata
+        }
+
+        #[zbus(property(emits_changed_signal = "false"))]
+        fn position(&self) -> i64 {
+            micros(self.state.position)
+        }
+
+        #[zbus(property)]
+        fn can_go_next(&self) -> bool {
+            true
+        }
+
+        #[zbus(property)]
+        fn can_go_previous(&self) -> bool {
+            true
+        }
+
+        #[zbus(property)]
+        fn can_play(&self) -> bool {
+            self.state.track.is_some() || self.state.status != MediaStatus::Stopped
+        }
+
+        #[zbus(property)]
+        fn can_pause(&self) -> bool {
+            true
+        }
+
+        #[zbus(property)]
+        fn can_seek(&self) -> bool {
+            false
+        }
+
+        #[zbus(property(emits_changed_signal = "const"))]
+        fn can_control(&self) -> bool {
+            true
+        }
+    }
+}
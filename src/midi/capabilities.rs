@@ -0,0 +1,119 @@
+use std::collections::BTreeSet;
+
+use serde::{Deserialize, Serialize};
+
+use super::sequence::{MidiSequence, PlaybackAdjustments};
+
+const PERCUSSION_CHANNEL: u8 = 9;
+/// General MIDI programs 0–7 are the piano family.
+const PIANO_PROGRAMS: std::ops::Range<u8> = 0..8;
+
+/// What an output device can play, as declared by the user. Songs that need
+/// more can be remapped with [`DeviceCapabilities::remap_adjustments`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct DeviceCapabilities {
+    /// Only listens on channel 1.
+    pub single_channel: bool,
+    /// Plays drums on channel 10.
+    pub percussion: bool,
+    /// Switches to other instruments on program changes.
+    pub program_changes: bool,
+    /// Remaps songs that need more without asking first.
+    pub auto_remap: bool,
+}
+
+impl Default for DeviceCapabilities {
+    fn default() -> Self {
+        Self {
+            single_channel: false,
+            percussion: true,
+            program_changes: true,
+            auto_remap: false,
+        }
+    }
+}
+
+/// Something a song uses that a device has declared it cannot play.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum CapabilityIssue {
+    /// Channels other than the first carry notes, numbered from 0.
+    Channels(Vec<u8>),
+    Percussion,
+    /// Programs outside the piano family, in ascending order.
+    Programs(Vec<u8>),
+}
+
+impl DeviceCapabilities {
+    /// A digital piano with a single sound on channel 1 and no drums.
+    pub const PIANO_ONLY: DeviceCapabilities = DeviceCapabilities {
+        single_channel: true,
+        percussion: false,
+        program_changes: false,
+        auto_remap: false,
+    };
+
+    /// Whether the device takes anything a General MIDI file can contain.
+    pub fn is_unrestricted(&self) -> bool {
+        !self.single_channel && self.percussion && self.program_changes
+    }
+
+    /// What `sequence` uses beyond these capabilities.
+    pub fn issues(&self, sequence: &MidiSequence) -> Vec<CapabilityIssue> {
+        let mut note_channels = BTreeSet::new();
+        let mut programs = BTreeSet::new();
+        for event in &sequence.events {
+            match event.data.as_slice() {
+                [status, _, velocity] if status & 0xF0 == 0x90 && *velocity > 0 => {
+                    note_channels.insert(status & 0x0F);
+                }
+                [status, program]
+                    if status & 0xF0 == 0xC0 && status & 0x0F != PERCUSSION_CHANNEL =>
+                {
+                    programs.insert(*program);
+                }
+                _ => {}
+            }
+        }
+
+        let mut issues = Vec::new();
+        if self.single_channel {
+            let others: Vec<u8> = note_channels
+                .iter()
+                .copied()
+                .filter(|channel| *channel != 0 && *channel != PERCUSSION_CHANNEL)
+                .collect();
+            if !others.is_empty() {
+                issues.push(CapabilityIssue::Channels(others));
+            }
+        }
+        if !self.percussion && note_channels.contains(&PERCUSSION_CHANNEL) {
+            issues.push(CapabilityIssue::Percussion);
+        }
+        if !self.program_changes {
+            let others: Vec<u8> = programs
+                .into_iter()
+                .filter(|program| !PIANO_PROGRAMS.contains(program))
+                .collect();
+            if !others.is_empty() {
+                issues.push(CapabilityIssue::Programs(others));
+            }
+        }
+        issues
+    }
+
+    /// Adjustments that fit a song to these capabilities: everything moves
+    /// to channel 1, drums are dropped and the piano is kept selected.
+    pub fn remap_adjustments(&self) -> PlaybackAdjustments {
+        PlaybackAdjustments {
+            merge_channel: self.single_channel.then_some(0),
+            muted_channels: if self.percussion {
+                0
+            } else {
+                1 << PERCUSSION_CHANNEL
+            },
+            program_override: (!self.program_changes).then_some(PIANO_PROGRAMS.start),
+            ..PlaybackAdjustments::default()
+        }
+    }
+}
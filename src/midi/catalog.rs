@@ -0,0 +1,213 @@
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use anyhow::{Context, Result, bail};
+use serde::{Deserialize, Serialize};
+use uuid::Uuid;
+
+const CATALOG_VERSION: u32 = 1;
+const CSV_HEADER: [&str; 7] = [
+    "id",
+    "name",
+    "folder",
+    "path",
+    "duration_secs",
+    "rating",
+    "favorite",
+];
+
+/// One library entry as listed for other tools. Ids are the library's own,
+/// which stay the same for a file across scans, so a catalog edited
+/// elsewhere can be read back in.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct CatalogEntry {
+    pub id: Uuid,
+    pub name: String,
+    /// Folder names from the library root down.
+    #[serde(default)]
+    pub folder: Vec<String>,
+    #[serde(default)]
+    pub path: PathBuf,
+    #[serde(default)]
+    pub duration_secs: Option<f64>,
+    /// Stars from 1 to 5; `None` when unrated.
+    #[serde(default)]
+    pub rating: Option<u8>,
+    #[serde(default)]
+    pub favorite: bool,
+}
+
+#[derive(Serialize, Deserialize)]
+struct CatalogFile {
+    version: u32,
+    entries: Vec<CatalogEntry>,
+}
+
+/// How a catalog file is written, going by its extension.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CatalogFormat {
+    Json,
+    /// One row per entry with a header row; folders are joined with `/`.
+    Csv,
+}
+
+impl CatalogFormat {
+    /// CSV for `.csv` files, JSON for anything else.
+    pub fn for_path(path: &Path) -> Self {
+        match path.extension().and_then(|ext| ext.to_str()) {
+            Some(ext) if ext.eq_ignore_ascii_case("csv") => CatalogFormat::Csv,
+            _ => CatalogFormat::Json,
+        }
+    }
+}
+
+/// Writes `entries` to `path` in the format its extension calls for.
+pub fn write(entries: &[CatalogEntry], path: &Path) -> Result<()> {
+    let contents = match CatalogFormat::for_path(path) {
+        CatalogFormat::Json => serde_json::to_string_pretty(&CatalogFile {
+            version: CATALOG_VERSION,
+            entries: entries.to_vec(),
+        })
+        .context("failed to serialize catalog")?,
+        CatalogFormat::Csv => {
+            let mut csv = csv_row(CSV_HEADER.iter().map(|field| field.to_string()));
+            for entry in entries {
+                csv.push_str(&csv_row([
+                    entry.id.to_string(),
+                    entry.name.clone(),
+                    entry.folder.join("/"),
+                    entry.path.display().to_string(),
+                    entry
+                        .duration_secs
+                        .map(|secs| format!("{secs:.1}"))
+                        .unwrap_or_default(),
+                    entry
+                        .rating
+                        .map(|stars| stars.to_string())
+                        .unwrap_or_default(),
+                    entry.favorite.to_string(),
+                ]));
+            }
+            csv
+        }
+    };
+    fs::write(path, contents).with_context(|| format!("failed to write {}", path.display()))
+}
+
+/// Reads a catalog written by [`write`], or edited since. CSV columns are
+/// matched by header name and only `id` is required.
+pub fn read(path: &Path) -> Result<Vec<CatalogEntry>> {
+    let data =
+        fs::read_to_string(path).with_context(|| format!("failed to read {}", path.display()))?;
+    match CatalogFormat::for_path(path) {
+        CatalogFormat::Json => {
+            let file: CatalogFile =
+                serde_json::from_str(&data).context("failed to parse catalog")?;
+            if file.version > CATALOG_VERSION {
+                bail!("catalog format {} is newer than this app", file.version);
+            }
+            Ok(file.entries)
+        }
+        CatalogFormat::Csv => parse_csv(&data),
+    }
+}
+
+fn parse_csv(data: &str) -> Result<Vec<CatalogEntry>> {
+    let mut rows = csv_records(data).into_iter();
+    let header = rows.next().context("catalog is empty")?;
+    let column = |name: &str| header.iter().position(|field| field.trim() == name);
+    let id_column = column("id").context("catalog has no id column")?;
+    let [name, folder, path, duration, rating, favorite] = [
+        "name",
+        "folder",
+        "path",
+        "duration_secs",
+        "rating",
+        "favorite",
+    ]
+    .map(column);
+
+    let mut entries = Vec::new();
+    for (line, row) in rows.enumerate() {
+        if row.iter().all(|field| field.trim().is_empty()) {
+            continue;
+        }
+        let field = |index: Option<usize>| {
+            index
+                .and_then(|index| row.get(index))
+                .map(|field| field.trim())
+                .unwrap_or_default()
+        };
+        let id = Uuid::parse_str(field(Some(id_column)))
+            .with_context(|| format!("row {} has no valid id", line + 2))?;
+        let rating = match field(rating) {
+            "" | "0" => None,
+            stars => Some(
+                stars
+                    .parse::<u8>()
+                    .ok()
+                    .filter(|stars| (1..=5).contains(stars))
+                    .with_context(|| format!("row {} has rating '{stars}'", line + 2))?,
+            ),
+        };
+        entries.push(CatalogEntry {
+            id,
+            name: field(name).to_string(),
+            folder: field(folder)
+                .split('/')
+                .filter(|part| !part.is_empty())
+                .map(str::to_string)
+                .collect(),
+            path: PathBuf::from(field(path)),
+            duration_secs: field(duration).parse().ok(),
+            rating,
+            favorite: field(favorite).eq_ignore_ascii_case("true"),
+        });
+    }
+    Ok(entries)
+}
+
+/// Splits CSV text into records, honouring quoted fields that hold commas,
+/// quotes or line breaks.
+fn csv_records(data: &str) -> Vec<Vec<String>> {
+    let mut records = Vec::new();
+    let mut record = Vec::new();
+    let mut field = String::new();
+    let mut quoted = false;
+    let mut chars = data.chars().peekable();
+    while let Some(c) = chars.next() {
+        match c {
+            '"' if quoted && chars.peek() == Some(&'"') => {
+                chars.next();
+                field.push('"');
+            }
+            '"' => quoted = !quoted,
+            ',' if !quoted => record.push(std::mem::take(&mut field)),
+            '\r' if !quoted => {}
+            '\n' if !quoted => {
+                record.push(std::mem::take(&mut field));
+                records.push(std::mem::take(&mut record));
+            }
+            c => field.push(c),
+        }
+    }
+    if !field.is_empty() || !record.is_empty() {
+        record.push(field);
+        records.push(record);
+    }
+    records
+}
+
+fn csv_row(fields: impl IntoIterator<Item = String>) -> String {
+    let escaped: Vec<String> = fields
+        .into_iter()
+        .map(|field| {
+            if field.contains([',', '"', '\n', '\r']) {
+                format!("\"{}\"", field.replace('"', "\"\""))
+            } else {
+                field
+            }
+        })
+        .collect();
+    format!("{}\n", escaped.join(","))
+}
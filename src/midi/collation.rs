@@ -0,0 +1,152 @@
+use std::cmp::Ordering;
+use std::fmt;
+
+use icu_normalizer::DecomposingNormalizerBorrowed;
+use serde::{Deserialize, Serialize};
+
+/// How titles are put in order.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub enum TitleCollation {
+    /// Lower-cased code point order: accented letters sort after `z` and
+    /// "Etude 10" before "Etude 2".
+    Simple,
+    /// Accents and width are ignored and numbers compare by value. Latin and
+    /// other alphabets come first, then kana, hangul and Chinese characters,
+    /// the last in radical-stroke order.
+    #[default]
+    Unicode,
+}
+
+impl TitleCollation {
+    pub const ALL: [TitleCollation; 2] = [TitleCollation::Simple, TitleCollation::Unicode];
+
+    /// A key that orders `title` among others; compare keys rather than
+    /// titles, and compute each once when sorting.
+    pub fn key(self, title: &str) -> SortKey {
+        let tiebreak = title.to_lowercase();
+        let segments = match self {
+            TitleCollation::Simple => vec![Segment::Text(
+                tiebreak.chars().map(|c| (Script::Other, c)).collect(),
+            )],
+            TitleCollation::Unicode => unicode_segments(title),
+        };
+        SortKey { segments, tiebreak }
+    }
+
+    pub fn compare(self, a: &str, b: &str) -> Ordering {
+        self.key(a).cmp(&self.key(b))
+    }
+}
+
+impl fmt::Display for TitleCollation {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            TitleCollation::Simple => "Simple",
+            TitleCollation::Unicode => "Language-aware",
+        })
+    }
+}
+
+/// Where a title belongs in order; see [`TitleCollation::key`].
+#[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord)]
+pub struct SortKey {
+    segments: Vec<Segment>,
+    /// Keeps titles that differ only in accents or case apart, in a fixed
+    /// order.
+    tiebreak: String,
+}
+
+/// Runs of digits and of other characters. Numbers come before words at
+/// the same position, as in most catalogues.
+#[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord)]
+enum Segment {
+    /// Digits without leading zeros; shorter is smaller.
+    Number {
+        digits: usize,
+        value: String,
+    },
+    Text(Vec<(Script, char)>),
+}
+
+/// Groups of writing systems, in the order they sort.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+enum Script {
+    Other,
+    Kana,
+    Hangul,
+    /// CJK Unified Ideographs, whose code points follow radical and stroke
+    /// count.
+    Han,
+}
+
+fn script(c: char) -> Script {
+    match c {
+        '\u{3040}'..='\u{30FF}' | '\u{31F0}'..='\u{31FF}' => Script::Kana,
+        '\u{1100}'..='\u{11FF}' | '\u{3130}'..='\u{318F}' | '\u{AC00}'..='\u{D7AF}' => {
+            Script::Hangul
+        }
+        '\u{3400}'..='\u{4DBF}'
+        | '\u{4E00}'..='\u{9FFF}'
+        | '\u{F900}'..='\u{FAFF}'
+        | '\u{20000}'..='\u{3134F}' => Script::Han,
+        _ => Script::Other,
+    }
+}
+
+fn is_combining_mark(c: char) -> bool {
+    matches!(
+        c,
+        '\u{0300}'..='\u{036F}'
+            | '\u{1AB0}'..='\u{1AFF}'
+            | '\u{1DC0}'..='\u{1DFF}'
+            | '\u{20D0}'..='\u{20FF}'
+            | '\u{FE20}'..='\u{FE2F}'
+            // Kana voicing marks, left apart by decomposition.
+            | '\u{3099}'..='\u{309A}'
+    )
+}
+
+/// Lower-cases `text` and drops accents and width differences, the way
+/// language-aware sorting sees it. Searches match on this too, so "etude"
+/// finds "Étude".
+pub fn fold(text: &str) -> String {
+    // Compatibility decomposition also folds full-width letters and digits
+    // into their usual forms.
+    DecomposingNormalizerBorrowed::new_nfkd()
+        .normalize(text)
+        .chars()
+        .filter(|c| !is_combining_mark(*c))
+        .flat_map(char::to_lowercase)
+        .collect()
+}
+
+fn unicode_segments(title: &str) -> Vec<Segment> {
+    let folded = fold(title);
+    let mut segments = Vec::new();
+    let mut chars = folded.chars().peekable();
+    while let Some(&c) = chars.peek() {
+        if c.is_ascii_digit() {
+            let mut value = String::new();
+            while let Some(digit) = chars.next_if(char::is_ascii_digit) {
+                value.push(digit);
+            }
+            let value = value.trim_start_matches('0').to_owned();
+            segments.push(Segment::Number {
+                digits: value.len(),
+                value,
+            });
+        } else {
+            let mut text = Vec::new();
+            while let Some(c) = chars.next_if(|c| !c.is_ascii_digit()) {
+                // Spacing and punctuation don't decide the order.
+                if c.is_alphanumeric() {
+                    text.push((script(c), c));
+                }
+            }
+            if !text.is_empty() {
+                segments.push(Segment::Text(text));
+            }
+        }
+    }
+    segments
+}
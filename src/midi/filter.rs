@@ -0,0 +1,466 @@
+use std::fmt;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::time::Duration;
+
+use anyhow::Result;
+use async_trait::async_trait;
+use serde::{Deserialize, Serialize};
+
+use super::sink::{MidiSink, SharedMidiSink, SinkStats};
+
+const CC_BANK_SELECT: u8 = 0;
+const CC_BANK_SELECT_LSB: u8 = 32;
+const CC_SUSTAIN: u8 = 64;
+const CC_SOFT_PEDAL: u8 = 67;
+const CC_VOLUME: u8 = 7;
+const CC_DATA_ENTRY: u8 = 6;
+const CC_DATA_ENTRY_LSB: u8 = 38;
+const CC_RPN_LSB: u8 = 100;
+const CC_RPN_MSB: u8 = 101;
+const PITCH_BEND_CENTER: i32 = 8192;
+/// Channel 10, numbered from 0.
+const PERCUSSION_CHANNEL: u8 = 9;
+const SYSEX_START: u8 = 0xF0;
+/// Starts an escape event, which carries arbitrary bytes such as a SysEx
+/// split over several events.
+const ESCAPE: u8 = 0xF7;
+/// How long instruments are given to reinitialise after a reset.
+const RESET_SETTLE: Duration = Duration::from_millis(100);
+
+/// What happens to one kind of message on its way to a device.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+pub enum FilterAction {
+    #[default]
+    Pass,
+    Drop,
+    /// Caps the value at the given 0–127 limit. For pitch bend the limit
+    /// scales the maximum deflection either side of centre.
+    Clamp(u8),
+    /// Scales the value to the given percentage, up to 100. For pitch bend
+    /// the deflection from centre is scaled, which narrows every bend for
+    /// instruments with a wider range than the song expects.
+    Scale(u8),
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum FilteredControl {
+    Sustain,
+    SoftPedal,
+    BankSelect,
+    PitchBend,
+}
+
+impl FilteredControl {
+    pub const ALL: [FilteredControl; 4] = [
+        FilteredControl::Sustain,
+        FilteredControl::SoftPedal,
+        FilteredControl::BankSelect,
+        FilteredControl::PitchBend,
+    ];
+}
+
+impl fmt::Display for FilteredControl {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let label = match self {
+            FilteredControl::Sustain => "Sustain pedal (CC64)",
+            FilteredControl::SoftPedal => "Soft pedal (CC67)",
+            FilteredControl::BankSelect => "Bank select (CC0/32)",
+            FilteredControl::PitchBend => "Pitch bend",
+        };
+        write!(f, "{label}")
+    }
+}
+
+/// A system exclusive reset that puts an instrument into a known state.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum DeviceReset {
+    /// General MIDI System On.
+    Gm,
+    /// Roland GS Reset.
+    Gs,
+    /// Yamaha XG System On.
+    Xg,
+}
+
+impl DeviceReset {
+    pub const ALL: [DeviceReset; 3] = [DeviceReset::Gm, DeviceReset::Gs, DeviceReset::Xg];
+
+    pub fn message(self) -> &'static [u8] {
+        match self {
+            DeviceReset::Gm => &[0xF0, 0x7E, 0x7F, 0x09, 0x01, 0xF7],
+            DeviceReset::Gs => &[
+                0xF0, 0x41, 0x10, 0x42, 0x12, 0x40, 0x00, 0x7F, 0x00, 0x41, 0xF7,
+            ],
+            DeviceReset::Xg => &[0xF0, 0x43, 0x10, 0x4C, 0x00, 0x00, 0x7E, 0x00, 0xF7],
+        }
+    }
+}
+
+impl fmt::Display for DeviceReset {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let label = match self {
+            DeviceReset::Gm => "GM reset",
+            DeviceReset::Gs => "GS reset",
+            DeviceReset::Xg => "XG reset",
+        };
+        write!(f, "{label}")
+    }
+}
+
+/// How the velocity of each note is changed before it reaches a device.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+pub enum VelocityCurve {
+    #[default]
+    Linear,
+    /// Lifts quiet notes, for instruments that sound too timid.
+    Soft,
+    /// Holds back quiet notes, for instruments that sound too loud.
+    Hard,
+    /// Plays every note at the given velocity.
+    Fixed(u8),
+}
+
+impl VelocityCurve {
+    pub const DEFAULT_FIXED: u8 = 100;
+
+    pub fn apply(self, velocity: u8) -> u8 {
+        let exponent = match self {
+            VelocityCurve::Linear => return velocity,
+            VelocityCurve::Fixed(fixed) => return fixed.clamp(1, 127),
+            VelocityCurve::Soft => 0.6,
+            VelocityCurve::Hard => 1.6,
+        };
+        let scaled = (velocity.min(127) as f64 / 127.0).powf(exponent) * 127.0;
+        (scaled.round() as u8).max(1)
+    }
+}
+
+impl fmt::Display for VelocityCurve {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            VelocityCurve::Linear => f.write_str("Linear"),
+            VelocityCurve::Soft => f.write_str("Soft"),
+            VelocityCurve::Hard => f.write_str("Hard"),
+            VelocityCurve::Fixed(velocity) => write!(f, "Fixed {velocity}"),
+        }
+    }
+}
+
+/// Ceilings for playing quietly, e.g. late at night on a digital piano
+/// whose keys and speakers carry through walls.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct QuietLimit {
+    /// Louder notes play at this velocity.
+    pub max_velocity: u8,
+    /// Channel volume (CC7) is held at or below this, and every channel is
+    /// set to it when connecting. `None` leaves volume to the song.
+    pub max_volume: Option<u8>,
+}
+
+impl QuietLimit {
+    pub const DEFAULT_MAX_VOLUME: u8 = 80;
+}
+
+impl Default for QuietLimit {
+    fn default() -> Self {
+        Self {
+            max_velocity: 60,
+            max_volume: None,
+        }
+    }
+}
+
+/// The channel each of the 16 channels is sent on, numbered from 0.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ChannelMap(pub [u8; 16]);
+
+impl ChannelMap {
+    pub fn target(&self, channel: u8) -> u8 {
+        self.0[(channel & 0x0F) as usize] & 0x0F
+    }
+
+    pub fn is_identity(&self) -> bool {
+        *self == ChannelMap::default()
+    }
+}
+
+impl Default for ChannelMap {
+    fn default() -> Self {
+        Self(std::array::from_fn(|channel| channel as u8))
+    }
+}
+
+/// Per-device rules for controllers that some instruments mishandle.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+#[serde(default)]
+pub struct OutputFilter {
+    pub sustain: FilterAction,
+    pub soft_pedal: FilterAction,
+    pub bank_select: FilterAction,
+    pub pitch_bend: FilterAction,
+    /// Drops SysEx and escape events, e.g. for instruments that lock up on
+    /// a manufacturer's SysEx.
+    pub drop_sysex: bool,
+    /// Sent before anything else on a connection, so every song starts
+    /// from the same state.
+    pub reset: Option<DeviceReset>,
+    pub velocity_curve: VelocityCurve,
+    pub channel_map: ChannelMap,
+    /// Pitch bend range in semitones, set on every channel with the
+    /// standard RPN sequence after the reset, for instruments that keep a
+    /// range other than the General MIDI two semitones between songs.
+    pub bend_range: Option<u8>,
+    /// Drops channel messages sent on channel 10, before any remapping.
+    pub drop_percussion: bool,
+    /// Every program change selects this program instead, and every
+    /// channel is set to it when connecting.
+    pub program: Option<u8>,
+    /// Set on each connection from the app-wide quiet mode rather than kept
+    /// with a device's rules.
+    #[serde(skip)]
+    pub quiet: Option<QuietLimit>,
+}
+
+impl OutputFilter {
+    pub const MAX_BEND_RANGE: u8 = 24;
+    /// How far Piano Mode lets a bend go, out of 127.
+    pub const PIANO_MODE_BEND_LIMIT: u8 = 32;
+
+    /// These rules with Piano Mode on top, for instruments that only play
+    /// piano on channel 1: every channel merged onto it, drums dropped,
+    /// the first program forced in bank 0 and bends kept small.
+    pub fn piano_mode(self) -> Self {
+        let pitch_bend = match self.pitch_bend {
+            FilterAction::Drop => FilterAction::Drop,
+            _ => FilterAction::Clamp(Self::PIANO_MODE_BEND_LIMIT),
+        };
+        Self {
+            channel_map: ChannelMap([0; 16]),
+            drop_percussion: true,
+            program: Some(0),
+            bank_select: FilterAction::Drop,
+            pitch_bend,
+            ..self
+        }
+    }
+
+    pub fn is_passthrough(&self) -> bool {
+        *self == OutputFilter::default()
+    }
+
+    pub fn action(&self, control: FilteredControl) -> FilterAction {
+        match control {
+            FilteredControl::Sustain => self.sustain,
+            FilteredControl::SoftPedal => self.soft_pedal,
+            FilteredControl::BankSelect => self.bank_select,
+            FilteredControl::PitchBend => self.pitch_bend,
+        }
+    }
+
+    pub fn set_action(&mut self, control: FilteredControl, action: FilterAction) {
+        match control {
+            FilteredControl::Sustain => self.sustain = action,
+            FilteredControl::SoftPedal => self.soft_pedal = action,
+            FilteredControl::BankSelect => self.bank_select = action,
+            FilteredControl::PitchBend => self.pitch_bend = action,
+        }
+    }
+
+    /// The message as it should reach the device, or `None` to drop it.
+    pub fn apply(&self, data: &[u8]) -> Option<Vec<u8>> {
+        match data.first() {
+            Some(&(SYSEX_START | ESCAPE)) if self.drop_sysex => return None,
+            Some(&status @ 0x80..=0xEF)
+                if self.drop_percussion && status & 0x0F == PERCUSSION_CHANNEL =>
+            {
+                return None;
+            }
+            _ => {}
+        }
+        let (Some(&status), Some(&first), Some(&second)) = (data.first(), data.get(1), data.get(2))
+        else {
+            return Some(self.remap_channel(data));
+        };
+        let status = match status {
+            0x80..=0xEF => status & 0xF0 | self.channel_map.target(status & 0x0F),
+            _ => status,
+        };
+        if status & 0xF0 == 0x90 && second > 0 {
+            let mut velocity = self.velocity_curve.apply(second);
+            if let Some(quiet) = self.quiet {
+                velocity = velocity.min(quiet.max_velocity.max(1));
+            }
+            return Some(vec![status, first, velocity]);
+        }
+        if status & 0xF0 == 0xB0
+            && first == CC_VOLUME
+            && let Some(max_volume) = self.quiet.and_then(|quiet| quiet.max_volume)
+        {
+            return Some(vec![status, first, second.min(max_volume)]);
+        }
+        let control = match (status & 0xF0, first) {
+            (0xB0, CC_SUSTAIN) => FilteredControl::Sustain,
+            (0xB0, CC_SOFT_PEDAL) => FilteredControl::SoftPedal,
+            (0xB0, CC_BANK_SELECT | CC_BANK_SELECT_LSB) => FilteredControl::BankSelect,
+            (0xE0, _) => FilteredControl::PitchBend,
+            _ => return Some(with_status(data, status)),
+        };
+        match self.action(control) {
+            FilterAction::Pass => Some(with_status(data, status)),
+            FilterAction::Drop => None,
+            FilterAction::Clamp(limit) if control == FilteredControl::PitchBend => {
+                let limit = limit.min(127) as i32 * (PITCH_BEND_CENTER - 1) / 127;
+                let bend = ((second as i32) << 7 | first as i32) - PITCH_BEND_CENTER;
+                let value = (bend.clamp(-limit, limit) + PITCH_BEND_CENTER) as u16;
+                Some(vec![status, (value & 0x7F) as u8, (value >> 7) as u8])
+            }
+            FilterAction::Clamp(limit) => Some(vec![status, first, second.min(limit)]),
+            FilterAction::Scale(percent) if control == FilteredControl::PitchBend => {
+                let bend = ((second as i32) << 7 | first as i32) - PITCH_BEND_CENTER;
+                let scaled = bend * percent.min(100) as i32 / 100;
+                let value = (scaled + PITCH_BEND_CENTER) as u16;
+                Some(vec![status, (value & 0x7F) as u8, (value >> 7) as u8])
+            }
+            FilterAction::Scale(percent) => Some(vec![
+                status,
+                first,
+                (second as u16 * percent.min(100) as u16 / 100) as u8,
+            ]),
+        }
+    }
+
+    /// The RPN messages that set [`OutputFilter::bend_range`] on every
+    /// channel, or none without a range. The RPN is deselected afterwards so
+    /// stray data entry in a song changes nothing.
+    pub fn bend_range_messages(&self) -> Vec<Vec<u8>> {
+        let mut messages = Vec::new();
+        if let Some(range) = self.bend_range {
+            let range = range.min(Self::MAX_BEND_RANGE);
+            for channel in 0..16u8 {
+                let status = 0xB0 | channel;
+                messages.extend([
+                    vec![status, CC_RPN_MSB, 0],
+                    vec![status, CC_RPN_LSB, 0],
+                    vec![status, CC_DATA_ENTRY, range],
+                    vec![status, CC_DATA_ENTRY_LSB, 0],
+                    vec![status, CC_RPN_MSB, 127],
+                    vec![status, CC_RPN_LSB, 127],
+                ]);
+            }
+        }
+        messages
+    }
+
+    /// [`OutputFilter::program`] on every channel, or none without one.
+    pub fn program_messages(&self) -> Vec<Vec<u8>> {
+        match self.program {
+            Some(program) => (0..16u8)
+                .map(|channel| vec![0xC0 | channel, program.min(127)])
+                .collect(),
+            None => Vec::new(),
+        }
+    }
+
+    /// Channel volume at the quiet mode's ceiling on every channel, or none
+    /// when quiet mode leaves volume alone.
+    pub fn quiet_volume_messages(&self) -> Vec<Vec<u8>> {
+        match self.quiet.and_then(|quiet| quiet.max_volume) {
+            Some(volume) => (0..16u8)
+                .map(|channel| vec![0xB0 | channel, CC_VOLUME, volume.min(127)])
+                .collect(),
+            None => Vec::new(),
+        }
+    }
+
+    /// Program changes and channel pressure only have two bytes.
+    fn remap_channel(&self, data: &[u8]) -> Vec<u8> {
+        match data.first() {
+            Some(&status @ 0x80..=0xEF) => {
+                let mut data =
+                    with_status(data, status & 0xF0 | self.channel_map.target(status & 0x0F));
+                if status & 0xF0 == 0xC0
+                    && let (Some(program), Some(selected)) = (self.program, data.get_mut(1))
+                {
+                    *selected = program.min(127);
+                }
+                data
+            }
+            _ => data.to_vec(),
+        }
+    }
+}
+
+fn with_status(data: &[u8], status: u8) -> Vec<u8> {
+    let mut data = data.to_vec();
+    data[0] = status;
+    data
+}
+
+/// Applies an [`OutputFilter`] to everything sent to the wrapped sink. The
+/// filter's reset, pitch bend range, program and quiet volume go out ahead
+/// of the first message.
+pub struct FilteredSink {
+    inner: SharedMidiSink,
+    filter: OutputFilter,
+    setup_pending: AtomicBool,
+}
+
+impl FilteredSink {
+    pub fn new(inner: SharedMidiSink, filter: OutputFilter) -> Self {
+        Self {
+            inner,
+            filter,
+            setup_pending: AtomicBool::new(
+                filter.reset.is_some()
+                    || filter.bend_range.is_some()
+                    || filter.program.is_some()
+                    || filter.quiet.is_some_and(|quiet| quiet.max_volume.is_some()),
+            ),
+        }
+    }
+
+    async fn send_pending_setup(&self) -> Result<()> {
+        if !self.setup_pending.swap(false, Ordering::AcqRel) {
+            return Ok(());
+        }
+        if let Some(reset) = self.filter.reset {
+            self.inner.send(reset.message()).await?;
+            tokio::time::sleep(RESET_SETTLE).await;
+        }
+        let mut setup = self.filter.bend_range_messages();
+        setup.extend(self.filter.program_messages());
+        setup.extend(self.filter.quiet_volume_messages());
+        if !setup.is_empty() {
+            self.inner.send_batch(&setup).await?;
+        }
+        Ok(())
+    }
+}
+
+#[async_trait]
+impl MidiSink for FilteredSink {
+    async fn send(&self, data: &[u8]) -> Result<()> {
+        self.send_pending_setup().await?;
+        match self.filter.apply(data) {
+            Some(data) => self.inner.send(&data).await,
+            None => Ok(()),
+        }
+    }
+
+    async fn send_batch(&self, messages: &[Vec<u8>]) -> Result<()> {
+        self.send_pending_setup().await?;
+        let filtered: Vec<Vec<u8>> = messages
+            .iter()
+            .filter_map(|message| self.filter.apply(message))
+            .collect();
+        if filtered.is_empty() {
+            return Ok(());
+        }
+        self.inner.send_batch(&filtered).await
+    }
+
+    fn stats(&self) -> Option<SinkStats> {
+        self.inner.stats()
+    }
+}
@@ -0,0 +1,49 @@
+use std::collections::HashMap;
+
+use serde::{Deserialize, Serialize};
+
+/// Colors a tree folder can be marked with.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum FolderColor {
+    Red,
+    Orange,
+    Yellow,
+    Green,
+    Blue,
+    Purple,
+    Grey,
+}
+
+impl FolderColor {
+    pub const ALL: [FolderColor; 7] = [
+        FolderColor::Red,
+        FolderColor::Orange,
+        FolderColor::Yellow,
+        FolderColor::Green,
+        FolderColor::Blue,
+        FolderColor::Purple,
+        FolderColor::Grey,
+    ];
+}
+
+/// How a tree folder is marked out, for telling composers or levels apart
+/// at a glance.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct FolderLabel {
+    #[serde(default)]
+    pub color: Option<FolderColor>,
+    /// Shown before the folder name; a few characters at most.
+    #[serde(default)]
+    pub emoji: String,
+}
+
+impl FolderLabel {
+    pub const MAX_EMOJI_CHARS: usize = 4;
+}
+
+/// Adds the labels of `imported` for folders that have none in `labels`.
+pub fn merge(labels: &mut HashMap<String, FolderLabel>, imported: HashMap<String, FolderLabel>) {
+    for (folder, label) in imported {
+        labels.entry(folder).or_insert(label);
+    }
+}
@@ -0,0 +1,307 @@
+use std::fmt;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use anyhow::{Context, Result};
+use serde::{Deserialize, Serialize};
+
+use super::sequence;
+
+const REJECTED_DIR: &str = "_rejected";
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, Default)]
+pub enum InboxGrouping {
+    #[default]
+    Composer,
+    FirstLetter,
+}
+
+impl fmt::Display for InboxGrouping {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let label = match self {
+            InboxGrouping::Composer => "By composer",
+            InboxGrouping::FirstLetter => "By first letter",
+        };
+        write!(f, "{label}")
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct WatchFolderConfig {
+    pub inbox: PathBuf,
+    pub library_root: PathBuf,
+    #[serde(default)]
+    pub grouping: InboxGrouping,
+}
+
+#[derive(Debug, Clone)]
+pub struct InboxImport {
+    pub path: PathBuf,
+    pub library_path: Vec<String>,
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct InboxReport {
+    pub imported: Vec<InboxImport>,
+    pub rejected: Vec<PathBuf>,
+}
+
+/// Moves every valid MIDI file found in the inbox into the configured library
+/// root, renaming it per the cleanup rules. Files that fail to parse are moved
+/// aside into a `_rejected` folder so they are not retried on every scan.
+pub fn import_inbox(config: &WatchFolderConfig) -> Result<InboxReport> {
+    let mut report = InboxReport::default();
+    if !config.inbox.is_dir() {
+        return Ok(report);
+    }
+
+    let listing = fs::read_dir(&config.inbox)
+        .with_context(|| format!("failed to read inbox {}", config.inbox.display()))?;
+    let mut candidates: Vec<PathBuf> = listing
+        .filter_map(|entry| entry.ok().map(|entry| entry.path()))
+        .filter(|path| path.is_file() && is_midi_file(path))
+        .collect();
+    candidates.sort();
+
+    for source in candidates {
+        if let Err(err) = sequence::inspect_file(&source) {
+            log::warn!("rejecting inbox file {}: {err:?}", source.display());
+            match move_into(
+                &source,
+                &config.inbox.join(REJECTED_DIR),
+                &file_name(&source),
+            ) {
+                Ok(_) => report.rejected.push(source),
+                Err(err) => log::warn!("failed to set aside {}: {err:?}", source.display()),
+            }
+            continue;
+        }
+
+        let stem = source
+            .file_stem()
+            .and_then(|stem| stem.to_str())
+            .unwrap_or_default();
+        let title = clean_title(stem);
+        let folder = group_folder(&title, config.grouping);
+        let extension = source
+            .extension()
+            .and_then(|ext| ext.to_str())
+            .map(|ext| ext.to_lowercase())
+            .unwrap_or_else(|| "mid".into());
+        let target_dir = config.library_root.join(&folder);
+        match move_into(&source, &target_dir, &format!("{title}.{extension}")) {
+            Ok(path) => report.imported.push(InboxImport {
+                path,
+                library_path: vec![folder],
+            }),
+            Err(err) => log::warn!("failed to import {}: {err:?}", source.display()),
+        }
+    }
+
+    Ok(report)
+}
+
+/// Lists the files previously imported into the library root so they can be
+/// re-added to the library on startup.
+pub fn scan_library_root(config: &WatchFolderConfig) -> Result<Vec<InboxImport>> {
+    let mut found = Vec::new();
+    if !config.library_root.is_dir() {
+        return Ok(found);
+    }
+
+    let folders = fs::read_dir(&config.library_root).with_context(|| {
+        format!(
+            "failed to read library folder {}",
+            config.library_root.display()
+        )
+    })?;
+    for folder in folders.filter_map(|entry| entry.ok()) {
+        let folder_path = folder.path();
+        if !folder_path.is_dir() {
+            continue;
+        }
+        let folder_name = folder.file_name().to_string_lossy().into_owned();
+        let files = match fs::read_dir(&folder_path) {
+            Ok(files) => files,
+            Err(err) => {
+                log::warn!("failed to read {}: {err}", folder_path.display());
+                continue;
+            }
+        };
+        for path in files.filter_map(|entry| entry.ok().map(|entry| entry.path())) {
+            if path.is_file() && is_midi_file(&path) {
+                found.push(InboxImport {
+                    path,
+                    library_path: vec![folder_name.clone()],
+                });
+            }
+        }
+    }
+
+    found.sort_by(|a, b| a.path.cmp(&b.path));
+    Ok(found)
+}
+
+/// Recursively lists the MIDI files below a music folder, grouping each under
+/// the folder's name followed by its sub-folders.
+pub fn scan_music_folder(root: &Path) -> Result<Vec<InboxImport>> {
+    let root_name = root
+        .file_name()
+        .map(|name| name.to_string_lossy().into_owned())
+        .unwrap_or_else(|| root.display().to_string());
+    let mut found = Vec::new();
+    let mut pending = vec![(root.to_path_buf(), vec![root_name])];
+    while let Some((directory, library_path)) = pending.pop() {
+        let listing = match fs::read_dir(&directory) {
+            Ok(listing) => listing,
+            Err(err) if directory == root => {
+                return Err(err)
+                    .with_context(|| format!("failed to read music folder {}", root.display()));
+            }
+            Err(err) => {
+                log::warn!("failed to read {}: {err}", directory.display());
+                continue;
+            }
+        };
+        for entry in listing.filter_map(|entry| entry.ok()) {
+            let path = entry.path();
+            if path.is_dir() {
+                let mut nested = library_path.clone();
+                nested.push(entry.file_name().to_string_lossy().into_owned());
+                pending.push((path, nested));
+            } else if is_midi_file(&path) {
+                found.push(InboxImport {
+                    path,
+                    library_path: library_path.clone(),
+                });
+            }
+        }
+    }
+
+    found.sort_by(|a, b| a.path.cmp(&b.path));
+    Ok(found)
+}
+
+pub fn is_midi_file(path: &Path) -> bool {
+    path.extension()
+        .and_then(|ext| ext.to_str())
+        .map(|ext| ext.eq_ignore_ascii_case("mid") || ext.eq_ignore_ascii_case("midi"))
+        .unwrap_or(false)
+}
+
+/// Normalizes a downloaded file stem: underscores become spaces, runs of
+/// whitespace collapse, and duplicate markers such as "(1)" or " copy" are
+/// dropped.
+pub fn clean_title(stem: &str) -> String {
+    let mut title = stem
+        .replace('_', " ")
+        .split_whitespace()
+        .collect::<Vec<_>>()
+        .join(" ");
+
+    loop {
+        let trimmed = title.trim_end();
+        let stripped = if let Some(rest) = trimmed.strip_suffix(" copy") {
+            Some(rest)
+        } else if trimmed.ends_with(')') {
+            trimmed.rfind(" (").and_then(|open| {
+                let inner = &trimmed[open + 2..trimmed.len() - 1];
+                (!inner.is_empty() && inner.chars().all(|c| c.is_ascii_digit()))
+                    .then(|| &trimmed[..open])
+            })
+        } else {
+            None
+        };
+        match stripped {
+            Some(rest) if !rest.trim().is_empty() => title = rest.trim_end().to_owned(),
+            _ => break,
+        }
+    }
+
+    if title.is_empty() {
+        "Untitled".into()
+    } else {
+        title
+    }
+}
+
+/// Files follow the "Title - Composer" naming used by the bundled assets, so
+/// the composer is whatever follows the last separator.
+pub fn detect_composer(title: &str) -> Option<String> {
+    let (_, composer) = title.rsplit_once(" - ")?;
+    let composer = composer.trim();
+    (!composer.is_empty()).then(|| composer.to_owned())
+}
+
+fn group_folder(title: &str, grouping: InboxGrouping) -> String {
+    if grouping == InboxGrouping::Composer
+        && let Some(composer) = detect_composer(title)
+    {
+        return folder_name(&composer);
+    }
+    title
+        .chars()
+        .find(|c| c.is_alphanumeric())
+        .map(|c| c.to_uppercase().collect())
+        .unwrap_or_else(|| "#".into())
+}
+
+/// A single directory name for `name`, which comes from a downloaded file
+/// name: separators and drive colons become spaces, and `.`/`..` cannot
+/// escape the root.
+fn folder_name(name: &str) -> String {
+    let name = name
+        .replace(['/', '\\', ':'], " ")
+        .split_whitespace()
+        .collect::<Vec<_>>()
+        .join(" ");
+    if name.chars().all(|c| c == '.' || c.is_whitespace()) {
+        "Unknown".into()
+    } else {
+        name
+    }
+}
+
+fn file_name(path: &Path) -> String {
+    path.file_name()
+        .map(|name| name.to_string_lossy().into_owned())
+        .unwrap_or_else(|| "unnamed.mid".into())
+}
+
+fn move_into(source: &Path, directory: &Path, file_name: &str) -> Result<PathBuf> {
+    fs::create_dir_all(directory)
+        .with_context(|| format!("failed to create {}", directory.display()))?;
+
+    let mut target = directory.join(file_name);
+    let stem = target
+        .file_stem()
+        .map(|stem| stem.to_string_lossy().into_owned())
+        .unwrap_or_default();
+    let extension = target
+        .extension()
+        .map(|ext| ext.to_string_lossy().into_owned());
+    let mut counter = 2;
+    while target.exists() {
+        let candidate = match &extension {
+            Some(ext) => format!("{stem} ({counter}).{ext}"),
+            None => format!("{stem} ({counter})"),
+        };
+        target = directory.join(candidate);
+        counter += 1;
+    }
+
+    if fs::rename(source, &target).is_err() {
+        // Renaming fails across filesystems; fall back to copy + delete.
+        fs::copy(source, &target).with_context(|| {
+            format!(
+                "failed to copy {} to {}",
+                source.display(),
+                target.display()
+            )
+        })?;
+        fs::remove_file(source)
+            .with_context(|| format!("failed to remove {}", source.display()))?;
+    }
+
+    Ok(target)
+}
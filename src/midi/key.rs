@@ -0,0 +1,233 @@
+use std::fmt;
+use std::path::Path;
+
+use anyhow::{Context, Result};
+use midly::{MetaMessage, MidiMessage, Smf, TrackEventKind};
+use serde::{Deserialize, Serialize};
+
+use super::library::read_midi_file;
+use super::sequence::check_timecode;
+
+const PITCH_NAMES: [&str; 12] = [
+    "C", "C#", "D", "Eb", "E", "F", "F#", "G", "Ab", "A", "Bb", "B",
+];
+
+// Krumhansl-Kessler key profiles, indexed from the tonic.
+const MAJOR_PROFILE: [f64; 12] = [
+    6.35, 2.23, 3.48, 2.33, 4.38, 4.09, 2.52, 5.19, 2.39, 3.66, 2.29, 2.88,
+];
+const MINOR_PROFILE: [f64; 12] = [
+    6.33, 2.68, 3.52, 5.38, 2.60, 3.53, 2.54, 4.75, 3.98, 2.69, 3.34, 3.17,
+];
+
+const PERCUSSION_CHANNEL: u8 = 9;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct MusicalKey {
+    /// Pitch class of the tonic, 0 = C.
+    pub tonic: u8,
+    pub minor: bool,
+}
+
+impl MusicalKey {
+    /// Position of the key (or its relative major) on the circle of fifths,
+    /// with C major / A minor at 0.
+    pub fn fifths_position(&self) -> u8 {
+        let major_tonic = if self.minor {
+            (self.tonic + 3) % 12
+        } else {
+            self.tonic
+        };
+        (major_tonic * 7) % 12
+    }
+
+    pub fn fifths_distance(&self, other: &MusicalKey) -> u8 {
+        let diff = self.fifths_position().abs_diff(other.fifths_position());
+        diff.min(12 - diff)
+    }
+
+    pub fn transposed(&self, semitones: i8) -> MusicalKey {
+        MusicalKey {
+            tonic: (self.tonic as i16 + semitones as i16).rem_euclid(12) as u8,
+            minor: self.minor,
+        }
+    }
+}
+
+impl fmt::Display for MusicalKey {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let mode = if self.minor { "minor" } else { "major" };
+        write!(f, "{} {mode}", PITCH_NAMES[self.tonic as usize])
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+pub enum KeyMatchMode {
+    #[default]
+    Off,
+    Reorder,
+    ReorderAndTranspose,
+}
+
+impl KeyMatchMode {
+    pub const ALL: [KeyMatchMode; 3] = [
+        KeyMatchMode::Off,
+        KeyMatchMode::Reorder,
+        KeyMatchMode::ReorderAndTranspose,
+    ];
+}
+
+impl fmt::Display for KeyMatchMode {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let label = match self {
+            KeyMatchMode::Off => "Off",
+            KeyMatchMode::Reorder => "Reorder",
+            KeyMatchMode::ReorderAndTranspose => "Reorder + transpose",
+        };
+        write!(f, "{label}")
+    }
+}
+
+/// Uses the file's first key signature when present, otherwise estimates the
+/// key from the pitch-class distribution of all non-percussion notes.
+pub fn detect_key(path: &Path) -> Result<Option<MusicalKey>> {
+    let contents = read_midi_file(path)?;
+    check_timecode(&contents)?;
+    let smf = Smf::parse(&contents)
+        .with_context(|| format!("failed to parse MIDI file {}", path.display()))?;
+
+    let mut pitch_classes = [0f64; 12];
+    let mut signature: Option<(u64, MusicalKey)> = None;
+    for track in &smf.tracks {
+        let mut tick: u64 = 0;
+        for event in track {
+            tick += event.delta.as_int() as u64;
+            match event.kind {
+                TrackEventKind::Meta(MetaMessage::KeySignature(sharps, minor))
+                    if signature.is_none_or(|(at, _)| tick < at) =>
+                {
+                    signature = Some((tick, key_from_signature(sharps, minor)));
+                }
+                TrackEventKind::Midi {
+                    channel,
+                    message: MidiMessage::NoteOn { key, vel },
+                } if vel.as_int() > 0 && channel.as_int() != PERCUSSION_CHANNEL => {
+                    pitch_classes[key.as_int() as usize % 12] += 1.0;
+                }
+                _ => {}
+            }
+        }
+    }
+
+    if let Some((_, key)) = signature {
+        return Ok(Some(key));
+    }
+    Ok(estimate_key(&pitch_classes))
+}
+
+fn key_from_signature(sharps: i8, minor: bool) -> MusicalKey {
+    let major_tonic = (sharps as i16 * 7).rem_euclid(12) as u8;
+    if minor {
+        MusicalKey {
+            tonic: (major_tonic + 9) % 12,
+            minor: true,
+        }
+    } else {
+        MusicalKey {
+            tonic: major_tonic,
+            minor: false,
+        }
+    }
+}
+
+fn estimate_key(pitch_classes: &[f64; 12]) -> Option<MusicalKey> {
+    if pitch_classes.iter().all(|count| *count == 0.0) {
+        return None;
+    }
+    let mut best: Option<(f64, MusicalKey)> = None;
+    for tonic in 0..12u8 {
+        for (minor, profile) in [(false, &MAJOR_PROFILE), (true, &MINOR_PROFILE)] {
+            let rotated: Vec<f64> = (0..12)
+                .map(|offset| pitch_classes[(tonic as usize + offset) % 12])
+                .collect();
+            let score = correlation(&rotated, profile);
+            if best.is_none_or(|(best_score, _)| score > best_score) {
+                best = Some((score, MusicalKey { tonic, minor }));
+            }
+        }
+    }
+    best.map(|(_, key)| key)
+}
+
+fn correlation(a: &[f64], b: &[f64]) -> f64 {
+    let mean_a = a.iter().sum::<f64>() / a.len() as f64;
+    let mean_b = b.iter().sum::<f64>() / b.len() as f64;
+    let mut numerator = 0.0;
+    let mut sum_a = 0.0;
+    let mut sum_b = 0.0;
+    for (x, y) in a.iter().zip(b) {
+        numerator += (x - mean_a) * (y - mean_b);
+        sum_a += (x - mean_a).powi(2);
+        sum_b += (y - mean_b).powi(2);
+    }
+    let denominator = (sum_a * sum_b).sqrt();
+    if denominator == 0.0 {
+        0.0
+    } else {
+        numerator / denominator
+    }
+}
+
+/// Arranges queue items after the first so consecutive keys sit at most one
+/// step apart on the circle of fifths. Returns `(index, transpose)` pairs in
+/// play order; the first item always stays first and untransposed. Ties keep
+/// the original order, and pieces with an unknown key are never transposed
+/// and are deferred until no piece with a known key remains.
+pub fn plan_key_matched_order(keys: &[Option<MusicalKey>], mode: KeyMatchMode) -> Vec<(usize, i8)> {
+    if keys.is_empty() {
+        return Vec::new();
+    }
+    if mode == KeyMatchMode::Off {
+        return (0..keys.len()).map(|index| (index, 0)).collect();
+    }
+
+    let mut plan = vec![(0, 0)];
+    let mut current = keys[0];
+    let mut remaining: Vec<usize> = (1..keys.len()).collect();
+    while !remaining.is_empty() {
+        let position = match current {
+            Some(current) => remaining
+                .iter()
+                .enumerate()
+                .min_by_key(|(_, index)| {
+                    keys[**index]
+                        .map(|key| key.fifths_distance(&current))
+                        .unwrap_or(u8::MAX)
+                })
+                .map(|(position, _)| position)
+                .unwrap_or(0),
+            None => 0,
+        };
+        let index = remaining.remove(position);
+
+        let mut transpose = 0;
+        if let (Some(previous), Some(key)) = (current, keys[index])
+            && mode == KeyMatchMode::ReorderAndTranspose
+            && key.fifths_distance(&previous) > 1
+        {
+            transpose = smallest_transpose(key, previous);
+        }
+        plan.push((index, transpose));
+        if let Some(key) = keys[index] {
+            current = Some(key.transposed(transpose));
+        }
+    }
+    plan
+}
+
+fn smallest_transpose(key: MusicalKey, target: MusicalKey) -> i8 {
+    [0i8, 1, -1, 2, -2, 3, -3, 4, -4, 5, -5, 6]
+        .into_iter()
+        .find(|semitones| key.transposed(*semitones).fifths_distance(&target) <= 1)
+        .unwrap_or(0)
+}
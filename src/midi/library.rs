@@ -1,28 +1,69 @@
-use std::collections::HashMap;
+use std::collections::{HashMap, HashSet};
 use std::fs;
+use std::ops::ControlFlow;
 use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicU64, Ordering};
 
 use anyhow::{Context, Result};
 use once_cell::sync::Lazy;
-use serde::Deserialize;
+use serde::{Deserialize, Serialize};
 use uuid::Uuid;
 
 static ASSETS_DIR: Lazy<PathBuf> = Lazy::new(|| PathBuf::from("assets/midi"));
 static MANIFEST_PATH: Lazy<PathBuf> = Lazy::new(|| PathBuf::from("assets/midi_manifest.json"));
+#[cfg(feature = "embed-assets")]
+mod embedded {
+    use std::path::Path;
+
+    use include_dir::{Dir, include_dir};
+
+    static ASSETS: Dir<'static> = include_dir!("$CARGO_MANIFEST_DIR/assets/midi");
+    pub static MANIFEST: &str = include_str!(concat!(
+        env!("CARGO_MANIFEST_DIR"),
+        "/assets/midi_manifest.json"
+    ));
+
+    /// Contents of an embedded asset, looked up by its path under
+    /// `assets/midi`.
+    pub fn get(path: &Path) -> Option<&'static [u8]> {
+        let relative = path.strip_prefix(&*super::ASSETS_DIR).ok()?;
+        ASSETS.get_file(relative).map(|file| file.contents())
+    }
+}
+
+/// Source of [`MidiLibrary::revision`] numbers, shared by all libraries so
+/// that one replaced by another never looks unchanged.
+static NEXT_REVISION: AtomicU64 = AtomicU64::new(1);
+
+/// Namespace for the v5 entry ids. Preferences key ratings, tags, playlists
+/// and works by these ids, so changing the namespace or the names hashed
+/// under it orphans all of them.
+static ENTRY_NAMESPACE: Lazy<Uuid> =
+    Lazy::new(|| Uuid::from_u128(0x6f1c2a8e_93d4_4b7e_a0c5_2d8e41b9f307));
 
 #[derive(Debug, Clone, Copy, PartialEq, Eq)]
 pub enum MidiOrigin {
     Asset,
     Local,
+    /// Listed in a remote catalog and downloaded into the cache on first use.
+    Remote,
 }
 
 #[derive(Debug, Clone)]
 pub struct MidiEntry {
     pub id: Uuid,
+    /// The title shown for the entry: the user's, or else
+    /// [`MidiEntry::file_name`].
     pub name: String,
+    /// The name the entry came with, from the file stem or the catalog.
+    pub file_name: String,
+    pub composer: Option<String>,
     pub path: PathBuf,
     pub origin: MidiOrigin,
     pub library_path: Option<Vec<String>>,
+    /// Download location of remote entries; `path` is where the cached copy
+    /// lives once fetched.
+    pub source_url: Option<String>,
 }
 
 #[derive(Debug, Default, Clone)]
@@ -30,48 +71,289 @@ pub struct MidiLibrary {
     entries: Vec<MidiEntry>,
     index_by_id: HashMap<Uuid, usize>,
     index_by_path: HashMap<PathBuf, Uuid>,
+    /// Entry names as searches see them, folded once when added rather than
+    /// on every keystroke.
+    search_keys: HashMap<Uuid, String>,
+    /// Titles and composers the user gave, kept for entries not added yet.
+    details: HashMap<Uuid, SongDetails>,
+    revision: u64,
+}
+
+/// A title and composer given to an entry in place of what its file name
+/// says.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct SongDetails {
+    pub title: Option<String>,
+    pub composer: Option<String>,
 }
 
-#[derive(Debug, Clone, Deserialize)]
+impl SongDetails {
+    /// Drops blank fields and trims the rest.
+    pub fn trimmed(self) -> Self {
+        let trim = |field: Option<String>| {
+            field
+                .map(|value| value.trim().to_owned())
+                .filter(|value| !value.is_empty())
+        };
+        Self {
+            title: trim(self.title),
+            composer: trim(self.composer),
+        }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.title.is_none() && self.composer.is_none()
+    }
+}
+
+/// Search text folded the way [`MidiLibrary`] keeps entry names, so the
+/// same query can be matched against many entries cheaply.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct SearchQuery(String);
+
+impl SearchQuery {
+    pub fn new(text: &str) -> Self {
+        Self(super::collation::fold(text.trim()))
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.0.is_empty()
+    }
+}
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
 struct Manifest(Vec<String>);
 
+impl Manifest {
+    fn read(path: &Path) -> Result<Self> {
+        let manifest = fs::read_to_string(path)
+            .with_context(|| format!("failed to read {}", path.display()))?;
+        Self::parse(&manifest)
+    }
+
+    fn parse(manifest: &str) -> Result<Self> {
+        serde_json::from_str(manifest).context("failed to parse MIDI manifest")
+    }
+}
+
+/// Reads a library file. With the `embed-assets` feature, bundled assets
+/// missing from disk are served from the copy compiled into the binary.
+pub fn read_midi_file(path: &Path) -> Result<Vec<u8>> {
+    let result = fs::read(path);
+    #[cfg(feature = "embed-assets")]
+    if result.is_err()
+        && let Some(contents) = embedded::get(path)
+    {
+        return Ok(contents.to_vec());
+    }
+    result.with_context(|| format!("failed to read MIDI file {}", path.display()))
+}
+
+/// How far [`MidiLibrary::load_assets_in_chunks`] has got through the
+/// manifest.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct AssetProgress {
+    pub verified: usize,
+    pub total: usize,
+}
+
+/// Manifest items added or removed by [`regenerate_manifest`], as paths
+/// relative to the assets directory.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct ManifestChanges {
+    pub added: Vec<String>,
+    pub removed: Vec<String>,
+}
+
+impl ManifestChanges {
+    pub fn is_empty(&self) -> bool {
+        self.added.is_empty() && self.removed.is_empty()
+    }
+}
+
+/// Regenerates the bundled manifest from `assets/midi` on disk.
+pub fn rescan_assets() -> Result<ManifestChanges> {
+    regenerate_manifest(&ASSETS_DIR, &MANIFEST_PATH)
+}
+
+/// Rewrites `manifest_path` to list every MIDI file under `assets_dir`.
+/// Existing items keep their order; new files are appended sorted by path.
+pub fn regenerate_manifest(assets_dir: &Path, manifest_path: &Path) -> Result<ManifestChanges> {
+    let existing = if manifest_path.exists() {
+        Manifest::read(manifest_path)?
+    } else {
+        Manifest::default()
+    };
+
+    let mut on_disk = Vec::new();
+    let mut pending = vec![assets_dir.to_path_buf()];
+    while let Some(directory) = pending.pop() {
+        let listing = fs::read_dir(&directory)
+            .with_context(|| format!("failed to read {}", directory.display()))?;
+        for entry in listing.filter_map(|entry| entry.ok()) {
+            let path = entry.path();
+            if path.is_dir() {
+                pending.push(path);
+            } else if super::inbox::is_midi_file(&path)
+                && let Ok(relative) = path.strip_prefix(assets_dir)
+            {
+                let item = relative
+                    .components()
+                    .map(|part| part.as_os_str().to_string_lossy())
+                    .collect::<Vec<_>>()
+                    .join("/");
+                on_disk.push(item);
+            }
+        }
+    }
+    on_disk.sort();
+
+    let found: HashSet<&String> = on_disk.iter().collect();
+    let listed: HashSet<&String> = existing.0.iter().collect();
+    let mut changes = ManifestChanges::default();
+    let mut items = Vec::with_capacity(on_disk.len());
+    for item in &existing.0 {
+        if found.contains(item) {
+            items.push(item.clone());
+        } else {
+            changes.removed.push(item.clone());
+        }
+    }
+    for item in &on_disk {
+        if !listed.contains(item) {
+            items.push(item.clone());
+            changes.added.push(item.clone());
+        }
+    }
+
+    if !changes.is_empty() || !manifest_path.exists() {
+        let serialized = serde_json::to_string_pretty(&Manifest(items))
+            .context("failed to serialize manifest")?;
+        fs::write(manifest_path, serialized)
+            .with_context(|| format!("failed to write {}", manifest_path.display()))?;
+    }
+    Ok(changes)
+}
+
 impl MidiLibrary {
+    /// Loads the bundled assets from disk, falling back to the embedded copy
+    /// when the binary was built with `embed-assets` and runs outside the
+    /// repository.
     pub fn load_with_assets() -> Result<Self> {
         let mut library = MidiLibrary::default();
-        if MANIFEST_PATH.exists() {
-            let manifest = fs::read_to_string(&*MANIFEST_PATH)
-                .with_context(|| format!("failed to read {}", MANIFEST_PATH.display()))?;
-            let entries: Manifest =
-                serde_json::from_str(&manifest).context("failed to parse MIDI manifest")?;
-            for item in entries.0 {
-                let candidate = ASSETS_DIR.join(&item);
-                if candidate.exists() {
-                    let mut parts: Vec<String> = item
-                        .split('/')
-                        .map(|s| s.trim().to_owned())
-                        .filter(|s| !s.is_empty())
-                        .collect();
-                    let _ = library.insert_entry(
-                        candidate,
-                        MidiOrigin::Asset,
-                        if parts.len() > 1 {
-                            parts.pop();
-                            Some(parts)
-                        } else {
-                            None
-                        },
-                    );
-                } else {
-                    log::warn!("skipping missing asset entry {}", candidate.display());
-                }
-            }
+        Self::load_assets_in_chunks(usize::MAX, |chunk, _| {
+            library.append(chunk);
+            ControlFlow::Continue(())
+        })?;
+        Ok(library)
+    }
+
+    /// Loads the bundled assets like [`MidiLibrary::load_with_assets`], but
+    /// hands them over `chunk_size` manifest items at a time so a caller can
+    /// show them while slow paths are still being checked. Each chunk holds
+    /// only its own entries; returning `Break` stops the load.
+    pub fn load_assets_in_chunks(
+        chunk_size: usize,
+        on_chunk: impl FnMut(MidiLibrary, AssetProgress) -> ControlFlow<()>,
+    ) -> Result<()> {
+        #[cfg(feature = "embed-assets")]
+        if !MANIFEST_PATH.exists() {
+            let manifest = Manifest::parse(embedded::MANIFEST)?;
+            Self::load_manifest(
+                manifest,
+                &ASSETS_DIR,
+                |path| embedded::get(path).is_some(),
+                chunk_size,
+                on_chunk,
+            );
+            return Ok(());
+        }
+        Self::load_from_in_chunks(&ASSETS_DIR, &MANIFEST_PATH, chunk_size, on_chunk)
+    }
+
+    /// Loads the asset entries listed in `manifest_path`, resolved against
+    /// `assets_dir`. Listed files that are missing are skipped.
+    pub fn load_from(assets_dir: &Path, manifest_path: &Path) -> Result<Self> {
+        let mut library = MidiLibrary::default();
+        Self::load_from_in_chunks(assets_dir, manifest_path, usize::MAX, |chunk, _| {
+            library.append(chunk);
+            ControlFlow::Continue(())
+        })?;
+        Ok(library)
+    }
+
+    /// [`MidiLibrary::load_from`] in chunks, as for
+    /// [`MidiLibrary::load_assets_in_chunks`].
+    pub fn load_from_in_chunks(
+        assets_dir: &Path,
+        manifest_path: &Path,
+        chunk_size: usize,
+        mut on_chunk: impl FnMut(MidiLibrary, AssetProgress) -> ControlFlow<()>,
+    ) -> Result<()> {
+        if manifest_path.exists() {
+            let manifest = Manifest::read(manifest_path)?;
+            Self::load_manifest(
+                manifest,
+                assets_dir,
+                |path| path.exists(),
+                chunk_size,
+                on_chunk,
+            );
         } else {
             log::warn!(
                 "MIDI manifest not found at {}, starting with empty asset library",
-                MANIFEST_PATH.display()
+                manifest_path.display()
             );
+            let _ = on_chunk(MidiLibrary::default(), AssetProgress::default());
+        }
+        Ok(())
+    }
+
+    fn load_manifest(
+        manifest: Manifest,
+        assets_dir: &Path,
+        exists: impl Fn(&Path) -> bool,
+        chunk_size: usize,
+        mut on_chunk: impl FnMut(MidiLibrary, AssetProgress) -> ControlFlow<()>,
+    ) {
+        let total = manifest.0.len();
+        if total == 0 {
+            let _ = on_chunk(MidiLibrary::default(), AssetProgress::default());
+            return;
+        }
+        let mut library = MidiLibrary::default();
+        for (index, item) in manifest.0.into_iter().enumerate() {
+            let candidate = assets_dir.join(&item);
+            if exists(&candidate) {
+                let mut parts: Vec<String> = item
+                    .split('/')
+                    .map(|s| s.trim().to_owned())
+                    .filter(|s| !s.is_empty())
+                    .collect();
+                let id = Uuid::new_v5(&ENTRY_NAMESPACE, format!("asset:{item}").as_bytes());
+                let _ = library.insert_entry(
+                    id,
+                    candidate,
+                    MidiOrigin::Asset,
+                    if parts.len() > 1 {
+                        parts.pop();
+                        Some(parts)
+                    } else {
+                        None
+                    },
+                );
+            } else {
+                log::warn!("skipping missing asset entry {}", candidate.display());
+            }
+            let verified = index + 1;
+            if verified % chunk_size.max(1) == 0 || verified == total {
+                let progress = AssetProgress { verified, total };
+                if on_chunk(std::mem::take(&mut library), progress).is_break() {
+                    return;
+                }
+            }
         }
-        Ok(library)
     }
 
     pub fn entries(&self) -> &[MidiEntry] {
@@ -84,12 +366,125 @@ impl MidiLibrary {
             .and_then(|index| self.entries.get(*index))
     }
 
+    /// Changes whenever entries are added or removed, so callers can tell
+    /// when something they derived from the entries is out of date.
+    pub fn revision(&self) -> u64 {
+        self.revision
+    }
+
+    /// Whether the entry's title, file name or composer contains `query`,
+    /// ignoring case, accents and width.
+    pub fn matches(&self, id: &Uuid, query: &SearchQuery) -> bool {
+        query.is_empty()
+            || self
+                .search_keys
+                .get(id)
+                .is_some_and(|key| key.contains(&query.0))
+    }
+
+    /// Entries matching `query`, in library order.
+    pub fn search<'a>(&'a self, query: &'a SearchQuery) -> impl Iterator<Item = &'a MidiEntry> {
+        self.entries
+            .iter()
+            .filter(move |entry| self.matches(&entry.id, query))
+    }
+
+    /// Adds `other`'s entries that are not in this library yet.
+    pub fn append(&mut self, other: MidiLibrary) {
+        let mut search_keys = other.search_keys;
+        for entry in other.entries {
+            if self.index_by_id.contains_key(&entry.id) {
+                continue;
+            }
+            let key = search_keys
+                .remove(&entry.id)
+                .unwrap_or_else(|| search_key(&entry));
+            self.search_keys.insert(entry.id, key);
+            self.index_by_id.insert(entry.id, self.entries.len());
+            self.index_by_path.insert(entry.path.clone(), entry.id);
+            self.entries.push(entry);
+            self.apply_details(self.entries.len() - 1);
+        }
+        self.touch();
+    }
+
+    /// Replaces every entry's title and composer with `details`, e.g. once
+    /// they are loaded from disk. Entries without any go back to their file
+    /// names.
+    pub fn set_all_details(&mut self, details: HashMap<Uuid, SongDetails>) {
+        self.details = details;
+        for index in 0..self.entries.len() {
+            self.apply_details(index);
+        }
+        self.touch();
+    }
+
+    /// Gives `id` a title and composer; empty details restore its file
+    /// name. Kept for the entry if it is only added later.
+    pub fn set_details(&mut self, id: Uuid, details: SongDetails) {
+        let details = details.trimmed();
+        if details.is_empty() {
+            self.details.remove(&id);
+        } else {
+            self.details.insert(id, details);
+        }
+        if let Some(&index) = self.index_by_id.get(&id) {
+            self.apply_details(index);
+        }
+        self.touch();
+    }
+
+    pub fn details(&self, id: &Uuid) -> Option<&SongDetails> {
+        self.details.get(id)
+    }
+
+    /// Brings the entry at `index` in line with its details, leaving alone
+    /// the search key of one that has none and never had.
+    fn apply_details(&mut self, index: usize) {
+        let Some(entry) = self.entries.get_mut(index) else {
+            return;
+        };
+        let details = self.details.get(&entry.id).cloned().unwrap_or_default();
+        if details.is_empty() && entry.name == entry.file_name && entry.composer.is_none() {
+            return;
+        }
+        entry.name = details.title.unwrap_or_else(|| entry.file_name.clone());
+        entry.composer = details.composer;
+        self.search_keys.insert(entry.id, search_key(entry));
+    }
+
+    /// Re-adds `other`'s local files, e.g. after reloading the assets.
+    pub fn add_local_entries_from(&mut self, other: &MidiLibrary) {
+        for entry in other.entries() {
+            if entry.origin == MidiOrigin::Local
+                && let Err(err) = self.add_local_file_at(&entry.path, entry.library_path.clone())
+            {
+                log::warn!(
+                    "failed to keep local file {}: {err:?}",
+                    entry.path.display()
+                );
+            }
+        }
+    }
+
     pub fn add_local_file<P: AsRef<Path>>(&mut self, path: P) -> Result<&MidiEntry> {
+        self.add_local_file_at(path, None)
+    }
+
+    pub fn add_local_file_at<P: AsRef<Path>>(
+        &mut self,
+        path: P,
+        library_path: Option<Vec<String>>,
+    ) -> Result<&MidiEntry> {
         let path = normalize_path(path.as_ref());
         let entry_id = if let Some(existing) = self.index_by_path.get(&path) {
             *existing
         } else {
-            self.insert_entry(path, MidiOrigin::Local, None)
+            let id = Uuid::new_v5(
+                &ENTRY_NAMESPACE,
+                format!("local:{}", path.display()).as_bytes(),
+            );
+            self.insert_entry(id, path, MidiOrigin::Local, library_path)
         };
         self.index_by_id
             .get(&entry_id)
@@ -97,34 +492,125 @@ impl MidiLibrary {
             .context("failed to retrieve newly added MIDI entry")
     }
 
+    /// Drops every entry of `origin`.
+    pub fn remove_origin(&mut self, origin: MidiOrigin) {
+        self.entries.retain(|entry| entry.origin != origin);
+        self.reindex();
+    }
+
+    /// Drops a single entry, returning it if it was in the library.
+    pub fn remove(&mut self, id: &Uuid) -> Option<MidiEntry> {
+        let index = *self.index_by_id.get(id)?;
+        let entry = self.entries.remove(index);
+        self.reindex();
+        Some(entry)
+    }
+
+    /// Drops local entries whose files no longer exist and returns them.
+    /// Assets and remote entries are left alone: the former may be served
+    /// from the binary, the latter are only cached on first use.
+    pub fn remove_missing_local(&mut self) -> Vec<MidiEntry> {
+        let (missing, kept): (Vec<_>, Vec<_>) = std::mem::take(&mut self.entries)
+            .into_iter()
+            .partition(|entry| entry.origin == MidiOrigin::Local && !entry.path.exists());
+        self.entries = kept;
+        self.reindex();
+        missing
+    }
+
+    fn reindex(&mut self) {
+        self.index_by_id.clear();
+        self.index_by_path.clear();
+        for (index, entry) in self.entries.iter().enumerate() {
+            self.index_by_id.insert(entry.id, index);
+            self.index_by_path.insert(entry.path.clone(), entry.id);
+        }
+        let index_by_id = &self.index_by_id;
+        self.search_keys
+            .retain(|id, _| index_by_id.contains_key(id));
+        self.touch();
+    }
+
+    fn touch(&mut self) {
+        self.revision = NEXT_REVISION.fetch_add(1, Ordering::Relaxed);
+    }
+
+    /// Adds a catalog entry whose file will be cached at `cache_path`. Adding
+    /// the same URL again returns the existing entry's id.
+    pub fn add_remote_entry(&mut self, name: &str, url: &str, cache_path: PathBuf) -> Uuid {
+        let id = Uuid::new_v5(&ENTRY_NAMESPACE, format!("remote:{url}").as_bytes());
+        if self.index_by_id.contains_key(&id) {
+            return id;
+        }
+        self.search_keys.insert(id, super::collation::fold(name));
+        self.index_by_id.insert(id, self.entries.len());
+        self.index_by_path.insert(cache_path.clone(), id);
+        self.entries.push(MidiEntry {
+            id,
+            name: name.to_owned(),
+            file_name: name.to_owned(),
+            composer: None,
+            path: cache_path,
+            origin: MidiOrigin::Remote,
+            library_path: None,
+            source_url: Some(url.to_owned()),
+        });
+        self.apply_details(self.entries.len() - 1);
+        self.touch();
+        id
+    }
+
+    /// Ids are derived from the manifest entry or local path so that ratings,
+    /// favorites and other per-entry data survive restarts.
     fn insert_entry<P: Into<PathBuf>>(
         &mut self,
+        id: Uuid,
         path: P,
         origin: MidiOrigin,
         library_path: Option<Vec<String>>,
     ) -> Uuid {
         let raw_path: PathBuf = path.into();
         let path = normalize_path(&raw_path);
-        let id = Uuid::new_v4();
         let name = path
             .file_stem()
             .and_then(|stem| stem.to_str())
             .map(|s| s.to_owned())
             .unwrap_or_else(|| path.display().to_string());
+        self.search_keys.insert(id, super::collation::fold(&name));
         let entry = MidiEntry {
             id,
-            name,
+            name: name.clone(),
+            file_name: name,
+            composer: None,
             path: path.clone(),
             origin,
             library_path,
+            source_url: None,
         };
         self.index_by_id.insert(id, self.entries.len());
         self.index_by_path.insert(path, id);
         self.entries.push(entry);
+        self.apply_details(self.entries.len() - 1);
+        self.touch();
         id
     }
 }
 
+/// What searches match an entry against: its title, its file name when
+/// that differs, and its composer.
+fn search_key(entry: &MidiEntry) -> String {
+    let mut key = super::collation::fold(&entry.name);
+    if entry.file_name != entry.name {
+        key.push('\n');
+        key.push_str(&super::collation::fold(&entry.file_name));
+    }
+    if let Some(composer) = &entry.composer {
+        key.push('\n');
+        key.push_str(&super::collation::fold(composer));
+    }
+    key
+}
+
 fn normalize_path(path: &Path) -> PathBuf {
     match path.canonicalize() {
         Ok(canon) => canon,
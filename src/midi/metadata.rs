@@ -0,0 +1,126 @@
+use std::collections::HashMap;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::time::{Duration, UNIX_EPOCH};
+
+use anyhow::{Context, Result};
+use serde::{Deserialize, Serialize};
+
+use super::library::read_midi_file;
+use super::sequence::{TempoSummary, file_timing};
+
+/// Facts about library files that take a parse to find out, kept on disk so
+/// the library can show them without reading every file at startup. An
+/// entry is recomputed when its file's size or modification time changes.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct LibraryMetadata {
+    files: HashMap<PathBuf, FileMetadata>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+struct FileMetadata {
+    /// `None` for files only available embedded in the binary.
+    stamp: Option<FileStamp>,
+    /// `None` when the file could not be parsed.
+    duration_ms: Option<u64>,
+    /// Missing from files looked at before tempos were kept, which are read
+    /// again.
+    #[serde(default)]
+    tempo: Option<TempoSummary>,
+}
+
+impl FileMetadata {
+    fn is_complete(&self) -> bool {
+        self.duration_ms.is_none() || self.tempo.is_some()
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+struct FileStamp {
+    size: u64,
+    modified_secs: u64,
+}
+
+impl FileStamp {
+    fn of(path: &Path) -> Option<Self> {
+        let meta = fs::metadata(path).ok()?;
+        let modified_secs = meta
+            .modified()
+            .ok()
+            .and_then(|time| time.duration_since(UNIX_EPOCH).ok())
+            .map_or(0, |since| since.as_secs());
+        Some(Self {
+            size: meta.len(),
+            modified_secs,
+        })
+    }
+}
+
+impl LibraryMetadata {
+    pub fn load(path: &Path) -> Result<Self> {
+        if !path.exists() {
+            return Ok(LibraryMetadata::default());
+        }
+        let data = fs::read_to_string(path)
+            .with_context(|| format!("failed to read {}", path.display()))?;
+        serde_json::from_str(&data).context("failed to parse library metadata")
+    }
+
+    pub fn save(&self, path: &Path) -> Result<()> {
+        if let Some(parent) = path.parent() {
+            fs::create_dir_all(parent).context("failed to create data directory")?;
+        }
+        let serialized =
+            serde_json::to_string(self).context("failed to serialize library metadata")?;
+        fs::write(path, serialized).with_context(|| format!("failed to write {}", path.display()))
+    }
+
+    pub fn duration(&self, path: &Path) -> Option<Duration> {
+        self.files.get(path)?.duration_ms.map(Duration::from_millis)
+    }
+
+    pub fn tempo(&self, path: &Path) -> Option<TempoSummary> {
+        self.files.get(path)?.tempo
+    }
+
+    /// Whether `path` has been looked at, however long ago.
+    pub fn contains(&self, path: &Path) -> bool {
+        self.files.get(path).is_some_and(FileMetadata::is_complete)
+    }
+
+    /// Brings the entries for `paths` up to date, reading only files that
+    /// are new or changed. Files that cannot be read are recorded without a
+    /// duration and looked at again once they appear. Returns whether
+    /// anything changed.
+    pub fn refresh<'a>(&mut self, paths: impl IntoIterator<Item = &'a Path>) -> bool {
+        let mut changed = false;
+        for path in paths {
+            let stamp = FileStamp::of(path);
+            if self
+                .files
+                .get(path)
+                .is_some_and(|known| known.stamp == stamp && known.is_complete())
+            {
+                continue;
+            }
+            let timing = read_midi_file(path).and_then(|contents| file_timing(&contents));
+            let (duration_ms, tempo) = match timing {
+                Ok(timing) => (Some(timing.duration.as_millis() as u64), Some(timing.tempo)),
+                Err(err) => {
+                    log::debug!("no duration for {}: {err:?}", path.display());
+                    (None, None)
+                }
+            };
+            self.files.insert(
+                path.to_path_buf(),
+                FileMetadata {
+                    stamp,
+                    duration_ms,
+                    tempo,
+                },
+            );
+            changed = true;
+        }
+        changed
+    }
+}
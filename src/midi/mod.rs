@@ -1,9 +1,26 @@
+pub mod capabilities;
+pub mod catalog;
+pub mod collation;
+pub mod filter;
+pub mod folder_label;
+pub mod inbox;
+pub mod key;
 pub mod library;
+pub mod metadata;
+pub mod monitor;
+pub mod null_sink;
 pub mod player;
+pub mod remote;
+pub mod render;
 pub mod sequence;
 pub mod sink;
+pub mod soundfont;
+pub mod timing;
+pub mod trace;
+pub mod validate;
 
 pub use library::*;
+pub use null_sink::*;
 pub use player::*;
 pub use sequence::*;
 pub use sink::*;
@@ -0,0 +1,402 @@
+use std::collections::VecDeque;
+use std::fmt;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Arc, Mutex, MutexGuard};
+use std::time::Duration;
+
+use anyhow::Result;
+use async_trait::async_trait;
+use tokio::time::Instant;
+
+use super::sequence::note_name;
+use super::sink::{MidiSink, SharedMidiSink, SinkStats};
+
+/// General MIDI level 1 instrument names, indexed by program number.
+pub const GM_PROGRAM_NAMES: [&str; 128] = [
+    "Acoustic Grand Piano",
+    "Bright Acoustic Piano",
+    "Electric Grand Piano",
+    "Honky-tonk Piano",
+    "Electric Piano 1",
+    "Electric Piano 2",
+    "Harpsichord",
+    "Clavinet",
+    "Celesta",
+    "Glockenspiel",
+    "Music Box",
+    "Vibraphone",
+    "Marimba",
+    "Xylophone",
+    "Tubular Bells",
+    "Dulcimer",
+    "Drawbar Organ",
+    "Percussive Organ",
+    "Rock Organ",
+    "Church Organ",
+    "Reed Organ",
+    "Accordion",
+    "Harmonica",
+    "Tango Accordion",
+    "Acoustic Guitar (nylon)",
+    "Acoustic Guitar (steel)",
+    "Electric Guitar (jazz)",
+    "Electric Guitar (clean)",
+    "Electric Guitar (muted)",
+    "Overdriven Guitar",
+    "Distortion Guitar",
+    "Guitar Harmonics",
+    "Acoustic Bass",
+    "Electric Bass (finger)",
+    "Electric Bass (pick)",
+    "Fretless Bass",
+    "Slap Bass 1",
+    "Slap Bass 2",
+    "Synth Bass 1",
+    "Synth Bass 2",
+    "Violin",
+    "Viola",
+    "Cello",
+    "Contrabass",
+    "Tremolo Strings",
+    "Pizzicato Strings",
+    "Orchestral Harp",
+    "Timpani",
+    "String Ensemble 1",
+    "String Ensemble 2",
+    "Synth Strings 1",
+    "Synth Strings 2",
+    "Choir Aahs",
+    "Voice Oohs",
+    "Synth Voice",
+    "Orchestra Hit",
+    "Trumpet",
+    "Trombone",
+    "Tuba",
+    "Muted Trumpet",
+    "French Horn",
+    "Brass Section",
+    "Synth Brass 1",
+    "Synth Brass 2",
+    "Soprano Sax",
+    "Alto Sax",
+    "Tenor Sax",
+    "Baritone Sax",
+    "Oboe",
+    "English Horn",
+    "Bassoon",
+    "Clarinet",
+    "Piccolo",
+    "Flute",
+    "Recorder",
+    "Pan Flute",
+    "Blown Bottle",
+    "Shakuhachi",
+    "Whistle",
+    "Ocarina",
+    "Lead 1 (square)",
+    "Lead 2 (sawtooth)",
+    "Lead 3 (calliope)",
+    "Lead 4 (chiff)",
+    "Lead 5 (charang)",
+    "Lead 6 (voice)",
+    "Lead 7 (fifths)",
+    "Lead 8 (bass + lead)",
+    "Pad 1 (new age)",
+    "Pad 2 (warm)",
+    "Pad 3 (polysynth)",
+    "Pad 4 (choir)",
+    "Pad 5 (bowed)",
+    "Pad 6 (metallic)",
+    "Pad 7 (halo)",
+    "Pad 8 (sweep)",
+    "FX 1 (rain)",
+    "FX 2 (soundtrack)",
+    "FX 3 (crystal)",
+    "FX 4 (atmosphere)",
+    "FX 5 (brightness)",
+    "FX 6 (goblins)",
+    "FX 7 (echoes)",
+    "FX 8 (sci-fi)",
+    "Sitar",
+    "Banjo",
+    "Shamisen",
+    "Koto",
+    "Kalimba",
+    "Bagpipe",
+    "Fiddle",
+    "Shanai",
+    "Tinkle Bell",
+    "Agogo",
+    "Steel Drums",
+    "Woodblock",
+    "Taiko Drum",
+    "Melodic Tom",
+    "Synth Drum",
+    "Reverse Cymbal",
+    "Guitar Fret Noise",
+    "Breath Noise",
+    "Seashore",
+    "Bird Tweet",
+    "Telephone Ring",
+    "Helicopter",
+    "Applause",
+    "Gunshot",
+];
+
+pub fn gm_program_name(program: u8) -> &'static str {
+    GM_PROGRAM_NAMES
+        .get(program as usize)
+        .copied()
+        .unwrap_or("Unknown")
+}
+
+pub fn controller_name(controller: u8) -> Option<&'static str> {
+    Some(match controller {
+        0 => "Bank Select",
+        1 => "Modulation",
+        2 => "Breath",
+        4 => "Foot Pedal",
+        5 => "Portamento Time",
+        6 => "Data Entry",
+        7 => "Volume",
+        8 => "Balance",
+        10 => "Pan",
+        11 => "Expression",
+        32 => "Bank Select LSB",
+        64 => "Sustain Pedal",
+        65 => "Portamento",
+        66 => "Sostenuto Pedal",
+        67 => "Soft Pedal",
+        68 => "Legato",
+        71 => "Resonance",
+        72 => "Release Time",
+        73 => "Attack Time",
+        74 => "Brightness",
+        84 => "Portamento Control",
+        91 => "Reverb",
+        93 => "Chorus",
+        98 => "NRPN LSB",
+        99 => "NRPN MSB",
+        100 => "RPN LSB",
+        101 => "RPN MSB",
+        120 => "All Sound Off",
+        121 => "Reset All Controllers",
+        122 => "Local Control",
+        123 => "All Notes Off",
+        124 => "Omni Off",
+        125 => "Omni On",
+        126 => "Mono On",
+        127 => "Poly On",
+        _ => return None,
+    })
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum MessageKind {
+    Note,
+    ControlChange,
+    ProgramChange,
+    PitchBend,
+    Aftertouch,
+    System,
+}
+
+impl MessageKind {
+    pub const ALL: [MessageKind; 6] = [
+        MessageKind::Note,
+        MessageKind::ControlChange,
+        MessageKind::ProgramChange,
+        MessageKind::PitchBend,
+        MessageKind::Aftertouch,
+        MessageKind::System,
+    ];
+
+    pub fn of(data: &[u8]) -> MessageKind {
+        match data.first().map(|status| status & 0xF0) {
+            Some(0x80 | 0x90) => MessageKind::Note,
+            Some(0xB0) => MessageKind::ControlChange,
+            Some(0xC0) => MessageKind::ProgramChange,
+            Some(0xE0) => MessageKind::PitchBend,
+            Some(0xA0 | 0xD0) => MessageKind::Aftertouch,
+            _ => MessageKind::System,
+        }
+    }
+}
+
+impl fmt::Display for MessageKind {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let label = match self {
+            MessageKind::Note => "Notes",
+            MessageKind::ControlChange => "Controllers",
+            MessageKind::ProgramChange => "Program changes",
+            MessageKind::PitchBend => "Pitch bend",
+            MessageKind::Aftertouch => "Aftertouch",
+            MessageKind::System => "System / SysEx",
+        };
+        write!(f, "{label}")
+    }
+}
+
+/// Zero-based channel of a channel message.
+pub fn message_channel(data: &[u8]) -> Option<u8> {
+    match data.first() {
+        Some(status) if (0x80..0xF0).contains(status) => Some(status & 0x0F),
+        _ => None,
+    }
+}
+
+/// Human-readable decode of a single complete MIDI message.
+pub fn describe_message(data: &[u8]) -> String {
+    let Some(&status) = data.first() else {
+        return "Empty message".into();
+    };
+    let byte = |index: usize| data.get(index).copied().unwrap_or(0);
+    match status & 0xF0 {
+        0x90 if byte(2) > 0 => format!(
+            "Note On  {} ({}) vel {}",
+            note_name(byte(1)),
+            byte(1),
+            byte(2)
+        ),
+        0x80 | 0x90 => format!("Note Off {} ({})", note_name(byte(1)), byte(1)),
+        0xA0 => format!(
+            "Poly Aftertouch {} ({}) {}",
+            note_name(byte(1)),
+            byte(1),
+            byte(2)
+        ),
+        0xB0 => match controller_name(byte(1)) {
+            Some(name) => format!("CC {} {name} = {}", byte(1), byte(2)),
+            None => format!("CC {} = {}", byte(1), byte(2)),
+        },
+        0xC0 => format!("Program {} {}", byte(1) + 1, gm_program_name(byte(1))),
+        0xD0 => format!("Channel Aftertouch {}", byte(1)),
+        0xE0 => {
+            let value = ((byte(2) as i32) << 7 | byte(1) as i32) - 8192;
+            format!("Pitch Bend {value:+}")
+        }
+        _ => match status {
+            0xF0 => format!("SysEx ({} bytes)", data.len()),
+            0xF8 => "Clock".into(),
+            0xFA => "Start".into(),
+            0xFB => "Continue".into(),
+            0xFC => "Stop".into(),
+            0xFE => "Active Sensing".into(),
+            0xFF => "System Reset".into(),
+            other => format!("System {other:02X}"),
+        },
+    }
+}
+
+pub fn hex_bytes(data: &[u8]) -> String {
+    data.iter()
+        .map(|byte| format!("{byte:02X}"))
+        .collect::<Vec<_>>()
+        .join(" ")
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct MonitorEntry {
+    /// Time since the monitor was created.
+    pub at: Duration,
+    pub data: Vec<u8>,
+}
+
+impl MonitorEntry {
+    pub fn kind(&self) -> MessageKind {
+        MessageKind::of(&self.data)
+    }
+
+    pub fn channel(&self) -> Option<u8> {
+        message_channel(&self.data)
+    }
+
+    pub fn describe(&self) -> String {
+        describe_message(&self.data)
+    }
+}
+
+/// Keeps the most recent messages sent through [`MonitoredSink`]s.
+#[derive(Debug)]
+pub struct MidiMonitor {
+    created: Instant,
+    capacity: usize,
+    revision: AtomicU64,
+    entries: Mutex<VecDeque<MonitorEntry>>,
+}
+
+impl MidiMonitor {
+    pub fn new(capacity: usize) -> Self {
+        Self {
+            created: Instant::now(),
+            capacity: capacity.max(1),
+            revision: AtomicU64::new(0),
+            entries: Mutex::new(VecDeque::with_capacity(capacity.max(1))),
+        }
+    }
+
+    pub fn record(&self, data: &[u8]) {
+        let at = self.created.elapsed();
+        let mut entries = self.lock();
+        if entries.len() == self.capacity {
+            entries.pop_front();
+        }
+        entries.push_back(MonitorEntry {
+            at,
+            data: data.to_vec(),
+        });
+        self.revision.fetch_add(1, Ordering::Relaxed);
+    }
+
+    /// Changes whenever a message is recorded or the log is cleared.
+    pub fn revision(&self) -> u64 {
+        self.revision.load(Ordering::Relaxed)
+    }
+
+    /// Oldest first.
+    pub fn entries(&self) -> Vec<MonitorEntry> {
+        self.lock().iter().cloned().collect()
+    }
+
+    pub fn clear(&self) {
+        self.lock().clear();
+        self.revision.fetch_add(1, Ordering::Relaxed);
+    }
+
+    fn lock(&self) -> MutexGuard<'_, VecDeque<MonitorEntry>> {
+        self.entries
+            .lock()
+            .unwrap_or_else(|poisoned| poisoned.into_inner())
+    }
+}
+
+/// Forwards to another sink, recording each message on the way.
+pub struct MonitoredSink {
+    inner: SharedMidiSink,
+    monitor: Arc<MidiMonitor>,
+}
+
+impl MonitoredSink {
+    pub fn new(inner: SharedMidiSink, monitor: Arc<MidiMonitor>) -> Self {
+        Self { inner, monitor }
+    }
+}
+
+#[async_trait]
+impl MidiSink for MonitoredSink {
+    async fn send(&self, data: &[u8]) -> Result<()> {
+        self.monitor.record(data);
+        self.inner.send(data).await
+    }
+
+    async fn send_batch(&self, messages: &[Vec<u8>]) -> Result<()> {
+        for message in messages {
+            self.monitor.record(message);
+        }
+        self.inner.send_batch(messages).await
+    }
+
+    fn stats(&self) -> Option<SinkStats> {
+        self.inner.stats()
+    }
+}
@@ -0,0 +1,85 @@
+use std::sync::Mutex;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::time::Duration;
+
+use anyhow::Result;
+use async_trait::async_trait;
+use tokio::time::Instant;
+
+use super::sink::{MidiSink, SendStats, SinkStats};
+
+/// A message received by a [`NullSink`], stamped with the time since the sink
+/// was created.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SentMessage {
+    pub at: Duration,
+    pub data: Vec<u8>,
+}
+
+/// Output that plays nothing and keeps everything it is sent.
+///
+/// Timestamps come from the tokio clock, so under a paused test runtime
+/// (`#[tokio::test(start_paused = true)]`) they are exact and repeatable.
+#[derive(Debug)]
+pub struct NullSink {
+    created: Instant,
+    log_messages: AtomicBool,
+    sent: Mutex<Vec<SentMessage>>,
+    stats: SendStats,
+}
+
+impl Default for NullSink {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl NullSink {
+    pub fn new() -> Self {
+        Self {
+            created: Instant::now(),
+            log_messages: AtomicBool::new(false),
+            sent: Mutex::new(Vec::new()),
+            stats: SendStats::default(),
+        }
+    }
+
+    /// Also writes every message to the log at info level.
+    pub fn set_logging(&self, enabled: bool) {
+        self.log_messages.store(enabled, Ordering::Relaxed);
+    }
+
+    pub fn sent(&self) -> Vec<SentMessage> {
+        self.lock().clone()
+    }
+
+    /// Returns and forgets everything recorded so far.
+    pub fn take(&self) -> Vec<SentMessage> {
+        std::mem::take(&mut *self.lock())
+    }
+
+    fn lock(&self) -> std::sync::MutexGuard<'_, Vec<SentMessage>> {
+        self.sent
+            .lock()
+            .unwrap_or_else(|poisoned| poisoned.into_inner())
+    }
+}
+
+#[async_trait]
+impl MidiSink for NullSink {
+    async fn send(&self, data: &[u8]) -> Result<()> {
+        let at = self.created.elapsed();
+        if self.log_messages.load(Ordering::Relaxed) {
+            log::info!("null sink @ {:.3}s: {data:02X?}", at.as_secs_f64());
+        }
+        let data = data.to_vec();
+        self.stats
+            .record(std::slice::from_ref(&data), Duration::ZERO, &Ok(()));
+        self.lock().push(SentMessage { at, data });
+        Ok(())
+    }
+
+    fn stats(&self) -> Option<SinkStats> {
+        Some(self.stats.snapshot())
+    }
+}
@@ -1,4 +1,6 @@
-use std::sync::Arc;
+use std::collections::HashSet;
+use std::sync::atomic::{AtomicBool, AtomicU8, Ordering};
+use std::sync::{Arc, Mutex};
 use std::time::Duration;
 
 use anyhow::{Result, anyhow};
@@ -6,15 +8,102 @@ use tokio::sync::{Notify, mpsc};
 use tokio::task::JoinHandle;
 use tokio::time::{self, Instant as TokioInstant};
 
-use super::sequence::MidiSequence;
-use super::sink::SharedMidiSink;
+use super::sequence::{Accent, MidiSequence};
+use super::sink::{SharedMidiSink, panic_messages};
+use super::timing::TimingCapture;
 
-const PROGRESS_UPDATE_STEP: Duration = Duration::from_millis(100);
+/// How often [`PlayerEvent::Progress`] is sent unless changed with
+/// [`MidiPlayer::set_progress_interval`].
+pub const DEFAULT_PROGRESS_INTERVAL: Duration = Duration::from_millis(100);
+/// Silence left in place when fast-forwarding through a gap.
+const GAP_LEAD: Duration = Duration::from_secs(1);
+
+/// Beat length assumed for the count-in when the file has no beat markers.
+const DEFAULT_BEAT: Duration = Duration::from_millis(500);
+/// Time between the numbers of a countdown.
+const COUNTDOWN_STEP: Duration = Duration::from_secs(1);
+/// Count-in and metronome clicks: GM hi and low wood block on the
+/// percussion channel.
+const ACCENT_CLICK: u8 = 76;
+const CLICK: u8 = 77;
+
+/// Silence left before the first note of a preview.
+const PREVIEW_PRE_ROLL: Duration = Duration::from_millis(500);
+
+/// Expression (CC11), ramped for fades.
+const EXPRESSION: u8 = 11;
+/// Expression messages sent over the length of a fade.
+const FADE_STEPS: u32 = 20;
+
+/// How playback from the top of a song begins, set with
+/// [`MidiPlayer::set_lead_in`].
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct LeadIn {
+    /// Skip leading silence, starting this long before the first note.
+    pub trim_to: Option<Duration>,
+    /// Click one measure on the percussion channel before the first note.
+    pub count_in: bool,
+    /// Seconds counted down before anything is sent, so there is time to
+    /// get to the keyboard; zero for none. Each second clicks when
+    /// `count_in` is on.
+    pub countdown: u8,
+}
+
+impl LeadIn {
+    /// The usual 3-2-1.
+    pub const COUNTDOWN: u8 = 3;
+}
+
+/// Watches for long stretches without sounding notes in the middle of a song.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct SilenceWatch {
+    pub threshold: Duration,
+    pub fast_forward: bool,
+}
 
 #[derive(Debug, Clone)]
 pub enum PlayerEvent {
-    Started { total: Duration },
-    Progress { elapsed: Duration, total: Duration },
+    /// Seconds left of the [countdown](LeadIn::countdown), reported as each
+    /// one begins, before [`PlayerEvent::Started`].
+    Countdown(u8),
+    Started {
+        /// Where in the song playback begins.
+        position: Duration,
+        total: Duration,
+        /// Time spent counting in before `position` plays.
+        count_in: Duration,
+    },
+    Progress {
+        elapsed: Duration,
+        total: Duration,
+    },
+    /// The tempo in beats per minute, as adjusted for playback. Reported
+    /// when playback starts, seeks or repeats, and whenever the song changes
+    /// tempo.
+    Tempo(f64),
+    /// Notes that started together: the loudest velocity per channel, zero
+    /// for channels without a new note.
+    ChannelActivity([u8; 16]),
+    /// The song went back to `position`, near the top, for pass `pass` of
+    /// `passes` set with [`MidiPlayer::set_repeat`].
+    Repeated {
+        pass: u8,
+        passes: u8,
+        position: Duration,
+        total: Duration,
+    },
+    /// Playback jumped to `position` after [`MidiPlayer::seek`].
+    Seeked {
+        position: Duration,
+        total: Duration,
+    },
+    /// A silent gap of `length` starts at `at`; `skipped` is how much of it
+    /// was fast-forwarded (zero when only notifying).
+    SilenceGap {
+        at: Duration,
+        length: Duration,
+        skipped: Duration,
+    },
     Finished,
     Stopped,
     Error(String),
@@ -23,18 +112,60 @@ pub enum PlayerEvent {
 struct PlaybackHandle {
     cancel: Arc<Notify>,
     join: JoinHandle<()>,
+    sink: SharedMidiSink,
+    /// Set once the player has reported the playback stopped, so a fade
+    /// out playing on reports nothing more.
+    stopped: Arc<AtomicBool>,
+    fade_in: FadeIn,
 }
 
-impl PlaybackHandle {
-    fn new(cancel: Arc<Notify>, join: JoinHandle<()>) -> Self {
-        Self { cancel, join }
+/// A fade out playing on after [`MidiPlayer::stop`], kept so that whatever
+/// plays next can cut it short rather than play under it.
+struct FadeOut {
+    cut_short: Arc<Notify>,
+    join: JoinHandle<()>,
+}
+
+impl FadeOut {
+    /// Ends the ramp where it got to. The faded playback still releases its
+    /// notes, and expression is restored, before the returned task ends.
+    fn cut_short(self) -> JoinHandle<()> {
+        self.cut_short.notify_one();
+        self.join
+    }
+}
+
+/// Forwards player events until the playback is reported stopped.
+struct Reporter {
+    sender: mpsc::UnboundedSender<PlayerEvent>,
+    stopped: Arc<AtomicBool>,
+}
+
+impl Reporter {
+    fn send(&self, event: PlayerEvent) -> Result<(), mpsc::error::SendError<PlayerEvent>> {
+        if self.stopped.load(Ordering::Relaxed) {
+            return Ok(());
+        }
+        self.sender.send(event)
     }
 }
 
 pub struct MidiPlayer {
     event_sender: mpsc::UnboundedSender<PlayerEvent>,
     playback: Option<PlaybackHandle>,
+    fading_out: Option<FadeOut>,
     active_sequence: Option<Arc<MidiSequence>>,
+    lead_in: LeadIn,
+    progress_interval: Duration,
+    /// Percent of each note-on's velocity that is played.
+    level: Arc<AtomicU8>,
+    /// Times in a row each song is played.
+    passes: Arc<AtomicU8>,
+    /// The pass under way, kept across seeks.
+    pass: Arc<AtomicU8>,
+    metronome: Arc<AtomicBool>,
+    fade: Duration,
+    timing: Option<Arc<TimingCapture>>,
 }
 
 impl MidiPlayer {
@@ -42,14 +173,88 @@ impl MidiPlayer {
         Self {
             event_sender,
             playback: None,
+            fading_out: None,
             active_sequence: None,
+            lead_in: LeadIn::default(),
+            progress_interval: DEFAULT_PROGRESS_INTERVAL,
+            level: Arc::new(AtomicU8::new(100)),
+            passes: Arc::new(AtomicU8::new(1)),
+            pass: Arc::new(AtomicU8::new(1)),
+            metronome: Arc::new(AtomicBool::new(false)),
+            fade: Duration::ZERO,
+            timing: None,
         }
     }
 
+    /// Ramps expression (CC11) up over `fade` when playback starts and down
+    /// over `fade` when it is stopped, rather than cutting notes off.
+    /// Channels that set expression themselves are left alone. Zero turns
+    /// fading off; seeking never fades.
+    pub fn set_fade(&mut self, fade: Duration) {
+        self.fade = fade;
+    }
+
+    /// Plays note-ons at `percent` of their velocity, e.g. to fade out.
+    /// Applies straight away, including to playback already running, and
+    /// stays until changed. Notes keep a velocity of at least 1 so they
+    /// are not mistaken for note-offs.
+    pub fn set_level(&self, percent: u8) {
+        self.level.store(percent.min(100), Ordering::Relaxed);
+    }
+
+    /// Plays each song `passes` times in a row. The player goes back to the
+    /// top as soon as a pass ends, on the same device and without counting
+    /// in or fading again, and reports [`PlayerEvent::Repeated`]. Applies
+    /// straight away, including to playback already running, and stays until
+    /// changed.
+    pub fn set_repeat(&self, passes: u8) {
+        self.passes.store(passes.max(1), Ordering::Relaxed);
+    }
+
+    /// Clicks every beat of the file's time signatures while playing,
+    /// accenting the first of each measure and, in compound meters, each
+    /// group of three. Applies straight away, including to playback already
+    /// running, and stays until changed.
+    pub fn set_metronome(&self, enabled: bool) {
+        self.metronome.store(enabled, Ordering::Relaxed);
+    }
+
+    /// Records how late each batch of events goes out in `timing`, from the
+    /// next start or seek; `None` stops recording.
+    pub fn set_timing_capture(&mut self, timing: Option<Arc<TimingCapture>>) {
+        self.timing = timing;
+    }
+
+    /// Progress is reported this often while playing, whether or not
+    /// anything is sent to the device. Applies from the next start or seek.
+    pub fn set_progress_interval(&mut self, interval: Duration) {
+        self.progress_interval = interval.max(Duration::from_millis(10));
+    }
+
+    /// Applies to playback started afterwards; seeking never counts in.
+    pub fn set_lead_in(&mut self, lead_in: LeadIn) {
+        self.lead_in = lead_in;
+    }
+
     pub fn start_playback(
         &mut self,
         sequence: Arc<MidiSequence>,
         sink: SharedMidiSink,
+        silence_watch: Option<SilenceWatch>,
+    ) -> Result<()> {
+        self.start_playback_from(sequence, sink, silence_watch, Duration::ZERO)
+    }
+
+    /// Starts playback `position` into the sequence. Controller, program and
+    /// other non-note messages before that point are sent up front so the
+    /// instrument is set up as if the song had played from the start.
+    /// Leading silence is only trimmed when starting from the top.
+    pub fn start_playback_from(
+        &mut self,
+        sequence: Arc<MidiSequence>,
+        sink: SharedMidiSink,
+        silence_watch: Option<SilenceWatch>,
+        position: Duration,
     ) -> Result<()> {
         if sequence.events.is_empty() {
             return Err(anyhow!(
@@ -57,57 +262,398 @@ impl MidiPlayer {
             ));
         }
 
-        self.stop_internal();
+        let previous = self.stop_internal(Duration::ZERO);
+        self.pass.store(1, Ordering::Relaxed);
+        self.spawn(sequence, sink, silence_watch, position, previous, false);
+        Ok(())
+    }
+
+    /// Jumps to `position` in the sequence that is playing. Sounding notes
+    /// are released and setup messages resent as for
+    /// [`MidiPlayer::start_playback_from`], but the player reports
+    /// [`PlayerEvent::Seeked`] rather than stopping and starting again.
+    pub fn seek(
+        &mut self,
+        sink: SharedMidiSink,
+        silence_watch: Option<SilenceWatch>,
+        position: Duration,
+    ) -> Result<()> {
+        let sequence = self
+            .active_sequence
+            .clone()
+            .filter(|_| self.playback.is_some())
+            .ok_or_else(|| anyhow!("nothing is playing"))?;
+        let previous = self.cancel();
+        self.spawn(sequence, sink, silence_watch, position, previous, true);
+        Ok(())
+    }
+
+    fn spawn(
+        &mut self,
+        sequence: Arc<MidiSequence>,
+        sink: SharedMidiSink,
+        silence_watch: Option<SilenceWatch>,
+        position: Duration,
+        previous: Option<JoinHandle<()>>,
+        seeking: bool,
+    ) {
         self.active_sequence = Some(sequence.clone());
 
         let cancel = Arc::new(Notify::new());
         let cancel_clone = cancel.clone();
-        let sender = self.event_sender.clone();
+        let stopped = Arc::new(AtomicBool::new(false));
+        let sender = Reporter {
+            sender: self.event_sender.clone(),
+            stopped: stopped.clone(),
+        };
+        let handle_sink = sink.clone();
+        let fade_in_state = FadeIn::default();
+        let handle_fade_in = fade_in_state.clone();
+        let fade = (!seeking)
+            .then_some(self.fade)
+            .filter(|fade| !fade.is_zero());
         let total_duration = sequence.duration;
+        let progress_interval = self.progress_interval;
+        let level = self.level.clone();
+        let passes = self.passes.clone();
+        let pass = self.pass.clone();
+        let metronome = self.metronome.clone();
+        let timing = self.timing.clone();
+        // Later passes skip leading silence like a fresh start would.
+        let top = self
+            .lead_in
+            .trim_to
+            .zip(sequence.events.iter().find(|event| is_note_on(&event.data)))
+            .map_or(Duration::ZERO, |(pre_roll, first_note)| {
+                first_note.at.saturating_sub(pre_roll)
+            });
+        let lead_in = if seeking {
+            LeadIn::default()
+        } else {
+            self.lead_in
+        };
 
         let join = tokio::spawn(async move {
-            let _ = sender.send(PlayerEvent::Started {
-                total: total_duration,
+            // Let the previous playback flush its note-offs before we start.
+            if let Some(previous) = previous {
+                let _ = previous.await;
+            }
+            let mut position = position.min(total_duration);
+            let first_note = sequence.events
+                [sequence.events.partition_point(|event| event.at < position)..]
+                .iter()
+                .find(|event| is_note_on(&event.data))
+                .map(|event| event.at);
+            if position.is_zero()
+                && let (Some(pre_roll), Some(first_note)) = (lead_in.trim_to, first_note)
+            {
+                position = first_note.saturating_sub(pre_roll);
+            }
+            let count_in = first_note
+                .filter(|_| lead_in.count_in)
+                .map(|first_note| CountIn::before(&sequence, first_note, position));
+            if lead_in.countdown > 0 {
+                let mut at = TokioInstant::now();
+                for remaining in (1..=lead_in.countdown).rev() {
+                    let _ = sender.send(PlayerEvent::Countdown(remaining));
+                    if lead_in.count_in {
+                        let key = if remaining == 1 { ACCENT_CLICK } else { CLICK };
+                        let click = [vec![0x99, key, 90], vec![0x89, key, 0]];
+                        if let Err(err) = sink.send_batch(&click).await {
+                            let _ = sender.send(PlayerEvent::Error(err.to_string()));
+                            return;
+                        }
+                    }
+                    at += COUNTDOWN_STEP;
+                    let wait_result = tokio::select! {
+                        _ = time::sleep_until(at) => WaitOutcome::Completed,
+                        _ = cancel_clone.notified() => WaitOutcome::Cancelled,
+                    };
+                    if let WaitOutcome::Cancelled = wait_result {
+                        return;
+                    }
+                }
+            }
+            let _ = sender.send(if seeking {
+                PlayerEvent::Seeked {
+                    position,
+                    total: total_duration,
+                }
+            } else {
+                PlayerEvent::Started {
+                    position,
+                    total: total_duration,
+                    count_in: count_in
+                        .as_ref()
+                        .map_or(Duration::ZERO, |count_in| count_in.delay),
+                }
             });
             let _ = sender.send(PlayerEvent::Progress {
-                elapsed: Duration::ZERO,
+                elapsed: position,
                 total: total_duration,
             });
+            let mut tempos_passed = sequence
+                .tempo_changes
+                .partition_point(|change| change.at <= position);
+            if let Some(bpm) = sequence.bpm_at(position) {
+                let _ = sender.send(PlayerEvent::Tempo(bpm));
+            }
 
-            let start = TokioInstant::now();
-            let mut last_reported = Duration::ZERO;
+            // Counting in holds back everything up to the first note, so
+            // whatever sits before it goes out with the setup.
+            let setup_end = match (&count_in, first_note) {
+                (Some(_), Some(first_note)) => first_note,
+                _ => position,
+            };
+            let mut index = sequence
+                .events
+                .partition_point(|event| event.at < setup_end);
+            // The count-in clicks the beats up to the first note itself.
+            let mut beat_index = sequence.beats.partition_point(|beat| beat.at < setup_end);
+            let setup: Vec<Vec<u8>> = sequence.events[..index]
+                .iter()
+                .filter(|event| !is_note_message(&event.data))
+                .map(|event| event.data.clone())
+                .collect();
+            if !setup.is_empty()
+                && let Err(err) = sink.send_batch(&setup).await
+            {
+                let _ = sender.send(PlayerEvent::Error(err.to_string()));
+                return;
+            }
 
-            let mut index = 0;
+            let mut start = TokioInstant::now();
+            if let Some(count_in) = count_in {
+                for (at, data) in count_in.clicks(start) {
+                    let wait_result = tokio::select! {
+                        _ = time::sleep_until(at) => WaitOutcome::Completed,
+                        _ = cancel_clone.notified() => WaitOutcome::Cancelled,
+                    };
+                    if let WaitOutcome::Cancelled = wait_result {
+                        return;
+                    }
+                    if let Err(err) = sink.send_batch(&data).await {
+                        let _ = sender.send(PlayerEvent::Error(err.to_string()));
+                        return;
+                    }
+                }
+                start += count_in.delay;
+            }
+            let mut fade_in = match fade {
+                Some(length) => {
+                    let channels = fade_channels(&sequence);
+                    if let Err(err) = sink.send_batch(&expression_messages(&channels, 0)).await {
+                        let _ = sender.send(PlayerEvent::Error(err.to_string()));
+                        return;
+                    }
+                    fade_in_state.level.store(0, Ordering::Relaxed);
+                    Some(Fade::start(sink.clone(), channels, length, fade_in_state))
+                }
+                None => None,
+            };
+            let mut last_reported = TokioInstant::now();
+
+            let mut active_notes = ActiveNotes::default();
             let total_events = sequence.events.len();
-            while index < total_events {
-                let event_at = sequence.events[index].at;
-                let target = start + event_at;
-                let wait_result = tokio::select! {
-                    _ = time::sleep_until(target) => WaitOutcome::Completed,
-                    _ = cancel_clone.notified() => WaitOutcome::Cancelled,
-                };
-
-                if let WaitOutcome::Cancelled = wait_result {
+            let next_note_on = silence_watch
+                .map(|_| next_note_on_times(&sequence))
+                .unwrap_or_default();
+            let mut notes_played = false;
+            let mut gap_reported_until = position;
+            let mut interrupted = false;
+            loop {
+                'playing: while index < total_events {
+                    let event_at = sequence.events[index].at;
+                    if let Some(beat) = sequence
+                        .beats
+                        .get(beat_index)
+                        .filter(|beat| beat.at <= event_at)
+                    {
+                        beat_index += 1;
+                        if !metronome.load(Ordering::Relaxed) {
+                            continue;
+                        }
+                        let target = start + beat.at.saturating_sub(position);
+                        let wait_result = tokio::select! {
+                            _ = time::sleep_until(target) => WaitOutcome::Completed,
+                            _ = cancel_clone.notified() => WaitOutcome::Cancelled,
+                        };
+                        if let WaitOutcome::Cancelled = wait_result {
+                            if let Err(err) =
+                                sink.send_batch(&active_notes.release_messages()).await
+                            {
+                                log::warn!("failed to silence notes after stop: {err:?}");
+                            }
+                            interrupted = true;
+                            break 'playing;
+                        }
+                        if let Err(err) = sink.send_batch(&click(beat.accent())).await {
+                            let _ = sender.send(PlayerEvent::Error(err.to_string()));
+                            let _ = sink.send_batch(&active_notes.release_messages()).await;
+                            interrupted = true;
+                            break 'playing;
+                        }
+                        continue;
+                    }
+                    let target = start + event_at.saturating_sub(position);
+                    // Keep reporting through sparse passages so the position
+                    // moves steadily.
+                    let wait_result = loop {
+                        let report_at = last_reported + progress_interval;
+                        if report_at >= target {
+                            break tokio::select! {
+                                _ = time::sleep_until(target) => WaitOutcome::Completed,
+                                _ = cancel_clone.notified() => WaitOutcome::Cancelled,
+                            };
+                        }
+                        if let WaitOutcome::Cancelled = tokio::select! {
+                            _ = time::sleep_until(report_at) => WaitOutcome::Completed,
+                            _ = cancel_clone.notified() => WaitOutcome::Cancelled,
+                        } {
+                            break WaitOutcome::Cancelled;
+                        }
+                        last_reported = report_at;
+                        let _ = sender.send(PlayerEvent::Progress {
+                            elapsed: (position + report_at.saturating_duration_since(start))
+                                .min(event_at),
+                            total: total_duration,
+                        });
+                    };
+
+                    if let WaitOutcome::Cancelled = wait_result {
+                        if let Err(err) = sink.send_batch(&active_notes.release_messages()).await {
+                            log::warn!("failed to silence notes after stop: {err:?}");
+                        }
+                        interrupted = true;
+                        break 'playing;
+                    }
+                    if let Some(timing) = &timing {
+                        timing.record_jitter(TokioInstant::now().saturating_duration_since(target));
+                    }
+
+                    let mut batch: Vec<Vec<u8>> = Vec::new();
+                    let level = level.load(Ordering::Relaxed);
+                    while index < total_events && sequence.events[index].at == event_at {
+                        let data = &sequence.events[index].data;
+                        notes_played |= is_note_on(data);
+                        active_notes.track(data);
+                        let mut data = data.clone();
+                        if level < 100 && is_note_on(&data) {
+                            data[2] = (u16::from(data[2]) * u16::from(level) / 100).max(1) as u8;
+                        }
+                        batch.push(data);
+                        index += 1;
+                    }
+
+                    if let Err(err) = sink.send_batch(&batch).await {
+                        let _ = sender.send(PlayerEvent::Error(err.to_string()));
+                        let _ = sink.send_batch(&active_notes.release_messages()).await;
+                        interrupted = true;
+                        break 'playing;
+                    }
+                    let levels = channel_levels(&batch);
+                    if levels.iter().any(|level| *level > 0) {
+                        let _ = sender.send(PlayerEvent::ChannelActivity(levels));
+                    }
+                    let passed = sequence
+                        .tempo_changes
+                        .partition_point(|change| change.at <= event_at);
+                    if passed > tempos_passed {
+                        tempos_passed = passed;
+                        let bpm = sequence.tempo_changes[passed - 1].bpm;
+                        let _ = sender.send(PlayerEvent::Tempo(bpm));
+                    }
+
+                    // Only gaps between the first and last note count; leading
+                    // and trailing silence is left to the file.
+                    if let Some(watch) = silence_watch
+                        && notes_played
+                        && active_notes.is_empty()
+                        && let Some(next_at) = next_note_on.get(index).copied().flatten()
+                        && next_at > gap_reported_until
+                    {
+                        let length = next_at.saturating_sub(event_at);
+                        if length > watch.threshold {
+                            gap_reported_until = next_at;
+                            let skipped = if watch.fast_forward {
+                                let skip = length.saturating_sub(GAP_LEAD);
+                                start = start.checked_sub(skip).unwrap_or(start);
+                                beat_index = sequence
+                                    .beats
+                                    .partition_point(|beat| beat.at < event_at + skip);
+                                skip
+                            } else {
+                                Duration::ZERO
+                            };
+                            let _ = sender.send(PlayerEvent::SilenceGap {
+                                at: event_at,
+                                length,
+                                skipped,
+                            });
+                        }
+                    }
+
+                    let now = TokioInstant::now();
+                    if now >= last_reported + progress_interval {
+                        last_reported = now;
+                        let _ = sender.send(PlayerEvent::Progress {
+                            elapsed: event_at,
+                            total: total_duration,
+                        });
+                    }
+                }
+
+                if let Some(fade_in) = fade_in.take() {
+                    fade_in.finish(&sink).await;
+                }
+                if interrupted {
                     return;
                 }
 
-                let mut batch: Vec<Vec<u8>> = Vec::new();
-                while index < total_events && sequence.events[index].at == event_at {
-                    batch.push(sequence.events[index].data.clone());
-                    index += 1;
+                // Files with unmatched note-ons would otherwise leave keys sounding.
+                let leftover = active_notes.note_off_messages();
+                if !leftover.is_empty() {
+                    let _ = sink.send_batch(&leftover).await;
                 }
 
-                if let Err(err) = sink.send_batch(&batch).await {
+                let pass_count = passes.load(Ordering::Relaxed);
+                let this_pass = pass.load(Ordering::Relaxed);
+                if this_pass >= pass_count {
+                    break;
+                }
+                pass.store(this_pass + 1, Ordering::Relaxed);
+                // The next pass starts where the song ends, as if it went on.
+                start += total_duration.saturating_sub(position);
+                position = top;
+                index = sequence.events.partition_point(|event| event.at < top);
+                beat_index = sequence.beats.partition_point(|beat| beat.at < top);
+                // Programs and controllers changed along the way go back to
+                // how they were at the top.
+                let setup: Vec<Vec<u8>> = sequence.events[..index]
+                    .iter()
+                    .filter(|event| !is_note_message(&event.data))
+                    .map(|event| event.data.clone())
+                    .collect();
+                if !setup.is_empty()
+                    && let Err(err) = sink.send_batch(&setup).await
+                {
                     let _ = sender.send(PlayerEvent::Error(err.to_string()));
                     return;
                 }
-
-                if event_at >= last_reported + PROGRESS_UPDATE_STEP || event_at >= total_duration {
-                    last_reported = event_at;
-                    let _ = sender.send(PlayerEvent::Progress {
-                        elapsed: event_at,
-                        total: total_duration,
-                    });
+                active_notes = ActiveNotes::default();
+                notes_played = false;
+                gap_reported_until = top;
+                let _ = sender.send(PlayerEvent::Repeated {
+                    pass: this_pass + 1,
+                    passes: pass_count,
+                    position,
+                    total: total_duration,
+                });
+                tempos_passed = sequence
+                    .tempo_changes
+                    .partition_point(|change| change.at <= top);
+                if let Some(bpm) = sequence.bpm_at(top) {
+                    let _ = sender.send(PlayerEvent::Tempo(bpm));
                 }
             }
 
@@ -118,26 +664,425 @@ impl MidiPlayer {
             let _ = sender.send(PlayerEvent::Finished);
         });
 
-        self.playback = Some(PlaybackHandle::new(cancel, join));
-
-        Ok(())
+        self.playback = Some(PlaybackHandle {
+            cancel,
+            join,
+            sink: handle_sink,
+            stopped,
+            fade_in: handle_fade_in,
+        });
     }
 
+    /// Stops playback, fading out first when a fade is set with
+    /// [`MidiPlayer::set_fade`]. [`PlayerEvent::Stopped`] is reported
+    /// straight away either way. Starting playback again cuts the fade out
+    /// short.
     pub fn stop(&mut self) {
-        self.stop_internal();
+        self.stop_internal(self.fade);
     }
 
-    fn stop_internal(&mut self) {
-        if let Some(handle) = self.playback.take() {
-            handle.cancel.notify_waiters();
-            let _ = self.event_sender.send(PlayerEvent::Stopped);
+    /// Stops playback without fading out, e.g. when the device is about to
+    /// be silenced anyway.
+    pub fn stop_now(&mut self) {
+        self.stop_internal(Duration::ZERO);
+    }
 
-            let join = handle.join;
-            tokio::spawn(async move {
-                let _ = join.await;
-            });
+    /// Stops playback, returning the task to wait for before anything else
+    /// is sent. A fade out is kept in `fading_out` instead; one already
+    /// under way is cut short.
+    fn stop_internal(&mut self, fade: Duration) -> Option<JoinHandle<()>> {
+        let fading_out = self.fading_out.take().map(FadeOut::cut_short);
+        let sequence = self.active_sequence.take();
+        let Some(handle) = self.playback.take() else {
+            return fading_out;
+        };
+        handle.stopped.store(true, Ordering::Relaxed);
+        let _ = self.event_sender.send(PlayerEvent::Stopped);
+        let Some(sequence) = sequence.filter(|_| !fade.is_zero()) else {
+            handle.cancel.notify_one();
+            return Some(handle.join);
+        };
+        let fade_in = handle.fade_in.take_over();
+        // The song plays on under the fade, then stops as usual.
+        let channels = fade_channels(&sequence);
+        let cut_short = Arc::new(Notify::new());
+        let cut = cut_short.clone();
+        let join = tokio::spawn(async move {
+            // A fade in still under way ends where it got to, and the fade
+            // out starts from there.
+            if let Some(fade_in) = fade_in {
+                fade_in.abort();
+                let _ = fade_in.await;
+            }
+            let level = &handle.fade_in.level;
+            let from = level.load(Ordering::Relaxed);
+            tokio::select! {
+                biased;
+                _ = cut.notified() => {}
+                result = ramp_expression(&handle.sink, &channels, from, 0, fade, level) => {
+                    if let Err(err) = result {
+                        log::warn!("failed to fade out: {err:?}");
+                    }
+                }
+            }
+            handle.cancel.notify_one();
+            let _ = handle.join.await;
+            if let Err(err) = handle
+                .sink
+                .send_batch(&expression_messages(&channels, 127))
+                .await
+            {
+                log::warn!("failed to restore expression after fading out: {err:?}");
+            }
+        });
+        self.fading_out = Some(FadeOut { cut_short, join });
+        None
+    }
+
+    /// Cancels the running playback without reporting it as stopped.
+    fn cancel(&mut self) -> Option<JoinHandle<()>> {
+        let handle = self.playback.take()?;
+        handle.cancel.notify_one();
+        Some(handle.join)
+    }
+}
+
+/// Plays the opening `length` of `sequence` on `sink` without going
+/// through a [`MidiPlayer`], e.g. to tell which piece a file is. Leading
+/// silence is skipped, with the setup messages before the first note sent up
+/// front. Notes still sounding when the time is up, or when `cancel` is
+/// notified, are released.
+pub async fn play_preview(
+    sequence: &MidiSequence,
+    sink: &SharedMidiSink,
+    length: Duration,
+    cancel: &Notify,
+) -> Result<()> {
+    let position = sequence
+        .events
+        .iter()
+        .find(|event| is_note_on(&event.data))
+        .map_or(Duration::ZERO, |event| {
+            event.at.saturating_sub(PREVIEW_PRE_ROLL)
+        });
+    let index = sequence.events.partition_point(|event| event.at < position);
+    let setup: Vec<Vec<u8>> = sequence.events[..index]
+        .iter()
+        .filter(|event| !is_note_message(&event.data))
+        .map(|event| event.data.clone())
+        .collect();
+    if !setup.is_empty() {
+        sink.send_batch(&setup).await?;
+    }
+
+    let mut active_notes = ActiveNotes::default();
+    let played = tokio::select! {
+        biased;
+        _ = cancel.notified() => Ok(()),
+        result = play_span(sequence, sink, index, position, length, &mut active_notes) => result,
+    };
+    let released = sink.send_batch(&active_notes.release_messages()).await;
+    played.and(released)
+}
+
+/// Sends the events from `index`, `position` into the sequence, until
+/// `length` has passed or the sequence ends.
+async fn play_span(
+    sequence: &MidiSequence,
+    sink: &SharedMidiSink,
+    mut index: usize,
+    position: Duration,
+    length: Duration,
+    active_notes: &mut ActiveNotes,
+) -> Result<()> {
+    let start = TokioInstant::now();
+    let end = position + length;
+    while let Some(event_at) = sequence
+        .events
+        .get(index)
+        .map(|event| event.at)
+        .filter(|at| *at < end)
+    {
+        time::sleep_until(start + event_at.saturating_sub(position)).await;
+        let mut batch = Vec::new();
+        while let Some(event) = sequence
+            .events
+            .get(index)
+            .filter(|event| event.at == event_at)
+        {
+            active_notes.track(&event.data);
+            batch.push(event.data.clone());
+            index += 1;
+        }
+        sink.send_batch(&batch).await?;
+    }
+    // Notes still held when the song goes on sound out the full length.
+    if index < sequence.events.len() {
+        time::sleep_until(start + length).await;
+    }
+    Ok(())
+}
+
+fn is_note_message(data: &[u8]) -> bool {
+    matches!(data.first(), Some(status) if matches!(status & 0xF0, 0x80 | 0x90 | 0xA0))
+}
+
+fn is_note_on(data: &[u8]) -> bool {
+    matches!(data, [status, _, velocity, ..] if status & 0xF0 == 0x90 && *velocity > 0)
+}
+
+fn channel_levels(batch: &[Vec<u8>]) -> [u8; 16] {
+    let mut levels = [0u8; 16];
+    for data in batch.iter().filter(|data| is_note_on(data)) {
+        let channel = usize::from(data[0] & 0x0F);
+        levels[channel] = levels[channel].max(data[2]);
+    }
+    levels
+}
+
+/// A measure of clicks ending on the first note.
+struct CountIn {
+    beat: Duration,
+    beats: u8,
+    beat_unit: u8,
+    /// How long the clicks hold back `position`.
+    delay: Duration,
+    /// When the first note plays, measured from the start of the count-in.
+    first_note: Duration,
+}
+
+impl CountIn {
+    fn before(sequence: &MidiSequence, first_note: Duration, position: Duration) -> Self {
+        let marker = sequence
+            .beat_at(first_note)
+            .or_else(|| sequence.beats.first().copied());
+        let beat = marker
+            .and_then(|marker| {
+                let next = sequence.beats.iter().find(|beat| beat.at > marker.at)?;
+                Some(next.at - marker.at)
+            })
+            .filter(|beat| !beat.is_zero())
+            .unwrap_or(DEFAULT_BEAT);
+        let beats = marker.map_or(4, |marker| marker.beats_per_measure.max(1));
+        let beat_unit = marker.map_or(4, |marker| marker.beat_unit);
+        let length = beat * u32::from(beats);
+        // Any pre-roll before the first note overlaps the count-in.
+        let lead = first_note.saturating_sub(position);
+        Self {
+            beat,
+            beats,
+            beat_unit,
+            delay: length.saturating_sub(lead),
+            first_note: length.max(lead),
+        }
+    }
+
+    fn clicks(&self, start: TokioInstant) -> impl Iterator<Item = (TokioInstant, Vec<Vec<u8>>)> {
+        let first_note = start + self.first_note;
+        (0..self.beats).map(move |beat| {
+            let accent = Accent::of(beat + 1, self.beats, self.beat_unit);
+            let at = first_note - self.beat * u32::from(self.beats - beat);
+            (at, click(accent))
+        })
+    }
+}
+
+/// A wood block click, higher and louder the stronger the beat.
+fn click(accent: Accent) -> Vec<Vec<u8>> {
+    let (key, velocity) = match accent {
+        Accent::Strong => (ACCENT_CLICK, 110),
+        Accent::Medium => (ACCENT_CLICK, 80),
+        Accent::Weak => (CLICK, 80),
+    };
+    vec![vec![0x99, key, velocity], vec![0x89, key, 0]]
+}
+
+/// For each event index, the time of the first note-on at or after it.
+fn next_note_on_times(sequence: &MidiSequence) -> Vec<Option<Duration>> {
+    let mut times = vec![None; sequence.events.len() + 1];
+    for (index, event) in sequence.events.iter().enumerate().rev() {
+        times[index] = if is_note_on(&event.data) {
+            Some(event.at)
+        } else {
+            times[index + 1]
+        };
+    }
+    times
+}
+
+/// Notes currently sounding, per channel and key, so they can be released
+/// when playback is interrupted.
+#[derive(Default)]
+struct ActiveNotes {
+    keys: HashSet<(u8, u8)>,
+}
+
+impl ActiveNotes {
+    fn is_empty(&self) -> bool {
+        self.keys.is_empty()
+    }
+
+    fn track(&mut self, data: &[u8]) {
+        let [status, key, velocity, ..] = *data else {
+            return;
+        };
+        let channel = status & 0x0F;
+        match status & 0xF0 {
+            0x90 if velocity > 0 => {
+                self.keys.insert((channel, key));
+            }
+            0x80 | 0x90 => {
+                self.keys.remove(&(channel, key));
+            }
+            _ => {}
+        }
+    }
+
+    fn note_off_messages(&self) -> Vec<Vec<u8>> {
+        self.keys
+            .iter()
+            .map(|&(channel, key)| vec![0x80 | channel, key, 0])
+            .collect()
+    }
+
+    fn release_messages(&self) -> Vec<Vec<u8>> {
+        let mut messages = self.note_off_messages();
+        messages.extend(panic_messages());
+        messages
+    }
+}
+
+/// Channels with notes that leave expression to the player.
+fn fade_channels(sequence: &MidiSequence) -> Vec<u8> {
+    let mut notes = [false; 16];
+    let mut automated = [false; 16];
+    for event in &sequence.events {
+        match *event.data.as_slice() {
+            [status, EXPRESSION, ..] if status & 0xF0 == 0xB0 => {
+                automated[usize::from(status & 0x0F)] = true;
+            }
+            [status, _, velocity, ..] if status & 0xF0 == 0x90 && velocity > 0 => {
+                notes[usize::from(status & 0x0F)] = true;
+            }
+            _ => {}
+        }
+    }
+    (0..16u8)
+        .filter(|&channel| notes[usize::from(channel)] && !automated[usize::from(channel)])
+        .collect()
+}
+
+fn expression_messages(channels: &[u8], value: u8) -> Vec<Vec<u8>> {
+    channels
+        .iter()
+        .map(|channel| vec![0xB0 | channel, EXPRESSION, value])
+        .collect()
+}
+
+/// Moves expression from `from` to `to` in even steps over `length`,
+/// keeping `level` at the value last sent.
+async fn ramp_expression(
+    sink: &SharedMidiSink,
+    channels: &[u8],
+    from: u8,
+    to: u8,
+    length: Duration,
+    level: &AtomicU8,
+) -> Result<()> {
+    if channels.is_empty() {
+        return Ok(());
+    }
+    for step in 1..=FADE_STEPS {
+        time::sleep(length / FADE_STEPS).await;
+        let value =
+            i32::from(from) + (i32::from(to) - i32::from(from)) * step as i32 / FADE_STEPS as i32;
+        sink.send_batch(&expression_messages(channels, value as u8))
+            .await?;
+        level.store(value as u8, Ordering::Relaxed);
+    }
+    Ok(())
+}
+
+/// The fade in of a playback, shared with its handle so that stopping can
+/// cut it short and fade out from where it got to.
+#[derive(Clone)]
+struct FadeIn {
+    task: Arc<Mutex<FadeTask>>,
+    /// Expression last sent on the faded channels.
+    level: Arc<AtomicU8>,
+}
+
+#[derive(Default)]
+struct FadeTask {
+    running: Option<JoinHandle<()>>,
+    /// Set when a fade out has taken over, so no fade in starts after it.
+    taken_over: bool,
+}
+
+impl Default for FadeIn {
+    fn default() -> Self {
+        Self {
+            task: Arc::default(),
+            level: Arc::new(AtomicU8::new(127)),
+        }
+    }
+}
+
+impl FadeIn {
+    /// Hands expression over to a fade out, returning the fade in if it is
+    /// still running.
+    fn take_over(&self) -> Option<JoinHandle<()>> {
+        let mut task = self.task.lock().unwrap();
+        task.taken_over = true;
+        task.running.take()
+    }
+}
+
+/// A fade in running alongside playback.
+struct Fade {
+    state: FadeIn,
+    channels: Vec<u8>,
+}
+
+impl Fade {
+    /// Ramps `channels` up to full expression over `length`, unless a fade
+    /// out has already taken over.
+    fn start(sink: SharedMidiSink, channels: Vec<u8>, length: Duration, state: FadeIn) -> Self {
+        let mut task = state.task.lock().unwrap();
+        if !task.taken_over {
+            let ramp_channels = channels.clone();
+            let level = state.level.clone();
+            task.running = Some(tokio::spawn(async move {
+                let from = level.load(Ordering::Relaxed);
+                if let Err(err) =
+                    ramp_expression(&sink, &ramp_channels, from, 127, length, &level).await
+                {
+                    log::warn!("failed to fade in: {err:?}");
+                }
+            }));
+        }
+        drop(task);
+        Self { state, channels }
+    }
+
+    /// Ends the fade, restoring full expression if it had not got there,
+    /// e.g. for a song shorter than the fade. Does nothing once stopping has
+    /// taken the fade over.
+    async fn finish(self, sink: &SharedMidiSink) {
+        let Some(task) = self.state.task.lock().unwrap().running.take() else {
+            return;
+        };
+        if task.is_finished() {
+            return;
+        }
+        task.abort();
+        if sink
+            .send_batch(&expression_messages(&self.channels, 127))
+            .await
+            .is_ok()
+        {
+            self.state.level.store(127, Ordering::Relaxed);
         }
-        self.active_sequence = None;
     }
 }
 
@@ -0,0 +1,140 @@
+use std::fs;
+use std::io::Read;
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+
+use anyhow::{Context, Result, bail};
+use serde::{Deserialize, Serialize};
+use uuid::Uuid;
+
+use super::sequence::check_timecode;
+
+const REQUEST_TIMEOUT: Duration = Duration::from_secs(30);
+/// Catalogs and MIDI files are small; anything larger is refused.
+const MAX_DOWNLOAD_BYTES: u64 = 16 * 1024 * 1024;
+
+/// One song listed in a remote catalog. A catalog is a JSON array of these.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct RemoteEntry {
+    pub name: String,
+    pub url: String,
+}
+
+pub fn parse_catalog(json: &str) -> Result<Vec<RemoteEntry>> {
+    let entries: Vec<RemoteEntry> =
+        serde_json::from_str(json).context("failed to parse remote catalog")?;
+    Ok(entries
+        .into_iter()
+        .filter(|entry| !entry.name.trim().is_empty() && !entry.url.trim().is_empty())
+        .collect())
+}
+
+pub fn fetch_catalog(url: &str) -> Result<Vec<RemoteEntry>> {
+    let bytes = http_get(url)?;
+    let json = String::from_utf8(bytes).context("remote catalog is not valid UTF-8")?;
+    parse_catalog(&json)
+}
+
+fn http_get(url: &str) -> Result<Vec<u8>> {
+    let response = ureq::get(url)
+        .timeout(REQUEST_TIMEOUT)
+        .call()
+        .with_context(|| format!("request to {url} failed"))?;
+    let mut body = Vec::new();
+    response
+        .into_reader()
+        .take(MAX_DOWNLOAD_BYTES + 1)
+        .read_to_end(&mut body)
+        .with_context(|| format!("failed to read response from {url}"))?;
+    if body.len() as u64 > MAX_DOWNLOAD_BYTES {
+        bail!("response from {url} is larger than {MAX_DOWNLOAD_BYTES} bytes");
+    }
+    Ok(body)
+}
+
+/// Directory of downloaded remote files, named after their URL so a file is
+/// only fetched once.
+#[derive(Debug, Clone)]
+pub struct RemoteCache {
+    dir: PathBuf,
+}
+
+impl RemoteCache {
+    pub fn new(dir: impl Into<PathBuf>) -> Self {
+        Self { dir: dir.into() }
+    }
+
+    pub fn path_for(&self, url: &str) -> PathBuf {
+        let id = Uuid::new_v5(&Uuid::NAMESPACE_URL, url.as_bytes());
+        self.dir.join(format!("{id}.mid"))
+    }
+
+    /// Returns the cached copy of `url`, downloading it first if needed.
+    /// Downloads that do not parse as MIDI are rejected and not cached.
+    pub fn fetch(&self, url: &str) -> Result<PathBuf> {
+        let path = self.path_for(url);
+        if !path.exists() {
+            self.fetch_bytes(url)?;
+        }
+        Ok(path)
+    }
+
+    /// Like [`RemoteCache::fetch`], but hands back the file's contents so
+    /// a fresh download can be used without reading it back from disk.
+    pub fn fetch_bytes(&self, url: &str) -> Result<Vec<u8>> {
+        let path = self.path_for(url);
+        if path.exists() {
+            return fs::read(&path).with_context(|| format!("failed to read {}", path.display()));
+        }
+        let bytes = http_get(url)?;
+        check_timecode(&bytes)?;
+        midly::Smf::parse(&bytes).with_context(|| format!("{url} is not a MIDI file"))?;
+        self.store(&path, &bytes)?;
+        Ok(bytes)
+    }
+
+    fn store(&self, path: &Path, bytes: &[u8]) -> Result<()> {
+        fs::create_dir_all(&self.dir)
+            .with_context(|| format!("failed to create {}", self.dir.display()))?;
+        // Write then rename so an interrupted download never looks cached.
+        let partial = path.with_extension("part");
+        fs::write(&partial, bytes)
+            .with_context(|| format!("failed to write {}", partial.display()))?;
+        fs::rename(&partial, path).with_context(|| format!("failed to store {}", path.display()))
+    }
+
+    /// Number of cached files and their total size in bytes.
+    pub fn usage(&self) -> Result<(usize, u64)> {
+        let mut files = 0;
+        let mut bytes = 0;
+        for path in self.cached_files()? {
+            files += 1;
+            bytes += fs::metadata(&path).map(|meta| meta.len()).unwrap_or(0);
+        }
+        Ok((files, bytes))
+    }
+
+    /// Deletes every cached file, returning how many were removed.
+    pub fn clear(&self) -> Result<usize> {
+        let mut removed = 0;
+        for path in self.cached_files()? {
+            fs::remove_file(&path)
+                .with_context(|| format!("failed to remove {}", path.display()))?;
+            removed += 1;
+        }
+        Ok(removed)
+    }
+
+    fn cached_files(&self) -> Result<Vec<PathBuf>> {
+        if !self.dir.exists() {
+            return Ok(Vec::new());
+        }
+        let listing = fs::read_dir(&self.dir)
+            .with_context(|| format!("failed to read {}", self.dir.display()))?;
+        Ok(listing
+            .filter_map(|entry| entry.ok())
+            .map(|entry| entry.path())
+            .filter(|path| path.is_file())
+            .collect())
+    }
+}
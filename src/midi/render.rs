@@ -0,0 +1,419 @@
+use std::path::Path;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::time::Duration;
+
+use anyhow::{Context, Result, bail};
+
+use super::sequence::MidiSequence;
+use super::soundfont::{SoundFont, VoiceRegion, decibels_to_gain};
+
+pub const RENDER_SAMPLE_RATE: u32 = 44_100;
+const MAX_VOICES: usize = 128;
+const MAX_TAIL: Duration = Duration::from_secs(3);
+const MASTER_GAIN: f32 = 0.4;
+const PITCH_BEND_RANGE: f32 = 2.0;
+const PERCUSSION_CHANNEL: usize = 9;
+
+#[derive(Debug, Clone, Copy)]
+pub struct RenderProgress {
+    pub rendered: Duration,
+    pub total: Duration,
+}
+
+impl RenderProgress {
+    pub fn fraction(&self) -> f32 {
+        if self.total.is_zero() {
+            1.0
+        } else {
+            (self.rendered.as_secs_f32() / self.total.as_secs_f32()).clamp(0.0, 1.0)
+        }
+    }
+}
+
+/// Renders a sequence to a 16-bit stereo WAV file without involving any sink,
+/// as fast as the CPU allows. Returns `Ok(false)` when cancelled.
+pub fn render_to_wav(
+    sequence: &MidiSequence,
+    soundfont: &SoundFont,
+    output: &Path,
+    cancel: &AtomicBool,
+    mut on_progress: impl FnMut(RenderProgress),
+) -> Result<bool> {
+    if sequence.events.is_empty() {
+        bail!("selected MIDI file does not contain playable events");
+    }
+
+    let spec = hound::WavSpec {
+        channels: 2,
+        sample_rate: RENDER_SAMPLE_RATE,
+        bits_per_sample: 16,
+        sample_format: hound::SampleFormat::Int,
+    };
+    let mut writer = hound::WavWriter::create(output, spec)
+        .with_context(|| format!("failed to create {}", output.display()))?;
+
+    let mut synth = OfflineSynth::new(soundfont);
+    let mut buffer = Vec::new();
+    let mut rendered_frames: u64 = 0;
+    let total = sequence.duration;
+    let mut last_report = Duration::ZERO;
+
+    for event in &sequence.events {
+        if cancel.load(Ordering::Relaxed) {
+            return Ok(false);
+        }
+        let target_frames = duration_to_frames(event.at);
+        if target_frames > rendered_frames {
+            buffer.clear();
+            synth.render((target_frames - rendered_frames) as usize, &mut buffer);
+            write_frames(&mut writer, &buffer)?;
+            rendered_frames = target_frames;
+        }
+        synth.handle(&event.data);
+
+        if event.at >= last_report + Duration::from_millis(500) {
+            last_report = event.at;
+            on_progress(RenderProgress {
+                rendered: event.at,
+                total,
+            });
+        }
+    }
+
+    synth.release_all();
+    let tail_limit = rendered_frames + duration_to_frames(MAX_TAIL);
+    while synth.has_active_voices() && rendered_frames < tail_limit {
+        buffer.clear();
+        let block = 1024.min((tail_limit - rendered_frames) as usize);
+        synth.render(block, &mut buffer);
+        write_frames(&mut writer, &buffer)?;
+        rendered_frames += block as u64;
+    }
+
+    writer
+        .finalize()
+        .with_context(|| format!("failed to finalize {}", output.display()))?;
+    on_progress(RenderProgress {
+        rendered: total,
+        total,
+    });
+    Ok(true)
+}
+
+fn duration_to_frames(duration: Duration) -> u64 {
+    (duration.as_secs_f64() * RENDER_SAMPLE_RATE as f64) as u64
+}
+
+fn write_frames<W: std::io::Write + std::io::Seek>(
+    writer: &mut hound::WavWriter<W>,
+    frames: &[(f32, f32)],
+) -> Result<()> {
+    for (left, right) in frames {
+        for sample in [left, right] {
+            let value = (sample * MASTER_GAIN).clamp(-1.0, 1.0);
+            writer
+                .write_sample((value * i16::MAX as f32) as i16)
+                .context("failed to write WAV sample")?;
+        }
+    }
+    Ok(())
+}
+
+#[derive(Debug, Clone, Copy)]
+struct ChannelState {
+    program: u8,
+    bank: u16,
+    volume: f32,
+    expression: f32,
+    pan: f32,
+    pitch_bend: f32,
+    sustain: bool,
+}
+
+impl Default for ChannelState {
+    fn default() -> Self {
+        Self {
+            program: 0,
+            bank: 0,
+            volume: 100.0 / 127.0,
+            expression: 1.0,
+            pan: 0.0,
+            pitch_bend: 0.0,
+            sustain: false,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum EnvelopeStage {
+    Attack,
+    Hold,
+    Decay,
+    Sustain,
+    Release,
+    Done,
+}
+
+struct Voice {
+    channel: usize,
+    key: u8,
+    region: VoiceRegion,
+    position: f64,
+    velocity_gain: f32,
+    stage: EnvelopeStage,
+    stage_time: f32,
+    level: f32,
+    release_start: f32,
+    held_by_pedal: bool,
+}
+
+impl Voice {
+    fn advance_envelope(&mut self, dt: f32) {
+        self.stage_time += dt;
+        let region = &self.region;
+        match self.stage {
+            EnvelopeStage::Attack => {
+                if self.stage_time >= region.attack {
+                    self.level = 1.0;
+                    self.stage = EnvelopeStage::Hold;
+                    self.stage_time = 0.0;
+                } else {
+                    self.level = self.stage_time / region.attack;
+                }
+            }
+            EnvelopeStage::Hold => {
+                if self.stage_time >= region.hold {
+                    self.stage = EnvelopeStage::Decay;
+                    self.stage_time = 0.0;
+                }
+            }
+            EnvelopeStage::Decay => {
+                if self.stage_time >= region.decay {
+                    self.level = region.sustain_level;
+                    self.stage = EnvelopeStage::Sustain;
+                } else {
+                    let progress = self.stage_time / region.decay;
+                    self.level = 1.0 + (region.sustain_level - 1.0) * progress;
+                }
+            }
+            EnvelopeStage::Sustain => {
+                if region.sustain_level <= 0.0001 {
+                    self.stage = EnvelopeStage::Done;
+                }
+            }
+            EnvelopeStage::Release => {
+                let progress = self.stage_time / region.release;
+                if progress >= 1.0 {
+                    self.level = 0.0;
+                    self.stage = EnvelopeStage::Done;
+                } else {
+                    self.level = self.release_start * (1.0 - progress);
+                }
+            }
+            EnvelopeStage::Done => {}
+        }
+    }
+
+    fn release(&mut self) {
+        if !matches!(self.stage, EnvelopeStage::Release | EnvelopeStage::Done) {
+            self.release_start = self.level;
+            self.stage = EnvelopeStage::Release;
+            self.stage_time = 0.0;
+        }
+    }
+
+    fn next_sample(&mut self, step: f64) -> Option<f32> {
+        let region = &self.region;
+        if region.looping && self.position >= region.loop_end as f64 {
+            let loop_len = (region.loop_end - region.loop_start) as f64;
+            self.position =
+                region.loop_start as f64 + (self.position - region.loop_start as f64) % loop_len;
+        }
+        let index = self.position as usize;
+        if index >= region.end || (!region.looping && index + 1 >= region.end) {
+            return None;
+        }
+        let frac = (self.position - index as f64) as f32;
+        let a = region.samples[index] as f32;
+        let next = if region.looping && index + 1 >= region.loop_end {
+            region.loop_start
+        } else {
+            index + 1
+        };
+        let b = region.samples[next] as f32;
+        self.position += step;
+        Some((a + (b - a) * frac) / 32768.0)
+    }
+}
+
+struct OfflineSynth<'a> {
+    soundfont: &'a SoundFont,
+    channels: [ChannelState; 16],
+    voices: Vec<Voice>,
+}
+
+impl<'a> OfflineSynth<'a> {
+    fn new(soundfont: &'a SoundFont) -> Self {
+        let mut channels = [ChannelState::default(); 16];
+        channels[PERCUSSION_CHANNEL].bank = 128;
+        Self {
+            soundfont,
+            channels,
+            voices: Vec::new(),
+        }
+    }
+
+    fn handle(&mut self, data: &[u8]) {
+        let Some(&status) = data.first() else {
+            return;
+        };
+        if status >= 0xF0 {
+            return;
+        }
+        let channel = (status & 0x0F) as usize;
+        let data1 = data.get(1).copied().unwrap_or(0);
+        let data2 = data.get(2).copied().unwrap_or(0);
+        match status & 0xF0 {
+            0x90 if data2 > 0 => self.note_on(channel, data1, data2),
+            0x80 | 0x90 => self.note_off(channel, data1),
+            0xB0 => self.controller(channel, data1, data2),
+            0xC0 => self.channels[channel].program = data1,
+            0xE0 => {
+                let raw = ((data2 as u16) << 7 | data1 as u16) as f32;
+                self.channels[channel].pitch_bend = (raw - 8192.0) / 8192.0 * PITCH_BEND_RANGE;
+            }
+            _ => {}
+        }
+    }
+
+    fn note_on(&mut self, channel: usize, key: u8, velocity: u8) {
+        let state = self.channels[channel];
+        // Retriggering a held key restarts it rather than stacking voices.
+        for voice in self
+            .voices
+            .iter_mut()
+            .filter(|voice| voice.channel == channel && voice.key == key)
+        {
+            voice.release();
+        }
+        let velocity_gain = (velocity as f32 / 127.0).powi(2);
+        for region in self
+            .soundfont
+            .regions(state.bank, state.program, key, velocity)
+        {
+            if self.voices.len() >= MAX_VOICES {
+                self.voices.remove(0);
+            }
+            self.voices.push(Voice {
+                channel,
+                key,
+                position: region.start as f64,
+                region,
+                velocity_gain,
+                stage: EnvelopeStage::Attack,
+                stage_time: 0.0,
+                level: 0.0,
+                release_start: 0.0,
+                held_by_pedal: false,
+            });
+        }
+    }
+
+    fn note_off(&mut self, channel: usize, key: u8) {
+        let sustain = self.channels[channel].sustain;
+        for voice in self
+            .voices
+            .iter_mut()
+            .filter(|voice| voice.channel == channel && voice.key == key)
+        {
+            if sustain {
+                voice.held_by_pedal = true;
+            } else {
+                voice.release();
+            }
+        }
+    }
+
+    fn controller(&mut self, channel: usize, controller: u8, value: u8) {
+        let state = &mut self.channels[channel];
+        match controller {
+            0 if channel != PERCUSSION_CHANNEL => state.bank = value as u16,
+            7 => state.volume = value as f32 / 127.0,
+            10 => state.pan = (value as f32 - 64.0) / 64.0,
+            11 => state.expression = value as f32 / 127.0,
+            64 => {
+                state.sustain = value >= 64;
+                if !state.sustain {
+                    for voice in self
+                        .voices
+                        .iter_mut()
+                        .filter(|voice| voice.channel == channel && voice.held_by_pedal)
+                    {
+                        voice.release();
+                    }
+                }
+            }
+            120 | 123 => {
+                for voice in self
+                    .voices
+                    .iter_mut()
+                    .filter(|voice| voice.channel == channel)
+                {
+                    voice.release();
+                }
+            }
+            121 => {
+                *state = ChannelState {
+                    program: state.program,
+                    bank: state.bank,
+                    ..ChannelState::default()
+                };
+            }
+            _ => {}
+        }
+    }
+
+    fn release_all(&mut self) {
+        for voice in &mut self.voices {
+            voice.release();
+        }
+    }
+
+    fn has_active_voices(&self) -> bool {
+        !self.voices.is_empty()
+    }
+
+    fn render(&mut self, frames: usize, out: &mut Vec<(f32, f32)>) {
+        out.resize(out.len() + frames, (0.0, 0.0));
+        let offset = out.len() - frames;
+        let dt = 1.0 / RENDER_SAMPLE_RATE as f32;
+        for voice in &mut self.voices {
+            let state = self.channels[voice.channel];
+            let semitones = voice.region.pitch_offset + state.pitch_bend;
+            let step = 2f64.powf(semitones as f64 / 12.0) * voice.region.sample_rate as f64
+                / RENDER_SAMPLE_RATE as f64;
+            let gain = voice.velocity_gain
+                * decibels_to_gain(voice.region.attenuation_db)
+                * state.volume
+                * state.expression;
+            let pan = (voice.region.pan + state.pan).clamp(-1.0, 1.0);
+            let left_gain = gain * (1.0 - pan).min(1.0);
+            let right_gain = gain * (1.0 + pan).min(1.0);
+            for frame in out[offset..].iter_mut() {
+                voice.advance_envelope(dt);
+                if voice.stage == EnvelopeStage::Done {
+                    break;
+                }
+                let Some(sample) = voice.next_sample(step) else {
+                    voice.stage = EnvelopeStage::Done;
+                    break;
+                };
+                let value = sample * voice.level;
+                frame.0 += value * left_gain;
+                frame.1 += value * right_gain;
+            }
+        }
+        self.voices
+            .retain(|voice| voice.stage != EnvelopeStage::Done);
+    }
+}
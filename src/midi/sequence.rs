@@ -1,65 +1,947 @@
+use std::borrow::Cow;
 use std::cmp::Ordering;
-use std::fs;
-use std::path::Path;
+use std::collections::{BTreeMap, BTreeSet};
+use std::fmt;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
 use std::time::Duration;
 
-use anyhow::{Context, Result, bail};
-use midly::num::u4;
-use midly::{MetaMessage, MidiMessage, Smf, Timing, TrackEventKind};
+use anyhow::{Context, Result};
+use midly::live::LiveEvent;
+use midly::num::{u4, u15, u24, u28};
+use midly::{Format, Header, MetaMessage, MidiMessage, Smf, Timing, TrackEvent, TrackEventKind};
+use serde::{Deserialize, Serialize};
+
+use super::library::{SongDetails, read_midi_file};
+use super::validate::{self, ValidationReport};
+use crate::error::PlaybackError;
+
+/// Resolution of files written by [`MidiSequence::to_smf_bytes`]. At the
+/// fixed export tempo of 120 BPM a tick is about half a millisecond.
+const EXPORT_PPQ: u16 = 960;
+const EXPORT_TEMPO: u32 = 500_000;
+/// General MIDI channel 10, numbered from 0.
+const PERCUSSION_CHANNEL: u8 = 9;
+/// Longest song that is played; anything longer is taken to be a broken or
+/// hostile file rather than music.
+const MAX_DURATION: Duration = Duration::from_secs(24 * 60 * 60);
+/// Beat markers laid out at most, a day at about 140 BPM. Files with tiny
+/// beats would otherwise ask for billions.
+const MAX_BEATS: usize = 200_000;
 
 #[derive(Clone, Debug)]
 pub struct PlaybackEvent {
     pub at: Duration,
     pub data: Vec<u8>,
+    /// Index of the SMF track the event came from.
+    pub track: u16,
+}
+
+/// A metrical position derived from the file's time signatures, used to
+/// follow along in a printed score.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct BeatMarker {
+    pub at: Duration,
+    pub measure: u32,
+    pub beat: u8,
+    pub beats_per_measure: u8,
+    /// The note value counted as a beat, as the signature's lower number: 4
+    /// for quarter notes, 8 for eighths.
+    pub beat_unit: u8,
+}
+
+impl BeatMarker {
+    pub fn accent(&self) -> Accent {
+        Accent::of(self.beat, self.beats_per_measure, self.beat_unit)
+    }
+}
+
+/// How hard a beat falls within its measure, for metronome clicks.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Accent {
+    /// The first beat of a measure.
+    Strong,
+    /// The start of each later group of three in compound meters such as
+    /// 6/8 or 12/8.
+    Medium,
+    Weak,
+}
+
+impl Accent {
+    /// The accent of `beat`, counting from one, in a measure of
+    /// `beats_per_measure` beats of `beat_unit` notes.
+    pub fn of(beat: u8, beats_per_measure: u8, beat_unit: u8) -> Self {
+        let compound =
+            beat_unit >= 8 && beats_per_measure > 3 && beats_per_measure.is_multiple_of(3);
+        if beat <= 1 {
+            Accent::Strong
+        } else if compound && (beat - 1).is_multiple_of(3) {
+            Accent::Medium
+        } else {
+            Accent::Weak
+        }
+    }
+}
+
+/// Bars and beats as `measure:beat`, like `24:3`.
+impl fmt::Display for BeatMarker {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}:{}", self.measure, self.beat)
+    }
+}
+
+#[derive(Clone, Debug)]
+pub struct TempoChange {
+    pub at: Duration,
+    pub bpm: f64,
+}
+
+/// How fast a song goes, from its tempo map.
+#[derive(Clone, Copy, Debug, PartialEq, Serialize, Deserialize)]
+pub struct TempoSummary {
+    /// The tempo the song starts at.
+    pub initial_bpm: f64,
+    /// Beats per minute over the whole song, so long passages count for
+    /// more than short ones.
+    pub average_bpm: f64,
+}
+
+impl TempoSummary {
+    /// Whether the tempo changes enough along the way for the average to
+    /// read differently from the start.
+    pub fn varies(&self) -> bool {
+        self.initial_bpm.round() != self.average_bpm.round()
+    }
+}
+
+/// How long a file plays and how fast; see [`file_timing`].
+#[derive(Clone, Copy, Debug)]
+pub struct FileTiming {
+    pub duration: Duration,
+    pub tempo: TempoSummary,
+}
+
+#[derive(Clone, Debug)]
+pub struct TimeSignatureChange {
+    pub at: Duration,
+    pub numerator: u8,
+    pub denominator: u16,
+}
+
+#[derive(Clone, Debug)]
+pub struct TrackSummary {
+    pub name: Option<String>,
+    pub channels: Vec<u8>,
+    pub note_count: usize,
+}
+
+/// How long a file plays, matching [`MidiSequence::duration`] without
+/// building the event list: only tempo changes and the tick of the last
+/// non-meta event are kept while walking the tracks.
+pub fn file_duration(contents: &[u8]) -> Result<Duration> {
+    file_timing(contents).map(|timing| timing.duration)
+}
+
+/// A title and composer as the file gives them: the first track's name,
+/// which in format 0 and 1 files names the song, and whoever its copyright
+/// notice credits. Only the first track is read.
+pub fn file_details(contents: &[u8]) -> Result<SongDetails> {
+    check_timecode(contents)?;
+    let (_, mut tracks) = midly::parse(contents).context(PlaybackError::Parse("header".into()))?;
+    let mut details = SongDetails::default();
+    let Some(track) = tracks.next() else {
+        return Ok(details);
+    };
+    for event in track.context(PlaybackError::Parse("track".into()))? {
+        let event = event.context(PlaybackError::Parse("event".into()))?;
+        match event.kind {
+            TrackEventKind::Meta(MetaMessage::TrackName(name)) if details.title.is_none() => {
+                details.title = track_name(name);
+            }
+            TrackEventKind::Meta(MetaMessage::Copyright(notice)) if details.composer.is_none() => {
+                details.composer = copyright_holder(notice);
+            }
+            _ => {}
+        }
+    }
+    Ok(details)
+}
+
+/// "Copyright © 2004 by Bernd Krueger" credits Bernd Krueger.
+fn copyright_holder(raw: &[u8]) -> Option<String> {
+    let notice = String::from_utf8_lossy(raw);
+    let holder: Vec<&str> = notice
+        .split_whitespace()
+        .skip_while(|word| {
+            let word = word
+                .trim_matches(|c: char| matches!(c, ',' | '.' | ':' | ';'))
+                .to_lowercase();
+            matches!(word.as_str(), "" | "copyright" | "(c)" | "©" | "by")
+                || word.chars().all(|c| c.is_ascii_digit() || c == '-')
+        })
+        .collect();
+    (!holder.is_empty()).then(|| holder.join(" "))
+}
+
+/// [`file_duration`] along with the file's tempo, read in the same pass.
+pub fn file_timing(contents: &[u8]) -> Result<FileTiming> {
+    check_timecode(contents)?;
+    let (header, tracks) = midly::parse(contents).context(PlaybackError::Parse("header".into()))?;
+    let ppq = metrical_ppq(header.timing)?;
+
+    let mut tempo_changes = Vec::new();
+    let mut last_tick = 0;
+    for track in tracks {
+        let track = track.context(PlaybackError::Parse("track".into()))?;
+        let mut tick: u64 = 0;
+        for event in track {
+            let event = event.context(PlaybackError::Parse("event".into()))?;
+            tick = tick.saturating_add(event.delta.as_int().into());
+            match event.kind {
+                TrackEventKind::Meta(MetaMessage::Tempo(tempo)) => {
+                    tempo_changes.push(TempoEntry {
+                        tick,
+                        micros_per_quarter: tempo.as_int(),
+                    });
+                }
+                TrackEventKind::Meta(_) => {}
+                _ => last_tick = last_tick.max(tick),
+            }
+        }
+    }
+    let tempo_map = TempoMap::new(tempo_changes, ppq);
+    Ok(FileTiming {
+        duration: tempo_map.ticks_to_duration(last_tick),
+        tempo: tempo_map.summary(last_tick),
+    })
+}
+
+/// Descriptive metadata about a MIDI file, gathered on demand for display
+/// rather than during playback preparation.
+#[derive(Clone, Debug)]
+pub struct SequenceInfo {
+    pub file_size: u64,
+    pub format: u8,
+    /// Pulses per quarter note; `None` for timecode-based files.
+    pub ppq: Option<u16>,
+    pub tempo_changes: Vec<TempoChange>,
+    /// `None` for timecode-based files, which have no tempo.
+    pub tempo: Option<TempoSummary>,
+    pub time_signatures: Vec<TimeSignatureChange>,
+    /// Bars by the file's time signatures; zero for timecode-based files.
+    pub measures: u32,
+    pub tracks: Vec<TrackSummary>,
+    pub channels: Vec<u8>,
+    /// Track names labelling channels; see [`MidiSequence::channel_names`].
+    pub channel_names: [Option<String>; 16],
+    /// Distinct (channel, program) pairs in order of channel.
+    pub programs: Vec<(u8, u8)>,
+    /// Note-on counts indexed by key number (128 entries).
+    pub note_histogram: Vec<u32>,
+}
+
+impl SequenceInfo {
+    pub fn note_range(&self) -> Option<(u8, u8)> {
+        let lowest = self.note_histogram.iter().position(|&count| count > 0)?;
+        let highest = self.note_histogram.iter().rposition(|&count| count > 0)?;
+        Some((lowest as u8, highest as u8))
+    }
+
+    pub fn note_count(&self) -> u32 {
+        self.note_histogram.iter().sum()
+    }
+}
+
+/// Refuses timecode timing at -128 frames per second, which midly negates
+/// with an overflow, panicking in debug builds. Call before handing
+/// untrusted bytes to midly.
+pub fn check_timecode(contents: &[u8]) -> Result<()> {
+    if header_division(contents).is_some_and(|division| division == 0x80) {
+        return Err(PlaybackError::UnsupportedFormat("timecode of -128 frames per second").into());
+    }
+    Ok(())
+}
+
+/// The first byte of the division in the header midly would read: the first
+/// `MThd` chunk, inside the `data` chunk of an RMID file when wrapped.
+fn header_division(contents: &[u8]) -> Option<u8> {
+    let smf = match contents.get(..4)? {
+        b"RIFF" => rmid_data(contents)?,
+        b"MThd" => contents,
+        _ => return None,
+    };
+    let (_, header) = chunks(smf, false).find(|(id, _)| id == b"MThd")?;
+    header.get(4).copied()
+}
+
+/// The SMF inside an RMID file, found as midly finds it.
+fn rmid_data(contents: &[u8]) -> Option<&[u8]> {
+    let (id, riff) = chunks(contents, true).next()?;
+    if id != *b"RIFF" || riff.get(..4)? != b"RMID" {
+        return None;
+    }
+    chunks(&riff[4..], true)
+        .find(|(id, _)| id == b"data")
+        .map(|(_, data)| data)
+}
+
+/// Chunk ids and bodies the way midly walks them, a chunk running past the
+/// end cut short there. RIFF chunks have little-endian lengths and are
+/// padded to an even length; SMF chunks have big-endian lengths.
+fn chunks(mut data: &[u8], riff: bool) -> impl Iterator<Item = ([u8; 4], &[u8])> {
+    std::iter::from_fn(move || {
+        let id: [u8; 4] = data.get(..4)?.try_into().ok()?;
+        let len: [u8; 4] = data.get(4..8)?.try_into().ok()?;
+        let len = if riff {
+            u32::from_le_bytes(len)
+        } else {
+            u32::from_be_bytes(len)
+        } as usize;
+        let rest = &data[8..];
+        let (body, next) = rest.split_at(len.min(rest.len()));
+        let pad = usize::from(riff && len % 2 == 1);
+        data = next.get(pad..).unwrap_or_default();
+        Some((id, body))
+    })
+}
+
+/// Reads a MIDI file for the song information panel. Unlike
+/// [`MidiSequence::from_file`] this accepts every SMF format and timing mode.
+pub fn inspect_file(path: &Path) -> Result<SequenceInfo> {
+    let contents = read_midi_file(path)?;
+    check_timecode(&contents)?;
+    let smf = Smf::parse(&contents)
+        .with_context(|| PlaybackError::Parse(format!("file {}", path.display())))?;
+
+    let (ppq, tempo_map) = match smf.header.timing {
+        Timing::Metrical(t) => (
+            Some(t.as_int()),
+            TempoMap::from_smf(&smf, t.as_int() as u32)?,
+        ),
+        Timing::Timecode(..) => (None, TempoMap::new(Vec::new(), 1)),
+    };
+    let ticks_per_second = match smf.header.timing {
+        Timing::Timecode(fps, subframe) => Some(fps.as_f32() as f64 * subframe as f64),
+        Timing::Metrical(_) => None,
+    };
+    let to_time = |tick: u64| match ticks_per_second {
+        Some(rate) if rate > 0.0 => Duration::from_secs_f64(tick as f64 / rate),
+        Some(_) => Duration::ZERO,
+        None => tempo_map.ticks_to_duration(tick),
+    };
+
+    let mut tempo_changes = Vec::new();
+    let mut time_signatures = Vec::new();
+    let mut tracks = Vec::with_capacity(smf.tracks.len());
+    let mut channels = BTreeSet::new();
+    let mut programs = BTreeSet::new();
+    let mut note_histogram = vec![0u32; 128];
+    let mut signature_ticks = Vec::new();
+    let mut last_tick = 0;
+
+    for track in &smf.tracks {
+        let mut tick: u64 = 0;
+        let mut summary = TrackSummary {
+            name: None,
+            channels: Vec::new(),
+            note_count: 0,
+        };
+        let mut track_channels = BTreeSet::new();
+        for event in track {
+            tick = tick.saturating_add(event.delta.as_int().into());
+            match event.kind {
+                TrackEventKind::Meta(MetaMessage::TrackName(name)) if summary.name.is_none() => {
+                    summary.name = track_name(name);
+                }
+                TrackEventKind::Meta(MetaMessage::Tempo(tempo)) => {
+                    tempo_changes.push(TempoChange {
+                        at: to_time(tick),
+                        bpm: bpm(tempo.as_int()),
+                    });
+                }
+                TrackEventKind::Meta(MetaMessage::TimeSignature(numerator, pow, ..)) => {
+                    signature_ticks.push((tick, numerator, pow));
+                    time_signatures.push(TimeSignatureChange {
+                        at: to_time(tick),
+                        numerator,
+                        denominator: 1u16 << pow.min(15),
+                    });
+                }
+                TrackEventKind::Midi { channel, message } => {
+                    last_tick = last_tick.max(tick);
+                    let channel = channel.as_int();
+                    track_channels.insert(channel);
+                    match message {
+                        MidiMessage::NoteOn { key, vel } if vel.as_int() > 0 => {
+                            note_histogram[key.as_int() as usize] += 1;
+                            summary.note_count += 1;
+                        }
+                        MidiMessage::ProgramChange { program } => {
+                            programs.insert((channel, program.as_int()));
+                        }
+                        _ => {}
+                    }
+                }
+                _ => {}
+            }
+        }
+        channels.extend(&track_channels);
+        summary.channels = track_channels.into_iter().collect();
+        tracks.push(summary);
+    }
+
+    tempo_changes.sort_by_key(|change| change.at);
+    time_signatures.sort_by_key(|change| change.at);
+    let measures = match ppq {
+        Some(_) => beat_markers(&signature_ticks, &tempo_map, last_tick)
+            .last()
+            .map_or(0, |beat| beat.measure),
+        None => 0,
+    };
+
+    Ok(SequenceInfo {
+        file_size: contents.len() as u64,
+        format: match smf.header.format {
+            midly::Format::SingleTrack => 0,
+            midly::Format::Parallel => 1,
+            midly::Format::Sequential => 2,
+        },
+        ppq,
+        tempo_changes,
+        tempo: ppq.map(|_| tempo_map.summary(last_tick)),
+        time_signatures,
+        measures,
+        channel_names: name_channels(
+            tracks
+                .iter()
+                .map(|track| (track.name.as_deref(), track.channels.as_slice())),
+        ),
+        tracks,
+        channels: channels.into_iter().collect(),
+        programs: programs.into_iter().collect(),
+        note_histogram,
+    })
+}
+
+fn is_note_message(data: &[u8]) -> bool {
+    matches!(data.first(), Some(status) if matches!(status & 0xF0, 0x80 | 0x90 | 0xA0))
+}
+
+fn track_name(raw: &[u8]) -> Option<String> {
+    let name = String::from_utf8_lossy(raw).trim().to_owned();
+    (!name.is_empty()).then_some(name)
+}
+
+/// Labels each channel with the name of a track that plays only on that
+/// channel, the first such track winning. Tracks spanning several channels
+/// (like the single track of a format 0 file) usually carry the song title
+/// and label nothing.
+fn name_channels<'a>(
+    tracks: impl IntoIterator<Item = (Option<&'a str>, &'a [u8])>,
+) -> [Option<String>; 16] {
+    let mut names: [Option<String>; 16] = Default::default();
+    for (name, channels) in tracks {
+        if let (Some(name), [channel]) = (name, channels) {
+            names[usize::from(*channel & 0x0F)].get_or_insert_with(|| name.to_owned());
+        }
+    }
+    names
+}
+
+/// Scientific pitch name for a MIDI key, with middle C (60) as C4.
+pub fn note_name(key: u8) -> String {
+    const NAMES: [&str; 12] = [
+        "C", "C#", "D", "D#", "E", "F", "F#", "G", "G#", "A", "A#", "B",
+    ];
+    let octave = key as i32 / 12 - 1;
+    format!("{}{octave}", NAMES[key as usize % 12])
+}
+
+/// Practice adjustments applied to a loaded sequence before playback.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct PlaybackAdjustments {
+    /// Playback speed as a percentage of the written tempo.
+    pub tempo_percent: u16,
+    /// Semitones added to every note outside the percussion channel.
+    pub transpose: i8,
+    /// Bit `n` set silences the notes on channel `n`.
+    pub muted_channels: u16,
+    pub hand_split: Option<HandSplit>,
+    /// General MIDI program forced on every channel except percussion.
+    pub program_override: Option<u8>,
+    /// Moves every channel except percussion onto this one, for
+    /// instruments that only listen on one channel.
+    pub merge_channel: Option<u8>,
+    pub percussion: PercussionHandling,
+}
+
+impl Default for PlaybackAdjustments {
+    fn default() -> Self {
+        Self {
+            tempo_percent: 100,
+            transpose: 0,
+            muted_channels: 0,
+            hand_split: None,
+            program_override: None,
+            merge_channel: None,
+            percussion: PercussionHandling::Keep,
+        }
+    }
+}
+
+/// What playback does with the percussion channel (channel 10), whose drum
+/// hits an instrument without a drum kit plays as unrelated pitches.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub enum PercussionHandling {
+    #[default]
+    Keep,
+    Drop,
+    /// Plays the drum hits as piano notes on the given channel: bass drums
+    /// low, snares and toms in the middle, hi-hats and cymbals high, so the
+    /// rhythm survives as a simple accompaniment.
+    Rhythm(u8),
+}
+
+impl PercussionHandling {
+    /// Piano-only instruments usually listen on channel 1.
+    pub const DEFAULT_RHYTHM_CHANNEL: u8 = 0;
+
+    /// The piano key a General MIDI drum note is played on.
+    pub fn rhythm_key(drum: u8) -> u8 {
+        match drum {
+            // Bass drums: C2.
+            35 | 36 => 36,
+            // Side stick, snares and claps: G2.
+            37..=40 => 43,
+            // Toms, low to high: C3, E3, G3.
+            41 | 43 => 48,
+            45 | 47 => 52,
+            48 | 50 => 55,
+            // Hi-hats: E4.
+            42 | 44 | 46 => 64,
+            // Crashes, rides, china and splash cymbals: G4.
+            49 | 51 | 52 | 53 | 55 | 57 | 59 => 67,
+            // Hand percussion and everything else: C4.
+            _ => 60,
+        }
+    }
+}
+
+impl fmt::Display for PercussionHandling {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            PercussionHandling::Keep => f.write_str("Keep drums"),
+            PercussionHandling::Drop => f.write_str("Drop drums"),
+            PercussionHandling::Rhythm(channel) => {
+                write!(f, "Drums as rhythm on channel {}", channel + 1)
+            }
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub enum HandClassifier {
+    /// Keys below the split point belong to the left hand.
+    SplitPoint(u8),
+    /// Notes from this SMF track belong to the left hand, all others to the right.
+    LeftTrack(u16),
+}
+
+/// Routes left- and right-hand notes to separate channels so instruments
+/// with dual-tone or hand-guidance modes can tell them apart.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct HandSplit {
+    pub classifier: HandClassifier,
+    pub left_channel: u8,
+    pub right_channel: u8,
+}
+
+impl Default for HandSplit {
+    fn default() -> Self {
+        Self {
+            classifier: HandClassifier::SplitPoint(60),
+            left_channel: 1,
+            right_channel: 0,
+        }
+    }
+}
+
+impl HandSplit {
+    fn channel_for(&self, track: u16, key: u8) -> u8 {
+        let left = match self.classifier {
+            HandClassifier::SplitPoint(split) => key < split,
+            HandClassifier::LeftTrack(left_track) => track == left_track,
+        };
+        if left {
+            self.left_channel
+        } else {
+            self.right_channel
+        }
+    }
+}
+
+impl PlaybackAdjustments {
+    pub const MIN_TEMPO_PERCENT: u16 = 25;
+    pub const MAX_TEMPO_PERCENT: u16 = 400;
+
+    pub fn is_identity(&self) -> bool {
+        *self == PlaybackAdjustments::default()
+    }
+
+    pub fn is_muted(&self, channel: u8) -> bool {
+        self.muted_channels & (1 << channel) != 0
+    }
+}
+
+/// Where a sequence is loaded from: a library file, or bytes already in
+/// memory such as a fresh download or a recording.
+#[derive(Debug, Clone)]
+pub enum MidiSource {
+    File(PathBuf),
+    Memory(Arc<[u8]>),
 }
 
 #[derive(Clone, Debug)]
 pub struct MidiSequence {
     pub events: Vec<PlaybackEvent>,
     pub duration: Duration,
+    pub beats: Vec<BeatMarker>,
+    /// Tempos in playback time, starting with the one at zero.
+    pub tempo_changes: Vec<TempoChange>,
+    /// Track names for the channels they play on, from the file's
+    /// `TrackName` meta events.
+    pub channel_names: [Option<String>; 16],
+    /// Malformed messages left out or fixed while loading.
+    pub validation: ValidationReport,
 }
 
 impl MidiSequence {
     pub fn from_file(path: &Path) -> Result<Self> {
-        let contents = fs::read(path)
-            .with_context(|| format!("failed to read MIDI file {}", path.display()))?;
+        let contents = read_midi_file(path)?;
+        check_timecode(&contents)?;
         let smf = Smf::parse(&contents)
-            .with_context(|| format!("failed to parse MIDI file {}", path.display()))?;
+            .with_context(|| PlaybackError::Parse(format!("file {}", path.display())))?;
         MidiSequence::from_smf(&smf)
     }
 
-    fn from_smf(smf: &Smf<'_>) -> Result<Self> {
-        let ppq = match smf.header.timing {
-            Timing::Metrical(t) => t.as_int() as u32,
-            Timing::Timecode(_fps, _subframe) => {
-                bail!("timecode-based MIDI timing is not supported");
+    /// Parses an in-memory Standard MIDI File.
+    pub fn from_bytes(contents: &[u8]) -> Result<Self> {
+        check_timecode(contents)?;
+        let smf = Smf::parse(contents).context(PlaybackError::Parse("data".into()))?;
+        MidiSequence::from_smf(&smf)
+    }
+
+    pub fn from_source(source: &MidiSource) -> Result<Self> {
+        match source {
+            MidiSource::File(path) => MidiSequence::from_file(path),
+            MidiSource::Memory(contents) => MidiSequence::from_bytes(contents),
+        }
+    }
+
+    /// Returns a copy with tempo scaled, notes transposed and muted channels'
+    /// notes removed. Controller and program messages are always kept so
+    /// unmuting mid-queue does not leave a channel on the wrong sound. With a
+    /// hand split, notes are re-channelled and channel messages are copied to
+    /// both hand channels. A program override rewrites the file's program
+    /// changes and selects the program up front on every channel with notes.
+    /// Percussion is kept, dropped or played as piano notes as
+    /// [`PercussionHandling`] says.
+    pub fn adjusted(&self, adjustments: PlaybackAdjustments) -> MidiSequence {
+        if adjustments.is_identity() {
+            return self.clone();
+        }
+        let tempo = adjustments.tempo_percent.clamp(
+            PlaybackAdjustments::MIN_TEMPO_PERCENT,
+            PlaybackAdjustments::MAX_TEMPO_PERCENT,
+        );
+        let scale = 100.0 / tempo as f64;
+
+        let mut events: Vec<PlaybackEvent> = self
+            .events
+            .iter()
+            .flat_map(|event| {
+                let at = event.at.mul_f64(scale);
+                adjust_message(event, adjustments)
+                    .into_iter()
+                    .map(move |data| PlaybackEvent {
+                        at,
+                        data,
+                        track: event.track,
+                    })
+            })
+            .collect();
+        if let Some(program) = adjustments.program_override {
+            let channels: BTreeSet<u8> = events
+                .iter()
+                .filter(|event| {
+                    event
+                        .data
+                        .first()
+                        .is_some_and(|status| status & 0xF0 == 0x90)
+                })
+                .map(|event| event.data[0] & 0x0F)
+                .filter(|channel| *channel != 9)
+                .collect();
+            let selections = channels.into_iter().map(|channel| PlaybackEvent {
+                at: Duration::ZERO,
+                data: vec![0xC0 | channel, program & 0x7F],
+                track: 0,
+            });
+            events.splice(0..0, selections);
+        }
+        let beats = self
+            .beats
+            .iter()
+            .map(|beat| BeatMarker {
+                at: beat.at.mul_f64(scale),
+                ..*beat
+            })
+            .collect();
+        let tempo_changes = self
+            .tempo_changes
+            .iter()
+            .map(|change| TempoChange {
+                at: change.at.mul_f64(scale),
+                bpm: change.bpm / scale,
+            })
+            .collect();
+
+        MidiSequence {
+            events,
+            duration: self.duration.mul_f64(scale),
+            beats,
+            tempo_changes,
+            channel_names: self.channel_names.clone(),
+            validation: self.validation.clone(),
+        }
+    }
+
+    /// Encodes the sequence as a format 1 Standard MIDI File, usually after
+    /// [`MidiSequence::adjusted`] to save a practice arrangement. Times are
+    /// written at a fixed tempo, so tempo changes survive as timing rather
+    /// than as tempo events. Each channel gets its own track, named from
+    /// [`MidiSequence::channel_names`]; system exclusive messages go in the
+    /// first track.
+    pub fn to_smf_bytes(&self) -> Result<Vec<u8>> {
+        let ticks_per_second = f64::from(EXPORT_PPQ) * 1_000_000.0 / f64::from(EXPORT_TEMPO);
+        let to_tick = |at: Duration| (at.as_secs_f64() * ticks_per_second).round() as u32;
+
+        let mut conductor = vec![(
+            0,
+            TrackEventKind::Meta(MetaMessage::Tempo(u24::new(EXPORT_TEMPO))),
+        )];
+        let mut channels: BTreeMap<u8, Vec<(u32, TrackEventKind<'_>)>> = BTreeMap::new();
+        for event in &self.events {
+            let tick = to_tick(event.at);
+            match event.data.split_first() {
+                Some((0xF0, data)) => conductor.push((tick, TrackEventKind::SysEx(data))),
+                Some((0xF7, data)) => conductor.push((tick, TrackEventKind::Escape(data))),
+                // Realtime and system common messages have no place in a
+                // file.
+                _ => {
+                    let Ok(LiveEvent::Midi { channel, message }) = LiveEvent::parse(&event.data)
+                    else {
+                        continue;
+                    };
+                    let name = self.channel_names[usize::from(channel.as_int())].as_deref();
+                    channels
+                        .entry(channel.as_int())
+                        .or_insert_with(|| {
+                            name.map(|name| {
+                                (
+                                    0,
+                                    TrackEventKind::Meta(MetaMessage::TrackName(name.as_bytes())),
+                                )
+                            })
+                            .into_iter()
+                            .collect()
+                        })
+                        .push((tick, TrackEventKind::Midi { channel, message }));
+                }
             }
+        }
+
+        let tracks = std::iter::once(conductor)
+            .chain(channels.into_values())
+            .map(|timed| {
+                let mut last = 0;
+                let mut track: Vec<TrackEvent<'_>> = timed
+                    .into_iter()
+                    .map(|(tick, kind)| {
+                        let delta = u28::new(tick.saturating_sub(last));
+                        last = last.max(tick);
+                        TrackEvent { delta, kind }
+                    })
+                    .collect();
+                track.push(TrackEvent {
+                    delta: u28::new(0),
+                    kind: TrackEventKind::Meta(MetaMessage::EndOfTrack),
+                });
+                track
+            })
+            .collect();
+        let smf = Smf {
+            header: Header::new(Format::Parallel, Timing::Metrical(u15::new(EXPORT_PPQ))),
+            tracks,
         };
+        let mut bytes = Vec::new();
+        smf.write_std(&mut bytes)
+            .context("failed to encode MIDI file")?;
+        Ok(bytes)
+    }
+
+    /// The part of the sequence from `start` up to `end`, moved to begin at
+    /// zero. Controller, program and other non-note messages before `start`
+    /// are kept at the beginning so the excerpt sounds the same; notes
+    /// already sounding at `start` are left out and notes still sounding at
+    /// `end` are released there. Both ends are limited to the sequence, so a
+    /// `start` past the end gives an excerpt without notes.
+    pub fn excerpt(&self, start: Duration, end: Duration) -> MidiSequence {
+        let start = start.min(self.duration);
+        let end = end.clamp(start, self.duration);
+        let mut events: Vec<PlaybackEvent> = self
+            .events
+            .iter()
+            .take_while(|event| event.at < start)
+            .filter(|event| !is_note_message(&event.data))
+            .map(|event| PlaybackEvent {
+                at: Duration::ZERO,
+                ..event.clone()
+            })
+            .collect();
+
+        let mut sounding = BTreeMap::new();
+        for event in self
+            .events
+            .iter()
+            .skip_while(|event| event.at < start)
+            .take_while(|event| event.at < end)
+        {
+            if let [status, key, velocity, ..] = event.data[..]
+                && matches!(status & 0xF0, 0x80 | 0x90)
+            {
+                let note = (status & 0x0F, key);
+                if status & 0xF0 == 0x90 && velocity > 0 {
+                    sounding.insert(note, event.track);
+                } else if sounding.remove(&note).is_none() {
+                    // Released a note that started before the excerpt.
+                    continue;
+                }
+            }
+            events.push(PlaybackEvent {
+                at: event.at - start,
+                ..event.clone()
+            });
+        }
+        let length = end - start;
+        events.extend(
+            sounding
+                .into_iter()
+                .map(|((channel, key), track)| PlaybackEvent {
+                    at: length,
+                    data: vec![0x80 | channel, key, 0],
+                    track,
+                }),
+        );
+
+        let beats = self
+            .beats
+            .iter()
+            .filter(|beat| (start..end).contains(&beat.at))
+            .map(|beat| BeatMarker {
+                at: beat.at - start,
+                ..*beat
+            })
+            .collect();
+        let from = self
+            .tempo_changes
+            .partition_point(|change| change.at <= start);
+        let tempo_changes = self.tempo_changes[from.saturating_sub(1)..]
+            .iter()
+            .take_while(|change| change.at < end)
+            .map(|change| TempoChange {
+                at: change.at.saturating_sub(start),
+                bpm: change.bpm,
+            })
+            .collect();
+
+        MidiSequence {
+            events,
+            duration: length,
+            beats,
+            tempo_changes,
+            channel_names: self.channel_names.clone(),
+            validation: self.validation.clone(),
+        }
+    }
+
+    /// When bar `measure`, counting from one, begins; `None` past the end.
+    pub fn measure_start(&self, measure: u32) -> Option<Duration> {
+        self.beats
+            .iter()
+            .find(|beat| beat.measure == measure)
+            .map(|beat| beat.at)
+    }
+
+    /// The tempo in effect at `at`; `None` only for a sequence built
+    /// without tempos.
+    pub fn bpm_at(&self, at: Duration) -> Option<f64> {
+        let index = self.tempo_changes.partition_point(|change| change.at <= at);
+        self.tempo_changes
+            .get(index.checked_sub(1)?)
+            .map(|change| change.bpm)
+    }
+
+    /// The beat sounding at `at`, or `None` before the first beat.
+    pub fn beat_at(&self, at: Duration) -> Option<BeatMarker> {
+        let index = self.beats.partition_point(|beat| beat.at <= at);
+        index.checked_sub(1).map(|index| self.beats[index])
+    }
+
+    fn from_smf(smf: &Smf<'_>) -> Result<Self> {
+        let ppq = metrical_ppq(smf.header.timing)?;
 
         if smf.header.format == midly::Format::Parallel && smf.tracks.len() < 2 {
             log::warn!("SMF declares format 1 but contains less than 2 tracks");
         }
 
         if smf.header.format == midly::Format::Sequential {
-            bail!("SMF format 2 files are not supported");
+            return Err(PlaybackError::UnsupportedFormat("SMF format 2").into());
         }
 
         let tempo_map = TempoMap::from_smf(smf, ppq)?;
 
         let mut raw_events: Vec<RawEvent> = Vec::new();
-        for track in &smf.tracks {
+        let mut time_signatures: Vec<(u64, u8, u8)> = Vec::new();
+        let mut track_channels: Vec<(Option<String>, Vec<u8>)> = Vec::new();
+        for (track_index, track) in smf.tracks.iter().enumerate() {
+            let track_index = track_index as u16;
             let mut tick_accumulator: u64 = 0;
+            let mut name = None;
+            let mut channels = BTreeSet::new();
             for event in track {
-                tick_accumulator += event.delta.as_int() as u64;
+                tick_accumulator = tick_accumulator.saturating_add(event.delta.as_int().into());
                 match &event.kind {
                     TrackEventKind::Meta(MetaMessage::Tempo(_)) => {
                         // handled in tempo map pass
                     }
+                    TrackEventKind::Meta(MetaMessage::TrackName(raw)) if name.is_none() => {
+                        name = track_name(raw);
+                    }
+                    TrackEventKind::Meta(MetaMessage::TimeSignature(
+                        numerator,
+                        denominator,
+                        ..,
+                    )) => {
+                        time_signatures.push((tick_accumulator, *numerator, *denominator));
+                    }
                     TrackEventKind::Midi { channel, message } => {
+                        channels.insert(channel.as_int());
                         if let Some(data) = encode_midi_message(*channel, message) {
                             raw_events.push(RawEvent {
                                 tick: tick_accumulator,
                                 data,
+                                track: track_index,
                             });
                         }
                     }
@@ -74,6 +956,7 @@ impl MidiSequence {
                         raw_events.push(RawEvent {
                             tick: tick_accumulator,
                             data: payload,
+                            track: track_index,
                         });
                     }
                     TrackEventKind::Escape(data) => {
@@ -83,12 +966,19 @@ impl MidiSequence {
                         raw_events.push(RawEvent {
                             tick: tick_accumulator,
                             data: payload,
+                            track: track_index,
                         });
                     }
                     _ => {}
                 }
             }
+            track_channels.push((name, channels.into_iter().collect()));
         }
+        let channel_names = name_channels(
+            track_channels
+                .iter()
+                .map(|(name, channels)| (name.as_deref(), channels.as_slice())),
+        );
 
         raw_events.sort_by(|a, b| {
             let ord = a.tick.cmp(&b.tick);
@@ -99,19 +989,53 @@ impl MidiSequence {
             }
         });
 
-        let mut events = Vec::with_capacity(raw_events.len());
-        let mut total_duration = Duration::ZERO;
-        for raw in raw_events {
-            let at = tempo_map.ticks_to_duration(raw.tick);
-            events.push(PlaybackEvent { at, data: raw.data });
-            if at > total_duration {
-                total_duration = at;
+        let mut validation = ValidationReport::default();
+        raw_events.retain_mut(|raw| match validate::normalize(&raw.data) {
+            Ok(Cow::Borrowed(_)) => true,
+            Ok(Cow::Owned(fixed)) => {
+                raw.data = fixed;
+                validation.fixed(raw.track);
+                true
+            }
+            Err(problem) => {
+                log::debug!("dropped message in track {}: {problem}", raw.track);
+                validation.dropped(raw.track);
+                false
             }
+        });
+
+        let last_tick = raw_events.last().map(|raw| raw.tick).unwrap_or(0);
+        let total_duration = tempo_map.ticks_to_duration(last_tick);
+        if total_duration > MAX_DURATION {
+            return Err(PlaybackError::UnsupportedFormat("songs longer than a day").into());
         }
+        let beats = beat_markers(&time_signatures, &tempo_map, last_tick);
+
+        let events = raw_events
+            .into_iter()
+            .map(|raw| PlaybackEvent {
+                at: tempo_map.ticks_to_duration(raw.tick),
+                data: raw.data,
+                track: raw.track,
+            })
+            .collect();
+
+        let tempo_changes = tempo_map
+            .entries
+            .iter()
+            .map(|entry| TempoChange {
+                at: tempo_map.ticks_to_duration(entry.tick),
+                bpm: bpm(entry.micros_per_quarter),
+            })
+            .collect();
 
         Ok(MidiSequence {
             events,
             duration: total_duration,
+            beats,
+            tempo_changes,
+            channel_names,
+            validation,
         })
     }
 }
@@ -120,6 +1044,7 @@ impl MidiSequence {
 struct RawEvent {
     tick: u64,
     data: Vec<u8>,
+    track: u16,
 }
 
 #[derive(Debug, Clone)]
@@ -131,23 +1056,22 @@ struct TempoEntry {
 #[derive(Debug, Clone)]
 struct TempoMap {
     entries: Vec<TempoEntry>,
+    /// Microseconds from the start to each entry, so converting a tick
+    /// doesn't walk every change before it.
+    starts: Vec<u128>,
     ppq: u32,
 }
 
 impl TempoMap {
     fn from_smf(smf: &Smf<'_>, ppq: u32) -> Result<Self> {
-        let mut entries = vec![TempoEntry {
-            tick: 0,
-            micros_per_quarter: 500_000,
-        }];
-
+        let mut changes = Vec::new();
         for track in &smf.tracks {
             let mut tick_accumulator: u64 = 0;
             for event in track {
-                tick_accumulator += event.delta.as_int() as u64;
+                tick_accumulator = tick_accumulator.saturating_add(event.delta.as_int().into());
                 if let TrackEventKind::Meta(MetaMessage::Tempo(tempo)) = event.kind {
                     let value = tempo.as_int();
-                    entries.push(TempoEntry {
+                    changes.push(TempoEntry {
                         tick: tick_accumulator,
                         micros_per_quarter: value,
                     });
@@ -155,39 +1079,138 @@ impl TempoMap {
             }
         }
 
-        entries.sort_by(|a, b| a.tick.cmp(&b.tick));
-        entries.dedup_by(|a, b| {
-            if a.tick == b.tick {
-                a.micros_per_quarter = b.micros_per_quarter;
+        Ok(TempoMap::new(changes, ppq))
+    }
+
+    fn new(changes: Vec<TempoEntry>, ppq: u32) -> Self {
+        let mut entries = vec![TempoEntry {
+            tick: 0,
+            micros_per_quarter: 500_000,
+        }];
+        entries.extend(changes);
+        entries.sort_by_key(|a| a.tick);
+        // The later of two changes at the same tick wins, so a file's own
+        // opening tempo replaces the default.
+        entries.dedup_by(|later, earlier| {
+            if later.tick == earlier.tick {
+                earlier.micros_per_quarter = later.micros_per_quarter;
                 true
             } else {
                 false
             }
         });
+        let ppq = ppq.max(1);
+        let mut starts = Vec::with_capacity(entries.len());
+        let mut start = 0;
+        for (index, entry) in entries.iter().enumerate() {
+            if let Some(previous) = index.checked_sub(1).map(|index| &entries[index]) {
+                start +=
+                    segment_duration(previous.micros_per_quarter, entry.tick - previous.tick, ppq);
+            }
+            starts.push(start);
+        }
 
-        Ok(TempoMap { entries, ppq })
+        TempoMap {
+            entries,
+            starts,
+            ppq,
+        }
     }
 
-    fn ticks_to_duration(&self, tick: u64) -> Duration {
-        let mut total_micros: u128 = 0;
-        let mut last_tick: u64 = 0;
-        let mut last_tempo = self
+    /// The opening tempo and, up to `last_tick`, the average one.
+    fn summary(&self, last_tick: u64) -> TempoSummary {
+        let initial_bpm = self
             .entries
             .first()
-            .map(|entry| entry.micros_per_quarter)
-            .unwrap_or(500_000);
+            .map_or(120.0, |entry| bpm(entry.micros_per_quarter));
+        let minutes = self.ticks_to_duration(last_tick).as_secs_f64() / 60.0;
+        let average_bpm = if minutes > 0.0 {
+            last_tick as f64 / f64::from(self.ppq.max(1)) / minutes
+        } else {
+            initial_bpm
+        };
+        TempoSummary {
+            initial_bpm,
+            average_bpm,
+        }
+    }
+
+    /// Saturates rather than wrapping for ticks too far out to fit.
+    fn ticks_to_duration(&self, tick: u64) -> Duration {
+        let index = self
+            .entries
+            .partition_point(|entry| entry.tick <= tick)
+            .saturating_sub(1);
+        let entry = &self.entries[index];
+        let micros = self.starts[index]
+            + segment_duration(entry.micros_per_quarter, tick - entry.tick, self.ppq);
+        Duration::from_micros(u64::try_from(micros).unwrap_or(u64::MAX))
+    }
+}
 
-        for entry in self.entries.iter().skip(1) {
-            if entry.tick > tick {
+fn bpm(micros_per_quarter: u32) -> f64 {
+    60_000_000.0 / f64::from(micros_per_quarter.max(1))
+}
+
+/// Lays out measures from tick 0 to `last_tick`. Signature changes take effect
+/// at the next beat boundary and start a new measure there.
+fn beat_markers(
+    time_signatures: &[(u64, u8, u8)],
+    tempo_map: &TempoMap,
+    last_tick: u64,
+) -> Vec<BeatMarker> {
+    let mut signatures = time_signatures.to_vec();
+    signatures.sort_by_key(|(tick, ..)| *tick);
+
+    let mut markers = Vec::new();
+    let mut numerator = 4u8;
+    let mut denominator_pow = 2u8;
+    let mut next_signature = 0;
+    let mut tick = 0u64;
+    let mut measure = 0u32;
+    let mut beat = 0u8;
+
+    while tick <= last_tick && markers.len() < MAX_BEATS {
+        let mut changed = false;
+        while let Some(&(at, num, pow)) = signatures.get(next_signature) {
+            if at > tick {
                 break;
             }
-            total_micros += segment_duration(last_tempo, entry.tick - last_tick, self.ppq);
-            last_tick = entry.tick;
-            last_tempo = entry.micros_per_quarter;
+            numerator = num.max(1);
+            denominator_pow = pow.min(6);
+            next_signature += 1;
+            changed = true;
+        }
+        if changed || beat == 0 || beat >= numerator {
+            measure += 1;
+            beat = 0;
         }
+        beat += 1;
+        markers.push(BeatMarker {
+            at: tempo_map.ticks_to_duration(tick),
+            measure,
+            beat,
+            beats_per_measure: numerator,
+            beat_unit: 1 << denominator_pow,
+        });
+        let beat_ticks = ((tempo_map.ppq as u64 * 4) >> denominator_pow).max(1);
+        tick += beat_ticks;
+    }
+
+    markers
+}
 
-        total_micros += segment_duration(last_tempo, tick.saturating_sub(last_tick), self.ppq);
-        Duration::from_micros(total_micros as u64)
+/// The ticks per quarter note of a file with metrical timing; playback
+/// can't place events in files with timecode timing or a zero resolution.
+fn metrical_ppq(timing: Timing) -> Result<u32> {
+    match timing {
+        Timing::Metrical(ppq) if ppq.as_int() > 0 => Ok(ppq.as_int().into()),
+        Timing::Metrical(_) => {
+            Err(PlaybackError::UnsupportedFormat("zero ticks per quarter note").into())
+        }
+        Timing::Timecode(..) => {
+            Err(PlaybackError::UnsupportedFormat("timecode-based timing").into())
+        }
     }
 }
 
@@ -199,6 +1222,93 @@ fn segment_duration(micros_per_quarter: u32, delta_ticks: u64, ppq: u32) -> u128
     numerator / ppq as u128
 }
 
+fn adjust_message(event: &PlaybackEvent, adjustments: PlaybackAdjustments) -> Vec<Vec<u8>> {
+    let data = &event.data;
+    let Some(&status) = data.first() else {
+        return Vec::new();
+    };
+    if status >= 0xF0 {
+        return vec![data.clone()];
+    }
+    let channel = status & 0x0F;
+    let split = adjustments.hand_split.filter(|_| channel != 9);
+    let is_note = matches!(status & 0xF0, 0x80 | 0x90 | 0xA0);
+    if channel == PERCUSSION_CHANNEL && adjustments.percussion != PercussionHandling::Keep {
+        // The kit's programs and controllers mean nothing on a piano
+        // channel, so only notes are carried over.
+        return match adjustments.percussion {
+            PercussionHandling::Rhythm(target)
+                if is_note && !adjustments.is_muted(channel) && data.len() > 2 =>
+            {
+                let mut data = with_channel(data, target);
+                data[1] = PercussionHandling::rhythm_key(data[1]);
+                vec![data]
+            }
+            _ => Vec::new(),
+        };
+    }
+    let mut data = data.clone();
+    if let Some(target) = adjustments.merge_channel
+        && channel != 9
+        && channel != target
+    {
+        // Another channel's program would replace the sound of the one
+        // everything now plays on.
+        if status & 0xF0 == 0xC0 {
+            return Vec::new();
+        }
+        data = with_channel(&data, target);
+    }
+    if let Some(program) = adjustments.program_override
+        && channel != 9
+    {
+        match (status & 0xF0, data.get(1).copied()) {
+            (0xC0, Some(_)) => data[1] = program & 0x7F,
+            // Bank changes would pick a variation of the forced program.
+            (0xB0, Some(0 | 32)) if data.len() > 2 => data[2] = 0,
+            _ => {}
+        }
+    }
+    if !is_note {
+        return match split {
+            Some(split) if split.left_channel != split.right_channel => {
+                [split.left_channel, split.right_channel]
+                    .into_iter()
+                    .map(|target| with_channel(&data, target))
+                    .collect()
+            }
+            Some(split) => vec![with_channel(&data, split.right_channel)],
+            None => vec![data],
+        };
+    }
+    if adjustments.is_muted(channel) {
+        return Vec::new();
+    }
+    let Some(&written_key) = data.get(1) else {
+        return Vec::new();
+    };
+    if let Some(split) = split {
+        // Hands are decided on the written pitch so transposing never moves
+        // a note between hands.
+        data = with_channel(&data, split.channel_for(event.track, written_key));
+    }
+    if adjustments.transpose != 0 && channel != 9 {
+        let key = written_key as i16 + adjustments.transpose as i16;
+        // Notes pushed off the keyboard are dropped along with their note-offs.
+        match u8::try_from(key).ok().filter(|key| *key <= 127) {
+            Some(key) => data[1] = key,
+            None => return Vec::new(),
+        }
+    }
+    vec![data]
+}
+
+fn with_channel(data: &[u8], channel: u8) -> Vec<u8> {
+    let mut data = data.to_vec();
+    data[0] = (data[0] & 0xF0) | (channel & 0x0F);
+    data
+}
+
 fn encode_midi_message(channel: u4, message: &MidiMessage) -> Option<Vec<u8>> {
     let channel_value = channel.as_int();
 
@@ -1,4 +1,6 @@
-use std::sync::Arc;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
 
 use anyhow::Result;
 use async_trait::async_trait;
@@ -7,6 +9,11 @@ use async_trait::async_trait;
 pub enum MidiTransport {
     Usb,
     Bluetooth,
+    /// Built into the app rather than backed by hardware.
+    Virtual,
+    /// A port the app creates for other software, such as a DAW or soft
+    /// synth, to connect to.
+    VirtualPort,
 }
 
 #[derive(Debug, Clone)]
@@ -36,6 +43,96 @@ pub trait MidiSink: Send + Sync {
         }
         Ok(())
     }
+
+    /// Silences every channel, for use when notes are left hanging.
+    async fn panic(&self) -> Result<()> {
+        self.send_batch(&panic_messages()).await
+    }
+
+    /// Whether an open connection can still be used. Checked before a
+    /// connection is reused for another song.
+    async fn is_alive(&self) -> bool {
+        true
+    }
+
+    /// Closes the connection ahead of dropping it, for devices that stay
+    /// connected otherwise.
+    async fn disconnect(&self) -> Result<()> {
+        Ok(())
+    }
+
+    /// What the connection has sent so far, for sinks that keep count.
+    /// Wrappers report their inner sink's.
+    fn stats(&self) -> Option<SinkStats> {
+        None
+    }
+}
+
+/// Sends through one connection since it opened, for telling whether
+/// dropouts come from the app or the transport.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct SinkStats {
+    pub messages: u64,
+    pub bytes: u64,
+    pub errors: u64,
+    /// How long a send took on average, failed ones included.
+    pub average_latency: Duration,
+    pub last_error: Option<String>,
+}
+
+/// Counts sends for a sink's [`MidiSink::stats`].
+#[derive(Debug, Default)]
+pub struct SendStats {
+    messages: AtomicU64,
+    bytes: AtomicU64,
+    errors: AtomicU64,
+    sends: AtomicU64,
+    latency_us: AtomicU64,
+    last_error: Mutex<Option<String>>,
+}
+
+impl SendStats {
+    /// Records one send of `messages` that took `took`.
+    pub fn record(&self, messages: &[Vec<u8>], took: Duration, result: &Result<()>) {
+        self.sends.fetch_add(1, Ordering::Relaxed);
+        self.latency_us
+            .fetch_add(took.as_micros() as u64, Ordering::Relaxed);
+        match result {
+            Ok(()) => {
+                let bytes: usize = messages.iter().map(Vec::len).sum();
+                self.messages
+                    .fetch_add(messages.len() as u64, Ordering::Relaxed);
+                self.bytes.fetch_add(bytes as u64, Ordering::Relaxed);
+            }
+            Err(err) => {
+                self.errors.fetch_add(1, Ordering::Relaxed);
+                *self.last_error.lock().expect("send stats poisoned") = Some(format!("{err:#}"));
+            }
+        }
+    }
+
+    pub fn snapshot(&self) -> SinkStats {
+        let sends = self.sends.load(Ordering::Relaxed);
+        SinkStats {
+            messages: self.messages.load(Ordering::Relaxed),
+            bytes: self.bytes.load(Ordering::Relaxed),
+            errors: self.errors.load(Ordering::Relaxed),
+            average_latency: Duration::from_micros(
+                self.latency_us
+                    .load(Ordering::Relaxed)
+                    .checked_div(sends)
+                    .unwrap_or_default(),
+            ),
+            last_error: self.last_error.lock().expect("send stats poisoned").clone(),
+        }
+    }
+}
+
+/// All Notes Off (CC123) followed by All Sound Off (CC120) on all 16 channels.
+pub fn panic_messages() -> Vec<Vec<u8>> {
+    (0u8..16)
+        .flat_map(|channel| [vec![0xB0 | channel, 123, 0], vec![0xB0 | channel, 120, 0]])
+        .collect()
 }
 
 pub type SharedMidiSink = Arc<dyn MidiSink>;
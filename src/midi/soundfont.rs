@@ -0,0 +1,442 @@
+use std::collections::HashMap;
+use std::fs;
+use std::path::Path;
+use std::sync::Arc;
+
+use anyhow::{Context, Result, bail};
+
+const GEN_START_OFFSET: u16 = 0;
+const GEN_END_OFFSET: u16 = 1;
+const GEN_START_LOOP_OFFSET: u16 = 2;
+const GEN_END_LOOP_OFFSET: u16 = 3;
+const GEN_START_COARSE_OFFSET: u16 = 4;
+const GEN_END_COARSE_OFFSET: u16 = 12;
+const GEN_PAN: u16 = 17;
+const GEN_ATTACK_VOL_ENV: u16 = 34;
+const GEN_HOLD_VOL_ENV: u16 = 35;
+const GEN_DECAY_VOL_ENV: u16 = 36;
+const GEN_SUSTAIN_VOL_ENV: u16 = 37;
+const GEN_RELEASE_VOL_ENV: u16 = 38;
+const GEN_INSTRUMENT: u16 = 41;
+const GEN_KEY_RANGE: u16 = 43;
+const GEN_VEL_RANGE: u16 = 44;
+const GEN_START_LOOP_COARSE_OFFSET: u16 = 45;
+const GEN_INITIAL_ATTENUATION: u16 = 48;
+const GEN_END_LOOP_COARSE_OFFSET: u16 = 50;
+const GEN_COARSE_TUNE: u16 = 51;
+const GEN_FINE_TUNE: u16 = 52;
+const GEN_SAMPLE_ID: u16 = 53;
+const GEN_SAMPLE_MODES: u16 = 54;
+const GEN_OVERRIDING_ROOT_KEY: u16 = 58;
+const GEN_COUNT: usize = 61;
+
+/// Parsed SoundFont 2 bank holding the sample pool and preset/instrument
+/// zones needed for offline rendering.
+#[derive(Debug)]
+pub struct SoundFont {
+    samples: Arc<Vec<i16>>,
+    sample_headers: Vec<SampleHeader>,
+    instruments: Vec<Instrument>,
+    presets: HashMap<(u16, u16), Preset>,
+}
+
+#[derive(Debug, Clone)]
+struct SampleHeader {
+    start: u32,
+    end: u32,
+    loop_start: u32,
+    loop_end: u32,
+    sample_rate: u32,
+    original_pitch: u8,
+    pitch_correction: i8,
+}
+
+#[derive(Debug, Clone)]
+struct Zone {
+    generators: [Option<i16>; GEN_COUNT],
+    key_range: (u8, u8),
+    vel_range: (u8, u8),
+}
+
+impl Zone {
+    fn empty() -> Self {
+        Self {
+            generators: [None; GEN_COUNT],
+            key_range: (0, 127),
+            vel_range: (0, 127),
+        }
+    }
+
+    fn get(&self, generator: u16) -> Option<i16> {
+        self.generators.get(generator as usize).copied().flatten()
+    }
+
+    fn matches(&self, key: u8, velocity: u8) -> bool {
+        key >= self.key_range.0
+            && key <= self.key_range.1
+            && velocity >= self.vel_range.0
+            && velocity <= self.vel_range.1
+    }
+}
+
+#[derive(Debug, Clone)]
+struct Instrument {
+    global: Zone,
+    zones: Vec<Zone>,
+}
+
+#[derive(Debug, Clone)]
+struct Preset {
+    global: Zone,
+    zones: Vec<Zone>,
+}
+
+/// Everything a voice needs to play one sample region for a single note.
+#[derive(Debug, Clone)]
+pub struct VoiceRegion {
+    pub samples: Arc<Vec<i16>>,
+    pub start: usize,
+    pub end: usize,
+    pub loop_start: usize,
+    pub loop_end: usize,
+    pub looping: bool,
+    pub sample_rate: u32,
+    /// Pitch of the note relative to the sample's root, in semitones.
+    pub pitch_offset: f32,
+    pub attenuation_db: f32,
+    pub pan: f32,
+    pub attack: f32,
+    pub hold: f32,
+    pub decay: f32,
+    pub sustain_level: f32,
+    pub release: f32,
+}
+
+impl SoundFont {
+    pub fn from_file(path: &Path) -> Result<Self> {
+        let contents = fs::read(path)
+            .with_context(|| format!("failed to read soundfont {}", path.display()))?;
+        Self::parse(&contents)
+            .with_context(|| format!("failed to parse soundfont {}", path.display()))
+    }
+
+    pub fn parse(data: &[u8]) -> Result<Self> {
+        if data.len() < 12 || &data[0..4] != b"RIFF" || &data[8..12] != b"sfbk" {
+            bail!("not a SoundFont 2 file");
+        }
+
+        let mut samples = None;
+        let mut pdta = HashMap::new();
+        for (id, body) in riff_chunks(&data[12..])? {
+            if id != *b"LIST" || body.len() < 4 {
+                continue;
+            }
+            let kind = &body[0..4];
+            for (sub_id, sub_body) in riff_chunks(&body[4..])? {
+                match kind {
+                    b"sdta" if sub_id == *b"smpl" => {
+                        samples = Some(
+                            sub_body
+                                .chunks_exact(2)
+                                .map(|pair| i16::from_le_bytes([pair[0], pair[1]]))
+                                .collect::<Vec<_>>(),
+                        );
+                    }
+                    b"pdta" => {
+                        pdta.insert(sub_id, sub_body);
+                    }
+                    _ => {}
+                }
+            }
+        }
+
+        let samples = samples.context("soundfont has no sample data")?;
+        let chunk = |name: &[u8; 4]| {
+            pdta.get(name).copied().with_context(|| {
+                format!("soundfont missing {} chunk", String::from_utf8_lossy(name))
+            })
+        };
+
+        let sample_headers: Vec<SampleHeader> = chunk(b"shdr")?
+            .chunks_exact(46)
+            .map(|record| SampleHeader {
+                start: read_u32(record, 20),
+                end: read_u32(record, 24),
+                loop_start: read_u32(record, 28),
+                loop_end: read_u32(record, 32),
+                sample_rate: read_u32(record, 36),
+                original_pitch: record[40],
+                pitch_correction: record[41] as i8,
+            })
+            .collect();
+
+        let instrument_bags = read_bags(chunk(b"ibag")?);
+        let instrument_gens = read_generators(chunk(b"igen")?);
+        let instrument_records: Vec<u16> = chunk(b"inst")?
+            .chunks_exact(22)
+            .map(|record| read_u16(record, 20))
+            .collect();
+        let instruments = build_zones(
+            &instrument_records,
+            &instrument_bags,
+            &instrument_gens,
+            GEN_SAMPLE_ID,
+        )
+        .into_iter()
+        .map(|(global, zones)| Instrument { global, zones })
+        .collect();
+
+        let preset_bags = read_bags(chunk(b"pbag")?);
+        let preset_gens = read_generators(chunk(b"pgen")?);
+        let preset_records: Vec<(u16, u16, u16)> = chunk(b"phdr")?
+            .chunks_exact(38)
+            .map(|record| {
+                (
+                    read_u16(record, 20),
+                    read_u16(record, 22),
+                    read_u16(record, 24),
+                )
+            })
+            .collect();
+        let bag_indices: Vec<u16> = preset_records.iter().map(|(_, _, bag)| *bag).collect();
+        let mut presets = HashMap::new();
+        for ((program, bank, _), (global, zones)) in preset_records.iter().zip(build_zones(
+            &bag_indices,
+            &preset_bags,
+            &preset_gens,
+            GEN_INSTRUMENT,
+        )) {
+            presets
+                .entry((*bank, *program))
+                .or_insert(Preset { global, zones });
+        }
+
+        Ok(SoundFont {
+            samples: Arc::new(samples),
+            sample_headers,
+            instruments,
+            presets,
+        })
+    }
+
+    /// Resolves the sample regions that should sound for a note, falling back
+    /// to bank 0 (or program 0) when the requested preset is missing.
+    pub fn regions(&self, bank: u16, program: u8, key: u8, velocity: u8) -> Vec<VoiceRegion> {
+        let program = program as u16;
+        let preset = self
+            .presets
+            .get(&(bank, program))
+            .or_else(|| {
+                self.presets
+                    .get(&(if bank == 128 { 128 } else { 0 }, program))
+            })
+            .or_else(|| self.presets.get(&(bank, 0)))
+            .or_else(|| self.presets.get(&(0, 0)));
+        let Some(preset) = preset else {
+            return Vec::new();
+        };
+
+        let mut regions = Vec::new();
+        for preset_zone in preset
+            .zones
+            .iter()
+            .filter(|zone| zone.matches(key, velocity))
+        {
+            let Some(instrument_index) = preset_zone.get(GEN_INSTRUMENT) else {
+                continue;
+            };
+            let Some(instrument) = self.instruments.get(instrument_index as u16 as usize) else {
+                continue;
+            };
+            for zone in instrument
+                .zones
+                .iter()
+                .filter(|zone| zone.matches(key, velocity))
+            {
+                if let Some(region) = self.region(
+                    key,
+                    [&instrument.global, zone],
+                    [&preset.global, preset_zone],
+                ) {
+                    regions.push(region);
+                }
+            }
+        }
+        regions
+    }
+
+    fn region(&self, key: u8, instrument: [&Zone; 2], preset: [&Zone; 2]) -> Option<VoiceRegion> {
+        let inst = |generator: u16, default: i16| -> i32 {
+            instrument[1]
+                .get(generator)
+                .or_else(|| instrument[0].get(generator))
+                .unwrap_or(default) as i32
+        };
+        let pre = |generator: u16| -> i32 {
+            preset[1]
+                .get(generator)
+                .or_else(|| preset[0].get(generator))
+                .unwrap_or(0) as i32
+        };
+        let sum = |generator: u16, default: i16| inst(generator, default) + pre(generator);
+
+        let header = self
+            .sample_headers
+            .get(instrument[1].get(GEN_SAMPLE_ID)? as u16 as usize)?;
+        let len = self.samples.len() as i64;
+        let offset = |base: u32, fine: u16, coarse: u16| -> usize {
+            (base as i64 + inst(fine, 0) as i64 + inst(coarse, 0) as i64 * 32768).clamp(0, len)
+                as usize
+        };
+        let start = offset(header.start, GEN_START_OFFSET, GEN_START_COARSE_OFFSET);
+        let end = offset(header.end, GEN_END_OFFSET, GEN_END_COARSE_OFFSET);
+        let loop_start = offset(
+            header.loop_start,
+            GEN_START_LOOP_OFFSET,
+            GEN_START_LOOP_COARSE_OFFSET,
+        );
+        let loop_end = offset(
+            header.loop_end,
+            GEN_END_LOOP_OFFSET,
+            GEN_END_LOOP_COARSE_OFFSET,
+        );
+        if end <= start || header.sample_rate == 0 {
+            return None;
+        }
+
+        let root = match inst(GEN_OVERRIDING_ROOT_KEY, -1) {
+            value @ 0..=127 => value,
+            _ => header.original_pitch.min(127) as i32,
+        };
+        let pitch_offset = (key as i32 - root + sum(GEN_COARSE_TUNE, 0)) as f32
+            + (sum(GEN_FINE_TUNE, 0) + header.pitch_correction as i32) as f32 / 100.0;
+        let sample_modes = inst(GEN_SAMPLE_MODES, 0);
+        let looping = matches!(sample_modes, 1 | 3) && loop_end > loop_start && loop_end <= end;
+
+        Some(VoiceRegion {
+            samples: Arc::clone(&self.samples),
+            start,
+            end,
+            loop_start,
+            loop_end,
+            looping,
+            sample_rate: header.sample_rate,
+            pitch_offset,
+            attenuation_db: sum(GEN_INITIAL_ATTENUATION, 0).max(0) as f32 / 10.0,
+            pan: (sum(GEN_PAN, 0) as f32 / 500.0).clamp(-1.0, 1.0),
+            attack: timecents(sum(GEN_ATTACK_VOL_ENV, -12000)),
+            hold: timecents(sum(GEN_HOLD_VOL_ENV, -12000)),
+            decay: timecents(sum(GEN_DECAY_VOL_ENV, -12000)),
+            sustain_level: decibels_to_gain(
+                sum(GEN_SUSTAIN_VOL_ENV, 0).clamp(0, 1440) as f32 / 10.0,
+            ),
+            release: timecents(sum(GEN_RELEASE_VOL_ENV, -12000)).max(0.01),
+        })
+    }
+}
+
+pub fn decibels_to_gain(attenuation_db: f32) -> f32 {
+    10f32.powf(-attenuation_db / 20.0)
+}
+
+fn timecents(value: i32) -> f32 {
+    if value <= -12000 {
+        0.0
+    } else {
+        2f32.powf(value as f32 / 1200.0)
+    }
+}
+
+fn build_zones(
+    bag_starts: &[u16],
+    bags: &[u16],
+    generators: &[(u16, [u8; 2])],
+    terminal: u16,
+) -> Vec<(Zone, Vec<Zone>)> {
+    let mut result = Vec::new();
+    // The final record is the EOI terminator and only bounds the previous one.
+    for window in bag_starts.windows(2) {
+        let (first_bag, last_bag) = (window[0] as usize, window[1] as usize);
+        let mut global = Zone::empty();
+        let mut zones = Vec::new();
+        for bag in first_bag..last_bag {
+            let (Some(&gen_start), Some(&gen_end)) = (bags.get(bag), bags.get(bag + 1)) else {
+                break;
+            };
+            let mut zone = Zone::empty();
+            let mut has_terminal = false;
+            for (oper, amount) in generators
+                .get(gen_start as usize..gen_end as usize)
+                .unwrap_or_default()
+            {
+                match *oper {
+                    GEN_KEY_RANGE => zone.key_range = (amount[0], amount[1]),
+                    GEN_VEL_RANGE => zone.vel_range = (amount[0], amount[1]),
+                    oper if (oper as usize) < GEN_COUNT => {
+                        zone.generators[oper as usize] = Some(i16::from_le_bytes(*amount));
+                        has_terminal |= oper == terminal;
+                    }
+                    _ => {}
+                }
+            }
+            if has_terminal {
+                zones.push(zone);
+            } else if bag == first_bag {
+                global = zone;
+            }
+        }
+        result.push((global, zones));
+    }
+    result
+}
+
+fn read_bags(data: &[u8]) -> Vec<u16> {
+    data.chunks_exact(4)
+        .map(|record| read_u16(record, 0))
+        .collect()
+}
+
+fn read_generators(data: &[u8]) -> Vec<(u16, [u8; 2])> {
+    data.chunks_exact(4)
+        .map(|record| (read_u16(record, 0), [record[2], record[3]]))
+        .collect()
+}
+
+/// Splits `data` into RIFF chunks, failing on a chunk whose declared size
+/// runs past the end of the data.
+fn riff_chunks(mut data: &[u8]) -> Result<Vec<([u8; 4], &[u8])>> {
+    let mut chunks = Vec::new();
+    while !data.is_empty() {
+        if data.len() < 8 {
+            bail!("soundfont ends inside a chunk header");
+        }
+        let id = [data[0], data[1], data[2], data[3]];
+        let size = read_u32(data, 4) as usize;
+        let rest = &data[8..];
+        let Some(body) = rest.get(..size) else {
+            bail!(
+                "soundfont {} chunk claims {size} bytes but only {} remain",
+                String::from_utf8_lossy(&id),
+                rest.len()
+            );
+        };
+        chunks.push((id, body));
+        // Odd-sized chunks are padded to an even length. A missing pad byte
+        // after the last chunk is tolerated.
+        data = rest.get(size + (size & 1)..).unwrap_or_default();
+    }
+    Ok(chunks)
+}
+
+fn read_u16(data: &[u8], offset: usize) -> u16 {
+    u16::from_le_bytes([data[offset], data[offset + 1]])
+}
+
+fn read_u32(data: &[u8], offset: usize) -> u32 {
+    u32::from_le_bytes([
+        data[offset],
+        data[offset + 1],
+        data[offset + 2],
+        data[offset + 3],
+    ])
+}
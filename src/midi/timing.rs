@@ -0,0 +1,196 @@
+use std::collections::BTreeMap;
+use std::fs;
+use std::path::Path;
+use std::sync::{Arc, Mutex, MutexGuard};
+use std::time::{Duration, Instant};
+
+use anyhow::{Context, Result};
+use async_trait::async_trait;
+use serde::{Deserialize, Serialize};
+
+use super::sink::{MidiSink, SharedMidiSink, SinkStats};
+
+/// Samples kept per measurement; later ones are counted but not stored.
+pub const MAX_SAMPLES: usize = 100_000;
+
+/// Timings gathered while the app runs, for comparing scheduler and sink
+/// changes on real hardware: how long songs take to parse, how late the
+/// player sends each batch after the time it was due, and how long each
+/// kind of sink takes to accept a send.
+#[derive(Debug, Default)]
+pub struct TimingCapture {
+    samples: Mutex<Samples>,
+}
+
+#[derive(Debug, Default)]
+struct Samples {
+    parse: Series,
+    jitter: Series,
+    /// Keyed by sink label, e.g. "usb".
+    send: BTreeMap<String, Series>,
+}
+
+#[derive(Debug, Default)]
+struct Series {
+    kept: Vec<Duration>,
+    overflow: usize,
+}
+
+impl Series {
+    fn push(&mut self, sample: Duration) {
+        if self.kept.len() < MAX_SAMPLES {
+            self.kept.push(sample);
+        } else {
+            self.overflow += 1;
+        }
+    }
+}
+
+impl TimingCapture {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn record_parse(&self, took: Duration) {
+        self.lock().parse.push(took);
+    }
+
+    /// How long after its due time a batch went out.
+    pub fn record_jitter(&self, late: Duration) {
+        self.lock().jitter.push(late);
+    }
+
+    pub fn record_send(&self, sink: &str, took: Duration) {
+        self.lock()
+            .send
+            .entry(sink.to_string())
+            .or_default()
+            .push(took);
+    }
+
+    /// Runs `parse`, recording how long it took whether or not it worked.
+    pub fn time_parse<T>(&self, parse: impl FnOnce() -> T) -> T {
+        let started = Instant::now();
+        let parsed = parse();
+        self.record_parse(started.elapsed());
+        parsed
+    }
+
+    pub fn report(&self) -> TimingReport {
+        let samples = self.lock();
+        TimingReport {
+            parse: TimingStats::of(&samples.parse),
+            jitter: TimingStats::of(&samples.jitter),
+            send: samples
+                .send
+                .iter()
+                .filter_map(|(sink, series)| Some((sink.clone(), TimingStats::of(series)?)))
+                .collect(),
+        }
+    }
+
+    pub fn clear(&self) {
+        *self.lock() = Samples::default();
+    }
+
+    fn lock(&self) -> MutexGuard<'_, Samples> {
+        self.samples.lock().expect("timing capture poisoned")
+    }
+}
+
+/// A summary of a [`TimingCapture`], as written by [`TimingReport::dump`].
+/// Measurements without samples are left out.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct TimingReport {
+    pub parse: Option<TimingStats>,
+    pub jitter: Option<TimingStats>,
+    pub send: BTreeMap<String, TimingStats>,
+}
+
+impl TimingReport {
+    pub fn dump(&self, path: &Path) -> Result<()> {
+        if let Some(parent) = path.parent() {
+            fs::create_dir_all(parent)
+                .with_context(|| format!("failed to create {}", parent.display()))?;
+        }
+        let serialized =
+            serde_json::to_string_pretty(self).context("failed to serialize timings")?;
+        fs::write(path, serialized).with_context(|| format!("failed to write {}", path.display()))
+    }
+}
+
+/// One measurement, in microseconds.
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub struct TimingStats {
+    /// Every sample taken, including those past [`MAX_SAMPLES`] that the
+    /// figures below leave out.
+    pub count: usize,
+    pub min_us: u64,
+    pub mean_us: u64,
+    pub p50_us: u64,
+    pub p99_us: u64,
+    pub max_us: u64,
+}
+
+impl TimingStats {
+    fn of(series: &Series) -> Option<Self> {
+        let mut micros: Vec<u64> = series
+            .kept
+            .iter()
+            .map(|sample| sample.as_micros() as u64)
+            .collect();
+        micros.sort_unstable();
+        let (&min_us, &max_us) = (micros.first()?, micros.last()?);
+        let percentile = |percent: usize| micros[(micros.len() - 1) * percent / 100];
+        Some(Self {
+            count: micros.len() + series.overflow,
+            min_us,
+            mean_us: micros.iter().sum::<u64>() / micros.len() as u64,
+            p50_us: percentile(50),
+            p99_us: percentile(99),
+            max_us,
+        })
+    }
+}
+
+/// Records how long the wrapped sink takes over each send under `label`.
+pub struct TimingSink {
+    inner: SharedMidiSink,
+    label: String,
+    capture: Arc<TimingCapture>,
+}
+
+impl TimingSink {
+    pub fn new(
+        inner: SharedMidiSink,
+        label: impl Into<String>,
+        capture: Arc<TimingCapture>,
+    ) -> Self {
+        Self {
+            inner,
+            label: label.into(),
+            capture,
+        }
+    }
+}
+
+#[async_trait]
+impl MidiSink for TimingSink {
+    async fn send(&self, data: &[u8]) -> Result<()> {
+        let started = Instant::now();
+        let result = self.inner.send(data).await;
+        self.capture.record_send(&self.label, started.elapsed());
+        result
+    }
+
+    async fn send_batch(&self, messages: &[Vec<u8>]) -> Result<()> {
+        let started = Instant::now();
+        let result = self.inner.send_batch(messages).await;
+        self.capture.record_send(&self.label, started.elapsed());
+        result
+    }
+
+    fn stats(&self) -> Option<SinkStats> {
+        self.inner.stats()
+    }
+}
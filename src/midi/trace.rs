@@ -0,0 +1,221 @@
+use std::fmt;
+use std::fs::{self, File, OpenOptions};
+use std::io::{BufWriter, Write};
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex, MutexGuard};
+
+use anyhow::{Context, Result};
+use async_trait::async_trait;
+use chrono::{DateTime, SecondsFormat, Utc};
+use serde::{Deserialize, Serialize};
+use uuid::Uuid;
+
+use super::monitor::hex_bytes;
+use super::sink::{MidiSink, SharedMidiSink, SinkStats};
+
+/// Size at which the trace moves on to a fresh file.
+pub const DEFAULT_MAX_FILE_BYTES: u64 = 5 * 1024 * 1024;
+/// Full files kept besides the one being written, oldest dropped first.
+pub const KEPT_FILES: usize = 3;
+
+/// How [`SendTrace`] writes each message.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub enum TraceFormat {
+    /// One line per message: RFC 3339 time, device id and hex bytes.
+    #[default]
+    Hex,
+    /// One record per message: microseconds since the Unix epoch (`i64`,
+    /// little endian), the device id's 16 bytes, the message length (`u16`,
+    /// little endian) and the message.
+    Binary,
+}
+
+impl TraceFormat {
+    pub const ALL: [TraceFormat; 2] = [TraceFormat::Hex, TraceFormat::Binary];
+
+    fn file_name(self) -> &'static str {
+        match self {
+            TraceFormat::Hex => "midi-send.log",
+            TraceFormat::Binary => "midi-send.bin",
+        }
+    }
+}
+
+impl fmt::Display for TraceFormat {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            TraceFormat::Hex => "Hex text",
+            TraceFormat::Binary => "Binary",
+        })
+    }
+}
+
+/// A log of every message sent to devices, for working out afterwards what
+/// a piano was actually told to play. Files rotate once they reach a size
+/// limit: `midi-send.log` is written, `midi-send.log.1` is the most recent
+/// full one, and so on up to [`KEPT_FILES`].
+#[derive(Debug)]
+pub struct SendTrace {
+    path: PathBuf,
+    format: TraceFormat,
+    max_bytes: u64,
+    file: Mutex<TraceFile>,
+}
+
+#[derive(Debug)]
+struct TraceFile {
+    writer: BufWriter<File>,
+    written: u64,
+    /// Set after a failed write so a broken disk logs one warning, not one
+    /// per message.
+    failed: bool,
+}
+
+impl SendTrace {
+    /// Appends to the trace in `dir`, creating the directory if needed.
+    pub fn open(dir: &Path, format: TraceFormat, max_bytes: u64) -> Result<Self> {
+        fs::create_dir_all(dir).with_context(|| format!("failed to create {}", dir.display()))?;
+        let path = dir.join(format.file_name());
+        let file = TraceFile::open(&path)?;
+        Ok(Self {
+            path,
+            format,
+            max_bytes: max_bytes.max(1),
+            file: Mutex::new(file),
+        })
+    }
+
+    /// The file being written.
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    pub fn format(&self) -> TraceFormat {
+        self.format
+    }
+
+    /// Records messages sent together to `device`, all with the same time.
+    pub fn record(&self, device: Uuid, messages: &[Vec<u8>]) {
+        let at = Utc::now();
+        let mut record = Vec::new();
+        for data in messages {
+            self.encode(&mut record, at, device, data);
+        }
+        let mut file = self.lock();
+        if let Err(err) = self.write(&mut file, &record) {
+            if !file.failed {
+                log::warn!("failed to write {}: {err:?}", self.path.display());
+            }
+            file.failed = true;
+        }
+    }
+
+    fn encode(&self, out: &mut Vec<u8>, at: DateTime<Utc>, device: Uuid, data: &[u8]) {
+        match self.format {
+            TraceFormat::Hex => {
+                let line = format!(
+                    "{} {device} {}\n",
+                    at.to_rfc3339_opts(SecondsFormat::Micros, true),
+                    hex_bytes(data)
+                );
+                out.extend_from_slice(line.as_bytes());
+            }
+            TraceFormat::Binary => {
+                out.extend_from_slice(&at.timestamp_micros().to_le_bytes());
+                out.extend_from_slice(device.as_bytes());
+                let len = u16::try_from(data.len()).unwrap_or(u16::MAX);
+                out.extend_from_slice(&len.to_le_bytes());
+                out.extend_from_slice(&data[..usize::from(len)]);
+            }
+        }
+    }
+
+    fn write(&self, file: &mut TraceFile, record: &[u8]) -> Result<()> {
+        if file.written > 0 && file.written + record.len() as u64 > self.max_bytes {
+            file.writer.flush()?;
+            self.rotate()?;
+            *file = TraceFile::open(&self.path)?;
+        }
+        file.writer.write_all(record)?;
+        // Flushed as it goes so the trace is complete up to a crash.
+        file.writer.flush()?;
+        file.written += record.len() as u64;
+        Ok(())
+    }
+
+    fn rotate(&self) -> Result<()> {
+        let numbered = |index: usize| {
+            let mut name = self.path.clone().into_os_string();
+            name.push(format!(".{index}"));
+            PathBuf::from(name)
+        };
+        let oldest = numbered(KEPT_FILES);
+        if oldest.exists() {
+            fs::remove_file(&oldest)?;
+        }
+        for index in (1..KEPT_FILES).rev() {
+            let from = numbered(index);
+            if from.exists() {
+                fs::rename(&from, numbered(index + 1))?;
+            }
+        }
+        fs::rename(&self.path, numbered(1))?;
+        Ok(())
+    }
+
+    fn lock(&self) -> MutexGuard<'_, TraceFile> {
+        self.file
+            .lock()
+            .unwrap_or_else(|poisoned| poisoned.into_inner())
+    }
+}
+
+impl TraceFile {
+    fn open(path: &Path) -> Result<Self> {
+        let file = OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(path)
+            .with_context(|| format!("failed to open {}", path.display()))?;
+        let written = file.metadata().map_or(0, |meta| meta.len());
+        Ok(Self {
+            writer: BufWriter::new(file),
+            written,
+            failed: false,
+        })
+    }
+}
+
+/// Forwards to a device's sink, adding each message to a [`SendTrace`].
+pub struct TracedSink {
+    inner: SharedMidiSink,
+    trace: Arc<SendTrace>,
+    device: Uuid,
+}
+
+impl TracedSink {
+    pub fn new(inner: SharedMidiSink, trace: Arc<SendTrace>, device: Uuid) -> Self {
+        Self {
+            inner,
+            trace,
+            device,
+        }
+    }
+}
+
+#[async_trait]
+impl MidiSink for TracedSink {
+    async fn send(&self, data: &[u8]) -> Result<()> {
+        self.trace.record(self.device, &[data.to_vec()]);
+        self.inner.send(data).await
+    }
+
+    async fn send_batch(&self, messages: &[Vec<u8>]) -> Result<()> {
+        self.trace.record(self.device, messages);
+        self.inner.send_batch(messages).await
+    }
+
+    fn stats(&self) -> Option<SinkStats> {
+        self.inner.stats()
+    }
+}
@@ -0,0 +1,207 @@
+use std::borrow::Cow;
+use std::collections::BTreeMap;
+use std::fmt;
+
+use anyhow::Result;
+use async_trait::async_trait;
+
+use super::sink::{MidiSink, SharedMidiSink, SinkStats};
+
+const SYSEX_START: u8 = 0xF0;
+const SYSEX_END: u8 = 0xF7;
+
+/// Why a message was held back rather than sent.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Malformed {
+    /// Empty, or starting with a data byte.
+    NoStatus,
+    /// Fewer data bytes than the status calls for.
+    Truncated,
+    /// A data byte with the top bit set, which a device reads as a status.
+    BadDataByte,
+    /// A status MIDI leaves undefined, like 0xF4.
+    UndefinedStatus,
+}
+
+impl fmt::Display for Malformed {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            Malformed::NoStatus => "no status byte",
+            Malformed::Truncated => "truncated",
+            Malformed::BadDataByte => "data byte above 0x7F",
+            Malformed::UndefinedStatus => "undefined status",
+        })
+    }
+}
+
+/// Data bytes that follow `status`; `None` for SysEx and undefined
+/// statuses.
+fn data_len(status: u8) -> Option<usize> {
+    match status {
+        0x80..=0xBF | 0xE0..=0xEF | 0xF2 => Some(2),
+        0xC0..=0xDF | 0xF1 | 0xF3 => Some(1),
+        0xF6 | 0xF8..=0xFF => Some(0),
+        _ => None,
+    }
+}
+
+/// `data` as it is safe to send: borrowed when it is fine as it is, owned
+/// when it was fixed. Bytes past the end of a complete message are cut off
+/// and SysEx without its closing 0xF7 gets one. Escapes (0xF7 followed by
+/// raw bytes) are passed on untouched.
+pub fn normalize(data: &[u8]) -> Result<Cow<'_, [u8]>, Malformed> {
+    let Some((&status, rest)) = data.split_first() else {
+        return Err(Malformed::NoStatus);
+    };
+    match status {
+        0x00..=0x7F => Err(Malformed::NoStatus),
+        SYSEX_END => Ok(Cow::Borrowed(data)),
+        SYSEX_START => {
+            let body = rest.strip_suffix(&[SYSEX_END]).unwrap_or(rest);
+            if body.iter().any(|byte| *byte > 0x7F) {
+                Err(Malformed::BadDataByte)
+            } else if body.len() == rest.len() {
+                let mut fixed = data.to_vec();
+                fixed.push(SYSEX_END);
+                Ok(Cow::Owned(fixed))
+            } else {
+                Ok(Cow::Borrowed(data))
+            }
+        }
+        _ => {
+            let len = data_len(status).ok_or(Malformed::UndefinedStatus)?;
+            if rest.len() < len {
+                Err(Malformed::Truncated)
+            } else if rest[..len].iter().any(|byte| *byte > 0x7F) {
+                Err(Malformed::BadDataByte)
+            } else if rest.len() > len {
+                Ok(Cow::Owned(data[..=len].to_vec()))
+            } else {
+                Ok(Cow::Borrowed(data))
+            }
+        }
+    }
+}
+
+/// Messages dropped and fixed by [`normalize`].
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct ValidationCounts {
+    pub dropped: usize,
+    pub fixed: usize,
+}
+
+/// What loading a song took out of or changed in each of its tracks,
+/// numbered from 0 in file order.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct ValidationReport {
+    tracks: BTreeMap<u16, ValidationCounts>,
+}
+
+impl ValidationReport {
+    pub fn dropped(&mut self, track: u16) {
+        self.tracks.entry(track).or_default().dropped += 1;
+    }
+
+    pub fn fixed(&mut self, track: u16) {
+        self.tracks.entry(track).or_default().fixed += 1;
+    }
+
+    /// Whether every message was fine as it was.
+    pub fn is_clean(&self) -> bool {
+        self.tracks.is_empty()
+    }
+
+    /// Tracks with anything dropped or fixed, in file order.
+    pub fn tracks(&self) -> impl Iterator<Item = (u16, ValidationCounts)> + '_ {
+        self.tracks.iter().map(|(track, counts)| (*track, *counts))
+    }
+
+    pub fn total(&self) -> ValidationCounts {
+        self.tracks
+            .values()
+            .fold(ValidationCounts::default(), |total, counts| {
+                ValidationCounts {
+                    dropped: total.dropped + counts.dropped,
+                    fixed: total.fixed + counts.fixed,
+                }
+            })
+    }
+}
+
+/// Passes everything sent through [`normalize`] before the wrapped sink
+/// sees it, so a malformed message never reaches firmware that may hang
+/// on one.
+pub struct ValidatingSink {
+    inner: SharedMidiSink,
+}
+
+impl ValidatingSink {
+    pub fn new(inner: SharedMidiSink) -> Self {
+        Self { inner }
+    }
+}
+
+fn checked(data: &[u8]) -> Option<Cow<'_, [u8]>> {
+    normalize(data)
+        .inspect_err(|problem| log::debug!("dropped MIDI message {data:02X?}: {problem}"))
+        .ok()
+}
+
+#[async_trait]
+impl MidiSink for ValidatingSink {
+    async fn send(&self, data: &[u8]) -> Result<()> {
+        match checked(data) {
+            Some(data) => self.inner.send(&data).await,
+            None => Ok(()),
+        }
+    }
+
+    async fn send_batch(&self, messages: &[Vec<u8>]) -> Result<()> {
+        let checked: Vec<Vec<u8>> = messages
+            .iter()
+            .filter_map(|message| checked(message).map(Cow::into_owned))
+            .collect();
+        if checked.is_empty() {
+            return Ok(());
+        }
+        self.inner.send_batch(&checked).await
+    }
+
+    fn stats(&self) -> Option<SinkStats> {
+        self.inner.stats()
+    }
+}
+
+/// Leaves out channel message status bytes that repeat the one before, as
+/// a MIDI byte stream allows. System messages other than real-time ones
+/// end the run.
+#[derive(Debug, Clone, Default)]
+pub struct RunningStatus {
+    last: Option<u8>,
+}
+
+impl RunningStatus {
+    /// The bytes of `message` to send after those already sent.
+    pub fn compress<'a>(&mut self, message: &'a [u8]) -> &'a [u8] {
+        match message.first() {
+            Some(&status @ 0x80..=0xEF) => {
+                if self.last.replace(status) == Some(status) {
+                    &message[1..]
+                } else {
+                    message
+                }
+            }
+            Some(0xF0..=0xF7) => {
+                self.last = None;
+                message
+            }
+            _ => message,
+        }
+    }
+
+    /// Starts a new run, e.g. after a failed send left the device's state
+    /// unknown.
+    pub fn reset(&mut self) {
+        self.last = None;
+    }
+}
@@ -0,0 +1,110 @@
+use std::collections::VecDeque;
+use std::time::{Duration, Instant};
+
+/// How long an informational toast stays up.
+const INFO_TIMEOUT: Duration = Duration::from_secs(4);
+/// Informational toasts beyond this many push out the oldest one. Errors
+/// are never dropped this way.
+const MAX_INFO_TOASTS: usize = 4;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Severity {
+    Info,
+    /// Stays up until dismissed.
+    Error,
+}
+
+#[derive(Debug, Clone)]
+pub struct Toast<A> {
+    pub id: u64,
+    pub severity: Severity,
+    pub text: String,
+    /// Suggested fix offered next to the text.
+    pub action: Option<A>,
+    expires_at: Option<Instant>,
+}
+
+/// Stack of toasts, oldest first. `A` is the action a toast may offer.
+#[derive(Debug)]
+pub struct Notifications<A> {
+    toasts: VecDeque<Toast<A>>,
+    next_id: u64,
+}
+
+impl<A> Default for Notifications<A> {
+    fn default() -> Self {
+        Self {
+            toasts: VecDeque::new(),
+            next_id: 0,
+        }
+    }
+}
+
+impl<A> Notifications<A> {
+    pub fn info(&mut self, text: impl Into<String>) {
+        self.push(Severity::Info, text.into(), None);
+    }
+
+    pub fn error(&mut self, text: impl Into<String>) {
+        self.push(Severity::Error, text.into(), None);
+    }
+
+    pub fn error_with_action(&mut self, text: impl Into<String>, action: A) {
+        self.push(Severity::Error, text.into(), Some(action));
+    }
+
+    fn push(&mut self, severity: Severity, text: String, action: Option<A>) {
+        // Repeating a message moves it to the top instead of stacking copies.
+        self.toasts
+            .retain(|toast| toast.severity != severity || toast.text != text);
+        if severity == Severity::Info {
+            let infos = self
+                .toasts
+                .iter()
+                .filter(|toast| toast.severity == Severity::Info)
+                .count();
+            if infos >= MAX_INFO_TOASTS
+                && let Some(oldest) = self
+                    .toasts
+                    .iter()
+                    .position(|toast| toast.severity == Severity::Info)
+            {
+                self.toasts.remove(oldest);
+            }
+        }
+        let expires_at = match severity {
+            Severity::Info => Some(Instant::now() + INFO_TIMEOUT),
+            Severity::Error => None,
+        };
+        self.toasts.push_back(Toast {
+            id: self.next_id,
+            severity,
+            text,
+            action,
+            expires_at,
+        });
+        self.next_id += 1;
+    }
+
+    pub fn dismiss(&mut self, id: u64) {
+        self.toasts.retain(|toast| toast.id != id);
+    }
+
+    pub fn dismiss_all(&mut self) {
+        self.toasts.clear();
+    }
+
+    /// Drops toasts whose timer has run out.
+    pub fn expire(&mut self, now: Instant) {
+        self.toasts
+            .retain(|toast| toast.expires_at.is_none_or(|at| at > now));
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = &Toast<A>> {
+        self.toasts.iter()
+    }
+
+    pub fn len(&self) -> usize {
+        self.toasts.len()
+    }
+}
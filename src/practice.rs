@@ -0,0 +1,268 @@
+use std::collections::HashMap;
+use std::fmt;
+use std::fs;
+use std::path::Path;
+
+use anyhow::{Context, Result};
+use chrono::{DateTime, Local, NaiveDate, TimeZone, Utc};
+use serde::{Deserialize, Serialize};
+use uuid::Uuid;
+
+/// One playback of a piece, recorded when playback finishes or is stopped.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct PracticeSession {
+    pub entry_id: Uuid,
+    pub entry_name: String,
+    pub started_at: DateTime<Utc>,
+    pub played_secs: f64,
+    pub total_secs: f64,
+    pub completed: bool,
+    /// Score in percent. Nothing scores practice yet, so this stays `None`
+    /// and the exported column stays empty until a scoring source is added.
+    #[serde(default)]
+    pub accuracy: Option<f32>,
+}
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct PracticeLog {
+    pub sessions: Vec<PracticeSession>,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct PieceSummary {
+    pub entry_id: Uuid,
+    pub entry_name: String,
+    pub sessions: usize,
+    pub play_count: usize,
+    pub practice_secs: f64,
+    pub last_practiced: DateTime<Utc>,
+    /// Mean of the scored sessions; `None` while none are scored.
+    pub average_accuracy: Option<f32>,
+}
+
+/// Inclusive calendar-day range in local time; open ends are unbounded.
+#[derive(Debug, Clone, Copy, Default, Serialize)]
+pub struct DateRange {
+    pub from: Option<NaiveDate>,
+    pub to: Option<NaiveDate>,
+}
+
+impl DateRange {
+    pub fn contains(&self, instant: &DateTime<Utc>) -> bool {
+        let day = instant.with_timezone(&Local).date_naive();
+        self.from.is_none_or(|from| day >= from) && self.to.is_none_or(|to| day <= to)
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum StatsExportKind {
+    SessionsCsv,
+    SummaryCsv,
+    ReportJson,
+}
+
+impl StatsExportKind {
+    pub const ALL: [StatsExportKind; 3] = [
+        StatsExportKind::SessionsCsv,
+        StatsExportKind::SummaryCsv,
+        StatsExportKind::ReportJson,
+    ];
+
+    pub fn extension(&self) -> &'static str {
+        match self {
+            StatsExportKind::SessionsCsv | StatsExportKind::SummaryCsv => "csv",
+            StatsExportKind::ReportJson => "json",
+        }
+    }
+}
+
+impl fmt::Display for StatsExportKind {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let label = match self {
+            StatsExportKind::SessionsCsv => "Sessions (CSV)",
+            StatsExportKind::SummaryCsv => "Per-piece summary (CSV)",
+            StatsExportKind::ReportJson => "Full report (JSON)",
+        };
+        write!(f, "{label}")
+    }
+}
+
+#[derive(Serialize)]
+struct StatsReport<'a> {
+    range: DateRange,
+    pieces: Vec<PieceSummary>,
+    sessions: Vec<&'a PracticeSession>,
+}
+
+impl PracticeLog {
+    pub fn load(path: &Path) -> Result<Self> {
+        if !path.exists() {
+            return Ok(PracticeLog::default());
+        }
+        let data = fs::read_to_string(path)
+            .with_context(|| format!("failed to read {}", path.display()))?;
+        serde_json::from_str(&data).context("failed to parse practice statistics")
+    }
+
+    pub fn save(&self, path: &Path) -> Result<()> {
+        if let Some(parent) = path.parent() {
+            fs::create_dir_all(parent).context("failed to create data directory")?;
+        }
+        let serialized =
+            serde_json::to_string_pretty(self).context("failed to serialize practice log")?;
+        fs::write(path, serialized).with_context(|| format!("failed to write {}", path.display()))
+    }
+
+    pub fn record(&mut self, session: PracticeSession) {
+        self.sessions.push(session);
+    }
+
+    /// Adds the sessions of `other` that aren't already logged, keeping the
+    /// log in start order.
+    pub fn merge(&mut self, other: PracticeLog) {
+        for session in other.sessions {
+            let logged = self.sessions.iter().any(|existing| {
+                existing.entry_id == session.entry_id && existing.started_at == session.started_at
+            });
+            if !logged {
+                self.sessions.push(session);
+            }
+        }
+        self.sessions.sort_by_key(|session| session.started_at);
+    }
+
+    pub fn sessions_in(&self, range: DateRange) -> Vec<&PracticeSession> {
+        self.sessions
+            .iter()
+            .filter(|session| range.contains(&session.started_at))
+            .collect()
+    }
+
+    pub fn summarize(&self, range: DateRange) -> Vec<PieceSummary> {
+        let mut by_entry: HashMap<Uuid, (PieceSummary, Vec<f32>)> = HashMap::new();
+        for session in self.sessions_in(range) {
+            let (summary, scores) = by_entry.entry(session.entry_id).or_insert_with(|| {
+                (
+                    PieceSummary {
+                        entry_id: session.entry_id,
+                        entry_name: session.entry_name.clone(),
+                        sessions: 0,
+                        play_count: 0,
+                        practice_secs: 0.0,
+                        last_practiced: session.started_at,
+                        average_accuracy: None,
+                    },
+                    Vec::new(),
+                )
+            });
+            summary.sessions += 1;
+            if session.completed {
+                summary.play_count += 1;
+            }
+            summary.practice_secs += session.played_secs;
+            if session.started_at >= summary.last_practiced {
+                summary.last_practiced = session.started_at;
+                summary.entry_name = session.entry_name.clone();
+            }
+            scores.extend(session.accuracy);
+        }
+
+        let mut summaries: Vec<PieceSummary> = by_entry
+            .into_values()
+            .map(|(mut summary, scores)| {
+                if !scores.is_empty() {
+                    summary.average_accuracy =
+                        Some(scores.iter().sum::<f32>() / scores.len() as f32);
+                }
+                summary
+            })
+            .collect();
+        summaries.sort_by(|a, b| {
+            b.practice_secs
+                .total_cmp(&a.practice_secs)
+                .then_with(|| a.entry_name.cmp(&b.entry_name))
+        });
+        summaries
+    }
+
+    pub fn export(&self, kind: StatsExportKind, range: DateRange, path: &Path) -> Result<()> {
+        let contents = match kind {
+            StatsExportKind::SessionsCsv => {
+                let mut csv = String::from(
+                    "entry_id,entry_name,started_at,played_secs,total_secs,completed,accuracy\n",
+                );
+                for session in self.sessions_in(range) {
+                    csv.push_str(&csv_row(&[
+                        session.entry_id.to_string(),
+                        session.entry_name.clone(),
+                        local_timestamp(&session.started_at),
+                        format!("{:.1}", session.played_secs),
+                        format!("{:.1}", session.total_secs),
+                        session.completed.to_string(),
+                        optional_score(session.accuracy),
+                    ]));
+                }
+                csv
+            }
+            StatsExportKind::SummaryCsv => {
+                let mut csv = String::from(
+                    "entry_id,entry_name,sessions,play_count,practice_minutes,last_practiced,average_accuracy\n",
+                );
+                for summary in self.summarize(range) {
+                    csv.push_str(&csv_row(&[
+                        summary.entry_id.to_string(),
+                        summary.entry_name.clone(),
+                        summary.sessions.to_string(),
+                        summary.play_count.to_string(),
+                        format!("{:.1}", summary.practice_secs / 60.0),
+                        local_timestamp(&summary.last_practiced),
+                        optional_score(summary.average_accuracy),
+                    ]));
+                }
+                csv
+            }
+            StatsExportKind::ReportJson => serde_json::to_string_pretty(&StatsReport {
+                range,
+                pieces: self.summarize(range),
+                sessions: self.sessions_in(range),
+            })
+            .context("failed to serialize statistics report")?,
+        };
+        fs::write(path, contents).with_context(|| format!("failed to write {}", path.display()))
+    }
+}
+
+pub fn parse_date(input: &str) -> Result<Option<NaiveDate>, String> {
+    let trimmed = input.trim();
+    if trimmed.is_empty() {
+        return Ok(None);
+    }
+    NaiveDate::parse_from_str(trimmed, "%Y-%m-%d")
+        .map(Some)
+        .map_err(|_| format!("'{trimmed}' is not a YYYY-MM-DD date"))
+}
+
+fn local_timestamp(instant: &DateTime<Utc>) -> String {
+    Local
+        .from_utc_datetime(&instant.naive_utc())
+        .format("%Y-%m-%d %H:%M:%S")
+        .to_string()
+}
+
+fn optional_score(score: Option<f32>) -> String {
+    score.map(|value| format!("{value:.1}")).unwrap_or_default()
+}
+
+fn csv_row(fields: &[String]) -> String {
+    let escaped: Vec<String> = fields
+        .iter()
+        .map(|field| {
+            if field.contains([',', '"', '\n', '\r']) {
+                format!("\"{}\"", field.replace('"', "\"\""))
+            } else {
+                field.clone()
+            }
+        })
+        .collect();
+    format!("{}\n", escaped.join(","))
+}
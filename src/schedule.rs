@@ -0,0 +1,68 @@
+use chrono::{DateTime, Datelike, Days, Local, NaiveTime, TimeZone};
+use serde::{Deserialize, Serialize};
+use uuid::Uuid;
+
+/// How far ahead of a scheduled start the countdown is shown.
+pub const COUNTDOWN: chrono::TimeDelta = chrono::TimeDelta::seconds(60);
+
+/// A playlist that starts by itself at a set time on chosen weekdays, e.g.
+/// a self-playing piano in a lobby or a daily practice reminder.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Schedule {
+    pub id: Uuid,
+    pub enabled: bool,
+    /// Monday first.
+    pub days: [bool; 7],
+    pub time: NaiveTime,
+    pub playlist_id: Option<Uuid>,
+    pub device_id: Option<Uuid>,
+}
+
+impl Schedule {
+    /// Weekdays at 18:00, waiting for a playlist and device to be chosen.
+    pub fn new() -> Self {
+        Self {
+            id: Uuid::new_v4(),
+            enabled: true,
+            days: [true, true, true, true, true, false, false],
+            time: NaiveTime::from_hms_opt(18, 0, 0).expect("valid time"),
+            playlist_id: None,
+            device_id: None,
+        }
+    }
+
+    /// Whether it has everything it needs to run.
+    pub fn is_ready(&self) -> bool {
+        self.enabled && self.days.contains(&true) && self.playlist_id.is_some()
+    }
+
+    /// The first start strictly after `after`.
+    pub fn next_after(&self, after: DateTime<Local>) -> Option<DateTime<Local>> {
+        if !self.is_ready() {
+            return None;
+        }
+        // A week and a day covers every weekday even when today's time has
+        // passed.
+        (0..=7).find_map(|offset| {
+            let date = after.date_naive().checked_add_days(Days::new(offset))?;
+            let weekday = date.weekday().num_days_from_monday() as usize;
+            if !self.days[weekday] {
+                return None;
+            }
+            // Skipped over by a daylight saving change, the start is missed
+            // that day; repeated by one, the earlier time is used.
+            let start = Local
+                .from_local_datetime(&date.and_time(self.time))
+                .earliest()?;
+            (start > after).then_some(start)
+        })
+    }
+}
+
+/// The next schedule to start after `after`, and when.
+pub fn next_due(schedules: &[Schedule], after: DateTime<Local>) -> Option<(Uuid, DateTime<Local>)> {
+    schedules
+        .iter()
+        .filter_map(|schedule| Some((schedule.id, schedule.next_after(after)?)))
+        .min_by_key(|(_, due)| *due)
+}
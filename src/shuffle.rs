@@ -0,0 +1,165 @@
+use std::collections::{HashMap, VecDeque};
+
+use rand::Rng;
+use serde::{Deserialize, Serialize};
+use uuid::Uuid;
+
+/// Songs played recently, newest last, kept across sessions so a shuffle
+/// does not open with what was just heard.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ShuffleHistory {
+    recent: VecDeque<Uuid>,
+}
+
+impl ShuffleHistory {
+    /// Plays remembered; older ones are forgotten.
+    pub const CAPACITY: usize = 500;
+    /// Recent plays a shuffle keeps for the end unless told otherwise.
+    pub const DEFAULT_AVOID: usize = 20;
+
+    /// Notes that `id` was played, making it the most recent if it was
+    /// already remembered.
+    pub fn record(&mut self, id: Uuid) {
+        self.recent.retain(|recent| *recent != id);
+        self.recent.push_back(id);
+        while self.recent.len() > Self::CAPACITY {
+            self.recent.pop_front();
+        }
+    }
+
+    /// The last `count` songs played, most recent first.
+    pub fn last(&self, count: usize) -> impl Iterator<Item = Uuid> + '_ {
+        self.recent.iter().rev().take(count).copied()
+    }
+
+    /// Orders `tracks` randomly, keeping those among the last `avoid` played
+    /// for the end. Those come in a weighted random order in which the
+    /// longer ago a song was played, the earlier it tends to come. With
+    /// `avoid` zero this is a plain shuffle.
+    pub fn shuffle(&self, tracks: &mut [Uuid], avoid: usize, rng: &mut impl Rng) {
+        // Rank 0 is the most recent.
+        let ranks: HashMap<Uuid, usize> = self
+            .last(avoid)
+            .enumerate()
+            .map(|(rank, id)| (id, rank))
+            .collect();
+        // Weighted sampling without replacement (Efraimidis–Spirakis):
+        // sorting by u^(1/w) for uniform u puts heavier songs earlier.
+        // Songs not played recently sort ahead of all of them.
+        let mut keyed: Vec<(bool, f64, Uuid)> = tracks
+            .iter()
+            .map(|&id| {
+                let u: f64 = rng.random();
+                match ranks.get(&id) {
+                    None => (false, u, id),
+                    Some(&rank) => {
+                        let weight = (rank + 1) as f64 / avoid as f64;
+                        (true, u.powf(1.0 / weight), id)
+                    }
+                }
+            })
+            .collect();
+        keyed.sort_by(|a, b| a.0.cmp(&b.0).then(b.1.total_cmp(&a.1)));
+        for (slot, (_, _, id)) in tracks.iter_mut().zip(keyed) {
+            *slot = id;
+        }
+    }
+}
+
+/// Songs started this session, oldest first, for Previous to step back
+/// through whatever queue or single play each came from.
+#[derive(Debug, Clone, Default)]
+pub struct PlaybackHistory {
+    played: VecDeque<Uuid>,
+    /// The song Previous last stepped back to; `None` while following the
+    /// queue.
+    cursor: Option<usize>,
+}
+
+impl PlaybackHistory {
+    /// Songs remembered; older ones are forgotten.
+    pub const CAPACITY: usize = 100;
+
+    /// Notes that `id` started playing. Ignored while stepping back, and when
+    /// `id` is already the latest, as when resuming after a pause.
+    pub fn record(&mut self, id: Uuid) {
+        if self.cursor.is_some() || self.played.back() == Some(&id) {
+            return;
+        }
+        self.played.push_back(id);
+        while self.played.len() > Self::CAPACITY {
+            self.played.pop_front();
+        }
+    }
+
+    /// Steps back to the song played before the current one, or `None` at
+    /// the oldest.
+    pub fn back(&mut self) -> Option<Uuid> {
+        let current = match self.cursor {
+            Some(cursor) => cursor,
+            None => self.played.len().checked_sub(1)?,
+        };
+        let previous = current.checked_sub(1)?;
+        self.cursor = Some(previous);
+        self.played.get(previous).copied()
+    }
+
+    /// Whether Previous has stepped back since the latest song started.
+    pub fn is_stepping_back(&self) -> bool {
+        self.cursor.is_some()
+    }
+
+    /// Stops stepping back, returning the latest song, the one playing when
+    /// Previous was first pressed. `None` when not stepping back.
+    pub fn resume(&mut self) -> Option<Uuid> {
+        self.cursor.take()?;
+        self.played.back().copied()
+    }
+}
+
+/// How much more often a song with `stars` (1–5) comes up in a weighted
+/// shuffle than a one-star song: each star doubles it. Unrated songs count
+/// as three stars.
+pub fn rating_weight(stars: Option<u8>) -> f64 {
+    let stars = stars.unwrap_or(3).clamp(1, 5);
+    f64::from(1u32 << (stars - 1))
+}
+
+/// Draws `count` songs from `tracks`, each chosen with probability in
+/// proportion to `weight`, so the same song can come up more than once.
+/// A song never follows itself, nor `previous` when given, unless there is
+/// nothing else to play.
+pub fn weighted_draw(
+    tracks: &[Uuid],
+    count: usize,
+    previous: Option<Uuid>,
+    weight: impl Fn(Uuid) -> f64,
+    rng: &mut impl Rng,
+) -> Vec<Uuid> {
+    let weights: Vec<f64> = tracks.iter().map(|id| weight(*id).max(0.0)).collect();
+    let mut drawn = Vec::with_capacity(count);
+    let mut last = previous;
+    for _ in 0..count {
+        let allowed = |index: usize| tracks.len() == 1 || Some(tracks[index]) != last;
+        let total: f64 = (0..tracks.len())
+            .filter(|index| allowed(*index))
+            .map(|index| weights[index])
+            .sum();
+        if total <= 0.0 {
+            break;
+        }
+        let mut target = rng.random_range(0.0..total);
+        let mut pick = None;
+        for index in (0..tracks.len()).filter(|index| allowed(*index)) {
+            pick = Some(tracks[index]);
+            if target < weights[index] {
+                break;
+            }
+            target -= weights[index];
+        }
+        let Some(pick) = pick else { break };
+        drawn.push(pick);
+        last = Some(pick);
+    }
+    drawn
+}
@@ -0,0 +1,165 @@
+//! System tray icon with transport controls and recently played tracks.
+//! The tray speaks the freedesktop StatusNotifierItem protocol, so it is
+//! only available on Linux; elsewhere [`spawn`] fails and the app runs
+//! without one.
+
+use std::fmt;
+
+use tokio::sync::mpsc::UnboundedSender;
+use uuid::Uuid;
+
+/// A tray menu choice, handed to the app through a channel.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum TrayCommand {
+    PlayPause,
+    Next,
+    Stop,
+    Play(Uuid),
+    ShowWindow,
+    Quit,
+}
+
+/// What the tray menu shows.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct TrayState {
+    pub playing: bool,
+    pub now_playing: Option<String>,
+    pub recent: Vec<(Uuid, String)>,
+}
+
+#[derive(Clone)]
+pub struct TrayHandle {
+    #[cfg(target_os = "linux")]
+    inner: ksni::Handle<linux::AppTray>,
+}
+
+impl fmt::Debug for TrayHandle {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("TrayHandle").finish_non_exhaustive()
+    }
+}
+
+impl TrayHandle {
+    /// Replaces the menu contents. The tray redraws in the background.
+    pub fn update(&self, state: TrayState) {
+        #[cfg(target_os = "linux")]
+        {
+            let handle = self.inner.clone();
+            tokio::spawn(async move {
+                handle.update(|tray| tray.state = state).await;
+            });
+        }
+        #[cfg(not(target_os = "linux"))]
+        let _ = state;
+    }
+}
+
+#[cfg(target_os = "linux")]
+pub async fn spawn(commands: UnboundedSender<TrayCommand>) -> Result<TrayHandle, String> {
+    use ksni::TrayMethods;
+
+    linux::AppTray {
+        commands,
+        state: TrayState::default(),
+    }
+    .spawn()
+    .await
+    .map(|inner| TrayHandle { inner })
+    .map_err(|err| err.to_string())
+}
+
+#[cfg(not(target_os = "linux"))]
+pub async fn spawn(_commands: UnboundedSender<TrayCommand>) -> Result<TrayHandle, String> {
+    Err("the system tray is only supported on Linux".into())
+}
+
+#[cfg(target_os = "linux")]
+mod linux {
+    use ksni::menu::{StandardItem, SubMenu};
+    use ksni::{MenuItem, ToolTip};
+    use tokio::sync::mpsc::UnboundedSender;
+
+    use super::{TrayCommand, TrayState};
+    use crate::i18n::t;
+
+    pub struct AppTray {
+        pub commands: UnboundedSender<TrayCommand>,
+        pub state: TrayState,
+    }
+
+    impl AppTray {
+        fn send(&self, command: TrayCommand) {
+            // The receiver only goes away when the app is exiting.
+            let _ = self.commands.send(command);
+        }
+    }
+
+    fn item(label: &str, command: TrayCommand) -> MenuItem<AppTray> {
+        StandardItem {
+            // Underscores mark access keys; double them to show them as-is.
+            label: label.replace('_', "__"),
+            activate: Box::new(move |tray: &mut AppTray| tray.send(command.clone())),
+            ..Default::default()
+        }
+        .into()
+    }
+
+    impl ksni::Tray for AppTray {
+        fn id(&self) -> String {
+            env!("CARGO_PKG_NAME").into()
+        }
+
+        fn title(&self) -> String {
+            "MIDI Piano Player".into()
+        }
+
+        fn icon_name(&self) -> String {
+            "audio-x-generic".into()
+        }
+
+        fn tool_tip(&self) -> ToolTip {
+            ToolTip {
+                title: self
+                    .state
+                    .now_playing
+                    .clone()
+                    .unwrap_or_else(|| self.title()),
+                ..Default::default()
+            }
+        }
+
+        fn activate(&mut self, _x: i32, _y: i32) {
+            self.send(TrayCommand::ShowWindow);
+        }
+
+        fn menu(&self) -> Vec<MenuItem<Self>> {
+            let play_label = if self.state.playing {
+                t!("Pause")
+            } else {
+                t!("Play")
+            };
+            let recent = self
+                .state
+                .recent
+                .iter()
+                .map(|(id, name)| item(name, TrayCommand::Play(*id)))
+                .collect::<Vec<_>>();
+            vec![
+                item(play_label, TrayCommand::PlayPause),
+                item(t!("Next Track"), TrayCommand::Next),
+                item(t!("Stop"), TrayCommand::Stop),
+                MenuItem::Separator,
+                SubMenu {
+                    label: t!("Recent").into(),
+                    enabled: !recent.is_empty(),
+                    submenu: recent,
+                    ..Default::default()
+                }
+                .into(),
+                MenuItem::Separator,
+                item(t!("Show Window"), TrayCommand::ShowWindow),
+                item(t!("Quit"), TrayCommand::Quit),
+            ]
+        }
+    }
+}
@@ -0,0 +1,47 @@
+//! Posts playback lifecycle events as JSON to a user-configured URL, for
+//! logging practice to a personal server or spreadsheet automation.
+
+use std::time::Duration;
+
+use anyhow::{Context, Result};
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use uuid::Uuid;
+
+const REQUEST_TIMEOUT: Duration = Duration::from_secs(10);
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum NowPlayingKind {
+    Started,
+    /// Played through to the end.
+    Finished,
+    /// Stopped or failed before the end.
+    Stopped,
+}
+
+/// The body of every webhook request.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct NowPlayingEvent {
+    pub event: NowPlayingKind,
+    pub track_id: Uuid,
+    pub track_name: String,
+    pub duration_secs: f64,
+    /// How much was played; zero for `started`.
+    pub played_secs: f64,
+    /// Name of the output device, when one was selected.
+    pub device: Option<String>,
+    pub started_at: DateTime<Utc>,
+    pub sent_at: DateTime<Utc>,
+}
+
+/// Sends `event` to `url`, failing on anything but a successful response.
+pub fn post(url: &str, event: &NowPlayingEvent) -> Result<()> {
+    let body = serde_json::to_string(event).context("failed to serialize webhook event")?;
+    ureq::post(url)
+        .timeout(REQUEST_TIMEOUT)
+        .set("Content-Type", "application/json")
+        .send_string(&body)
+        .with_context(|| format!("webhook request to {url} failed"))?;
+    Ok(())
+}
@@ -0,0 +1,117 @@
+use std::collections::{HashMap, HashSet};
+use std::time::Duration;
+
+use serde::{Deserialize, Serialize};
+use uuid::Uuid;
+
+/// Songs that play together in order as one piece, e.g. the movements of a
+/// sonata kept in separate files. A song is a movement of at most one work.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Work {
+    pub id: Uuid,
+    pub name: String,
+    pub movements: Vec<Uuid>,
+    /// Seconds of silence between movements.
+    #[serde(default)]
+    pub pause_secs: u8,
+}
+
+impl Work {
+    /// Pause between movements of a new work.
+    pub const DEFAULT_PAUSE_SECS: u8 = 3;
+
+    pub fn new(name: String, movements: Vec<Uuid>) -> Self {
+        Self {
+            id: Uuid::new_v4(),
+            name,
+            movements,
+            pause_secs: Self::DEFAULT_PAUSE_SECS,
+        }
+    }
+
+    pub fn pause(&self) -> Duration {
+        Duration::from_secs(self.pause_secs.into())
+    }
+
+    /// The movement played after `id`, unless `id` is the last one or not a
+    /// movement of this work.
+    pub fn movement_after(&self, id: Uuid) -> Option<Uuid> {
+        let index = self.movements.iter().position(|movement| *movement == id)?;
+        self.movements.get(index + 1).copied()
+    }
+}
+
+/// Adds `work`, taking its movements out of any other work. Works left with
+/// fewer than two movements are dropped.
+pub fn insert(works: &mut Vec<Work>, work: Work) {
+    let taken: HashSet<Uuid> = work.movements.iter().copied().collect();
+    forget(works, &taken);
+    works.push(work);
+}
+
+/// Adds the works of `imported` that share no movement with a work already
+/// in `works`. Existing works are kept as they are, like other merged
+/// preferences.
+pub fn merge(works: &mut Vec<Work>, imported: Vec<Work>) {
+    for work in imported {
+        let grouped = works.iter().any(|existing| {
+            existing.id == work.id
+                || existing
+                    .movements
+                    .iter()
+                    .any(|id| work.movements.contains(id))
+        });
+        if !grouped {
+            works.push(work);
+        }
+    }
+}
+
+/// Takes `ids` out of every work, dropping works left with fewer than two
+/// movements.
+pub fn forget(works: &mut Vec<Work>, ids: &HashSet<Uuid>) {
+    for work in works.iter_mut() {
+        work.movements.retain(|id| !ids.contains(id));
+    }
+    works.retain(|work| work.movements.len() >= 2);
+}
+
+/// Looks up the work each song is a movement of.
+#[derive(Debug, Default)]
+pub struct WorkIndex<'a> {
+    by_movement: HashMap<Uuid, &'a Work>,
+}
+
+impl<'a> WorkIndex<'a> {
+    pub fn new(works: &'a [Work]) -> Self {
+        let by_movement = works
+            .iter()
+            .flat_map(|work| work.movements.iter().map(move |id| (*id, work)))
+            .collect();
+        Self { by_movement }
+    }
+
+    pub fn work_of(&self, id: Uuid) -> Option<&'a Work> {
+        self.by_movement.get(&id).copied()
+    }
+
+    /// `order` with each work any of whose movements it mentions played
+    /// whole, all movements in order, where the first mention was. Later
+    /// mentions of a work are dropped; other songs are left as they are,
+    /// repeats included.
+    pub fn keep_together(&self, order: &[Uuid]) -> Vec<Uuid> {
+        let mut placed = HashSet::new();
+        let mut kept = Vec::with_capacity(order.len());
+        for &id in order {
+            match self.work_of(id) {
+                Some(work) => {
+                    if placed.insert(work.id) {
+                        kept.extend(&work.movements);
+                    }
+                }
+                None => kept.push(id),
+            }
+        }
+        kept
+    }
+}
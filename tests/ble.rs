@@ -0,0 +1,106 @@
+use std::time::Duration;
+
+use btleplug::api::{CharPropFlags, WriteType};
+use midi_piano_rs::devices::{
+    MIN_BLE_PACKET_SIZE, WriteCredits, first_ble_write_type, pack_ble_midi_packets,
+};
+use tokio::time::{self, Instant};
+
+#[test]
+fn short_messages_share_packets_without_splitting() {
+    let notes: Vec<Vec<u8>> = (0..8).map(|key| vec![0x90, 60 + key, 100]).collect();
+
+    let packets = pack_ble_midi_packets(&notes, MIN_BLE_PACKET_SIZE);
+
+    // A header, then four bytes per note: four notes take 17 bytes and a
+    // fifth would not fit in 20.
+    assert_eq!(packets.len(), 2);
+    assert_eq!(packets[0].len(), 17);
+    assert_eq!(&packets[0][..5], &[0x80, 0x80, 0x90, 60, 100]);
+    assert_eq!(packets[1][..2], [0x80, 0x80]);
+    assert_eq!(packets[1].len(), 1 + 4 * 4);
+}
+
+#[test]
+fn long_sysex_continues_across_packets() {
+    let data: Vec<u8> = (0..38).collect();
+    let sysex = [vec![0xF0], data.clone(), vec![0xF7]].concat();
+    let messages = vec![vec![0x90, 60, 100], sysex];
+
+    let packets = pack_ble_midi_packets(&messages, MIN_BLE_PACKET_SIZE);
+
+    let expected = vec![
+        [
+            vec![0x80, 0x80, 0x90, 60, 100, 0x80, 0xF0],
+            data[..13].to_vec(),
+        ]
+        .concat(),
+        [vec![0x80], data[13..32].to_vec()].concat(),
+        [vec![0x80], data[32..].to_vec(), vec![0x80, 0xF7]].concat(),
+    ];
+    assert_eq!(packets, expected);
+    assert!(
+        packets
+            .iter()
+            .all(|packet| packet.len() <= MIN_BLE_PACKET_SIZE)
+    );
+}
+
+#[test]
+fn larger_packets_hold_a_whole_sysex() {
+    let sysex = [vec![0xF0], vec![0x11; 100], vec![0xF7]].concat();
+
+    let packets = pack_ble_midi_packets(std::slice::from_ref(&sysex), 185);
+
+    assert_eq!(packets.len(), 1);
+    assert_eq!(packets[0].len(), 1 + 1 + 101 + 2);
+    assert_eq!(packets[0][packets[0].len() - 2..], [0x80, 0xF7]);
+}
+
+#[tokio::test(start_paused = true)]
+async fn unconfirmed_writes_hold_their_credit_for_a_while() {
+    let hold = Duration::from_millis(15);
+    let credits = WriteCredits::new(2, hold);
+    let started = Instant::now();
+
+    credits.acquire().await.unconfirmed();
+    credits.acquire().await.unconfirmed();
+    assert_eq!(credits.available(), 0);
+
+    // A third write waits for the first to have gone out.
+    let third = credits.acquire().await;
+    assert_eq!(started.elapsed(), hold);
+    third.unconfirmed();
+
+    time::sleep(hold * 2).await;
+    assert_eq!(credits.available(), 2);
+}
+
+#[tokio::test]
+async fn confirmed_writes_give_their_credit_straight_back() {
+    let credits = WriteCredits::new(1, Duration::from_secs(60));
+
+    drop(credits.acquire().await);
+
+    assert_eq!(credits.available(), 1);
+    drop(credits.acquire().await);
+}
+
+#[test]
+fn reliable_writes_wait_for_a_response_when_the_device_takes_one() {
+    let both = CharPropFlags::WRITE | CharPropFlags::WRITE_WITHOUT_RESPONSE;
+
+    assert_eq!(
+        first_ble_write_type(both, false),
+        WriteType::WithoutResponse
+    );
+    assert_eq!(first_ble_write_type(both, true), WriteType::WithResponse);
+    assert_eq!(
+        first_ble_write_type(CharPropFlags::WRITE, false),
+        WriteType::WithResponse
+    );
+    assert_eq!(
+        first_ble_write_type(CharPropFlags::WRITE_WITHOUT_RESPONSE, true),
+        WriteType::WithoutResponse
+    );
+}
@@ -0,0 +1,56 @@
+mod common;
+
+use std::time::Duration;
+
+use common::smf_bytes;
+use midi_piano_rs::midi::capabilities::{CapabilityIssue, DeviceCapabilities};
+use midi_piano_rs::midi::{MidiSequence, PlaybackEvent};
+
+fn band_sequence() -> MidiSequence {
+    let mut sequence = MidiSequence::from_bytes(&smf_bytes(&[
+        (0, 10, 0, 60),
+        (0, 10, 2, 48),
+        (0, 10, 9, 36),
+    ]))
+    .unwrap();
+    // Strings on channel 3.
+    sequence.events.insert(
+        0,
+        PlaybackEvent {
+            at: Duration::ZERO,
+            data: vec![0xC2, 48],
+            track: 0,
+        },
+    );
+    sequence
+}
+
+#[test]
+fn piano_only_devices_flag_extra_channels_drums_and_instruments() {
+    let sequence = band_sequence();
+
+    assert_eq!(
+        DeviceCapabilities::PIANO_ONLY.issues(&sequence),
+        vec![
+            CapabilityIssue::Channels(vec![2]),
+            CapabilityIssue::Percussion,
+            CapabilityIssue::Programs(vec![48]),
+        ]
+    );
+    assert!(DeviceCapabilities::default().issues(&sequence).is_empty());
+}
+
+#[test]
+fn remapped_songs_fit_the_device() {
+    let capabilities = DeviceCapabilities::PIANO_ONLY;
+    let remapped = band_sequence().adjusted(capabilities.remap_adjustments());
+
+    assert!(capabilities.issues(&remapped).is_empty());
+    let keys: Vec<&[u8]> = remapped
+        .events
+        .iter()
+        .filter(|event| event.data[0] & 0xF0 == 0x90 && event.data[2] > 0)
+        .map(|event| event.data.as_slice())
+        .collect();
+    assert_eq!(keys, vec![&[0x90, 60, 100][..], &[0x90, 48, 100][..]]);
+}
@@ -0,0 +1,82 @@
+use std::fs;
+use std::path::PathBuf;
+
+use midi_piano_rs::midi::catalog::{self, CatalogEntry};
+use uuid::Uuid;
+
+fn scratch_dir(name: &str) -> PathBuf {
+    let dir = std::env::temp_dir().join(format!("midi-piano-{name}-{}", std::process::id()));
+    let _ = fs::remove_dir_all(&dir);
+    fs::create_dir_all(&dir).unwrap();
+    dir
+}
+
+fn entries() -> Vec<CatalogEntry> {
+    vec![
+        CatalogEntry {
+            id: Uuid::new_v4(),
+            name: "Prelude, \"No. 1\"".into(),
+            folder: vec!["Bach".into(), "WTC".into()],
+            path: PathBuf::from("assets/midi/bach/prelude1.mid"),
+            duration_secs: Some(134.5),
+            rating: Some(5),
+            favorite: true,
+        },
+        CatalogEntry {
+            id: Uuid::new_v4(),
+            name: "Etude".into(),
+            folder: Vec::new(),
+            path: PathBuf::from("etude.mid"),
+            duration_secs: None,
+            rating: None,
+            favorite: false,
+        },
+    ]
+}
+
+#[test]
+fn json_catalogs_round_trip() {
+    let path = scratch_dir("catalog-json").join("catalog.json");
+    catalog::write(&entries(), &path).unwrap();
+    let expected = entries();
+    let read = catalog::read(&path).unwrap();
+    assert_eq!(read.len(), expected.len());
+    assert_eq!(read[0].name, expected[0].name);
+    assert_eq!(read[0].folder, expected[0].folder);
+    assert_eq!(read[1].rating, None);
+}
+
+#[test]
+fn csv_catalogs_round_trip_with_quoted_names() {
+    let path = scratch_dir("catalog-csv").join("catalog.csv");
+    let written = entries();
+    catalog::write(&written, &path).unwrap();
+
+    let csv = fs::read_to_string(&path).unwrap();
+    assert!(csv.starts_with("id,name,folder,path,duration_secs,rating,favorite\n"));
+    assert!(csv.contains("\"Prelude, \"\"No. 1\"\"\""));
+
+    assert_eq!(catalog::read(&path).unwrap(), written);
+}
+
+#[test]
+fn edited_csv_catalogs_only_need_ids() {
+    let path = scratch_dir("catalog-edited").join("catalog.csv");
+    let id = Uuid::new_v4();
+    fs::write(&path, format!("rating,id\r\n4,{id}\r\n\r\n")).unwrap();
+
+    let read = catalog::read(&path).unwrap();
+    assert_eq!(read.len(), 1);
+    assert_eq!(read[0].id, id);
+    assert_eq!(read[0].rating, Some(4));
+    assert!(!read[0].favorite);
+}
+
+#[test]
+fn bad_ratings_are_rejected() {
+    let path = scratch_dir("catalog-bad").join("catalog.csv");
+    fs::write(&path, format!("id,rating\n{},7\n", Uuid::new_v4())).unwrap();
+
+    let err = catalog::read(&path).unwrap_err();
+    assert!(format!("{err:#}").contains("row 2"));
+}
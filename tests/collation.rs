@@ -0,0 +1,68 @@
+use std::cmp::Ordering;
+
+use midi_piano_rs::midi::collation::TitleCollation;
+
+fn sorted(collation: TitleCollation, titles: &[&str]) -> Vec<String> {
+    let mut titles: Vec<String> = titles.iter().map(|title| title.to_string()).collect();
+    titles.sort_by_cached_key(|title| collation.key(title));
+    titles
+}
+
+#[test]
+fn accents_and_case_are_ignored() {
+    assert_eq!(
+        sorted(
+            TitleCollation::Unicode,
+            &["Zeta", "Étude", "eclogue", "Fantasie"]
+        ),
+        ["eclogue", "Étude", "Fantasie", "Zeta"]
+    );
+}
+
+#[test]
+fn numbers_compare_by_value() {
+    assert_eq!(
+        sorted(
+            TitleCollation::Unicode,
+            &["Etude 10", "Etude 2", "Etude 02b", "Etude 1"]
+        ),
+        ["Etude 1", "Etude 2", "Etude 02b", "Etude 10"]
+    );
+}
+
+#[test]
+fn full_width_forms_sort_with_their_usual_forms() {
+    assert_eq!(
+        sorted(TitleCollation::Unicode, &["Ｂｏｌｅｒｏ", "Aria", "Canon"]),
+        ["Aria", "Ｂｏｌｅｒｏ", "Canon"]
+    );
+}
+
+#[test]
+fn alphabets_come_before_kana_hangul_and_han() {
+    assert_eq!(
+        sorted(
+            TitleCollation::Unicode,
+            &["月光", "さくら", "아리랑", "Yesterday"]
+        ),
+        ["Yesterday", "さくら", "아리랑", "月光"]
+    );
+}
+
+#[test]
+fn titles_differing_only_in_accents_keep_a_fixed_order() {
+    let collation = TitleCollation::Unicode;
+    assert_eq!(collation.compare("Etude", "Étude"), Ordering::Less);
+    assert_eq!(collation.compare("Étude", "etude"), Ordering::Greater);
+}
+
+#[test]
+fn simple_collation_keeps_code_point_order() {
+    assert_eq!(
+        sorted(
+            TitleCollation::Simple,
+            &["Étude", "Zeta", "etude 10", "etude 2"]
+        ),
+        ["etude 10", "etude 2", "Zeta", "Étude"]
+    );
+}
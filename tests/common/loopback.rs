@@ -0,0 +1,89 @@
+//! A virtual MIDI input the app can find and play to like a USB device, so
+//! tests can check what reaches the system's MIDI layer and when.
+
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+
+/// A message as the loopback input received it.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Received {
+    /// Since the loopback was opened.
+    pub at: Duration,
+    pub data: Vec<u8>,
+}
+
+/// A virtual input port, open until dropped.
+pub struct Loopback {
+    port_name: String,
+    opened: Instant,
+    received: Arc<Mutex<Vec<Received>>>,
+    #[cfg(unix)]
+    _connection: midir::MidiInputConnection<()>,
+}
+
+impl Loopback {
+    /// Opens a port named after `test`. Panics when this system can't create
+    /// virtual ports (no ALSA sequencer in a container, or a platform without
+    /// them), as the tests using it only run when asked for.
+    #[cfg(unix)]
+    pub fn open(test: &str) -> Self {
+        use midir::os::unix::VirtualInput;
+
+        let port_name = format!("midi-piano-rs loopback {test} {}", std::process::id());
+        let opened = Instant::now();
+        let received = Arc::new(Mutex::new(Vec::new()));
+        let input = midir::MidiInput::new("midi-piano-rs tests")
+            .unwrap_or_else(|err| panic!("{test}: no MIDI input available: {err}"));
+        let sink = received.clone();
+        let connection = input.create_virtual(
+            &port_name,
+            move |_, data, _| {
+                sink.lock().unwrap().push(Received {
+                    at: opened.elapsed(),
+                    data: data.to_vec(),
+                });
+            },
+            (),
+        );
+        let connection =
+            connection.unwrap_or_else(|err| panic!("{test}: no virtual MIDI ports: {err}"));
+        Self {
+            port_name,
+            opened,
+            received,
+            _connection: connection,
+        }
+    }
+
+    #[cfg(not(unix))]
+    pub fn open(test: &str) -> Self {
+        panic!("{test}: virtual MIDI ports are not supported here");
+    }
+
+    /// The name other software sees the port under.
+    pub fn port_name(&self) -> &str {
+        &self.port_name
+    }
+
+    /// Time since the port was opened, on the same clock as
+    /// [`Received::at`].
+    pub fn now(&self) -> Duration {
+        self.opened.elapsed()
+    }
+
+    pub fn received(&self) -> Vec<Received> {
+        self.received.lock().unwrap().clone()
+    }
+
+    /// Waits for at least `count` messages, giving up after `timeout`.
+    pub async fn wait_for(&self, count: usize, timeout: Duration) -> Vec<Received> {
+        let deadline = Instant::now() + timeout;
+        loop {
+            let received = self.received();
+            if received.len() >= count || Instant::now() >= deadline {
+                return received;
+            }
+            tokio::time::sleep(Duration::from_millis(5)).await;
+        }
+    }
+}
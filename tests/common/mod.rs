@@ -0,0 +1,243 @@
+#![allow(dead_code)]
+
+pub mod loopback;
+
+use std::sync::Mutex;
+
+use anyhow::{Result, anyhow};
+use async_trait::async_trait;
+use midi_piano_rs::midi::MidiSink;
+use midly::num::{u4, u7, u15, u24, u28};
+use midly::{Format, Header, MetaMessage, MidiMessage, Smf, Timing, TrackEvent, TrackEventKind};
+
+pub const PPQ: u16 = 480;
+
+/// Records every message it is sent.
+#[derive(Default)]
+pub struct MockSink {
+    sent: Mutex<Vec<Vec<u8>>>,
+}
+
+impl MockSink {
+    pub fn sent(&self) -> Vec<Vec<u8>> {
+        self.sent.lock().unwrap().clone()
+    }
+}
+
+#[async_trait]
+impl MidiSink for MockSink {
+    async fn send(&self, data: &[u8]) -> Result<()> {
+        self.sent.lock().unwrap().push(data.to_vec());
+        Ok(())
+    }
+}
+
+/// Rejects every message, like a device that was unplugged.
+pub struct FailingSink;
+
+#[async_trait]
+impl MidiSink for FailingSink {
+    async fn send(&self, _data: &[u8]) -> Result<()> {
+        Err(anyhow!("device disconnected"))
+    }
+}
+
+/// A note as `(start tick, length in ticks, channel, key)`.
+pub type Note = (u32, u32, u8, u8);
+
+/// Encodes a single-track SMF at 120 BPM (one tick is about a millisecond).
+pub fn smf_bytes(notes: &[Note]) -> Vec<u8> {
+    encode(Format::SingleTrack, vec![track(None, &[], notes)])
+}
+
+/// Like [`smf_bytes`], with tempo changes as `(tick, microseconds per
+/// quarter note)`.
+pub fn tempo_smf(tempos: &[(u32, u32)], notes: &[Note]) -> Vec<u8> {
+    encode(Format::SingleTrack, vec![track(None, tempos, notes)])
+}
+
+/// Encodes a format 1 SMF with one track per entry, each optionally named.
+pub fn named_tracks_smf(tracks: &[(Option<&'static str>, &[Note])]) -> Vec<u8> {
+    let tracks = tracks
+        .iter()
+        .map(|(name, notes)| track(*name, &[], notes))
+        .collect();
+    encode(Format::Parallel, tracks)
+}
+
+fn track(
+    name: Option<&'static str>,
+    tempos: &[(u32, u32)],
+    notes: &[Note],
+) -> Vec<TrackEvent<'static>> {
+    let mut timed: Vec<(u32, TrackEventKind<'static>)> = Vec::new();
+    if let Some(name) = name {
+        timed.push((
+            0,
+            TrackEventKind::Meta(MetaMessage::TrackName(name.as_bytes())),
+        ));
+    }
+    for &(tick, micros) in tempos {
+        timed.push((
+            tick,
+            TrackEventKind::Meta(MetaMessage::Tempo(u24::new(micros))),
+        ));
+    }
+    for &(start, length, channel, key) in notes {
+        let channel = u4::new(channel);
+        let key = u7::new(key);
+        timed.push((
+            start,
+            TrackEventKind::Midi {
+                channel,
+                message: MidiMessage::NoteOn {
+                    key,
+                    vel: u7::new(100),
+                },
+            },
+        ));
+        timed.push((
+            start + length,
+            TrackEventKind::Midi {
+                channel,
+                message: MidiMessage::NoteOff {
+                    key,
+                    vel: u7::new(0),
+                },
+            },
+        ));
+    }
+    timed.sort_by_key(|(tick, _)| *tick);
+
+    let mut track = Vec::new();
+    let mut last = 0;
+    for (tick, kind) in timed {
+        track.push(TrackEvent {
+            delta: u28::new(tick - last),
+            kind,
+        });
+        last = tick;
+    }
+    track.push(TrackEvent {
+        delta: u28::new(0),
+        kind: TrackEventKind::Meta(MetaMessage::EndOfTrack),
+    });
+
+    track
+}
+
+fn encode(format: Format, tracks: Vec<Vec<TrackEvent<'static>>>) -> Vec<u8> {
+    let smf = Smf {
+        header: Header::new(format, Timing::Metrical(u15::new(PPQ))),
+        tracks,
+    };
+    let mut bytes = Vec::new();
+    smf.write_std(&mut bytes)
+        .expect("failed to encode test MIDI");
+    bytes
+}
+
+/// Encodes a RIFF chunk, padding odd-sized bodies to an even length.
+pub fn riff_chunk(id: &[u8; 4], body: &[u8]) -> Vec<u8> {
+    let mut chunk = id.to_vec();
+    chunk.extend((body.len() as u32).to_le_bytes());
+    chunk.extend(body);
+    if body.len() % 2 == 1 {
+        chunk.push(0);
+    }
+    chunk
+}
+
+/// Encodes a list chunk such as `RIFF` or `LIST` holding `chunks`.
+pub fn riff_list(id: &[u8; 4], kind: &[u8; 4], chunks: &[Vec<u8>]) -> Vec<u8> {
+    let mut body = kind.to_vec();
+    for chunk in chunks {
+        body.extend(chunk);
+    }
+    riff_chunk(id, &body)
+}
+
+/// Sample frames in the bank from [`soundfont_bytes`].
+pub const SOUNDFONT_SAMPLES: u32 = 1000;
+
+/// A SoundFont 2 bank with one preset (bank 0, program 0) playing one
+/// looped square wave, rooted at A4, across the whole keyboard.
+pub fn soundfont_bytes() -> Vec<u8> {
+    fn record(name: &str, fields: &[&[u8]]) -> Vec<u8> {
+        let mut record = vec![0; 20];
+        record[..name.len()].copy_from_slice(name.as_bytes());
+        for field in fields {
+            record.extend(*field);
+        }
+        record
+    }
+    fn pairs(values: &[(u16, u16)]) -> Vec<u8> {
+        values
+            .iter()
+            .flat_map(|(a, b)| a.to_le_bytes().into_iter().chain(b.to_le_bytes()))
+            .collect()
+    }
+
+    let samples: Vec<u8> = (0..SOUNDFONT_SAMPLES + 46)
+        .flat_map(|index| {
+            let value: i16 = match index {
+                SOUNDFONT_SAMPLES.. => 0,
+                _ if index / 50 % 2 == 0 => 8000,
+                _ => -8000,
+            };
+            value.to_le_bytes()
+        })
+        .collect();
+
+    // Program 0 in bank 0, then the terminal record.
+    let mut phdr = record(
+        "Square",
+        &[
+            &0u16.to_le_bytes(),
+            &0u16.to_le_bytes(),
+            &0u16.to_le_bytes(),
+            &[0; 12],
+        ],
+    );
+    phdr.extend(record("EOP", &[&[0; 4], &1u16.to_le_bytes(), &[0; 12]]));
+    let mut inst = record("Square", &[&0u16.to_le_bytes()]);
+    inst.extend(record("EOI", &[&1u16.to_le_bytes()]));
+    let mut shdr = record(
+        "Square",
+        &[
+            &0u32.to_le_bytes(),
+            &SOUNDFONT_SAMPLES.to_le_bytes(),
+            &100u32.to_le_bytes(),
+            &900u32.to_le_bytes(),
+            &44_100u32.to_le_bytes(),
+            &[69, 0],
+            &[0; 4],
+        ],
+    );
+    shdr.extend(record("EOS", &[&[0; 26]]));
+
+    riff_list(
+        b"RIFF",
+        b"sfbk",
+        &[
+            riff_list(b"LIST", b"sdta", &[riff_chunk(b"smpl", &samples)]),
+            riff_list(
+                b"LIST",
+                b"pdta",
+                &[
+                    riff_chunk(b"phdr", &phdr),
+                    riff_chunk(b"pbag", &pairs(&[(0, 0), (1, 0)])),
+                    riff_chunk(b"pmod", &[0; 10]),
+                    // Instrument 0, then the terminal generator.
+                    riff_chunk(b"pgen", &pairs(&[(41, 0), (0, 0)])),
+                    riff_chunk(b"inst", &inst),
+                    riff_chunk(b"ibag", &pairs(&[(0, 0), (2, 0)])),
+                    riff_chunk(b"imod", &[0; 10]),
+                    // Looping sample 0, then the terminal generator.
+                    riff_chunk(b"igen", &pairs(&[(54, 1), (53, 0), (0, 0)])),
+                    riff_chunk(b"shdr", &shdr),
+                ],
+            ),
+        ],
+    )
+}
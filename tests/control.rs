@@ -0,0 +1,120 @@
+use std::net::SocketAddr;
+use std::time::Duration;
+
+use futures::{SinkExt, StreamExt};
+use midi_piano_rs::control::{
+    ControlCommand, ControlServer, ControlState, ControlStatus, seek_target,
+};
+use serde_json::Value;
+use tokio::sync::{mpsc, watch};
+use tokio_tungstenite::tungstenite::Message;
+
+const TOKEN: &str = "secret";
+
+async fn start_server() -> (
+    SocketAddr,
+    mpsc::UnboundedReceiver<ControlCommand>,
+    watch::Sender<ControlStatus>,
+) {
+    let server = ControlServer::bind("127.0.0.1:0".parse().unwrap(), TOKEN)
+        .await
+        .unwrap();
+    let addr = server.local_addr().unwrap();
+    let (commands, received) = mpsc::unbounded_channel();
+    let (status, status_rx) = watch::channel(ControlStatus::default());
+    tokio::spawn(server.run(commands, status_rx));
+    (addr, received, status)
+}
+
+async fn next_json<S>(socket: &mut S) -> Value
+where
+    S: StreamExt<Item = Result<Message, tokio_tungstenite::tungstenite::Error>> + Unpin,
+{
+    let message = socket.next().await.unwrap().unwrap();
+    serde_json::from_str(message.to_text().unwrap()).unwrap()
+}
+
+#[tokio::test]
+async fn rejects_clients_without_the_token() {
+    let (addr, _commands, _status) = start_server().await;
+
+    let wrong = tokio_tungstenite::connect_async(format!("ws://{addr}/?token=guess")).await;
+    let missing = tokio_tungstenite::connect_async(format!("ws://{addr}/")).await;
+
+    assert!(wrong.is_err());
+    assert!(missing.is_err());
+}
+
+#[tokio::test]
+async fn forwards_commands_and_pushes_status() {
+    let (addr, mut commands, status) = start_server().await;
+    let (mut socket, _) = tokio_tungstenite::connect_async(format!("ws://{addr}/?token={TOKEN}"))
+        .await
+        .unwrap();
+
+    assert_eq!(next_json(&mut socket).await["state"], "stopped");
+
+    socket
+        .send(Message::text(r#"{"command": "seek", "seconds": 12.5}"#))
+        .await
+        .unwrap();
+    assert_eq!(
+        commands.recv().await,
+        Some(ControlCommand::Seek { seconds: 12.5 })
+    );
+
+    socket
+        .send(Message::text(r#"{"command": "dance"}"#))
+        .await
+        .unwrap();
+    assert_eq!(next_json(&mut socket).await["type"], "error");
+
+    status.send_modify(|status| status.state = ControlState::Playing);
+    let pushed = next_json(&mut socket).await;
+    assert_eq!(pushed["type"], "status");
+    assert_eq!(pushed["state"], "playing");
+}
+
+#[tokio::test]
+async fn refuses_seeks_outside_the_song() {
+    let (addr, mut commands, _status) = start_server().await;
+    let (mut socket, _) = tokio_tungstenite::connect_async(format!("ws://{addr}/?token={TOKEN}"))
+        .await
+        .unwrap();
+    assert_eq!(next_json(&mut socket).await["type"], "status");
+
+    for seconds in ["-1", "1e400"] {
+        socket
+            .send(Message::text(format!(
+                r#"{{"command": "seek", "seconds": {seconds}}}"#
+            )))
+            .await
+            .unwrap();
+        assert_eq!(next_json(&mut socket).await["type"], "error", "{seconds}");
+    }
+
+    socket
+        .send(Message::text(r#"{"command": "seek", "seconds": 1e20}"#))
+        .await
+        .unwrap();
+    // Finite positions past the end are the app's to clamp.
+    assert_eq!(
+        commands.recv().await,
+        Some(ControlCommand::Seek { seconds: 1e20 })
+    );
+}
+
+#[test]
+fn seeks_land_inside_the_song() {
+    let total = Duration::from_secs(90);
+
+    assert_eq!(
+        seek_target(12.5, total),
+        Some(Duration::from_millis(12_500))
+    );
+    assert_eq!(seek_target(1e20, total), Some(total));
+    assert_eq!(seek_target(1e39, total), Some(total));
+    assert_eq!(seek_target(f64::INFINITY, total), None);
+    assert_eq!(seek_target(f64::NAN, total), None);
+    assert_eq!(seek_target(-1.0, total), None);
+}
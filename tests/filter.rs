@@ -0,0 +1,236 @@
+use std::sync::Arc;
+
+use midi_piano_rs::midi::filter::{
+    ChannelMap, DeviceReset, FilterAction, FilteredSink, OutputFilter, QuietLimit, VelocityCurve,
+};
+use midi_piano_rs::midi::{NullSink, SharedMidiSink};
+
+#[test]
+fn drops_and_clamps_selected_controllers() {
+    let filter = OutputFilter {
+        sustain: FilterAction::Drop,
+        soft_pedal: FilterAction::Clamp(40),
+        pitch_bend: FilterAction::Clamp(0),
+        ..OutputFilter::default()
+    };
+
+    assert_eq!(filter.apply(&[0xB0, 64, 127]), None);
+    assert_eq!(filter.apply(&[0xB2, 67, 100]), Some(vec![0xB2, 67, 40]));
+    assert_eq!(filter.apply(&[0xE0, 0x7F, 0x7F]), Some(vec![0xE0, 0, 64]));
+    assert_eq!(filter.apply(&[0xB0, 7, 100]), Some(vec![0xB0, 7, 100]));
+    assert_eq!(filter.apply(&[0x90, 60, 100]), Some(vec![0x90, 60, 100]));
+}
+
+#[test]
+fn reshapes_velocities_and_moves_channels() {
+    let mut channel_map = ChannelMap::default();
+    channel_map.0[1] = 0;
+    let filter = OutputFilter {
+        velocity_curve: VelocityCurve::Fixed(90),
+        channel_map,
+        ..OutputFilter::default()
+    };
+
+    assert_eq!(filter.apply(&[0x91, 60, 20]), Some(vec![0x90, 60, 90]));
+    // Note-offs written as silent note-ons stay silent.
+    assert_eq!(filter.apply(&[0x91, 60, 0]), Some(vec![0x90, 60, 0]));
+    assert_eq!(filter.apply(&[0xC1, 5]), Some(vec![0xC0, 5]));
+    assert_eq!(filter.apply(&[0x92, 60, 20]), Some(vec![0x92, 60, 90]));
+
+    assert!(VelocityCurve::Soft.apply(40) > 40);
+    assert!(VelocityCurve::Hard.apply(40) < 40);
+    assert_eq!(VelocityCurve::Hard.apply(127), 127);
+}
+
+#[tokio::test]
+async fn filtered_sink_only_forwards_what_passes() {
+    let target = Arc::new(NullSink::new());
+    let filter = OutputFilter {
+        bank_select: FilterAction::Drop,
+        ..OutputFilter::default()
+    };
+    let sink: SharedMidiSink = Arc::new(FilteredSink::new(target.clone(), filter));
+
+    sink.send_batch(&[vec![0xB0, 0, 1], vec![0xB0, 32, 2], vec![0xC0, 5]])
+        .await
+        .unwrap();
+
+    let sent: Vec<Vec<u8>> = target
+        .sent()
+        .into_iter()
+        .map(|message| message.data)
+        .collect();
+    assert_eq!(sent, vec![vec![0xC0, 5]]);
+}
+
+#[tokio::test(start_paused = true)]
+async fn sysex_is_dropped_and_the_reset_goes_out_first() {
+    let target = Arc::new(NullSink::new());
+    let filter = OutputFilter {
+        drop_sysex: true,
+        reset: Some(DeviceReset::Gm),
+        ..OutputFilter::default()
+    };
+    let sink: SharedMidiSink = Arc::new(FilteredSink::new(target.clone(), filter));
+
+    sink.send_batch(&[
+        vec![
+            0xF0, 0x41, 0x10, 0x42, 0x12, 0x40, 0x00, 0x7F, 0x00, 0x41, 0xF7,
+        ],
+        vec![0xF7, 0x01, 0x02],
+        vec![0xC0, 5],
+    ])
+    .await
+    .unwrap();
+    sink.send(&[0x90, 60, 100]).await.unwrap();
+
+    let sent: Vec<Vec<u8>> = target
+        .sent()
+        .into_iter()
+        .map(|message| message.data)
+        .collect();
+    assert_eq!(
+        sent,
+        vec![
+            DeviceReset::Gm.message().to_vec(),
+            vec![0xC0, 5],
+            vec![0x90, 60, 100],
+        ]
+    );
+}
+
+#[test]
+fn scales_pitch_bends_and_controllers() {
+    let filter = OutputFilter {
+        pitch_bend: FilterAction::Scale(50),
+        sustain: FilterAction::Scale(50),
+        ..OutputFilter::default()
+    };
+
+    // Full bend up, full bend down and centre.
+    assert_eq!(
+        filter.apply(&[0xE0, 0x7F, 0x7F]),
+        Some(vec![0xE0, 0x7F, 0x5F])
+    );
+    assert_eq!(
+        filter.apply(&[0xE0, 0x00, 0x00]),
+        Some(vec![0xE0, 0x00, 0x20])
+    );
+    assert_eq!(
+        filter.apply(&[0xE0, 0x00, 0x40]),
+        Some(vec![0xE0, 0x00, 0x40])
+    );
+    assert_eq!(filter.apply(&[0xB0, 64, 127]), Some(vec![0xB0, 64, 63]));
+}
+
+#[tokio::test(start_paused = true)]
+async fn bend_range_is_set_on_every_channel_after_the_reset() {
+    let target = Arc::new(NullSink::new());
+    let filter = OutputFilter {
+        reset: Some(DeviceReset::Gm),
+        bend_range: Some(12),
+        ..OutputFilter::default()
+    };
+    let sink: SharedMidiSink = Arc::new(FilteredSink::new(target.clone(), filter));
+
+    sink.send(&[0xE3, 0, 0x50]).await.unwrap();
+
+    let sent: Vec<Vec<u8>> = target
+        .sent()
+        .into_iter()
+        .map(|message| message.data)
+        .collect();
+    assert_eq!(sent.len(), 1 + 16 * 6 + 1);
+    assert_eq!(sent[0], DeviceReset::Gm.message().to_vec());
+    assert_eq!(
+        sent[1..7],
+        [
+            vec![0xB0, 101, 0],
+            vec![0xB0, 100, 0],
+            vec![0xB0, 6, 12],
+            vec![0xB0, 38, 0],
+            vec![0xB0, 101, 127],
+            vec![0xB0, 100, 127],
+        ]
+    );
+    assert_eq!(sent[96], vec![0xBF, 100, 127]);
+    assert_eq!(sent.last(), Some(&vec![0xE3, 0, 0x50]));
+}
+
+#[tokio::test]
+async fn quiet_mode_caps_velocity_and_volume() {
+    let target = Arc::new(NullSink::new());
+    let filter = OutputFilter {
+        velocity_curve: VelocityCurve::Soft,
+        quiet: Some(QuietLimit {
+            max_velocity: 50,
+            max_volume: Some(70),
+        }),
+        ..OutputFilter::default()
+    };
+    assert_eq!(filter.apply(&[0x90, 60, 120]), Some(vec![0x90, 60, 50]));
+    assert_eq!(filter.apply(&[0x90, 60, 0]), Some(vec![0x90, 60, 0]));
+    assert_eq!(filter.apply(&[0xB2, 7, 127]), Some(vec![0xB2, 7, 70]));
+    assert_eq!(filter.apply(&[0xB2, 7, 40]), Some(vec![0xB2, 7, 40]));
+
+    let sink: SharedMidiSink = Arc::new(FilteredSink::new(target.clone(), filter));
+    sink.send(&[0x90, 60, 100]).await.unwrap();
+
+    let sent: Vec<Vec<u8>> = target
+        .sent()
+        .into_iter()
+        .map(|message| message.data)
+        .collect();
+    assert_eq!(sent.len(), 16 + 1);
+    assert_eq!(sent[0], vec![0xB0, 7, 70]);
+    assert_eq!(sent[15], vec![0xBF, 7, 70]);
+    assert_eq!(sent[16], vec![0x90, 60, 50]);
+}
+
+#[test]
+fn piano_mode_plays_everything_as_piano_on_channel_one() {
+    let filter = OutputFilter::default().piano_mode();
+
+    assert_eq!(filter.apply(&[0x93, 60, 100]), Some(vec![0x90, 60, 100]));
+    assert_eq!(filter.apply(&[0x99, 36, 100]), None);
+    assert_eq!(filter.apply(&[0xB9, 7, 100]), None);
+    assert_eq!(filter.apply(&[0xC4, 40]), Some(vec![0xC0, 0]));
+    assert_eq!(filter.apply(&[0xB2, 0, 8]), None);
+    // A full bend up stays within a quarter of the range.
+    assert_eq!(filter.apply(&[0xE5, 0x7F, 0x7F]), Some(vec![0xE0, 15, 80]));
+}
+
+#[test]
+fn piano_mode_keeps_the_rest_of_a_devices_rules() {
+    let filter = OutputFilter {
+        sustain: FilterAction::Drop,
+        pitch_bend: FilterAction::Drop,
+        velocity_curve: VelocityCurve::Fixed(90),
+        ..OutputFilter::default()
+    }
+    .piano_mode();
+
+    assert_eq!(filter.apply(&[0xB1, 64, 127]), None);
+    assert_eq!(filter.apply(&[0xE1, 0, 0x7F]), None);
+    assert_eq!(filter.apply(&[0x91, 60, 20]), Some(vec![0x90, 60, 90]));
+}
+
+#[tokio::test]
+async fn piano_mode_selects_the_piano_before_the_song_starts() {
+    let target = Arc::new(NullSink::new());
+    let sink: SharedMidiSink = Arc::new(FilteredSink::new(
+        target.clone(),
+        OutputFilter::default().piano_mode(),
+    ));
+
+    sink.send(&[0x91, 60, 100]).await.unwrap();
+
+    let sent: Vec<Vec<u8>> = target
+        .sent()
+        .into_iter()
+        .map(|message| message.data)
+        .collect();
+    let mut expected: Vec<Vec<u8>> = (0..16u8).map(|channel| vec![0xC0 | channel, 0]).collect();
+    expected.push(vec![0x90, 60, 100]);
+    assert_eq!(sent, expected);
+}
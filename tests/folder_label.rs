@@ -0,0 +1,27 @@
+use std::collections::HashMap;
+
+use midi_piano_rs::midi::folder_label::{self, FolderColor, FolderLabel};
+
+fn label(color: FolderColor, emoji: &str) -> FolderLabel {
+    FolderLabel {
+        color: Some(color),
+        emoji: emoji.into(),
+    }
+}
+
+#[test]
+fn merging_adds_labels_for_unlabelled_folders_only() {
+    let mut labels = HashMap::from([("Bach".to_owned(), label(FolderColor::Blue, "🎹"))]);
+
+    folder_label::merge(
+        &mut labels,
+        HashMap::from([
+            ("Bach".to_owned(), label(FolderColor::Red, "")),
+            ("Grade 3".to_owned(), label(FolderColor::Green, "3")),
+        ]),
+    );
+
+    assert_eq!(labels.len(), 2);
+    assert_eq!(labels["Bach"], label(FolderColor::Blue, "🎹"));
+    assert_eq!(labels["Grade 3"], label(FolderColor::Green, "3"));
+}
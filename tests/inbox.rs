@@ -0,0 +1,126 @@
+mod common;
+
+use std::fs;
+use std::path::PathBuf;
+
+use common::smf_bytes;
+use midi_piano_rs::midi::inbox::{InboxGrouping, WatchFolderConfig, import_inbox};
+use midly::num::{u4, u7, u15, u28};
+use midly::{
+    Format, Fps, Header, MetaMessage, MidiMessage, Smf, Timing, TrackEvent, TrackEventKind,
+};
+
+fn scratch_config(name: &str) -> WatchFolderConfig {
+    let dir = std::env::temp_dir().join(format!("midi-piano-inbox-{name}-{}", std::process::id()));
+    let _ = fs::remove_dir_all(&dir);
+    fs::create_dir_all(dir.join("inbox")).unwrap();
+    WatchFolderConfig {
+        inbox: dir.join("inbox"),
+        library_root: dir.join("library"),
+        grouping: InboxGrouping::Composer,
+    }
+}
+
+fn encode(format: Format, timing: Timing) -> Vec<u8> {
+    let note = TrackEvent {
+        delta: u28::new(0),
+        kind: TrackEventKind::Midi {
+            channel: u4::new(0),
+            message: MidiMessage::NoteOn {
+                key: u7::new(60),
+                vel: u7::new(100),
+            },
+        },
+    };
+    let end = TrackEvent {
+        delta: u28::new(100),
+        kind: TrackEventKind::Meta(MetaMessage::EndOfTrack),
+    };
+    let smf = Smf {
+        header: Header::new(format, timing),
+        tracks: vec![vec![note, end]; 2],
+    };
+    let mut bytes = Vec::new();
+    smf.write_std(&mut bytes).unwrap();
+    bytes
+}
+
+fn imported_folders(config: &WatchFolderConfig) -> Vec<PathBuf> {
+    let report = import_inbox(config).unwrap();
+    report
+        .imported
+        .iter()
+        .map(|import| {
+            let folder = import.path.parent().unwrap();
+            assert!(folder.starts_with(&config.library_root), "{folder:?}");
+            folder
+                .strip_prefix(&config.library_root)
+                .unwrap()
+                .to_owned()
+        })
+        .collect()
+}
+
+#[test]
+fn accepts_every_format_and_timing_and_sets_aside_the_rest() {
+    let config = scratch_config("formats");
+    fs::write(
+        config.inbox.join("Round - Tallis.mid"),
+        smf_bytes(&[(0, 100, 0, 60)]),
+    )
+    .unwrap();
+    fs::write(
+        config.inbox.join("Patterns - Reich.mid"),
+        encode(Format::Sequential, Timing::Metrical(u15::new(480))),
+    )
+    .unwrap();
+    fs::write(
+        config.inbox.join("Cue - Glass.mid"),
+        encode(Format::Parallel, Timing::Timecode(Fps::Fps25, 40)),
+    )
+    .unwrap();
+    fs::write(config.inbox.join("Broken - Nobody.mid"), b"MThd garbage").unwrap();
+
+    let report = import_inbox(&config).unwrap();
+
+    let mut titles: Vec<_> = report
+        .imported
+        .iter()
+        .map(|import| {
+            import
+                .path
+                .file_stem()
+                .unwrap()
+                .to_string_lossy()
+                .into_owned()
+        })
+        .collect();
+    titles.sort();
+    assert_eq!(
+        titles,
+        ["Cue - Glass", "Patterns - Reich", "Round - Tallis"]
+    );
+    assert_eq!(report.rejected, [config.inbox.join("Broken - Nobody.mid")]);
+    assert!(config.inbox.join("_rejected/Broken - Nobody.mid").is_file());
+}
+
+#[test]
+fn composer_folders_stay_inside_the_library_root() {
+    let config = scratch_config("composers");
+    let song = smf_bytes(&[(0, 100, 0, 60)]);
+    fs::write(config.inbox.join("Escape - ...mid"), &song).unwrap();
+    fs::write(config.inbox.join("Drive - C:x.mid"), &song).unwrap();
+    fs::write(config.inbox.join("Climb - ..\\..\\up.mid"), &song).unwrap();
+
+    let mut folders = imported_folders(&config);
+    folders.sort();
+
+    assert_eq!(
+        folders,
+        [
+            PathBuf::from(".. .. up"),
+            PathBuf::from("C x"),
+            PathBuf::from("Unknown"),
+        ]
+    );
+}
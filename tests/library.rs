@@ -0,0 +1,303 @@
+use std::fs;
+use std::ops::ControlFlow;
+use std::path::PathBuf;
+
+use std::collections::HashMap;
+
+use midi_piano_rs::midi::{
+    AssetProgress, MidiLibrary, SearchQuery, SongDetails, file_details, regenerate_manifest,
+};
+use midly::num::{u15, u28};
+use midly::{Format, Header, MetaMessage, Smf, Timing, TrackEvent, TrackEventKind};
+
+fn scratch_dir(name: &str) -> PathBuf {
+    let dir = std::env::temp_dir().join(format!("midi-piano-{name}-{}", std::process::id()));
+    let _ = fs::remove_dir_all(&dir);
+    fs::create_dir_all(dir.join("midi/Songs")).unwrap();
+    dir
+}
+
+fn read_manifest(path: &std::path::Path) -> Vec<String> {
+    serde_json::from_str(&fs::read_to_string(path).unwrap()).unwrap()
+}
+
+#[test]
+fn regenerating_keeps_order_and_reports_changes() {
+    let dir = scratch_dir("manifest");
+    let assets = dir.join("midi");
+    let manifest = dir.join("manifest.json");
+    for file in ["Songs/b.mid", "Songs/a.mid", "c.midi", "notes.txt"] {
+        fs::write(assets.join(file), b"").unwrap();
+    }
+    fs::write(&manifest, r#"["Songs/b.mid", "gone.mid", "Songs/a.mid"]"#).unwrap();
+
+    let changes = regenerate_manifest(&assets, &manifest).unwrap();
+
+    assert_eq!(changes.added, vec!["c.midi".to_string()]);
+    assert_eq!(changes.removed, vec!["gone.mid".to_string()]);
+    assert_eq!(
+        read_manifest(&manifest),
+        vec!["Songs/b.mid", "Songs/a.mid", "c.midi"]
+    );
+
+    let library = MidiLibrary::load_from(&assets, &manifest).unwrap();
+    let names: Vec<_> = library.entries().iter().map(|entry| &entry.name).collect();
+    assert_eq!(names, vec!["b", "a", "c"]);
+    assert_eq!(
+        library.entries()[0].library_path,
+        Some(vec!["Songs".to_string()])
+    );
+
+    assert!(regenerate_manifest(&assets, &manifest).unwrap().is_empty());
+    let _ = fs::remove_dir_all(&dir);
+}
+
+#[cfg(feature = "embed-assets")]
+#[test]
+fn embedded_assets_load_outside_the_repository() {
+    let dir = scratch_dir("embedded");
+    std::env::set_current_dir(&dir).unwrap();
+
+    let library = MidiLibrary::load_with_assets().unwrap();
+    let entry = library
+        .entries()
+        .first()
+        .expect("bundled assets are embedded");
+    assert!(!entry.path.exists());
+    let contents = midi_piano_rs::midi::read_midi_file(&entry.path).unwrap();
+    assert!(contents.starts_with(b"MThd"));
+    let _ = fs::remove_dir_all(&dir);
+}
+
+#[test]
+fn removing_entries_keeps_the_rest_addressable() {
+    let dir = scratch_dir("remove");
+    let files: Vec<_> = ["a.mid", "b.mid", "c.mid"]
+        .iter()
+        .map(|name| dir.join(name))
+        .collect();
+    for file in &files {
+        fs::write(file, b"").unwrap();
+    }
+    let mut library = MidiLibrary::default();
+    let ids: Vec<_> = files
+        .iter()
+        .map(|file| library.add_local_file(file).unwrap().id)
+        .collect();
+
+    assert_eq!(
+        library.remove(&ids[0]).map(|entry| entry.name),
+        Some("a".into())
+    );
+    assert!(library.remove(&ids[0]).is_none());
+    assert_eq!(
+        library.get(&ids[2]).map(|entry| entry.name.as_str()),
+        Some("c")
+    );
+
+    fs::remove_file(&files[1]).unwrap();
+    let missing = library.remove_missing_local();
+    assert_eq!(missing.len(), 1);
+    assert_eq!(missing[0].id, ids[1]);
+    assert!(library.get(&ids[1]).is_none());
+    assert_eq!(
+        library.get(&ids[2]).map(|entry| entry.name.as_str()),
+        Some("c")
+    );
+    assert_eq!(library.add_local_file(&files[2]).unwrap().id, ids[2]);
+    let _ = fs::remove_dir_all(&dir);
+}
+
+#[test]
+fn assets_load_in_chunks_and_can_stop_early() {
+    let dir = scratch_dir("chunks");
+    let assets = dir.join("midi");
+    let manifest = dir.join("manifest.json");
+    for file in ["a.mid", "b.mid", "c.mid", "d.mid"] {
+        fs::write(assets.join(file), b"").unwrap();
+    }
+    fs::write(
+        &manifest,
+        r#"["a.mid", "gone.mid", "b.mid", "c.mid", "d.mid"]"#,
+    )
+    .unwrap();
+
+    let mut chunks = Vec::new();
+    MidiLibrary::load_from_in_chunks(&assets, &manifest, 2, |chunk, progress| {
+        let names: Vec<_> = chunk
+            .entries()
+            .iter()
+            .map(|entry| entry.name.clone())
+            .collect();
+        chunks.push((names, progress));
+        ControlFlow::Continue(())
+    })
+    .unwrap();
+    let progress = |verified| AssetProgress { verified, total: 5 };
+    assert_eq!(
+        chunks,
+        vec![
+            (vec!["a".to_string()], progress(2)),
+            (vec!["b".to_string(), "c".to_string()], progress(4)),
+            (vec!["d".to_string()], progress(5)),
+        ]
+    );
+
+    let mut seen = 0;
+    MidiLibrary::load_from_in_chunks(&assets, &manifest, 2, |_, _| {
+        seen += 1;
+        ControlFlow::Break(())
+    })
+    .unwrap();
+    assert_eq!(seen, 1);
+
+    let mut library = MidiLibrary::default();
+    library.append(MidiLibrary::load_from(&assets, &manifest).unwrap());
+    library.append(MidiLibrary::load_from(&assets, &manifest).unwrap());
+    assert_eq!(library.entries().len(), 4);
+    let _ = fs::remove_dir_all(&dir);
+}
+
+#[test]
+fn searches_ignore_case_and_accents_and_follow_removals() {
+    let dir = scratch_dir("search");
+    let files: Vec<_> = ["Étude No 3.mid", "Nocturne.mid", "ｅｔｕｄｅ 9.mid"]
+        .iter()
+        .map(|name| dir.join(name))
+        .collect();
+    for file in &files {
+        fs::write(file, b"").unwrap();
+    }
+    let mut library = MidiLibrary::default();
+    let ids: Vec<_> = files
+        .iter()
+        .map(|file| library.add_local_file(file).unwrap().id)
+        .collect();
+
+    let query = SearchQuery::new("  ETUDE ");
+    let found: Vec<_> = library.search(&query).map(|entry| entry.id).collect();
+    assert_eq!(found, vec![ids[0], ids[2]]);
+    assert!(!library.matches(&ids[1], &query));
+    assert!(library.matches(&ids[1], &SearchQuery::new("")));
+
+    let before = library.revision();
+    library.remove(&ids[0]);
+    assert_ne!(library.revision(), before);
+    assert!(!library.matches(&ids[0], &query));
+    assert_eq!(library.search(&query).count(), 1);
+    let _ = fs::remove_dir_all(&dir);
+}
+
+#[test]
+fn titles_replace_file_names_and_are_searchable() {
+    let dir = scratch_dir("details");
+    let file = dir.join("chpn_op9_2_format0.mid");
+    fs::write(&file, b"").unwrap();
+    let mut library = MidiLibrary::default();
+    let id = library.add_local_file(&file).unwrap().id;
+
+    library.set_details(
+        id,
+        SongDetails {
+            title: Some("  Nocturne Op. 9 No. 2 ".into()),
+            composer: Some("Frédéric Chopin".into()),
+        },
+    );
+
+    let entry = library.get(&id).unwrap();
+    assert_eq!(entry.name, "Nocturne Op. 9 No. 2");
+    assert_eq!(entry.file_name, "chpn_op9_2_format0");
+    for query in ["nocturne", "chpn_op9", "frederic"] {
+        assert!(library.matches(&id, &SearchQuery::new(query)), "{query}");
+    }
+
+    library.set_details(id, SongDetails::default());
+    assert_eq!(library.get(&id).unwrap().name, "chpn_op9_2_format0");
+    assert!(library.get(&id).unwrap().composer.is_none());
+    assert!(!library.matches(&id, &SearchQuery::new("chopin")));
+    let _ = fs::remove_dir_all(&dir);
+}
+
+#[test]
+fn details_wait_for_entries_added_later() {
+    let dir = scratch_dir("details-later");
+    let file = dir.join("song.mid");
+    fs::write(&file, b"").unwrap();
+    let mut scratch = MidiLibrary::default();
+    let id = scratch.add_local_file(&file).unwrap().id;
+
+    let mut library = MidiLibrary::default();
+    library.set_all_details(HashMap::from([(
+        id,
+        SongDetails {
+            title: Some("Gymnopédie No. 1".into()),
+            composer: None,
+        },
+    )]));
+    library.append(scratch);
+
+    assert_eq!(library.get(&id).unwrap().name, "Gymnopédie No. 1");
+    assert!(library.matches(&id, &SearchQuery::new("gymnopedie")));
+    let _ = fs::remove_dir_all(&dir);
+}
+
+#[test]
+fn details_come_from_the_first_tracks_name_and_copyright() {
+    let meta = |message| TrackEvent {
+        delta: u28::new(0),
+        kind: TrackEventKind::Meta(message),
+    };
+    let smf = Smf {
+        header: Header::new(Format::Parallel, Timing::Metrical(u15::new(480))),
+        tracks: vec![
+            vec![
+                meta(MetaMessage::Copyright(
+                    b"Copyright \xc2\xa9 2004 by Bernd Krueger",
+                )),
+                meta(MetaMessage::TrackName(b" Nocturne in E flat ")),
+                meta(MetaMessage::EndOfTrack),
+            ],
+            vec![
+                meta(MetaMessage::TrackName(b"Piano right")),
+                meta(MetaMessage::EndOfTrack),
+            ],
+        ],
+    };
+    let mut bytes = Vec::new();
+    smf.write_std(&mut bytes).unwrap();
+
+    assert_eq!(
+        file_details(&bytes).unwrap(),
+        SongDetails {
+            title: Some("Nocturne in E flat".into()),
+            composer: Some("Bernd Krueger".into()),
+        }
+    );
+}
+
+#[test]
+fn entry_ids_stay_the_same_across_loads() {
+    let dir = scratch_dir("stable-ids");
+    let assets = dir.join("midi");
+    let manifest = dir.join("manifest.json");
+    fs::write(assets.join("Songs/a.mid"), b"").unwrap();
+    fs::write(&manifest, r#"["Songs/a.mid"]"#).unwrap();
+    let local = dir.join("local.mid");
+    fs::write(&local, b"").unwrap();
+
+    let first = MidiLibrary::load_from(&assets, &manifest).unwrap();
+    let second = MidiLibrary::load_from(&assets, &manifest).unwrap();
+    assert_eq!(first.entries()[0].id, second.entries()[0].id);
+    // Saved preferences depend on this exact value.
+    assert_eq!(
+        first.entries()[0].id.to_string(),
+        "936cec7b-2c61-5506-8c2b-9982dcad985f"
+    );
+
+    let local_id = MidiLibrary::default().add_local_file(&local).unwrap().id;
+    assert_eq!(
+        MidiLibrary::default().add_local_file(&local).unwrap().id,
+        local_id
+    );
+    let _ = fs::remove_dir_all(&dir);
+}
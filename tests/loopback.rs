@@ -0,0 +1,106 @@
+//! Plays through the real player, device pipeline and system MIDI layer into
+//! a virtual input. Virtual ports need an ALSA sequencer or CoreMIDI, which
+//! containers and Windows lack, so these tests are ignored by default. Run
+//! them with `cargo test --test loopback -- --ignored`; they fail if the port
+//! can't be created.
+
+mod common;
+
+use std::sync::Arc;
+use std::time::Duration;
+
+use common::loopback::{Loopback, Received};
+use midi_piano_rs::devices::MidiDeviceManager;
+use midi_piano_rs::midi::{MidiPlayer, MidiSequence, PlayerEvent};
+use tokio::sync::mpsc;
+
+/// How far a message may arrive from when it was due, allowing for a busy
+/// CI machine.
+const TOLERANCE: Duration = Duration::from_millis(40);
+
+/// Plays four notes about 100ms apart to `loopback` and returns the song
+/// with what arrived.
+async fn play_through(loopback: &Loopback, running_status: bool) -> (MidiSequence, Vec<Received>) {
+    let mut manager = MidiDeviceManager::new();
+    manager.set_usb_running_status(running_status);
+    let descriptor = manager
+        .refresh_usb()
+        .unwrap()
+        .into_iter()
+        .find(|descriptor| descriptor.info.name.contains(loopback.port_name()))
+        .expect("the loopback port is listed as an output");
+    let sink = manager
+        .connector(&descriptor.info.id)
+        .unwrap()
+        .connect(Duration::from_secs(5))
+        .await
+        .unwrap();
+
+    let bytes = common::smf_bytes(&[
+        (0, 90, 0, 60),
+        (96, 90, 0, 64),
+        (192, 90, 0, 67),
+        (288, 90, 1, 72),
+    ]);
+    let sequence = MidiSequence::from_bytes(&bytes).unwrap();
+    let (sender, mut events) = mpsc::unbounded_channel();
+    let mut player = MidiPlayer::new(sender);
+    player
+        .start_playback(Arc::new(sequence.clone()), sink, None)
+        .unwrap();
+    while let Some(event) = events.recv().await {
+        match event {
+            PlayerEvent::Finished => break,
+            PlayerEvent::Error(err) => panic!("playback failed: {err}"),
+            _ => {}
+        }
+    }
+    let received = loopback
+        .wait_for(sequence.events.len(), Duration::from_secs(2))
+        .await;
+    manager.disconnect_all().await;
+    (sequence, received)
+}
+
+fn assert_arrived_in_order_and_on_time(sequence: &MidiSequence, received: &[Received]) {
+    let sent: Vec<&[u8]> = sequence
+        .events
+        .iter()
+        .map(|event| event.data.as_slice())
+        .collect();
+    let arrived: Vec<&[u8]> = received
+        .iter()
+        .map(|message| message.data.as_slice())
+        .collect();
+    assert_eq!(arrived, sent);
+
+    let first = received[0].at;
+    for (event, message) in sequence.events.iter().zip(received) {
+        let offset = message.at - first;
+        let drift = offset.abs_diff(event.at);
+        assert!(
+            drift <= TOLERANCE,
+            "{:02X?} arrived {offset:?} in, due at {:?}",
+            message.data,
+            event.at
+        );
+    }
+}
+
+#[tokio::test(flavor = "multi_thread")]
+#[ignore = "needs virtual MIDI ports"]
+async fn played_songs_arrive_in_order_and_on_time() {
+    let loopback = Loopback::open("played_songs_arrive_in_order_and_on_time");
+    let started = loopback.now();
+    let (sequence, received) = play_through(&loopback, false).await;
+    assert!(received.iter().all(|message| message.at >= started));
+    assert_arrived_in_order_and_on_time(&sequence, &received);
+}
+
+#[tokio::test(flavor = "multi_thread")]
+#[ignore = "needs virtual MIDI ports"]
+async fn running_status_arrives_as_complete_messages() {
+    let loopback = Loopback::open("running_status_arrives_as_complete_messages");
+    let (sequence, received) = play_through(&loopback, true).await;
+    assert_arrived_in_order_and_on_time(&sequence, &received);
+}
@@ -0,0 +1,43 @@
+use std::sync::Arc;
+
+use midi_piano_rs::midi::monitor::{MessageKind, MidiMonitor, MonitoredSink, describe_message};
+use midi_piano_rs::midi::{NullSink, SharedMidiSink};
+
+#[test]
+fn decodes_common_messages() {
+    assert_eq!(
+        describe_message(&[0x90, 60, 100]),
+        "Note On  C4 (60) vel 100"
+    );
+    assert_eq!(describe_message(&[0x91, 61, 0]), "Note Off C#4 (61)");
+    assert_eq!(
+        describe_message(&[0xB0, 64, 127]),
+        "CC 64 Sustain Pedal = 127"
+    );
+    assert_eq!(describe_message(&[0xB0, 3, 9]), "CC 3 = 9");
+    assert_eq!(describe_message(&[0xC0, 40]), "Program 41 Violin");
+    assert_eq!(describe_message(&[0xE0, 0, 64]), "Pitch Bend +0");
+    assert_eq!(describe_message(&[0xF0, 1, 2, 0xF7]), "SysEx (4 bytes)");
+    assert_eq!(MessageKind::of(&[0xD3, 10]), MessageKind::Aftertouch);
+}
+
+#[tokio::test]
+async fn monitored_sink_records_and_forwards() {
+    let monitor = Arc::new(MidiMonitor::new(2));
+    let target = Arc::new(NullSink::new());
+    let sink: SharedMidiSink = Arc::new(MonitoredSink::new(target.clone(), monitor.clone()));
+
+    sink.send_batch(&[vec![0x90, 60, 100], vec![0x80, 60, 0]])
+        .await
+        .unwrap();
+    sink.send(&[0xC1, 0]).await.unwrap();
+
+    let recorded: Vec<Vec<u8>> = monitor
+        .entries()
+        .into_iter()
+        .map(|entry| entry.data)
+        .collect();
+    assert_eq!(recorded, vec![vec![0x80, 60, 0], vec![0xC1, 0]]);
+    assert_eq!(monitor.entries()[1].channel(), Some(1));
+    assert_eq!(target.sent().len(), 3);
+}
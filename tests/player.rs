@@ -0,0 +1,591 @@
+mod common;
+
+use std::sync::Arc;
+use std::time::Duration;
+
+use common::{FailingSink, MockSink, smf_bytes, tempo_smf};
+use midi_piano_rs::midi::{
+    MidiPlayer, MidiSequence, PlayerEvent, SharedMidiSink, panic_messages, play_preview,
+};
+use tokio::sync::Notify;
+use tokio::sync::mpsc::{self, UnboundedReceiver};
+use tokio::time::timeout;
+
+const WAIT: Duration = Duration::from_secs(5);
+
+async fn wait_for(
+    events: &mut UnboundedReceiver<PlayerEvent>,
+    matches: impl Fn(&PlayerEvent) -> bool,
+) -> Vec<PlayerEvent> {
+    let mut seen = Vec::new();
+    loop {
+        let event = timeout(WAIT, events.recv())
+            .await
+            .expect("timed out waiting for player event")
+            .expect("player event channel closed");
+        let done = matches(&event);
+        seen.push(event);
+        if done {
+            return seen;
+        }
+    }
+}
+
+fn sequence(notes: &[common::Note]) -> Arc<MidiSequence> {
+    Arc::new(MidiSequence::from_bytes(&smf_bytes(notes)).unwrap())
+}
+
+#[tokio::test]
+async fn plays_every_event_in_order() {
+    let (tx, mut events) = mpsc::unbounded_channel();
+    let mut player = MidiPlayer::new(tx);
+    let sink = Arc::new(MockSink::default());
+
+    player
+        .start_playback(
+            sequence(&[(0, 50, 0, 60), (100, 50, 0, 64)]),
+            sink.clone() as SharedMidiSink,
+            None,
+        )
+        .unwrap();
+    let seen = wait_for(&mut events, |event| {
+        matches!(event, PlayerEvent::Finished | PlayerEvent::Error(_))
+    })
+    .await;
+
+    assert!(matches!(seen.first(), Some(PlayerEvent::Started { .. })));
+    assert!(matches!(seen.last(), Some(PlayerEvent::Finished)));
+    assert_eq!(
+        sink.sent(),
+        vec![
+            vec![0x90, 60, 100],
+            vec![0x80, 60, 0],
+            vec![0x90, 64, 100],
+            vec![0x80, 64, 0],
+        ]
+    );
+}
+
+#[tokio::test]
+async fn stopping_releases_sounding_notes() {
+    let (tx, mut events) = mpsc::unbounded_channel();
+    let mut player = MidiPlayer::new(tx);
+    let sink = Arc::new(MockSink::default());
+
+    player
+        .start_playback(
+            sequence(&[(0, 10_000, 2, 72)]),
+            sink.clone() as SharedMidiSink,
+            None,
+        )
+        .unwrap();
+    wait_for(&mut events, |event| {
+        matches!(event, PlayerEvent::Started { .. })
+    })
+    .await;
+    while sink.sent().is_empty() {
+        tokio::time::sleep(Duration::from_millis(5)).await;
+    }
+    player.stop();
+    wait_for(&mut events, |event| matches!(event, PlayerEvent::Stopped)).await;
+
+    timeout(WAIT, async {
+        while !sink.sent().contains(&vec![0x82, 72, 0]) {
+            tokio::time::sleep(Duration::from_millis(5)).await;
+        }
+    })
+    .await
+    .expect("note was never released");
+    let sent = sink.sent();
+    assert_eq!(sent[0], vec![0x90 | 2, 72, 100]);
+    for channel in 0u8..16 {
+        assert!(sent.contains(&vec![0xB0 | channel, 123, 0]));
+    }
+}
+
+#[tokio::test]
+async fn sink_errors_are_reported() {
+    let (tx, mut events) = mpsc::unbounded_channel();
+    let mut player = MidiPlayer::new(tx);
+
+    player
+        .start_playback(
+            sequence(&[(0, 50, 0, 60)]),
+            Arc::new(FailingSink) as SharedMidiSink,
+            None,
+        )
+        .unwrap();
+    let seen = wait_for(&mut events, |event| {
+        matches!(event, PlayerEvent::Finished | PlayerEvent::Error(_))
+    })
+    .await;
+
+    match seen.last() {
+        Some(PlayerEvent::Error(message)) => assert!(message.contains("device disconnected")),
+        other => panic!("expected an error event, got {other:?}"),
+    }
+}
+
+#[tokio::test]
+async fn empty_sequences_are_rejected() {
+    let (tx, _events) = mpsc::unbounded_channel();
+    let mut player = MidiPlayer::new(tx);
+
+    let result = player.start_playback(
+        sequence(&[]),
+        Arc::new(MockSink::default()) as SharedMidiSink,
+        None,
+    );
+    assert!(result.is_err());
+}
+
+#[tokio::test]
+async fn seeking_jumps_ahead_without_stopping() {
+    let (tx, mut events) = mpsc::unbounded_channel();
+    let mut player = MidiPlayer::new(tx);
+    let sink = Arc::new(MockSink::default());
+
+    player
+        .start_playback(
+            sequence(&[(0, 4_000, 1, 60), (3_000, 50, 1, 64)]),
+            sink.clone() as SharedMidiSink,
+            None,
+        )
+        .unwrap();
+    wait_for(&mut events, |event| {
+        matches!(event, PlayerEvent::Started { .. })
+    })
+    .await;
+    player
+        .seek(
+            sink.clone() as SharedMidiSink,
+            None,
+            Duration::from_millis(2_500),
+        )
+        .unwrap();
+    let seen = wait_for(&mut events, |event| {
+        matches!(event, PlayerEvent::Finished | PlayerEvent::Error(_))
+    })
+    .await;
+
+    assert!(
+        seen.iter()
+            .any(|event| matches!(event, PlayerEvent::Seeked { position, .. } if *position == Duration::from_millis(2_500)))
+    );
+    assert!(
+        !seen
+            .iter()
+            .any(|event| matches!(event, PlayerEvent::Stopped))
+    );
+    let sent = sink.sent();
+    assert!(sent.contains(&vec![0x81, 60, 0]), "held note is released");
+    assert!(sent.contains(&vec![0x91, 64, 100]));
+}
+
+#[tokio::test]
+async fn seeking_needs_active_playback() {
+    let (tx, _events) = mpsc::unbounded_channel();
+    let mut player = MidiPlayer::new(tx);
+
+    let result = player.seek(
+        Arc::new(MockSink::default()) as SharedMidiSink,
+        None,
+        Duration::from_secs(1),
+    );
+    assert!(result.is_err());
+}
+
+#[tokio::test]
+async fn reports_note_activity_per_channel() {
+    let (tx, mut events) = mpsc::unbounded_channel();
+    let mut player = MidiPlayer::new(tx);
+
+    player
+        .start_playback(
+            sequence(&[(0, 50, 0, 60), (0, 50, 9, 36)]),
+            Arc::new(MockSink::default()) as SharedMidiSink,
+            None,
+        )
+        .unwrap();
+    let seen = wait_for(&mut events, |event| {
+        matches!(event, PlayerEvent::Finished | PlayerEvent::Error(_))
+    })
+    .await;
+
+    let activity: Vec<[u8; 16]> = seen
+        .iter()
+        .filter_map(|event| match event {
+            PlayerEvent::ChannelActivity(levels) => Some(*levels),
+            _ => None,
+        })
+        .collect();
+    let mut expected = [0u8; 16];
+    expected[0] = 100;
+    expected[9] = 100;
+    assert_eq!(activity, vec![expected]);
+}
+
+#[tokio::test]
+async fn level_scales_note_velocities() {
+    let (tx, mut events) = mpsc::unbounded_channel();
+    let mut player = MidiPlayer::new(tx);
+    let sink = Arc::new(MockSink::default());
+
+    player.set_level(50);
+    player
+        .start_playback(
+            sequence(&[(0, 50, 0, 60)]),
+            sink.clone() as SharedMidiSink,
+            None,
+        )
+        .unwrap();
+    wait_for(&mut events, |event| {
+        matches!(event, PlayerEvent::Finished | PlayerEvent::Error(_))
+    })
+    .await;
+    player.set_level(0);
+    player
+        .start_playback(
+            sequence(&[(0, 50, 0, 62)]),
+            sink.clone() as SharedMidiSink,
+            None,
+        )
+        .unwrap();
+    wait_for(&mut events, |event| {
+        matches!(event, PlayerEvent::Finished | PlayerEvent::Error(_))
+    })
+    .await;
+
+    assert_eq!(
+        sink.sent(),
+        vec![
+            vec![0x90, 60, 50],
+            vec![0x80, 60, 0],
+            vec![0x90, 62, 1],
+            vec![0x80, 62, 0],
+        ]
+    );
+}
+
+fn expression_values(sent: &[Vec<u8>]) -> Vec<u8> {
+    sent.iter()
+        .filter_map(|data| match data.as_slice() {
+            [0xB0, 11, value] => Some(*value),
+            _ => None,
+        })
+        .collect()
+}
+
+#[tokio::test]
+async fn fading_in_ramps_expression_up() {
+    let (tx, mut events) = mpsc::unbounded_channel();
+    let mut player = MidiPlayer::new(tx);
+    let sink = Arc::new(MockSink::default());
+
+    player.set_fade(Duration::from_millis(100));
+    player
+        .start_playback(
+            sequence(&[(0, 300, 0, 60)]),
+            sink.clone() as SharedMidiSink,
+            None,
+        )
+        .unwrap();
+    wait_for(&mut events, |event| {
+        matches!(event, PlayerEvent::Finished | PlayerEvent::Error(_))
+    })
+    .await;
+
+    let sent = sink.sent();
+    assert_eq!(sent[0], vec![0xB0, 11, 0]);
+    assert_eq!(sent[1], vec![0x90, 60, 100]);
+    let values = expression_values(&sent);
+    assert!(values.windows(2).all(|pair| pair[0] <= pair[1]));
+    assert_eq!(values.last(), Some(&127));
+}
+
+#[tokio::test]
+async fn stopping_fades_out_before_releasing_notes() {
+    let (tx, mut events) = mpsc::unbounded_channel();
+    let mut player = MidiPlayer::new(tx);
+    let sink = Arc::new(MockSink::default());
+
+    player.set_fade(Duration::from_millis(100));
+    player
+        .start_playback(
+            sequence(&[(0, 10_000, 0, 60)]),
+            sink.clone() as SharedMidiSink,
+            None,
+        )
+        .unwrap();
+    timeout(WAIT, async {
+        while !expression_values(&sink.sent()).contains(&127) {
+            tokio::time::sleep(Duration::from_millis(5)).await;
+        }
+    })
+    .await
+    .expect("never faded in");
+    player.stop();
+    wait_for(&mut events, |event| matches!(event, PlayerEvent::Stopped)).await;
+
+    timeout(WAIT, async {
+        loop {
+            let sent = sink.sent();
+            if sent.contains(&vec![0x80, 60, 0]) && sent.last() == Some(&vec![0xB0, 11, 127]) {
+                break;
+            }
+            tokio::time::sleep(Duration::from_millis(5)).await;
+        }
+    })
+    .await
+    .expect("expression was never restored");
+    let sent = sink.sent();
+    let faded = sent
+        .iter()
+        .rposition(|data| *data == [0xB0, 11, 0])
+        .unwrap();
+    let released = sent.iter().position(|data| *data == [0x80, 60, 0]).unwrap();
+    assert!(faded < released);
+    // Nothing is reported once stopped, though the song plays on under the
+    // fade.
+    assert!(events.try_recv().is_err());
+}
+
+#[tokio::test]
+async fn stopping_mid_fade_in_fades_out_from_where_it_got_to() {
+    let (tx, mut events) = mpsc::unbounded_channel();
+    let mut player = MidiPlayer::new(tx);
+    let sink = Arc::new(MockSink::default());
+
+    player.set_fade(Duration::from_millis(400));
+    player
+        .start_playback(
+            sequence(&[(0, 10_000, 0, 60)]),
+            sink.clone() as SharedMidiSink,
+            None,
+        )
+        .unwrap();
+    timeout(WAIT, async {
+        while !expression_values(&sink.sent())
+            .iter()
+            .any(|value| *value >= 30)
+        {
+            tokio::time::sleep(Duration::from_millis(5)).await;
+        }
+    })
+    .await
+    .expect("never started fading in");
+    player.stop();
+    let stopped_at = sink.sent().len();
+    wait_for(&mut events, |event| matches!(event, PlayerEvent::Stopped)).await;
+
+    timeout(WAIT, async {
+        while sink.sent().last() != Some(&vec![0xB0, 11, 127]) {
+            tokio::time::sleep(Duration::from_millis(5)).await;
+        }
+    })
+    .await
+    .expect("expression was never restored");
+    let after = expression_values(&sink.sent()[stopped_at..]);
+    let (restored, fade_out) = after.split_last().unwrap();
+    assert_eq!(*restored, 127);
+    assert!(fade_out[0] < 127, "{after:?}");
+    assert!(
+        fade_out.windows(2).all(|pair| pair[0] >= pair[1]),
+        "{after:?}"
+    );
+    assert_eq!(fade_out.last(), Some(&0));
+}
+
+#[tokio::test(start_paused = true)]
+async fn starting_during_a_fade_out_cuts_it_short() {
+    let (tx, mut events) = mpsc::unbounded_channel();
+    let mut player = MidiPlayer::new(tx);
+    let sink = Arc::new(MockSink::default());
+    let notes = |key| {
+        (0..50)
+            .map(|beat| (beat * 200, 100, 0, key))
+            .collect::<Vec<_>>()
+    };
+
+    player.set_fade(Duration::from_secs(1));
+    player
+        .start_playback(sequence(&notes(60)), sink.clone() as SharedMidiSink, None)
+        .unwrap();
+    tokio::time::sleep(Duration::from_secs(2)).await;
+    player.stop();
+    player
+        .start_playback(sequence(&notes(72)), sink.clone() as SharedMidiSink, None)
+        .unwrap();
+    wait_for(&mut events, |event| {
+        matches!(event, PlayerEvent::Started { .. })
+    })
+    .await;
+    tokio::time::sleep(Duration::from_secs(3)).await;
+
+    let sent = sink.sent();
+    let restarted = sent
+        .iter()
+        .position(|data| data[..] == [0x90, 72, 100])
+        .expect("the second song never played");
+    let after = &sent[restarted..];
+    assert!(
+        !after
+            .iter()
+            .any(|data| matches!(data[..], [0x90, 60, velocity] if velocity > 0)),
+        "the first song played on under the second"
+    );
+    let expression = expression_values(after);
+    assert!(
+        expression.windows(2).all(|pair| pair[0] <= pair[1]),
+        "{expression:?}"
+    );
+    assert_eq!(expression.last(), Some(&127));
+}
+
+#[tokio::test]
+async fn repeats_the_song_without_starting_again() {
+    let (tx, mut events) = mpsc::unbounded_channel();
+    let mut player = MidiPlayer::new(tx);
+    let sink = Arc::new(MockSink::default());
+    player.set_repeat(3);
+
+    player
+        .start_playback(
+            sequence(&[(0, 20, 0, 60)]),
+            sink.clone() as SharedMidiSink,
+            None,
+        )
+        .unwrap();
+    let seen = wait_for(&mut events, |event| {
+        matches!(event, PlayerEvent::Finished | PlayerEvent::Error(_))
+    })
+    .await;
+
+    let passes: Vec<u8> = seen
+        .iter()
+        .filter_map(|event| match event {
+            PlayerEvent::Repeated { pass, passes, .. } => {
+                assert_eq!(*passes, 3);
+                Some(*pass)
+            }
+            _ => None,
+        })
+        .collect();
+    assert_eq!(passes, vec![2, 3]);
+    assert_eq!(
+        seen.iter()
+            .filter(|event| matches!(event, PlayerEvent::Started { .. }))
+            .count(),
+        1
+    );
+    assert_eq!(sink.sent().len(), 6);
+}
+
+#[tokio::test]
+async fn reports_tempo_changes_as_they_play() {
+    let (tx, mut events) = mpsc::unbounded_channel();
+    let mut player = MidiPlayer::new(tx);
+    let ppq = common::PPQ as u32;
+    let bytes = tempo_smf(
+        &[(0, 60_000), (ppq, 30_000)],
+        &[(0, ppq, 0, 60), (ppq, ppq, 0, 62)],
+    );
+
+    player
+        .start_playback(
+            Arc::new(MidiSequence::from_bytes(&bytes).unwrap()),
+            Arc::new(MockSink::default()) as SharedMidiSink,
+            None,
+        )
+        .unwrap();
+    let seen = wait_for(&mut events, |event| {
+        matches!(event, PlayerEvent::Finished | PlayerEvent::Error(_))
+    })
+    .await;
+
+    let tempos: Vec<f64> = seen
+        .iter()
+        .filter_map(|event| match event {
+            PlayerEvent::Tempo(bpm) => Some(*bpm),
+            _ => None,
+        })
+        .collect();
+    assert_eq!(tempos, vec![1000.0, 2000.0]);
+}
+
+#[tokio::test]
+async fn metronome_accents_the_first_beat_of_each_bar() {
+    let (tx, mut events) = mpsc::unbounded_channel();
+    let mut player = MidiPlayer::new(tx);
+    let sink = Arc::new(MockSink::default());
+    player.set_metronome(true);
+    let ppq = common::PPQ as u32;
+    // A 4/4 bar at 1000 BPM, then the downbeat of the next.
+    let bytes = tempo_smf(&[(0, 60_000)], &[(0, ppq * 4, 0, 60)]);
+
+    player
+        .start_playback(
+            Arc::new(MidiSequence::from_bytes(&bytes).unwrap()),
+            sink.clone() as SharedMidiSink,
+            None,
+        )
+        .unwrap();
+    wait_for(&mut events, |event| {
+        matches!(event, PlayerEvent::Finished | PlayerEvent::Error(_))
+    })
+    .await;
+
+    let clicks: Vec<(u8, u8)> = sink
+        .sent()
+        .iter()
+        .filter(|data| data[0] == 0x99)
+        .map(|data| (data[1], data[2]))
+        .collect();
+    assert_eq!(
+        clicks,
+        vec![(76, 110), (77, 80), (77, 80), (77, 80), (76, 110)]
+    );
+    assert_eq!(sink.sent()[2], vec![0x90, 60, 100]);
+}
+
+#[tokio::test(start_paused = true)]
+async fn previews_only_the_opening_seconds() {
+    let sink = Arc::new(MockSink::default());
+    let sequence = sequence(&[
+        (1_000, 500, 0, 60),
+        (1_500, 20_000, 0, 64),
+        (30_000, 100, 0, 67),
+    ]);
+
+    let output = sink.clone() as SharedMidiSink;
+    play_preview(&sequence, &output, Duration::from_secs(2), &Notify::new())
+        .await
+        .unwrap();
+
+    let mut expected = vec![
+        vec![0x90, 60, 100],
+        vec![0x80, 60, 0],
+        vec![0x90, 64, 100],
+        vec![0x80, 64, 0],
+    ];
+    expected.extend(panic_messages());
+    assert_eq!(sink.sent(), expected);
+}
+
+#[tokio::test(start_paused = true)]
+async fn cancelled_previews_release_notes() {
+    let sink = Arc::new(MockSink::default());
+    let cancel = Notify::new();
+    cancel.notify_one();
+
+    play_preview(
+        &sequence(&[(0, 10_000, 0, 60)]),
+        &(sink.clone() as SharedMidiSink),
+        Duration::from_secs(10),
+        &cancel,
+    )
+    .await
+    .unwrap();
+
+    assert_eq!(sink.sent(), panic_messages());
+}
@@ -0,0 +1,68 @@
+use std::collections::HashSet;
+
+use midi_piano_rs::devices::profile::{DeviceProfile, DeviceProfiles};
+use midi_piano_rs::midi::filter::{FilterAction, OutputFilter};
+use uuid::Uuid;
+
+#[test]
+fn devices_share_a_profile_until_it_is_deleted() {
+    let filter = OutputFilter {
+        sustain: FilterAction::Drop,
+        ..OutputFilter::default()
+    };
+    let (piano, keyboard) = (Uuid::new_v4(), Uuid::new_v4());
+    let mut profiles = DeviceProfiles::default();
+    let profile = profiles.add(DeviceProfile::new("Stage piano", filter));
+    profiles.assign(piano, Some(profile));
+    profiles.assign(keyboard, Some(profile));
+    profiles.assign(keyboard, Some(Uuid::new_v4()));
+
+    assert_eq!(
+        profiles
+            .profile_for(keyboard)
+            .map(|profile| &profile.name[..]),
+        Some("Stage piano")
+    );
+    assert_eq!(profiles.filters().get(&piano), Some(&filter));
+
+    profiles.remove(profile);
+    assert!(profiles.profile_for(piano).is_none());
+    assert!(profiles.filters().is_empty());
+}
+
+#[test]
+fn merging_adds_missing_profiles_and_keeps_existing_assignments() {
+    let device = Uuid::new_v4();
+    let other_device = Uuid::new_v4();
+    let mut here = DeviceProfiles::default();
+    let mine = here.add(DeviceProfile::new("Mine", OutputFilter::default()));
+    here.assign(device, Some(mine));
+
+    let mut there = DeviceProfiles::default();
+    let theirs = there.add(DeviceProfile::new("Theirs", OutputFilter::default()));
+    there.assign(device, Some(theirs));
+    there.assign(other_device, Some(theirs));
+    let copy = there.add(here.get(mine).unwrap().clone());
+    assert_eq!(copy, mine);
+
+    here.merge(there);
+
+    assert_eq!(here.iter().count(), 2);
+    assert_eq!(here.profile_for(device).unwrap().id, mine);
+    assert_eq!(here.profile_for(other_device).unwrap().id, theirs);
+}
+
+#[test]
+fn only_devices_whose_profile_asks_write_reliably() {
+    let (piano, keyboard) = (Uuid::new_v4(), Uuid::new_v4());
+    let mut profiles = DeviceProfiles::default();
+    let careful = profiles.add(DeviceProfile {
+        reliable_writes: true,
+        ..DeviceProfile::new("Careful", OutputFilter::default())
+    });
+    let plain = profiles.add(DeviceProfile::new("Plain", OutputFilter::default()));
+    profiles.assign(piano, Some(careful));
+    profiles.assign(keyboard, Some(plain));
+
+    assert_eq!(profiles.reliable_write_devices(), HashSet::from([piano]));
+}
@@ -0,0 +1,65 @@
+use std::fs;
+
+use midi_piano_rs::midi::remote::{RemoteCache, RemoteEntry, parse_catalog};
+use midi_piano_rs::midi::{MidiLibrary, MidiOrigin};
+
+#[test]
+fn catalog_skips_entries_without_name_or_url() {
+    let catalog = parse_catalog(
+        r#"[
+            {"name": "Gymnopédie No.1", "url": "https://example.com/gymnopedie.mid"},
+            {"name": "", "url": "https://example.com/blank.mid"},
+            {"name": "No link", "url": " "}
+        ]"#,
+    )
+    .unwrap();
+
+    assert_eq!(
+        catalog,
+        vec![RemoteEntry {
+            name: "Gymnopédie No.1".into(),
+            url: "https://example.com/gymnopedie.mid".into(),
+        }]
+    );
+    assert!(parse_catalog("{}").is_err());
+}
+
+#[test]
+fn cache_names_files_by_url_and_clears() {
+    let dir = std::env::temp_dir().join(format!("midi-piano-cache-{}", std::process::id()));
+    let _ = fs::remove_dir_all(&dir);
+    let cache = RemoteCache::new(&dir);
+
+    let first = cache.path_for("https://example.com/a.mid");
+    assert_eq!(first, cache.path_for("https://example.com/a.mid"));
+    assert_ne!(first, cache.path_for("https://example.com/b.mid"));
+    assert_eq!(cache.usage().unwrap(), (0, 0));
+
+    fs::create_dir_all(&dir).unwrap();
+    fs::write(&first, b"MThd").unwrap();
+    assert_eq!(cache.usage().unwrap(), (1, 4));
+    assert_eq!(cache.fetch("https://example.com/a.mid").unwrap(), first);
+    assert_eq!(cache.clear().unwrap(), 1);
+    assert!(!first.exists());
+    let _ = fs::remove_dir_all(&dir);
+}
+
+#[test]
+fn remote_entries_can_be_replaced() {
+    let mut library = MidiLibrary::default();
+    let id = library.add_remote_entry("Song", "https://example.com/song.mid", "song.mid".into());
+    assert_eq!(
+        id,
+        library.add_remote_entry("Song", "https://example.com/song.mid", "song.mid".into())
+    );
+    let entry = library.get(&id).unwrap();
+    assert_eq!(entry.origin, MidiOrigin::Remote);
+    assert_eq!(
+        entry.source_url.as_deref(),
+        Some("https://example.com/song.mid")
+    );
+
+    library.remove_origin(MidiOrigin::Remote);
+    assert!(library.get(&id).is_none());
+    assert!(library.entries().is_empty());
+}
@@ -0,0 +1,76 @@
+mod common;
+
+use std::sync::atomic::AtomicBool;
+
+use common::{PPQ, smf_bytes, soundfont_bytes};
+use midi_piano_rs::midi::MidiSequence;
+use midi_piano_rs::midi::render::{RENDER_SAMPLE_RATE, render_to_wav};
+use midi_piano_rs::midi::soundfont::SoundFont;
+
+fn scratch_wav(name: &str) -> std::path::PathBuf {
+    std::env::temp_dir().join(format!(
+        "midi-piano-render-{name}-{}.wav",
+        std::process::id()
+    ))
+}
+
+#[test]
+fn renders_a_note_to_a_stereo_wav() {
+    let soundfont = SoundFont::parse(&soundfont_bytes()).unwrap();
+    // Half a second of A4, then half a second of silence.
+    let sequence = MidiSequence::from_bytes(&smf_bytes(&[
+        (0, PPQ as u32, 0, 69),
+        (2 * PPQ as u32, 0, 0, 60),
+    ]))
+    .unwrap();
+    let output = scratch_wav("note");
+    let mut reports = Vec::new();
+
+    let finished = render_to_wav(
+        &sequence,
+        &soundfont,
+        &output,
+        &AtomicBool::new(false),
+        |progress| reports.push(progress.fraction()),
+    )
+    .unwrap();
+
+    assert!(finished);
+    assert_eq!(reports.last(), Some(&1.0));
+    let mut reader = hound::WavReader::open(&output).unwrap();
+    let spec = reader.spec();
+    assert_eq!((spec.channels, spec.sample_rate), (2, RENDER_SAMPLE_RATE));
+    let samples: Vec<i16> = reader.samples::<i16>().map(Result::unwrap).collect();
+    let frames = samples.len() / 2;
+    let rate = RENDER_SAMPLE_RATE as usize;
+    assert!(frames >= rate, "only {frames} frames rendered");
+    let loudest = |range: std::ops::Range<usize>| {
+        samples[range.start * 2..range.end * 2]
+            .iter()
+            .map(|sample| sample.unsigned_abs())
+            .max()
+            .unwrap_or(0)
+    };
+    assert!(loudest(0..rate / 2) > 1000);
+    assert_eq!(loudest(rate * 3 / 4..rate), 0);
+    let _ = std::fs::remove_file(&output);
+}
+
+#[test]
+fn cancelled_renders_report_false() {
+    let soundfont = SoundFont::parse(&soundfont_bytes()).unwrap();
+    let sequence = MidiSequence::from_bytes(&smf_bytes(&[(0, PPQ as u32, 0, 69)])).unwrap();
+    let output = scratch_wav("cancel");
+
+    let finished = render_to_wav(
+        &sequence,
+        &soundfont,
+        &output,
+        &AtomicBool::new(true),
+        |_| {},
+    )
+    .unwrap();
+
+    assert!(!finished);
+    let _ = std::fs::remove_file(&output);
+}
@@ -0,0 +1,368 @@
+//! Timing tests run on tokio's paused clock: time only moves when every task
+//! is waiting, and then jumps straight to the next timer, so the timestamps
+//! recorded by the null sink are exact.
+
+mod common;
+
+use std::sync::Arc;
+use std::time::Duration;
+
+use common::{PPQ, smf_bytes};
+use midi_piano_rs::midi::{
+    LeadIn, MidiPlayer, MidiSequence, NullSink, PlaybackAdjustments, PlayerEvent, SentMessage,
+    SharedMidiSink, SilenceWatch,
+};
+use tokio::sync::mpsc::{self, UnboundedReceiver};
+
+const QUARTER: u32 = PPQ as u32;
+
+fn sequence(notes: &[common::Note]) -> Arc<MidiSequence> {
+    Arc::new(MidiSequence::from_bytes(&smf_bytes(notes)).unwrap())
+}
+
+fn sent(at_ms: u64, data: &[u8]) -> SentMessage {
+    SentMessage {
+        at: Duration::from_millis(at_ms),
+        data: data.to_vec(),
+    }
+}
+
+async fn until_finished(events: &mut UnboundedReceiver<PlayerEvent>) -> Vec<PlayerEvent> {
+    let mut seen = Vec::new();
+    while let Some(event) = events.recv().await {
+        let done = matches!(event, PlayerEvent::Finished | PlayerEvent::Error(_));
+        seen.push(event);
+        if done {
+            break;
+        }
+    }
+    seen
+}
+
+#[tokio::test(start_paused = true)]
+async fn sends_each_event_at_its_scheduled_time() {
+    let (tx, mut events) = mpsc::unbounded_channel();
+    let mut player = MidiPlayer::new(tx);
+    let sink = Arc::new(NullSink::new());
+
+    player
+        .start_playback(
+            sequence(&[(0, QUARTER, 0, 60), (QUARTER, QUARTER / 2, 0, 64)]),
+            sink.clone() as SharedMidiSink,
+            None,
+        )
+        .unwrap();
+    until_finished(&mut events).await;
+
+    assert_eq!(
+        sink.sent(),
+        vec![
+            sent(0, &[0x90, 60, 100]),
+            sent(500, &[0x80, 60, 0]),
+            sent(500, &[0x90, 64, 100]),
+            sent(750, &[0x80, 64, 0]),
+        ]
+    );
+}
+
+#[tokio::test(start_paused = true)]
+async fn tempo_adjustment_rescales_the_schedule() {
+    let (tx, mut events) = mpsc::unbounded_channel();
+    let mut player = MidiPlayer::new(tx);
+    let sink = Arc::new(NullSink::new());
+
+    let adjusted = sequence(&[(0, QUARTER, 0, 60)]).adjusted(PlaybackAdjustments {
+        tempo_percent: 50,
+        ..PlaybackAdjustments::default()
+    });
+    player
+        .start_playback(Arc::new(adjusted), sink.clone() as SharedMidiSink, None)
+        .unwrap();
+    until_finished(&mut events).await;
+
+    assert_eq!(
+        sink.sent(),
+        vec![sent(0, &[0x90, 60, 100]), sent(1000, &[0x80, 60, 0])]
+    );
+}
+
+#[tokio::test(start_paused = true)]
+async fn stop_releases_notes_at_the_moment_of_stopping() {
+    let (tx, mut events) = mpsc::unbounded_channel();
+    let mut player = MidiPlayer::new(tx);
+    let sink = Arc::new(NullSink::new());
+
+    player
+        .start_playback(
+            sequence(&[(0, QUARTER * 8, 3, 67)]),
+            sink.clone() as SharedMidiSink,
+            None,
+        )
+        .unwrap();
+    tokio::time::sleep(Duration::from_millis(300)).await;
+    player.stop();
+    tokio::time::sleep(Duration::from_millis(10)).await;
+
+    let messages = sink.take();
+    assert_eq!(messages[0], sent(0, &[0x93, 67, 100]));
+    assert_eq!(messages[1], sent(300, &[0x83, 67, 0]));
+    assert!(
+        messages[2..]
+            .iter()
+            .all(|message| message.at == Duration::from_millis(300)
+                && message.data[0] & 0xF0 == 0xB0)
+    );
+    assert_eq!(messages.len(), 2 + 32);
+    while let Ok(event) = events.try_recv() {
+        assert!(!matches!(event, PlayerEvent::Finished));
+    }
+}
+
+#[tokio::test(start_paused = true)]
+async fn restarting_flushes_the_previous_song_first() {
+    let (tx, _events) = mpsc::unbounded_channel();
+    let mut player = MidiPlayer::new(tx);
+    let sink = Arc::new(NullSink::new());
+
+    player
+        .start_playback(
+            sequence(&[(0, QUARTER * 8, 0, 60)]),
+            sink.clone() as SharedMidiSink,
+            None,
+        )
+        .unwrap();
+    tokio::time::sleep(Duration::from_millis(100)).await;
+    player
+        .start_playback(
+            sequence(&[(0, QUARTER, 0, 72)]),
+            sink.clone() as SharedMidiSink,
+            None,
+        )
+        .unwrap();
+    tokio::time::sleep(Duration::from_millis(10)).await;
+
+    let sent = sink.sent();
+    let release = sent
+        .iter()
+        .position(|message| message.data == [0x80, 60, 0])
+        .expect("first song's note was never released");
+    let next = sent
+        .iter()
+        .position(|message| message.data == [0x90, 72, 100])
+        .expect("second song never started");
+    assert!(release < next);
+    assert_eq!(sent[next].at, Duration::from_millis(100));
+}
+
+#[tokio::test(start_paused = true)]
+async fn fast_forwards_long_silences() {
+    let (tx, mut events) = mpsc::unbounded_channel();
+    let mut player = MidiPlayer::new(tx);
+    let sink = Arc::new(NullSink::new());
+
+    player
+        .start_playback(
+            sequence(&[(0, QUARTER, 0, 60), (QUARTER * 20, QUARTER, 0, 62)]),
+            sink.clone() as SharedMidiSink,
+            Some(SilenceWatch {
+                threshold: Duration::from_secs(2),
+                fast_forward: true,
+            }),
+        )
+        .unwrap();
+    let seen = until_finished(&mut events).await;
+
+    let gap = seen
+        .iter()
+        .find_map(|event| match event {
+            PlayerEvent::SilenceGap {
+                at,
+                length,
+                skipped,
+            } => Some((*at, *length, *skipped)),
+            _ => None,
+        })
+        .expect("no silence gap reported");
+    assert_eq!(
+        gap,
+        (
+            Duration::from_millis(500),
+            Duration::from_millis(9500),
+            Duration::from_millis(8500)
+        )
+    );
+    assert_eq!(
+        sink.sent(),
+        vec![
+            sent(0, &[0x90, 60, 100]),
+            sent(500, &[0x80, 60, 0]),
+            sent(1500, &[0x90, 62, 100]),
+            sent(2000, &[0x80, 62, 0]),
+        ]
+    );
+}
+
+#[tokio::test(start_paused = true)]
+async fn starting_mid_song_sends_setup_then_skips_ahead() {
+    let (tx, mut events) = mpsc::unbounded_channel();
+    let mut player = MidiPlayer::new(tx);
+    let sink = Arc::new(NullSink::new());
+
+    let adjusted = sequence(&[(0, QUARTER / 2, 0, 60), (QUARTER, QUARTER / 2, 0, 64)]).adjusted(
+        PlaybackAdjustments {
+            program_override: Some(5),
+            ..PlaybackAdjustments::default()
+        },
+    );
+    player
+        .start_playback_from(
+            Arc::new(adjusted),
+            sink.clone() as SharedMidiSink,
+            None,
+            Duration::from_millis(500),
+        )
+        .unwrap();
+    let seen = until_finished(&mut events).await;
+
+    assert!(matches!(
+        seen.first(),
+        Some(PlayerEvent::Started { position, .. }) if *position == Duration::from_millis(500)
+    ));
+    assert_eq!(
+        sink.sent(),
+        vec![
+            sent(0, &[0xC0, 5]),
+            sent(0, &[0x90, 64, 100]),
+            sent(250, &[0x80, 64, 0]),
+        ]
+    );
+}
+
+#[tokio::test(start_paused = true)]
+async fn trimming_starts_just_before_the_first_note() {
+    let (tx, mut events) = mpsc::unbounded_channel();
+    let mut player = MidiPlayer::new(tx);
+    let sink = Arc::new(NullSink::new());
+
+    player.set_lead_in(LeadIn {
+        trim_to: Some(Duration::from_millis(500)),
+        ..LeadIn::default()
+    });
+    player
+        .start_playback(
+            sequence(&[(QUARTER * 4, QUARTER, 0, 60)]),
+            sink.clone() as SharedMidiSink,
+            None,
+        )
+        .unwrap();
+    let seen = until_finished(&mut events).await;
+
+    assert!(matches!(
+        seen.first(),
+        Some(PlayerEvent::Started { position, .. }) if *position == Duration::from_millis(1500)
+    ));
+    assert_eq!(
+        sink.sent(),
+        vec![sent(500, &[0x90, 60, 100]), sent(1000, &[0x80, 60, 0])]
+    );
+}
+
+#[tokio::test(start_paused = true)]
+async fn count_in_clicks_a_measure_ending_on_the_first_note() {
+    let (tx, mut events) = mpsc::unbounded_channel();
+    let mut player = MidiPlayer::new(tx);
+    let sink = Arc::new(NullSink::new());
+
+    player.set_lead_in(LeadIn {
+        count_in: true,
+        ..LeadIn::default()
+    });
+    player
+        .start_playback(
+            sequence(&[(QUARTER, QUARTER, 0, 60)]),
+            sink.clone() as SharedMidiSink,
+            None,
+        )
+        .unwrap();
+    let seen = until_finished(&mut events).await;
+
+    assert!(matches!(
+        seen.first(),
+        Some(PlayerEvent::Started { count_in, .. }) if *count_in == Duration::from_millis(1500)
+    ));
+    assert_eq!(
+        sink.sent(),
+        vec![
+            sent(0, &[0x99, 76, 110]),
+            sent(0, &[0x89, 76, 0]),
+            sent(500, &[0x99, 77, 80]),
+            sent(500, &[0x89, 77, 0]),
+            sent(1000, &[0x99, 77, 80]),
+            sent(1000, &[0x89, 77, 0]),
+            sent(1500, &[0x99, 77, 80]),
+            sent(1500, &[0x89, 77, 0]),
+            sent(2000, &[0x90, 60, 100]),
+            sent(2500, &[0x80, 60, 0]),
+        ]
+    );
+}
+
+#[tokio::test(start_paused = true)]
+async fn countdown_holds_everything_back_and_reports_each_second() {
+    let (tx, mut events) = mpsc::unbounded_channel();
+    let mut player = MidiPlayer::new(tx);
+    let sink = Arc::new(NullSink::new());
+
+    player.set_lead_in(LeadIn {
+        countdown: LeadIn::COUNTDOWN,
+        ..LeadIn::default()
+    });
+    player
+        .start_playback(
+            sequence(&[(0, QUARTER, 0, 60)]),
+            sink.clone() as SharedMidiSink,
+            None,
+        )
+        .unwrap();
+    let seen = until_finished(&mut events).await;
+
+    let countdown: Vec<u8> = seen
+        .iter()
+        .map_while(|event| match event {
+            PlayerEvent::Countdown(remaining) => Some(*remaining),
+            _ => None,
+        })
+        .collect();
+    assert_eq!(countdown, vec![3, 2, 1]);
+    assert!(matches!(seen[3], PlayerEvent::Started { .. }));
+    assert_eq!(
+        sink.sent(),
+        vec![sent(3000, &[0x90, 60, 100]), sent(3500, &[0x80, 60, 0])]
+    );
+}
+
+#[tokio::test(start_paused = true)]
+async fn progress_is_reported_steadily_through_silence() {
+    let (tx, mut events) = mpsc::unbounded_channel();
+    let mut player = MidiPlayer::new(tx);
+    let sink = Arc::new(NullSink::new());
+
+    player.set_progress_interval(Duration::from_millis(250));
+    player
+        .start_playback(
+            sequence(&[(0, QUARTER / 4, 0, 60), (QUARTER * 2, QUARTER / 4, 0, 62)]),
+            sink as SharedMidiSink,
+            None,
+        )
+        .unwrap();
+    let progress: Vec<u128> = until_finished(&mut events)
+        .await
+        .into_iter()
+        .filter_map(|event| match event {
+            PlayerEvent::Progress { elapsed, .. } => Some(elapsed.as_millis()),
+            _ => None,
+        })
+        .collect();
+
+    assert_eq!(progress, vec![0, 250, 500, 750, 1000, 1125]);
+}
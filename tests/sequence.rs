@@ -0,0 +1,330 @@
+mod common;
+
+use std::time::Duration;
+
+use common::{PPQ, named_tracks_smf, smf_bytes, tempo_smf};
+use midi_piano_rs::error::PlaybackError;
+use midi_piano_rs::midi::{
+    Accent, MidiSequence, MidiSource, PercussionHandling, PlaybackAdjustments, file_duration,
+    file_timing,
+};
+
+#[test]
+fn converts_ticks_to_time_at_default_tempo() {
+    let ppq = PPQ as u32;
+    let sequence =
+        MidiSequence::from_bytes(&smf_bytes(&[(0, ppq, 0, 60), (ppq, ppq, 0, 62)])).unwrap();
+
+    let times: Vec<Duration> = sequence.events.iter().map(|event| event.at).collect();
+    assert_eq!(
+        times,
+        vec![
+            Duration::ZERO,
+            Duration::from_millis(500),
+            Duration::from_millis(500),
+            Duration::from_millis(1000),
+        ]
+    );
+    assert_eq!(sequence.duration, Duration::from_millis(1000));
+}
+
+#[test]
+fn adjustments_transpose_mute_and_scale_tempo() {
+    let ppq = PPQ as u32;
+    let sequence =
+        MidiSequence::from_bytes(&smf_bytes(&[(0, ppq, 0, 60), (0, ppq, 1, 48)])).unwrap();
+
+    let adjusted = sequence.adjusted(PlaybackAdjustments {
+        tempo_percent: 200,
+        transpose: 2,
+        muted_channels: 1 << 1,
+        ..PlaybackAdjustments::default()
+    });
+
+    let notes: Vec<(Duration, Vec<u8>)> = adjusted
+        .events
+        .iter()
+        .map(|event| (event.at, event.data.clone()))
+        .collect();
+    assert_eq!(
+        notes,
+        vec![
+            (Duration::ZERO, vec![0x90, 62, 100]),
+            (Duration::from_millis(250), vec![0x80, 62, 0]),
+        ]
+    );
+}
+
+#[test]
+fn rejects_invalid_data() {
+    let err = MidiSequence::from_bytes(b"not a midi file").unwrap_err();
+    assert_eq!(
+        PlaybackError::find(&err),
+        Some(&PlaybackError::Parse("data".into()))
+    );
+}
+
+#[test]
+fn rejects_format_2_files() {
+    let mut bytes = smf_bytes(&[(0, PPQ as u32, 0, 60)]);
+    // The format is the big-endian word right after the header length.
+    bytes[9] = 2;
+    let err = MidiSequence::from_bytes(&bytes).unwrap_err();
+    assert_eq!(
+        PlaybackError::find(&err),
+        Some(&PlaybackError::UnsupportedFormat("SMF format 2"))
+    );
+}
+
+#[test]
+fn program_override_replaces_programs_except_percussion() {
+    let sequence = MidiSequence::from_bytes(&smf_bytes(&[(0, 10, 0, 60), (0, 10, 9, 36)])).unwrap();
+
+    let adjusted = sequence.adjusted(PlaybackAdjustments {
+        program_override: Some(0),
+        ..PlaybackAdjustments::default()
+    });
+
+    assert_eq!(adjusted.events[0].data, vec![0xC0, 0]);
+    assert!(
+        adjusted
+            .events
+            .iter()
+            .all(|event| event.data != vec![0xC9, 0])
+    );
+}
+
+#[test]
+fn merge_channel_moves_everything_but_percussion() {
+    let sequence = MidiSequence::from_bytes(&smf_bytes(&[(0, 10, 3, 60), (0, 10, 9, 36)])).unwrap();
+
+    let adjusted = sequence.adjusted(PlaybackAdjustments {
+        merge_channel: Some(0),
+        ..PlaybackAdjustments::default()
+    });
+
+    let channels: Vec<u8> = adjusted
+        .events
+        .iter()
+        .map(|event| event.data[0] & 0x0F)
+        .collect();
+    assert_eq!(channels, vec![0, 9, 0, 9]);
+}
+
+#[test]
+fn file_and_memory_sources_load_the_same_sequence() {
+    let bytes = smf_bytes(&[(0, PPQ as u32, 0, 60)]);
+    let path = std::env::temp_dir().join(format!("midi-piano-source-{}.mid", std::process::id()));
+    std::fs::write(&path, &bytes).unwrap();
+
+    let from_file = MidiSequence::from_source(&MidiSource::File(path.clone())).unwrap();
+    let from_memory = MidiSequence::from_source(&MidiSource::Memory(bytes.into())).unwrap();
+    let _ = std::fs::remove_file(&path);
+
+    assert_eq!(from_file.duration, from_memory.duration);
+    assert_eq!(
+        from_file
+            .events
+            .iter()
+            .map(|event| &event.data)
+            .collect::<Vec<_>>(),
+        from_memory
+            .events
+            .iter()
+            .map(|event| &event.data)
+            .collect::<Vec<_>>()
+    );
+    assert!(MidiSequence::from_source(&MidiSource::File(path)).is_err());
+}
+
+#[test]
+fn file_duration_matches_the_full_parse() {
+    let ppq = PPQ as u32;
+    let bytes = smf_bytes(&[(0, ppq, 0, 60), (ppq * 2, ppq * 3, 1, 64)]);
+
+    let sequence = MidiSequence::from_bytes(&bytes).unwrap();
+    assert_eq!(file_duration(&bytes).unwrap(), sequence.duration);
+    assert!(file_duration(b"not a midi file").is_err());
+}
+
+#[test]
+fn tempo_maps_give_the_opening_average_and_current_tempo() {
+    let ppq = PPQ as u32;
+    // A beat at 100 BPM, then one at 200 BPM.
+    let bytes = tempo_smf(
+        &[(0, 600_000), (ppq, 300_000)],
+        &[(0, ppq, 0, 60), (ppq, ppq, 0, 62)],
+    );
+
+    let timing = file_timing(&bytes).unwrap();
+    assert_eq!(timing.duration, Duration::from_millis(900));
+    assert_eq!(timing.tempo.initial_bpm, 100.0);
+    assert!((timing.tempo.average_bpm - 133.3).abs() < 0.1);
+    assert!(timing.tempo.varies());
+
+    let sequence = MidiSequence::from_bytes(&bytes).unwrap();
+    assert_eq!(sequence.bpm_at(Duration::ZERO), Some(100.0));
+    assert_eq!(sequence.bpm_at(Duration::from_millis(700)), Some(200.0));
+
+    let faster = sequence.adjusted(PlaybackAdjustments {
+        tempo_percent: 200,
+        ..PlaybackAdjustments::default()
+    });
+    assert_eq!(faster.bpm_at(Duration::from_millis(350)), Some(400.0));
+}
+
+#[test]
+fn track_names_label_the_channel_they_play_on() {
+    let ppq = PPQ as u32;
+    let bytes = named_tracks_smf(&[
+        (Some("Piano RH"), &[(0, ppq, 0, 72)]),
+        (Some("Piano LH"), &[(0, ppq, 1, 48)]),
+        (Some("Both hands"), &[(0, ppq, 2, 60), (0, ppq, 3, 64)]),
+        (None, &[(0, ppq, 4, 60)]),
+    ]);
+
+    let sequence = MidiSequence::from_bytes(&bytes).unwrap();
+    assert_eq!(sequence.channel_names[0].as_deref(), Some("Piano RH"));
+    assert_eq!(sequence.channel_names[1].as_deref(), Some("Piano LH"));
+    assert!(sequence.channel_names[2..].iter().all(Option::is_none));
+}
+
+#[test]
+fn exported_arrangement_plays_like_the_adjusted_sequence() {
+    let ppq = PPQ as u32;
+    let original = MidiSequence::from_bytes(&named_tracks_smf(&[
+        (Some("Melody"), &[(0, ppq, 0, 72), (ppq, ppq, 0, 74)]),
+        (Some("Bass"), &[(0, ppq * 2, 1, 48)]),
+    ]))
+    .unwrap();
+    let adjusted = original.adjusted(PlaybackAdjustments {
+        tempo_percent: 50,
+        transpose: 2,
+        muted_channels: 1 << 1,
+        ..PlaybackAdjustments::default()
+    });
+
+    let exported = MidiSequence::from_bytes(&adjusted.to_smf_bytes().unwrap()).unwrap();
+
+    let messages = |sequence: &MidiSequence| {
+        sequence
+            .events
+            .iter()
+            .map(|event| (event.at.as_millis(), event.data.clone()))
+            .collect::<Vec<_>>()
+    };
+    assert_eq!(messages(&exported), messages(&adjusted));
+    assert_eq!(exported.duration, Duration::from_secs(2));
+    assert_eq!(exported.channel_names[0].as_deref(), Some("Melody"));
+}
+
+#[test]
+fn excerpt_keeps_the_section_and_releases_held_notes() {
+    let ppq = PPQ as u32;
+    let bar = ppq * 4;
+    // A note held across the start of bar 2, one inside it and one held
+    // past its end.
+    let sequence = MidiSequence::from_bytes(&smf_bytes(&[
+        (0, bar + ppq, 0, 60),
+        (bar + ppq, ppq, 0, 62),
+        (bar + ppq * 3, bar, 0, 64),
+    ]))
+    .unwrap();
+
+    let start = sequence.measure_start(2).unwrap();
+    let end = sequence.measure_start(3).unwrap();
+    assert_eq!(
+        (start, end),
+        (Duration::from_secs(2), Duration::from_secs(4))
+    );
+
+    let excerpt = sequence.excerpt(start, end);
+    let messages: Vec<(u128, Vec<u8>)> = excerpt
+        .events
+        .iter()
+        .map(|event| (event.at.as_millis(), event.data.clone()))
+        .collect();
+    assert_eq!(
+        messages,
+        vec![
+            (500, vec![0x90, 62, 100]),
+            (1000, vec![0x80, 62, 0]),
+            (1500, vec![0x90, 64, 100]),
+            (2000, vec![0x80, 64, 0]),
+        ]
+    );
+    assert_eq!(excerpt.duration, Duration::from_secs(2));
+    assert_eq!(excerpt.beat_at(Duration::ZERO).unwrap().measure, 2);
+}
+
+#[test]
+fn excerpt_starting_past_the_end_is_empty() {
+    let sequence = MidiSequence::from_bytes(&smf_bytes(&[(0, PPQ as u32, 0, 60)])).unwrap();
+    let past = sequence.duration + Duration::from_secs(5);
+
+    let excerpt = sequence.excerpt(past, past + Duration::from_secs(1));
+
+    assert!(
+        excerpt
+            .events
+            .iter()
+            .all(|event| event.data[0] & 0xE0 != 0x80)
+    );
+    assert_eq!(excerpt.duration, Duration::ZERO);
+}
+
+#[test]
+fn positions_read_as_bars_and_beats() {
+    let ppq = PPQ as u32;
+    let sequence = MidiSequence::from_bytes(&smf_bytes(&[(0, ppq * 12, 0, 60)])).unwrap();
+
+    let position = |millis| {
+        sequence
+            .beat_at(Duration::from_millis(millis))
+            .unwrap()
+            .to_string()
+    };
+    assert_eq!(position(0), "1:1");
+    assert_eq!(position(1999), "1:4");
+    assert_eq!(position(2600), "2:2");
+}
+
+#[test]
+fn percussion_can_be_dropped_or_played_as_a_piano_rhythm() {
+    let sequence = MidiSequence::from_bytes(&smf_bytes(&[(0, 10, 0, 72), (0, 10, 9, 38)])).unwrap();
+
+    let dropped = sequence.adjusted(PlaybackAdjustments {
+        percussion: PercussionHandling::Drop,
+        ..PlaybackAdjustments::default()
+    });
+    assert!(dropped.events.iter().all(|event| event.data[0] & 0x0F != 9));
+    assert_eq!(dropped.events.len(), 2);
+
+    let rhythm = sequence.adjusted(PlaybackAdjustments {
+        percussion: PercussionHandling::Rhythm(2),
+        ..PlaybackAdjustments::default()
+    });
+    let notes: Vec<Vec<u8>> = rhythm
+        .events
+        .iter()
+        .map(|event| event.data.clone())
+        .filter(|data| data[0] & 0x0F == 2)
+        .collect();
+    assert_eq!(notes, vec![vec![0x92, 43, 100], vec![0x82, 43, 0]]);
+    assert!(rhythm.events.iter().all(|event| event.data[0] & 0x0F != 9));
+}
+
+#[test]
+fn compound_meters_accent_each_group_of_three() {
+    let accents = |beats: u8, unit: u8| -> Vec<Accent> {
+        (1..=beats)
+            .map(|beat| Accent::of(beat, beats, unit))
+            .collect()
+    };
+    use Accent::{Medium, Strong, Weak};
+
+    assert_eq!(accents(6, 8), vec![Strong, Weak, Weak, Medium, Weak, Weak]);
+    assert_eq!(accents(3, 4), vec![Strong, Weak, Weak]);
+    assert_eq!(accents(3, 8), vec![Strong, Weak, Weak]);
+    assert_eq!(accents(6, 4), vec![Strong, Weak, Weak, Weak, Weak, Weak]);
+}
@@ -0,0 +1,159 @@
+use std::collections::HashSet;
+
+use midi_piano_rs::shuffle::{self, PlaybackHistory, ShuffleHistory};
+use rand::SeedableRng;
+use rand::rngs::StdRng;
+use uuid::Uuid;
+
+fn tracks(count: usize) -> Vec<Uuid> {
+    (0..count).map(|_| Uuid::new_v4()).collect()
+}
+
+#[test]
+fn recent_plays_come_after_everything_else() {
+    let songs = tracks(10);
+    let mut history = ShuffleHistory::default();
+    for id in &songs[..3] {
+        history.record(*id);
+    }
+    let recent: HashSet<Uuid> = songs[..3].iter().copied().collect();
+
+    for seed in 0..50 {
+        let mut order = songs.clone();
+        history.shuffle(&mut order, 5, &mut StdRng::seed_from_u64(seed));
+        let sorted: HashSet<Uuid> = order.iter().copied().collect();
+        assert_eq!(sorted.len(), songs.len());
+        assert!(order[..7].iter().all(|id| !recent.contains(id)));
+        assert!(order[7..].iter().all(|id| recent.contains(id)));
+    }
+}
+
+#[test]
+fn plays_beyond_the_avoided_count_shuffle_freely() {
+    let songs = tracks(4);
+    let mut history = ShuffleHistory::default();
+    for id in &songs {
+        history.record(*id);
+    }
+
+    // Only the last play is held back; the first song still leads sometimes.
+    let leads: HashSet<Uuid> = (0..50)
+        .map(|seed| {
+            let mut order = songs.clone();
+            history.shuffle(&mut order, 1, &mut StdRng::seed_from_u64(seed));
+            assert_eq!(order[3], songs[3]);
+            order[0]
+        })
+        .collect();
+    assert!(leads.contains(&songs[0]));
+}
+
+#[test]
+fn older_recent_plays_tend_to_come_first() {
+    let songs = tracks(2);
+    let mut history = ShuffleHistory::default();
+    history.record(songs[0]);
+    history.record(songs[1]);
+
+    let older_first = (0..500)
+        .filter(|seed| {
+            let mut order = songs.clone();
+            history.shuffle(&mut order, 2, &mut StdRng::seed_from_u64(*seed));
+            order[0] == songs[0]
+        })
+        .count();
+    assert!(older_first > 300, "older song led {older_first} of 500");
+}
+
+#[test]
+fn replaying_a_song_makes_it_the_most_recent() {
+    let songs = tracks(3);
+    let mut history = ShuffleHistory::default();
+    for id in &songs {
+        history.record(*id);
+    }
+    history.record(songs[0]);
+
+    let last: Vec<Uuid> = history.last(5).collect();
+    assert_eq!(last, vec![songs[0], songs[2], songs[1]]);
+}
+
+#[test]
+fn history_forgets_beyond_its_capacity() {
+    let mut history = ShuffleHistory::default();
+    let songs = tracks(ShuffleHistory::CAPACITY + 10);
+    for id in &songs {
+        history.record(*id);
+    }
+
+    assert_eq!(history.last(usize::MAX).count(), ShuffleHistory::CAPACITY);
+    assert_eq!(history.last(1).next(), songs.last().copied());
+}
+
+#[test]
+fn weighted_draws_favour_heavier_songs_and_never_repeat_back_to_back() {
+    let songs = tracks(2);
+    let weight = |id: Uuid| {
+        if id == songs[0] {
+            shuffle::rating_weight(Some(5))
+        } else {
+            shuffle::rating_weight(Some(1))
+        }
+    };
+    let drawn = shuffle::weighted_draw(
+        &songs,
+        200,
+        Some(songs[1]),
+        weight,
+        &mut StdRng::seed_from_u64(7),
+    );
+
+    assert_eq!(drawn.len(), 200);
+    assert_eq!(drawn[0], songs[0]);
+    assert!(drawn.windows(2).all(|pair| pair[0] != pair[1]));
+}
+
+#[test]
+fn weighted_draws_follow_the_weights() {
+    let songs = tracks(3);
+    let weight = |id: Uuid| if id == songs[0] { 16.0 } else { 1.0 };
+    let drawn = shuffle::weighted_draw(&songs, 1000, None, weight, &mut StdRng::seed_from_u64(3));
+
+    // The favoured song can't follow itself, so it gets at most every other
+    // slot, but it should get nearly all of those.
+    let favoured = drawn.iter().filter(|id| **id == songs[0]).count();
+    assert!(favoured > 450, "favoured song drawn {favoured} of 1000");
+}
+
+#[test]
+fn unrated_songs_weigh_as_three_stars() {
+    assert_eq!(
+        shuffle::rating_weight(None),
+        shuffle::rating_weight(Some(3))
+    );
+    assert!(shuffle::rating_weight(Some(5)) > shuffle::rating_weight(Some(4)));
+    assert_eq!(shuffle::rating_weight(Some(1)), 1.0);
+}
+
+#[test]
+fn previous_steps_back_through_what_played_and_resume_returns_to_the_latest() {
+    let songs = tracks(3);
+    let mut history = PlaybackHistory::default();
+    for id in &songs {
+        history.record(*id);
+    }
+    // Resuming after a pause starts the same song again.
+    history.record(songs[2]);
+
+    assert_eq!(history.back(), Some(songs[1]));
+    // Songs started while stepping back are not recorded again.
+    history.record(songs[1]);
+    assert_eq!(history.back(), Some(songs[0]));
+    assert_eq!(history.back(), None);
+    assert!(history.is_stepping_back());
+
+    assert_eq!(history.resume(), Some(songs[2]));
+    assert!(!history.is_stepping_back());
+    assert_eq!(history.resume(), None);
+    assert_eq!(history.back(), Some(songs[1]));
+}
@@ -0,0 +1,59 @@
+mod common;
+
+use std::sync::Arc;
+use std::time::Duration;
+
+use anyhow::anyhow;
+use common::MockSink;
+use midi_piano_rs::midi::sink::{SendStats, SinkStats};
+use midi_piano_rs::midi::validate::ValidatingSink;
+use midi_piano_rs::midi::{MidiSink, NullSink, SharedMidiSink};
+
+#[test]
+fn sends_are_counted_and_failures_remembered() {
+    let stats = SendStats::default();
+    let chord = vec![vec![0x90, 60, 100], vec![0x90, 64, 100]];
+
+    stats.record(&chord, Duration::from_micros(300), &Ok(()));
+    stats.record(
+        &[vec![0xC0, 1]],
+        Duration::from_micros(900),
+        &Err(anyhow!("device disconnected")),
+    );
+
+    assert_eq!(
+        stats.snapshot(),
+        SinkStats {
+            messages: 2,
+            bytes: 6,
+            errors: 1,
+            average_latency: Duration::from_micros(600),
+            last_error: Some("device disconnected".into()),
+        }
+    );
+}
+
+#[test]
+fn nothing_sent_has_no_latency() {
+    assert_eq!(SendStats::default().snapshot(), SinkStats::default());
+}
+
+#[tokio::test]
+async fn wrappers_report_the_connection_underneath() {
+    let null = Arc::new(NullSink::new());
+    let sink = ValidatingSink::new(null.clone() as SharedMidiSink);
+
+    sink.send_batch(&[vec![0x90, 60, 100], vec![0x80, 60, 0]])
+        .await
+        .unwrap();
+
+    let stats = sink.stats().unwrap();
+    assert_eq!(stats.messages, 2);
+    assert_eq!(stats.bytes, 6);
+    assert_eq!(null.stats(), Some(stats));
+}
+
+#[test]
+fn sinks_that_keep_no_count_report_none() {
+    assert_eq!(MockSink::default().stats(), None);
+}
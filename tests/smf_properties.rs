@@ -0,0 +1,204 @@
+mod common;
+
+use std::time::Duration;
+
+use common::{riff_chunk, riff_list};
+use midi_piano_rs::midi::MidiSequence;
+use midi_piano_rs::midi::sequence::file_duration;
+use midly::num::{u4, u7, u15, u24, u28};
+use midly::{Format, Header, MetaMessage, MidiMessage, Smf, Timing, TrackEvent, TrackEventKind};
+use proptest::prelude::*;
+
+/// Encodes one track from `(tick, event)` pairs in any order.
+fn encode(ppq: u16, mut events: Vec<(u64, TrackEventKind<'static>)>) -> Vec<u8> {
+    events.sort_by_key(|(tick, _)| *tick);
+    let mut last = 0;
+    let mut track: Vec<TrackEvent<'static>> = events
+        .into_iter()
+        .map(|(tick, kind)| {
+            let delta = tick - last;
+            last = tick;
+            TrackEvent {
+                delta: u28::new(delta as u32),
+                kind,
+            }
+        })
+        .collect();
+    track.push(TrackEvent {
+        delta: u28::new(0),
+        kind: TrackEventKind::Meta(MetaMessage::EndOfTrack),
+    });
+    let smf = Smf {
+        header: Header::new(Format::SingleTrack, Timing::Metrical(u15::new(ppq))),
+        tracks: vec![track],
+    };
+    let mut bytes = Vec::new();
+    smf.write_std(&mut bytes).unwrap();
+    bytes
+}
+
+fn note_on(key: u8) -> TrackEventKind<'static> {
+    TrackEventKind::Midi {
+        channel: u4::new(0),
+        message: MidiMessage::NoteOn {
+            key: u7::new(key),
+            vel: u7::new(100),
+        },
+    }
+}
+
+fn tempo(micros_per_quarter: u32) -> TrackEventKind<'static> {
+    TrackEventKind::Meta(MetaMessage::Tempo(u24::new(micros_per_quarter)))
+}
+
+/// When `tick` plays, worked out independently of the parser.
+fn expected_micros(ppq: u16, tempos: &[(u64, u32)], tick: u64) -> f64 {
+    let mut sorted = tempos.to_vec();
+    // Of two changes at the same tick the later in the file wins.
+    sorted.sort_by_key(|(at, _)| *at);
+    let mut micros = 0.0;
+    let mut last_tick = 0;
+    let mut current = 500_000.0;
+    for (at, value) in sorted.into_iter().take_while(|(at, _)| *at <= tick) {
+        micros += (at - last_tick) as f64 * current / f64::from(ppq);
+        last_tick = at;
+        current = f64::from(value);
+    }
+    micros + (tick - last_tick) as f64 * current / f64::from(ppq)
+}
+
+proptest! {
+    #[test]
+    fn notes_play_when_their_ticks_and_tempos_say(
+        ppq in 1u16..=960,
+        tempos in prop::collection::vec((0u64..20_000, 1u32..2_000_000), 0..8),
+        notes in prop::collection::vec((0u64..20_000, 0u8..128), 1..40),
+    ) {
+        let mut events: Vec<(u64, TrackEventKind<'static>)> = tempos
+            .iter()
+            .map(|(tick, value)| (*tick, tempo(*value)))
+            .collect();
+        events.extend(notes.iter().map(|(tick, key)| (*tick, note_on(*key))));
+        let bytes = encode(ppq, events);
+        let sequence = MidiSequence::from_bytes(&bytes).unwrap();
+
+        // Each tempo segment rounds down to a whole microsecond.
+        let tolerance = tempos.len() as f64 + 1.0;
+        let mut expected: Vec<f64> = notes
+            .iter()
+            .map(|(tick, _)| expected_micros(ppq, &tempos, *tick))
+            .collect();
+        expected.sort_by(f64::total_cmp);
+        prop_assert_eq!(sequence.events.len(), notes.len());
+        for (event, expected) in sequence.events.iter().zip(&expected) {
+            let at = event.at.as_micros() as f64;
+            prop_assert!((at - expected).abs() <= tolerance, "{at} vs {expected}");
+        }
+        prop_assert!(sequence.events.windows(2).all(|pair| pair[0].at <= pair[1].at));
+        prop_assert_eq!(file_duration(&bytes).unwrap(), sequence.duration);
+    }
+
+    #[test]
+    fn arbitrary_bytes_never_panic(bytes in prop::collection::vec(any::<u8>(), 0..512)) {
+        let _ = MidiSequence::from_bytes(&bytes);
+        let _ = file_duration(&bytes);
+    }
+
+    #[test]
+    fn damaged_files_never_panic(
+        damage in prop::collection::vec((any::<prop::sample::Index>(), any::<u8>()), 1..16),
+    ) {
+        let mut bytes = encode(
+            480,
+            vec![(0, tempo(400_000)), (0, note_on(60)), (480, note_on(64)), (960, note_on(67))],
+        );
+        for (index, byte) in damage {
+            let at = index.index(bytes.len());
+            bytes[at] = byte;
+        }
+        if let Ok(sequence) = MidiSequence::from_bytes(&bytes) {
+            prop_assert!(sequence.duration <= Duration::from_secs(24 * 60 * 60));
+            let _ = sequence.to_smf_bytes();
+        }
+        let _ = file_duration(&bytes);
+    }
+
+    #[test]
+    fn damaged_rmid_files_never_panic(
+        damage in prop::collection::vec((any::<prop::sample::Index>(), any::<u8>()), 1..16),
+    ) {
+        let mut bytes = rmid(&encode(480, vec![(0, note_on(60)), (480, note_on(64))]));
+        for (index, byte) in damage {
+            let at = index.index(bytes.len());
+            bytes[at] = byte;
+        }
+        let _ = MidiSequence::from_bytes(&bytes);
+        let _ = file_duration(&bytes);
+    }
+}
+
+/// Wraps an SMF in an RMID file, after an unrelated chunk.
+fn rmid(smf: &[u8]) -> Vec<u8> {
+    riff_list(
+        b"RIFF",
+        b"RMID",
+        &[riff_chunk(b"DISP", b"tune"), riff_chunk(b"data", smf)],
+    )
+}
+
+#[test]
+fn files_without_a_resolution_are_rejected() {
+    let bytes = encode(0, vec![(0, note_on(60)), (10, note_on(62))]);
+    let err = MidiSequence::from_bytes(&bytes).unwrap_err();
+    assert!(format!("{err:#}").contains("zero ticks per quarter note"));
+    assert!(file_duration(&bytes).is_err());
+}
+
+#[test]
+fn timecode_at_minus_128_fps_is_rejected() {
+    let mut bytes = encode(480, vec![(0, note_on(60))]);
+    bytes[12] = 0x80;
+    assert!(MidiSequence::from_bytes(&bytes).is_err());
+    assert!(file_duration(&bytes).is_err());
+
+    let wrapped = rmid(&bytes);
+    assert!(MidiSequence::from_bytes(&wrapped).is_err());
+    assert!(file_duration(&wrapped).is_err());
+    // midly skips unknown chunks ahead of the header inside RMID data.
+    let behind_junk = rmid(&[b"junk\0\0\0\0".as_slice(), &bytes].concat());
+    assert!(MidiSequence::from_bytes(&behind_junk).is_err());
+}
+
+#[test]
+fn rmid_files_play_like_the_smf_inside() {
+    let bytes = encode(480, vec![(0, note_on(60)), (480, note_on(64))]);
+    let plain = MidiSequence::from_bytes(&bytes).unwrap();
+    let wrapped = MidiSequence::from_bytes(&rmid(&bytes)).unwrap();
+    assert_eq!(wrapped.duration, plain.duration);
+    assert_eq!(wrapped.events.len(), plain.events.len());
+}
+
+#[test]
+fn songs_longer_than_a_day_are_rejected() {
+    let last = u64::from(u28::max_value().as_int());
+    let bytes = encode(
+        1,
+        vec![(0, tempo(0xFF_FFFF)), (0, note_on(60)), (last, note_on(62))],
+    );
+    let err = MidiSequence::from_bytes(&bytes).unwrap_err();
+    assert!(format!("{err:#}").contains("longer than a day"));
+}
+
+#[test]
+fn tiny_beats_are_laid_out_only_so_far() {
+    // A quarter note per tick at the fastest tempo: hundreds of millions
+    // of beats in under five minutes.
+    let last = u64::from(u28::max_value().as_int());
+    let bytes = encode(
+        1,
+        vec![(0, tempo(1)), (0, note_on(60)), (last, note_on(62))],
+    );
+    let sequence = MidiSequence::from_bytes(&bytes).unwrap();
+    assert!(sequence.beats.len() <= 200_000);
+    assert_eq!(sequence.duration, Duration::from_micros(last));
+}
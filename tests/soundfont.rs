@@ -0,0 +1,67 @@
+mod common;
+
+use common::{SOUNDFONT_SAMPLES, riff_chunk, riff_list, soundfont_bytes};
+use midi_piano_rs::midi::soundfont::SoundFont;
+
+#[test]
+fn parses_presets_instruments_and_samples() {
+    let soundfont = SoundFont::parse(&soundfont_bytes()).unwrap();
+
+    let regions = soundfont.regions(0, 0, 69, 100);
+    assert_eq!(regions.len(), 1);
+    let region = &regions[0];
+    assert_eq!((region.start, region.end), (0, SOUNDFONT_SAMPLES as usize));
+    assert_eq!((region.loop_start, region.loop_end), (100, 900));
+    assert!(region.looping);
+    assert_eq!(region.sample_rate, 44_100);
+    assert_eq!(region.pitch_offset, 0.0);
+    assert_eq!(soundfont.regions(0, 0, 81, 100)[0].pitch_offset, 12.0);
+    // Missing presets fall back to program 0.
+    assert_eq!(soundfont.regions(0, 40, 60, 100).len(), 1);
+}
+
+#[test]
+fn rejects_files_that_are_not_soundfonts() {
+    assert!(SoundFont::parse(b"").is_err());
+    assert!(SoundFont::parse(b"RIFF\0\0\0\0WAVE").is_err());
+    assert!(SoundFont::parse(&riff_list(b"RIFF", b"sfbk", &[])).is_err());
+}
+
+#[test]
+fn chunks_claiming_more_bytes_than_remain_are_errors() {
+    let valid = soundfont_bytes();
+    // The sdta list header sits right after "RIFF", the size and "sfbk".
+    let sdta_size = 16..20;
+
+    let mut truncated = valid.clone();
+    let claimed = u32::from_le_bytes(valid[sdta_size.clone()].try_into().unwrap()) + 2;
+    truncated[sdta_size.clone()].copy_from_slice(&(claimed + valid.len() as u32).to_le_bytes());
+    let error = SoundFont::parse(&truncated).unwrap_err();
+    assert!(error.to_string().contains("LIST chunk claims"), "{error:#}");
+
+    let mut oversized = valid.clone();
+    oversized[sdta_size].copy_from_slice(&u32::MAX.to_le_bytes());
+    assert!(SoundFont::parse(&oversized).is_err());
+
+    let inner = riff_list(b"LIST", b"pdta", &[riff_chunk(b"phdr", &[0; 38])]);
+    let mut cut_short = riff_list(b"RIFF", b"sfbk", &[inner]);
+    cut_short.truncate(cut_short.len() - 10);
+    assert!(SoundFont::parse(&cut_short).is_err());
+}
+
+#[test]
+fn damaged_soundfonts_never_panic() {
+    let valid = soundfont_bytes();
+    for len in 0..valid.len() {
+        let _ = SoundFont::parse(&valid[..len]);
+    }
+    for index in 0..valid.len() {
+        let mut damaged = valid.clone();
+        damaged[index] ^= 0xFF;
+        if let Ok(soundfont) = SoundFont::parse(&damaged) {
+            for key in [0, 60, 127] {
+                let _ = soundfont.regions(0, 0, key, 100);
+            }
+        }
+    }
+}
@@ -0,0 +1,76 @@
+mod common;
+
+use std::sync::Arc;
+use std::time::Duration;
+
+use midi_piano_rs::midi::timing::{TimingCapture, TimingReport, TimingSink};
+use midi_piano_rs::midi::{MidiPlayer, MidiSequence, NullSink, PlayerEvent, SharedMidiSink};
+use tokio::sync::mpsc;
+
+#[test]
+fn reports_summarise_each_measurement() {
+    let capture = TimingCapture::new();
+    for micros in 1..=100 {
+        capture.record_jitter(Duration::from_micros(micros));
+    }
+    capture.record_send("usb", Duration::from_micros(250));
+
+    let report = capture.report();
+    let jitter = report.jitter.unwrap();
+    assert_eq!(jitter.count, 100);
+    assert_eq!((jitter.min_us, jitter.max_us), (1, 100));
+    assert_eq!(jitter.p50_us, 50);
+    assert_eq!(jitter.p99_us, 99);
+    assert_eq!(jitter.mean_us, 50);
+    assert_eq!(report.send["usb"].count, 1);
+    assert!(report.parse.is_none());
+
+    capture.clear();
+    assert_eq!(capture.report(), TimingReport::default());
+}
+
+#[test]
+fn reports_round_trip_through_json() {
+    let capture = TimingCapture::new();
+    let sequence =
+        capture.time_parse(|| MidiSequence::from_bytes(&common::smf_bytes(&[(0, 10, 0, 60)])));
+    assert!(sequence.is_ok());
+
+    let dir = std::env::temp_dir().join(format!("midi-piano-timing-{}", std::process::id()));
+    let path = dir.join("timings.json");
+    capture.report().dump(&path).unwrap();
+
+    let read: TimingReport =
+        serde_json::from_str(&std::fs::read_to_string(&path).unwrap()).unwrap();
+    assert_eq!(read.parse.unwrap().count, 1);
+    let _ = std::fs::remove_dir_all(dir);
+}
+
+#[tokio::test(start_paused = true)]
+async fn playback_records_jitter_and_send_latency() {
+    let capture = Arc::new(TimingCapture::new());
+    let null = Arc::new(NullSink::new());
+    let sink: SharedMidiSink = Arc::new(TimingSink::new(null.clone(), "null", capture.clone()));
+    let sequence = Arc::new(
+        MidiSequence::from_bytes(&common::smf_bytes(&[(0, 100, 0, 60), (200, 100, 0, 64)]))
+            .unwrap(),
+    );
+    let (sender, mut events) = mpsc::unbounded_channel();
+    let mut player = MidiPlayer::new(sender);
+    player.set_timing_capture(Some(capture.clone()));
+    player.start_playback(sequence, sink, None).unwrap();
+    while let Some(event) = events.recv().await {
+        if matches!(event, PlayerEvent::Finished) {
+            break;
+        }
+    }
+
+    let report = capture.report();
+    // Two note-ons and two note-offs, each sent within the timer's
+    // millisecond resolution.
+    let jitter = report.jitter.unwrap();
+    assert_eq!(jitter.count, 4);
+    assert!(jitter.max_us < 1000, "{jitter:?}");
+    assert_eq!(report.send["null"].count, 4);
+    assert_eq!(null.sent().len(), 4);
+}
@@ -0,0 +1,92 @@
+mod common;
+
+use std::fs;
+use std::path::PathBuf;
+use std::sync::Arc;
+
+use common::MockSink;
+use midi_piano_rs::midi::trace::{KEPT_FILES, SendTrace, TraceFormat, TracedSink};
+use midi_piano_rs::midi::{MidiSink, SharedMidiSink};
+use uuid::Uuid;
+
+fn scratch_dir(name: &str) -> PathBuf {
+    let dir = std::env::temp_dir().join(format!("midi-piano-{name}-{}", std::process::id()));
+    let _ = fs::remove_dir_all(&dir);
+    dir
+}
+
+#[tokio::test]
+async fn traced_sinks_log_hex_lines_and_forward() {
+    let dir = scratch_dir("trace-hex");
+    let device = Uuid::new_v4();
+    let trace = Arc::new(SendTrace::open(&dir, TraceFormat::Hex, 1024 * 1024).unwrap());
+    let inner = Arc::new(MockSink::default());
+    let sink = TracedSink::new(inner.clone() as SharedMidiSink, trace.clone(), device);
+
+    sink.send(&[0x90, 60, 100]).await.unwrap();
+    sink.send_batch(&[vec![0x80, 60, 0], vec![0xB0, 7, 90]])
+        .await
+        .unwrap();
+
+    assert_eq!(inner.sent().len(), 3);
+    let log = fs::read_to_string(trace.path()).unwrap();
+    let lines: Vec<Vec<&str>> = log
+        .lines()
+        .map(|line| line.splitn(3, ' ').collect())
+        .collect();
+    assert_eq!(lines.len(), 3);
+    let device = device.to_string();
+    assert_eq!(lines[0][1..], [device.as_str(), "90 3C 64"]);
+    assert_eq!(lines[1][1..], [device.as_str(), "80 3C 00"]);
+    assert_eq!(lines[2][1..], [device.as_str(), "B0 07 5A"]);
+    assert!(chrono::DateTime::parse_from_rfc3339(lines[0][0]).is_ok());
+    let _ = fs::remove_dir_all(&dir);
+}
+
+#[test]
+fn binary_records_carry_time_device_and_length() {
+    let dir = scratch_dir("trace-binary");
+    let device = Uuid::new_v4();
+    let trace = SendTrace::open(&dir, TraceFormat::Binary, 1024 * 1024).unwrap();
+
+    trace.record(device, &[vec![0x90, 60, 100]]);
+
+    let data = fs::read(trace.path()).unwrap();
+    assert_eq!(data.len(), 8 + 16 + 2 + 3);
+    let micros = i64::from_le_bytes(data[..8].try_into().unwrap());
+    assert!(micros > 0);
+    assert_eq!(&data[8..24], device.as_bytes());
+    assert_eq!(&data[24..26], &3u16.to_le_bytes());
+    assert_eq!(&data[26..], &[0x90, 60, 100]);
+    let _ = fs::remove_dir_all(&dir);
+}
+
+#[test]
+fn full_files_rotate_and_the_oldest_is_dropped() {
+    let dir = scratch_dir("trace-rotate");
+    let device = Uuid::new_v4();
+    // Each record is 29 bytes, so every one after the first starts a new
+    // file.
+    let trace = SendTrace::open(&dir, TraceFormat::Binary, 40).unwrap();
+
+    for key in 0..(KEPT_FILES as u8 + 3) {
+        trace.record(device, &[vec![0x90, key, 100]]);
+    }
+
+    let mut names: Vec<String> = fs::read_dir(&dir)
+        .unwrap()
+        .map(|entry| entry.unwrap().file_name().to_string_lossy().into_owned())
+        .collect();
+    names.sort();
+    let mut expected = vec!["midi-send.bin".to_owned()];
+    expected.extend((1..=KEPT_FILES).map(|index| format!("midi-send.bin.{index}")));
+    assert_eq!(names, expected);
+    // The newest message is in the current file, the one before it in .1.
+    let last_key = KEPT_FILES as u8 + 2;
+    assert_eq!(fs::read(trace.path()).unwrap()[27], last_key);
+    assert_eq!(
+        fs::read(dir.join("midi-send.bin.1")).unwrap()[27],
+        last_key - 1
+    );
+    let _ = fs::remove_dir_all(&dir);
+}
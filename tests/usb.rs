@@ -0,0 +1,44 @@
+use midi_piano_rs::devices::coalesce_midi_messages;
+
+#[test]
+fn a_chord_goes_out_in_one_send() {
+    let chord: Vec<Vec<u8>> = [60, 64, 67]
+        .iter()
+        .map(|&key| vec![0x90, key, 100])
+        .collect();
+
+    let chunks = coalesce_midi_messages(chord.iter().map(Vec::as_slice));
+
+    assert_eq!(
+        chunks,
+        vec![vec![0x90, 60, 100, 0x90, 64, 100, 0x90, 67, 100]]
+    );
+}
+
+#[test]
+fn sysex_is_sent_on_its_own() {
+    let sysex = vec![0xF0, 0x7E, 0x7F, 0x09, 0x01, 0xF7];
+    let messages = [
+        vec![0xB0, 7, 100],
+        vec![0xB0, 10, 64],
+        sysex.clone(),
+        vec![0x90, 60, 100],
+    ];
+
+    let chunks = coalesce_midi_messages(messages.iter().map(Vec::as_slice));
+
+    assert_eq!(
+        chunks,
+        vec![vec![0xB0, 7, 100, 0xB0, 10, 64], sysex, vec![0x90, 60, 100]]
+    );
+}
+
+#[test]
+fn running_status_bytes_stay_joined() {
+    // Messages already stripped of a repeated status byte.
+    let messages: [&[u8]; 3] = [&[0x90, 60, 100], &[64, 100], &[67, 100]];
+
+    let chunks = coalesce_midi_messages(messages);
+
+    assert_eq!(chunks, vec![vec![0x90, 60, 100, 64, 100, 67, 100]]);
+}
@@ -0,0 +1,114 @@
+mod common;
+
+use std::borrow::Cow;
+use std::sync::Arc;
+
+use midi_piano_rs::midi::validate::{Malformed, RunningStatus, ValidatingSink, normalize};
+use midi_piano_rs::midi::{MidiSequence, NullSink, SharedMidiSink};
+use midly::num::{u4, u7, u15, u28};
+use midly::{Format, Header, MidiMessage, Smf, Timing, TrackEvent, TrackEventKind};
+
+#[test]
+fn well_formed_messages_pass_untouched() {
+    for message in [
+        &[0x90, 60, 100][..],
+        &[0xC3, 5],
+        &[0xF0, 0x7E, 0x7F, 0x09, 0x01, 0xF7],
+        &[0xF8],
+        &[0xF7, 0x90, 60],
+    ] {
+        assert!(
+            matches!(normalize(message), Ok(Cow::Borrowed(_))),
+            "{message:02X?}"
+        );
+    }
+}
+
+#[test]
+fn malformed_messages_are_rejected_or_fixed() {
+    assert_eq!(normalize(&[]), Err(Malformed::NoStatus));
+    assert_eq!(normalize(&[60, 100]), Err(Malformed::NoStatus));
+    assert_eq!(normalize(&[0x90, 60]), Err(Malformed::Truncated));
+    assert_eq!(normalize(&[0x90, 60, 0x80]), Err(Malformed::BadDataByte));
+    assert_eq!(normalize(&[0xF4]), Err(Malformed::UndefinedStatus));
+    assert_eq!(
+        normalize(&[0xF0, 0x43, 0x90, 0xF7]),
+        Err(Malformed::BadDataByte)
+    );
+
+    assert_eq!(normalize(&[0xC0, 5, 9]).unwrap().as_ref(), &[0xC0, 5][..]);
+    assert_eq!(
+        normalize(&[0xF0, 0x43, 0x10]).unwrap().as_ref(),
+        &[0xF0, 0x43, 0x10, 0xF7][..]
+    );
+}
+
+#[test]
+fn running_status_drops_repeated_channel_statuses() {
+    let mut running = RunningStatus::default();
+    assert_eq!(running.compress(&[0x90, 60, 100]), &[0x90, 60, 100]);
+    assert_eq!(running.compress(&[0x90, 64, 100]), &[64, 100]);
+    // Real-time messages leave the run alone; system messages end it.
+    assert_eq!(running.compress(&[0xF8]), &[0xF8]);
+    assert_eq!(running.compress(&[0x90, 67, 100]), &[67, 100]);
+    assert_eq!(running.compress(&[0xB0, 64, 127]), &[0xB0, 64, 127]);
+    assert_eq!(running.compress(&[0xF0, 0x7E, 0xF7]), &[0xF0, 0x7E, 0xF7]);
+    assert_eq!(running.compress(&[0xB0, 64, 0]), &[0xB0, 64, 0]);
+    running.reset();
+    assert_eq!(running.compress(&[0xB0, 64, 0]), &[0xB0, 64, 0]);
+}
+
+#[tokio::test]
+async fn validating_sinks_hold_back_malformed_messages() {
+    let target = Arc::new(NullSink::new());
+    let sink: SharedMidiSink = Arc::new(ValidatingSink::new(target.clone()));
+
+    sink.send_batch(&[vec![0x90, 60, 100], vec![0x90, 0xFF, 1], vec![0x80, 60]])
+        .await
+        .unwrap();
+    sink.send(&[0xC0, 5, 5]).await.unwrap();
+
+    let sent: Vec<Vec<u8>> = target
+        .sent()
+        .into_iter()
+        .map(|message| message.data)
+        .collect();
+    assert_eq!(sent, vec![vec![0x90, 60, 100], vec![0xC0, 5]]);
+}
+
+#[test]
+fn loading_reports_bad_messages_per_track() {
+    let note = |key: u8| TrackEventKind::Midi {
+        channel: u4::new(0),
+        message: MidiMessage::NoteOn {
+            key: u7::new(key),
+            vel: u7::new(100),
+        },
+    };
+    let event = |kind| TrackEvent {
+        delta: u28::new(0),
+        kind,
+    };
+    let smf = Smf {
+        header: Header::new(Format::Parallel, Timing::Metrical(u15::new(common::PPQ))),
+        tracks: vec![
+            vec![event(note(60))],
+            vec![
+                event(TrackEventKind::SysEx(&[0x43, 0x90, 0xF7])),
+                event(note(64)),
+            ],
+        ],
+    };
+    let mut bytes = Vec::new();
+    smf.write_std(&mut bytes).unwrap();
+
+    let sequence = MidiSequence::from_bytes(&bytes).unwrap();
+    assert_eq!(sequence.events.len(), 2);
+    let tracks: Vec<(u16, usize)> = sequence
+        .validation
+        .tracks()
+        .map(|(track, counts)| (track, counts.dropped))
+        .collect();
+    assert_eq!(tracks, vec![(1, 1)]);
+    assert_eq!(sequence.validation.total().fixed, 0);
+}
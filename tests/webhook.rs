@@ -0,0 +1,68 @@
+use std::io::{BufRead, BufReader, Read, Write};
+use std::net::TcpListener;
+use std::thread;
+
+use chrono::Utc;
+use midi_piano_rs::webhook::{self, NowPlayingEvent, NowPlayingKind};
+use uuid::Uuid;
+
+/// Answers one request with `status` and hands back its body.
+fn one_shot_server(status: &'static str) -> (String, thread::JoinHandle<String>) {
+    let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+    let url = format!("http://{}/hook", listener.local_addr().unwrap());
+    let handle = thread::spawn(move || {
+        let (stream, _) = listener.accept().unwrap();
+        let mut reader = BufReader::new(stream);
+        let mut length = 0;
+        loop {
+            let mut line = String::new();
+            reader.read_line(&mut line).unwrap();
+            if line == "\r\n" {
+                break;
+            }
+            if let Some(value) = line.to_ascii_lowercase().strip_prefix("content-length:") {
+                length = value.trim().parse().unwrap();
+            }
+        }
+        let mut body = vec![0; length];
+        reader.read_exact(&mut body).unwrap();
+        let response = format!("HTTP/1.1 {status}\r\nContent-Length: 0\r\n\r\n");
+        reader.get_mut().write_all(response.as_bytes()).unwrap();
+        String::from_utf8(body).unwrap()
+    });
+    (url, handle)
+}
+
+fn finished_event() -> NowPlayingEvent {
+    NowPlayingEvent {
+        event: NowPlayingKind::Finished,
+        track_id: Uuid::new_v4(),
+        track_name: "Für Elise".into(),
+        duration_secs: 180.0,
+        played_secs: 180.0,
+        device: Some("Stage Piano".into()),
+        started_at: Utc::now(),
+        sent_at: Utc::now(),
+    }
+}
+
+#[test]
+fn posts_the_event_as_json() {
+    let (url, server) = one_shot_server("200 OK");
+    let event = finished_event();
+
+    webhook::post(&url, &event).unwrap();
+
+    let body: serde_json::Value = serde_json::from_str(&server.join().unwrap()).unwrap();
+    assert_eq!(body["event"], "finished");
+    assert_eq!(body["track_name"], "Für Elise");
+    assert_eq!(body["device"], "Stage Piano");
+}
+
+#[test]
+fn reports_rejected_requests() {
+    let (url, server) = one_shot_server("500 Internal Server Error");
+
+    assert!(webhook::post(&url, &finished_event()).is_err());
+    server.join().unwrap();
+}
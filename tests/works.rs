@@ -0,0 +1,91 @@
+use std::collections::HashSet;
+use std::time::Duration;
+
+use midi_piano_rs::works::{self, Work, WorkIndex};
+use uuid::Uuid;
+
+fn songs(count: usize) -> Vec<Uuid> {
+    (0..count).map(|_| Uuid::new_v4()).collect()
+}
+
+#[test]
+fn works_play_whole_where_first_mentioned() {
+    let songs = songs(5);
+    let sonata = Work::new("Sonata".into(), vec![songs[1], songs[2], songs[3]]);
+    let works = [sonata];
+    let index = WorkIndex::new(&works);
+
+    let order = index.keep_together(&[songs[0], songs[3], songs[4], songs[1], songs[0]]);
+
+    assert_eq!(
+        order,
+        vec![songs[0], songs[1], songs[2], songs[3], songs[4], songs[0]]
+    );
+}
+
+#[test]
+fn movements_follow_each_other_in_order() {
+    let songs = songs(3);
+    let work = Work::new("Suite".into(), songs.clone());
+
+    assert_eq!(work.movement_after(songs[0]), Some(songs[1]));
+    assert_eq!(work.movement_after(songs[2]), None);
+    assert_eq!(work.movement_after(Uuid::new_v4()), None);
+    assert_eq!(
+        work.pause(),
+        Duration::from_secs(Work::DEFAULT_PAUSE_SECS.into())
+    );
+}
+
+#[test]
+fn a_song_is_a_movement_of_one_work_at_most() {
+    let songs = songs(4);
+    let mut all = vec![Work::new("First".into(), vec![songs[0], songs[1]])];
+    works::insert(
+        &mut all,
+        Work::new("Second".into(), vec![songs[1], songs[2], songs[3]]),
+    );
+
+    assert_eq!(all.len(), 1);
+    assert_eq!(all[0].name, "Second");
+    assert_eq!(
+        WorkIndex::new(&all)
+            .work_of(songs[1])
+            .map(|work| work.name.as_str()),
+        Some("Second")
+    );
+}
+
+#[test]
+fn forgotten_songs_leave_their_works() {
+    let songs = songs(5);
+    let mut all = vec![
+        Work::new("Pair".into(), vec![songs[0], songs[1]]),
+        Work::new("Trio".into(), vec![songs[2], songs[3], songs[4]]),
+    ];
+
+    works::forget(&mut all, &HashSet::from([songs[0], songs[3]]));
+
+    assert_eq!(all.len(), 1);
+    assert_eq!(all[0].movements, vec![songs[2], songs[4]]);
+}
+
+#[test]
+fn merging_keeps_existing_works_whole() {
+    let songs = songs(6);
+    let sonata = Work::new("Sonata".into(), vec![songs[0], songs[1], songs[2]]);
+    let mut all = vec![sonata.clone()];
+
+    works::merge(
+        &mut all,
+        vec![
+            Work::new("Regrouped".into(), vec![songs[2], songs[3]]),
+            Work::new("Suite".into(), vec![songs[4], songs[5]]),
+            sonata.clone(),
+        ],
+    );
+
+    let names: Vec<_> = all.iter().map(|work| work.name.as_str()).collect();
+    assert_eq!(names, ["Sonata", "Suite"]);
+    assert_eq!(all[0], sonata);
+}